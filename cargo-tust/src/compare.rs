@@ -0,0 +1,560 @@
+//! `cargo tust compare`: the differences between two runs.
+//!
+//! Both runs are read from the JSON events tust prints with `--format json`,
+//! such as a run of the baseline branch kept by CI and one of a pull
+//! request; see `tust_runtime::report::json`. Tests are matched by name, the
+//! last event of a name counting if several binaries' events were written to
+//! one file. The command lists the tests that started failing, that started
+//! passing, that were added, and that were removed, along with the passed
+//! tests whose duration changed by at least the threshold, and at least
+//! [`MIN_CHANGE`].
+//!
+//! It exits with 1 if a test started failing, counting added tests that
+//! failed, so that CI can gate a change on it; with `--fail-on-slower`, a
+//! test becoming slower does too.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+const USAGE: &str = "\
+Usage: cargo tust compare [OPTIONS] BASELINE CURRENT
+
+Compares two runs recorded with `--format json`, listing the tests that
+started failing, started passing, were added, or were removed, and those
+whose duration changed. A path of `-` reads standard input:
+
+    cargo test -- --format json | cargo tust compare baseline.json -
+
+Exits with 1 if a test started failing.
+
+Options:
+    --threshold PERCENT  List duration changes of at least PERCENT (default 20)
+    --fail-on-slower     Also exit with 1 if a test became slower
+    --format FORMAT      Output format: text or json (default text)
+    -h, --help           Print this help and exit";
+
+/// The smallest change in duration listed, whatever the threshold, so that
+/// the noise in the durations of quick tests is not.
+const MIN_CHANGE: Duration = Duration::from_millis(50);
+
+struct Options {
+    baseline: String,
+    current: String,
+    /// The smallest change in duration listed, relative to the baseline.
+    threshold: f64,
+    fail_on_slower: bool,
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Passed => "passed",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+/// The tests of a run, by name.
+type Run = BTreeMap<String, (Status, Duration)>;
+
+pub(crate) fn main(args: Vec<String>) -> ExitCode {
+    let options = match parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let runs = read(&options.baseline).and_then(|baseline| {
+        let current = read(&options.current)?;
+        Ok((baseline, current))
+    });
+    let (baseline, current) = match runs {
+        Ok(runs) => runs,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let comparison = Comparison::new(&baseline, &current, options.threshold);
+    if options.json {
+        println!("{}", comparison.to_json());
+    } else {
+        comparison.print();
+    }
+    let failing = !comparison.newly_failing.is_empty()
+        || comparison
+            .added
+            .iter()
+            .any(|(_, status)| *status == Status::Failed);
+    if failing || (options.fail_on_slower && !comparison.slower.is_empty()) {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse(args: Vec<String>) -> Result<Option<Options>, String> {
+    let mut paths = Vec::new();
+    let mut threshold = 0.2;
+    let mut fail_on_slower = false;
+    let mut json = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("`{}` requires a value", name))
+        };
+        match arg.as_str() {
+            "--threshold" => {
+                let percent = value("--threshold")?;
+                threshold = percent
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|percent| percent.is_finite() && *percent >= 0.0)
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid `--threshold` value `{}`; expected a percentage",
+                            percent
+                        )
+                    })?
+                    / 100.0;
+            }
+            "--fail-on-slower" => fail_on_slower = true,
+            "--format" => match value("--format")?.as_str() {
+                "text" => json = false,
+                "json" => json = true,
+                other => {
+                    return Err(format!(
+                        "invalid `--format` value `{}`; expected text or json",
+                        other
+                    ))
+                }
+            },
+            "-h" | "--help" => return Ok(None),
+            "-" => paths.push(arg),
+            other if other.starts_with('-') => {
+                return Err(format!("unrecognized option `{}`", other))
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [baseline, current]: [String; 2] = paths
+        .try_into()
+        .map_err(|_| "expected the two runs to compare".to_string())?;
+    if baseline == "-" && current == "-" {
+        return Err("only one of the runs can be read from standard input".to_string());
+    }
+    Ok(Some(Options {
+        baseline,
+        current,
+        threshold,
+        fail_on_slower,
+        json,
+    }))
+}
+
+/// Reads the tests of the run recorded in the file at `path`, or on
+/// standard input for `-`.
+fn read(path: &str) -> Result<Run, String> {
+    let mut events = String::new();
+    let read = if path == "-" {
+        io::stdin().read_to_string(&mut events).map(drop)
+    } else {
+        fs::read_to_string(path).map(|contents| events = contents)
+    };
+    read.map_err(|err| format!("cannot read {}: {}", path, err))?;
+
+    let mut run = Run::new();
+    let mut suites = 0;
+    for (number, line) in events.lines().enumerate() {
+        let line = line.trim();
+        // Tolerate what else was printed to the same stream, such as a
+        // libtest binary's plain output.
+        if !line.starts_with('{') {
+            continue;
+        }
+        let event: Value = serde_json::from_str(line)
+            .map_err(|err| format!("{}:{}: invalid JSON event: {}", path, number + 1, err))?;
+        match event["type"].as_str() {
+            Some("suite") => suites += 1,
+            Some("test") => {
+                let status = match event["event"].as_str() {
+                    Some("ok") => Status::Passed,
+                    Some("failed") => Status::Failed,
+                    Some("ignored") => Status::Skipped,
+                    _ => continue,
+                };
+                let Some(name) = event["name"].as_str() else {
+                    continue;
+                };
+                let duration = event["exec_time"]
+                    .as_f64()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .map_or(Duration::ZERO, Duration::from_secs_f64);
+                run.insert(name.to_string(), (status, duration));
+            }
+            _ => {}
+        }
+    }
+    if suites == 0 {
+        return Err(format!(
+            "{} holds no run; record one with `--format json`",
+            path
+        ));
+    }
+    Ok(run)
+}
+
+/// A change in a test's duration: its duration in the baseline run and in
+/// the current one.
+type Change = (String, Duration, Duration);
+
+/// The differences between a baseline run and the current one.
+#[derive(Default)]
+struct Comparison {
+    newly_failing: Vec<String>,
+    newly_passing: Vec<String>,
+    /// Tests that failed in both runs.
+    still_failing: Vec<String>,
+    added: Vec<(String, Status)>,
+    removed: Vec<String>,
+    /// Passed tests that became slower, the largest change first.
+    slower: Vec<Change>,
+    /// Passed tests that became faster, the largest change first.
+    faster: Vec<Change>,
+    /// Tests in both runs.
+    compared: usize,
+}
+
+impl Comparison {
+    fn new(baseline: &Run, current: &Run, threshold: f64) -> Self {
+        let mut comparison = Comparison::default();
+        for (name, &(status, duration)) in current {
+            let Some(&(before, baseline_duration)) = baseline.get(name) else {
+                comparison.added.push((name.clone(), status));
+                continue;
+            };
+            comparison.compared += 1;
+            match (before, status) {
+                (Status::Failed, Status::Failed) => comparison.still_failing.push(name.clone()),
+                (_, Status::Failed) => comparison.newly_failing.push(name.clone()),
+                (Status::Failed, Status::Passed) => comparison.newly_passing.push(name.clone()),
+                (Status::Passed, Status::Passed) => {
+                    let change = baseline_duration.as_secs_f64() * threshold;
+                    let change = MIN_CHANGE.max(Duration::from_secs_f64(change));
+                    let entry = (name.clone(), baseline_duration, duration);
+                    if duration >= baseline_duration + change {
+                        comparison.slower.push(entry);
+                    } else if duration + change <= baseline_duration {
+                        comparison.faster.push(entry);
+                    }
+                }
+                _ => {}
+            }
+        }
+        comparison.removed = baseline
+            .keys()
+            .filter(|name| !current.contains_key(*name))
+            .cloned()
+            .collect();
+        comparison
+            .slower
+            .sort_by_key(|(_, before, after)| std::cmp::Reverse(*after - *before));
+        comparison
+            .faster
+            .sort_by_key(|(_, before, after)| std::cmp::Reverse(*before - *after));
+        comparison
+    }
+
+    fn print(&self) {
+        let section = |title: &str, names: &[String]| {
+            if !names.is_empty() {
+                println!("{} ({}):", title, names.len());
+                for name in names {
+                    println!("    {}", name);
+                }
+            }
+        };
+        section("newly failing", &self.newly_failing);
+        section("newly passing", &self.newly_passing);
+        if !self.added.is_empty() {
+            println!("added ({}):", self.added.len());
+            for (name, status) in &self.added {
+                println!("    {} ({})", name, status.name());
+            }
+        }
+        section("removed", &self.removed);
+        let changes = |title: &str, changes: &[Change]| {
+            if !changes.is_empty() {
+                println!("{} ({}):", title, changes.len());
+                for (name, before, after) in changes {
+                    println!(
+                        "    {:>8.3}s -> {:>8.3}s  {:>+5.0}%  {}",
+                        before.as_secs_f64(),
+                        after.as_secs_f64(),
+                        percent(*before, *after),
+                        name
+                    );
+                }
+            }
+        };
+        changes("slower", &self.slower);
+        changes("faster", &self.faster);
+
+        println!(
+            "compared {} {}: {} newly failing, {} newly passing, {} still failing, \
+             {} added, {} removed, {} slower, {} faster",
+            self.compared,
+            if self.compared == 1 { "test" } else { "tests" },
+            self.newly_failing.len(),
+            self.newly_passing.len(),
+            self.still_failing.len(),
+            self.added.len(),
+            self.removed.len(),
+            self.slower.len(),
+            self.faster.len()
+        );
+    }
+
+    fn to_json(&self) -> Value {
+        let changes = |changes: &[Change]| -> Vec<Value> {
+            changes
+                .iter()
+                .map(|(name, before, after)| {
+                    json!({
+                        "name": name,
+                        "baseline": before.as_secs_f64(),
+                        "current": after.as_secs_f64(),
+                        "change": percent(*before, *after) / 100.0,
+                    })
+                })
+                .collect()
+        };
+        let added: Vec<Value> = self
+            .added
+            .iter()
+            .map(|(name, status)| json!({ "name": name, "status": status.name() }))
+            .collect();
+        json!({
+            "compared": self.compared,
+            "newly_failing": self.newly_failing,
+            "newly_passing": self.newly_passing,
+            "still_failing": self.still_failing,
+            "added": added,
+            "removed": self.removed,
+            "slower": changes(&self.slower),
+            "faster": changes(&self.faster),
+        })
+    }
+}
+
+/// The change from `before` to `after`, in percent of `before`.
+fn percent(before: Duration, after: Duration) -> f64 {
+    let before = before.as_secs_f64();
+    if before == 0.0 {
+        return 0.0;
+    }
+    (after.as_secs_f64() - before) / before * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(tests: &[(&str, Status, u64)]) -> Run {
+        tests
+            .iter()
+            .map(|&(name, status, ms)| (name.to_string(), (status, Duration::from_millis(ms))))
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn classifies_the_tests_of_both_runs() {
+        use Status::*;
+        let baseline = run(&[
+            ("breaks", Passed, 10),
+            ("fixed", Failed, 10),
+            ("broken", Failed, 10),
+            ("gone", Passed, 10),
+            ("unskipped", Skipped, 0),
+            ("skipped", Passed, 10),
+        ]);
+        let current = run(&[
+            ("breaks", Failed, 10),
+            ("fixed", Passed, 10),
+            ("broken", Failed, 10),
+            ("new_pass", Passed, 10),
+            ("new_fail", Failed, 10),
+            ("unskipped", Failed, 10),
+            ("skipped", Skipped, 0),
+        ]);
+        let comparison = Comparison::new(&baseline, &current, 0.2);
+        assert_eq!(comparison.newly_failing, ["breaks", "unskipped"]);
+        assert_eq!(comparison.newly_passing, ["fixed"]);
+        assert_eq!(comparison.still_failing, ["broken"]);
+        assert_eq!(
+            comparison.added,
+            [
+                ("new_fail".to_string(), Failed),
+                ("new_pass".to_string(), Passed)
+            ]
+        );
+        assert_eq!(comparison.removed, ["gone"]);
+        assert_eq!(comparison.compared, 5);
+        assert!(comparison.slower.is_empty() && comparison.faster.is_empty());
+    }
+
+    #[test]
+    fn lists_duration_changes_beyond_the_threshold() {
+        use Status::*;
+        let ms = Duration::from_millis;
+        let baseline = run(&[
+            ("a", Passed, 1000),
+            ("b", Passed, 1000),
+            ("c", Passed, 1000),
+            ("d", Passed, 10),
+            ("e", Passed, 1000),
+            ("f", Failed, 1000),
+        ]);
+        let current = run(&[
+            ("a", Passed, 1300),
+            ("b", Passed, 1500),
+            ("c", Passed, 1100),
+            // Five times slower, but by less than `MIN_CHANGE`.
+            ("d", Passed, 50),
+            ("e", Passed, 700),
+            ("f", Failed, 5000),
+        ]);
+        let comparison = Comparison::new(&baseline, &current, 0.2);
+        assert_eq!(
+            comparison.slower,
+            [
+                ("b".to_string(), ms(1000), ms(1500)),
+                ("a".to_string(), ms(1000), ms(1300))
+            ]
+        );
+        assert_eq!(comparison.faster, [("e".to_string(), ms(1000), ms(700))]);
+
+        let json = Comparison::new(&baseline, &current, 0.5).to_json();
+        assert_eq!(
+            json["slower"],
+            json!([{ "name": "b", "baseline": 1.0, "current": 1.5, "change": 0.5 }])
+        );
+        assert_eq!(json["faster"], json!([]));
+        assert_eq!(json["still_failing"], json!(["f"]));
+        assert_eq!(json["compared"], 6);
+    }
+
+    #[test]
+    fn reads_the_last_event_of_each_test() {
+        let path = std::env::temp_dir().join(format!("tust-compare-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            concat!(
+                "running 3 tests\n",
+                "{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 3 }\n",
+                "{ \"type\": \"test\", \"event\": \"started\", \"name\": \"a\" }\n",
+                "{ \"type\": \"test\", \"event\": \"failed\", \"name\": \"a\", \"exec_time\": 0.5 }\n",
+                "{ \"type\": \"test\", \"event\": \"ok\", \"name\": \"b\", \"exec_time\": 0.25 }\n",
+                "{ \"type\": \"test\", \"event\": \"ignored\", \"name\": \"c\" }\n",
+                "{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 1 }\n",
+                "{ \"type\": \"test\", \"event\": \"ok\", \"name\": \"a\", \"exec_time\": -1 }\n",
+            ),
+        )
+        .unwrap();
+        let path_str = path.to_str().unwrap();
+        assert_eq!(
+            read(path_str).unwrap(),
+            run(&[
+                ("a", Status::Passed, 0),
+                ("b", Status::Passed, 250),
+                ("c", Status::Skipped, 0)
+            ])
+        );
+
+        fs::write(&path, "test result: ok\n").unwrap();
+        assert_eq!(
+            read(path_str).unwrap_err(),
+            format!("{} holds no run; record one with `--format json`", path_str)
+        );
+        fs::write(&path, "{ \"type\": \"suite\" }\n{ oops\n").unwrap();
+        assert!(read(path_str)
+            .unwrap_err()
+            .starts_with(&format!("{}:2: invalid JSON event: ", path_str)));
+        fs::remove_file(&path).unwrap();
+        assert!(read(path_str)
+            .unwrap_err()
+            .starts_with(&format!("cannot read {}: ", path_str)));
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options = parse(args(&[
+            "--threshold",
+            "35",
+            "--fail-on-slower",
+            "base.json",
+            "-",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (options.baseline.as_str(), options.current.as_str()),
+            ("base.json", "-")
+        );
+        assert_eq!(options.threshold, 0.35);
+        assert!(options.fail_on_slower && !options.json);
+        assert!(
+            parse(args(&["a", "b", "--format", "json"]))
+                .unwrap()
+                .unwrap()
+                .json
+        );
+        assert!(parse(args(&["--help"])).unwrap().is_none());
+
+        for (args_, err) in [
+            (&["a"][..], "expected the two runs to compare"),
+            (&["a", "b", "c"], "expected the two runs to compare"),
+            (
+                &["-", "-"],
+                "only one of the runs can be read from standard input",
+            ),
+            (&["a", "b", "--threshold"], "`--threshold` requires a value"),
+            (
+                &["a", "b", "--threshold", "-5"],
+                "invalid `--threshold` value `-5`; expected a percentage",
+            ),
+            (
+                &["a", "b", "--format", "xml"],
+                "invalid `--format` value `xml`; expected text or json",
+            ),
+            (&["a", "b", "--slower"], "unrecognized option `--slower`"),
+        ] {
+            assert_eq!(parse(args(args_)).err().as_deref(), Some(err));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! `cargo tust embedded`: reporting tests run on an embedded target.
+//!
+//! Test programs built with `tust-embedded` report their tests over defmt.
+//! This command reads the decoded messages, as printed by a tool such as
+//! `probe-rs run`, and reports the run like tust does on the host; see
+//! `tust_runtime::embedded` for the messages. Given a command after `--`,
+//! it runs the command and reads its output, which lets it stand in front
+//! of the runner in Cargo's configuration: Cargo appends the program to
+//! run, so `cargo run` flashes the target and reports its tests.
+
+use std::io::{self, BufReader};
+use std::process::{Command, ExitCode, Stdio};
+
+use tust_runtime::embedded;
+use tust_runtime::runner::EXIT_USAGE;
+use tust_runtime::Args;
+
+const USAGE: &str = "\
+Usage: cargo tust embedded [OPTIONS] [-- COMMAND [ARGS...]]
+
+Reports the tests run on an embedded target with tust-embedded, reading the
+messages the target logs with defmt from the output of COMMAND, or from
+standard input without one. As a Cargo runner:
+
+    runner = \"cargo tust embedded -- probe-rs run --chip nRF52840_xxAA\"
+
+Options:
+    --format FORMAT       Output format: tree, pretty, terse, json, tap, or plain
+    --report FORMAT=PATH  Also write a junit, html, or timings report to PATH
+    --color WHEN          Colorize output: auto, always, or never
+    -q, --quiet           Print one character per test instead of one line
+    --show-output         Also print the output of passed tests
+    -h, --help            Print this help and exit";
+
+pub fn main(args: Vec<String>) -> ExitCode {
+    let (options, command) = match args.iter().position(|arg| arg == "--") {
+        Some(at) => (&args[..at], &args[at + 1..]),
+        None => (&args[..], &[][..]),
+    };
+    let options = match Args::parse(options.iter().cloned()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let Some((program, program_args)) = command.split_first() else {
+        return embedded::decode(io::stdin().lock(), &options);
+    };
+    let mut child = match Command::new(program)
+        .args(program_args)
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("error: cannot run `{}`: {}", program, err);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let code = embedded::decode(BufReader::new(stdout), &options);
+    // Once the run has ended, nothing the tool prints matters, and it may
+    // keep the target running.
+    let _ = child.kill();
+    let _ = child.wait();
+    code
+}
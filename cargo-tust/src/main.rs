@@ -0,0 +1,57 @@
+//! `cargo tust`: Cargo subcommand for the tust test framework.
+//!
+//! Installed as `cargo-tust`, it is run by Cargo as `cargo tust <command>`:
+//!
+//! - `cargo tust watch` rebuilds and reruns tests whenever a source file
+//!   changes; see [`watch`].
+//! - `cargo tust embedded` reports tests run on an embedded target; see
+//!   [`embedded`].
+//! - `cargo tust compare` lists the differences between two runs; see
+//!   [`compare`].
+//! - `cargo tust workspace` runs the tests of every package of the
+//!   workspace as one run; see [`workspace`].
+
+mod compare;
+mod embedded;
+mod watch;
+mod workspace;
+
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: cargo tust <COMMAND> [OPTIONS]
+
+Commands:
+    watch       Rebuild and rerun tests whenever a source file changes
+    embedded    Report tests run on an embedded target
+    compare     List the differences between two runs
+    workspace   Run the tests of every package of the workspace as one run
+
+Run `cargo tust <COMMAND> --help` for the options of a command.";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // Cargo passes the subcommand's name before its arguments.
+    if args.first().map(String::as_str) == Some("tust") {
+        args.remove(0);
+    }
+    let Some(command) = args.first().cloned() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let rest = args.split_off(1);
+    match command.as_str() {
+        "watch" => watch::main(rest),
+        "embedded" => embedded::main(rest),
+        "compare" => compare::main(rest),
+        "workspace" => workspace::main(rest),
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        other => {
+            eprintln!("error: unknown command `{}`\n\n{}", other, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,471 @@
+//! `cargo tust watch`: rerunning tests as the code changes.
+//!
+//! The watcher polls the workspace for changes to Rust sources and Cargo
+//! manifests. After each change it rebuilds the test targets with `cargo
+//! test --no-run` and runs the resulting binaries. Test binaries using the
+//! tust runner run only the tests defined in the changed files (selected
+//! with `--file`), tests that failed in their last run first. A change to a
+//! file that defines no tests may affect any test, so it reruns them all, as
+//! does a change to a manifest or a removed file. Binaries using libtest
+//! always run in full.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+
+const USAGE: &str = "\
+Usage: cargo tust watch [OPTIONS] [-- TEST-ARGS...]
+
+Rebuilds and reruns tests whenever a Rust source file or Cargo manifest in
+the workspace changes. Only tests defined in the changed files rerun, tests
+that failed in their last run first, unless a changed file defines no tests.
+
+Options:
+    -p, --package SPEC   Build and run the tests of package SPEC (repeatable)
+    --test NAME          Build and run only integration test NAME (repeatable)
+    --lib                Build and run only the library's unit tests
+    -F, --features LIST  Enable the features in LIST
+    --all                Rerun every test after each change
+    --interval MS        Check for changes every MS milliseconds (default 500)
+    --clear              Clear the screen before each run
+    -h, --help           Print this help and exit
+
+TEST-ARGS are passed to every test binary.";
+
+/// Attributes that define tests, such as `#[test]`, `#[tust::bench]`, or
+/// `#[case::empty(...)]`, by a segment of their path.
+const TEST_ATTRIBUTES: &[&str] = &["test", "case", "property", "fuzz", "bench", "embedded_test"];
+
+/// Macros that define tests, such as `suite!` or `tust::doctests!`, by the
+/// last segment of their path. A file using none of these and none of
+/// [`TEST_ATTRIBUTES`] defines no tests.
+const TEST_MACROS: &[&str] = &["suite", "test_suite", "doctests"];
+
+/// Whether `line` starts with one of [`TEST_ATTRIBUTES`] or
+/// [`TEST_MACROS`].
+fn defines_tests(line: &str) -> bool {
+    // The path at the start of `text`, and what follows it.
+    fn path_of(text: &str) -> (&str, &str) {
+        let end = text
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(text.len());
+        text.split_at(end)
+    }
+
+    let line = line.trim_start();
+    if let Some(attribute) = line.strip_prefix("#[") {
+        let (path, _) = path_of(attribute.trim_start());
+        return path
+            .split("::")
+            .any(|segment| TEST_ATTRIBUTES.contains(&segment));
+    }
+    let (path, rest) = path_of(line);
+    rest.trim_start().starts_with('!')
+        && path
+            .rsplit("::")
+            .next()
+            .is_some_and(|name| TEST_MACROS.contains(&name))
+}
+
+struct Options {
+    /// Arguments passed to `cargo test --no-run`.
+    cargo_args: Vec<String>,
+    /// Arguments passed to every test binary.
+    test_args: Vec<String>,
+    all: bool,
+    interval: Duration,
+    clear: bool,
+}
+
+pub(crate) fn main(args: Vec<String>) -> ExitCode {
+    let options = match parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let root = workspace_root();
+    let mut watcher = Watcher {
+        options,
+        runners: HashMap::new(),
+    };
+
+    let mut sources = scan(&root);
+    watcher.run(&root, None);
+    loop {
+        thread::sleep(watcher.options.interval);
+        let mut current = scan(&root);
+        if current == sources {
+            continue;
+        }
+        // Wait for editors and formatters to finish writing.
+        loop {
+            thread::sleep(watcher.options.interval);
+            let settled = scan(&root);
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        let changes = changes(&sources, &current);
+        sources = current;
+        watcher.run(&root, Some(&changes));
+    }
+}
+
+fn parse(args: Vec<String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        cargo_args: Vec::new(),
+        test_args: Vec::new(),
+        all: false,
+        interval: Duration::from_millis(500),
+        clear: false,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("`{}` requires a value", name))
+        };
+        match arg.as_str() {
+            "-p" | "--package" | "--test" | "-F" | "--features" => {
+                let flag = if arg == "-F" { "--features" } else { &arg };
+                let value = value(&arg)?;
+                options.cargo_args.extend([flag.to_string(), value]);
+            }
+            "--lib" => options.cargo_args.push(arg),
+            "--all" => options.all = true,
+            "--interval" => {
+                let interval = value("--interval")?;
+                let ms: u64 = interval.parse().ok().filter(|&ms| ms > 0).ok_or_else(|| {
+                    format!(
+                        "invalid `--interval` value `{}`; expected a positive number",
+                        interval
+                    )
+                })?;
+                options.interval = Duration::from_millis(ms);
+            }
+            "--clear" => options.clear = true,
+            "-h" | "--help" => return Ok(None),
+            "--" => {
+                options.test_args.extend(args);
+                break;
+            }
+            other => return Err(format!("unrecognized option `{}`", other)),
+        }
+    }
+    Ok(Some(options))
+}
+
+/// The files that changed between two scans, relative to the workspace root.
+struct Changes {
+    /// Files added or modified.
+    modified: Vec<PathBuf>,
+    removed: Vec<PathBuf>,
+}
+
+fn changes(before: &HashMap<PathBuf, SystemTime>, after: &HashMap<PathBuf, SystemTime>) -> Changes {
+    let mut modified: Vec<_> = after
+        .iter()
+        .filter(|&(path, time)| before.get(path) != Some(time))
+        .map(|(path, _)| path.clone())
+        .collect();
+    let mut removed: Vec<_> = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned()
+        .collect();
+    modified.sort();
+    removed.sort();
+    Changes { modified, removed }
+}
+
+/// Which test binaries use the tust runner, by path and modification time.
+type Runners = HashMap<PathBuf, (SystemTime, bool)>;
+
+struct Watcher {
+    options: Options,
+    runners: Runners,
+}
+
+impl Watcher {
+    /// Rebuilds the tests and runs those affected by `changes`, or every
+    /// test on the first run.
+    fn run(&mut self, root: &Path, changes: Option<&Changes>) {
+        if self.options.clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        let files = match changes {
+            Some(changes) => {
+                for path in changes.modified.iter().chain(&changes.removed) {
+                    println!("[watch] changed: {}", path.display());
+                }
+                self.affected_files(root, changes)
+            }
+            None => None,
+        };
+
+        let Some(binaries) = build(&self.options.cargo_args) else {
+            println!("[watch] build failed; waiting for changes");
+            return;
+        };
+        let mut failed = 0;
+        for binary in &binaries {
+            let mut command = Command::new(&binary.executable);
+            command
+                .current_dir(&binary.package_dir)
+                .env("CARGO_MANIFEST_DIR", &binary.package_dir);
+            if self.is_tust(&binary.executable) {
+                command.arg("--failed-first");
+                for file in files.iter().flatten() {
+                    command.arg("--file").arg(file);
+                }
+            }
+            command.args(&self.options.test_args);
+            let _ = io::stdout().flush();
+            match command.status() {
+                Ok(status) if status.success() => {}
+                Ok(_) => failed += 1,
+                Err(err) => {
+                    eprintln!(
+                        "[watch] cannot run {}: {}",
+                        binary.executable.display(),
+                        err
+                    );
+                    failed += 1;
+                }
+            }
+        }
+        if failed == 0 {
+            println!("[watch] tests passed; waiting for changes");
+        } else {
+            println!(
+                "[watch] {} of {} test binaries failed; waiting for changes",
+                failed,
+                binaries.len()
+            );
+        }
+    }
+
+    /// The files whose tests to rerun after `changes`, or `None` to rerun
+    /// every test.
+    fn affected_files(&self, root: &Path, changes: &Changes) -> Option<Vec<PathBuf>> {
+        if self.options.all || !changes.removed.is_empty() {
+            return None;
+        }
+        let mut files = Vec::new();
+        for path in &changes.modified {
+            if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+                return None;
+            }
+            let source = fs::read_to_string(root.join(path)).unwrap_or_default();
+            if !source.lines().any(defines_tests) {
+                return None;
+            }
+            files.push(path.clone());
+        }
+        Some(files)
+    }
+
+    /// Whether `executable` uses the tust runner, which names itself in its
+    /// `--help`.
+    fn is_tust(&mut self, executable: &Path) -> bool {
+        let modified = fs::metadata(executable)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if let Some(&(seen, is_tust)) = self.runners.get(executable) {
+            if seen == modified {
+                return is_tust;
+            }
+        }
+        let is_tust = Command::new(executable)
+            .arg("--help")
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| {
+                String::from_utf8_lossy(&output.stdout).contains("registered tust test")
+            });
+        self.runners
+            .insert(executable.to_path_buf(), (modified, is_tust));
+        is_tust
+    }
+}
+
+/// A test binary built by Cargo.
+struct Binary {
+    executable: PathBuf,
+    /// Directory of the package's manifest, which Cargo runs tests in.
+    package_dir: PathBuf,
+}
+
+/// Builds the test binaries, returning them, or `None` if the build failed.
+fn build(cargo_args: &[String]) -> Option<Vec<Binary>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut child = Command::new(cargo)
+        .args([
+            "test",
+            "--no-run",
+            "--message-format=json-render-diagnostics",
+        ])
+        .args(cargo_args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| eprintln!("[watch] cannot run cargo: {}", err))
+        .ok()?;
+
+    let mut binaries = Vec::new();
+    let mut seen = HashSet::new();
+    let stdout = child.stdout.take()?;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-artifact" || message["profile"]["test"] != true {
+            continue;
+        }
+        let (Some(executable), Some(manifest)) = (
+            message["executable"].as_str(),
+            message["manifest_path"].as_str(),
+        ) else {
+            continue;
+        };
+        if seen.insert(executable.to_string()) {
+            binaries.push(Binary {
+                executable: PathBuf::from(executable),
+                package_dir: Path::new(manifest)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    let status = child.wait().ok()?;
+    status.success().then_some(binaries)
+}
+
+/// The root of the workspace containing the current directory.
+fn workspace_root() -> PathBuf {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    Command::new(cargo)
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .stderr(Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+            manifest.parent().map(Path::to_path_buf)
+        })
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Modification times of the Rust sources and manifests under `root`, by
+/// path relative to it, skipping `target` and hidden directories.
+fn scan(root: &Path) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if name != "target" && !name.starts_with('.') {
+                    dirs.push(path);
+                }
+            } else if name.ends_with(".rs") || name == "Cargo.toml" {
+                let modified = entry
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                files.insert(relative, modified);
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_test_attributes_and_macros() {
+        for line in [
+            "#[test]",
+            "    #[tust::test(tags(\"db\"))]",
+            "#[tokio::test]",
+            "#[case::empty(\"\")]",
+            "#[tust::bench]",
+            "#[tust::fuzz(corpus = \"fuzz\")]",
+            "#[property]",
+            "suite! {",
+            "tust::test_suite!(Stack, {",
+            "tust::doctests!(\"README.md\");",
+        ] {
+            assert!(defines_tests(line), "{}", line);
+        }
+        for line in [
+            "#[cfg(test)]",
+            "#[testing]",
+            "#[fixture]",
+            "fn test() {}",
+            "// suite! is not used here",
+            "let suite = 1;",
+            "tust::main!();",
+        ] {
+            assert!(!defines_tests(line), "{}", line);
+        }
+    }
+
+    /// Every macro of `tust-macros` either defines tests, and is listed as
+    /// doing so, or is known not to.
+    #[test]
+    fn lists_every_macro_defining_tests() {
+        const NOT_TESTS: &[&str] = &[
+            "fixture",
+            "before_each",
+            "after_each",
+            "before_all",
+            "after_all",
+            "automock",
+            "test_builder",
+        ];
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tust-macros/src/lib.rs");
+        let source = fs::read_to_string(path).unwrap();
+        let names: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub fn "))
+            .map(|rest| rest.split('(').next().unwrap())
+            .collect();
+        assert!(names.contains(&"test"));
+        for name in names {
+            assert!(
+                TEST_ATTRIBUTES.contains(&name)
+                    || TEST_MACROS.contains(&name)
+                    || NOT_TESTS.contains(&name),
+                "`{}` is not known to define tests or not",
+                name
+            );
+        }
+    }
+}
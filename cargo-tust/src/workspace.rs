@@ -0,0 +1,153 @@
+//! `cargo tust workspace`: the tests of every package as one run.
+//!
+//! The command builds the test binaries of the workspace's packages, runs
+//! those using the tust runner, and reports their tests as one run, grouped
+//! by package; see `tust_runtime::workspace`. Options selecting packages and
+//! features go to Cargo, options choosing how the run is reported to the
+//! merged run, and the arguments after `--` to every test binary.
+
+use std::process::ExitCode;
+
+use tust_runtime::runner::EXIT_USAGE;
+use tust_runtime::workspace;
+use tust_runtime::Args;
+
+const USAGE: &str = "\
+Usage: cargo tust workspace [OPTIONS] [-- TEST-ARGS...]
+
+Builds the test binaries of every package of the workspace, runs those using
+the tust runner, and reports their tests as one run, grouped by package.
+
+Options:
+    -p, --package SPEC    Run only the tests of package SPEC (repeatable)
+    --exclude SPEC        Leave out the tests of package SPEC (repeatable)
+    -F, --features LIST   Enable the features in LIST
+    --all-features        Enable every feature of the selected packages
+    --format FORMAT       Output format: tree, pretty, terse, json, tap, or plain
+    --report FORMAT=PATH  Also write a junit, html, timings, or github report
+                          to PATH
+    --color WHEN          Colorize output: auto, always, or never
+    -q, --quiet           Print one character per test instead of one line
+    --show-output         Also print the output of passed tests
+    -h, --help            Print this help and exit
+
+TEST-ARGS are passed to every test binary, such as filters, --tag, or
+--timeout.";
+
+pub(crate) fn main(args: Vec<String>) -> ExitCode {
+    let (options, test_args) = match args.iter().position(|arg| arg == "--") {
+        Some(at) => (&args[..at], &args[at + 1..]),
+        None => (&args[..], &[][..]),
+    };
+    let (cargo_args, report_args) = match split(options) {
+        Ok(split) => split,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    let options = match Args::parse(report_args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match workspace::discover(&cargo_args) {
+        Ok(binaries) => workspace::run(&binaries, test_args, &options),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(EXIT_USAGE)
+        }
+    }
+}
+
+/// Splits `options` into the arguments selecting packages and features,
+/// for Cargo, and the options choosing how the run is reported. Without a
+/// package, Cargo builds the whole workspace.
+fn split(options: &[String]) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut cargo_args = Vec::new();
+    let mut report_args = Vec::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "-p" | "--package" | "--exclude" | "-F" | "--features" => {
+                let value = options
+                    .next()
+                    .ok_or_else(|| format!("`{}` requires a value", option))?;
+                cargo_args.extend([option.clone(), value.clone()]);
+            }
+            "--all-features" => cargo_args.push(option.clone()),
+            _ => report_args.push(option.clone()),
+        }
+    }
+    if !cargo_args
+        .iter()
+        .any(|arg| arg == "-p" || arg == "--package")
+    {
+        cargo_args.insert(0, "--workspace".to_string());
+    }
+    Ok((cargo_args, report_args))
+}
+
+#[cfg(test)]
+mod tests {
+    fn split(options: &[&str]) -> Result<(Vec<String>, Vec<String>), String> {
+        let options: Vec<String> = options.iter().map(|option| option.to_string()).collect();
+        super::split(&options)
+    }
+
+    #[test]
+    fn selects_the_whole_workspace_unless_given_packages() {
+        assert_eq!(
+            split(&["--exclude", "docs", "--format", "tree", "-F", "db"]).unwrap(),
+            (
+                vec![
+                    "--workspace".into(),
+                    "--exclude".into(),
+                    "docs".into(),
+                    "-F".into(),
+                    "db".into()
+                ],
+                vec!["--format".into(), "tree".into()]
+            )
+        );
+        assert_eq!(
+            split(&[
+                "-p",
+                "parser",
+                "--quiet",
+                "--package",
+                "storage",
+                "--all-features"
+            ])
+            .unwrap(),
+            (
+                vec![
+                    "-p".into(),
+                    "parser".into(),
+                    "--package".into(),
+                    "storage".into(),
+                    "--all-features".into()
+                ],
+                vec!["--quiet".into()]
+            )
+        );
+        assert_eq!(split(&[]).unwrap(), (vec!["--workspace".into()], vec![]));
+    }
+
+    #[test]
+    fn requires_the_values_of_cargo_options() {
+        for option in ["-p", "--package", "--exclude", "-F", "--features"] {
+            assert_eq!(
+                split(&["--quiet", option]).unwrap_err(),
+                format!("`{}` requires a value", option)
+            );
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Support for [`assert_no_alloc!`](crate::assert_no_alloc) and
+//! [`assert_allocates_at_most!`](crate::assert_allocates_at_most).
+
+use tust_runtime::alloc::track;
+use tust_runtime::TestFailure;
+
+/// Calls `f`, which must not allocate, and returns its result.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_no_alloc<R>(assertion: &str, f: impl FnOnce() -> R) -> R {
+    let (result, stats) = track(f);
+    if stats.allocated() {
+        TestFailure::new(format!(
+            "assertion `{}` failed: the closure allocated",
+            assertion
+        ))
+        .with_metadata("counted", stats)
+        .raise();
+    }
+    result
+}
+
+/// Calls `f`, which must allocate at most `max_bytes` bytes, and returns its
+/// result.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_allocates_at_most<R>(assertion: &str, max_bytes: u64, f: impl FnOnce() -> R) -> R {
+    let (result, stats) = track(f);
+    if stats.bytes_allocated > max_bytes {
+        TestFailure::new(format!(
+            "assertion `{}` failed: the closure allocated {} bytes, more than {}",
+            assertion, stats.bytes_allocated, max_bytes
+        ))
+        .with_metadata("counted", stats)
+        .raise();
+    }
+    result
+}
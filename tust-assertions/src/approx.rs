@@ -0,0 +1,391 @@
+//! Approximate equality of floating-point numbers and containers of them,
+//! for [`assert_approx_eq!`](crate::assert_approx_eq).
+//!
+//! Two numbers are approximately equal within a [`Tolerance`] if they differ
+//! by at most its absolute `epsilon`, or by at most its `relative` tolerance
+//! times the larger magnitude of the two, or by at most `ulps` units in the
+//! last place: steps from one representable number to the next. `NaN` is
+//! not equal to anything, and an infinity only to itself.
+//!
+//! Slices, arrays, `Vec`s (with the `alloc` feature), and `Option`s compare
+//! element by element. Other containers, such as matrices, join in by
+//! implementing [`ApproxEq`] in terms of their elements:
+//!
+//! ```
+//! use tust_assertions::approx::{ApproxEq, Tolerance};
+//! use tust_assertions::assert_approx_eq;
+//!
+//! #[derive(Debug)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! impl ApproxEq for Point {
+//!     fn approx_eq(&self, other: &Point, tolerance: &Tolerance) -> bool {
+//!         self.x.approx_eq(&other.x, tolerance) && self.y.approx_eq(&other.y, tolerance)
+//!     }
+//! }
+//!
+//! let point = Point { x: 0.1 + 0.2, y: 1.0 };
+//! assert_approx_eq!(point, Point { x: 0.3, y: 1.0 });
+//! ```
+
+use core::fmt;
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{format, string::String, string::ToString, vec::Vec};
+
+/// How far apart two numbers may be and still count as equal. Any one of
+/// the tolerances suffices; [`Tolerance::new`] sets none of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Largest absolute difference.
+    pub epsilon: f64,
+    /// Largest difference relative to the larger magnitude of the two.
+    pub relative: f64,
+    /// Largest distance in units in the last place, i.e. in steps from one
+    /// representable number to the next.
+    pub ulps: u64,
+}
+
+impl Tolerance {
+    /// A tolerance that only accepts numbers that are equal.
+    pub const fn new() -> Self {
+        Tolerance {
+            epsilon: 0.0,
+            relative: 0.0,
+            ulps: 0,
+        }
+    }
+
+    pub const fn epsilon(self, epsilon: f64) -> Self {
+        Tolerance { epsilon, ..self }
+    }
+
+    pub const fn relative(self, relative: f64) -> Self {
+        Tolerance { relative, ..self }
+    }
+
+    pub const fn ulps(self, ulps: u64) -> Self {
+        Tolerance { ulps, ..self }
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance::new()
+    }
+}
+
+impl fmt::Display for Tolerance {
+    /// Completes "equal ...", e.g. `within 1e-9 or 4 ULPs`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = usize::from(self.epsilon > 0.0)
+            + usize::from(self.relative > 0.0)
+            + usize::from(self.ulps > 0);
+        if count == 0 {
+            return f.write_str("exactly");
+        }
+        f.write_str("within ")?;
+        let mut written = 0;
+        let mut separate = |f: &mut fmt::Formatter<'_>| {
+            written += 1;
+            match written {
+                1 => Ok(()),
+                n if n == count => f.write_str(" or "),
+                _ => f.write_str(", "),
+            }
+        };
+        if self.epsilon > 0.0 {
+            separate(f)?;
+            write!(f, "{:?}", self.epsilon)?;
+        }
+        if self.relative > 0.0 {
+            separate(f)?;
+            write!(f, "{:?} relative", self.relative)?;
+        }
+        if self.ulps > 0 {
+            separate(f)?;
+            write!(f, "{} ULPs", self.ulps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Approximate equality within a [`Tolerance`].
+pub trait ApproxEq<Rhs: ?Sized = Self> {
+    /// Whether `self` and `other` are equal within `tolerance`.
+    fn approx_eq(&self, other: &Rhs, tolerance: &Tolerance) -> bool;
+
+    /// Describes how `self` and `other` differ, printed below the values
+    /// when an assertion fails, e.g. `at index 2: 1.0 and 1.5 differ by 0.5`.
+    /// Only called for values that are not equal, and only with the `alloc`
+    /// feature.
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &Rhs, tolerance: &Tolerance) -> Option<String> {
+        let _ = (other, tolerance);
+        None
+    }
+}
+
+macro_rules! float_approx_eq {
+    ($($float:ty => $ulps:ident),*) => {$(
+        impl ApproxEq for $float {
+            fn approx_eq(&self, other: &$float, tolerance: &Tolerance) -> bool {
+                let (a, b) = (*self, *other);
+                if a == b {
+                    return true;
+                }
+                if !a.is_finite() || !b.is_finite() {
+                    return false;
+                }
+                let difference = f64::from(abs(a - b));
+                let magnitude = f64::from(abs(a).max(abs(b)));
+                difference <= tolerance.epsilon
+                    || difference <= tolerance.relative * magnitude
+                    || $ulps(a, b) <= tolerance.ulps
+            }
+
+            #[cfg(feature = "alloc")]
+            fn explain_difference(&self, other: &$float, _: &Tolerance) -> Option<String> {
+                let (a, b) = (*self, *other);
+                Some(if a.is_nan() || b.is_nan() {
+                    "NaN is not equal to anything".to_string()
+                } else if !a.is_finite() || !b.is_finite() {
+                    format!("{:?} and {:?} differ", a, b)
+                } else {
+                    format!(
+                        "{:?} and {:?} differ by {:?}, {} ULPs apart",
+                        a,
+                        b,
+                        abs(a - b),
+                        $ulps(a, b)
+                    )
+                })
+            }
+        }
+    )*};
+}
+
+float_approx_eq!(f32 => ulps_f32, f64 => ulps_f64);
+
+/// The magnitude of `x`, since `f32::abs` and `f64::abs` need the standard
+/// library on the oldest Rust supported.
+fn abs<F: Copy + PartialOrd + Default + core::ops::Neg<Output = F>>(x: F) -> F {
+    if x < F::default() {
+        -x
+    } else {
+        x
+    }
+}
+
+/// How many representable `f32`s apart two finite numbers are, counting
+/// both zeros as one.
+fn ulps_f32(a: f32, b: f32) -> u64 {
+    // Maps floats to integers in the same order.
+    let key = |x: f32| {
+        let bits = x.to_bits() as i32;
+        if bits < 0 {
+            i32::MIN.wrapping_sub(bits)
+        } else {
+            bits
+        }
+    };
+    u64::from(key(a).abs_diff(key(b)))
+}
+
+/// Like [`ulps_f32`], for `f64`s.
+fn ulps_f64(a: f64, b: f64) -> u64 {
+    let key = |x: f64| {
+        let bits = x.to_bits() as i64;
+        if bits < 0 {
+            i64::MIN.wrapping_sub(bits)
+        } else {
+            bits
+        }
+    };
+    key(a).abs_diff(key(b))
+}
+
+impl<A, B> ApproxEq<[B]> for [A]
+where
+    A: ApproxEq<B>,
+{
+    fn approx_eq(&self, other: &[B], tolerance: &Tolerance) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other)
+                .all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &[B], tolerance: &Tolerance) -> Option<String> {
+        if self.len() != other.len() {
+            return Some(format!(
+                "the lengths differ: {} and {}",
+                self.len(),
+                other.len()
+            ));
+        }
+        let mut differing = self
+            .iter()
+            .zip(other)
+            .enumerate()
+            .filter(|(_, (a, b))| !a.approx_eq(b, tolerance));
+        let (index, (a, b)) = differing.next()?;
+        let mut explanation = match a.explain_difference(b, tolerance) {
+            Some(difference) => format!("at index {}: {}", index, difference),
+            None => format!("at index {}", index),
+        };
+        match differing.count() {
+            0 => {}
+            1 => explanation.push_str(", and 1 more element differs"),
+            more => explanation.push_str(&format!(", and {} more elements differ", more)),
+        }
+        Some(explanation)
+    }
+}
+
+impl<A, B, const N: usize> ApproxEq<[B; N]> for [A; N]
+where
+    A: ApproxEq<B>,
+{
+    fn approx_eq(&self, other: &[B; N], tolerance: &Tolerance) -> bool {
+        self[..].approx_eq(&other[..], tolerance)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &[B; N], tolerance: &Tolerance) -> Option<String> {
+        self[..].explain_difference(&other[..], tolerance)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A, B> ApproxEq<Vec<B>> for Vec<A>
+where
+    A: ApproxEq<B>,
+{
+    fn approx_eq(&self, other: &Vec<B>, tolerance: &Tolerance) -> bool {
+        self[..].approx_eq(&other[..], tolerance)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &Vec<B>, tolerance: &Tolerance) -> Option<String> {
+        self[..].explain_difference(&other[..], tolerance)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A, B, const N: usize> ApproxEq<[B; N]> for Vec<A>
+where
+    A: ApproxEq<B>,
+{
+    fn approx_eq(&self, other: &[B; N], tolerance: &Tolerance) -> bool {
+        self[..].approx_eq(&other[..], tolerance)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &[B; N], tolerance: &Tolerance) -> Option<String> {
+        self[..].explain_difference(&other[..], tolerance)
+    }
+}
+
+impl<A, B> ApproxEq<Option<B>> for Option<A>
+where
+    A: ApproxEq<B>,
+{
+    fn approx_eq(&self, other: &Option<B>, tolerance: &Tolerance) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.approx_eq(b, tolerance),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &Option<B>, tolerance: &Tolerance) -> Option<String> {
+        match (self, other) {
+            (Some(a), Some(b)) => a.explain_difference(b, tolerance),
+            _ => None,
+        }
+    }
+}
+
+impl<A, B> ApproxEq<&B> for &A
+where
+    A: ApproxEq<B> + ?Sized,
+    B: ?Sized,
+{
+    fn approx_eq(&self, other: &&B, tolerance: &Tolerance) -> bool {
+        (**self).approx_eq(*other, tolerance)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn explain_difference(&self, other: &&B, tolerance: &Tolerance) -> Option<String> {
+        (**self).explain_difference(*other, tolerance)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use crate::failure::tests::{failure_of, message_of};
+
+    #[test]
+    fn numbers_are_equal_within_any_tolerance() {
+        let sum = 0.1 + 0.2;
+        crate::assert_approx_eq!(sum, 0.3);
+        crate::assert_approx_eq!(1.0f32, 1.05, epsilon = 0.1);
+        crate::assert_approx_eq!(100.0, 101.0, relative = 0.01);
+        crate::assert_approx_eq!(-0.0, 0.0, ulps = 0);
+        crate::assert_approx_eq!(f64::INFINITY, f64::INFINITY);
+        assert!(!f64::NAN.approx_eq(&f64::NAN, &Tolerance::new().epsilon(1.0)));
+        assert!(!f64::INFINITY.approx_eq(&f64::MAX, &Tolerance::new().ulps(u64::MAX)));
+        assert_eq!(ulps_f64(-f64::MIN_POSITIVE, f64::MIN_POSITIVE), 2 << 52);
+    }
+
+    #[test]
+    fn tolerances_are_described() {
+        assert_eq!(Tolerance::new().to_string(), "exactly");
+        assert_eq!(Tolerance::new().ulps(4).to_string(), "within 4 ULPs");
+        let all = Tolerance::new().epsilon(1e-9).relative(0.5).ulps(4);
+        assert_eq!(all.to_string(), "within 1e-9, 0.5 relative or 4 ULPs");
+    }
+
+    #[test]
+    fn numbers_that_differ_report_by_how_much() {
+        let failure = failure_of(|| crate::assert_approx_eq!(1.0, 1.5, epsilon = 0.1));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_approx_eq!(1.0, 1.5, epsilon = 0.1)` failed: the values are not \
+             equal within 0.1\n  1.0 and 1.5 differ by 0.5, 2251799813685248 ULPs apart"
+        );
+        let diff = failure.diff.expect("the values are compared");
+        assert_eq!((diff.left.as_str(), diff.right.as_str()), ("1.0", "1.5"));
+        assert_eq!(
+            message_of(|| crate::assert_approx_eq!(f64::NAN, 0.0)),
+            "assertion `assert_approx_eq!(f64::NAN, 0.0)` failed: the values are not equal \
+             within 4 ULPs\n  NaN is not equal to anything"
+        );
+    }
+
+    #[test]
+    fn containers_report_the_first_difference() {
+        let actual = vec![1.0, 2.0, 3.0, 4.0];
+        assert_eq!(
+            message_of(|| crate::assert_approx_eq!(actual, [1.0, 2.5, 3.0, 5.0])),
+            "assertion `assert_approx_eq!(actual, [1.0, 2.5, 3.0, 5.0])` failed: the values are \
+             not equal within 4 ULPs\n  at index 1: 2.0 and 2.5 differ by 0.5, 1125899906842624 \
+             ULPs apart, and 1 more element differs"
+        );
+        assert_eq!(
+            message_of(|| crate::assert_approx_eq!(actual, [1.0])),
+            "assertion `assert_approx_eq!(actual, [1.0])` failed: the values are not equal \
+             within 4 ULPs\n  the lengths differ: 4 and 1"
+        );
+        crate::assert_approx_eq!(Some([0.1 + 0.2]), Some([0.3]));
+    }
+}
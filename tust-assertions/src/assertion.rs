@@ -0,0 +1,223 @@
+//! Building blocks for assertions defined outside this crate.
+//!
+//! A domain-specific assertion implements [`Assertion`] for the values it
+//! checks and describes a failure with a [`FailureBuilder`], which lays the
+//! message out like the built-in assertions: a header naming the assertion as
+//! written, labeled values, notes, and a colored diff for large values. The
+//! failure points at the line that ran the assertion.
+//!
+//! ```
+//! use tust_assertions::assert_that;
+//! use tust_assertions::assertion::{Assertion, FailureBuilder};
+//!
+//! struct IsValidPort;
+//!
+//! impl Assertion<i64> for IsValidPort {
+//!     fn check(&self, port: &i64, failure: FailureBuilder) -> Result<(), FailureBuilder> {
+//!         if (1..=65535).contains(port) {
+//!             Ok(())
+//!         } else {
+//!             Err(failure
+//!                 .reason("not a valid port")
+//!                 .value("port", port)
+//!                 .note("ports range from 1 to 65535"))
+//!         }
+//!     }
+//! }
+//!
+//! assert_that!(8080, IsValidPort);
+//! ```
+//!
+//! [`assert_that!`](crate::assert_that) ends the test when the assertion
+//! fails, and [`check_that!`](crate::check_that) records the failure and lets
+//! the test continue. Assertions with their own macros can skip the trait
+//! and build a failure directly:
+//!
+//! ```
+//! use tust_assertions::assertion::FailureBuilder;
+//!
+//! macro_rules! assert_even {
+//!     ($value:expr) => {
+//!         match &$value {
+//!             value if value % 2 != 0 => {
+//!                 FailureBuilder::new(concat!("assert_even!(", stringify!($value), ")"))
+//!                     .reason("the value is odd")
+//!                     .value("value", value)
+//!                     .fail()
+//!             }
+//!             _ => {}
+//!         }
+//!     };
+//! }
+//!
+//! assert_even!(2 + 2);
+//! ```
+
+use std::fmt::{self, Debug, Write};
+
+use tust_runtime::TestFailure;
+
+use crate::failure::{self, Mode};
+
+/// A check of values of type `T`, run by [`assert_that!`](crate::assert_that)
+/// and [`check_that!`](crate::check_that).
+pub trait Assertion<T: ?Sized> {
+    /// Checks `actual`. On failure, returns `failure`, which already names
+    /// the assertion as written, with the details added.
+    fn check(&self, actual: &T, failure: FailureBuilder) -> Result<(), FailureBuilder>;
+}
+
+impl<T: ?Sized, A: Assertion<T> + ?Sized> Assertion<T> for &A {
+    fn check(&self, actual: &T, failure: FailureBuilder) -> Result<(), FailureBuilder> {
+        (**self).check(actual, failure)
+    }
+}
+
+/// A failure message laid out like those of the built-in assertions:
+///
+/// ```text
+/// assertion `assert_that!(port, IsValidPort)` failed: not a valid port
+///   port: 0
+///   ports range from 1 to 65535
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "a failure is only reported by `fail` or `fail_soft`"]
+pub struct FailureBuilder {
+    assertion: String,
+    reason: Option<String>,
+    details: Vec<Detail>,
+}
+
+#[derive(Debug, Clone)]
+enum Detail {
+    Value(String, String),
+    Note(String),
+    Comparison(String, String),
+}
+
+impl FailureBuilder {
+    /// Starts the failure of `assertion`, the assertion as written, e.g.
+    /// `concat!("assert_even!(", stringify!($value), ")")`.
+    pub fn new(assertion: impl Into<String>) -> Self {
+        FailureBuilder {
+            assertion: assertion.into(),
+            reason: None,
+            details: Vec::new(),
+        }
+    }
+
+    /// Says why the assertion failed, after the header: "assertion `...`
+    /// failed: <reason>".
+    pub fn reason(self, reason: impl fmt::Display) -> Self {
+        FailureBuilder {
+            reason: Some(reason.to_string()),
+            ..self
+        }
+    }
+
+    /// Adds a value pretty-printed (`{:#?}`) after `label`. Labels are
+    /// aligned on their colons.
+    pub fn value<T: Debug + ?Sized>(mut self, label: impl Into<String>, value: &T) -> Self {
+        self.details
+            .push(Detail::Value(label.into(), format!("{:#?}", value)));
+        self
+    }
+
+    /// Adds a line of text.
+    pub fn note(mut self, note: impl fmt::Display) -> Self {
+        self.details.push(Detail::Note(note.to_string()));
+        self
+    }
+
+    /// Adds two values that should have been equal, as `left` and `right`,
+    /// or as a colored diff from `left` to `right` if they are large.
+    pub fn compare<L, R>(mut self, left: &L, right: &R) -> Self
+    where
+        L: Debug + ?Sized,
+        R: Debug + ?Sized,
+    {
+        self.details.push(Detail::Comparison(
+            format!("{:#?}", left),
+            format!("{:#?}", right),
+        ));
+        self
+    }
+
+    /// Ends the test with the failure.
+    #[track_caller]
+    pub fn fail(self) -> ! {
+        self.build().raise()
+    }
+
+    /// Records the failure and lets the test continue, like a failed
+    /// [`check!`](crate::check).
+    #[track_caller]
+    pub fn fail_soft(self) {
+        failure::fail(Mode::Soft, self.build());
+    }
+
+    /// The failure as reporters receive it: the first pair of compared
+    /// values becomes its diff, and the rest of the details its message.
+    pub fn build(self) -> TestFailure {
+        let mut comparisons = self.details.iter().filter_map(|detail| match detail {
+            Detail::Comparison(left, right) => Some((left.clone(), right.clone())),
+            _ => None,
+        });
+        let diff = comparisons.next();
+        let failure = TestFailure::new(self.message(diff.is_some()));
+        match diff {
+            Some((left, right)) => failure.with_diff(left, right),
+            None => failure,
+        }
+    }
+
+    /// The header and the details, leaving out the first pair of compared
+    /// values if `skip_diff` is set.
+    fn message(&self, skip_diff: bool) -> String {
+        let mut skip_diff = skip_diff;
+        let mut out = match &self.reason {
+            Some(reason) => format!("assertion `{}` failed: {}", self.assertion, reason),
+            None => format!("assertion `{}` failed", self.assertion),
+        };
+        let width = self
+            .details
+            .iter()
+            .map(|detail| match detail {
+                Detail::Value(label, _) => label.chars().count(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        for detail in &self.details {
+            match detail {
+                Detail::Value(label, value) => {
+                    let _ = write!(out, "\n  {:>width$}: {}", label, value, width = width);
+                }
+                Detail::Note(note) => {
+                    let _ = write!(out, "\n  {}", note);
+                }
+                Detail::Comparison(_, _) if skip_diff => skip_diff = false,
+                Detail::Comparison(left, right) => failure::write_rendered(&mut out, left, right),
+            }
+        }
+        out.trim_end().to_string()
+    }
+}
+
+impl fmt::Display for FailureBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.clone().build().description())
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn run<T, A>(mode: Mode, assertion: &str, actual: &T, check: &A)
+where
+    T: ?Sized,
+    A: Assertion<T> + ?Sized,
+{
+    if let Err(failure) = check.check(actual, FailureBuilder::new(assertion)) {
+        failure::fail(mode, failure.build());
+    }
+}
@@ -0,0 +1,295 @@
+//! Receiving ends of channels, for
+//! [`expect_messages!`](crate::expect_messages).
+//!
+//! The macro receives messages through [`Receive`], which the receivers of
+//! `std::sync::mpsc` implement, and with the `tokio` feature those of
+//! Tokio's `mpsc` and `broadcast` channels. Other channels can implement it
+//! too.
+//!
+//! Tokio's receivers are polled without blocking, with short sleeps in
+//! between, so in `async` tests the messages must come from other threads,
+//! such as the workers of a `multi_thread` runtime or threads of the test's
+//! own, rather than from tasks waiting for the test's thread.
+
+use std::fmt::{Debug, Write};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::collections::{list, unmatched};
+use crate::failure::{comparison_failed, fail, Mode};
+
+/// How long [`expect_messages!`](crate::expect_messages) waits for all of
+/// its messages unless given a timeout.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What [`Receive::receive`] got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Received<T> {
+    Message(T),
+    /// No message arrived in time.
+    Timeout,
+    /// Every sender is gone, and no message is left.
+    Closed,
+}
+
+/// The receiving end of a channel.
+pub trait Receive {
+    type Message;
+
+    /// Waits up to `timeout` for the next message.
+    fn receive(&mut self, timeout: Duration) -> Received<Self::Message>;
+}
+
+impl<R: Receive + ?Sized> Receive for &mut R {
+    type Message = R::Message;
+
+    fn receive(&mut self, timeout: Duration) -> Received<Self::Message> {
+        (**self).receive(timeout)
+    }
+}
+
+impl<T> Receive for mpsc::Receiver<T> {
+    type Message = T;
+
+    fn receive(&mut self, timeout: Duration) -> Received<T> {
+        match self.recv_timeout(timeout) {
+            Ok(message) => Received::Message(message),
+            Err(mpsc::RecvTimeoutError::Timeout) => Received::Timeout,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Received::Closed,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_channels {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::{broadcast, mpsc};
+
+    use super::{Receive, Received};
+
+    /// How long to sleep between polls of a Tokio receiver.
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// Polls `try_receive` until it returns something or `timeout` elapses.
+    fn poll<T>(
+        timeout: Duration,
+        mut try_receive: impl FnMut() -> Option<Received<T>>,
+    ) -> Received<T> {
+        let start = Instant::now();
+        loop {
+            if let Some(received) = try_receive() {
+                return received;
+            }
+            if start.elapsed() >= timeout {
+                return Received::Timeout;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    impl<T> Receive for mpsc::Receiver<T> {
+        type Message = T;
+
+        fn receive(&mut self, timeout: Duration) -> Received<T> {
+            poll(timeout, || match self.try_recv() {
+                Ok(message) => Some(Received::Message(message)),
+                Err(mpsc::error::TryRecvError::Empty) => None,
+                Err(mpsc::error::TryRecvError::Disconnected) => Some(Received::Closed),
+            })
+        }
+    }
+
+    impl<T> Receive for mpsc::UnboundedReceiver<T> {
+        type Message = T;
+
+        fn receive(&mut self, timeout: Duration) -> Received<T> {
+            poll(timeout, || match self.try_recv() {
+                Ok(message) => Some(Received::Message(message)),
+                Err(mpsc::error::TryRecvError::Empty) => None,
+                Err(mpsc::error::TryRecvError::Disconnected) => Some(Received::Closed),
+            })
+        }
+    }
+
+    /// Messages the receiver lagged behind on are skipped.
+    impl<T: Clone> Receive for broadcast::Receiver<T> {
+        type Message = T;
+
+        fn receive(&mut self, timeout: Duration) -> Received<T> {
+            poll(timeout, || match self.try_recv() {
+                Ok(message) => Some(Received::Message(message)),
+                Err(broadcast::error::TryRecvError::Empty)
+                | Err(broadcast::error::TryRecvError::Lagged(_)) => None,
+                Err(broadcast::error::TryRecvError::Closed) => Some(Received::Closed),
+            })
+        }
+    }
+}
+
+/// Receives as many messages as `expected` holds, waiting up to `timeout`
+/// for them in all, and compares them against `expected`: in order, or in
+/// any order if `unordered`. Returns the messages received.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn expect_messages<R, T>(
+    assertion: &str,
+    receiver: &mut R,
+    expected: &[T],
+    unordered: bool,
+    timeout: Duration,
+) -> Vec<R::Message>
+where
+    R: Receive + ?Sized,
+    R::Message: PartialEq<T> + Debug,
+    T: Debug,
+{
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut ended = None;
+    while received.len() < expected.len() {
+        let left = timeout.saturating_sub(start.elapsed());
+        match receiver.receive(left) {
+            Received::Message(message) => received.push(message),
+            Received::Timeout => {
+                ended = Some(format!("within {:?}", timeout));
+                break;
+            }
+            Received::Closed => {
+                ended = Some("before the channel closed".to_string());
+                break;
+            }
+        }
+    }
+
+    let short = ended.map(|ended| {
+        format!(
+            "received {} of {} {}",
+            received.len(),
+            messages(expected.len()),
+            ended
+        )
+    });
+    if unordered {
+        let (extra, missing) = unmatched(&received, expected);
+        if extra.is_empty() && missing.is_empty() {
+            return received;
+        }
+        let mut message = match &short {
+            Some(short) => format!("assertion `{}` failed: {}", assertion, short),
+            None => format!("assertion `{}` failed", assertion),
+        };
+        if !missing.is_empty() {
+            let _ = write!(message, "\n   missing: {}", list(&missing));
+        }
+        if !extra.is_empty() {
+            let _ = write!(message, "\n     extra: {}", list(&extra));
+        }
+        let _ = write!(
+            message,
+            "\n  received: {:?}\n  expected: {:?}",
+            received, expected
+        );
+        fail(Mode::Fatal, message);
+    } else if short.is_some()
+        || received
+            .iter()
+            .zip(expected)
+            .any(|(message, expected)| *message != *expected)
+    {
+        match &short {
+            Some(short) => comparison_failed(
+                Mode::Fatal,
+                assertion,
+                &received,
+                expected,
+                Some(format_args!("{}", short)),
+            ),
+            None => comparison_failed(Mode::Fatal, assertion, &received, expected, None),
+        }
+    }
+    received
+}
+
+fn messages(count: usize) -> String {
+    match count {
+        1 => "1 message".to_string(),
+        count => format!("{} messages", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::failure::tests::{failure_of, message_of};
+
+    const SHORT: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn messages_are_received_in_order() {
+        let (sender, mut receiver) = mpsc::channel();
+        for event in ["started", "connected", "ready"] {
+            sender.send(event).unwrap();
+        }
+        assert_eq!(crate::expect_messages!(receiver, ["started"]), ["started"]);
+        crate::expect_messages!(receiver, unordered: ["ready", "connected"]);
+    }
+
+    #[test]
+    fn messages_out_of_order_are_shown_against_those_expected() {
+        let (sender, mut receiver) = mpsc::channel();
+        sender.send(2).unwrap();
+        sender.send(1).unwrap();
+        let failure = failure_of(|| crate::expect_messages!(receiver, [1, 2]));
+        assert_eq!(
+            failure.message,
+            "assertion `expect_messages!(receiver, [1, 2])` failed"
+        );
+        let diff = failure.diff.expect("the messages are compared");
+        assert_eq!(diff.left, format!("{:#?}", [2, 1]));
+        assert_eq!(diff.right, format!("{:#?}", [1, 2]));
+    }
+
+    #[test]
+    fn missing_messages_time_out() {
+        let (sender, mut receiver) = mpsc::channel();
+        sender.send("started").unwrap();
+        let failure =
+            failure_of(|| crate::expect_messages!(receiver, ["started", "ready"], timeout = SHORT));
+        assert_eq!(
+            failure.message,
+            "assertion `expect_messages!(receiver, [\"started\", \"ready\"], timeout = SHORT)` \
+             failed: received 1 of 2 messages within 10ms"
+        );
+        drop(sender);
+    }
+
+    #[test]
+    fn closed_channels_end_the_wait() {
+        let (sender, mut receiver) = mpsc::channel();
+        sender.send(3).unwrap();
+        drop(sender);
+        assert_eq!(
+            message_of(|| crate::expect_messages!(receiver, unordered: [1, 3])),
+            "assertion `expect_messages!(receiver, unordered: [1, 3])` failed: received 1 of 2 \
+             messages before the channel closed\n   missing: [1]\n  received: [3]\n  expected: \
+             [1, 3]"
+        );
+    }
+
+    #[test]
+    fn unordered_messages_name_the_unexpected_ones() {
+        let (sender, mut receiver) = mpsc::channel();
+        for message in ["a", "b", "c"] {
+            sender.send(message).unwrap();
+        }
+        assert_eq!(
+            message_of(|| crate::expect_messages!(receiver, unordered: ["c", "a", "d"])),
+            "assertion `expect_messages!(receiver, unordered: [\"c\", \"a\", \"d\"])` failed\n   \
+             missing: [\"d\"]\n     extra: [\"b\"]\n  received: [\"a\", \"b\", \"c\"]\n  \
+             expected: [\"c\", \"a\", \"d\"]"
+        );
+    }
+}
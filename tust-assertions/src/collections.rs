@@ -0,0 +1,335 @@
+//! Support for the collection assertions, such as
+//! [`assert_same_elements!`](crate::assert_same_elements), and the matchers
+//! built on the same comparisons.
+
+use std::cmp::Ordering;
+use std::fmt::{Debug, Write};
+
+use crate::failure::{fail, Mode};
+
+/// Pairs up equal elements of `actual` and `expected`, using each element at
+/// most once, and returns the elements of `actual` left over and those of
+/// `expected` left over.
+pub(crate) fn unmatched<'a, 'b, T, U>(
+    actual: impl IntoIterator<Item = &'a T>,
+    expected: impl IntoIterator<Item = &'b U>,
+) -> (Vec<&'a T>, Vec<&'b U>)
+where
+    T: PartialEq<U> + ?Sized + 'a,
+    U: ?Sized + 'b,
+{
+    let mut extra: Vec<&T> = actual.into_iter().collect();
+    let mut missing = Vec::new();
+    for item in expected {
+        match extra.iter().position(|element| **element == *item) {
+            Some(index) => {
+                extra.remove(index);
+            }
+            None => missing.push(item),
+        }
+    }
+    (extra, missing)
+}
+
+/// The index of the first element that `compare` orders after its
+/// successor, with both elements.
+pub(crate) fn first_unsorted<'a, T: ?Sized + 'a>(
+    elements: impl IntoIterator<Item = &'a T>,
+    mut compare: impl FnMut(&T, &T) -> Ordering,
+) -> Option<(usize, &'a T, &'a T)> {
+    let mut elements = elements.into_iter();
+    let mut previous = elements.next()?;
+    for (index, element) in elements.enumerate() {
+        if compare(previous, element) == Ordering::Greater {
+            return Some((index, previous, element));
+        }
+        previous = element;
+    }
+    None
+}
+
+/// Orders elements, treating incomparable ones such as `NaN` as unsorted.
+pub fn partial_order<T: PartialOrd + ?Sized>(a: &T, b: &T) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Greater)
+}
+
+/// Formats elements as a list, e.g. `[1, 2]`.
+pub(crate) fn list<T: Debug + ?Sized>(elements: &[&T]) -> String {
+    format!("{:?}", elements)
+}
+
+#[track_caller]
+pub fn assert_same_elements<A, B, T, U>(assertion: &str, actual: &A, expected: &B)
+where
+    A: Debug + ?Sized,
+    B: Debug + ?Sized,
+    for<'a> &'a A: IntoIterator<Item = &'a T>,
+    for<'b> &'b B: IntoIterator<Item = &'b U>,
+    T: PartialEq<U> + Debug,
+    U: Debug,
+{
+    let (extra, missing) = unmatched(actual, expected);
+    if extra.is_empty() && missing.is_empty() {
+        return;
+    }
+    let mut message = format!("assertion `{}` failed", assertion);
+    if !missing.is_empty() {
+        let _ = write!(message, "\n   missing: {}", list(&missing));
+    }
+    if !extra.is_empty() {
+        let _ = write!(message, "\n     extra: {}", list(&extra));
+    }
+    let _ = write!(
+        message,
+        "\n    actual: {:?}\n  expected: {:?}",
+        actual, expected
+    );
+    fail(Mode::Fatal, message);
+}
+
+#[track_caller]
+pub fn assert_contains_all<A, B, T, U>(assertion: &str, actual: &A, expected: &B)
+where
+    A: Debug + ?Sized,
+    B: ?Sized,
+    for<'a> &'a A: IntoIterator<Item = &'a T>,
+    for<'b> &'b B: IntoIterator<Item = &'b U>,
+    T: PartialEq<U>,
+    U: Debug,
+{
+    let (_, missing) = unmatched(actual, expected);
+    if missing.is_empty() {
+        return;
+    }
+    fail(
+        Mode::Fatal,
+        format!(
+            "assertion `{}` failed\n  missing: {}\n   actual: {:?}",
+            assertion,
+            list(&missing),
+            actual
+        ),
+    );
+}
+
+#[track_caller]
+pub fn assert_sorted_by<A, T>(assertion: &str, actual: &A, compare: impl FnMut(&T, &T) -> Ordering)
+where
+    A: Debug + ?Sized,
+    for<'a> &'a A: IntoIterator<Item = &'a T>,
+    T: Debug,
+{
+    if let Some((index, first, second)) = first_unsorted(actual, compare) {
+        fail(
+            Mode::Fatal,
+            format!(
+                "assertion `{}` failed: the elements at {} and {} are out of order\n  \
+                 [{}]: {:?}\n  [{}]: {:?}\n  actual: {:?}",
+                assertion,
+                index,
+                index + 1,
+                index,
+                first,
+                index + 1,
+                second,
+                actual
+            ),
+        );
+    }
+}
+
+/// Checks the elements of `actual` one at a time, stopping at the first that
+/// does not satisfy `predicate`, so that no iterator is collected.
+#[track_caller]
+pub fn assert_all<I>(assertion: &str, actual: I, mut predicate: impl FnMut(&I::Item) -> bool)
+where
+    I: IntoIterator,
+    I::Item: Debug,
+{
+    let failing = actual
+        .into_iter()
+        .enumerate()
+        .find(|(_, element)| !predicate(element));
+    if let Some((index, element)) = failing {
+        fail(
+            Mode::Fatal,
+            format!(
+                "assertion `{}` failed: the element at {} does not satisfy the predicate\n  \
+                 [{}]: {:?}",
+                assertion, index, index, element
+            ),
+        );
+    }
+}
+
+/// Checks the elements of `actual` one at a time, stopping at the first that
+/// satisfies `predicate`.
+#[track_caller]
+pub fn assert_any<I>(assertion: &str, actual: I, mut predicate: impl FnMut(&I::Item) -> bool)
+where
+    I: IntoIterator,
+{
+    let mut checked = 0;
+    for element in actual {
+        if predicate(&element) {
+            return;
+        }
+        checked += 1;
+    }
+    fail(
+        Mode::Fatal,
+        format!(
+            "assertion `{}` failed: none of the {} {} satisfies the predicate",
+            assertion,
+            checked,
+            if checked == 1 { "element" } else { "elements" }
+        ),
+    );
+}
+
+/// Compares two maps entry by entry, reporting the keys only one of them
+/// has and the keys whose values differ.
+#[track_caller]
+pub fn assert_map_eq<A, B, K, V>(assertion: &str, actual: &A, expected: &B)
+where
+    A: ?Sized,
+    B: ?Sized,
+    for<'a> &'a A: IntoIterator<Item = (&'a K, &'a V)>,
+    for<'b> &'b B: IntoIterator<Item = (&'b K, &'b V)>,
+    K: PartialEq + Debug,
+    V: PartialEq + Debug,
+{
+    let expected: Vec<(&K, &V)> = expected.into_iter().collect();
+    let mut extra = Vec::new();
+    let mut changed = Vec::new();
+    let mut found = vec![false; expected.len()];
+    for (key, value) in actual {
+        match expected.iter().position(|(expected, _)| *expected == key) {
+            Some(index) => {
+                found[index] = true;
+                let expected = expected[index].1;
+                if value != expected {
+                    changed.push(format!("{:?}: {:?}, expected {:?}", key, value, expected));
+                }
+            }
+            None => extra.push(format!("{:?}: {:?}", key, value)),
+        }
+    }
+    let missing: Vec<String> = expected
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| !found)
+        .map(|((key, value), _)| format!("{:?}: {:?}", key, value))
+        .collect();
+    if missing.is_empty() && extra.is_empty() && changed.is_empty() {
+        return;
+    }
+
+    let mut message = format!("assertion `{}` failed", assertion);
+    for (label, entries) in [
+        ("missing", missing),
+        ("  extra", extra),
+        ("changed", changed),
+    ] {
+        if !entries.is_empty() {
+            let _ = write!(message, "\n  {}: {{{}}}", label, entries.join(", "));
+        }
+    }
+    fail(Mode::Fatal, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use crate::failure::tests::message_of;
+
+    #[test]
+    fn same_elements_ignore_order_but_count_repeats() {
+        let actual = vec![3, 1, 2, 1];
+        crate::assert_same_elements!(actual, [1, 1, 2, 3]);
+        assert_eq!(
+            message_of(|| crate::assert_same_elements!(actual, [1, 2, 3, 4])),
+            "assertion `assert_same_elements!(actual, [1, 2, 3, 4])` failed\n   \
+             missing: [4]\n     extra: [1]\n    actual: [3, 1, 2, 1]\n  expected: [1, 2, 3, 4]"
+        );
+    }
+
+    #[test]
+    fn contains_all_names_the_missing_elements() {
+        let tags = HashSet::from(["unit"]);
+        crate::assert_contains_all!(tags, ["unit"]);
+        assert_eq!(
+            message_of(|| crate::assert_contains_all!(tags, ["unit", "db", "net"])),
+            "assertion `assert_contains_all!(tags, [\"unit\", \"db\", \"net\"])` failed\n  \
+             missing: [\"db\", \"net\"]\n   actual: {\"unit\"}"
+        );
+    }
+
+    #[test]
+    fn sorted_names_the_first_pair_out_of_order() {
+        let numbers = [1, 2, 2, 5];
+        crate::assert_sorted!(numbers);
+        let numbers = [1, 3, 2, 0];
+        assert_eq!(
+            message_of(|| crate::assert_sorted!(numbers)),
+            "assertion `assert_sorted!(numbers)` failed: the elements at 1 and 2 are out of \
+             order\n  [1]: 3\n  [2]: 2\n  actual: [1, 3, 2, 0]"
+        );
+        let floats = [1.0, f64::NAN];
+        assert_eq!(
+            message_of(|| crate::assert_sorted!(floats)),
+            "assertion `assert_sorted!(floats)` failed: the elements at 0 and 1 are out of \
+             order\n  [0]: 1.0\n  [1]: NaN\n  actual: [1.0, NaN]"
+        );
+        let words = ["framework", "tust", "is"];
+        crate::assert_sorted_by!(words, |a, b| b.len().cmp(&a.len()));
+    }
+
+    #[test]
+    fn all_stops_at_the_first_element_failing() {
+        let mut checked = Vec::new();
+        let message = message_of(|| {
+            crate::assert_all!(1.., |n| {
+                checked.push(*n);
+                *n < 3
+            })
+        });
+        assert_eq!(
+            message,
+            "assertion `assert_all!(1.., |n| { checked.push(*n); *n < 3 })` failed: the element \
+             at 2 does not satisfy the predicate\n  [2]: 3"
+        );
+        assert_eq!(checked, [1, 2, 3]);
+        crate::assert_all!([2, 4], |n| n % 2 == 0);
+    }
+
+    #[test]
+    fn any_stops_at_the_first_element_passing() {
+        crate::assert_any!(1.., |n| *n == 3);
+        let odd = [1, 3, 5];
+        assert_eq!(
+            message_of(|| crate::assert_any!(odd, |n| n % 2 == 0)),
+            "assertion `assert_any!(odd, |n| n % 2 == 0)` failed: none of the 3 elements \
+             satisfies the predicate"
+        );
+        let empty: [u8; 0] = [];
+        assert_eq!(
+            message_of(|| crate::assert_any!(empty, |_| true)),
+            "assertion `assert_any!(empty, |_| true)` failed: none of the 0 elements satisfies \
+             the predicate"
+        );
+    }
+
+    #[test]
+    fn map_eq_names_the_entries_that_differ() {
+        let actual = BTreeMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        crate::assert_map_eq!(actual, BTreeMap::from([("c", 3), ("b", 2), ("a", 1)]));
+        let expected = BTreeMap::from([("a", 1), ("b", 5), ("d", 4)]);
+        assert_eq!(
+            message_of(|| crate::assert_map_eq!(actual, expected)),
+            "assertion `assert_map_eq!(actual, expected)` failed\n  missing: {\"d\": 4}\n    \
+             extra: {\"c\": 3}\n  changed: {\"b\": 2, expected 5}"
+        );
+    }
+}
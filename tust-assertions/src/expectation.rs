@@ -0,0 +1,49 @@
+//! The value under test in `expect!(value).to(matcher)`.
+
+use std::fmt::{Debug, Write};
+
+use crate::failure::{self, Mode};
+use crate::matchers::Matcher;
+
+/// A value checked against [matchers](crate::matchers), created by
+/// [`expect!`](crate::expect).
+///
+/// A failed expectation is fatal and ends the test.
+#[must_use = "an expectation does nothing until `to` or `not_to` is called"]
+pub struct Expectation<'a, T: ?Sized> {
+    actual: &'a T,
+    expression: &'static str,
+}
+
+impl<'a, T: Debug + ?Sized> Expectation<'a, T> {
+    /// Wraps `actual`; `expression` is its source text.
+    pub fn new(actual: &'a T, expression: &'static str) -> Self {
+        Expectation { actual, expression }
+    }
+
+    /// Fails unless `matcher` accepts the value.
+    #[track_caller]
+    pub fn to<M: Matcher<T>>(self, matcher: M) {
+        if !matcher.matches(self.actual) {
+            self.fail(&matcher.describe(), matcher.explain_mismatch(self.actual));
+        }
+    }
+
+    /// Fails if `matcher` accepts the value.
+    #[track_caller]
+    pub fn not_to<M: Matcher<T>>(self, matcher: M) {
+        if matcher.matches(self.actual) {
+            self.fail(&format!("not {}", matcher.describe()), None);
+        }
+    }
+
+    #[track_caller]
+    fn fail(&self, description: &str, explanation: Option<String>) {
+        let mut message = format!("expected `{}` to {}", self.expression, description);
+        let _ = write!(message, "\n  actual: {:?}", self.actual);
+        if let Some(explanation) = explanation {
+            let _ = write!(message, "\n          {}", explanation);
+        }
+        failure::fail(Mode::Fatal, message);
+    }
+}
@@ -0,0 +1,321 @@
+//! Failures raised by the assertion macros, as [`TestFailure`]s whose
+//! compared values reporters receive apart from the message.
+//!
+//! Without the standard library there are no `TestFailure`s, and failures
+//! panic with their description instead.
+
+use core::fmt::{self, Debug};
+#[cfg(feature = "std")]
+use std::fmt::Write;
+
+#[cfg(feature = "std")]
+use tust_runtime::{soft, FailureKind, TestFailure};
+
+use crate::approx::{ApproxEq, Tolerance};
+#[cfg(feature = "std")]
+use crate::diff;
+
+/// What happens when an assertion fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Panic, ending the test.
+    Fatal,
+    /// Record the failure and let the test continue; see [`tust_runtime::soft`].
+    /// Without the standard library, panic like [`Mode::Fatal`].
+    Soft,
+}
+
+/// Reports a failed comparison of `left` and `right`.
+///
+/// `assertion` is the assertion as written, e.g. `check_eq!(a, b)`.
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn comparison_failed<L, R>(
+    mode: Mode,
+    assertion: &str,
+    left: &L,
+    right: &R,
+    message: Option<fmt::Arguments<'_>>,
+) where
+    L: Debug + ?Sized,
+    R: Debug + ?Sized,
+{
+    fail(mode, with_values(header(assertion, message), left, right));
+}
+
+/// Reports `left` and `right` that are not equal within `tolerance`.
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn approx_failed<L, R>(assertion: &str, left: &L, right: &R, tolerance: &Tolerance)
+where
+    L: ApproxEq<R> + Debug + ?Sized,
+    R: Debug + ?Sized,
+{
+    let mut out = format!(
+        "{}: the values are not equal {}",
+        header(assertion, None),
+        tolerance
+    );
+    if let Some(difference) = left.explain_difference(right, tolerance) {
+        let _ = write!(out, "\n  {}", difference);
+    }
+    fail(Mode::Fatal, with_values(out, left, right));
+}
+
+#[cfg(feature = "std")]
+/// A failure with `message` comparing the pretty-printed values.
+fn with_values<L, R>(message: String, left: &L, right: &R) -> TestFailure
+where
+    L: Debug + ?Sized,
+    R: Debug + ?Sized,
+{
+    TestFailure::new(message).with_diff(format!("{:#?}", left), format!("{:#?}", right))
+}
+
+#[cfg(feature = "std")]
+/// Appends values already pretty-printed to `out`, as a diff if they are
+/// large, for messages comparing more than one pair of values.
+pub(crate) fn write_rendered(out: &mut String, left: &str, right: &str) {
+    if diff::wants_diff(left, right) {
+        let _ = write!(out, "\n\n{}", diff::format_diff(left, right));
+    } else {
+        let _ = write!(out, "\n  left: {}\n right: {}", left, right);
+    }
+}
+
+/// Reports a boolean assertion that evaluated to `false`.
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn condition_failed(mode: Mode, assertion: &str, message: Option<fmt::Arguments<'_>>) {
+    fail(mode, header(assertion, message));
+}
+
+#[cfg(feature = "std")]
+/// Reports a value that is not of the shape an assertion expects, e.g. an
+/// `Err` given to `assert_ok!`. `expected` describes the shape, such as
+/// `Ok(_)` or a pattern.
+#[track_caller]
+pub fn variant_mismatch<T: Debug + ?Sized>(
+    assertion: &str,
+    expected: &str,
+    found: &T,
+    message: Option<fmt::Arguments<'_>>,
+) -> ! {
+    TestFailure::new(header(assertion, message))
+        .with_metadata("expected", expected)
+        .with_metadata("found", format!("{:#?}", found))
+        .raise()
+}
+
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn fail(mode: Mode, failure: impl Into<TestFailure>) {
+    match mode {
+        Mode::Fatal => failure.into().raise(),
+        Mode::Soft => soft::record(TestFailure {
+            kind: FailureKind::Check,
+            ..failure.into()
+        }),
+    }
+}
+
+#[cfg(feature = "std")]
+fn header(assertion: &str, message: Option<fmt::Arguments<'_>>) -> String {
+    match message {
+        Some(message) => format!("assertion `{}` failed: {}", assertion, message),
+        None => format!("assertion `{}` failed", assertion),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[track_caller]
+pub fn comparison_failed<L, R>(
+    _mode: Mode,
+    assertion: &str,
+    left: &L,
+    right: &R,
+    message: Option<fmt::Arguments<'_>>,
+) where
+    L: Debug + ?Sized,
+    R: Debug + ?Sized,
+{
+    panic!(
+        "{}\n  left: {:#?}\n right: {:#?}",
+        Header(assertion, message),
+        left,
+        right
+    );
+}
+
+#[cfg(not(feature = "std"))]
+#[track_caller]
+pub fn approx_failed<L, R>(assertion: &str, left: &L, right: &R, tolerance: &Tolerance)
+where
+    L: ApproxEq<R> + Debug + ?Sized,
+    R: Debug + ?Sized,
+{
+    #[cfg(feature = "alloc")]
+    if let Some(difference) = left.explain_difference(right, tolerance) {
+        panic!(
+            "{}: the values are not equal {}\n  {}\n  left: {:#?}\n right: {:#?}",
+            Header(assertion, None),
+            tolerance,
+            difference,
+            left,
+            right
+        );
+    }
+    panic!(
+        "{}: the values are not equal {}\n  left: {:#?}\n right: {:#?}",
+        Header(assertion, None),
+        tolerance,
+        left,
+        right
+    );
+}
+
+#[cfg(not(feature = "std"))]
+#[track_caller]
+pub fn condition_failed(_mode: Mode, assertion: &str, message: Option<fmt::Arguments<'_>>) {
+    panic!("{}", Header(assertion, message));
+}
+
+#[cfg(not(feature = "std"))]
+#[track_caller]
+pub fn variant_mismatch<T: Debug + ?Sized>(
+    assertion: &str,
+    expected: &str,
+    found: &T,
+    message: Option<fmt::Arguments<'_>>,
+) -> ! {
+    panic!(
+        "{}\n  expected: {}\n     found: {:#?}",
+        Header(assertion, message),
+        expected,
+        found
+    );
+}
+
+/// The first line of a failure, written without allocating.
+#[cfg(not(feature = "std"))]
+struct Header<'a>(&'a str, Option<fmt::Arguments<'a>>);
+
+#[cfg(not(feature = "std"))]
+impl fmt::Display for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "assertion `{}` failed", self.0)?;
+        if let Some(message) = self.1 {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use tust_runtime::TestFailure;
+
+    /// The failure that `f` ends the test with.
+    #[track_caller]
+    pub(crate) fn failure_of<R>(f: impl FnOnce() -> R) -> TestFailure {
+        match tust_runtime::__private::catch_panic(f) {
+            Ok(_) => panic!("the assertion passed"),
+            Err(failure) => failure,
+        }
+    }
+
+    /// The message of the failure that `f` ends the test with.
+    #[track_caller]
+    pub(crate) fn message_of<R>(f: impl FnOnce() -> R) -> String {
+        failure_of(f).message
+    }
+
+    fn metadata(failure: &TestFailure) -> Vec<(&str, &str)> {
+        failure
+            .metadata
+            .iter()
+            .map(|(label, value)| (label.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn variant_assertions_evaluate_to_what_they_hold() {
+        let parsed: Result<u16, String> = Ok(8080);
+        let failed: Result<u16, &str> = Err("invalid");
+        let found = Some(3);
+        assert_eq!(crate::assert_ok!(parsed), 8080);
+        assert_eq!(crate::assert_err!(failed), "invalid");
+        assert_eq!(crate::assert_some!(found), 3);
+        crate::assert_none!(None::<u8>);
+    }
+
+    #[test]
+    fn variant_assertions_report_the_other_variant() {
+        let failed: Result<u16, &str> = Err("invalid");
+        let failure = failure_of(|| crate::assert_ok!(failed));
+        assert_eq!(failure.message, "assertion `assert_ok!(failed)` failed");
+        assert_eq!(
+            metadata(&failure),
+            [
+                ("expected", "Ok(_)"),
+                ("found", "Err(\n    \"invalid\",\n)")
+            ]
+        );
+
+        let parsed: Result<u16, &str> = Ok(8080);
+        let failure = failure_of(|| crate::assert_err!(parsed, "port {}", 1));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_err!(parsed)` failed: port 1"
+        );
+        assert_eq!(
+            metadata(&failure),
+            [("expected", "Err(_)"), ("found", "Ok(\n    8080,\n)")]
+        );
+
+        let missing: Option<u8> = None;
+        let failure = failure_of(|| crate::assert_some!(missing));
+        assert_eq!(failure.message, "assertion `assert_some!(missing)` failed");
+        assert_eq!(
+            metadata(&failure),
+            [("expected", "Some(_)"), ("found", "None")]
+        );
+
+        let found = Some(3);
+        let failure = failure_of(|| crate::assert_none!(found));
+        assert_eq!(failure.message, "assertion `assert_none!(found)` failed");
+        assert_eq!(
+            metadata(&failure),
+            [("expected", "None"), ("found", "Some(\n    3,\n)")]
+        );
+    }
+
+    #[test]
+    fn assert_matches_reports_the_pattern_and_the_value() {
+        let port = 80;
+        crate::assert_matches!(port, 1..=1023);
+        let failure = failure_of(|| crate::assert_matches!(port, 1024..=65535));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_matches!(port, 1024..=65535)` failed"
+        );
+        assert_eq!(
+            metadata(&failure),
+            [("expected", "1024..=65535"), ("found", "80")]
+        );
+        let message = message_of(|| crate::assert_matches!(port, p if p > 1023, "not {}", "free"));
+        assert_eq!(
+            message,
+            "assertion `assert_matches!(port, p if p > 1023)` failed: not free"
+        );
+    }
+
+    #[test]
+    fn soft_checks_outside_a_test_fail_like_fatal_ones() {
+        crate::check_eq!(1 + 1, 2);
+        assert_eq!(
+            message_of(|| crate::check_eq!(1 + 1, 3)),
+            "assertion `check_eq!(1 + 1, 3)` failed\n  left: 2\n right: 3"
+        );
+    }
+}
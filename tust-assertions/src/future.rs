@@ -0,0 +1,307 @@
+//! Support for [`assert_ready!`](crate::assert_ready),
+//! [`assert_pending!`](crate::assert_pending),
+//! [`assert_stream_yields!`](crate::assert_stream_yields), and
+//! [`assert_completes_within!`](crate::assert_completes_within).
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tust_runtime::TestFailure;
+
+use crate::failure::{comparison_failed, Mode};
+
+/// A waker that does nothing, for polling a future once.
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+fn poll_once<F: Future>(future: F) -> Poll<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(NoopWaker));
+    future.as_mut().poll(&mut Context::from_waker(&waker))
+}
+
+/// Polls `future` once, which must be ready, and returns its output.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_ready<F: Future>(assertion: &str, future: F) -> F::Output {
+    match poll_once(future) {
+        Poll::Ready(output) => output,
+        Poll::Pending => TestFailure::new(format!(
+            "assertion `{}` failed: the future is pending",
+            assertion
+        ))
+        .raise(),
+    }
+}
+
+/// Polls `future` once, which must be pending.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_pending<F>(assertion: &str, future: F)
+where
+    F: Future,
+    F::Output: Debug,
+{
+    if let Poll::Ready(output) = poll_once(future) {
+        TestFailure::new(format!(
+            "assertion `{}` failed: the future is ready",
+            assertion
+        ))
+        .with_metadata("output", format!("{:?}", output))
+        .raise();
+    }
+}
+
+/// Collects the items of `stream` until it ends.
+pub async fn collect<S: Stream>(stream: S) -> Vec<S::Item> {
+    let mut stream = std::pin::pin!(stream);
+    let mut items = Vec::new();
+    while let Some(item) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+        items.push(item);
+    }
+    items
+}
+
+/// Compares the items a stream yielded before it ended against `expected`.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_stream_yields<T>(assertion: &str, items: &[T], expected: &[T])
+where
+    T: PartialEq + Debug,
+{
+    if items != expected {
+        comparison_failed(
+            Mode::Fatal,
+            assertion,
+            items,
+            expected,
+            Some(format_args!(
+                "the stream yielded {} before it ended, not {}",
+                count(items.len()),
+                count(expected.len())
+            )),
+        );
+    }
+}
+
+fn count(items: usize) -> String {
+    match items {
+        1 => "1 item".to_string(),
+        items => format!("{} items", items),
+    }
+}
+
+/// Runs `future` until it completes or `timeout` elapses, whichever comes
+/// first, with any executor: a thread wakes the task once `timeout` elapses.
+///
+/// Resolves to the output, or to `None` if `timeout` elapsed first.
+pub async fn within<F: Future>(timeout: Duration, future: F) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let mut timer = None;
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        let timer = timer.get_or_insert_with(|| Timer::start(timeout));
+        if timer.elapsed(cx.waker()) {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Fails the test for a future that did not complete within `timeout`.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn completes_within_failed(assertion: &str, timeout: Duration) -> ! {
+    TestFailure::new(format!(
+        "assertion `{}` failed: the future did not complete within {:?}",
+        assertion, timeout
+    ))
+    .raise()
+}
+
+/// The timer of [`within`]: a thread waking the task once the timeout
+/// elapses, stopped early when the timer is dropped.
+struct Timer {
+    shared: Arc<(Mutex<TimerState>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct TimerState {
+    elapsed: bool,
+    stopped: bool,
+    /// The waker of the latest poll, to wake once the timeout elapses.
+    waker: Option<Waker>,
+}
+
+impl Timer {
+    fn start(timeout: Duration) -> Self {
+        let shared = Arc::new((Mutex::new(TimerState::default()), Condvar::new()));
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("tust-timeout".to_string())
+                .spawn(move || {
+                    let (state, stopped) = &*shared;
+                    let state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                    let (mut state, _) = stopped
+                        .wait_timeout_while(state, timeout, |state| !state.stopped)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    if state.stopped {
+                        return;
+                    }
+                    state.elapsed = true;
+                    let waker = state.waker.take();
+                    drop(state);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                })
+        };
+        let timer = Timer {
+            shared,
+            thread: thread.ok(),
+        };
+        if timer.thread.is_none() {
+            // Without a thread, there is no waiting for the timeout.
+            timer.state().elapsed = true;
+        }
+        timer
+    }
+
+    /// Whether the timeout elapsed; if not, `waker` is woken once it does.
+    fn elapsed(&self, waker: &Waker) -> bool {
+        let mut state = self.state();
+        state.waker = Some(waker.clone());
+        state.elapsed
+    }
+
+    fn state(&self) -> MutexGuard<'_, TimerState> {
+        self.shared.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.state().stopped = true;
+        self.shared.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::pin::Pin;
+
+    use crate::failure::tests::{failure_of, message_of};
+
+    /// A stream of the numbers below `end`, pending before each of them.
+    struct Count {
+        next: u32,
+        end: u32,
+        pending: bool,
+    }
+
+    impl Stream for Count {
+        type Item = u32;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u32>> {
+            if self.next == self.end {
+                return Poll::Ready(None);
+            }
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.next += 1;
+            Poll::Ready(Some(self.next - 1))
+        }
+    }
+
+    fn numbers(end: u32) -> Count {
+        Count {
+            next: 0,
+            end,
+            pending: false,
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tust_runtime::block_on(&tust_runtime::AsyncConfig::DEFAULT, future)
+    }
+
+    #[test]
+    fn ready_futures_give_their_output() {
+        assert_eq!(crate::assert_ready!(std::future::ready(7)), 7);
+        assert_eq!(
+            message_of(|| crate::assert_ready!(std::future::pending::<()>())),
+            "assertion `assert_ready!(std::future::pending::<()>())` failed: the future is pending"
+        );
+    }
+
+    #[test]
+    fn pending_futures_are_not_ready() {
+        crate::assert_pending!(std::future::pending::<()>());
+        let failure = failure_of(|| crate::assert_pending!(std::future::ready(7)));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_pending!(std::future::ready(7))` failed: the future is ready"
+        );
+        assert_eq!(failure.metadata, [("output".to_string(), "7".to_string())]);
+    }
+
+    #[test]
+    fn streams_are_compared_once_they_end() {
+        let items = block_on(collect(numbers(3)));
+        assert_eq!(items, [0, 1, 2]);
+        assert_stream_yields(
+            "assert_stream_yields!(count(3), [0, 1, 2])",
+            &items,
+            &[0, 1, 2],
+        );
+        let failure = failure_of(|| {
+            assert_stream_yields("assert_stream_yields!(count(3), [0])", &items, &[0])
+        });
+        assert_eq!(
+            failure.message,
+            "assertion `assert_stream_yields!(count(3), [0])` failed: the stream yielded 3 items \
+             before it ended, not 1 item"
+        );
+        assert!(failure.diff.is_some());
+    }
+
+    #[test]
+    fn within_times_out_futures_that_do_not_complete() {
+        let quick = block_on(within(Duration::from_secs(5), std::future::ready(1)));
+        assert_eq!(quick, Some(1));
+        let slow = block_on(within(
+            Duration::from_millis(10),
+            std::future::pending::<()>(),
+        ));
+        assert_eq!(slow, None);
+        assert_eq!(
+            message_of(|| completes_within_failed("assert_completes_within!(f)", Duration::from_millis(10))),
+            "assertion `assert_completes_within!(f)` failed: the future did not complete within 10ms"
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Failure reporting for `assert_matches_file!`.
+
+use tust_runtime::golden::{self, GoldenAssertion, GoldenOutcome};
+use tust_runtime::TestFailure;
+
+/// Compares `assertion` against its golden file, panicking if it does not
+/// match.
+#[track_caller]
+pub fn assert_matches_file(assertion: GoldenAssertion<'_>) {
+    let outcome = match golden::check(&assertion) {
+        Ok(outcome) => outcome,
+        Err(error) => TestFailure::new(error.to_string()).raise(),
+    };
+
+    match outcome {
+        GoldenOutcome::Matched | GoldenOutcome::Updated { .. } => {}
+        GoldenOutcome::Mismatched { path, expected } => {
+            let actual = assertion.contents.replace("\r\n", "\n");
+            TestFailure::new(format!(
+                "`{}` does not match golden file {}",
+                assertion.expression,
+                path.display()
+            ))
+            .with_labeled_diff(
+                "golden file",
+                "actual",
+                expected,
+                actual.trim_end_matches('\n'),
+            )
+            .with_metadata("to update", format!("rerun with {}=1", golden::UPDATE_ENV))
+            .raise();
+        }
+        GoldenOutcome::Missing { path } => {
+            TestFailure::new(format!("golden file {} does not exist", path.display()))
+                .with_metadata("to create", format!("rerun with {}=1", golden::UPDATE_ENV))
+                .raise();
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Structural comparison of JSON values, for
+//! [`assert_json_eq!`](crate::assert_json_eq) and
+//! [`assert_json_includes!`](crate::assert_json_includes).
+//!
+//! Both sides are converted to [`Value`]s with `serde_json`, so anything
+//! implementing `Serialize` can be compared, including the values built by
+//! [`json!`]. Objects compare regardless of key order, and numbers by value,
+//! so `1` equals `1.0`. A failure lists every difference with its path, such
+//! as `$.users[0].name`.
+//!
+//! # Ignoring paths
+//!
+//! Values that change from run to run, such as timestamps and generated IDs,
+//! can be left out with `ignore = [...]`, a list of paths in a small subset
+//! of JSONPath:
+//!
+//! - `$` is the whole value;
+//! - `.name` or `["name"]` is a field of an object, and `[2]` an element of
+//!   an array;
+//! - `.*` or `[*]` is any field or element;
+//! - `..name` is a field at any depth below, and `..*` anything below.
+//!
+//! ```
+//! use tust_assertions::assert_json_eq;
+//! use tust_assertions::json::json;
+//!
+//! let event = json!({"kind": "login", "id": 7, "at": "2024-06-01T12:00:00Z"});
+//! assert_json_eq!(event, json!({"id": 7, "kind": "login"}), ignore = ["$.at"]);
+//! ```
+
+use std::fmt::{self, Write};
+
+use serde::Serialize;
+use tust_runtime::TestFailure;
+
+pub use serde_json::{json, Value};
+
+use crate::failure::{fail, Mode};
+
+/// Longest rendering of a value in a failure message before it is cut.
+const MAX_VALUE_WIDTH: usize = 60;
+
+/// How [`assert_json`] compares: for equality, or for inclusion of the
+/// expected value in the actual one.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonMode {
+    Equal,
+    /// Objects may have fields the expected object lacks, and arrays
+    /// elements after those expected.
+    Includes,
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_json<A, E>(assertion: &str, actual: &A, expected: &E, mode: JsonMode, ignore: &[&str])
+where
+    A: Serialize + ?Sized,
+    E: Serialize + ?Sized,
+{
+    let ignore: Vec<Pattern> = ignore
+        .iter()
+        .map(|path| match Pattern::parse(path) {
+            Ok(pattern) => pattern,
+            Err(error) => TestFailure::new(format!(
+                "invalid path {:?} passed to `{}`: {}",
+                path, assertion, error
+            ))
+            .raise(),
+        })
+        .collect();
+    let actual = to_value(assertion, actual);
+    let expected = to_value(assertion, expected);
+
+    let mut comparison = Comparison {
+        mode,
+        ignore: &ignore,
+        path: Vec::new(),
+        differences: Vec::new(),
+    };
+    comparison.compare(Some(&actual), Some(&expected));
+    let differences = comparison.differences;
+    if differences.is_empty() {
+        return;
+    }
+
+    let mut message = match differences.len() {
+        1 => format!("assertion `{}` failed: 1 difference", assertion),
+        count => format!("assertion `{}` failed: {} differences", assertion, count),
+    };
+    for difference in &differences {
+        let _ = write!(message, "\n  {}", difference);
+    }
+    fail(Mode::Fatal, message);
+}
+
+#[track_caller]
+fn to_value<T: Serialize + ?Sized>(assertion: &str, value: &T) -> Value {
+    match serde_json::to_value(value) {
+        Ok(value) => value,
+        Err(error) => TestFailure::new(format!(
+            "a value passed to `{}` cannot be converted to JSON: {}",
+            assertion, error
+        ))
+        .raise(),
+    }
+}
+
+/// One step from a value into a field or element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+/// A step of an ignored path, which may match several segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Field(String),
+    Index(usize),
+    Any,
+}
+
+impl Step {
+    fn matches(&self, segment: &Segment) -> bool {
+        match (self, segment) {
+            (Step::Any, _) => true,
+            (Step::Field(step), Segment::Field(field)) => step == field,
+            (Step::Index(step), Segment::Index(index)) => step == index,
+            _ => false,
+        }
+    }
+}
+
+/// A parsed ignored path: steps, each flagged if it may match at any depth
+/// below the previous one, as written with `..`.
+#[derive(Debug)]
+struct Pattern(Vec<(bool, Step)>);
+
+impl Pattern {
+    fn parse(path: &str) -> Result<Pattern, String> {
+        let mut rest = path
+            .strip_prefix('$')
+            .ok_or_else(|| "paths start with `$`".to_string())?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                let (step, after) = parse_name(after)?;
+                steps.push((true, step));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let (step, after) = parse_name(after)?;
+                steps.push((false, step));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| "unclosed `[`".to_string())?;
+                let inside = &after[..end];
+                let step = if inside == "*" {
+                    Step::Any
+                } else if let Some(name) = quoted(inside) {
+                    Step::Field(name.to_string())
+                } else {
+                    Step::Index(inside.parse().map_err(|_| {
+                        format!("expected an index, `*`, or a quoted name in `[{}]`", inside)
+                    })?)
+                };
+                steps.push((false, step));
+                rest = &after[end + 1..];
+            } else {
+                return Err(format!("expected `.` or `[` before `{}`", rest));
+            }
+        }
+        Ok(Pattern(steps))
+    }
+
+    fn matches(&self, path: &[Segment]) -> bool {
+        fn matches(steps: &[(bool, Step)], path: &[Segment]) -> bool {
+            match steps.split_first() {
+                None => path.is_empty(),
+                Some(((true, step), rest)) => (0..path.len())
+                    .any(|skip| step.matches(&path[skip]) && matches(rest, &path[skip + 1..])),
+                Some(((false, step), rest)) => path
+                    .split_first()
+                    .is_some_and(|(first, tail)| step.matches(first) && matches(rest, tail)),
+            }
+        }
+        matches(&self.0, path)
+    }
+}
+
+/// Parses the field name or `*` after a `.`, up to the next `.` or `[`.
+fn parse_name(input: &str) -> Result<(Step, &str), String> {
+    let end = input.find(['.', '[']).unwrap_or(input.len());
+    let (name, rest) = input.split_at(end);
+    match name {
+        "" => Err("expected a field name or `*` after `.`".to_string()),
+        "*" => Ok((Step::Any, rest)),
+        name => Ok((Step::Field(name.to_string()), rest)),
+    }
+}
+
+fn quoted(input: &str) -> Option<&str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        input
+            .strip_prefix(quote)
+            .and_then(|inside| inside.strip_suffix(quote))
+    })
+}
+
+/// A path to a value, rendered like `$.users[0]["display name"]`.
+struct Path<'a>(&'a [Segment]);
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for segment in self.0 {
+            match segment {
+                Segment::Field(name)
+                    if !name.is_empty()
+                        && name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+                {
+                    write!(f, ".{}", name)?
+                }
+                Segment::Field(name) => write!(f, "[{:?}]", name)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Comparison<'a> {
+    mode: JsonMode,
+    ignore: &'a [Pattern],
+    /// Path to the values being compared.
+    path: Vec<Segment>,
+    differences: Vec<String>,
+}
+
+impl Comparison<'_> {
+    fn compare(&mut self, actual: Option<&Value>, expected: Option<&Value>) {
+        if self
+            .ignore
+            .iter()
+            .any(|pattern| pattern.matches(&self.path))
+        {
+            return;
+        }
+        let path = Path(&self.path);
+        let difference = match (actual, expected) {
+            (None, None) => None,
+            (Some(actual), None) => Some(format!("{}: unexpected {}", path, render(actual))),
+            (None, Some(expected)) => {
+                Some(format!("{}: missing, expected {}", path, render(expected)))
+            }
+            (Some(Value::Object(actual)), Some(Value::Object(expected))) => {
+                for (name, expected) in expected {
+                    self.descend(
+                        Segment::Field(name.clone()),
+                        actual.get(name),
+                        Some(expected),
+                    );
+                }
+                if self.mode == JsonMode::Equal {
+                    for (name, actual) in actual {
+                        if !expected.contains_key(name) {
+                            self.descend(Segment::Field(name.clone()), Some(actual), None);
+                        }
+                    }
+                }
+                None
+            }
+            (Some(Value::Array(actual)), Some(Value::Array(expected))) => {
+                let len = match self.mode {
+                    JsonMode::Equal => actual.len().max(expected.len()),
+                    JsonMode::Includes => expected.len(),
+                };
+                for index in 0..len {
+                    self.descend(
+                        Segment::Index(index),
+                        actual.get(index),
+                        expected.get(index),
+                    );
+                }
+                None
+            }
+            (Some(Value::Number(a)), Some(Value::Number(e)))
+                if a == e || (a.as_f64().is_some() && a.as_f64() == e.as_f64()) =>
+            {
+                None
+            }
+            (Some(actual), Some(expected)) if actual == expected => None,
+            (Some(actual), Some(expected)) => Some(format!(
+                "{}: expected {}, found {}",
+                path,
+                render(expected),
+                render(actual)
+            )),
+        };
+        self.differences.extend(difference);
+    }
+
+    fn descend(&mut self, segment: Segment, actual: Option<&Value>, expected: Option<&Value>) {
+        self.path.push(segment);
+        self.compare(actual, expected);
+        self.path.pop();
+    }
+}
+
+/// Renders a value compactly, cut short if it is long.
+fn render(value: &Value) -> String {
+    let rendered = value.to_string();
+    if rendered.chars().count() <= MAX_VALUE_WIDTH {
+        return rendered;
+    }
+    let mut cut: String = rendered.chars().take(MAX_VALUE_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::failure::tests::message_of;
+
+    #[test]
+    fn equal_values_ignore_key_order_and_number_forms() {
+        let actual = json!({"name": "Ada", "id": 1, "tags": ["a", "b"]});
+        crate::assert_json_eq!(
+            actual,
+            json!({"tags": ["a", "b"], "id": 1.0, "name": "Ada"})
+        );
+    }
+
+    #[test]
+    fn differences_are_listed_with_their_paths() {
+        let actual = json!({"name": "Ada", "id": 1, "tags": ["a"], "display name": "A"});
+        let expected = json!({"name": "Grace", "id": 1, "tags": ["a", "b"], "admin": false});
+        assert_eq!(
+            message_of(|| crate::assert_json_eq!(actual, expected)),
+            "assertion `assert_json_eq!(actual, expected)` failed: 4 differences\n  \
+             $.name: expected \"Grace\", found \"Ada\"\n  \
+             $.tags[1]: missing, expected \"b\"\n  \
+             $.admin: missing, expected false\n  \
+             $[\"display name\"]: unexpected \"A\""
+        );
+    }
+
+    #[test]
+    fn inclusion_allows_more_fields_and_later_elements() {
+        let actual = json!({"status": "ok", "items": [{"id": 1, "tags": []}, {"id": 2}]});
+        crate::assert_json_includes!(actual, json!({"items": [{"id": 1}]}));
+        let expected = json!({"items": [{"id": 2}]});
+        assert_eq!(
+            message_of(|| crate::assert_json_includes!(actual, expected)),
+            "assertion `assert_json_includes!(actual, expected)` failed: 1 difference\n  \
+             $.items[0].id: expected 2, found 1"
+        );
+    }
+
+    #[test]
+    fn ignored_paths_are_left_out() {
+        let actual = json!({"id": 7, "at": "now", "user": {"token": "x", "name": "Ada"}});
+        let expected = json!({"id": 7, "user": {"name": "Ada"}});
+        crate::assert_json_eq!(actual, expected, ignore = ["$.at", "$..token"]);
+        crate::assert_json_eq!(actual, json!({"id": 7}), ignore = ["$.at", "$.user"]);
+        crate::assert_json_eq!(actual, json!({}), ignore = ["$.*"]);
+        let list = json!([1, 2, 3]);
+        crate::assert_json_eq!(list, json!([1, 0, 3]), ignore = ["$[1]"]);
+    }
+
+    #[test]
+    fn invalid_paths_are_reported() {
+        let actual = json!({});
+        assert_eq!(
+            message_of(|| crate::assert_json_eq!(actual, actual, ignore = ["at"])),
+            "invalid path \"at\" passed to `assert_json_eq!(actual, actual, ignore = [\"at\"])`: \
+             paths start with `$`"
+        );
+        for (path, error) in [
+            ("$.", "expected a field name or `*` after `.`"),
+            ("$[0", "unclosed `[`"),
+            ("$[x]", "expected an index, `*`, or a quoted name in `[x]`"),
+            ("$x", "expected `.` or `[` before `x`"),
+        ] {
+            assert_eq!(Pattern::parse(path).unwrap_err(), error, "{}", path);
+        }
+    }
+
+    #[test]
+    fn long_values_are_cut() {
+        let long = Value::String("x".repeat(100));
+        let rendered = render(&long);
+        assert_eq!(rendered.chars().count(), MAX_VALUE_WIDTH);
+        assert!(rendered.ends_with('…'));
+    }
+}
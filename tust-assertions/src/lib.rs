@@ -1,4 +1,193 @@
-// Rich assertion library for tust test framework
-// Provides enhanced assertions with colored diffs, pattern matching, etc.
+//! Rich assertion library for the tust test framework.
+//!
+//! The macros in this crate replace `assert_eq!` and friends with versions
+//! whose failure messages include a colored, line-by-line diff of the
+//! pretty-printed (`{:#?}`) values, which keeps large structs readable.
+//!
+//! Assertions come in two families:
+//!
+//! - [`expect_that!`] is fatal: a failure panics and ends the test.
+//! - [`check!`], [`check_eq!`], and [`check_ne!`] are soft: a failure is
+//!   recorded and the test keeps running, so one run reports every failed
+//!   check. Outside of a test run by tust they panic like fatal assertions.
+//!
+//! ```
+//! use tust_assertions::{check_eq, expect_that};
+//!
+//! let numbers = vec![1, 2, 3];
+//! check_eq!(numbers.len(), 3);
+//! expect_that!(numbers[0] != numbers[1]);
+//! ```
+//!
+//! For conditions that read better as a sentence, [`expect!`] checks a value
+//! against composable [`matchers`]:
+//!
+//! ```
+//! use tust_assertions::expect;
+//! use tust_assertions::matchers::{be_ok, contain_substring};
+//!
+//! let greeting: Result<&str, ()> = Ok("hello, world");
+//! expect!(greeting).to(be_ok(contain_substring("world")));
+//! ```
+//!
+//! [`assert_ok!`], [`assert_err!`], [`assert_some!`], and [`assert_none!`]
+//! check which variant a `Result` or `Option` is, and evaluate to what it
+//! holds; [`assert_matches!`] checks a value against a pattern, and
+//! [`assert_panics!`] that a closure panics. These are all fatal.
+//!
+//! ```
+//! use tust_assertions::{assert_matches, assert_ok, assert_panics};
+//!
+//! let port: u16 = assert_ok!("8080".parse());
+//! assert_matches!(port, 1024..=65535);
+//! assert_panics!(|| port.checked_add(u16::MAX).unwrap());
+//! ```
+//!
+//! [`assert_ready!`] and [`assert_pending!`] poll a future once, and check
+//! whether it is ready; in `async` tests, [`assert_stream_yields!`] checks
+//! every item of a stream, and [`assert_completes_within!`] that a future
+//! completes in time, with any executor.
+//!
+//! [`assert_no_alloc!`] and [`assert_allocates_at_most!`] check that a
+//! closure does not allocate, or allocates at most so many bytes, on the
+//! calling thread; they need the tracking allocator of
+//! [`tust_runtime::alloc`] installed as the global allocator.
+//!
+//! [`assert_approx_eq!`] compares floating-point numbers, and slices, arrays,
+//! and other containers of them, within an absolute, relative, or ULP
+//! tolerance; see [`approx`].
+//!
+//! [`assert_same_elements!`], [`assert_contains_all!`], [`assert_sorted!`],
+//! [`assert_sorted_by!`], and [`assert_map_eq!`] compare collections
+//! regardless of order where order does not matter, and name exactly the
+//! elements or entries that differ. [`assert_all!`] and [`assert_any!`]
+//! check a predicate over the elements of any iterator as they are produced,
+//! stopping at the element that decides.
+//!
+//! [`expect_messages!`] receives messages from a channel and compares them,
+//! in order or in any order, against those expected; see [`channel`].
+//!
+//! [`assert_str_eq!`] compares strings, showing where they first differ and,
+//! for multiline strings, a diff; [`assert_contains!`],
+//! [`assert_starts_with!`], [`assert_ends_with!`], and
+//! [`assert_matches_regex!`] check parts of a string. All but the last accept
+//! `normalize_whitespace` and `ignore_case` options.
+//!
+//! [`assert_json_eq!`] and [`assert_json_includes!`] compare values as JSON,
+//! ignoring key order and optionally volatile paths; see [`json`].
+//!
+//! Other crates can define assertions of their own, reported in the same
+//! format, by implementing [`Assertion`] and describing failures with a
+//! [`FailureBuilder`]; see [`assertion`].
+//!
+//! [`assert_snapshot!`] and [`assert_debug_snapshot!`] compare a rendered
+//! value against a file stored in `tests/snapshots/`,
+//! [`assert_binary_snapshot!`] bytes, and [`assert_image_snapshot!`] PNG
+//! images, pixel by pixel; and
+//! [`assert_matches_file!`] against a golden file at a path of the test's
+//! choosing.
+//!
+//! # Without the standard library
+//!
+//! With its default `std` feature turned off, the crate is `#![no_std]` and
+//! keeps only the core assertions: [`expect_that!`], [`check!`],
+//! [`check_eq!`], [`check_ne!`], [`assert_ok!`], [`assert_err!`],
+//! [`assert_some!`], [`assert_none!`], [`assert_matches!`], and
+//! [`assert_approx_eq!`] with [`approx`]. Tests running on an embedded
+//! target can then share their assertions with those running on the host:
+//!
+//! ```toml
+//! [dependencies]
+//! tust-assertions = { version = "0.1", default-features = false }
+//! ```
+//!
+//! A failure panics with the same message as it would under tust, but
+//! without a diff of large values, and soft assertions panic like fatal
+//! ones. The `alloc` feature adds the approximate equality of `Vec`s and
+//! the line explaining how approximately equal values differ, for
+//! [`ApproxEq`](approx::ApproxEq) implementations that need to allocate to
+//! describe it.
 
-// TODO: Implement assertion macros
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod alloc;
+pub mod approx;
+#[cfg(feature = "std")]
+pub mod assertion;
+#[cfg(feature = "std")]
+pub mod channel;
+#[cfg(feature = "std")]
+mod collections;
+#[cfg(feature = "std")]
+mod expectation;
+mod failure;
+#[cfg(feature = "std")]
+mod future;
+#[cfg(feature = "std")]
+mod golden;
+#[cfg(feature = "std")]
+pub mod json;
+mod macros;
+#[cfg(feature = "std")]
+pub mod matchers;
+#[cfg(feature = "std")]
+pub mod mock;
+#[cfg(feature = "std")]
+mod panics;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod strings;
+
+#[cfg(feature = "std")]
+pub use assertion::{Assertion, FailureBuilder};
+#[cfg(feature = "std")]
+pub use expectation::Expectation;
+#[cfg(feature = "std")]
+pub use tust_runtime::diff;
+
+/// Items used by the exported macros. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "std")]
+    pub use tust_runtime::golden::GoldenAssertion;
+    #[cfg(feature = "std")]
+    pub use tust_runtime::snapshot::{BinarySnapshotAssertion, ImageOptions, SnapshotAssertion};
+
+    #[cfg(feature = "std")]
+    pub use crate::alloc::{assert_allocates_at_most, assert_no_alloc};
+    #[cfg(feature = "std")]
+    pub use crate::assertion::run as assert_that;
+    #[cfg(feature = "std")]
+    pub use crate::channel::{expect_messages, DEFAULT_TIMEOUT as MESSAGE_TIMEOUT};
+    #[cfg(feature = "std")]
+    pub use crate::collections::{
+        assert_all, assert_any, assert_contains_all, assert_map_eq, assert_same_elements,
+        assert_sorted_by, partial_order,
+    };
+    pub use crate::failure::{
+        approx_failed, comparison_failed, condition_failed, variant_mismatch, Mode,
+    };
+    #[cfg(feature = "std")]
+    pub use crate::future::{
+        assert_pending, assert_ready, assert_stream_yields, collect as collect_stream,
+        completes_within_failed, within,
+    };
+    #[cfg(feature = "std")]
+    pub use crate::golden::assert_matches_file;
+    #[cfg(feature = "std")]
+    pub use crate::json::{assert_json, JsonMode};
+    #[cfg(feature = "std")]
+    pub use crate::panics::assert_panics;
+    #[cfg(feature = "std")]
+    pub use crate::snapshot::{assert_binary_snapshot, assert_snapshot};
+    #[cfg(feature = "std")]
+    pub use crate::strings::{
+        assert_contains, assert_ends_with, assert_matches_regex, assert_starts_with, assert_str_eq,
+        StrComparison,
+    };
+}
@@ -0,0 +1,1533 @@
+//! Equality and comparison assertions, fatal and soft.
+
+/// Checks that two expressions are equal, recording a structural diff of
+/// their `Debug` representations as a soft failure if they are not.
+///
+/// The test continues after a failed check and fails once it finishes.
+/// Accepts an optional format string and arguments, like `assert_eq!`.
+///
+/// ```
+/// # use tust_assertions::check_eq;
+/// check_eq!(vec![1, 2], vec![1, 2]);
+/// check_eq!(1 + 1, 2, "arithmetic is broken");
+/// ```
+#[macro_export]
+macro_rules! check_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::__private::comparison_failed(
+                        $crate::__private::Mode::Soft,
+                        ::core::concat!(
+                            "check_eq!(",
+                            ::core::stringify!($left),
+                            ", ",
+                            ::core::stringify!($right),
+                            ")"
+                        ),
+                        left,
+                        right,
+                        ::core::option::Option::None,
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($message:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::__private::comparison_failed(
+                        $crate::__private::Mode::Soft,
+                        ::core::concat!(
+                            "check_eq!(",
+                            ::core::stringify!($left),
+                            ", ",
+                            ::core::stringify!($right),
+                            ")"
+                        ),
+                        left,
+                        right,
+                        ::core::option::Option::Some(::core::format_args!($($message)+)),
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Checks that two expressions are not equal, recording a soft failure if
+/// they are.
+///
+/// Accepts an optional format string and arguments, like `assert_ne!`.
+#[macro_export]
+macro_rules! check_ne {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left == *right {
+                    $crate::__private::comparison_failed(
+                        $crate::__private::Mode::Soft,
+                        ::core::concat!(
+                            "check_ne!(",
+                            ::core::stringify!($left),
+                            ", ",
+                            ::core::stringify!($right),
+                            ")"
+                        ),
+                        left,
+                        right,
+                        ::core::option::Option::None,
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($message:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if *left == *right {
+                    $crate::__private::comparison_failed(
+                        $crate::__private::Mode::Soft,
+                        ::core::concat!(
+                            "check_ne!(",
+                            ::core::stringify!($left),
+                            ", ",
+                            ::core::stringify!($right),
+                            ")"
+                        ),
+                        left,
+                        right,
+                        ::core::option::Option::Some(::core::format_args!($($message)+)),
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Checks a condition written as a plain expression, recording a soft
+/// failure if it does not hold.
+///
+/// Accepts the same forms as [`expect_that!`]: a top-level comparison shows
+/// both operands on failure.
+///
+/// ```
+/// # use tust_assertions::check;
+/// let items = ["a", "b"];
+/// check!(items.len() == 2);
+/// check!(items.len() <= 3, "too many items");
+/// check!(items.contains(&"a"), "missing {}", "a");
+/// ```
+#[macro_export]
+macro_rules! check {
+    ($($tokens:tt)+) => {
+        $crate::__assert_expr!(@left Soft [] $($tokens)+)
+    };
+}
+
+/// Asserts a condition written as a plain expression, ending the test if it
+/// does not hold.
+///
+/// When the top-level operator is a comparison (`==`, `!=`, `<`, `<=`, `>`,
+/// or `>=`), both operands are evaluated once, and a failure shows the
+/// expression as written with the value of each operand, as a structural
+/// diff if they are large. Any other expression must evaluate to `bool`;
+/// one joining comparisons with `&&` or `||` is checked as a whole. An
+/// optional format string and arguments may follow after a comma.
+///
+/// ```
+/// # use tust_assertions::expect_that;
+/// let name = "tust";
+/// expect_that!(name.len() == 4);
+/// expect_that!(name != "libtest", "unexpected name {}", name);
+/// expect_that!(name.len() < 10);
+/// expect_that!(name.starts_with('t'));
+/// ```
+///
+/// The failure of `expect_that!(name.len() > 4)` reads:
+///
+/// ```text
+/// assertion `name.len() > 4` failed
+///   left: 4
+///  right: 4
+/// ```
+///
+/// The operands are split at the first top-level comparison token, so
+/// closures containing comparisons must be parenthesized.
+#[macro_export]
+macro_rules! expect_that {
+    ($($tokens:tt)+) => {
+        $crate::__assert_expr!(@left Fatal [] $($tokens)+)
+    };
+}
+
+/// Splits an asserted expression at its top-level comparison operator and
+/// at the comma introducing the message, then reports failures in `$mode`.
+///
+/// Generic arguments written with `::<...>` and qualified paths `<T as
+/// Trait>` are skipped over by `@generic`, so their `<`, `>`, and commas
+/// split nothing. `$cont` is the state to return to afterwards.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_expr {
+    (@left $mode:ident [] < $($rest:tt)+) => {
+        $crate::__assert_expr!(@generic [@left $mode] [<] [<] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)*] :: < $($rest:tt)+) => {
+        $crate::__assert_expr!(@generic [@left $mode] [$($left)* :: <] [<] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] == $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [==] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] != $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [!=] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] < $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [<] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] <= $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [<=] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] > $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [>] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] >= $($rest:tt)+) => {
+        $crate::__assert_expr!(@right $mode [>=] [$($left)+] [] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] && $($rest:tt)+) => {
+        $crate::__assert_expr!(@cond $mode [$($left)+ &&] $($rest)+)
+    };
+    (@left $mode:ident [$($left:tt)+] || $($rest:tt)+) => {
+        $crate::__assert_expr!(@cond $mode [$($left)+ ||] $($rest)+)
+    };
+    (@left $mode:ident [$($cond:tt)+] , $($message:tt)+) => {
+        $crate::__assert_expr!(@cond $mode [$($cond)+] , $($message)+)
+    };
+    (@left $mode:ident [$($cond:tt)+]) => {
+        $crate::__assert_expr!(@cond $mode [$($cond)+])
+    };
+    (@left $mode:ident [$($left:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__assert_expr!(@left $mode [$($left)* $next] $($rest)*)
+    };
+
+    (@cond $mode:ident [$($cond:tt)*] :: < $($rest:tt)+) => {
+        $crate::__assert_expr!(@generic [@cond $mode] [$($cond)* :: <] [<] $($rest)+)
+    };
+    (@cond $mode:ident [$($cond:tt)+] , $($message:tt)+) => {
+        if !($($cond)+) {
+            $crate::__private::condition_failed(
+                $crate::__private::Mode::$mode,
+                ::core::stringify!($($cond)+),
+                ::core::option::Option::Some(::core::format_args!($($message)+)),
+            );
+        }
+    };
+    (@cond $mode:ident [$($cond:tt)+]) => {
+        if !($($cond)+) {
+            $crate::__private::condition_failed(
+                $crate::__private::Mode::$mode,
+                ::core::stringify!($($cond)+),
+                ::core::option::Option::None,
+            );
+        }
+    };
+    (@cond $mode:ident [$($cond:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__assert_expr!(@cond $mode [$($cond)* $next] $($rest)*)
+    };
+
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)*] :: < $($rest:tt)+) => {
+        $crate::__assert_expr!(
+            @generic [@right $mode [$op] [$($left)+]] [$($right)* :: <] [<] $($rest)+
+        )
+    };
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)+] && $($rest:tt)+) => {
+        $crate::__assert_expr!(@cond $mode [$($left)+ $op $($right)+ &&] $($rest)+)
+    };
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)+] || $($rest:tt)+) => {
+        $crate::__assert_expr!(@cond $mode [$($left)+ $op $($right)+ ||] $($rest)+)
+    };
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)+] , $($message:tt)+) => {
+        $crate::__assert_expr!(
+            @compare $mode $op [$($left)+] [$($right)+]
+            ::core::option::Option::Some(::core::format_args!($($message)+))
+        )
+    };
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)+]) => {
+        $crate::__assert_expr!(
+            @compare $mode $op [$($left)+] [$($right)+] ::core::option::Option::None
+        )
+    };
+    (@right $mode:ident [$op:tt] [$($left:tt)+] [$($right:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__assert_expr!(@right $mode [$op] [$($left)+] [$($right)* $next] $($rest)*)
+    };
+
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [<] > $($rest:tt)*) => {
+        $crate::__assert_expr!($($cont)* [$($acc)* >] $($rest)*)
+    };
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [< $($depth:tt)+] > $($rest:tt)*) => {
+        $crate::__assert_expr!(@generic [$($cont)*] [$($acc)* >] [$($depth)+] $($rest)*)
+    };
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [< <] >> $($rest:tt)*) => {
+        $crate::__assert_expr!($($cont)* [$($acc)* >>] $($rest)*)
+    };
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [< < $($depth:tt)+] >> $($rest:tt)*) => {
+        $crate::__assert_expr!(@generic [$($cont)*] [$($acc)* >>] [$($depth)+] $($rest)*)
+    };
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [$($depth:tt)+] < $($rest:tt)*) => {
+        $crate::__assert_expr!(@generic [$($cont)*] [$($acc)* <] [< $($depth)+] $($rest)*)
+    };
+    (@generic [$($cont:tt)*] [$($acc:tt)*] [$($depth:tt)+] $next:tt $($rest:tt)*) => {
+        $crate::__assert_expr!(@generic [$($cont)*] [$($acc)* $next] [$($depth)+] $($rest)*)
+    };
+
+    (@compare $mode:ident $op:tt [$($left:tt)+] [$($right:tt)+] $message:expr) => {
+        match (&($($left)+), &($($right)+)) {
+            (left, right) => {
+                if !(*left $op *right) {
+                    $crate::__private::comparison_failed(
+                        $crate::__private::Mode::$mode,
+                        ::core::stringify!($($left)+ $op $($right)+),
+                        left,
+                        right,
+                        $message,
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Starts an expectation on a value, to be checked with a
+/// [matcher](crate::matchers).
+///
+/// The value is borrowed, not moved. A failed expectation ends the test.
+///
+/// ```
+/// use tust_assertions::expect;
+/// use tust_assertions::matchers::*;
+///
+/// expect!(7).to(be_greater_than(5));
+/// expect!(vec![1, 2, 3]).to(both(contain(2), have_len(3)));
+/// expect!("tust").not_to(start_with("lib"));
+/// expect!(Some(0.1 + 0.2)).to(be_some(be_near(0.3, 1e-9)));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! expect {
+    ($actual:expr $(,)?) => {
+        $crate::Expectation::new(&$actual, ::core::stringify!($actual))
+    };
+}
+
+/// Runs an [`Assertion`](crate::Assertion) on a value, ending the test with
+/// the failure it describes.
+///
+/// The value is borrowed, not moved. See [`assertion`](crate::assertion) for
+/// writing assertions.
+///
+/// ```
+/// use tust_assertions::assert_that;
+/// use tust_assertions::assertion::{Assertion, FailureBuilder};
+///
+/// struct IsSorted;
+///
+/// impl Assertion<[i32]> for IsSorted {
+///     fn check(&self, list: &[i32], failure: FailureBuilder) -> Result<(), FailureBuilder> {
+///         match list.windows(2).position(|pair| pair[0] > pair[1]) {
+///             None => Ok(()),
+///             Some(index) => Err(failure
+///                 .reason(format!("the elements at {} and {} are out of order", index, index + 1))
+///                 .value("list", list)),
+///         }
+///     }
+/// }
+///
+/// assert_that!(*vec![1, 2, 3], IsSorted);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_that {
+    ($actual:expr, $assertion:expr $(,)?) => {
+        $crate::__private::assert_that(
+            $crate::__private::Mode::Fatal,
+            ::core::concat!(
+                "assert_that!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($assertion),
+                ")"
+            ),
+            &$actual,
+            &$assertion,
+        )
+    };
+}
+
+/// Like [`assert_that!`], but soft: a failure is recorded and the test keeps
+/// running, like a failed [`check!`].
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! check_that {
+    ($actual:expr, $assertion:expr $(,)?) => {
+        $crate::__private::assert_that(
+            $crate::__private::Mode::Soft,
+            ::core::concat!(
+                "check_that!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($assertion),
+                ")"
+            ),
+            &$actual,
+            &$assertion,
+        )
+    };
+}
+
+/// Asserts that a `Result` is `Ok` and evaluates to the value inside it,
+/// ending the test with the error otherwise.
+///
+/// Accepts an optional format string and arguments, like `assert!`.
+///
+/// ```
+/// # use tust_assertions::assert_ok;
+/// let port: u16 = assert_ok!("8080".parse::<u16>());
+/// assert_eq!(port, 8080);
+/// ```
+#[macro_export]
+macro_rules! assert_ok {
+    ($result:expr $(,)?) => {
+        $crate::__assert_variant!(assert_ok, $result, ::core::option::Option::None)
+    };
+    ($result:expr, $($message:tt)+) => {
+        $crate::__assert_variant!(
+            assert_ok,
+            $result,
+            ::core::option::Option::Some(::core::format_args!($($message)+))
+        )
+    };
+}
+
+/// Asserts that a `Result` is `Err` and evaluates to the error inside it,
+/// ending the test with the value otherwise.
+///
+/// ```
+/// # use tust_assertions::assert_err;
+/// let error = assert_err!("port".parse::<u16>());
+/// assert_eq!(error.to_string(), "invalid digit found in string");
+/// ```
+#[macro_export]
+macro_rules! assert_err {
+    ($result:expr $(,)?) => {
+        $crate::__assert_variant!(assert_err, $result, ::core::option::Option::None)
+    };
+    ($result:expr, $($message:tt)+) => {
+        $crate::__assert_variant!(
+            assert_err,
+            $result,
+            ::core::option::Option::Some(::core::format_args!($($message)+))
+        )
+    };
+}
+
+/// Asserts that an `Option` is `Some` and evaluates to the value inside it,
+/// ending the test otherwise.
+///
+/// ```
+/// # use tust_assertions::assert_some;
+/// let first = assert_some!([3, 1, 2].iter().max());
+/// assert_eq!(*first, 3);
+/// ```
+#[macro_export]
+macro_rules! assert_some {
+    ($option:expr $(,)?) => {
+        $crate::__assert_variant!(assert_some, $option, ::core::option::Option::None)
+    };
+    ($option:expr, $($message:tt)+) => {
+        $crate::__assert_variant!(
+            assert_some,
+            $option,
+            ::core::option::Option::Some(::core::format_args!($($message)+))
+        )
+    };
+}
+
+/// Asserts that an `Option` is `None`, ending the test with the value
+/// otherwise.
+///
+/// ```
+/// # use tust_assertions::assert_none;
+/// assert_none!("tust".find('x'));
+/// ```
+#[macro_export]
+macro_rules! assert_none {
+    ($option:expr $(,)?) => {
+        $crate::__assert_variant!(assert_none, $option, ::core::option::Option::None)
+    };
+    ($option:expr, $($message:tt)+) => {
+        $crate::__assert_variant!(
+            assert_none,
+            $option,
+            ::core::option::Option::Some(::core::format_args!($($message)+))
+        )
+    };
+}
+
+/// Matches a `Result` or `Option` against the variant an assertion expects,
+/// evaluating to its contents, and reports any other variant.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_variant {
+    (@fail $name:ident, $value:expr, $expected:literal, $found:expr, $message:expr) => {
+        $crate::__private::variant_mismatch(
+            ::core::concat!(::core::stringify!($name), "!(", ::core::stringify!($value), ")"),
+            $expected,
+            $found,
+            $message,
+        )
+    };
+    (assert_ok, $value:expr, $message:expr) => {
+        match $value {
+            ::core::result::Result::Ok(value) => value,
+            ::core::result::Result::Err(error) => $crate::__assert_variant!(
+                @fail assert_ok, $value, "Ok(_)",
+                &::core::result::Result::<(), _>::Err(error), $message
+            ),
+        }
+    };
+    (assert_err, $value:expr, $message:expr) => {
+        match $value {
+            ::core::result::Result::Err(error) => error,
+            ::core::result::Result::Ok(value) => $crate::__assert_variant!(
+                @fail assert_err, $value, "Err(_)",
+                &::core::result::Result::<_, ()>::Ok(value), $message
+            ),
+        }
+    };
+    (assert_some, $value:expr, $message:expr) => {
+        match $value {
+            ::core::option::Option::Some(value) => value,
+            ::core::option::Option::None => $crate::__assert_variant!(
+                @fail assert_some, $value, "Some(_)",
+                &::core::option::Option::<()>::None, $message
+            ),
+        }
+    };
+    (assert_none, $value:expr, $message:expr) => {
+        match $value {
+            ::core::option::Option::None => {}
+            ::core::option::Option::Some(value) => $crate::__assert_variant!(
+                @fail assert_none, $value, "None",
+                &::core::option::Option::Some(value), $message
+            ),
+        }
+    };
+}
+
+/// Asserts that a value matches a pattern, with an optional `if` guard,
+/// ending the test with the value otherwise.
+///
+/// Like a `match`, the value is moved unless it is a place the pattern only
+/// borrows from. Accepts an optional format string and arguments after the
+/// pattern.
+///
+/// ```
+/// # use tust_assertions::assert_matches;
+/// # #[derive(Debug)]
+/// enum Token { Number(u32), Word(&'static str) }
+/// assert_matches!(Token::Number(7), Token::Number(n) if n > 5);
+/// assert_matches!(Token::Word("fn"), Token::Word("fn" | "let"), "not a keyword");
+/// ```
+#[macro_export]
+macro_rules! assert_matches {
+    ($value:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $value {
+            $pattern $(if $guard)? => {}
+            ref value => $crate::__private::variant_mismatch(
+                ::core::concat!(
+                    "assert_matches!(",
+                    ::core::stringify!($value),
+                    ", ",
+                    ::core::stringify!($pattern $(if $guard)?),
+                    ")"
+                ),
+                ::core::stringify!($pattern $(if $guard)?),
+                value,
+                ::core::option::Option::None,
+            ),
+        }
+    };
+    ($value:expr, $pattern:pat $(if $guard:expr)?, $($message:tt)+) => {
+        match $value {
+            $pattern $(if $guard)? => {}
+            ref value => $crate::__private::variant_mismatch(
+                ::core::concat!(
+                    "assert_matches!(",
+                    ::core::stringify!($value),
+                    ", ",
+                    ::core::stringify!($pattern $(if $guard)?),
+                    ")"
+                ),
+                ::core::stringify!($pattern $(if $guard)?),
+                value,
+                ::core::option::Option::Some(::core::format_args!($($message)+)),
+            ),
+        }
+    };
+}
+
+/// Asserts that calling a closure panics, and evaluates to the panic
+/// message. With `contains = "..."`, the message must also contain the given
+/// text.
+///
+/// The expected panic is not printed, and does not fail the test.
+///
+/// ```
+/// # use tust_assertions::assert_panics;
+/// let message = assert_panics!(|| vec![1, 2, 3][7]);
+/// assert!(message.contains("out of bounds"));
+/// assert_panics!(|| u8::try_from(300).unwrap(), contains = "TryFromIntError");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_panics {
+    ($closure:expr $(,)?) => {
+        $crate::__private::assert_panics(
+            ::core::concat!("assert_panics!(", ::core::stringify!($closure), ")"),
+            $closure,
+            ::core::option::Option::None,
+        )
+    };
+    ($closure:expr, contains = $text:expr $(,)?) => {
+        $crate::__private::assert_panics(
+            ::core::concat!(
+                "assert_panics!(",
+                ::core::stringify!($closure),
+                ", contains = ",
+                ::core::stringify!($text),
+                ")"
+            ),
+            $closure,
+            ::core::option::Option::Some($text),
+        )
+    };
+}
+
+/// Asserts that a future is ready when polled once, and evaluates to its
+/// output.
+///
+/// The future is polled in place, so `&mut future` of an `Unpin` or pinned
+/// future can be polled again later.
+///
+/// ```
+/// # use tust_assertions::assert_ready;
+/// let answer = assert_ready!(std::future::ready(42));
+/// assert_eq!(answer, 42);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_ready {
+    ($future:expr $(,)?) => {
+        $crate::__private::assert_ready(
+            ::core::concat!("assert_ready!(", ::core::stringify!($future), ")"),
+            $future,
+        )
+    };
+}
+
+/// Asserts that a future is pending when polled once.
+///
+/// The waker it is polled with does nothing, so what would wake the future
+/// does not reach an executor; pass `&mut future` to poll it again later with
+/// [`assert_ready!`].
+///
+/// ```
+/// # use tust_assertions::assert_pending;
+/// assert_pending!(std::future::pending::<()>());
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_pending {
+    ($future:expr $(,)?) => {
+        $crate::__private::assert_pending(
+            ::core::concat!("assert_pending!(", ::core::stringify!($future), ")"),
+            $future,
+        )
+    };
+}
+
+/// Asserts that a stream yields exactly the given items, in order, and then
+/// ends.
+///
+/// The macro awaits the stream, so it is used in `async` code, such as an
+/// `async` test; the stream implements `futures_core::Stream`, as those of
+/// `futures` and `tokio-stream` do, and its items `PartialEq` and `Debug`.
+///
+/// ```ignore
+/// #[tust::test]
+/// async fn pages_through_the_results() {
+///     assert_stream_yields!(client.pages("rust"), [page(1), page(2)]);
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_stream_yields {
+    ($stream:expr, $expected:expr $(,)?) => {
+        match $crate::__private::collect_stream($stream).await {
+            items => $crate::__private::assert_stream_yields(
+                ::core::concat!(
+                    "assert_stream_yields!(",
+                    ::core::stringify!($stream),
+                    ", ",
+                    ::core::stringify!($expected),
+                    ")"
+                ),
+                &items[..],
+                &$expected[..],
+            ),
+        }
+    };
+}
+
+/// Asserts that a future completes within a `Duration`, and evaluates to its
+/// output.
+///
+/// The macro awaits the future, so it is used in `async` code, such as an
+/// `async` test. It works with any executor, timing the future on a thread
+/// of its own; a future that does not complete in time is dropped.
+///
+/// ```ignore
+/// #[tust::test]
+/// async fn answers_quickly() {
+///     let reply = assert_completes_within!(Duration::from_millis(200), client.ping());
+///     assert_eq!(reply, "pong");
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_completes_within {
+    ($timeout:expr, $future:expr $(,)?) => {
+        match $timeout {
+            timeout => match $crate::__private::within(timeout, $future).await {
+                ::core::option::Option::Some(output) => output,
+                ::core::option::Option::None => $crate::__private::completes_within_failed(
+                    ::core::concat!(
+                        "assert_completes_within!(",
+                        ::core::stringify!($timeout),
+                        ", ",
+                        ::core::stringify!($future),
+                        ")"
+                    ),
+                    timeout,
+                ),
+            },
+        }
+    };
+}
+
+/// Asserts that calling a closure does not allocate on the heap, and
+/// evaluates to what the closure returns.
+///
+/// Only allocations made by the calling thread count, and the test target
+/// must install the tracking allocator:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: tust::alloc::TrackingAllocator = tust::alloc::TrackingAllocator::new();
+///
+/// let buffer = vec![0u8; 1024];
+/// let sum: u32 = assert_no_alloc!(|| buffer.iter().map(|&b| u32::from(b)).sum());
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_no_alloc {
+    ($closure:expr $(,)?) => {
+        $crate::__private::assert_no_alloc(
+            ::core::concat!("assert_no_alloc!(", ::core::stringify!($closure), ")"),
+            $closure,
+        )
+    };
+}
+
+/// Asserts that calling a closure allocates at most the given number of
+/// bytes on the heap, and evaluates to what the closure returns.
+///
+/// Bytes freed within the closure still count, so the limit bounds the
+/// churn rather than what the closure keeps. Like with [`assert_no_alloc!`],
+/// only the calling thread counts, and the tracking allocator must be
+/// installed.
+///
+/// ```ignore
+/// let line = assert_allocates_at_most!(64, || format!("{}: {}", key, value));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_allocates_at_most {
+    ($bytes:expr, $closure:expr $(,)?) => {
+        $crate::__private::assert_allocates_at_most(
+            ::core::concat!(
+                "assert_allocates_at_most!(",
+                ::core::stringify!($bytes),
+                ", ",
+                ::core::stringify!($closure),
+                ")"
+            ),
+            $bytes,
+            $closure,
+        )
+    };
+}
+
+/// Asserts that two floating-point numbers, or containers of them, are
+/// approximately equal, ending the test with both values otherwise.
+///
+/// The tolerance is given as `epsilon = ABSOLUTE`, `relative = FRACTION`, or
+/// `ulps = STEPS`, or a combination of them, any of which suffices; without
+/// any, the numbers must be within 4 ULPs. Both sides are borrowed, and must
+/// implement [`ApproxEq`](crate::approx::ApproxEq) against each other.
+///
+/// ```
+/// # use tust_assertions::assert_approx_eq;
+/// assert_approx_eq!(0.1 + 0.2, 0.3);
+/// assert_approx_eq!(1.0_f32 / 3.0, 0.333, epsilon = 1e-3);
+/// assert_approx_eq!(vec![1e6, 2e-6], [1.000001e6, 2e-6], relative = 1e-6);
+/// assert_approx_eq!([1.0_f64.sqrt()], [1.0], ulps = 2, epsilon = 1e-12);
+/// ```
+#[macro_export]
+macro_rules! assert_approx_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::__assert_approx_eq!(
+            ::core::concat!(
+                "assert_approx_eq!(",
+                ::core::stringify!($left),
+                ", ",
+                ::core::stringify!($right),
+                ")"
+            ),
+            $left,
+            $right,
+            $crate::approx::Tolerance::new().ulps(4)
+        )
+    };
+    ($left:expr, $right:expr, $($bound:ident = $value:expr),+ $(,)?) => {
+        $crate::__assert_approx_eq!(
+            ::core::concat!(
+                "assert_approx_eq!(",
+                ::core::stringify!($left),
+                ", ",
+                ::core::stringify!($right),
+                $(", ", ::core::stringify!($bound), " = ", ::core::stringify!($value),)+
+                ")"
+            ),
+            $left,
+            $right,
+            $crate::approx::Tolerance::new()$(.$bound($value))+
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_approx_eq {
+    ($assertion:expr, $left:expr, $right:expr, $tolerance:expr) => {
+        match (&$left, &$right, $tolerance) {
+            (left, right, tolerance) => {
+                if !$crate::approx::ApproxEq::approx_eq(left, right, &tolerance) {
+                    $crate::__private::approx_failed($assertion, left, right, &tolerance);
+                }
+            }
+        }
+    };
+}
+
+/// Receives messages from a channel, waiting up to a timeout for them, and
+/// asserts that they are the expected ones, in order or, after
+/// `unordered:`, in any order. Evaluates to the messages received.
+///
+/// As many messages are received as are expected, and later ones stay in
+/// the channel. The timeout, for all of them together, is a second unless
+/// given with `timeout = ...`. On failure, the messages received are shown
+/// against those expected. The receiver is borrowed mutably, and is anything
+/// implementing [`channel::Receive`](crate::channel::Receive).
+///
+/// ```
+/// # use tust_assertions::expect_messages;
+/// use std::sync::mpsc;
+/// use std::time::Duration;
+///
+/// let (sender, mut receiver) = mpsc::channel();
+/// std::thread::spawn(move || {
+///     for event in ["started", "connected", "ready"] {
+///         sender.send(event).unwrap();
+///     }
+/// });
+/// expect_messages!(receiver, ["started"]);
+/// expect_messages!(receiver, unordered: ["ready", "connected"], timeout = Duration::from_secs(5));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! expect_messages {
+    ($receiver:expr, unordered: [$($message:expr),* $(,)?] $(, timeout = $timeout:expr)? $(,)?) => {
+        $crate::__private::expect_messages(
+            ::core::concat!(
+                "expect_messages!(",
+                ::core::stringify!($receiver),
+                ", unordered: [",
+                ::core::stringify!($($message),*),
+                "]",
+                $(", timeout = ", ::core::stringify!($timeout),)?
+                ")"
+            ),
+            &mut $receiver,
+            &[$($message),*],
+            true,
+            $crate::__expect_messages_timeout!($($timeout)?),
+        )
+    };
+    ($receiver:expr, [$($message:expr),* $(,)?] $(, timeout = $timeout:expr)? $(,)?) => {
+        $crate::__private::expect_messages(
+            ::core::concat!(
+                "expect_messages!(",
+                ::core::stringify!($receiver),
+                ", [",
+                ::core::stringify!($($message),*),
+                "]",
+                $(", timeout = ", ::core::stringify!($timeout),)?
+                ")"
+            ),
+            &mut $receiver,
+            &[$($message),*],
+            false,
+            $crate::__expect_messages_timeout!($($timeout)?),
+        )
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __expect_messages_timeout {
+    () => {
+        $crate::__private::MESSAGE_TIMEOUT
+    };
+    ($timeout:expr) => {
+        $timeout
+    };
+}
+
+/// Asserts that two collections have the same elements, in any order, and
+/// ends the test with the elements missing from the first and those extra in
+/// it otherwise.
+///
+/// Both collections are borrowed, and may be of different types: anything
+/// whose references iterate over its elements, with elements that compare
+/// with `==`. An element present twice in one must be present twice in the
+/// other.
+///
+/// ```
+/// # use tust_assertions::assert_same_elements;
+/// assert_same_elements!(vec![3, 1, 2], [1, 2, 3]);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_same_elements {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__private::assert_same_elements(
+            ::core::concat!(
+                "assert_same_elements!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($expected),
+                ")"
+            ),
+            &$actual,
+            &$expected,
+        )
+    };
+}
+
+/// Asserts that a collection contains every element of another, in any
+/// order, and ends the test with the elements it lacks otherwise.
+///
+/// ```
+/// # use tust_assertions::assert_contains_all;
+/// let tags = std::collections::HashSet::from(["fast", "unit", "db"]);
+/// assert_contains_all!(tags, ["unit", "fast"]);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_contains_all {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__private::assert_contains_all(
+            ::core::concat!(
+                "assert_contains_all!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($expected),
+                ")"
+            ),
+            &$actual,
+            &$expected,
+        )
+    };
+}
+
+/// Asserts that a collection's elements are in ascending order, allowing
+/// equal neighbours, and ends the test with the first pair out of order
+/// otherwise.
+///
+/// ```
+/// # use tust_assertions::assert_sorted;
+/// assert_sorted!([1, 2, 2, 5]);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_sorted {
+    ($actual:expr $(,)?) => {
+        $crate::__private::assert_sorted_by(
+            ::core::concat!("assert_sorted!(", ::core::stringify!($actual), ")"),
+            &$actual,
+            $crate::__private::partial_order,
+        )
+    };
+}
+
+/// Asserts that `compare` orders no element of a collection after the next,
+/// as for [`slice::sort_by`].
+///
+/// ```
+/// # use tust_assertions::assert_sorted_by;
+/// let words = ["framework", "tust", "is"];
+/// assert_sorted_by!(words, |a, b| b.len().cmp(&a.len()));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_sorted_by {
+    ($actual:expr, $compare:expr $(,)?) => {
+        $crate::__private::assert_sorted_by(
+            ::core::concat!(
+                "assert_sorted_by!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($compare),
+                ")"
+            ),
+            &$actual,
+            $compare,
+        )
+    };
+}
+
+/// Asserts that every element of an iterator, or of anything iterable,
+/// satisfies a predicate, and ends the test with the index and value of the
+/// first that does not otherwise. Elements are checked as the iterator
+/// produces them, and none after the first failing one, so that a long or
+/// endless iterator need not be collected.
+///
+/// ```
+/// # use tust_assertions::assert_all;
+/// assert_all!((1..1_000_000).map(|n| n * 2), |n| n % 2 == 0);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_all {
+    ($actual:expr, $predicate:expr $(,)?) => {
+        $crate::__private::assert_all(
+            ::core::concat!(
+                "assert_all!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($predicate),
+                ")"
+            ),
+            $actual,
+            $predicate,
+        )
+    };
+}
+
+/// Asserts that some element of an iterator, or of anything iterable,
+/// satisfies a predicate, and ends the test with the number of elements
+/// checked otherwise. No element after the first satisfying one is checked.
+///
+/// ```
+/// # use tust_assertions::assert_any;
+/// let words = ["tust", "runs", "tests"];
+/// assert_any!(words.iter(), |word| word.starts_with('r'));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_any {
+    ($actual:expr, $predicate:expr $(,)?) => {
+        $crate::__private::assert_any(
+            ::core::concat!(
+                "assert_any!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($predicate),
+                ")"
+            ),
+            $actual,
+            $predicate,
+        )
+    };
+}
+
+/// Asserts that two maps have the same entries, and ends the test with the
+/// keys missing from the first, the keys extra in it, and the keys whose
+/// values differ otherwise.
+///
+/// Works with any maps whose references iterate over `(&key, &value)`
+/// pairs, such as `HashMap` and `BTreeMap`, also compared with each other.
+///
+/// ```
+/// # use tust_assertions::assert_map_eq;
+/// use std::collections::{BTreeMap, HashMap};
+///
+/// let ports = HashMap::from([("http", 80), ("https", 443)]);
+/// assert_map_eq!(ports, BTreeMap::from([("https", 443), ("http", 80)]));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_map_eq {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__private::assert_map_eq(
+            ::core::concat!(
+                "assert_map_eq!(",
+                ::core::stringify!($actual),
+                ", ",
+                ::core::stringify!($expected),
+                ")"
+            ),
+            &$actual,
+            &$expected,
+        )
+    };
+}
+
+/// Asserts that two strings are equal, ending the test otherwise with the
+/// line and column at which they first differ, and a line-by-line diff if
+/// they span several lines.
+///
+/// Both arguments may be anything implementing `AsRef<str>`. Options may
+/// follow them: `normalize_whitespace` collapses runs of spaces and tabs,
+/// trims lines, and ignores blank lines at either end and `\r\n` line
+/// endings; `ignore_case` compares the lowercase forms.
+///
+/// ```
+/// # use tust_assertions::assert_str_eq;
+/// let rendered = String::from("total:   3\nitems:\tapple\n");
+/// assert_str_eq!(rendered, "total:   3\nitems:\tapple\n");
+/// assert_str_eq!(rendered, "Total: 3\nItems: apple", normalize_whitespace, ignore_case);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_str_eq {
+    ($left:expr, $right:expr $(, $option:ident)* $(,)?) => {
+        $crate::__private::assert_str_eq(
+            ::core::concat!(
+                "assert_str_eq!(",
+                ::core::stringify!($left),
+                ", ",
+                ::core::stringify!($right),
+                $(", ", ::core::stringify!($option),)*
+                ")"
+            ),
+            ::core::convert::AsRef::<str>::as_ref(&$left),
+            ::core::convert::AsRef::<str>::as_ref(&$right),
+            $crate::__private::StrComparison::new()$(.$option())*,
+        )
+    };
+}
+
+/// Asserts that a string contains a substring, ending the test with the string otherwise.
+///
+/// Both arguments may be anything implementing `AsRef<str>`. The options
+/// `normalize_whitespace` and `ignore_case` may follow, as for
+/// [`assert_str_eq!`].
+///
+/// ```
+/// # use tust_assertions::assert_contains;
+/// let log = "server listening on port 8080";
+/// assert_contains!(log, "listening");
+/// assert_contains!(log, "PORT", ignore_case);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_contains {
+    ($string:expr, $needle:expr $(, $option:ident)* $(,)?) => {
+        $crate::__private::assert_contains(
+            ::core::concat!(
+                "assert_contains!(",
+                ::core::stringify!($string),
+                ", ",
+                ::core::stringify!($needle),
+                $(", ", ::core::stringify!($option),)*
+                ")"
+            ),
+            ::core::convert::AsRef::<str>::as_ref(&$string),
+            ::core::convert::AsRef::<str>::as_ref(&$needle),
+            $crate::__private::StrComparison::new()$(.$option())*,
+        )
+    };
+}
+
+/// Asserts that a string starts with a prefix, ending the test with the string otherwise.
+///
+/// Both arguments may be anything implementing `AsRef<str>`. The options
+/// `normalize_whitespace` and `ignore_case` may follow, as for
+/// [`assert_str_eq!`].
+///
+/// ```
+/// # use tust_assertions::assert_starts_with;
+/// assert_starts_with!("GET /index.html HTTP/1.1", "GET ");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_starts_with {
+    ($string:expr, $prefix:expr $(, $option:ident)* $(,)?) => {
+        $crate::__private::assert_starts_with(
+            ::core::concat!(
+                "assert_starts_with!(",
+                ::core::stringify!($string),
+                ", ",
+                ::core::stringify!($prefix),
+                $(", ", ::core::stringify!($option),)*
+                ")"
+            ),
+            ::core::convert::AsRef::<str>::as_ref(&$string),
+            ::core::convert::AsRef::<str>::as_ref(&$prefix),
+            $crate::__private::StrComparison::new()$(.$option())*,
+        )
+    };
+}
+
+/// Asserts that a string ends with a suffix, ending the test with the string otherwise.
+///
+/// Both arguments may be anything implementing `AsRef<str>`. The options
+/// `normalize_whitespace` and `ignore_case` may follow, as for
+/// [`assert_str_eq!`].
+///
+/// ```
+/// # use tust_assertions::assert_ends_with;
+/// assert_ends_with!("report.csv", ".csv");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_ends_with {
+    ($string:expr, $suffix:expr $(, $option:ident)* $(,)?) => {
+        $crate::__private::assert_ends_with(
+            ::core::concat!(
+                "assert_ends_with!(",
+                ::core::stringify!($string),
+                ", ",
+                ::core::stringify!($suffix),
+                $(", ", ::core::stringify!($option),)*
+                ")"
+            ),
+            ::core::convert::AsRef::<str>::as_ref(&$string),
+            ::core::convert::AsRef::<str>::as_ref(&$suffix),
+            $crate::__private::StrComparison::new()$(.$option())*,
+        )
+    };
+}
+
+/// Asserts that a regular expression finds a match in a string, ending the
+/// test with the string otherwise. Anchor the pattern with `^` and `$` to
+/// match the whole string.
+///
+/// # Panics
+///
+/// Panics if the pattern is not a valid regular expression.
+///
+/// ```
+/// # use tust_assertions::assert_matches_regex;
+/// assert_matches_regex!("build 2024-06-01", r"\d{4}-\d{2}-\d{2}");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_matches_regex {
+    ($string:expr, $pattern:expr $(,)?) => {
+        $crate::__private::assert_matches_regex(
+            ::core::concat!(
+                "assert_matches_regex!(",
+                ::core::stringify!($string),
+                ", ",
+                ::core::stringify!($pattern),
+                ")"
+            ),
+            ::core::convert::AsRef::<str>::as_ref(&$string),
+            ::core::convert::AsRef::<str>::as_ref(&$pattern),
+        )
+    };
+}
+
+/// Asserts that two values are equal as JSON, ending the test with every
+/// difference and its path otherwise.
+///
+/// Both values are borrowed and converted with `serde_json`, so they may be
+/// [`Value`](crate::json::Value)s or anything implementing `Serialize`.
+/// Objects compare regardless of key order. `ignore = [...]` leaves out the
+/// values at the given paths on both sides; see [`json`](crate::json) for
+/// their syntax.
+///
+/// ```
+/// # use tust_assertions::assert_json_eq;
+/// use tust_assertions::json::json;
+///
+/// let user = json!({"name": "Ada", "id": 1, "created": 1717243200});
+/// assert_json_eq!(user, json!({"id": 1.0, "name": "Ada"}), ignore = ["$.created"]);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_json_eq {
+    ($actual:expr, $expected:expr $(, ignore = $ignore:expr)? $(,)?) => {
+        $crate::__private::assert_json(
+            $crate::__json_assertion!(assert_json_eq, $actual, $expected $(, $ignore)?),
+            &$actual,
+            &$expected,
+            $crate::__private::JsonMode::Equal,
+            &$crate::__json_ignore!($($ignore)?),
+        )
+    };
+}
+
+/// Asserts that a value includes another when both are converted to JSON:
+/// objects may have more fields than expected, and arrays more elements
+/// after the expected ones. Otherwise like [`assert_json_eq!`].
+///
+/// ```
+/// # use tust_assertions::assert_json_includes;
+/// use tust_assertions::json::json;
+///
+/// let response = json!({"status": "ok", "items": [{"id": 1, "tags": []}, {"id": 2}]});
+/// assert_json_includes!(response, json!({"items": [{"id": 1}]}));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_json_includes {
+    ($actual:expr, $expected:expr $(, ignore = $ignore:expr)? $(,)?) => {
+        $crate::__private::assert_json(
+            $crate::__json_assertion!(assert_json_includes, $actual, $expected $(, $ignore)?),
+            &$actual,
+            &$expected,
+            $crate::__private::JsonMode::Includes,
+            &$crate::__json_ignore!($($ignore)?),
+        )
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_assertion {
+    ($name:ident, $actual:expr, $expected:expr) => {
+        ::core::concat!(
+            ::core::stringify!($name),
+            "!(",
+            ::core::stringify!($actual),
+            ", ",
+            ::core::stringify!($expected),
+            ")"
+        )
+    };
+    ($name:ident, $actual:expr, $expected:expr, $ignore:expr) => {
+        ::core::concat!(
+            ::core::stringify!($name),
+            "!(",
+            ::core::stringify!($actual),
+            ", ",
+            ::core::stringify!($expected),
+            ", ignore = ",
+            ::core::stringify!($ignore),
+            ")"
+        )
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_ignore {
+    () => {
+        [] as [&str; 0]
+    };
+    ($ignore:expr) => {
+        $ignore
+    };
+}
+
+/// Compares a value's `Display` output against a snapshot stored in
+/// `tests/snapshots/`.
+///
+/// The snapshot is named after the running test unless a name is given as
+/// the first argument. A new or changed value fails the assertion and is
+/// written to a `.snap.new` file for review; see [`tust_runtime::snapshot`]
+/// for the review workflow.
+///
+/// ```no_run
+/// # use tust_assertions::assert_snapshot;
+/// # fn render() -> String { String::new() }
+/// assert_snapshot!(render());
+/// assert_snapshot!("empty_page", render());
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:literal, $value:expr $(,)?) => {
+        $crate::__snapshot!(::core::option::Option::Some($name), $value, "{}")
+    };
+    ($value:expr $(,)?) => {
+        $crate::__snapshot!(::core::option::Option::None, $value, "{}")
+    };
+}
+
+/// Like [`assert_snapshot!`], but snapshots the value's pretty-printed
+/// `Debug` output (`{:#?}`).
+///
+/// ```no_run
+/// # use tust_assertions::assert_debug_snapshot;
+/// assert_debug_snapshot!(vec![("id", 1), ("id", 2)]);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_debug_snapshot {
+    ($name:literal, $value:expr $(,)?) => {
+        $crate::__snapshot!(::core::option::Option::Some($name), $value, "{:#?}")
+    };
+    ($value:expr $(,)?) => {
+        $crate::__snapshot!(::core::option::Option::None, $value, "{:#?}")
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __snapshot {
+    ($name:expr, $value:expr, $format:literal) => {
+        $crate::__private::assert_snapshot($crate::__private::SnapshotAssertion {
+            manifest_dir: ::core::env!("CARGO_MANIFEST_DIR"),
+            source_file: ::core::file!(),
+            expression: ::core::stringify!($value),
+            name: $name,
+            contents: &::std::format!($format, $value),
+        })
+    };
+}
+
+/// Compares bytes against a snapshot stored in `tests/snapshots/`, byte for
+/// byte.
+///
+/// The value is anything that is `AsRef<[u8]>`. The snapshot's name is
+/// given with the extension of its file, or just the extension to name it
+/// after the running test; without an extension, the file ends in `.bin`.
+/// A new or changed value fails the assertion and is written next to the
+/// snapshot with `new` before the extension, such as `report.new.pdf`; see
+/// [`tust_runtime::snapshot`] for the review workflow.
+///
+/// ```no_run
+/// # use tust_assertions::assert_binary_snapshot;
+/// # fn render_pdf() -> Vec<u8> { Vec::new() }
+/// assert_binary_snapshot!("report.pdf", render_pdf());
+/// assert_binary_snapshot!(".pdf", render_pdf());
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_binary_snapshot {
+    ($name:literal, $value:expr $(,)?) => {
+        $crate::__binary_snapshot!(
+            ::core::option::Option::Some($name),
+            $value,
+            ::core::option::Option::None
+        )
+    };
+    ($value:expr $(,)?) => {
+        $crate::__binary_snapshot!(
+            ::core::option::Option::None,
+            $value,
+            ::core::option::Option::None
+        )
+    };
+}
+
+/// Compares a PNG image against a snapshot stored in `tests/snapshots/`,
+/// pixel by pixel.
+///
+/// Like [`assert_binary_snapshot!`], but the images match if their pixels
+/// do, however they are encoded, and may differ within the limits set by
+/// the [`ImageOptions`](tust_runtime::snapshot::ImageOptions) given after
+/// the value: `threshold`, how different a pixel may be, and
+/// `max_diff_pixels`, how many pixels may differ by more. A mismatch also
+/// writes a diff image highlighting the differing pixels in red, attached
+/// to the test under tust.
+///
+/// ```no_run
+/// # use tust_assertions::assert_image_snapshot;
+/// # fn screenshot() -> Vec<u8> { Vec::new() }
+/// assert_image_snapshot!(screenshot());
+/// assert_image_snapshot!("home_page", screenshot(), threshold = 0.2, max_diff_pixels = 10);
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_image_snapshot {
+    ($name:literal, $value:expr $(, $option:ident = $setting:expr)* $(,)?) => {
+        $crate::__binary_snapshot!(
+            ::core::option::Option::Some($name),
+            $value,
+            ::core::option::Option::Some(
+                $crate::__private::ImageOptions::default()$(.$option($setting))*
+            )
+        )
+    };
+    ($value:expr $(, $option:ident = $setting:expr)* $(,)?) => {
+        $crate::__binary_snapshot!(
+            ::core::option::Option::None,
+            $value,
+            ::core::option::Option::Some(
+                $crate::__private::ImageOptions::default()$(.$option($setting))*
+            )
+        )
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __binary_snapshot {
+    ($name:expr, $value:expr, $image:expr) => {
+        $crate::__private::assert_binary_snapshot($crate::__private::BinarySnapshotAssertion {
+            manifest_dir: ::core::env!("CARGO_MANIFEST_DIR"),
+            expression: ::core::stringify!($value),
+            name: $name,
+            contents: ::core::convert::AsRef::<[u8]>::as_ref(&$value),
+            image: $image,
+        })
+    };
+}
+
+/// Compares a value's `Display` output against a golden file, given as a path
+/// relative to the crate's manifest directory.
+///
+/// A differing value or a missing file fails the assertion; set
+/// `TUST_UPDATE_GOLDEN=1` to rewrite the file instead. See
+/// [`tust_runtime::golden`] for how files are compared.
+///
+/// ```no_run
+/// # use tust_assertions::assert_matches_file;
+/// # fn render() -> String { String::new() }
+/// assert_matches_file!(render(), "tests/golden/output.txt");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! assert_matches_file {
+    ($value:expr, $path:expr $(,)?) => {
+        $crate::__private::assert_matches_file($crate::__private::GoldenAssertion {
+            manifest_dir: ::core::env!("CARGO_MANIFEST_DIR"),
+            path: $path,
+            expression: ::core::stringify!($value),
+            contents: &::std::format!("{}", $value),
+        })
+    };
+}
@@ -0,0 +1,245 @@
+//! Containment, order, and size matchers for collections.
+//!
+//! They accept any type whose references iterate over its elements, such as
+//! slices, arrays, `Vec`, `VecDeque`, and the sets.
+
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use super::Matcher;
+use crate::collections::{first_unsorted, list, partial_order, unmatched};
+
+/// Matches collections with an element equal to `item`.
+pub fn contain<E>(item: E) -> Contain<E> {
+    Contain(item)
+}
+
+/// See [`contain`].
+#[derive(Debug, Clone, Copy)]
+pub struct Contain<E>(E);
+
+impl<C, T, E> Matcher<C> for Contain<E>
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: PartialEq<E>,
+    E: Debug,
+{
+    fn matches(&self, actual: &C) -> bool {
+        actual.into_iter().any(|element| *element == self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("contain {:?}", self.0)
+    }
+}
+
+/// Matches collections with the same elements as `expected`, in any order:
+/// each element of one is paired with an equal element of the other.
+pub fn contain_same_elements<E>(expected: E) -> ContainSameElements<E> {
+    ContainSameElements(expected)
+}
+
+/// See [`contain_same_elements`].
+#[derive(Debug, Clone, Copy)]
+pub struct ContainSameElements<E>(E);
+
+impl<C, T, E, U> Matcher<C> for ContainSameElements<E>
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    for<'b> &'b E: IntoIterator<Item = &'b U>,
+    T: PartialEq<U> + Debug,
+    E: Debug,
+    U: Debug,
+{
+    fn matches(&self, actual: &C) -> bool {
+        let (extra, missing) = unmatched(actual, &self.0);
+        extra.is_empty() && missing.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        format!("contain the same elements as {:?}", self.0)
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        let (extra, missing) = unmatched(actual, &self.0);
+        Some(match (missing.is_empty(), extra.is_empty()) {
+            (false, false) => format!(
+                "which lacks {} and has an extra {}",
+                list(&missing),
+                list(&extra)
+            ),
+            (false, true) => format!("which lacks {}", list(&missing)),
+            _ => format!("which has an extra {}", list(&extra)),
+        })
+    }
+}
+
+/// Matches collections containing every element of `expected`, in any
+/// order; an element expected twice must be there twice.
+pub fn contain_all<E>(expected: E) -> ContainAll<E> {
+    ContainAll(expected)
+}
+
+/// See [`contain_all`].
+#[derive(Debug, Clone, Copy)]
+pub struct ContainAll<E>(E);
+
+impl<C, T, E, U> Matcher<C> for ContainAll<E>
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    for<'b> &'b E: IntoIterator<Item = &'b U>,
+    T: PartialEq<U>,
+    E: Debug,
+    U: Debug,
+{
+    fn matches(&self, actual: &C) -> bool {
+        unmatched(actual, &self.0).1.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        format!("contain all of {:?}", self.0)
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        Some(format!(
+            "which lacks {}",
+            list(&unmatched(actual, &self.0).1)
+        ))
+    }
+}
+
+/// Matches collections whose elements are in ascending order, allowing
+/// equal neighbours.
+pub fn be_sorted() -> BeSorted {
+    BeSorted
+}
+
+/// See [`be_sorted`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeSorted;
+
+impl<C, T> Matcher<C> for BeSorted
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: PartialOrd + Debug,
+{
+    fn matches(&self, actual: &C) -> bool {
+        first_unsorted(actual, partial_order).is_none()
+    }
+
+    fn describe(&self) -> String {
+        "be sorted".to_string()
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        first_unsorted(actual, partial_order).map(out_of_order)
+    }
+}
+
+/// Matches collections in which `compare` orders no element after the
+/// next, as for [`slice::sort_by`].
+pub fn be_sorted_by<F>(compare: F) -> BeSortedBy<F> {
+    BeSortedBy(compare)
+}
+
+/// See [`be_sorted_by`].
+#[derive(Clone, Copy)]
+pub struct BeSortedBy<F>(F);
+
+impl<C, T, F> Matcher<C> for BeSortedBy<F>
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: Debug,
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn matches(&self, actual: &C) -> bool {
+        first_unsorted(actual, &self.0).is_none()
+    }
+
+    fn describe(&self) -> String {
+        "be sorted by the given order".to_string()
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        first_unsorted(actual, &self.0).map(out_of_order)
+    }
+}
+
+fn out_of_order<T: Debug + ?Sized>((index, first, second): (usize, &T, &T)) -> String {
+    format!(
+        "which has {:?} at index {} before {:?}",
+        first, index, second
+    )
+}
+
+/// Matches collections without elements.
+pub fn be_empty() -> BeEmpty {
+    BeEmpty
+}
+
+/// See [`be_empty`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeEmpty;
+
+impl<C> Matcher<C> for BeEmpty
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator,
+{
+    fn matches(&self, actual: &C) -> bool {
+        actual.into_iter().next().is_none()
+    }
+
+    fn describe(&self) -> String {
+        "be empty".to_string()
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        Some(format!(
+            "which has {}",
+            elements(actual.into_iter().count())
+        ))
+    }
+}
+
+/// Matches collections with exactly `len` elements.
+pub fn have_len(len: usize) -> HaveLen {
+    HaveLen(len)
+}
+
+/// See [`have_len`].
+#[derive(Debug, Clone, Copy)]
+pub struct HaveLen(usize);
+
+impl<C> Matcher<C> for HaveLen
+where
+    C: ?Sized,
+    for<'a> &'a C: IntoIterator,
+{
+    fn matches(&self, actual: &C) -> bool {
+        actual.into_iter().count() == self.0
+    }
+
+    fn describe(&self) -> String {
+        format!("have {}", elements(self.0))
+    }
+
+    fn explain_mismatch(&self, actual: &C) -> Option<String> {
+        Some(format!(
+            "which has {}",
+            elements(actual.into_iter().count())
+        ))
+    }
+}
+
+fn elements(count: usize) -> String {
+    match count {
+        1 => "1 element".to_string(),
+        count => format!("{} elements", count),
+    }
+}
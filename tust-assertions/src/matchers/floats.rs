@@ -0,0 +1,39 @@
+//! Approximate comparison of floating-point numbers.
+
+use super::Matcher;
+
+/// Matches floats within `tolerance` of `expected`, inclusive. `NaN` never
+/// matches.
+pub fn be_near<F>(expected: F, tolerance: F) -> BeNear<F> {
+    BeNear {
+        expected,
+        tolerance,
+    }
+}
+
+/// See [`be_near`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeNear<F> {
+    expected: F,
+    tolerance: F,
+}
+
+macro_rules! near_matcher {
+    ($($float:ty),*) => {$(
+        impl Matcher<$float> for BeNear<$float> {
+            fn matches(&self, actual: &$float) -> bool {
+                (actual - self.expected).abs() <= self.tolerance
+            }
+
+            fn describe(&self) -> String {
+                format!("be within {:?} of {:?}", self.tolerance, self.expected)
+            }
+
+            fn explain_mismatch(&self, actual: &$float) -> Option<String> {
+                Some(format!("which is {:?} away", (actual - self.expected).abs()))
+            }
+        }
+    )*};
+}
+
+near_matcher!(f32, f64);
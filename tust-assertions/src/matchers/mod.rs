@@ -0,0 +1,219 @@
+//! Composable matchers for [`expect!`](crate::expect).
+//!
+//! A matcher decides whether a value is acceptable and describes what it
+//! expects, so a failure reads as a sentence:
+//!
+//! ```text
+//! expected `queue.len()` to be less than 3
+//!   actual: 5
+//! ```
+//!
+//! Matchers nest, so `be_some(be_greater_than(2))` accepts `Some(3)`, and
+//! combine with [`not`], [`both`], and [`either`].
+//!
+//! Matchers see the value's own type, so dereference borrowed values first:
+//! `expect!(*slice).to(contain(1))` rather than `expect!(slice)`.
+//!
+//! # Writing a matcher
+//!
+//! Implement [`Matcher`] for the types the matcher accepts. `describe`
+//! completes the sentence "expected `<expr>` to ...":
+//!
+//! ```
+//! use tust_assertions::expect;
+//! use tust_assertions::matchers::Matcher;
+//!
+//! struct BeEven;
+//!
+//! impl Matcher<u32> for BeEven {
+//!     fn matches(&self, actual: &u32) -> bool {
+//!         actual % 2 == 0
+//!     }
+//!
+//!     fn describe(&self) -> String {
+//!         "be even".to_string()
+//!     }
+//! }
+//!
+//! expect!(4).to(BeEven);
+//! ```
+//!
+//! One-off conditions can use [`satisfy`] instead of a new type.
+
+mod collections;
+mod floats;
+mod ordering;
+mod strings;
+mod variants;
+
+pub use collections::{
+    be_empty, be_sorted, be_sorted_by, contain, contain_all, contain_same_elements, have_len,
+    BeEmpty, BeSorted, BeSortedBy, Contain, ContainAll, ContainSameElements, HaveLen,
+};
+pub use floats::{be_near, BeNear};
+pub use ordering::{
+    be_greater_or_equal, be_greater_than, be_less_or_equal, be_less_than, eq, equal,
+    BeGreaterOrEqual, BeGreaterThan, BeLessOrEqual, BeLessThan, Equal,
+};
+pub use strings::{
+    contain_substring, end_with, match_regex, start_with, ContainSubstring, EndWith, MatchRegex,
+    StartWith,
+};
+pub use variants::{be_err, be_none, be_ok, be_some, BeErr, BeNone, BeOk, BeSome};
+
+use std::fmt;
+
+/// Decides whether values of type `T` are acceptable.
+pub trait Matcher<T: ?Sized> {
+    /// Whether `actual` is acceptable.
+    fn matches(&self, actual: &T) -> bool;
+
+    /// Describes an acceptable value as the end of the sentence "expected
+    /// `<expr>` to ...", e.g. `be greater than 5`.
+    fn describe(&self) -> String;
+
+    /// Describes what is wrong with `actual`, printed after its value when
+    /// the match fails, e.g. `which is 0.3 away`.
+    fn explain_mismatch(&self, actual: &T) -> Option<String> {
+        let _ = actual;
+        None
+    }
+}
+
+impl<T: ?Sized, M: Matcher<T> + ?Sized> Matcher<T> for &M {
+    fn matches(&self, actual: &T) -> bool {
+        (**self).matches(actual)
+    }
+
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+
+    fn explain_mismatch(&self, actual: &T) -> Option<String> {
+        (**self).explain_mismatch(actual)
+    }
+}
+
+/// Matches any value.
+pub fn anything() -> Anything {
+    Anything
+}
+
+/// See [`anything`].
+#[derive(Debug, Clone, Copy)]
+pub struct Anything;
+
+impl<T: ?Sized> Matcher<T> for Anything {
+    fn matches(&self, _actual: &T) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        "be anything".to_string()
+    }
+}
+
+/// Matches values rejected by `matcher`.
+pub fn not<M>(matcher: M) -> Not<M> {
+    Not(matcher)
+}
+
+/// See [`not`].
+#[derive(Debug, Clone, Copy)]
+pub struct Not<M>(M);
+
+impl<T: ?Sized, M: Matcher<T>> Matcher<T> for Not<M> {
+    fn matches(&self, actual: &T) -> bool {
+        !self.0.matches(actual)
+    }
+
+    fn describe(&self) -> String {
+        format!("not {}", self.0.describe())
+    }
+}
+
+/// Matches values accepted by both `first` and `second`.
+pub fn both<A, B>(first: A, second: B) -> Both<A, B> {
+    Both(first, second)
+}
+
+/// See [`both`].
+#[derive(Debug, Clone, Copy)]
+pub struct Both<A, B>(A, B);
+
+impl<T: ?Sized, A: Matcher<T>, B: Matcher<T>> Matcher<T> for Both<A, B> {
+    fn matches(&self, actual: &T) -> bool {
+        self.0.matches(actual) && self.1.matches(actual)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} and {}", self.0.describe(), self.1.describe())
+    }
+
+    fn explain_mismatch(&self, actual: &T) -> Option<String> {
+        if self.0.matches(actual) {
+            self.1.explain_mismatch(actual)
+        } else {
+            self.0.explain_mismatch(actual)
+        }
+    }
+}
+
+/// Matches values accepted by `first`, `second`, or both.
+pub fn either<A, B>(first: A, second: B) -> Either<A, B> {
+    Either(first, second)
+}
+
+/// See [`either`].
+#[derive(Debug, Clone, Copy)]
+pub struct Either<A, B>(A, B);
+
+impl<T: ?Sized, A: Matcher<T>, B: Matcher<T>> Matcher<T> for Either<A, B> {
+    fn matches(&self, actual: &T) -> bool {
+        self.0.matches(actual) || self.1.matches(actual)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} or {}", self.0.describe(), self.1.describe())
+    }
+}
+
+/// Matches values for which `predicate` returns `true`. `description`
+/// completes "expected `<expr>` to ...".
+///
+/// ```
+/// use tust_assertions::expect;
+/// use tust_assertions::matchers::satisfy;
+///
+/// expect!(12).to(satisfy("be a multiple of 3", |n: &i32| n % 3 == 0));
+/// ```
+pub fn satisfy<F>(description: impl Into<String>, predicate: F) -> Satisfy<F> {
+    Satisfy {
+        description: description.into(),
+        predicate,
+    }
+}
+
+/// See [`satisfy`].
+pub struct Satisfy<F> {
+    description: String,
+    predicate: F,
+}
+
+impl<F> fmt::Debug for Satisfy<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Satisfy")
+            .field("description", &self.description)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized, F: Fn(&T) -> bool> Matcher<T> for Satisfy<F> {
+    fn matches(&self, actual: &T) -> bool {
+        (self.predicate)(actual)
+    }
+
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+}
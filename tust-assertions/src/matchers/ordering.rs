@@ -0,0 +1,70 @@
+//! Equality and ordering matchers.
+
+use std::fmt::Debug;
+
+use super::Matcher;
+
+/// Matches values equal to `expected`.
+pub fn equal<E>(expected: E) -> Equal<E> {
+    Equal(expected)
+}
+
+/// Matches values equal to `expected`; a shorter name for [`equal`], which
+/// reads well in mock expectations such as `.with(eq(3))`.
+pub fn eq<E>(expected: E) -> Equal<E> {
+    Equal(expected)
+}
+
+/// See [`equal`].
+#[derive(Debug, Clone, Copy)]
+pub struct Equal<E>(E);
+
+impl<T: PartialEq<E> + ?Sized, E: Debug> Matcher<T> for Equal<E> {
+    fn matches(&self, actual: &T) -> bool {
+        *actual == self.0
+    }
+
+    fn describe(&self) -> String {
+        format!("equal {:?}", self.0)
+    }
+}
+
+macro_rules! ordering_matcher {
+    ($(#[$doc:meta])* $function:ident, $matcher:ident, $op:tt, $phrase:literal) => {
+        $(#[$doc])*
+        pub fn $function<E>(bound: E) -> $matcher<E> {
+            $matcher(bound)
+        }
+
+        #[doc = concat!("See [`", stringify!($function), "`].")]
+        #[derive(Debug, Clone, Copy)]
+        pub struct $matcher<E>(E);
+
+        impl<T: PartialOrd<E> + ?Sized, E: Debug> Matcher<T> for $matcher<E> {
+            fn matches(&self, actual: &T) -> bool {
+                *actual $op self.0
+            }
+
+            fn describe(&self) -> String {
+                format!(concat!($phrase, " {:?}"), self.0)
+            }
+        }
+    };
+}
+
+ordering_matcher!(
+    /// Matches values strictly greater than `bound`.
+    be_greater_than, BeGreaterThan, >, "be greater than"
+);
+ordering_matcher!(
+    /// Matches values greater than or equal to `bound`.
+    be_greater_or_equal, BeGreaterOrEqual, >=, "be greater than or equal to"
+);
+ordering_matcher!(
+    /// Matches values strictly less than `bound`.
+    be_less_than, BeLessThan, <, "be less than"
+);
+ordering_matcher!(
+    /// Matches values less than or equal to `bound`.
+    be_less_or_equal, BeLessOrEqual, <=, "be less than or equal to"
+);
@@ -0,0 +1,96 @@
+//! String pattern matchers. They accept `str`, `String`, and anything else
+//! implementing `AsRef<str>`.
+
+use regex::Regex;
+use tust_runtime::TestFailure;
+
+use super::Matcher;
+
+/// Matches strings starting with `prefix`.
+pub fn start_with(prefix: impl Into<String>) -> StartWith {
+    StartWith(prefix.into())
+}
+
+/// See [`start_with`].
+#[derive(Debug, Clone)]
+pub struct StartWith(String);
+
+impl<S: AsRef<str> + ?Sized> Matcher<S> for StartWith {
+    fn matches(&self, actual: &S) -> bool {
+        actual.as_ref().starts_with(&self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("start with {:?}", self.0)
+    }
+}
+
+/// Matches strings ending with `suffix`.
+pub fn end_with(suffix: impl Into<String>) -> EndWith {
+    EndWith(suffix.into())
+}
+
+/// See [`end_with`].
+#[derive(Debug, Clone)]
+pub struct EndWith(String);
+
+impl<S: AsRef<str> + ?Sized> Matcher<S> for EndWith {
+    fn matches(&self, actual: &S) -> bool {
+        actual.as_ref().ends_with(&self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("end with {:?}", self.0)
+    }
+}
+
+/// Matches strings containing `needle`.
+pub fn contain_substring(needle: impl Into<String>) -> ContainSubstring {
+    ContainSubstring(needle.into())
+}
+
+/// See [`contain_substring`].
+#[derive(Debug, Clone)]
+pub struct ContainSubstring(String);
+
+impl<S: AsRef<str> + ?Sized> Matcher<S> for ContainSubstring {
+    fn matches(&self, actual: &S) -> bool {
+        actual.as_ref().contains(&self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("contain {:?}", self.0)
+    }
+}
+
+/// Matches strings in which the regular expression `pattern` finds a match.
+/// Anchor the pattern with `^` and `$` to match the whole string.
+///
+/// # Panics
+///
+/// Panics if `pattern` is not a valid regular expression.
+#[track_caller]
+pub fn match_regex(pattern: &str) -> MatchRegex {
+    match Regex::new(pattern) {
+        Ok(regex) => MatchRegex(regex),
+        Err(error) => TestFailure::new(format!(
+            "invalid regular expression passed to `match_regex`: {}",
+            error
+        ))
+        .raise(),
+    }
+}
+
+/// See [`match_regex`].
+#[derive(Debug, Clone)]
+pub struct MatchRegex(Regex);
+
+impl<S: AsRef<str> + ?Sized> Matcher<S> for MatchRegex {
+    fn matches(&self, actual: &S) -> bool {
+        self.0.is_match(actual.as_ref())
+    }
+
+    fn describe(&self) -> String {
+        format!("match the regex /{}/", self.0.as_str())
+    }
+}
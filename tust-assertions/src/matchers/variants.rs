@@ -0,0 +1,100 @@
+//! Matchers for `Option` and `Result` variants. Each takes a matcher for the
+//! contained value; pass [`anything()`](super::anything) to accept any value.
+
+use super::Matcher;
+
+/// Matches `Some` holding a value accepted by `inner`.
+pub fn be_some<M>(inner: M) -> BeSome<M> {
+    BeSome(inner)
+}
+
+/// See [`be_some`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeSome<M>(M);
+
+impl<T, M: Matcher<T>> Matcher<Option<T>> for BeSome<M> {
+    fn matches(&self, actual: &Option<T>) -> bool {
+        actual.as_ref().is_some_and(|value| self.0.matches(value))
+    }
+
+    fn describe(&self) -> String {
+        format!("be Some with a value that should {}", self.0.describe())
+    }
+
+    fn explain_mismatch(&self, actual: &Option<T>) -> Option<String> {
+        actual
+            .as_ref()
+            .and_then(|value| self.0.explain_mismatch(value))
+    }
+}
+
+/// Matches `None`.
+pub fn be_none() -> BeNone {
+    BeNone
+}
+
+/// See [`be_none`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeNone;
+
+impl<T> Matcher<Option<T>> for BeNone {
+    fn matches(&self, actual: &Option<T>) -> bool {
+        actual.is_none()
+    }
+
+    fn describe(&self) -> String {
+        "be None".to_string()
+    }
+}
+
+/// Matches `Ok` holding a value accepted by `inner`.
+pub fn be_ok<M>(inner: M) -> BeOk<M> {
+    BeOk(inner)
+}
+
+/// See [`be_ok`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeOk<M>(M);
+
+impl<T, E, M: Matcher<T>> Matcher<Result<T, E>> for BeOk<M> {
+    fn matches(&self, actual: &Result<T, E>) -> bool {
+        actual.as_ref().is_ok_and(|value| self.0.matches(value))
+    }
+
+    fn describe(&self) -> String {
+        format!("be Ok with a value that should {}", self.0.describe())
+    }
+
+    fn explain_mismatch(&self, actual: &Result<T, E>) -> Option<String> {
+        actual
+            .as_ref()
+            .ok()
+            .and_then(|value| self.0.explain_mismatch(value))
+    }
+}
+
+/// Matches `Err` holding an error accepted by `inner`.
+pub fn be_err<M>(inner: M) -> BeErr<M> {
+    BeErr(inner)
+}
+
+/// See [`be_err`].
+#[derive(Debug, Clone, Copy)]
+pub struct BeErr<M>(M);
+
+impl<T, E, M: Matcher<E>> Matcher<Result<T, E>> for BeErr<M> {
+    fn matches(&self, actual: &Result<T, E>) -> bool {
+        actual.as_ref().is_err_and(|error| self.0.matches(error))
+    }
+
+    fn describe(&self) -> String {
+        format!("be Err with an error that should {}", self.0.describe())
+    }
+
+    fn explain_mismatch(&self, actual: &Result<T, E>) -> Option<String> {
+        actual
+            .as_ref()
+            .err()
+            .and_then(|error| self.0.explain_mismatch(error))
+    }
+}
@@ -0,0 +1,388 @@
+//! Mock implementations of traits, generated by `#[automock]`.
+//!
+//! `#[automock]` applied to a trait `Store` generates a `MockStore` type that
+//! implements it. For every method, such as `get`, the mock has an
+//! `expect_get` method adding an expectation of a call:
+//!
+//! ```ignore
+//! #[tust::automock]
+//! trait Store {
+//!     fn get(&self, key: u32) -> u32;
+//! }
+//!
+//! #[tust::test]
+//! fn doubles_the_stored_value() {
+//!     let mut store = MockStore::new();
+//!     store.expect_get().with(eq(3)).returning(|key| key + 4).times(2);
+//!     assert_eq!(double(&store, 3), 14);
+//! }
+//! ```
+//!
+//! An expectation is configured with:
+//!
+//! - `with(m0, m1, ...)`: one [matcher](crate::matchers) per argument;
+//!   the expectation applies only to the calls whose arguments all match.
+//!   `withf(|a, b| ...)` takes a predicate over borrowed arguments instead.
+//! - `returning(|a, b| ...)`: computes the value to return from the
+//!   arguments, or `return_const(value)` to return clones of `value`.
+//!   Methods returning `()` need neither.
+//! - `times(n)`: how many calls are expected, as a number or a range such as
+//!   `1..=3` or `..`; `once()` and `never()` are shorthands. Unless told
+//!   otherwise, an expectation expects to be called at least once.
+//!
+//! A call is handled by the first expectation, in the order they were added,
+//! whose matchers accept its arguments and that has not yet been called as
+//! often as it expects. A call that no expectation accepts panics, naming
+//! the call and the expectations of the method.
+//!
+//! Expectations are verified when the mock is dropped, or earlier by its
+//! `checkpoint` method: each expectation called fewer times than it expects
+//! fails the test that dropped it, pointing at the `expect_` call; the test
+//! keeps running, as with a failed [`check!`](crate::check). A mock dropped
+//! while its thread panics is not verified, since the test has failed
+//! already.
+//!
+//! Mocked methods can take references and return values borrowing from
+//! nothing: a return type written as `&str` is returned as `&'static str`.
+//! Generic methods, and traits that are generic or have associated types,
+//! cannot be mocked.
+
+use std::fmt::{self, Debug};
+use std::ops::{Bound, RangeBounds};
+use std::panic::Location as CallerLocation;
+use std::sync::{Mutex, PoisonError};
+
+use tust_runtime::{soft, Location, TestFailure};
+
+/// How many calls an expectation expects: a number, or a range of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Times {
+    min: usize,
+    /// The maximum, inclusive, or `None` for no maximum.
+    max: Option<usize>,
+}
+
+impl Times {
+    /// Exactly `count` calls.
+    pub fn exactly(count: usize) -> Self {
+        Times {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    /// Any number of calls within `range`.
+    pub fn within(range: impl RangeBounds<usize>) -> Self {
+        let min = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => Some(end.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        Times { min, max }
+    }
+
+    fn is_satisfied_by(&self, calls: usize) -> bool {
+        calls >= self.min
+    }
+
+    fn is_saturated_by(&self, calls: usize) -> bool {
+        self.max.is_some_and(|max| calls >= max)
+    }
+}
+
+impl Default for Times {
+    /// At least one call.
+    fn default() -> Self {
+        Times::within(1..)
+    }
+}
+
+impl From<usize> for Times {
+    fn from(count: usize) -> Self {
+        Times::exactly(count)
+    }
+}
+
+macro_rules! times_from_range {
+    ($($range:ty),*) => {
+        $(
+            impl From<$range> for Times {
+                fn from(range: $range) -> Self {
+                    Times::within(range)
+                }
+            }
+        )*
+    };
+}
+
+times_from_range!(
+    std::ops::Range<usize>,
+    std::ops::RangeInclusive<usize>,
+    std::ops::RangeFrom<usize>,
+    std::ops::RangeTo<usize>,
+    std::ops::RangeToInclusive<usize>,
+    std::ops::RangeFull
+);
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.max) {
+            (0, Some(0)) => f.write_str("never"),
+            (min, Some(max)) if min == max => f.write_str(&calls(min)),
+            (0, Some(max)) => write!(f, "at most {}", calls(max)),
+            (0, None) => f.write_str("any number of times"),
+            (min, None) => write!(f, "at least {}", calls(min)),
+            (min, Some(max)) => write!(f, "between {} and {} times", min, max),
+        }
+    }
+}
+
+fn calls(count: usize) -> String {
+    match count {
+        1 => "once".to_string(),
+        2 => "twice".to_string(),
+        count => format!("{} times", count),
+    }
+}
+
+/// One expectation of a mocked method, with its matcher of type `M` and
+/// action of type `A`, both unsized closure types of the method's
+/// arguments. Configured through the per-method wrappers `#[automock]`
+/// generates.
+#[doc(hidden)]
+pub struct Expectation<M: ?Sized, A: ?Sized> {
+    matcher: Option<Box<M>>,
+    /// What the matcher accepts, e.g. "`key` to equal 3".
+    conditions: Vec<String>,
+    action: Option<Box<A>>,
+    times: Times,
+    calls: usize,
+    location: &'static CallerLocation<'static>,
+}
+
+impl<M: ?Sized, A: ?Sized> Expectation<M, A> {
+    pub fn set_matcher(&mut self, matcher: Box<M>, conditions: Vec<String>) {
+        self.matcher = Some(matcher);
+        self.conditions = conditions;
+    }
+
+    pub fn set_action(&mut self, action: Box<A>) {
+        self.action = Some(action);
+    }
+
+    pub fn set_times(&mut self, times: impl Into<Times>) {
+        self.times = times.into();
+    }
+
+    fn describe(&self, method: &str) -> String {
+        if self.conditions.is_empty() {
+            format!("`{}`", method)
+        } else {
+            format!("`{}` with {}", method, self.conditions.join(" and "))
+        }
+    }
+}
+
+/// Describes the condition a matcher for parameter `param` checks.
+#[doc(hidden)]
+pub fn condition(param: &str, description: String) -> String {
+    format!("`{}` to {}", param, description)
+}
+
+/// Describes the condition checked by `withf`.
+#[doc(hidden)]
+pub fn predicate() -> Vec<String> {
+    vec!["arguments satisfying a predicate".to_string()]
+}
+
+/// Every expectation of one mocked method, e.g. `MockStore::get`.
+#[doc(hidden)]
+pub struct Expectations<M: ?Sized, A: ?Sized> {
+    method: &'static str,
+    list: Mutex<Vec<Expectation<M, A>>>,
+}
+
+impl<M: ?Sized, A: ?Sized> Expectations<M, A> {
+    pub fn new(method: &'static str) -> Self {
+        Expectations {
+            method,
+            list: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds an expectation, expected to be called at least once, recording
+    /// the caller's location to report it at.
+    #[track_caller]
+    pub fn push(&mut self) -> &mut Expectation<M, A> {
+        let list = self.list.get_mut().unwrap_or_else(PoisonError::into_inner);
+        list.push(Expectation {
+            matcher: None,
+            conditions: Vec::new(),
+            action: None,
+            times: Times::default(),
+            calls: 0,
+            location: CallerLocation::caller(),
+        });
+        list.last_mut().expect("an expectation was just added")
+    }
+
+    /// Handles a call with `args`: finds the expectation it belongs to,
+    /// counts the call, and runs the expectation's action with `args`, if it
+    /// has one. `matches` applies a matcher to the arguments, and `render`
+    /// formats them for the message of an unexpected call.
+    ///
+    /// # Panics
+    ///
+    /// If no expectation accepts the call.
+    #[track_caller]
+    pub fn call<Args, R>(
+        &self,
+        args: Args,
+        matches: impl Fn(&M, &Args) -> bool,
+        render: impl FnOnce(&Args) -> Vec<String>,
+        invoke: impl FnOnce(&mut A, Args) -> R,
+    ) -> Option<R> {
+        let mut list = self.list.lock().unwrap_or_else(PoisonError::into_inner);
+        let accepts = |expectation: &Expectation<M, A>| {
+            expectation
+                .matcher
+                .as_deref()
+                .map_or(true, |matcher| matches(matcher, &args))
+        };
+        let found = list.iter().position(|expectation| {
+            !expectation.times.is_saturated_by(expectation.calls) && accepts(expectation)
+        });
+        let Some(index) = found else {
+            let call = format!("{}({})", self.method, render(&args).join(", "));
+            let saturated = list.iter().find(|expectation| accepts(expectation));
+            let message = match saturated {
+                Some(expectation) => format!(
+                    "unexpected call `{}`: expected {} to be called {}, but it was called \
+                     more often\n  expected at {}",
+                    call,
+                    expectation.describe(self.method),
+                    expectation.times,
+                    expectation.location
+                ),
+                None => self.unmatched(&call, &list),
+            };
+            drop(list);
+            TestFailure::new(message).raise();
+        };
+        let expectation = &mut list[index];
+        expectation.calls += 1;
+        expectation
+            .action
+            .as_deref_mut()
+            .map(|action| invoke(action, args))
+    }
+
+    fn unmatched(&self, call: &str, list: &[Expectation<M, A>]) -> String {
+        if list.is_empty() {
+            return format!("unexpected call `{}`: the method has no expectations", call);
+        }
+        let mut message = format!(
+            "unexpected call `{}`: no expectation accepts its arguments\n  expectations:",
+            call
+        );
+        for expectation in list {
+            message.push_str(&format!(
+                "\n    {}: expected {}, called {} (at {})",
+                expectation.describe(self.method),
+                expectation.times,
+                calls(expectation.calls),
+                expectation.location
+            ));
+        }
+        message
+    }
+
+    /// The value returned by an expectation's action, if it had one.
+    ///
+    /// # Panics
+    ///
+    /// If it had none, as the method has no value to return.
+    #[track_caller]
+    pub fn returned<R>(&self, value: Option<R>) -> R {
+        match value {
+            Some(value) => value,
+            None => TestFailure::new(format!(
+                "`{}` was called, but its expectation sets no value to return; use \
+                 `returning` or `return_const`",
+                self.method
+            ))
+            .raise(),
+        }
+    }
+
+    /// Records a soft failure for every expectation called fewer times than
+    /// it expects, then removes every expectation.
+    pub fn checkpoint(&mut self) {
+        let list = std::mem::take(self.list.get_mut().unwrap_or_else(PoisonError::into_inner));
+        for expectation in list {
+            if expectation.times.is_satisfied_by(expectation.calls) {
+                continue;
+            }
+            let called = match expectation.calls {
+                0 => "never called".to_string(),
+                count => format!("called {}", calls(count)),
+            };
+            let location = expectation.location;
+            let message = format!(
+                "expected {} to be called {}, but it was {}",
+                expectation.describe(self.method),
+                expectation.times,
+                called
+            );
+            soft::record(TestFailure {
+                location: Some(Location {
+                    file: location.file().to_string(),
+                    line: location.line(),
+                    column: location.column(),
+                }),
+                ..TestFailure::new(message)
+            });
+        }
+    }
+
+    /// Verifies the expectations of a mock being dropped, unless its thread
+    /// is panicking.
+    pub fn verify(&mut self) {
+        if !std::thread::panicking() {
+            self.checkpoint();
+        }
+    }
+}
+
+/// An argument of a call, rendered with `Debug` if it implements it:
+/// `(&&Arg(&value)).render()` picks [`DebugArg`] when it applies and
+/// [`OpaqueArg`] otherwise.
+#[doc(hidden)]
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait DebugArg {
+    fn render(&self) -> String;
+}
+
+impl<T: Debug + ?Sized> DebugArg for &Arg<'_, T> {
+    fn render(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait OpaqueArg {
+    fn render(&self) -> String;
+}
+
+impl<T: ?Sized> OpaqueArg for Arg<'_, T> {
+    fn render(&self) -> String {
+        "_".to_string()
+    }
+}
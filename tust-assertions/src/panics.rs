@@ -0,0 +1,70 @@
+//! Support for [`assert_panics!`](crate::assert_panics).
+
+use tust_runtime::__private::catch_panic;
+use tust_runtime::TestFailure;
+
+/// Calls `f`, which must panic, and returns its panic message. Fails if `f`
+/// returns, or if `contains` is given and the message does not contain it.
+///
+/// `assertion` is the assertion as written, for the failure message.
+#[track_caller]
+pub fn assert_panics<R>(assertion: &str, f: impl FnOnce() -> R, contains: Option<&str>) -> String {
+    let failure = match catch_panic(f) {
+        Ok(_) => TestFailure::new(format!(
+            "assertion `{}` failed: the closure did not panic",
+            assertion
+        ))
+        .raise(),
+        Err(failure) => failure,
+    };
+    if let Some(text) = contains {
+        if !failure.message.contains(text) {
+            let mut mismatch = TestFailure::new(format!(
+                "assertion `{}` failed: the panic message does not contain {:?}",
+                assertion, text
+            ))
+            .with_metadata("message", &failure.message);
+            if let Some(location) = &failure.location {
+                mismatch = mismatch.with_metadata("at", location);
+            }
+            mismatch.raise();
+        }
+    }
+    failure.message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::failure::tests::failure_of;
+
+    #[test]
+    fn evaluates_to_the_panic_message() {
+        let message = crate::assert_panics!(|| panic!("boom: {}", 1));
+        assert_eq!(message, "boom: 1");
+        crate::assert_panics!(|| panic!("boom"), contains = "oo");
+    }
+
+    #[test]
+    fn closures_that_return_fail() {
+        let failure = failure_of(|| crate::assert_panics!(|| 1));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_panics!(|| 1)` failed: the closure did not panic"
+        );
+    }
+
+    #[test]
+    fn other_panics_fail_with_their_message() {
+        let failure = failure_of(|| crate::assert_panics!(|| panic!("boom"), contains = "bang"));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_panics!(|| panic!(\"boom\"), contains = \"bang\")` failed: the \
+             panic message does not contain \"bang\""
+        );
+        assert_eq!(
+            failure.metadata[0],
+            ("message".to_string(), "boom".to_string())
+        );
+        assert_eq!(failure.metadata[1].0, "at");
+    }
+}
@@ -0,0 +1,91 @@
+//! Failure reporting for `assert_snapshot!` and the binary and image
+//! snapshot assertions.
+
+use tust_runtime::snapshot::{
+    self, BinarySnapshotAssertion, BinarySnapshotOutcome, SnapshotAssertion, SnapshotOutcome,
+};
+use tust_runtime::TestFailure;
+
+/// Compares `assertion` against its stored snapshot, panicking if it does not
+/// match.
+#[track_caller]
+pub fn assert_snapshot(assertion: SnapshotAssertion<'_>) {
+    let outcome = match snapshot::check(&assertion) {
+        Ok(outcome) => outcome,
+        Err(error) => TestFailure::new(error.to_string()).raise(),
+    };
+
+    match outcome {
+        SnapshotOutcome::Matched | SnapshotOutcome::Accepted { .. } => {}
+        SnapshotOutcome::Mismatched { path, pending, old } => {
+            let new = assertion.contents.replace("\r\n", "\n");
+            TestFailure::new(format!(
+                "snapshot {} does not match `{}`",
+                path.display(),
+                assertion.expression
+            ))
+            .with_labeled_diff("old snapshot", "new value", old, new.trim_end_matches('\n'))
+            .with_metadata("new snapshot", pending.display())
+            .with_metadata("to accept", how_to_accept())
+            .raise();
+        }
+        SnapshotOutcome::Missing { path, pending } => {
+            TestFailure::new(format!("snapshot {} does not exist yet", path.display()))
+                .with_metadata("new snapshot", pending.display())
+                .with_metadata("to accept", how_to_accept())
+                .raise();
+        }
+    }
+}
+
+/// Compares the bytes of `assertion` against their stored snapshot,
+/// panicking if they do not match.
+#[track_caller]
+pub fn assert_binary_snapshot(assertion: BinarySnapshotAssertion<'_>) {
+    let outcome = match snapshot::check_binary(&assertion) {
+        Ok(outcome) => outcome,
+        Err(error) => TestFailure::new(error.to_string()).raise(),
+    };
+
+    match outcome {
+        BinarySnapshotOutcome::Matched | BinarySnapshotOutcome::Accepted { .. } => {}
+        BinarySnapshotOutcome::Mismatched {
+            path,
+            pending,
+            difference,
+            diff_image,
+        } => {
+            let mut failure = TestFailure::new(format!(
+                "snapshot {} does not match `{}`",
+                path.display(),
+                assertion.expression
+            ))
+            .with_metadata("difference", difference)
+            .with_metadata("new snapshot", pending.display());
+            if let Some(diff_image) = diff_image {
+                failure = failure.with_metadata("diff image", diff_image.display());
+            }
+            failure.with_metadata("to accept", how_to_accept()).raise();
+        }
+        BinarySnapshotOutcome::Missing { path, pending } => {
+            TestFailure::new(format!("snapshot {} does not exist yet", path.display()))
+                .with_metadata("new snapshot", pending.display())
+                .with_metadata("to accept", how_to_accept())
+                .raise();
+        }
+        BinarySnapshotOutcome::NotAnImage { reason } => {
+            TestFailure::new(format!(
+                "`{}` is not a PNG image: {}",
+                assertion.expression, reason
+            ))
+            .raise();
+        }
+    }
+}
+
+fn how_to_accept() -> String {
+    format!(
+        "review it, then rename it or rerun with {}=1",
+        snapshot::UPDATE_ENV
+    )
+}
@@ -0,0 +1,346 @@
+//! Support for the string assertions, such as
+//! [`assert_str_eq!`](crate::assert_str_eq).
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use regex::Regex;
+use tust_runtime::TestFailure;
+
+use crate::diff;
+use crate::failure::{fail, Mode};
+
+/// Widest excerpt of a line shown around the first difference.
+const EXCERPT_WIDTH: usize = 60;
+
+/// How the string assertions compare strings: as they are, or after
+/// normalizing their whitespace or case. Built by the macros from the
+/// options written after their arguments.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrComparison {
+    normalize_whitespace: bool,
+    ignore_case: bool,
+}
+
+impl StrComparison {
+    pub fn new() -> Self {
+        StrComparison::default()
+    }
+
+    /// Compares with every run of spaces and tabs collapsed into one space,
+    /// lines trimmed, `\r\n` line endings made `\n`, and blank lines at the
+    /// start and end removed.
+    pub fn normalize_whitespace(self) -> Self {
+        StrComparison {
+            normalize_whitespace: true,
+            ..self
+        }
+    }
+
+    /// Compares the lowercase forms of the strings.
+    pub fn ignore_case(self) -> Self {
+        StrComparison {
+            ignore_case: true,
+            ..self
+        }
+    }
+
+    /// `s` in the form in which it is compared.
+    fn apply<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let mut s = Cow::Borrowed(s);
+        if self.normalize_whitespace {
+            let lines: Vec<String> = s
+                .lines()
+                .map(|line| {
+                    line.split([' ', '\t'])
+                        .filter(|word| !word.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            let start = lines.iter().position(|line| !line.is_empty());
+            let end = lines.iter().rposition(|line| !line.is_empty());
+            s = Cow::Owned(match (start, end) {
+                (Some(start), Some(end)) => lines[start..=end].join("\n"),
+                _ => String::new(),
+            });
+        }
+        if self.ignore_case {
+            s = Cow::Owned(s.to_lowercase());
+        }
+        s
+    }
+
+    /// Appended to failure messages when the strings were normalized.
+    fn note(&self) -> &'static str {
+        match (self.normalize_whitespace, self.ignore_case) {
+            (false, false) => "",
+            (true, false) => " (comparing with normalized whitespace)",
+            (false, true) => " (comparing case-insensitively)",
+            (true, true) => " (comparing case-insensitively, with normalized whitespace)",
+        }
+    }
+}
+
+#[track_caller]
+pub fn assert_str_eq(assertion: &str, left: &str, right: &str, comparison: StrComparison) {
+    let (left, right) = (comparison.apply(left), comparison.apply(right));
+    let Some((line, column)) = first_difference(&left, &right) else {
+        return;
+    };
+
+    let mut message = format!(
+        "assertion `{}` failed: the strings differ at line {}, column {}{}",
+        assertion,
+        line + 1,
+        column + 1,
+        comparison.note()
+    );
+    let (left_line, caret) = excerpt(left.split('\n').nth(line), column);
+    let (right_line, _) = excerpt(right.split('\n').nth(line), column);
+    let _ = write!(
+        message,
+        "\n   left: {}\n  right: {}\n         {}^",
+        left_line,
+        right_line,
+        " ".repeat(caret)
+    );
+    let mut failure = TestFailure::new(message.trim_end());
+    if diff::wants_diff(&left, &right) {
+        failure = failure.with_diff(left, right);
+    }
+    fail(Mode::Fatal, failure);
+}
+
+#[track_caller]
+pub fn assert_contains(assertion: &str, string: &str, needle: &str, comparison: StrComparison) {
+    let (compared, needle) = (comparison.apply(string), comparison.apply(needle));
+    if !compared.contains(&*needle) {
+        substring_failed(assertion, "contain", &needle, &compared, comparison);
+    }
+}
+
+#[track_caller]
+pub fn assert_starts_with(assertion: &str, string: &str, prefix: &str, comparison: StrComparison) {
+    let (compared, prefix) = (comparison.apply(string), comparison.apply(prefix));
+    if !compared.starts_with(&*prefix) {
+        substring_failed(assertion, "start with", &prefix, &compared, comparison);
+    }
+}
+
+#[track_caller]
+pub fn assert_ends_with(assertion: &str, string: &str, suffix: &str, comparison: StrComparison) {
+    let (compared, suffix) = (comparison.apply(string), comparison.apply(suffix));
+    if !compared.ends_with(&*suffix) {
+        substring_failed(assertion, "end with", &suffix, &compared, comparison);
+    }
+}
+
+#[track_caller]
+fn substring_failed(
+    assertion: &str,
+    relation: &str,
+    needle: &str,
+    string: &str,
+    comparison: StrComparison,
+) {
+    fail(
+        Mode::Fatal,
+        format!(
+            "assertion `{}` failed: the string does not {} {:?}{}\n{}",
+            assertion,
+            relation,
+            needle,
+            comparison.note(),
+            show(string)
+        ),
+    );
+}
+
+/// Fails unless the regular expression `pattern` finds a match in `string`.
+///
+/// # Panics
+///
+/// Panics if `pattern` is not a valid regular expression.
+#[track_caller]
+pub fn assert_matches_regex(assertion: &str, string: &str, pattern: &str) {
+    let regex = match Regex::new(pattern) {
+        Ok(regex) => regex,
+        Err(error) => TestFailure::new(format!(
+            "invalid regular expression passed to `{}`: {}",
+            assertion, error
+        ))
+        .raise(),
+    };
+    if !regex.is_match(string) {
+        fail(
+            Mode::Fatal,
+            format!(
+                "assertion `{}` failed: the string does not match /{}/\n{}",
+                assertion,
+                pattern,
+                show(string)
+            ),
+        );
+    }
+}
+
+/// Shows a string under a failure message: quoted if it is a single line,
+/// and indented line by line otherwise.
+fn show(string: &str) -> String {
+    if string.contains('\n') {
+        let mut out = String::from("  string:");
+        for line in string.lines() {
+            let _ = write!(out, "\n    | {}", line);
+        }
+        out
+    } else {
+        format!("  string: {:?}", string)
+    }
+}
+
+/// The line and column, both counted in characters from zero, at which
+/// `left` and `right` first differ, or `None` if they are equal.
+fn first_difference(left: &str, right: &str) -> Option<(usize, usize)> {
+    if left == right {
+        return None;
+    }
+    let (mut line, mut column) = (0, 0);
+    let mut right_chars = right.chars();
+    for l in left.chars() {
+        match right_chars.next() {
+            Some(r) if r == l => {
+                if l == '\n' {
+                    line += 1;
+                    column = 0;
+                } else {
+                    column += 1;
+                }
+            }
+            _ => break,
+        }
+    }
+    Some((line, column))
+}
+
+/// The part of `line` around `column` that fits the excerpt width, with `…`
+/// where it is cut, and the position of `column` within the excerpt.
+fn excerpt(line: Option<&str>, column: usize) -> (String, usize) {
+    let Some(line) = line else {
+        return ("<no such line>".to_string(), 0);
+    };
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let chars: Vec<char> = line.chars().collect();
+    let start = column.saturating_sub(EXCERPT_WIDTH / 2);
+    let end = (start + EXCERPT_WIDTH).min(chars.len());
+    let mut out = String::new();
+    let mut caret = column - start;
+    if start > 0 {
+        out.push('…');
+        caret += 1;
+    }
+    out.extend(&chars[start.min(end)..end]);
+    if end < chars.len() {
+        out.push('…');
+    }
+    (out, caret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::failure::tests::{failure_of, message_of};
+
+    #[test]
+    fn str_eq_points_at_the_first_difference() {
+        let greeting = "hello, world";
+        crate::assert_str_eq!(greeting, "hello, world");
+        assert_eq!(
+            message_of(|| crate::assert_str_eq!(greeting, "hello, there")),
+            "assertion `assert_str_eq!(greeting, \"hello, there\")` failed: the strings differ at \
+             line 1, column 8\n   left: hello, world\n  right: hello, there\n                ^"
+        );
+    }
+
+    #[test]
+    fn str_eq_diffs_multiline_strings() {
+        let report = "one\ntwo\nthree\nfour";
+        let failure = failure_of(|| crate::assert_str_eq!(report, "one\ntwo\n3\nfour"));
+        assert_eq!(
+            failure.message,
+            "assertion `assert_str_eq!(report, \"one\\ntwo\\n3\\nfour\")` failed: the strings \
+             differ at line 3, column 1\n   left: three\n  right: 3\n         ^"
+        );
+        let diff = failure.diff.expect("multiline strings are diffed");
+        assert_eq!(
+            (diff.left.as_str(), diff.right.as_str()),
+            (report, "one\ntwo\n3\nfour")
+        );
+    }
+
+    #[test]
+    fn options_normalize_what_is_compared() {
+        crate::assert_str_eq!(
+            "  Hello,\tWorld \r\n\n",
+            "hello, world",
+            normalize_whitespace,
+            ignore_case
+        );
+        assert_eq!(
+            message_of(|| crate::assert_str_eq!("a  b", "a c", normalize_whitespace)),
+            "assertion `assert_str_eq!(\"a  b\", \"a c\", normalize_whitespace)` failed: the \
+             strings differ at line 1, column 3 (comparing with normalized whitespace)\n   \
+             left: a b\n  right: a c\n           ^"
+        );
+    }
+
+    #[test]
+    fn excerpts_cut_long_lines_around_the_difference() {
+        let line = format!("{}x{}", "a".repeat(100), "b".repeat(100));
+        let (cut, caret) = excerpt(Some(&line), 100);
+        assert_eq!(cut, format!("…{}x{}…", "a".repeat(30), "b".repeat(29)));
+        assert_eq!(cut.chars().nth(caret), Some('x'));
+        assert_eq!(excerpt(None, 3), ("<no such line>".to_string(), 0));
+    }
+
+    #[test]
+    fn substring_assertions_show_the_string() {
+        let log = "server listening on port 8080";
+        crate::assert_contains!(log, "PORT", ignore_case);
+        crate::assert_starts_with!(log, "server");
+        crate::assert_ends_with!(log, "8080");
+        assert_eq!(
+            message_of(|| crate::assert_contains!(log, "closed")),
+            "assertion `assert_contains!(log, \"closed\")` failed: the string does not contain \
+             \"closed\"\n  string: \"server listening on port 8080\""
+        );
+        assert_eq!(
+            message_of(|| crate::assert_starts_with!("one\ntwo", "two")),
+            "assertion `assert_starts_with!(\"one\\ntwo\", \"two\")` failed: the string does not \
+             start with \"two\"\n  string:\n    | one\n    | two"
+        );
+        assert_eq!(
+            message_of(|| crate::assert_ends_with!(log, "port  80", normalize_whitespace)),
+            "assertion `assert_ends_with!(log, \"port  80\", normalize_whitespace)` failed: the \
+             string does not end with \"port 80\" (comparing with normalized whitespace)\n  string: \
+             \"server listening on port 8080\""
+        );
+    }
+
+    #[test]
+    fn regex_assertions_show_the_pattern() {
+        let version = "tust 0.1.0";
+        crate::assert_matches_regex!(version, r"\d+\.\d+\.\d+");
+        assert_eq!(
+            message_of(|| crate::assert_matches_regex!(version, r"^v\d")),
+            "assertion `assert_matches_regex!(version, r\"^v\\d\")` failed: the string does not \
+             match /^v\\d/\n  string: \"tust 0.1.0\""
+        );
+        assert!(
+            message_of(|| crate::assert_matches_regex!(version, "(")).starts_with(
+                "invalid regular expression passed to `assert_matches_regex!(version, \"(\")`: "
+            )
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Stage 2: validate the benchmark function and its arguments.
+
+use syn::spanned::Spanned;
+use syn::{Error, ItemFn, Lit, LitFloat, LitInt, Result, ReturnType};
+
+use super::parse::ParsedBench;
+
+/// A validated benchmark.
+pub struct BenchModel {
+    pub func: ItemFn,
+    pub warmup: Option<LitInt>,
+    pub samples: Option<LitInt>,
+    /// The threshold as an `f64` literal, whether written as an integer or
+    /// a float.
+    pub threshold: Option<LitFloat>,
+}
+
+pub fn analyze_bench(parsed: ParsedBench) -> Result<BenchModel> {
+    let ParsedBench { args, func } = parsed;
+    let sig = &func.sig;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "benchmarks cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(asyncness.span(), "benchmarks cannot be async"));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(ty.span(), "benchmarks must return `()`"));
+    }
+
+    if let Some(warmup) = &args.warmup {
+        warmup.base10_parse::<u64>()?;
+    }
+    if let Some(samples) = &args.samples {
+        if samples.base10_parse::<u32>()? == 0 {
+            return Err(Error::new(samples.span(), "`samples` must be at least 1"));
+        }
+    }
+    let mut percentage = None;
+    if let Some(threshold) = &args.threshold {
+        let percent = match threshold {
+            Lit::Int(int) => int.base10_parse::<f64>()?,
+            Lit::Float(float) => float.base10_parse::<f64>()?,
+            lit => {
+                return Err(Error::new(
+                    lit.span(),
+                    "expected `threshold` as a percentage, such as `5` or `2.5`",
+                ))
+            }
+        };
+        if !percent.is_finite() || percent <= 0.0 {
+            return Err(Error::new(
+                threshold.span(),
+                "`threshold` must be a positive percentage",
+            ));
+        }
+        percentage = Some(LitFloat::new(
+            &format!("{:?}f64", percent),
+            threshold.span(),
+        ));
+    }
+
+    Ok(BenchModel {
+        func,
+        warmup: args.warmup,
+        samples: args.samples,
+        threshold: percentage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::bench::parse::parse_bench;
+
+    fn analyze(
+        attr: proc_macro2::TokenStream,
+        item: proc_macro2::TokenStream,
+    ) -> Result<BenchModel> {
+        analyze_bench(parse_bench(attr, item)?)
+    }
+
+    fn error(attr: proc_macro2::TokenStream, item: proc_macro2::TokenStream) -> String {
+        analyze(attr, item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn thresholds_are_floats() {
+        let threshold = |attr| {
+            analyze(
+                attr,
+                quote!(
+                    fn sorts() {}
+                ),
+            )
+            .unwrap()
+            .threshold
+            .map(|threshold| threshold.to_string())
+        };
+        assert_eq!(threshold(quote!()), None);
+        assert_eq!(threshold(quote!(threshold = 5)).unwrap(), "5.0f64");
+        assert_eq!(threshold(quote!(threshold = 2.5)).unwrap(), "2.5f64");
+    }
+
+    #[test]
+    fn rejects_bad_benchmarks() {
+        let item = || {
+            quote!(
+                fn sorts() {}
+            )
+        };
+        assert_eq!(
+            error(quote!(samples = 0), item()),
+            "`samples` must be at least 1"
+        );
+        assert_eq!(
+            error(quote!(threshold = 0), item()),
+            "`threshold` must be a positive percentage"
+        );
+        assert_eq!(
+            error(quote!(threshold = "5%"), item()),
+            "expected `threshold` as a percentage, such as `5` or `2.5`"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn sorts<T>() {}
+                )
+            ),
+            "benchmarks cannot be generic"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    async fn sorts() {}
+                )
+            ),
+            "benchmarks cannot be async"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn sorts() -> u8 {
+                        1
+                    }
+                )
+            ),
+            "benchmarks must return `()`"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Stage 4: emit the test running the benchmark.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::BenchIR;
+
+pub fn codegen_bench(ir: BenchIR) -> TokenStream2 {
+    let BenchIR {
+        attrs,
+        vis,
+        sig,
+        body,
+        warmup,
+        samples,
+        threshold,
+    } = ir;
+
+    let warmup = match warmup {
+        Some(warmup) => quote! {
+            ::core::option::Option::Some(::core::time::Duration::from_millis(#warmup))
+        },
+        None => quote! { ::core::option::Option::None },
+    };
+    let samples = match samples {
+        Some(samples) => quote! { ::core::option::Option::Some(#samples) },
+        None => quote! { ::core::option::Option::None },
+    };
+    let threshold = match threshold {
+        Some(threshold) => quote! { ::core::option::Option::Some(#threshold) },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    quote! {
+        #[::tust::test(serial, tags("bench"))]
+        #(#attrs)*
+        #vis #sig {
+            ::tust::__private::run_bench(
+                &::tust::__private::BenchConfig {
+                    warmup: #warmup,
+                    samples: #samples,
+                    threshold: #threshold,
+                },
+                || #body,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::ItemFn;
+
+    use super::*;
+    use crate::bench::analyze::analyze_bench;
+    use crate::bench::lower::lower_bench;
+    use crate::bench::parse::parse_bench;
+
+    #[test]
+    fn expands_to_a_serial_test_tagged_bench() {
+        let parsed = parse_bench(
+            quote!(warmup = 10),
+            quote!(
+                fn sorts() {
+                    run();
+                }
+            ),
+        )
+        .unwrap();
+        let tokens = codegen_bench(lower_bench(analyze_bench(parsed).unwrap()));
+        let test: ItemFn = syn::parse2(tokens).unwrap();
+        let attr = &test.attrs[0];
+        assert_eq!(
+            quote!(#attr).to_string(),
+            "# [:: tust :: test (serial , tags (\"bench\"))]"
+        );
+        let body = quote!(#test).to_string();
+        assert!(body.contains("Duration :: from_millis (10)"), "{}", body);
+        assert!(
+            body.contains("samples : :: core :: option :: Option :: None"),
+            "{}",
+            body
+        );
+        assert!(body.contains("| | { run () ; }"), "{}", body);
+    }
+}
@@ -0,0 +1,69 @@
+//! Stage 3: split the benchmark into the test to register and the body to
+//! measure.
+
+use syn::{Attribute, Block, LitFloat, LitInt, Signature, Visibility};
+
+use super::analyze::BenchModel;
+
+pub struct BenchIR {
+    /// Attributes of the user's function, passed on to the test so that
+    /// `#[tust::test]` sees its helper attributes.
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    /// The signature of the registered test, parameters included.
+    pub sig: Signature,
+    /// The measured body.
+    pub body: Block,
+    pub warmup: Option<LitInt>,
+    pub samples: Option<LitInt>,
+    pub threshold: Option<LitFloat>,
+}
+
+pub fn lower_bench(model: BenchModel) -> BenchIR {
+    let BenchModel {
+        func,
+        warmup,
+        samples,
+        threshold,
+    } = model;
+
+    BenchIR {
+        attrs: func.attrs,
+        vis: func.vis,
+        sig: func.sig,
+        body: *func.block,
+        warmup,
+        samples,
+        threshold,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+    use crate::bench::analyze::analyze_bench;
+    use crate::bench::parse::parse_bench;
+
+    #[test]
+    fn keeps_the_signature_and_measures_the_body() {
+        let parsed = parse_bench(
+            quote!(),
+            quote!(
+                #[skip]
+                pub fn sorts(data: Vec<u8>) {
+                    run();
+                }
+            ),
+        );
+        let ir = lower_bench(analyze_bench(parsed.unwrap()).unwrap());
+        assert_eq!(ir.attrs.len(), 1);
+        assert!(matches!(ir.vis, Visibility::Public(_)));
+        assert_eq!(
+            ir.sig.to_token_stream().to_string(),
+            "fn sorts (data : Vec < u8 >)"
+        );
+        assert_eq!(ir.body.to_token_stream().to_string(), "{ run () ; }");
+    }
+}
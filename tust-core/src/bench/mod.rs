@@ -0,0 +1,11 @@
+//! Pipeline for the `#[tust::bench]` attribute.
+//!
+//! A benchmark expands to a serial `#[tust::test]` tagged `bench` whose body
+//! hands a closure over the user's body to the runtime's benchmark runner.
+//! Parameters stay on the test, so fixtures are set up once rather than per
+//! iteration.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
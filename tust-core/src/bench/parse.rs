@@ -0,0 +1,102 @@
+//! Stage 1: parse the attribute arguments and the benchmark function.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::{ItemFn, Lit, LitInt, Result};
+
+/// Arguments given to `#[tust::bench(...)]`, kept as written until
+/// analysis.
+#[derive(Debug, Default)]
+pub struct BenchArgs {
+    /// `warmup = MILLISECONDS`
+    pub warmup: Option<LitInt>,
+    /// `samples = N`
+    pub samples: Option<LitInt>,
+    /// `threshold = PERCENT`, an integer or a float.
+    pub threshold: Option<Lit>,
+}
+
+impl BenchArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> Result<()> {
+        if meta.path.is_ident("warmup") {
+            set_once(&mut self.warmup, &meta)
+        } else if meta.path.is_ident("samples") {
+            set_once(&mut self.samples, &meta)
+        } else if meta.path.is_ident("threshold") {
+            set_once(&mut self.threshold, &meta)
+        } else {
+            Err(meta.error(
+                "unsupported `#[tust::bench]` argument; expected `warmup`, `samples`, or `threshold`",
+            ))
+        }
+    }
+}
+
+/// Parses `name = value` into `slot`, rejecting a repeated argument.
+fn set_once<T: syn::parse::Parse>(slot: &mut Option<T>, meta: &ParseNestedMeta<'_>) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+    *slot = Some(meta.value()?.parse()?);
+    Ok(())
+}
+
+/// The raw, unvalidated input of a `#[tust::bench]` invocation.
+pub struct ParsedBench {
+    pub args: BenchArgs,
+    pub func: ItemFn,
+}
+
+/// Parses the attribute arguments and the function the attribute is applied to.
+pub fn parse_bench(attr: TokenStream2, item: TokenStream2) -> Result<ParsedBench> {
+    let mut args = BenchArgs::default();
+    syn::meta::parser(|meta| args.parse_meta(meta)).parse2(attr)?;
+    let func: ItemFn = syn::parse2(item)?;
+
+    Ok(ParsedBench { args, func })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_arguments() {
+        let parsed = parse_bench(
+            quote!(warmup = 50, samples = 20, threshold = 2.5),
+            quote!(
+                fn sorts() {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.args.warmup.unwrap().base10_digits(), "50");
+        assert_eq!(parsed.args.samples.unwrap().base10_digits(), "20");
+        assert!(matches!(parsed.args.threshold, Some(Lit::Float(_))));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let error = |attr| {
+            parse_bench(
+                attr,
+                quote!(
+                    fn sorts() {}
+                ),
+            )
+            .err()
+            .unwrap()
+            .to_string()
+        };
+        assert_eq!(
+            error(quote!(iterations = 5)),
+            "unsupported `#[tust::bench]` argument; expected `warmup`, `samples`, or `threshold`"
+        );
+        assert_eq!(
+            error(quote!(samples = 1, samples = 2)),
+            "duplicate argument"
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Stage 2: read each file and extract its fenced Rust examples.
+
+use std::path::PathBuf;
+
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use syn::{Error, LitStr, Result};
+
+use super::parse::ParsedDoctests;
+
+/// The examples of every file, in the order the files were given.
+pub struct DoctestsModel {
+    pub files: Vec<FileModel>,
+}
+
+pub struct FileModel {
+    /// The path as written, relative to the crate root.
+    pub path: LitStr,
+    /// The module the file's tests are emitted in, named after the path.
+    pub ident: Ident,
+    pub examples: Vec<Example>,
+}
+
+/// A fenced Rust example, with its hidden lines revealed.
+pub struct Example {
+    /// The line of the opening fence.
+    pub line: usize,
+    pub code: TokenStream2,
+    pub ignore: bool,
+    pub should_panic: bool,
+    pub no_run: bool,
+    pub entry: Entry,
+}
+
+/// How the example is run once its code is in place.
+pub enum Entry {
+    /// The code is the body of the test.
+    Inline,
+    /// The code defines `fn main`, which the test calls.
+    Main,
+    /// The code ends with `Ok::<(), E>(())`, and `?` may be used in it.
+    Result,
+}
+
+/// A fenced block whose info string marks it as Rust.
+struct Block {
+    line: usize,
+    info: Vec<String>,
+    lines: Vec<String>,
+}
+
+pub fn analyze_doctests(parsed: ParsedDoctests) -> Result<DoctestsModel> {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let mut files: Vec<FileModel> = Vec::with_capacity(parsed.paths.len());
+    for path in parsed.paths {
+        let name = path.value();
+        let text = std::fs::read_to_string(root.join(&name))
+            .map_err(|err| Error::new(path.span(), format!("cannot read `{}`: {}", name, err)))?;
+        let blocks = if name.ends_with(".rs") {
+            fenced_blocks(doc_comment_lines(&text), true)
+        } else {
+            fenced_blocks(
+                text.lines()
+                    .enumerate()
+                    .map(|(i, line)| Some((i + 1, line))),
+                false,
+            )
+        };
+        let mut examples = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            if let Some(example) = analyze_block(&path, block)? {
+                examples.push(example);
+            }
+        }
+        if examples.is_empty() {
+            return Err(Error::new(
+                path.span(),
+                format!("`{}` contains no fenced Rust examples", name),
+            ));
+        }
+        let ident = module_ident(&name);
+        if let Some(other) = files.iter().find(|file| file.ident == ident) {
+            return Err(Error::new(
+                path.span(),
+                format!(
+                    "`{}` and `{}` would both be emitted as module `{}`",
+                    other.path.value(),
+                    name,
+                    ident
+                ),
+            ));
+        }
+        files.push(FileModel {
+            path,
+            ident,
+            examples,
+        });
+    }
+    Ok(DoctestsModel { files })
+}
+
+/// The text of the `///` and `//!` comments of a Rust file, with their line
+/// numbers; `None` ends a comment, so that a fence never spans two.
+fn doc_comment_lines(text: &str) -> impl Iterator<Item = Option<(usize, &str)>> {
+    text.lines().enumerate().map(|(i, line)| {
+        let line = line.trim_start();
+        let rest = line
+            .strip_prefix("///")
+            .filter(|rest| !rest.starts_with('/'))
+            .or_else(|| line.strip_prefix("//!"))?;
+        Some((i + 1, rest.strip_prefix(' ').unwrap_or(rest)))
+    })
+}
+
+/// The fenced blocks among `lines` whose info string marks them as Rust.
+/// In doc comments, as in rustdoc, a block with no language is Rust too.
+fn fenced_blocks<'a>(
+    lines: impl Iterator<Item = Option<(usize, &'a str)>>,
+    doc_comment: bool,
+) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // The fence that opened the current block, and the block if it is Rust.
+    let mut open: Option<(String, Option<Block>)> = None;
+    for line in lines {
+        let Some((number, line)) = line else {
+            open = None;
+            continue;
+        };
+        let trimmed = line.trim_start();
+        if let Some((fence, block)) = &mut open {
+            let closes = trimmed
+                .strip_prefix(fence.as_str())
+                .is_some_and(|rest| rest.trim_start_matches(&fence[..1]).trim().is_empty());
+            if closes {
+                blocks.extend(block.take());
+                open = None;
+            } else if let Some(block) = block {
+                block.lines.push(line.to_string());
+            }
+            continue;
+        }
+        let Some(marker) = trimmed.chars().next().filter(|&c| c == '`' || c == '~') else {
+            continue;
+        };
+        let length = trimmed.chars().take_while(|&c| c == marker).count();
+        if length < 3 {
+            continue;
+        }
+        let info: Vec<String> = trimmed[length..]
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect();
+        let rust = info.iter().any(|word| word == "rust")
+            || (doc_comment && info.iter().all(|word| is_rustdoc_attribute(word)));
+        let block = rust.then(|| Block {
+            line: number,
+            info,
+            lines: Vec::new(),
+        });
+        open = Some((marker.to_string().repeat(length), block));
+    }
+    blocks
+}
+
+fn is_rustdoc_attribute(word: &str) -> bool {
+    matches!(
+        word,
+        "ignore" | "should_panic" | "no_run" | "compile_fail" | "test_harness" | "standalone_crate"
+    ) || word.starts_with("ignore-")
+        || word.starts_with("edition")
+}
+
+fn analyze_block(path: &LitStr, block: Block) -> Result<Option<Example>> {
+    let has = |attribute: &str| block.info.iter().any(|word| word == attribute);
+    // These change how rustdoc builds the example, which a test cannot do.
+    if has("compile_fail") || has("test_harness") {
+        return Ok(None);
+    }
+    let lines: Vec<&str> = block.lines.iter().map(|line| reveal(line)).collect();
+    let source = lines.join("\n");
+    let code: TokenStream2 = source.parse().map_err(|err| {
+        Error::new(
+            path.span(),
+            format!(
+                "the example at `{}` line {} is not valid Rust: {}",
+                path.value(),
+                block.line,
+                err
+            ),
+        )
+    })?;
+    let entry = if lines.iter().any(|line| defines_main(line)) {
+        Entry::Main
+    } else if lines
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .is_some_and(|last| last.starts_with("Ok::<") && last.ends_with("(())"))
+    {
+        Entry::Result
+    } else {
+        Entry::Inline
+    };
+    Ok(Some(Example {
+        line: block.line,
+        code,
+        ignore: block
+            .info
+            .iter()
+            .any(|word| word == "ignore" || word.starts_with("ignore-")),
+        should_panic: has("should_panic"),
+        no_run: has("no_run"),
+        entry,
+    }))
+}
+
+/// A line of an example as compiled: rustdoc hides lines starting with `# `
+/// from the rendered docs but compiles them, and reads `##` as a literal `#`.
+fn reveal(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    if trimmed == "#" {
+        ""
+    } else if let Some(rest) = trimmed.strip_prefix("# ") {
+        rest
+    } else if trimmed.starts_with("##") {
+        &trimmed[1..]
+    } else {
+        line
+    }
+}
+
+fn defines_main(line: &str) -> bool {
+    let line = line.trim_start();
+    let line = line.strip_prefix("pub ").unwrap_or(line);
+    line.strip_prefix("fn main")
+        .is_some_and(|rest| rest.trim_start().starts_with('('))
+}
+
+/// `README.md` becomes `readme_md`, and `src/lib.rs` becomes `src_lib_rs`.
+fn module_ident(path: &str) -> Ident {
+    let mut name: String = path
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "file_");
+    }
+    if syn::parse_str::<Ident>(&name).is_err() {
+        name.push('_');
+    }
+    Ident::new(&name, Span::call_site())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::doctests::parse::parse_doctests;
+    use crate::testing::write;
+
+    pub(crate) fn analyze(paths: &[&str]) -> Result<DoctestsModel> {
+        analyze_doctests(parse_doctests(quote!(#(#paths),*))?)
+    }
+
+    fn blocks(text: &str, doc_comment: bool) -> Vec<(usize, Vec<String>, Vec<String>)> {
+        let lines: Vec<_> = if doc_comment {
+            doc_comment_lines(text).collect()
+        } else {
+            text.lines()
+                .enumerate()
+                .map(|(i, line)| Some((i + 1, line)))
+                .collect()
+        };
+        fenced_blocks(lines.into_iter(), doc_comment)
+            .into_iter()
+            .map(|block| (block.line, block.info, block.lines))
+            .collect()
+    }
+
+    #[test]
+    fn markdown_blocks_must_say_rust() {
+        let text = "# Title\n\n```rust\nlet a = 1;\n```\n\n```\nnot rust\n```\n\n\
+                    ````rust,no_run\n```\nnested\n````\n\n~~~toml\na = 1\n~~~\n";
+        let found = blocks(text, false);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0],
+            (3, vec!["rust".to_string()], vec!["let a = 1;".to_string()])
+        );
+        assert_eq!(found[1].1, ["rust", "no_run"]);
+        assert_eq!(found[1].2, ["```", "nested"]);
+    }
+
+    #[test]
+    fn doc_comment_blocks_are_rust_unless_they_say_otherwise() {
+        let text = "//! ```\n//! let a = 1;\n//! ```\n\
+                    /// ```text\n/// output\n/// ```\n\
+                    /// ```should_panic\n/// panic!();\n/// ```\n\
+                    //// ```\n//// not a doc comment\n//// ```\n";
+        let found = blocks(text, true);
+        let lines: Vec<_> = found.iter().map(|(line, _, _)| *line).collect();
+        assert_eq!(lines, [1, 7]);
+        assert_eq!(found[0].2, ["let a = 1;"]);
+    }
+
+    #[test]
+    fn fences_end_with_their_comment() {
+        let text = "/// ```\n/// let a = 1;\nfn f() {}\n/// ```\n/// let b = 2;\n";
+        assert!(blocks(text, true).is_empty());
+    }
+
+    #[test]
+    fn reveals_hidden_lines() {
+        assert_eq!(reveal("# use std::fmt;"), "use std::fmt;");
+        assert_eq!(reveal("    # let a = 1;"), "let a = 1;");
+        assert_eq!(reveal("#"), "");
+        assert_eq!(reveal("##[derive(Debug)]"), "#[derive(Debug)]");
+        assert_eq!(reveal("#[derive(Debug)]"), "#[derive(Debug)]");
+    }
+
+    #[test]
+    fn names_modules_after_paths() {
+        assert_eq!(module_ident("README.md"), "readme_md");
+        assert_eq!(module_ident("src/lib.rs"), "src_lib_rs");
+        assert_eq!(module_ident("2024.md"), "file_2024_md");
+        assert_eq!(module_ident("self"), "self_");
+    }
+
+    #[test]
+    fn examples_and_how_they_run() {
+        let path = write(
+            "GUIDE.md",
+            "```rust\nassert_eq!(1 + 1, 2);\n```\n\
+             ```rust\nfn main() {}\n```\n\
+             ```rust\nlet n: u8 = \"1\".parse()?;\nOk::<(), std::num::ParseIntError>(())\n```\n\
+             ```rust,ignore\nnot compiled\n```\n\
+             ```rust,should_panic,no_run\npanic!();\n```\n\
+             ```rust,compile_fail\nlet a: u8 = \"\";\n```\n",
+        );
+        let model = analyze(&[path.as_str()]).unwrap();
+        let file = &model.files[0];
+        assert_eq!(file.ident, module_ident(&path));
+        let examples = &file.examples;
+        assert_eq!(examples.len(), 5);
+        assert!(matches!(examples[0].entry, Entry::Inline));
+        assert!(matches!(examples[1].entry, Entry::Main));
+        assert!(matches!(examples[2].entry, Entry::Result));
+        assert!(examples[3].ignore);
+        assert!(examples[4].should_panic && examples[4].no_run);
+        let lines: Vec<_> = examples.iter().map(|example| example.line).collect();
+        assert_eq!(lines, [1, 4, 7, 11, 14]);
+    }
+
+    #[test]
+    fn rejects_files_without_usable_examples() {
+        let error = |paths: &[&str]| analyze(paths).err().unwrap().to_string();
+        let empty = write("EMPTY.md", "No examples.\n");
+        assert_eq!(
+            error(&[empty.as_str()]),
+            format!("`{}` contains no fenced Rust examples", empty)
+        );
+        let broken = write("BROKEN.md", "text\n\n```rust\nlet a = (;\n```\n");
+        assert!(error(&[broken.as_str()]).starts_with(&format!(
+            "the example at `{}` line 3 is not valid Rust",
+            broken
+        )));
+        let missing = format!("{}/MISSING.md", std::env::temp_dir().display());
+        assert!(error(&[missing.as_str()]).starts_with(&format!("cannot read `{}`", missing)));
+    }
+
+    #[test]
+    fn rejects_files_named_as_the_same_module() {
+        let first = write("a.md", "```rust\n```\n");
+        let second = first.replace("a.md", "a_md");
+        std::fs::copy(&first, &second).unwrap();
+        assert_eq!(
+            analyze(&[&first, &second]).err().unwrap().to_string(),
+            format!(
+                "`{}` and `{}` would both be emitted as module `{}`",
+                first,
+                second,
+                module_ident(&first)
+            )
+        );
+    }
+}
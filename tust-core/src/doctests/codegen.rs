@@ -0,0 +1,83 @@
+//! Stage 4: emit a module of tests per file.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::{DoctestsIR, TestIR};
+
+pub fn codegen_doctests(ir: DoctestsIR) -> TokenStream2 {
+    let modules = ir.modules.into_iter().map(|module| {
+        let ident = module.ident;
+        let path = module.path;
+        let tests = module
+            .tests
+            .into_iter()
+            .map(|TestIR { ident, attrs, body }| {
+                quote! {
+                    #[::tust::test(tags("doctest"))]
+                    #attrs
+                    #[allow(unused)]
+                    fn #ident() {
+                        #body
+                    }
+                }
+            });
+        quote! {
+            mod #ident {
+                #[allow(unused_imports)]
+                use super::*;
+
+                // Rebuilds the tests when the file changes.
+                const _: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path));
+
+                #(#tests)*
+            }
+        }
+    });
+    quote! { #(#modules)* }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+    use syn::Item;
+
+    use super::*;
+    use crate::doctests::lower::tests::lower;
+
+    #[test]
+    fn emits_a_module_of_tagged_tests_per_file() {
+        let ir = lower("CODEGEN.md", "```rust\nassert!(true);\n```\n");
+        let ident = ir.modules[0].ident.to_string();
+        let path = ir.modules[0].path.value();
+        let file = syn::parse2::<syn::File>(codegen_doctests(ir)).unwrap();
+        let Item::Mod(module) = &file.items[0] else {
+            panic!("expected a module");
+        };
+        assert_eq!(module.ident, ident);
+        let items = &module.content.as_ref().unwrap().1;
+        assert_eq!(
+            items[0].to_token_stream().to_string(),
+            quote!(
+                #[allow(unused_imports)]
+                use super::*;
+            )
+            .to_string()
+        );
+        assert_eq!(
+            items[1].to_token_stream().to_string(),
+            quote!(
+                const _: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path));
+            )
+            .to_string()
+        );
+        let Item::Fn(test) = &items[2] else {
+            panic!("expected a test function");
+        };
+        assert_eq!(test.sig.ident, "line_1");
+        assert_eq!(
+            test.attrs[0].to_token_stream().to_string(),
+            quote!(#[::tust::test(tags("doctest"))]).to_string()
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Stage 3: lower each example into the test function that runs it.
+
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::LitStr;
+
+use super::analyze::{DoctestsModel, Entry, Example};
+
+pub struct DoctestsIR {
+    pub modules: Vec<ModuleIR>,
+}
+
+/// The module of one file.
+pub struct ModuleIR {
+    pub ident: Ident,
+    pub path: LitStr,
+    pub tests: Vec<TestIR>,
+}
+
+pub struct TestIR {
+    pub ident: Ident,
+    /// Helper attributes for `#[tust::test]`.
+    pub attrs: TokenStream2,
+    pub body: TokenStream2,
+}
+
+pub fn lower_doctests(model: DoctestsModel) -> DoctestsIR {
+    let modules = model
+        .files
+        .into_iter()
+        .map(|file| {
+            let name = file.path.value();
+            let tests = file
+                .examples
+                .into_iter()
+                .map(|example| lower_example(&name, example))
+                .collect();
+            ModuleIR {
+                ident: file.ident,
+                path: file.path,
+                tests,
+            }
+        })
+        .collect();
+    DoctestsIR { modules }
+}
+
+fn lower_example(file: &str, example: Example) -> TestIR {
+    let ident = format_ident!("line_{}", example.line);
+    let mut attrs = TokenStream2::new();
+    if example.should_panic {
+        attrs.extend(quote!(#[should_fail]));
+    }
+    if example.ignore {
+        // rustdoc does not build ignored examples, so neither do we.
+        let reason = LitStr::new(&format!("marked `ignore` in {}", file), Span::call_site());
+        attrs.extend(quote!(#[skip(reason = #reason)]));
+        return TestIR {
+            ident,
+            attrs,
+            body: TokenStream2::new(),
+        };
+    }
+    let code = example.code;
+    let body = match example.entry {
+        Entry::Inline => code,
+        Entry::Main => quote! {
+            #code
+            ::tust::__private::check_example(main());
+        },
+        Entry::Result => quote! {
+            fn __tust_example() -> ::core::result::Result<(), impl ::core::fmt::Debug> {
+                #code
+            }
+            ::tust::__private::check_example(__tust_example());
+        },
+    };
+    let body = if example.no_run {
+        quote! {
+            #[allow(dead_code)]
+            fn __tust_no_run() {
+                #body
+            }
+        }
+    } else {
+        body
+    };
+    TestIR { ident, attrs, body }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::doctests::analyze::tests::analyze;
+    use crate::testing::write;
+
+    pub(crate) fn lower(name: &str, text: &str) -> DoctestsIR {
+        lower_doctests(analyze(&[&write(name, text)]).unwrap())
+    }
+
+    #[test]
+    fn names_tests_after_their_lines() {
+        let ir = lower("LINES.md", "text\n\n```rust\n```\n\n```rust\n```\n");
+        let idents: Vec<_> = ir.modules[0]
+            .tests
+            .iter()
+            .map(|test| test.ident.to_string())
+            .collect();
+        assert_eq!(idents, ["line_3", "line_6"]);
+    }
+
+    #[test]
+    fn ignored_examples_are_skipped_and_not_built() {
+        let ir = lower("IGNORE.md", "```rust,ignore\nnot rust at all\n```\n");
+        let module = &ir.modules[0];
+        let test = &module.tests[0];
+        assert_eq!(
+            test.attrs.to_string(),
+            quote!(#[skip(reason = "")]).to_string().replace(
+                "\"\"",
+                &format!("\"marked `ignore` in {}\"", module.path.value())
+            )
+        );
+        assert!(test.body.is_empty());
+    }
+
+    #[test]
+    fn should_panic_is_an_expected_failure() {
+        let ir = lower("PANIC.md", "```rust,should_panic\npanic!();\n```\n");
+        let test = &ir.modules[0].tests[0];
+        assert_eq!(test.attrs.to_string(), quote!(#[should_fail]).to_string());
+    }
+
+    #[test]
+    fn calls_main_and_wraps_results() {
+        let ir = lower(
+            "ENTRIES.md",
+            "```rust\nfn main() {}\n```\n\
+             ```rust\nOk::<(), ()>(())\n```\n\
+             ```rust,no_run\nloop {}\n```\n",
+        );
+        let bodies: Vec<_> = ir.modules[0]
+            .tests
+            .iter()
+            .map(|test| test.body.to_string())
+            .collect();
+        assert_eq!(
+            bodies[0],
+            quote!(fn main() {} ::tust::__private::check_example(main());).to_string()
+        );
+        // The code is reparsed from text, which spaces `::<` differently.
+        let unspaced = |text: String| text.replace(' ', "");
+        assert_eq!(
+            unspaced(bodies[1].clone()),
+            unspaced(
+                quote! {
+                    fn __tust_example() -> ::core::result::Result<(), impl ::core::fmt::Debug> {
+                        Ok::<(), ()>(())
+                    }
+                    ::tust::__private::check_example(__tust_example());
+                }
+                .to_string()
+            )
+        );
+        assert_eq!(
+            bodies[2],
+            quote!(
+                #[allow(dead_code)]
+                fn __tust_no_run() {
+                    loop {}
+                }
+            )
+            .to_string()
+        );
+    }
+}
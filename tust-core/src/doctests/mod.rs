@@ -0,0 +1,13 @@
+//! Pipeline for the `tust::doctests!` macro.
+//!
+//! The macro reads markdown files, and the doc comments of Rust files, at
+//! compile time and emits one module per file with one `#[tust::test]`
+//! function per fenced Rust example, so that examples run with the rest of
+//! the suite. Fence attributes follow rustdoc's: `ignore` skips the test,
+//! `should_panic` expects it to fail, `no_run` only compiles it, and
+//! `compile_fail` examples are left out.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
@@ -0,0 +1,55 @@
+//! Stage 1: parse the paths of the files to read examples from.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::{Error, LitStr, Result};
+
+/// The files, relative to the crate root, as written.
+pub struct ParsedDoctests {
+    pub paths: Vec<LitStr>,
+}
+
+impl Parse for ParsedDoctests {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let paths: Punctuated<LitStr, Comma> = Punctuated::parse_terminated(input)?;
+        if paths.is_empty() {
+            return Err(Error::new(
+                input.span(),
+                "expected the paths of files to read examples from, such as `\"README.md\"`",
+            ));
+        }
+        Ok(ParsedDoctests {
+            paths: paths.into_iter().collect(),
+        })
+    }
+}
+
+pub fn parse_doctests(input: TokenStream2) -> Result<ParsedDoctests> {
+    syn::parse2(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_paths() {
+        let parsed = parse_doctests(quote!("README.md", "src/lib.rs",)).unwrap();
+        let paths: Vec<_> = parsed.paths.iter().map(LitStr::value).collect();
+        assert_eq!(paths, ["README.md", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn rejects_anything_but_paths() {
+        let err = parse_doctests(quote!()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "expected the paths of files to read examples from, such as `\"README.md\"`"
+        );
+        assert!(parse_doctests(quote!(README)).is_err());
+    }
+}
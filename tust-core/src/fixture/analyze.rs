@@ -0,0 +1,189 @@
+//! Stage 2: validate the fixture function and its scope.
+
+use syn::spanned::Spanned;
+use syn::{Attribute, Error, ItemFn, Result, ReturnType, Type};
+
+use super::parse::ParsedFixture;
+use crate::params::{self, Param};
+
+/// How long a fixture value lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// A fresh value is created for every parameter that requests it.
+    Test,
+    /// Created once per run and cloned for every parameter that requests it.
+    Session,
+}
+
+/// A validated fixture function.
+pub struct FixtureModel {
+    pub func: ItemFn,
+    pub scope: Scope,
+    /// Whether the function is an `async fn`, whose future the fixture
+    /// runs to completion.
+    pub is_async: bool,
+    pub dependencies: Vec<Param>,
+    pub output: Type,
+    pub cfgs: Vec<Attribute>,
+}
+
+pub fn analyze_fixture(parsed: ParsedFixture) -> Result<FixtureModel> {
+    let ParsedFixture { args, func } = parsed;
+    let sig = &func.sig;
+
+    let scope = match &args.scope {
+        None => Scope::Test,
+        Some(lit) => match lit.value().as_str() {
+            "test" => Scope::Test,
+            "session" => Scope::Session,
+            other => {
+                return Err(Error::new(
+                    lit.span(),
+                    format!(
+                        "unknown fixture scope `{}`; expected \"test\" or \"session\"",
+                        other
+                    ),
+                ))
+            }
+        },
+    };
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "fixture functions cannot be generic",
+        ));
+    }
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => (**ty).clone(),
+        ReturnType::Default => {
+            return Err(Error::new(
+                sig.ident.span(),
+                "fixture functions must return the value they provide",
+            ))
+        }
+    };
+    let dependencies = params::named_params(&sig.inputs)?;
+
+    let cfgs = func
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .cloned()
+        .collect();
+
+    Ok(FixtureModel {
+        scope,
+        is_async: func.sig.asyncness.is_some(),
+        func,
+        dependencies,
+        output,
+        cfgs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream as TokenStream2;
+    use quote::{quote, ToTokens};
+
+    use super::*;
+    use crate::fixture::parse::parse_fixture;
+
+    fn analyze(attr: TokenStream2, item: TokenStream2) -> Result<FixtureModel> {
+        analyze_fixture(parse_fixture(attr, item)?)
+    }
+
+    fn error(attr: TokenStream2, item: TokenStream2) -> String {
+        analyze(attr, item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn scopes() {
+        let scope = |attr| {
+            analyze(
+                attr,
+                quote!(
+                    fn db() -> u8 {
+                        1
+                    }
+                ),
+            )
+            .unwrap()
+            .scope
+        };
+        assert_eq!(scope(quote!()), Scope::Test);
+        assert_eq!(scope(quote!(scope = "test")), Scope::Test);
+        assert_eq!(scope(quote!(scope = "session")), Scope::Session);
+        assert_eq!(
+            error(
+                quote!(scope = "module"),
+                quote!(
+                    fn db() -> u8 {
+                        1
+                    }
+                )
+            ),
+            "unknown fixture scope `module`; expected \"test\" or \"session\""
+        );
+    }
+
+    #[test]
+    fn takes_dependencies_from_the_parameters() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                fn db(url: String, pool: Pool) -> Db {
+                    todo!()
+                }
+            ),
+        );
+        let model = model.unwrap();
+        let names: Vec<_> = model
+            .dependencies
+            .iter()
+            .map(|p| p.ident.to_string())
+            .collect();
+        assert_eq!(names, ["url", "pool"]);
+        assert_eq!(model.output.to_token_stream().to_string(), "Db");
+    }
+
+    #[test]
+    fn rejects_functions_that_cannot_provide_a_value() {
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn db() {}
+                )
+            ),
+            "fixture functions must return the value they provide"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn db<T>() -> T {
+                        todo!()
+                    }
+                )
+            ),
+            "fixture functions cannot be generic"
+        );
+    }
+
+    #[test]
+    fn async_functions_are_awaited() {
+        let is_async = |item| analyze(quote!(), item).unwrap().is_async;
+        assert!(is_async(quote!(
+            async fn db() -> u8 {
+                1
+            }
+        )));
+        assert!(!is_async(quote!(
+            fn db() -> u8 {
+                1
+            }
+        )));
+    }
+}
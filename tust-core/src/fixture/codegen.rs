@@ -0,0 +1,199 @@
+//! Stage 4: emit the fixture function and its registry entry.
+
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+
+use super::analyze::Scope;
+use super::lower::FixtureIR;
+use crate::params;
+
+pub fn codegen_fixture(ir: FixtureIR) -> TokenStream2 {
+    let FixtureIR {
+        func,
+        name,
+        scope,
+        is_async,
+        dependencies,
+        output,
+        cfgs,
+        span,
+    } = ir;
+    let fn_ident = &func.sig.ident;
+    let fixtures = Ident::new("fixtures", Span::call_site());
+    let resolve = params::resolve(&dependencies, &fixtures);
+    let requests = params::requests(&dependencies);
+    let args = dependencies.iter().map(|param| &param.ident);
+    // An async fixture is awaited on the default executor, as an async test
+    // without executor options is.
+    let value = match is_async {
+        false => quote! { #fn_ident(#(#args),*) },
+        true => quote! {
+            ::tust::__private::block_on(
+                &::tust::__private::AsyncConfig::DEFAULT,
+                #fn_ident(#(#args),*),
+            )
+        },
+    };
+    let location = quote_spanned! {span=>
+        file: ::core::file!(),
+        line: ::core::line!(),
+    };
+
+    let provider = match scope {
+        Scope::Test => quote! {
+            ::tust::__private::Provider::PerTest({
+                fn __create(
+                    #fixtures: &mut ::tust::__private::Fixtures,
+                ) -> ::core::result::Result<
+                    ::std::boxed::Box<dyn ::core::any::Any>,
+                    ::tust::__private::FixtureError,
+                > {
+                    #resolve
+                    ::core::result::Result::Ok(::std::boxed::Box::new(#value))
+                }
+                __create
+            })
+        },
+        // Session values are shared between worker threads and handed out by
+        // cloning, so the bounds are checked where the fixture's type is named.
+        Scope::Session => {
+            let boxed = quote_spanned! {output.span()=>
+                ::std::boxed::Box::<#output>::new
+            };
+            let clone = quote_spanned! {output.span()=>
+                <#output as ::core::clone::Clone>::clone
+            };
+            quote! {
+                ::tust::__private::Provider::PerSession {
+                    create: {
+                        fn __create(
+                            #fixtures: &mut ::tust::__private::Fixtures,
+                        ) -> ::core::result::Result<
+                            ::std::boxed::Box<dyn ::core::any::Any + ::core::marker::Send>,
+                            ::tust::__private::FixtureError,
+                        > {
+                            #resolve
+                            ::core::result::Result::Ok(#boxed(#value))
+                        }
+                        __create
+                    },
+                    clone: {
+                        fn __clone(
+                            value: &(dyn ::core::any::Any + ::core::marker::Send),
+                        ) -> ::std::boxed::Box<dyn ::core::any::Any> {
+                            let value = value
+                                .downcast_ref::<#output>()
+                                .expect("session fixture cache holds a value of another type");
+                            ::std::boxed::Box::new(#clone(value))
+                        }
+                        __clone
+                    },
+                }
+            }
+        }
+    };
+
+    quote! {
+        #func
+
+        #(#cfgs)*
+        ::tust::__private::inventory::submit! {
+            ::tust::__private::FixtureDef {
+                name: #name,
+                module_path: ::core::module_path!(),
+                #location
+                dependencies: #requests,
+                type_name: ::core::any::type_name::<#output>,
+                type_id: ::core::any::TypeId::of::<#output>,
+                provider: #provider,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::fixture::analyze::analyze_fixture;
+    use crate::fixture::lower::lower_fixture;
+    use crate::fixture::parse::parse_fixture;
+
+    fn expand(attr: TokenStream2, item: TokenStream2) -> Vec<Item> {
+        let ir = lower_fixture(analyze_fixture(parse_fixture(attr, item).unwrap()).unwrap());
+        syn::parse2::<File>(codegen_fixture(ir)).unwrap().items
+    }
+
+    #[test]
+    fn keeps_the_function_and_registers_it() {
+        let items = expand(
+            quote!(),
+            quote!(
+                fn db(url: String) -> Db {
+                    Db::new(url)
+                }
+            ),
+        );
+        assert_eq!(items.len(), 2);
+        let Item::Fn(func) = &items[0] else {
+            panic!("the fixture function is not kept");
+        };
+        assert_eq!(func.sig.ident, "db");
+        let Item::Macro(submit) = &items[1] else {
+            panic!("the fixture is not registered");
+        };
+        let def = submit.mac.tokens.to_string();
+        assert!(def.contains("name : \"db\""), "{}", def);
+        assert!(def.contains("name : \"url\""), "{}", def);
+        assert!(def.contains("Provider :: PerTest"), "{}", def);
+    }
+
+    #[test]
+    fn session_fixtures_are_cloned() {
+        let items = expand(
+            quote!(scope = "session"),
+            quote!(
+                fn db() -> Db {
+                    Db::new()
+                }
+            ),
+        );
+        let def = items[1].to_token_stream().to_string();
+        assert!(def.contains("Provider :: PerSession"), "{}", def);
+        assert!(
+            def.contains("< Db as :: core :: clone :: Clone > :: clone"),
+            "{}",
+            def
+        );
+    }
+
+    #[test]
+    fn async_fixtures_are_run_to_completion() {
+        for attr in [quote!(), quote!(scope = "session")] {
+            let items = expand(
+                attr,
+                quote!(
+                    async fn db(url: String) -> Db {
+                        Db::connect(url).await
+                    }
+                ),
+            );
+            let Item::Fn(func) = &items[0] else {
+                panic!("the fixture function is not kept");
+            };
+            assert!(func.sig.asyncness.is_some());
+            let def = items[1].to_token_stream().to_string();
+            assert!(
+                def.contains(
+                    ":: tust :: __private :: block_on (& :: tust :: __private :: AsyncConfig \
+                     :: DEFAULT , db (url) ,)"
+                ),
+                "{}",
+                def
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Stage 3: lower the fixture model into the registry entry to generate.
+
+use proc_macro2::Span;
+use syn::{Attribute, ItemFn, Type};
+
+use super::analyze::{FixtureModel, Scope};
+use crate::params::Param;
+
+pub struct FixtureIR {
+    /// The user's function, emitted unchanged so it can still be called.
+    pub func: ItemFn,
+    /// Name other fixtures and tests request this fixture by.
+    pub name: String,
+    pub scope: Scope,
+    /// Whether the function's future must be run to get the value.
+    pub is_async: bool,
+    pub dependencies: Vec<Param>,
+    pub output: Type,
+    pub cfgs: Vec<Attribute>,
+    pub span: Span,
+}
+
+pub fn lower_fixture(model: FixtureModel) -> FixtureIR {
+    let FixtureModel {
+        func,
+        scope,
+        is_async,
+        dependencies,
+        output,
+        cfgs,
+    } = model;
+    let ident = &func.sig.ident;
+
+    FixtureIR {
+        name: ident.to_string(),
+        span: ident.span(),
+        func,
+        scope,
+        is_async,
+        dependencies,
+        output,
+        cfgs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::fixture::analyze::analyze_fixture;
+    use crate::fixture::parse::parse_fixture;
+
+    #[test]
+    fn names_the_fixture_after_its_function() {
+        let parsed = parse_fixture(
+            quote!(),
+            quote!(
+                fn db() -> u8 {
+                    1
+                }
+            ),
+        )
+        .unwrap();
+        let ir = lower_fixture(analyze_fixture(parsed).unwrap());
+        assert_eq!(ir.name, "db");
+        assert_eq!(ir.func.sig.ident, "db");
+        assert_eq!(ir.scope, Scope::Test);
+    }
+}
@@ -0,0 +1,6 @@
+//! Pipeline for the `#[fixture]` attribute.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
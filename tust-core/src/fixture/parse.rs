@@ -0,0 +1,89 @@
+//! Stage 1: parse the attribute arguments and the fixture function.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::{ItemFn, LitStr, Result};
+
+/// Arguments given to `#[fixture(...)]`.
+#[derive(Debug, Default)]
+pub struct FixtureArgs {
+    /// `scope = "..."`, kept as written until analysis.
+    pub scope: Option<LitStr>,
+}
+
+impl FixtureArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> Result<()> {
+        if meta.path.is_ident("scope") {
+            if self.scope.is_some() {
+                return Err(meta.error("duplicate `scope` argument"));
+            }
+            self.scope = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `#[fixture]` argument; expected `scope`"))
+        }
+    }
+}
+
+/// The raw, unvalidated input of a `#[fixture]` invocation.
+pub struct ParsedFixture {
+    pub args: FixtureArgs,
+    pub func: ItemFn,
+}
+
+/// Parses the attribute arguments and the function the attribute is applied to.
+pub fn parse_fixture(attr: TokenStream2, item: TokenStream2) -> Result<ParsedFixture> {
+    let mut args = FixtureArgs::default();
+    syn::meta::parser(|meta| args.parse_meta(meta)).parse2(attr)?;
+    let func: ItemFn = syn::parse2(item)?;
+
+    Ok(ParsedFixture { args, func })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_scope() {
+        let parsed = parse_fixture(
+            quote!(scope = "session"),
+            quote!(
+                fn db() -> u8 {
+                    1
+                }
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.args.scope.unwrap().value(), "session");
+        assert_eq!(parsed.func.sig.ident, "db");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let error = |attr| {
+            parse_fixture(
+                attr,
+                quote!(
+                    fn db() -> u8 {
+                        1
+                    }
+                ),
+            )
+            .err()
+            .unwrap()
+            .to_string()
+        };
+        assert_eq!(
+            error(quote!(lifetime = "session")),
+            "unsupported `#[fixture]` argument; expected `scope`"
+        );
+        assert_eq!(
+            error(quote!(scope = "test", scope = "session")),
+            "duplicate `scope` argument"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Stage 2: validate the fuzz target and its input.
+
+use syn::spanned::Spanned;
+use syn::{Error, FnArg, ItemFn, LitInt, LitStr, Pat, Result, ReturnType, Type};
+
+use super::parse::ParsedFuzz;
+use crate::params::Param;
+
+/// A validated fuzz target.
+pub struct FuzzModel {
+    pub func: ItemFn,
+    pub input: Param,
+    /// Whether the input is `&[u8]`, the bytes themselves, rather than a
+    /// value generated from them.
+    pub bytes: bool,
+    pub iterations: Option<LitInt>,
+    pub time: Option<LitInt>,
+    pub corpus: Option<LitStr>,
+}
+
+pub fn analyze_fuzz(parsed: ParsedFuzz) -> Result<FuzzModel> {
+    let ParsedFuzz { args, func } = parsed;
+    let sig = &func.sig;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "fuzz targets cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(asyncness.span(), "fuzz targets cannot be async"));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(ty.span(), "fuzz targets must return `()`"));
+    }
+    if sig.inputs.len() != 1 {
+        return Err(Error::new(
+            sig.inputs.span(),
+            "a fuzz target takes exactly one parameter: `&[u8]` or a type implementing `Arbitrary`",
+        ));
+    }
+    let input = match &sig.inputs[0] {
+        FnArg::Typed(arg) => match &*arg.pat {
+            Pat::Ident(pat) if pat.subpat.is_none() && pat.by_ref.is_none() => Param {
+                ident: pat.ident.clone(),
+                ty: (*arg.ty).clone(),
+            },
+            pat => {
+                return Err(Error::new(
+                    pat.span(),
+                    "expected a named parameter; the name labels the input in failures",
+                ))
+            }
+        },
+        FnArg::Receiver(receiver) => {
+            return Err(Error::new(
+                receiver.span(),
+                "expected a named parameter, found `self`",
+            ))
+        }
+    };
+    let bytes = is_byte_slice(&input.ty);
+    if !bytes {
+        if let Type::Reference(reference) = &input.ty {
+            return Err(Error::new(
+                reference.span(),
+                "the only reference a fuzz target takes is `&[u8]`; take other inputs by value",
+            ));
+        }
+    }
+
+    for count in [&args.iterations, &args.time].into_iter().flatten() {
+        if count.base10_parse::<u64>()? == 0 {
+            return Err(Error::new(count.span(), "must be at least 1"));
+        }
+    }
+    if let Some(corpus) = &args.corpus {
+        if corpus.value().is_empty() {
+            return Err(Error::new(
+                corpus.span(),
+                "the corpus directory cannot be empty",
+            ));
+        }
+    }
+
+    Ok(FuzzModel {
+        func,
+        input,
+        bytes,
+        iterations: args.iterations,
+        time: args.time,
+        corpus: args.corpus,
+    })
+}
+
+/// Whether `ty` is `&[u8]`.
+fn is_byte_slice(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    if reference.mutability.is_some() {
+        return false;
+    }
+    let Type::Slice(slice) = &*reference.elem else {
+        return false;
+    };
+    matches!(&*slice.elem, Type::Path(path) if path.qself.is_none() && path.path.is_ident("u8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::fuzz::parse::parse_fuzz;
+
+    fn analyze(
+        attr: proc_macro2::TokenStream,
+        item: proc_macro2::TokenStream,
+    ) -> Result<FuzzModel> {
+        analyze_fuzz(parse_fuzz(attr, item)?)
+    }
+
+    fn error(attr: proc_macro2::TokenStream, item: proc_macro2::TokenStream) -> String {
+        analyze(attr, item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn takes_bytes_or_generated_values() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                fn f(data: &[u8]) {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(model.input.ident, "data");
+        assert!(model.bytes);
+
+        let model = analyze(
+            quote!(iterations = 10),
+            quote!(
+                fn f(input: Vec<u16>) {}
+            ),
+        )
+        .unwrap();
+        assert!(!model.bytes);
+        assert_eq!(model.iterations.unwrap().base10_digits(), "10");
+    }
+
+    #[test]
+    fn rejects_bad_targets() {
+        let attr = quote!();
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    fn f<T>(data: &[u8]) {}
+                )
+            ),
+            "fuzz targets cannot be generic"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    async fn f(data: &[u8]) {}
+                )
+            ),
+            "fuzz targets cannot be async"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    fn f(data: &[u8]) -> bool {
+                        true
+                    }
+                )
+            ),
+            "fuzz targets must return `()`"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    fn f(a: u8, b: u8) {}
+                )
+            ),
+            "a fuzz target takes exactly one parameter: `&[u8]` or a type implementing \
+             `Arbitrary`"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    fn f((a, b): (u8, u8)) {}
+                )
+            ),
+            "expected a named parameter; the name labels the input in failures"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote!(
+                    fn f(data: &mut [u8]) {}
+                )
+            ),
+            "the only reference a fuzz target takes is `&[u8]`; take other inputs by value"
+        );
+        assert_eq!(
+            error(
+                attr,
+                quote!(
+                    fn f(text: &str) {}
+                )
+            ),
+            "the only reference a fuzz target takes is `&[u8]`; take other inputs by value"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_limits() {
+        let item = quote!(
+            fn f(data: &[u8]) {}
+        );
+        assert_eq!(
+            error(quote!(iterations = 0), item.clone()),
+            "must be at least 1"
+        );
+        assert_eq!(error(quote!(time = 0), item.clone()), "must be at least 1");
+        assert_eq!(
+            error(quote!(corpus = ""), item),
+            "the corpus directory cannot be empty"
+        );
+    }
+}
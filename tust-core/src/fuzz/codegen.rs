@@ -0,0 +1,145 @@
+//! Stage 4: emit the test running the fuzzer, and the target's registration.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+
+use super::lower::FuzzIR;
+
+pub fn codegen_fuzz(ir: FuzzIR) -> TokenStream2 {
+    let FuzzIR {
+        ident,
+        attrs,
+        body,
+        source,
+        input,
+        bytes,
+        iterations,
+        time,
+        corpus,
+    } = ir;
+    let body_ident = &body.sig.ident;
+    let name = ident.to_string();
+    let input_ty = &input.ty;
+    let input_type = input_ty.to_token_stream().to_string();
+
+    let optional = |value: Option<TokenStream2>| match value {
+        Some(value) => quote! { ::core::option::Option::Some(#value) },
+        None => quote! { ::core::option::Option::None },
+    };
+    let iterations = optional(iterations.map(ToTokens::into_token_stream));
+    let time = optional(time.map(ToTokens::into_token_stream));
+    let corpus = optional(corpus.map(ToTokens::into_token_stream));
+    let config = quote! {
+        &::tust::__private::FuzzConfig {
+            name: #name,
+            manifest_dir: ::core::env!("CARGO_MANIFEST_DIR"),
+            iterations: #iterations,
+            time: #time,
+            corpus: #corpus,
+        }
+    };
+    let check = if bytes {
+        quote! { ::tust::__private::check_fuzz_bytes(#config, #body_ident) }
+    } else {
+        quote! { ::tust::__private::check_fuzz::<#input_ty>(#config, #body_ident) }
+    };
+    // A test compiled out leaves no target to export.
+    let cfgs = attrs.iter().filter(|attr| attr.path().is_ident("cfg"));
+
+    quote! {
+        #[::tust::test]
+        #(#attrs)*
+        fn #ident() {
+            #body
+
+            #check;
+        }
+
+        #(#cfgs)*
+        ::tust::__private::inventory::submit! {
+            ::tust::__private::FuzzDef {
+                name: #name,
+                module_path: ::core::module_path!(),
+                file: ::core::file!(),
+                line: ::core::line!(),
+                source: #source,
+                input: #input_type,
+                bytes: #bytes,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::fuzz::analyze::analyze_fuzz;
+    use crate::fuzz::lower::lower_fuzz;
+    use crate::fuzz::parse::parse_fuzz;
+
+    fn expand(attr: TokenStream2, item: TokenStream2) -> Vec<Item> {
+        let parsed = parse_fuzz(attr, item).unwrap();
+        let tokens = codegen_fuzz(lower_fuzz(analyze_fuzz(parsed).unwrap()));
+        syn::parse2::<File>(tokens).unwrap().items
+    }
+
+    #[test]
+    fn expands_to_a_test_and_a_registration() {
+        let items = expand(
+            quote!(iterations = 5),
+            quote!(
+                fn parses(data: &[u8]) {}
+            ),
+        );
+        let Item::Fn(test) = &items[0] else {
+            panic!("expected the test");
+        };
+        assert_eq!(test.sig.ident, "parses");
+        assert!(test.sig.inputs.is_empty());
+        let body = test.to_token_stream().to_string();
+        assert!(
+            body.contains(":: tust :: __private :: check_fuzz_bytes ("),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("iterations : :: core :: option :: Option :: Some (5)"),
+            "{}",
+            body
+        );
+        let registration = items[1].to_token_stream().to_string();
+        assert!(registration.contains("bytes : true"), "{}", registration);
+        assert!(
+            registration.contains("input : \"& [u8]\""),
+            "{}",
+            registration
+        );
+    }
+
+    #[test]
+    fn generates_other_inputs_and_compiles_out_with_the_test() {
+        let items = expand(
+            quote!(),
+            quote! {
+                #[cfg(feature = "fuzzing")]
+                fn decodes(input: Vec<u8>) {}
+            },
+        );
+        let body = items[0].to_token_stream().to_string();
+        assert!(
+            body.contains(":: tust :: __private :: check_fuzz :: < Vec < u8 > > ("),
+            "{}",
+            body
+        );
+        let Item::Macro(registration) = &items[1] else {
+            panic!("expected the registration");
+        };
+        let cfg = &registration.attrs[0];
+        assert_eq!(
+            cfg.to_token_stream().to_string(),
+            quote!(#[cfg(feature = "fuzzing")]).to_string()
+        );
+    }
+}
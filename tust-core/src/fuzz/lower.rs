@@ -0,0 +1,90 @@
+//! Stage 3: split the fuzz target into the test to register and the function
+//! the fuzzer calls.
+
+use proc_macro2::{Ident, Span};
+use quote::ToTokens;
+use syn::{Attribute, ItemFn, LitInt, LitStr, Visibility};
+
+use super::analyze::FuzzModel;
+use crate::params::Param;
+
+pub struct FuzzIR {
+    /// Name of the registered test and of the target.
+    pub ident: Ident,
+    /// Attributes of the user's function, passed on to the test so that
+    /// `#[tust::test]` sees its helper attributes.
+    pub attrs: Vec<Attribute>,
+    /// The user's function, renamed and without attributes, nested in the
+    /// test.
+    pub body: ItemFn,
+    /// The source of the user's function without its attributes, under its
+    /// own name, for exporting it to cargo-fuzz.
+    pub source: String,
+    pub input: Param,
+    pub bytes: bool,
+    pub iterations: Option<LitInt>,
+    pub time: Option<LitInt>,
+    pub corpus: Option<LitStr>,
+}
+
+pub fn lower_fuzz(model: FuzzModel) -> FuzzIR {
+    let FuzzModel {
+        mut func,
+        input,
+        bytes,
+        iterations,
+        time,
+        corpus,
+    } = model;
+    let ident = func.sig.ident.clone();
+    let attrs = std::mem::take(&mut func.attrs);
+    func.vis = Visibility::Inherited;
+    let source = func.to_token_stream().to_string();
+    func.sig.ident = Ident::new("__tust_fuzz", Span::call_site());
+
+    FuzzIR {
+        ident,
+        attrs,
+        body: func,
+        source,
+        input,
+        bytes,
+        iterations,
+        time,
+        corpus,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::fuzz::analyze::analyze_fuzz;
+    use crate::fuzz::parse::parse_fuzz;
+
+    #[test]
+    fn keeps_the_source_and_moves_the_attributes_to_the_test() {
+        let parsed = parse_fuzz(
+            quote!(),
+            quote! {
+                #[timeout(100)]
+                pub fn parses(data: &[u8]) {}
+            },
+        )
+        .unwrap();
+        let ir = lower_fuzz(analyze_fuzz(parsed).unwrap());
+        assert_eq!(ir.ident, "parses");
+        assert_eq!(ir.attrs.len(), 1);
+        assert!(ir.body.attrs.is_empty());
+        assert_eq!(ir.body.sig.ident, "__tust_fuzz");
+        assert!(matches!(ir.body.vis, Visibility::Inherited));
+        assert_eq!(
+            ir.source,
+            quote!(
+                fn parses(data: &[u8]) {}
+            )
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,10 @@
+//! Pipeline for the `#[tust::fuzz]` attribute.
+//!
+//! A fuzz target expands, like a property, to a `#[tust::test]` without
+//! parameters whose body hands the target to the runtime's fuzzer, plus a
+//! registration of the target's source for `--export-fuzz`.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
@@ -0,0 +1,97 @@
+//! Stage 1: parse the attribute arguments and the fuzz target function.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::{ItemFn, LitInt, LitStr, Result};
+
+/// Arguments given to `#[tust::fuzz(...)]`, kept as written until analysis.
+#[derive(Debug, Default)]
+pub struct FuzzArgs {
+    /// `iterations = N`
+    pub iterations: Option<LitInt>,
+    /// `time = MILLISECONDS`
+    pub time: Option<LitInt>,
+    /// `corpus = "dir"`
+    pub corpus: Option<LitStr>,
+}
+
+impl FuzzArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> Result<()> {
+        if meta.path.is_ident("iterations") {
+            set_once(&mut self.iterations, &meta)
+        } else if meta.path.is_ident("time") {
+            set_once(&mut self.time, &meta)
+        } else if meta.path.is_ident("corpus") {
+            set_once(&mut self.corpus, &meta)
+        } else {
+            Err(meta.error(
+                "unsupported `#[tust::fuzz]` argument; expected `iterations`, `time`, or `corpus`",
+            ))
+        }
+    }
+}
+
+fn set_once<T: syn::parse::Parse>(slot: &mut Option<T>, meta: &ParseNestedMeta<'_>) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+    *slot = Some(meta.value()?.parse()?);
+    Ok(())
+}
+
+/// The raw, unvalidated input of a `#[tust::fuzz]` invocation.
+pub struct ParsedFuzz {
+    pub args: FuzzArgs,
+    pub func: ItemFn,
+}
+
+/// Parses the attribute arguments and the function the attribute is applied to.
+pub fn parse_fuzz(attr: TokenStream2, item: TokenStream2) -> Result<ParsedFuzz> {
+    let mut args = FuzzArgs::default();
+    syn::meta::parser(|meta| args.parse_meta(meta)).parse2(attr)?;
+    let func: ItemFn = syn::parse2(item)?;
+
+    Ok(ParsedFuzz { args, func })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_iterations_time_and_corpus() {
+        let parsed = parse_fuzz(
+            quote!(iterations = 100, time = 50, corpus = "fuzz/corpus"),
+            quote!(
+                fn f(data: &[u8]) {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.args.iterations.unwrap().base10_digits(), "100");
+        assert_eq!(parsed.args.time.unwrap().base10_digits(), "50");
+        assert_eq!(parsed.args.corpus.unwrap().value(), "fuzz/corpus");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let error = |attr| {
+            parse_fuzz(
+                attr,
+                quote!(
+                    fn f(data: &[u8]) {}
+                ),
+            )
+            .err()
+            .unwrap()
+            .to_string()
+        };
+        assert_eq!(
+            error(quote!(cases = 5)),
+            "unsupported `#[tust::fuzz]` argument; expected `iterations`, `time`, or `corpus`"
+        );
+        assert_eq!(error(quote!(time = 1, time = 2)), "duplicate argument");
+    }
+}
@@ -0,0 +1,109 @@
+//! Stage 2: validate the hook function.
+
+use syn::spanned::Spanned;
+use syn::{Attribute, Error, ItemFn, Result, ReturnType};
+
+use super::parse::ParsedHook;
+use super::HookKind;
+
+/// A validated hook function.
+pub struct HookModel {
+    pub kind: HookKind,
+    pub func: ItemFn,
+    pub cfgs: Vec<Attribute>,
+}
+
+pub fn analyze_hook(parsed: ParsedHook) -> Result<HookModel> {
+    let ParsedHook { kind, func } = parsed;
+    let sig = &func.sig;
+    let what = format!("`#[{}]` functions", kind.attribute());
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            format!("{} cannot be generic", what),
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            format!("{} cannot be async", what),
+        ));
+    }
+    if !sig.inputs.is_empty() {
+        return Err(Error::new(
+            sig.inputs.span(),
+            format!("{} cannot take parameters", what),
+        ));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(ty.span(), format!("{} must return `()`", what)));
+    }
+
+    let cfgs = func
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .cloned()
+        .collect();
+
+    Ok(HookModel { kind, func, cfgs })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::hook::parse::parse_hook;
+
+    fn error(item: proc_macro2::TokenStream) -> String {
+        let parsed = parse_hook(HookKind::BeforeAll, quote!(), item).unwrap();
+        analyze_hook(parsed).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn keeps_cfg_attributes() {
+        let parsed = parse_hook(
+            HookKind::AfterEach,
+            quote!(),
+            quote!(
+                #[cfg(unix)]
+                fn reset() {}
+            ),
+        );
+        let model = analyze_hook(parsed.unwrap()).unwrap();
+        assert_eq!(model.kind, HookKind::AfterEach);
+        assert_eq!(model.cfgs.len(), 1);
+    }
+
+    #[test]
+    fn rejects_functions_that_cannot_be_hooks() {
+        assert_eq!(
+            error(quote!(
+                fn setup<T>() {}
+            )),
+            "`#[before_all]` functions cannot be generic"
+        );
+        assert_eq!(
+            error(quote!(
+                async fn setup() {}
+            )),
+            "`#[before_all]` functions cannot be async"
+        );
+        assert_eq!(
+            error(quote!(
+                fn setup(db: Db) {}
+            )),
+            "`#[before_all]` functions cannot take parameters"
+        );
+        assert_eq!(
+            error(quote!(
+                fn setup() -> bool {
+                    true
+                }
+            )),
+            "`#[before_all]` functions must return `()`"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Stage 4: emit the hook function and its registry entry.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+
+use super::lower::HookIR;
+
+pub fn codegen_hook(ir: HookIR) -> TokenStream2 {
+    let HookIR {
+        func,
+        name,
+        kind,
+        cfgs,
+        span,
+    } = ir;
+    let fn_ident = &func.sig.ident;
+    let location = quote_spanned! {span=>
+        file: ::core::file!(),
+        line: ::core::line!(),
+    };
+
+    quote! {
+        #func
+
+        #(#cfgs)*
+        ::tust::__private::inventory::submit! {
+            ::tust::__private::HookDef {
+                kind: ::tust::__private::HookKind::#kind,
+                name: #name,
+                module_path: ::core::module_path!(),
+                #location
+                run: #fn_ident,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::hook::analyze::analyze_hook;
+    use crate::hook::lower::lower_hook;
+    use crate::hook::parse::parse_hook;
+    use crate::HookKind;
+
+    #[test]
+    fn keeps_the_function_and_registers_it() {
+        let parsed = parse_hook(
+            HookKind::BeforeEach,
+            quote!(),
+            quote!(
+                fn setup() {}
+            ),
+        )
+        .unwrap();
+        let tokens = codegen_hook(lower_hook(analyze_hook(parsed).unwrap()));
+        let items = syn::parse2::<File>(tokens).unwrap().items;
+        assert!(matches!(&items[0], Item::Fn(func) if func.sig.ident == "setup"));
+        let Item::Macro(submit) = &items[1] else {
+            panic!("the hook is not registered");
+        };
+        let def = submit.mac.tokens.to_string();
+        assert!(def.contains("kind : :: tust :: __private :: HookKind :: BeforeEach"));
+        assert!(def.contains("name : \"setup\""), "{}", def);
+        assert!(def.contains("run : setup"), "{}", def);
+    }
+}
@@ -0,0 +1,67 @@
+//! Stage 3: lower the hook model into the registry entry to generate.
+
+use proc_macro2::{Ident, Span};
+use syn::{Attribute, ItemFn};
+
+use super::analyze::HookModel;
+use super::HookKind;
+
+pub struct HookIR {
+    /// The user's function, emitted unchanged so it can still be called.
+    pub func: ItemFn,
+    pub name: String,
+    /// Variant of the runtime's `HookKind` enum.
+    pub kind: Ident,
+    pub cfgs: Vec<Attribute>,
+    pub span: Span,
+}
+
+pub fn lower_hook(model: HookModel) -> HookIR {
+    let HookModel { kind, func, cfgs } = model;
+    let ident = &func.sig.ident;
+    let kind = match kind {
+        HookKind::BeforeEach => "BeforeEach",
+        HookKind::AfterEach => "AfterEach",
+        HookKind::BeforeAll => "BeforeAll",
+        HookKind::AfterAll => "AfterAll",
+    };
+
+    HookIR {
+        name: ident.to_string(),
+        kind: Ident::new(kind, Span::call_site()),
+        span: ident.span(),
+        func,
+        cfgs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::hook::analyze::analyze_hook;
+    use crate::hook::parse::parse_hook;
+
+    #[test]
+    fn names_the_runtime_kind() {
+        for (kind, variant) in [
+            (HookKind::BeforeEach, "BeforeEach"),
+            (HookKind::AfterEach, "AfterEach"),
+            (HookKind::BeforeAll, "BeforeAll"),
+            (HookKind::AfterAll, "AfterAll"),
+        ] {
+            let parsed = parse_hook(
+                kind,
+                quote!(),
+                quote!(
+                    fn setup() {}
+                ),
+            )
+            .unwrap();
+            let ir = lower_hook(analyze_hook(parsed).unwrap());
+            assert_eq!(ir.kind, variant);
+            assert_eq!(ir.name, "setup");
+        }
+    }
+}
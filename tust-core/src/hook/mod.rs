@@ -0,0 +1,28 @@
+//! Pipeline for the `#[before_each]`, `#[after_each]`, `#[before_all]`, and
+//! `#[after_all]` attributes.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
+
+/// When a hook runs, as selected by the attribute it is declared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    BeforeEach,
+    AfterEach,
+    BeforeAll,
+    AfterAll,
+}
+
+impl HookKind {
+    /// Name of the attribute declaring this kind of hook.
+    pub fn attribute(self) -> &'static str {
+        match self {
+            HookKind::BeforeEach => "before_each",
+            HookKind::AfterEach => "after_each",
+            HookKind::BeforeAll => "before_all",
+            HookKind::AfterAll => "after_all",
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Stage 1: parse the hook function; hooks take no attribute arguments.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Error, ItemFn, Result};
+
+use super::HookKind;
+
+/// The raw, unvalidated input of a hook attribute.
+pub struct ParsedHook {
+    pub kind: HookKind,
+    pub func: ItemFn,
+}
+
+/// Parses the function a hook attribute is applied to.
+pub fn parse_hook(kind: HookKind, attr: TokenStream2, item: TokenStream2) -> Result<ParsedHook> {
+    if !attr.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            format!("`#[{}]` does not take arguments", kind.attribute()),
+        ));
+    }
+    let func: ItemFn = syn::parse2(item)?;
+
+    Ok(ParsedHook { kind, func })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_function() {
+        let parsed = parse_hook(
+            HookKind::BeforeEach,
+            quote!(),
+            quote!(
+                fn setup() {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.kind, HookKind::BeforeEach);
+        assert_eq!(parsed.func.sig.ident, "setup");
+    }
+
+    #[test]
+    fn rejects_arguments() {
+        let err = parse_hook(
+            HookKind::AfterAll,
+            quote!(once),
+            quote!(
+                fn teardown() {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "`#[after_all]` does not take arguments");
+    }
+}
@@ -1,4 +1,191 @@
-// Core parsing and code generation logic for tust test framework
-// All logic lives here to enable testing (using proc_macro2 instead of proc_macro)
+//! Core parsing and code generation logic for the tust test framework.
+//!
+//! All logic lives here rather than in `tust-macros` so that it can be unit
+//! tested: everything operates on `proc_macro2` token streams. Each macro is
+//! implemented as a four-stage pipeline (Parse → Analyze → Lower → Codegen);
+//! see `ARCHITECTURE.md` for an overview.
 
-// TODO: Implement parsing pipeline (Parse → Analyze → Lower → Codegen)
+use proc_macro2::TokenStream as TokenStream2;
+
+pub use hook::HookKind;
+
+mod bench;
+mod doctests;
+mod fixture;
+mod fuzz;
+mod hook;
+mod mock;
+mod params;
+mod property;
+mod suite;
+mod test;
+mod test_builder;
+mod test_suite;
+#[cfg(test)]
+mod testing;
+
+/// Expands `#[tust::test]` applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed or the function
+/// cannot be used as a test.
+pub fn expand_test(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = test::parse::parse_test(attr, item)?;
+    let model = test::analyze::analyze_test(parsed)?;
+    let ir = test::lower::lower_test(model);
+    Ok(test::codegen::codegen_test(ir))
+}
+
+/// Expands `#[tust::test]` applied to a function in a crate built for an
+/// embedded target with `tust-embedded`.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed, the function
+/// cannot be used as a test, or it uses what embedded targets lack, such as
+/// fixtures.
+pub fn expand_embedded_test(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = test::parse::parse_test(attr, item)?;
+    let model = test::analyze::analyze_test(parsed)?;
+    test::analyze::check_embedded(&model)?;
+    let ir = test::lower::lower_test(model);
+    Ok(test::codegen::codegen_embedded_test(ir))
+}
+
+/// Expands `#[fixture]` applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed or the function
+/// cannot provide a fixture.
+pub fn expand_fixture(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = fixture::parse::parse_fixture(attr, item)?;
+    let model = fixture::analyze::analyze_fixture(parsed)?;
+    let ir = fixture::lower::lower_fixture(model);
+    Ok(fixture::codegen::codegen_fixture(ir))
+}
+
+/// Expands a hook attribute, such as `#[before_each]`, applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute is given arguments or the function
+/// cannot be used as a hook.
+pub fn expand_hook(
+    kind: HookKind,
+    attr: TokenStream2,
+    item: TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let parsed = hook::parse::parse_hook(kind, attr, item)?;
+    let model = hook::analyze::analyze_hook(parsed)?;
+    let ir = hook::lower::lower_hook(model);
+    Ok(hook::codegen::codegen_hook(ir))
+}
+
+/// Expands `#[tust::bench]` applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed or the function
+/// cannot be used as a benchmark.
+pub fn expand_bench(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = bench::parse::parse_bench(attr, item)?;
+    let model = bench::analyze::analyze_bench(parsed)?;
+    let ir = bench::lower::lower_bench(model);
+    Ok(bench::codegen::codegen_bench(ir))
+}
+
+/// Expands `#[tust::property]` applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed or the function
+/// cannot be used as a property.
+pub fn expand_property(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = property::parse::parse_property(attr, item)?;
+    let model = property::analyze::analyze_property(parsed)?;
+    let ir = property::lower::lower_property(model);
+    Ok(property::codegen::codegen_property(ir))
+}
+
+/// Expands `#[tust::fuzz]` applied to a function.
+///
+/// # Errors
+///
+/// Returns an error if the attribute arguments are malformed or the function
+/// cannot be used as a fuzz target.
+pub fn expand_fuzz(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = fuzz::parse::parse_fuzz(attr, item)?;
+    let model = fuzz::analyze::analyze_fuzz(parsed)?;
+    let ir = fuzz::lower::lower_fuzz(model);
+    Ok(fuzz::codegen::codegen_fuzz(ir))
+}
+
+/// Expands `suite! { ... }`, a tree of `describe` and `it` blocks.
+///
+/// # Errors
+///
+/// Returns an error if a block is malformed or two descriptions in the same
+/// block name the same module or test.
+pub fn expand_suite(input: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = suite::parse::parse_suite(input)?;
+    let model = suite::analyze::analyze_suite(parsed)?;
+    let ir = suite::lower::lower_suite(model);
+    Ok(suite::codegen::codegen_suite(ir))
+}
+
+/// Expands `test_suite! { ... }`, tests generic over one type, into a
+/// macro instantiating them for a concrete one.
+///
+/// # Errors
+///
+/// Returns an error if the suite does not take exactly one type parameter,
+/// or if one of its tests does not take exactly one value of that type.
+pub fn expand_test_suite(input: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = test_suite::parse::parse_test_suite(input)?;
+    let model = test_suite::analyze::analyze_test_suite(parsed)?;
+    let ir = test_suite::lower::lower_test_suite(model);
+    Ok(test_suite::codegen::codegen_test_suite(ir))
+}
+
+/// Expands `doctests!("README.md", ...)`, the fenced Rust examples of the
+/// given files.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, holds no examples, or holds an
+/// example that is not valid Rust, or if two files would share a module name.
+pub fn expand_doctests(input: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = doctests::parse::parse_doctests(input)?;
+    let model = doctests::analyze::analyze_doctests(parsed)?;
+    let ir = doctests::lower::lower_doctests(model);
+    Ok(doctests::codegen::codegen_doctests(ir))
+}
+
+/// Expands `#[automock]` applied to a trait.
+///
+/// # Errors
+///
+/// Returns an error if the attribute is given arguments or the trait has
+/// items that cannot be mocked.
+pub fn expand_automock(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = mock::parse::parse_mock(attr, item)?;
+    let model = mock::analyze::analyze_mock(parsed)?;
+    let ir = mock::lower::lower_mock(model);
+    Ok(mock::codegen::codegen_mock(ir))
+}
+
+/// Expands `#[derive(TestBuilder)]` applied to a struct.
+///
+/// # Errors
+///
+/// Returns an error if the item is not a struct with named fields, a
+/// `#[test_builder]` attribute is malformed, or a field's name clashes with
+/// a method of the builder.
+pub fn expand_test_builder(input: TokenStream2) -> syn::Result<TokenStream2> {
+    let parsed = test_builder::parse::parse_test_builder(input)?;
+    let model = test_builder::analyze::analyze_test_builder(parsed)?;
+    let ir = test_builder::lower::lower_test_builder(model);
+    Ok(test_builder::codegen::codegen_test_builder(ir))
+}
@@ -0,0 +1,269 @@
+//! Stage 2: validate the trait and collect the methods to mock.
+
+use syn::spanned::Spanned;
+use syn::{
+    Error, FnArg, GenericArgument, ItemTrait, Pat, PathArguments, Result, ReturnType, Signature,
+    TraitItem, Type,
+};
+
+use super::parse::ParsedMock;
+
+/// A trait every method of which can be mocked.
+pub struct MockModel {
+    pub item: ItemTrait,
+    pub methods: Vec<MethodModel>,
+}
+
+pub struct MethodModel {
+    pub sig: Signature,
+    /// Name of each parameter after `self`, for failure messages, and its
+    /// type.
+    pub params: Vec<(String, Type)>,
+    /// The return type, or `None` for methods returning `()`.
+    pub output: Option<Type>,
+}
+
+pub fn analyze_mock(parsed: ParsedMock) -> Result<MockModel> {
+    let ParsedMock { item } = parsed;
+
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "`#[automock]` does not support generic traits",
+        ));
+    }
+    let mut methods = Vec::new();
+    for trait_item in &item.items {
+        match trait_item {
+            TraitItem::Fn(method) => methods.push(analyze_method(&method.sig)?),
+            TraitItem::Type(ty) => {
+                return Err(Error::new(
+                    ty.span(),
+                    "`#[automock]` does not support associated types",
+                ))
+            }
+            TraitItem::Const(constant) => {
+                return Err(Error::new(
+                    constant.span(),
+                    "`#[automock]` does not support associated constants",
+                ))
+            }
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "`#[automock]` supports only methods",
+                ))
+            }
+        }
+    }
+
+    Ok(MockModel { item, methods })
+}
+
+fn analyze_method(sig: &Signature) -> Result<MethodModel> {
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "`#[automock]` does not support generic methods",
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(Error::new(
+            variadic.span(),
+            "`#[automock]` does not support variadic methods",
+        ));
+    }
+    match sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) if receiver.colon_token.is_none() => {}
+        Some(FnArg::Receiver(receiver)) => {
+            return Err(Error::new(
+                receiver.span(),
+                "`#[automock]` supports only `self`, `&self`, and `&mut self` receivers",
+            ))
+        }
+        _ => {
+            return Err(Error::new(
+                sig.ident.span(),
+                "`#[automock]` cannot mock associated functions without a `self` receiver",
+            ))
+        }
+    }
+
+    let mut params = Vec::new();
+    for (index, input) in sig.inputs.iter().skip(1).enumerate() {
+        let FnArg::Typed(arg) = input else {
+            return Err(Error::new(input.span(), "unexpected second receiver"));
+        };
+        check_type(&arg.ty)?;
+        let name = match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.to_string(),
+            _ => format!("argument {}", index + 1),
+        };
+        params.push((name, (*arg.ty).clone()));
+    }
+    let output = match &sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) if matches!(&**ty, Type::Tuple(tuple) if tuple.elems.is_empty()) => {
+            None
+        }
+        ReturnType::Type(_, ty) => {
+            check_type(ty)?;
+            Some((**ty).clone())
+        }
+    };
+
+    Ok(MethodModel {
+        sig: sig.clone(),
+        params,
+        output,
+    })
+}
+
+/// Rejects the types a mock cannot store closures over: `impl Trait`, which
+/// makes a method generic, and paths into `Self`, which the mock does not
+/// define.
+fn check_type(ty: &Type) -> Result<()> {
+    match ty {
+        Type::ImplTrait(ty) => Err(Error::new(
+            ty.span(),
+            "`#[automock]` does not support `impl Trait` in method signatures",
+        )),
+        Type::Path(path) => {
+            if path.qself.is_some()
+                || (path.path.segments.len() > 1
+                    && path
+                        .path
+                        .segments
+                        .first()
+                        .is_some_and(|s| s.ident == "Self"))
+            {
+                return Err(Error::new(
+                    path.span(),
+                    "`#[automock]` does not support associated types",
+                ));
+            }
+            for segment in &path.path.segments {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    for arg in &args.args {
+                        if let GenericArgument::Type(ty) = arg {
+                            check_type(ty)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Type::Reference(reference) => check_type(&reference.elem),
+        Type::Ptr(ptr) => check_type(&ptr.elem),
+        Type::Slice(slice) => check_type(&slice.elem),
+        Type::Array(array) => check_type(&array.elem),
+        Type::Paren(paren) => check_type(&paren.elem),
+        Type::Group(group) => check_type(&group.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().try_for_each(check_type),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+    use crate::mock::parse::parse_mock;
+
+    fn analyze(item: proc_macro2::TokenStream) -> Result<MockModel> {
+        analyze_mock(parse_mock(quote!(), item)?)
+    }
+
+    fn error(item: proc_macro2::TokenStream) -> String {
+        analyze(item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn collects_the_methods() {
+        let model = analyze(quote! {
+            trait Store {
+                fn get(&self, key: &str) -> Option<String>;
+                fn put(&mut self, key: String, (a, b): (u8, u8)) -> ();
+            }
+        })
+        .unwrap();
+        assert_eq!(model.methods.len(), 2);
+        let get = &model.methods[0];
+        assert_eq!(get.params[0].0, "key");
+        assert_eq!(
+            get.output.to_token_stream().to_string(),
+            "Option < String >"
+        );
+        let put = &model.methods[1];
+        let names: Vec<_> = put.params.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["key", "argument 2"]);
+        assert!(put.output.is_none());
+    }
+
+    #[test]
+    fn rejects_what_cannot_be_mocked() {
+        assert_eq!(
+            error(quote!(
+                trait Store<T> {}
+            )),
+            "`#[automock]` does not support generic traits"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    type Key;
+                }
+            )),
+            "`#[automock]` does not support associated types"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    const N: usize;
+                }
+            )),
+            "`#[automock]` does not support associated constants"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    fn get<K>(&self, key: K);
+                }
+            )),
+            "`#[automock]` does not support generic methods"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    fn new() -> Self;
+                }
+            )),
+            "`#[automock]` cannot mock associated functions without a `self` receiver"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    fn get(self: Box<Self>);
+                }
+            )),
+            "`#[automock]` supports only `self`, `&self`, and `&mut self` receivers"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    fn keys(&self) -> impl Iterator<Item = u8>;
+                }
+            )),
+            "`#[automock]` does not support `impl Trait` in method signatures"
+        );
+        assert_eq!(
+            error(quote!(
+                trait Store {
+                    fn key(&self) -> Option<Self::Key>;
+                }
+            )),
+            "`#[automock]` does not support associated types"
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Stage 4: emit the trait, the mock type, and its expectation types.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::{MethodIR, MockIR};
+
+pub fn codegen_mock(ir: MockIR) -> TokenStream2 {
+    let MockIR {
+        item,
+        mock,
+        vis,
+        methods,
+    } = ir;
+    let trait_ident = &item.ident;
+    let unsafety = &item.unsafety;
+    let mock_name = mock.to_string();
+    let doc = format!(
+        "Mock implementation of [`{}`], generated by `#[automock]`.",
+        trait_ident
+    );
+
+    let fields = methods.iter().map(|method| {
+        let MethodIR { field, .. } = method;
+        let (matcher, action) = closure_types(method);
+        quote! {
+            #field: ::tust::__private::mock::Expectations<#matcher, #action>
+        }
+    });
+    let inits = methods.iter().map(|MethodIR { field, name, .. }| {
+        quote! { #field: ::tust::__private::mock::Expectations::new(#name) }
+    });
+    let expects = methods.iter().map(
+        |MethodIR {
+             field,
+             expect,
+             wrapper,
+             ..
+         }| {
+            let doc = format!(
+                "Adds an expectation of a call to `{}`, expected at least once unless \
+                 configured otherwise.",
+                field
+            );
+            quote! {
+                #[doc = #doc]
+                #[track_caller]
+                #vis fn #expect(&mut self) -> #wrapper<'_> {
+                    #wrapper(self.#field.push())
+                }
+            }
+        },
+    );
+    let field_idents: Vec<_> = methods.iter().map(|method| &method.field).collect();
+    let wrappers = methods.iter().map(|method| wrapper(method, &vis));
+    let impls = methods.iter().map(method_impl);
+
+    quote! {
+        #item
+
+        #[doc = #doc]
+        #vis struct #mock {
+            #(#fields,)*
+        }
+
+        impl #mock {
+            /// Creates a mock without expectations.
+            #vis fn new() -> Self {
+                #mock {
+                    #(#inits,)*
+                }
+            }
+
+            #(#expects)*
+
+            /// Verifies the expectations added so far, failing the test for
+            /// each called fewer times than it expects, and removes them.
+            #vis fn checkpoint(&mut self) {
+                #(self.#field_idents.checkpoint();)*
+            }
+        }
+
+        impl ::core::default::Default for #mock {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl ::core::fmt::Debug for #mock {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(#mock_name).finish_non_exhaustive()
+            }
+        }
+
+        impl ::core::ops::Drop for #mock {
+            fn drop(&mut self) {
+                #(self.#field_idents.verify();)*
+            }
+        }
+
+        #unsafety impl #trait_ident for #mock {
+            #(#impls)*
+        }
+
+        #(#wrappers)*
+    }
+}
+
+/// The unsized types of a method's matcher, which borrows its arguments,
+/// and of its action, which takes them.
+fn closure_types(method: &MethodIR) -> (TokenStream2, TokenStream2) {
+    let tys: Vec<_> = method.params.iter().map(|param| &param.ty).collect();
+    let output = match &method.output {
+        Some(ty) => quote! { #ty },
+        None => quote! { () },
+    };
+    (
+        quote! { dyn ::core::ops::Fn(#(&#tys),*) -> bool + ::core::marker::Send },
+        quote! { dyn ::core::ops::FnMut(#(#tys),*) -> #output + ::core::marker::Send },
+    )
+}
+
+/// The type configuring one expectation of `method`.
+fn wrapper(method: &MethodIR, vis: &syn::Visibility) -> TokenStream2 {
+    let MethodIR {
+        wrapper,
+        params,
+        output,
+        ..
+    } = method;
+    let (matcher, action) = closure_types(method);
+    let tys: Vec<_> = params.iter().map(|param| &param.ty).collect();
+    let output_ty = match output {
+        Some(ty) => quote! { #ty },
+        None => quote! { () },
+    };
+
+    let matching = (!params.is_empty()).then(|| {
+        let matchers: Vec<_> = (0..params.len())
+            .map(|index| quote::format_ident!("__matcher{}", index))
+            .collect();
+        let args: Vec<_> = (0..params.len())
+            .map(|index| quote::format_ident!("__arg{}", index))
+            .collect();
+        let bounds = params.iter().map(|param| {
+            let named = &param.named;
+            let lifetimes = &param.lifetimes;
+            let hrtb = (!lifetimes.is_empty()).then(|| quote! { for<#(#lifetimes),*> });
+            quote! {
+                impl #hrtb ::tust::matchers::Matcher<#named>
+                    + ::core::marker::Send
+                    + 'static
+            }
+        });
+        let conditions = params.iter().zip(&matchers).map(|(param, matcher)| {
+            let name = &param.name;
+            let concrete = &param.concrete;
+            quote! {
+                ::tust::__private::mock::condition(
+                    #name,
+                    ::tust::matchers::Matcher::<#concrete>::describe(&#matcher),
+                )
+            }
+        });
+        quote! {
+            /// Applies the expectation only to calls whose arguments are
+            /// accepted by the matchers, one per argument.
+            #vis fn with(self, #(#matchers: #bounds),*) -> Self {
+                let conditions = ::std::vec![#(#conditions),*];
+                self.0.set_matcher(
+                    ::std::boxed::Box::new(move |#(#args: &#tys),*| {
+                        true #(&& ::tust::matchers::Matcher::matches(&#matchers, #args))*
+                    }),
+                    conditions,
+                );
+                self
+            }
+
+            /// Applies the expectation only to calls for whose borrowed
+            /// arguments `predicate` returns `true`.
+            #vis fn withf(
+                self,
+                predicate: impl ::core::ops::Fn(#(&#tys),*) -> bool + ::core::marker::Send + 'static,
+            ) -> Self {
+                self.0.set_matcher(
+                    ::std::boxed::Box::new(predicate),
+                    ::tust::__private::mock::predicate(),
+                );
+                self
+            }
+        }
+    });
+    let return_const = output.as_ref().map(|output| {
+        quote! {
+            /// Returns a clone of `value` from every call.
+            #vis fn return_const<V>(self, value: V) -> Self
+            where
+                V: ::core::convert::Into<#output>
+                    + ::core::clone::Clone
+                    + ::core::marker::Send
+                    + 'static,
+            {
+                self.0.set_action(::std::boxed::Box::new(move |#(_: #tys),*| {
+                    ::core::clone::Clone::clone(&value).into()
+                }));
+                self
+            }
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #vis struct #wrapper<'__tust>(
+            &'__tust mut ::tust::__private::mock::Expectation<#matcher, #action>,
+        );
+
+        impl #wrapper<'_> {
+            #matching
+
+            /// Answers every call with the value `action` computes from its
+            /// arguments.
+            #vis fn returning(
+                self,
+                action: impl ::core::ops::FnMut(#(#tys),*) -> #output_ty
+                    + ::core::marker::Send
+                    + 'static,
+            ) -> Self {
+                self.0.set_action(::std::boxed::Box::new(action));
+                self
+            }
+
+            #return_const
+
+            /// Sets how many calls are expected: a number, or a range such as
+            /// `1..=3`, or `..` for any number.
+            #vis fn times(self, times: impl ::core::convert::Into<::tust::__private::mock::Times>) -> Self {
+                self.0.set_times(times);
+                self
+            }
+
+            /// Expects exactly one call.
+            #vis fn once(self) -> Self {
+                self.times(1)
+            }
+
+            /// Expects no calls.
+            #vis fn never(self) -> Self {
+                self.times(0)
+            }
+        }
+    }
+}
+
+/// The mock's implementation of one trait method.
+fn method_impl(method: &MethodIR) -> TokenStream2 {
+    let MethodIR {
+        sig,
+        field,
+        args,
+        output,
+        ..
+    } = method;
+    let call = quote! {
+        self.#field.call(
+            (#(#args,)*),
+            |__matcher, (#(#args,)*)| __matcher(#(#args),*),
+            |(#(#args,)*)| {
+                use ::tust::__private::mock::{DebugArg as _, OpaqueArg as _};
+                ::std::vec![#((&&::tust::__private::mock::Arg(#args)).render()),*]
+            },
+            |__action, (#(#args,)*)| __action(#(#args),*),
+        )
+    };
+    let body = match output {
+        Some(_) => quote! {
+            let __value = #call;
+            self.#field.returned(__value)
+        },
+        None => quote! {
+            let _ = #call;
+        },
+    };
+
+    quote! {
+        #sig {
+            #body
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::mock::analyze::analyze_mock;
+    use crate::mock::lower::lower_mock;
+    use crate::mock::parse::parse_mock;
+
+    #[test]
+    fn emits_the_trait_the_mock_and_its_impl() {
+        let item = quote!(
+            pub trait Store {
+                fn get(&self, key: &str) -> u8;
+            }
+        );
+        let ir = lower_mock(analyze_mock(parse_mock(quote!(), item).unwrap()).unwrap());
+        let items = syn::parse2::<File>(codegen_mock(ir)).unwrap().items;
+        assert!(matches!(&items[0], Item::Trait(item) if item.ident == "Store"));
+        assert!(items
+            .iter()
+            .any(|item| matches!(item, Item::Struct(mock) if mock.ident == "MockStore")));
+        let impls: Vec<_> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Impl(imp) => Some(imp.to_token_stream().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert!(impls
+            .iter()
+            .any(|imp| imp.starts_with("impl Store for MockStore")));
+        assert!(impls
+            .iter()
+            .any(|imp| imp.contains("fn expect_get (& mut self) -> __MockStore_get < '_ >")));
+    }
+}
@@ -0,0 +1,250 @@
+//! Stage 3: lower the trait model into the mock type to generate.
+
+use proc_macro2::Span;
+use quote::format_ident;
+use syn::{
+    FnArg, GenericArgument, Ident, ItemTrait, Lifetime, Pat, PatIdent, PathArguments, Signature,
+    Type, Visibility,
+};
+
+use super::analyze::{MethodModel, MockModel};
+
+pub struct MockIR {
+    /// The user's trait, emitted unchanged.
+    pub item: ItemTrait,
+    /// `Mock` followed by the trait's name.
+    pub mock: Ident,
+    pub vis: Visibility,
+    pub methods: Vec<MethodIR>,
+}
+
+pub struct MethodIR {
+    /// The method's signature, with its parameters after `self` renamed to
+    /// `args`.
+    pub sig: Signature,
+    /// The method as named in failure messages, e.g. `MockStore::get`.
+    pub name: String,
+    /// The mock's field holding the method's expectations.
+    pub field: Ident,
+    /// The mock's method adding an expectation, e.g. `expect_get`.
+    pub expect: Ident,
+    /// The type configuring one expectation.
+    pub wrapper: Ident,
+    pub args: Vec<Ident>,
+    pub params: Vec<ParamIR>,
+    /// The return type with elided lifetimes made `'static`, or `None` for
+    /// methods returning `()`.
+    pub output: Option<Type>,
+}
+
+pub struct ParamIR {
+    pub name: String,
+    /// The parameter's type, with `Self` replaced by the mock.
+    pub ty: Type,
+    /// `ty` with its elided lifetimes named by `lifetimes`, for the bound of
+    /// a matcher accepting it for every lifetime.
+    pub named: Type,
+    pub lifetimes: Vec<Lifetime>,
+    /// `ty` with its elided lifetimes made `'static`.
+    pub concrete: Type,
+}
+
+pub fn lower_mock(model: MockModel) -> MockIR {
+    let MockModel { item, methods } = model;
+    let mock = format_ident!("Mock{}", item.ident);
+    let methods = methods
+        .into_iter()
+        .map(|method| lower_method(method, &mock))
+        .collect();
+
+    MockIR {
+        vis: item.vis.clone(),
+        item,
+        mock,
+        methods,
+    }
+}
+
+fn lower_method(method: MethodModel, mock: &Ident) -> MethodIR {
+    let MethodModel {
+        mut sig,
+        params,
+        output,
+    } = method;
+    let ident = sig.ident.clone();
+    let args: Vec<Ident> = (0..params.len())
+        .map(|index| format_ident!("__arg{}", index))
+        .collect();
+    for (input, arg) in sig.inputs.iter_mut().skip(1).zip(&args) {
+        if let FnArg::Typed(typed) = input {
+            *typed.pat = Pat::Ident(PatIdent {
+                attrs: Vec::new(),
+                by_ref: None,
+                mutability: None,
+                ident: arg.clone(),
+                subpat: None,
+            });
+        }
+    }
+    let params = params
+        .into_iter()
+        .map(|(name, mut ty)| {
+            replace_self(&mut ty, mock);
+            let mut lifetimes = Vec::new();
+            let mut named = ty.clone();
+            rewrite_elided(&mut named, &mut || {
+                let lifetime =
+                    Lifetime::new(&format!("'__tust{}", lifetimes.len()), Span::call_site());
+                lifetimes.push(lifetime.clone());
+                lifetime
+            });
+            let mut concrete = ty.clone();
+            rewrite_elided(&mut concrete, &mut || {
+                Lifetime::new("'static", Span::call_site())
+            });
+            ParamIR {
+                name,
+                ty,
+                named,
+                lifetimes,
+                concrete,
+            }
+        })
+        .collect();
+    let output = output.map(|mut ty| {
+        replace_self(&mut ty, mock);
+        rewrite_elided(&mut ty, &mut || Lifetime::new("'static", Span::call_site()));
+        ty
+    });
+
+    MethodIR {
+        name: format!("{}::{}", mock, ident),
+        expect: format_ident!("expect_{}", ident),
+        wrapper: format_ident!("__{}_{}", mock, ident),
+        field: ident,
+        sig,
+        args,
+        params,
+        output,
+    }
+}
+
+/// Replaces `Self` in `ty` with the mock implementing the trait.
+fn replace_self(ty: &mut Type, mock: &Ident) {
+    match ty {
+        Type::Path(path) if path.qself.is_none() && path.path.is_ident("Self") => {
+            *ty = syn::parse_quote!(#mock);
+        }
+        _ => for_each_child(ty, &mut |child| replace_self(child, mock)),
+    }
+}
+
+/// Gives every lifetime elided in `ty`, as in `&str` or `Cow<'_, str>`, the
+/// lifetime `name` returns.
+fn rewrite_elided(ty: &mut Type, name: &mut dyn FnMut() -> Lifetime) {
+    match ty {
+        Type::Reference(reference) => {
+            match &mut reference.lifetime {
+                Some(lifetime) if lifetime.ident == "_" => *lifetime = name(),
+                Some(_) => {}
+                None => reference.lifetime = Some(name()),
+            }
+            rewrite_elided(&mut reference.elem, name);
+        }
+        Type::Path(path) => {
+            for segment in &mut path.path.segments {
+                if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                    for arg in &mut args.args {
+                        match arg {
+                            GenericArgument::Lifetime(lifetime) if lifetime.ident == "_" => {
+                                *lifetime = name();
+                            }
+                            GenericArgument::Type(ty) => rewrite_elided(ty, name),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        _ => for_each_child(ty, &mut |child| rewrite_elided(child, name)),
+    }
+}
+
+/// Calls `f` with every type directly nested in `ty`.
+fn for_each_child(ty: &mut Type, f: &mut dyn FnMut(&mut Type)) {
+    match ty {
+        Type::Reference(reference) => f(&mut reference.elem),
+        Type::Ptr(ptr) => f(&mut ptr.elem),
+        Type::Slice(slice) => f(&mut slice.elem),
+        Type::Array(array) => f(&mut array.elem),
+        Type::Paren(paren) => f(&mut paren.elem),
+        Type::Group(group) => f(&mut group.elem),
+        Type::Tuple(tuple) => tuple.elems.iter_mut().for_each(f),
+        Type::Path(path) => {
+            for segment in &mut path.path.segments {
+                if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                    for arg in &mut args.args {
+                        if let GenericArgument::Type(ty) = arg {
+                            f(ty);
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+    use crate::mock::analyze::analyze_mock;
+    use crate::mock::parse::parse_mock;
+
+    fn lower(item: proc_macro2::TokenStream) -> MockIR {
+        lower_mock(analyze_mock(parse_mock(quote!(), item).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn names_the_mock_and_its_methods_after_the_trait() {
+        let ir = lower(quote!(
+            pub trait Store {
+                fn get(&self, key: &str) -> u8;
+            }
+        ));
+        assert_eq!(ir.mock, "MockStore");
+        assert!(matches!(ir.vis, Visibility::Public(_)));
+        let get = &ir.methods[0];
+        assert_eq!(get.name, "MockStore::get");
+        assert_eq!(get.field, "get");
+        assert_eq!(get.expect, "expect_get");
+        assert_eq!(get.wrapper, "__MockStore_get");
+        assert_eq!(get.args, ["__arg0"]);
+        assert_eq!(
+            get.sig.to_token_stream().to_string(),
+            "fn get (& self , __arg0 : & str) -> u8"
+        );
+    }
+
+    #[test]
+    fn names_elided_lifetimes_and_replaces_self() {
+        let ir = lower(quote! {
+            trait Store {
+                fn merge(&self, other: &Self, parts: Vec<Cow<'_, str>>) -> &str;
+            }
+        });
+        let merge = &ir.methods[0];
+        let text = |ty: &Type| ty.to_token_stream().to_string();
+        assert_eq!(text(&merge.params[0].ty), "& MockStore");
+        assert_eq!(text(&merge.params[0].named), "& '__tust0 MockStore");
+        assert_eq!(text(&merge.params[0].concrete), "& 'static MockStore");
+        assert_eq!(
+            text(&merge.params[1].named),
+            "Vec < Cow < '__tust0 , str > >"
+        );
+        assert_eq!(merge.params[1].lifetimes.len(), 1);
+        assert_eq!(text(merge.output.as_ref().unwrap()), "& 'static str");
+    }
+}
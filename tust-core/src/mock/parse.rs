@@ -0,0 +1,63 @@
+//! Stage 1: parse the trait; `#[automock]` takes no attribute arguments.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Error, ItemTrait, Result};
+
+/// The raw, unvalidated input of an `#[automock]` invocation.
+pub struct ParsedMock {
+    pub item: ItemTrait,
+}
+
+/// Parses the trait `#[automock]` is applied to.
+pub fn parse_mock(attr: TokenStream2, item: TokenStream2) -> Result<ParsedMock> {
+    if !attr.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            "`#[automock]` does not take arguments",
+        ));
+    }
+    let item: ItemTrait = syn::parse2(item)?;
+
+    Ok(ParsedMock { item })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_trait() {
+        let parsed = parse_mock(
+            quote!(),
+            quote!(
+                trait Store {
+                    fn get(&self) -> u8;
+                }
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.item.ident, "Store");
+    }
+
+    #[test]
+    fn rejects_arguments_and_other_items() {
+        let err = parse_mock(
+            quote!(strict),
+            quote!(
+                trait Store {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "`#[automock]` does not take arguments");
+        assert!(parse_mock(
+            quote!(),
+            quote!(
+                struct Store;
+            )
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Function parameters of tests and fixtures.
+//!
+//! Parameters that are not bound by a test case are injected from the
+//! runtime's fixture resolver: each is looked up by name and must have the
+//! type the fixture provides.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
+use syn::{Error, FnArg, Ident, Pat, Result, Type};
+
+/// A named function parameter.
+#[derive(Clone)]
+pub struct Param {
+    pub ident: Ident,
+    pub ty: Type,
+}
+
+/// Collects the parameters of a function, which must all be plain bindings.
+pub fn named_params(inputs: &Punctuated<FnArg, Comma>) -> Result<Vec<Param>> {
+    inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Receiver(receiver) => Err(Error::new(
+                receiver.span(),
+                "expected a named parameter, found `self`",
+            )),
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) if pat.subpat.is_none() && pat.by_ref.is_none() => Ok(Param {
+                    ident: pat.ident.clone(),
+                    ty: (*arg.ty).clone(),
+                }),
+                pat => Err(Error::new(
+                    pat.span(),
+                    "expected a named parameter; the name selects the fixture to inject",
+                )),
+            },
+        })
+        .collect()
+}
+
+/// The names of the parameters of a function whose parameters
+/// [`named_params`] accepts, in order.
+pub fn names(inputs: &Punctuated<FnArg, Comma>) -> Vec<Ident> {
+    inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => Some(pat.ident.clone()),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Emits a `&[FixtureRequest]` literal describing `params`.
+pub fn requests(params: &[Param]) -> TokenStream2 {
+    let requests = params.iter().map(|Param { ident, ty }| {
+        let name = ident.to_string();
+        quote! {
+            ::tust::__private::FixtureRequest {
+                name: #name,
+                type_name: ::core::any::type_name::<#ty>,
+                type_id: ::core::any::TypeId::of::<#ty>,
+            }
+        }
+    });
+    quote! { &[#(#requests),*] }
+}
+
+/// Emits `let` bindings resolving every parameter through `fixtures`, which
+/// must name a `&mut Fixtures` in scope of a function returning
+/// `Result<_, FixtureError>`.
+pub fn resolve(params: &[Param], fixtures: &Ident) -> TokenStream2 {
+    let bindings = params.iter().map(|Param { ident, ty }| {
+        let name = ident.to_string();
+        quote_spanned! {ty.span()=>
+            let #ident: #ty = #fixtures.get::<#ty>(#name)?;
+        }
+    });
+    quote! { #(#bindings)* }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::format_ident;
+    use syn::{parse_quote, ItemFn};
+
+    use super::*;
+
+    fn params(func: ItemFn) -> Result<Vec<Param>> {
+        named_params(&func.sig.inputs)
+    }
+
+    #[test]
+    fn named_parameters() {
+        let func: ItemFn = parse_quote!(
+            fn uses(db: Db, mut count: usize) {}
+        );
+        let params = named_params(&func.sig.inputs).unwrap();
+        let idents: Vec<_> = params.iter().map(|param| param.ident.clone()).collect();
+        assert_eq!(idents, ["db", "count"]);
+        assert_eq!(names(&func.sig.inputs), idents);
+    }
+
+    #[test]
+    fn rejects_parameters_without_a_name() {
+        let error = |func| params(func).err().unwrap().to_string();
+        assert_eq!(
+            error(parse_quote!(
+                fn uses((a, b): (u8, u8)) {}
+            )),
+            "expected a named parameter; the name selects the fixture to inject"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                fn uses(ref db: Db) {}
+            )),
+            "expected a named parameter; the name selects the fixture to inject"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                fn uses(&self) {}
+            )),
+            "expected a named parameter, found `self`"
+        );
+    }
+
+    #[test]
+    fn resolves_each_parameter_by_name() {
+        let func: ItemFn = parse_quote!(
+            fn uses(db: Db) {}
+        );
+        let params = named_params(&func.sig.inputs).unwrap();
+        let resolve = resolve(&params, &format_ident!("fixtures")).to_string();
+        assert_eq!(
+            resolve,
+            "let db : Db = fixtures . get :: < Db > (\"db\") ? ;"
+        );
+        let requests = requests(&params).to_string();
+        assert!(requests.contains("name : \"db\""), "{}", requests);
+        assert!(requests.contains("type_id : :: core :: any :: TypeId :: of :: < Db >"));
+    }
+}
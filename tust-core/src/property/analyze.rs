@@ -0,0 +1,187 @@
+//! Stage 2: validate the property function and its arguments.
+
+use syn::spanned::Spanned;
+use syn::{Error, FnArg, ItemFn, LitInt, Pat, Result, ReturnType};
+
+use super::parse::ParsedProperty;
+use crate::params::Param;
+
+/// The most parameters a property can take; inputs are generated as a tuple.
+const MAX_PARAMS: usize = 8;
+
+/// A validated property.
+pub struct PropertyModel {
+    pub func: ItemFn,
+    /// The generated inputs, in declaration order.
+    pub params: Vec<Param>,
+    pub cases: Option<LitInt>,
+    pub seed: Option<LitInt>,
+}
+
+pub fn analyze_property(parsed: ParsedProperty) -> Result<PropertyModel> {
+    let ParsedProperty { args, func } = parsed;
+    let sig = &func.sig;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "properties cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(asyncness.span(), "properties cannot be async"));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(ty.span(), "properties must return `()`"));
+    }
+
+    let params = sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) if pat.subpat.is_none() && pat.by_ref.is_none() => Ok(Param {
+                    ident: pat.ident.clone(),
+                    ty: (*arg.ty).clone(),
+                }),
+                pat => Err(Error::new(
+                    pat.span(),
+                    "expected a named parameter; the name labels the input in counterexamples",
+                )),
+            },
+            FnArg::Receiver(receiver) => Err(Error::new(
+                receiver.span(),
+                "expected a named parameter, found `self`",
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if params.is_empty() {
+        return Err(Error::new(
+            sig.ident.span(),
+            "a property takes at least one parameter to generate; use `#[tust::test]` otherwise",
+        ));
+    }
+    if params.len() > MAX_PARAMS {
+        return Err(Error::new(
+            sig.inputs.span(),
+            format!("a property takes at most {} parameters", MAX_PARAMS),
+        ));
+    }
+
+    if let Some(cases) = &args.cases {
+        if cases.base10_parse::<u32>()? == 0 {
+            return Err(Error::new(cases.span(), "`cases` must be at least 1"));
+        }
+    }
+    if let Some(seed) = &args.seed {
+        seed.base10_parse::<u64>()?;
+    }
+
+    Ok(PropertyModel {
+        func,
+        params,
+        cases: args.cases,
+        seed: args.seed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::property::parse::parse_property;
+
+    fn analyze(
+        attr: proc_macro2::TokenStream,
+        item: proc_macro2::TokenStream,
+    ) -> Result<PropertyModel> {
+        analyze_property(parse_property(attr, item)?)
+    }
+
+    fn error(attr: proc_macro2::TokenStream, item: proc_macro2::TokenStream) -> String {
+        analyze(attr, item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn generates_every_parameter() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                fn p(a: u8, b: Vec<u8>) {}
+            ),
+        )
+        .unwrap();
+        let names: Vec<_> = model.params.iter().map(|p| p.ident.to_string()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_functions_that_cannot_be_properties() {
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn p() {}
+                )
+            ),
+            "a property takes at least one parameter to generate; use `#[tust::test]` otherwise"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn p(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8, h: u8, i: u8) {}
+                )
+            ),
+            "a property takes at most 8 parameters"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn p((a, b): (u8, u8)) {}
+                )
+            ),
+            "expected a named parameter; the name labels the input in counterexamples"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn p<T>(n: T) {}
+                )
+            ),
+            "properties cannot be generic"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    async fn p(n: u8) {}
+                )
+            ),
+            "properties cannot be async"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn p(n: u8) -> bool {
+                        true
+                    }
+                )
+            ),
+            "properties must return `()`"
+        );
+        assert_eq!(
+            error(
+                quote!(cases = 0),
+                quote!(
+                    fn p(n: u8) {}
+                )
+            ),
+            "`cases` must be at least 1"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Stage 4: emit the test running the property checker.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::PropertyIR;
+use crate::params::Param;
+
+pub fn codegen_property(ir: PropertyIR) -> TokenStream2 {
+    let PropertyIR {
+        ident,
+        attrs,
+        body,
+        params,
+        cases,
+        seed,
+    } = ir;
+    let body_ident = &body.sig.ident;
+    let names: Vec<_> = params.iter().map(|Param { ident, .. }| ident).collect();
+    let labels = names.iter().map(|name| name.to_string());
+    let types: Vec<_> = params.iter().map(|Param { ty, .. }| ty).collect();
+
+    let cases = match cases {
+        Some(cases) => quote! { ::core::option::Option::Some(#cases) },
+        None => quote! { ::core::option::Option::None },
+    };
+    let seed = match seed {
+        Some(seed) => quote! { ::core::option::Option::Some(#seed) },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    quote! {
+        #[::tust::test]
+        #(#attrs)*
+        fn #ident() {
+            #body
+
+            ::tust::__private::check_property(
+                &::tust::__private::PropertyConfig {
+                    cases: #cases,
+                    seed: #seed,
+                },
+                |(#(#names,)*): (#(#types,)*)| #body_ident(#(#names),*),
+                |(#(#names,)*): &(#(#types,)*)| {
+                    ::std::vec![#((#labels, ::std::format!("{:?}", #names))),*]
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::ItemFn;
+
+    use super::*;
+    use crate::property::analyze::analyze_property;
+    use crate::property::lower::lower_property;
+    use crate::property::parse::parse_property;
+
+    #[test]
+    fn expands_to_a_test_running_the_checker() {
+        let parsed = parse_property(
+            quote!(seed = 7),
+            quote!(
+                fn adds(a: u8, b: u8) {}
+            ),
+        )
+        .unwrap();
+        let tokens = codegen_property(lower_property(analyze_property(parsed).unwrap()));
+        let test: ItemFn = syn::parse2(tokens).unwrap();
+        assert_eq!(test.sig.ident, "adds");
+        assert!(test.sig.inputs.is_empty());
+        let attr = test.attrs[0].path();
+        assert_eq!(quote!(#attr).to_string(), ":: tust :: test");
+        let body = quote!(#test).to_string();
+        assert!(
+            body.contains("seed : :: core :: option :: Option :: Some (7)"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("| (a , b ,) : (u8 , u8 ,) | __tust_property (a , b)"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("(\"a\" , :: std :: format ! (\"{:?}\" , a))"),
+            "{}",
+            body
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Stage 3: split the property into the test to register and the function
+//! checked against generated inputs.
+
+use proc_macro2::{Ident, Span};
+use syn::{Attribute, ItemFn, LitInt, Visibility};
+
+use super::analyze::PropertyModel;
+use crate::params::Param;
+
+pub struct PropertyIR {
+    /// Name of the registered test.
+    pub ident: Ident,
+    /// Attributes of the user's function, passed on to the test so that
+    /// `#[tust::test]` sees its helper attributes.
+    pub attrs: Vec<Attribute>,
+    /// The user's function, renamed and without attributes, nested in the
+    /// test.
+    pub body: ItemFn,
+    pub params: Vec<Param>,
+    pub cases: Option<LitInt>,
+    pub seed: Option<LitInt>,
+}
+
+pub fn lower_property(model: PropertyModel) -> PropertyIR {
+    let PropertyModel {
+        mut func,
+        params,
+        cases,
+        seed,
+    } = model;
+    let ident = func.sig.ident.clone();
+    let attrs = std::mem::take(&mut func.attrs);
+    func.vis = Visibility::Inherited;
+    func.sig.ident = Ident::new("__tust_property", Span::call_site());
+
+    PropertyIR {
+        ident,
+        attrs,
+        body: func,
+        params,
+        cases,
+        seed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::property::analyze::analyze_property;
+    use crate::property::parse::parse_property;
+
+    #[test]
+    fn moves_the_attributes_to_the_test() {
+        let parsed = parse_property(
+            quote!(),
+            quote! {
+                #[timeout(100)]
+                pub fn reverses(v: Vec<u8>) {}
+            },
+        )
+        .unwrap();
+        let ir = lower_property(analyze_property(parsed).unwrap());
+        assert_eq!(ir.ident, "reverses");
+        assert_eq!(ir.attrs.len(), 1);
+        assert!(ir.body.attrs.is_empty());
+        assert_eq!(ir.body.sig.ident, "__tust_property");
+        assert!(matches!(ir.body.vis, Visibility::Inherited));
+    }
+}
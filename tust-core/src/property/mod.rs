@@ -0,0 +1,10 @@
+//! Pipeline for the `#[tust::property]` attribute.
+//!
+//! A property expands to a `#[tust::test]` without parameters whose body
+//! hands the property to the runtime's property checker, so the helper
+//! attributes of tests, such as `#[timeout]` or `#[skip]`, apply unchanged.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
@@ -0,0 +1,90 @@
+//! Stage 1: parse the attribute arguments and the property function.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::{ItemFn, LitInt, Result};
+
+/// Arguments given to `#[tust::property(...)]`, kept as written until
+/// analysis.
+#[derive(Debug, Default)]
+pub struct PropertyArgs {
+    /// `cases = N`
+    pub cases: Option<LitInt>,
+    /// `seed = N`
+    pub seed: Option<LitInt>,
+}
+
+impl PropertyArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> Result<()> {
+        let slot = if meta.path.is_ident("cases") {
+            &mut self.cases
+        } else if meta.path.is_ident("seed") {
+            &mut self.seed
+        } else {
+            return Err(
+                meta.error("unsupported `#[tust::property]` argument; expected `cases` or `seed`")
+            );
+        };
+        if slot.is_some() {
+            return Err(meta.error("duplicate argument"));
+        }
+        *slot = Some(meta.value()?.parse()?);
+        Ok(())
+    }
+}
+
+/// The raw, unvalidated input of a `#[tust::property]` invocation.
+pub struct ParsedProperty {
+    pub args: PropertyArgs,
+    pub func: ItemFn,
+}
+
+/// Parses the attribute arguments and the function the attribute is applied to.
+pub fn parse_property(attr: TokenStream2, item: TokenStream2) -> Result<ParsedProperty> {
+    let mut args = PropertyArgs::default();
+    syn::meta::parser(|meta| args.parse_meta(meta)).parse2(attr)?;
+    let func: ItemFn = syn::parse2(item)?;
+
+    Ok(ParsedProperty { args, func })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_cases_and_seed() {
+        let parsed = parse_property(
+            quote!(cases = 50, seed = 7),
+            quote!(
+                fn p(n: u8) {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(parsed.args.cases.unwrap().base10_digits(), "50");
+        assert_eq!(parsed.args.seed.unwrap().base10_digits(), "7");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let error = |attr| {
+            parse_property(
+                attr,
+                quote!(
+                    fn p(n: u8) {}
+                ),
+            )
+            .err()
+            .unwrap()
+            .to_string()
+        };
+        assert_eq!(
+            error(quote!(runs = 5)),
+            "unsupported `#[tust::property]` argument; expected `cases` or `seed`"
+        );
+        assert_eq!(error(quote!(seed = 1, seed = 2)), "duplicate argument");
+    }
+}
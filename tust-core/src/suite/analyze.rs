@@ -0,0 +1,244 @@
+//! Stage 2: name the modules and tests after their descriptions, and check
+//! that the names are usable and distinct.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, Span};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::{Attribute, Block, Error, FnArg, Item, LitStr, Result, Token};
+
+use super::parse::{Entry, ParsedSuite};
+use crate::hook::HookKind;
+
+/// A suite or `describe` block with every entry named.
+pub struct SuiteModel {
+    pub entries: Vec<EntryModel>,
+}
+
+pub enum EntryModel {
+    Describe {
+        attrs: Vec<Attribute>,
+        ident: Ident,
+        body: SuiteModel,
+    },
+    It {
+        attrs: Vec<Attribute>,
+        asyncness: Option<Token![async]>,
+        ident: Ident,
+        params: Punctuated<FnArg, Comma>,
+        body: Block,
+    },
+    Hook {
+        attrs: Vec<Attribute>,
+        kind: HookKind,
+        ident: Ident,
+        body: Block,
+    },
+    Item(Box<Item>),
+}
+
+pub fn analyze_suite(parsed: ParsedSuite) -> Result<SuiteModel> {
+    // Names given so far in this block, with what gave them, to report
+    // clashes between descriptions that differ only in punctuation or case.
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut claim = |name: String, what: String, span: Span| match names.get(&name) {
+        Some(previous) => Err(Error::new(
+            span,
+            format!(
+                "{} is named `{}`, like {} in the same block; \
+                 descriptions must differ in more than punctuation and case",
+                what, name, previous
+            ),
+        )),
+        None => {
+            names.insert(name, what);
+            Ok(())
+        }
+    };
+    let mut hooks: HashMap<&'static str, usize> = HashMap::new();
+
+    let mut entries = Vec::new();
+    for entry in parsed.entries {
+        entries.push(match entry {
+            Entry::Describe {
+                attrs,
+                description,
+                body,
+            } => {
+                let ident = ident_for("describe", &description)?;
+                claim(
+                    ident.to_string(),
+                    format!("`describe {:?}`", description.value()),
+                    description.span(),
+                )?;
+                EntryModel::Describe {
+                    attrs,
+                    ident,
+                    body: analyze_suite(body)?,
+                }
+            }
+            Entry::It {
+                attrs,
+                asyncness,
+                description,
+                params,
+                body,
+            } => {
+                let ident = ident_for("it", &description)?;
+                claim(
+                    ident.to_string(),
+                    format!("`it {:?}`", description.value()),
+                    description.span(),
+                )?;
+                EntryModel::It {
+                    attrs,
+                    asyncness,
+                    ident,
+                    params,
+                    body,
+                }
+            }
+            Entry::Hook {
+                attrs,
+                kind,
+                span,
+                body,
+            } => {
+                // Several hooks of a kind run in the order they are written,
+                // as with the hook attributes, so number the later ones.
+                let count = hooks.entry(kind.attribute()).or_insert(0);
+                *count += 1;
+                let name = match *count {
+                    1 => kind.attribute().to_string(),
+                    count => format!("{}_{}", kind.attribute(), count),
+                };
+                claim(name.clone(), format!("a `{}` hook", kind.attribute()), span)?;
+                EntryModel::Hook {
+                    attrs,
+                    kind,
+                    ident: Ident::new(&name, span),
+                    body,
+                }
+            }
+            Entry::Item(item) => EntryModel::Item(item),
+        });
+    }
+    Ok(SuiteModel { entries })
+}
+
+/// The identifier a description names its module or test with:
+/// `"handles empty input"` becomes `handles_empty_input`, and `"2 items"`,
+/// after `it`, becomes `it_2_items`.
+fn ident_for(keyword: &str, description: &LitStr) -> Result<Ident> {
+    let mut name = String::new();
+    for c in description.value().chars() {
+        if c.is_alphanumeric() {
+            name.extend(c.to_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let mut name = name.trim_end_matches('_').to_string();
+    if name.is_empty() {
+        return Err(Error::new(
+            description.span(),
+            "descriptions must contain a letter or a digit",
+        ));
+    }
+    if name.starts_with(|c: char| c.is_numeric()) {
+        name = format!("{}_{}", keyword, name);
+    }
+    // Keywords such as `type` or `match` cannot name a module or function.
+    if syn::parse_str::<Ident>(&name).is_err() {
+        name.push('_');
+    }
+    Ok(Ident::new(&name, description.span()))
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::suite::parse::parse_suite;
+
+    fn analyze(input: proc_macro2::TokenStream) -> Result<SuiteModel> {
+        analyze_suite(parse_suite(input)?)
+    }
+
+    fn idents(model: &SuiteModel) -> Vec<String> {
+        model
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                EntryModel::Describe { ident, .. }
+                | EntryModel::It { ident, .. }
+                | EntryModel::Hook { ident, .. } => Some(ident.to_string()),
+                EntryModel::Item(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_entries_after_their_descriptions() {
+        let model = analyze(quote! {
+            describe "Parser, when empty" {
+                it "returns None" {}
+            }
+            it "handles 2 items" {}
+            it "2 items" {}
+            it "type" {}
+            it "naïve café" {}
+        })
+        .unwrap();
+        assert_eq!(
+            idents(&model),
+            [
+                "parser_when_empty",
+                "handles_2_items",
+                "it_2_items",
+                "type_",
+                "naïve_café"
+            ]
+        );
+        let EntryModel::Describe { body, .. } = &model.entries[0] else {
+            panic!("expected a `describe` block");
+        };
+        assert_eq!(idents(body), ["returns_none"]);
+    }
+
+    #[test]
+    fn numbers_later_hooks_of_a_kind() {
+        let model = analyze(quote! {
+            before_each {}
+            after_each {}
+            before_each {}
+        })
+        .unwrap();
+        assert_eq!(
+            idents(&model),
+            ["before_each", "after_each", "before_each_2"]
+        );
+    }
+
+    #[test]
+    fn rejects_descriptions_that_name_nothing_or_clash() {
+        let error = |input| analyze(input).err().unwrap().to_string();
+        assert_eq!(
+            error(quote!(it "!?" {})),
+            "descriptions must contain a letter or a digit"
+        );
+        assert_eq!(
+            error(quote!(it "Adds one" {} it "adds one!" {})),
+            "`it \"adds one!\"` is named `adds_one`, like `it \"Adds one\"` in the same block; \
+             descriptions must differ in more than punctuation and case"
+        );
+        // Blocks are named apart from each other only within their parent.
+        assert!(analyze(quote! {
+            describe "a" { it "works" {} }
+            describe "b" { it "works" {} }
+        })
+        .is_ok());
+    }
+}
@@ -0,0 +1,50 @@
+//! Stage 4: emit the modules and functions of the suite.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::{ItemIR, SuiteIR};
+
+pub fn codegen_suite(ir: SuiteIR) -> TokenStream2 {
+    let items = ir.items.into_iter().map(|item| match item {
+        ItemIR::Module { attrs, ident, body } => {
+            let body = codegen_suite(body);
+            quote! {
+                #(#attrs)*
+                mod #ident {
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    #body
+                }
+            }
+        }
+        ItemIR::Item(item) => quote! { #item },
+    });
+    quote! { #(#items)* }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::suite::analyze::analyze_suite;
+    use crate::suite::lower::lower_suite;
+    use crate::suite::parse::parse_suite;
+
+    #[test]
+    fn modules_see_the_items_of_their_parent() {
+        let input = quote!(describe "a stack" { it "works" {} });
+        let ir = lower_suite(analyze_suite(parse_suite(input).unwrap()).unwrap());
+        let items = syn::parse2::<File>(codegen_suite(ir)).unwrap().items;
+        let Item::Mod(module) = &items[0] else {
+            panic!("expected a module");
+        };
+        assert_eq!(module.ident, "a_stack");
+        let content = &module.content.as_ref().unwrap().1;
+        assert!(matches!(&content[0], Item::Use(import)
+            if quote!(#import).to_string() == "# [allow (unused_imports)] use super :: * ;"));
+        assert!(matches!(&content[1], Item::Fn(test) if test.sig.ident == "works"));
+    }
+}
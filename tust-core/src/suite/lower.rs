@@ -0,0 +1,125 @@
+//! Stage 3: lower the suite into the modules and functions to generate.
+
+use proc_macro2::Ident;
+use quote::quote;
+use syn::{parse_quote, Attribute, Item, ItemFn};
+
+use super::analyze::{EntryModel, SuiteModel};
+use crate::hook::HookKind;
+
+/// The items of a suite or `describe` block.
+pub struct SuiteIR {
+    pub items: Vec<ItemIR>,
+}
+
+pub enum ItemIR {
+    /// A `describe` block, emitted as a module that sees the items of its
+    /// parent.
+    Module {
+        attrs: Vec<Attribute>,
+        ident: Ident,
+        body: SuiteIR,
+    },
+    /// A test or hook function with its attribute, or an item given as is.
+    Item(Box<Item>),
+}
+
+pub fn lower_suite(model: SuiteModel) -> SuiteIR {
+    let items = model
+        .entries
+        .into_iter()
+        .map(|entry| match entry {
+            EntryModel::Describe { attrs, ident, body } => ItemIR::Module {
+                attrs,
+                ident,
+                body: lower_suite(body),
+            },
+            EntryModel::It {
+                attrs,
+                asyncness,
+                ident,
+                params,
+                body,
+            } => {
+                // `#[tust::test]` comes first so that it sees the helper
+                // attributes, such as `#[timeout]`, written before `it`.
+                let func: ItemFn = parse_quote! {
+                    #[::tust::test]
+                    #(#attrs)*
+                    #asyncness fn #ident(#params) #body
+                };
+                ItemIR::Item(Box::new(Item::Fn(func)))
+            }
+            EntryModel::Hook {
+                attrs,
+                kind,
+                ident,
+                body,
+            } => {
+                let attribute = match kind {
+                    HookKind::BeforeEach => quote!(::tust::before_each),
+                    HookKind::AfterEach => quote!(::tust::after_each),
+                    HookKind::BeforeAll => quote!(::tust::before_all),
+                    HookKind::AfterAll => quote!(::tust::after_all),
+                };
+                let func: ItemFn = parse_quote! {
+                    #[#attribute]
+                    #(#attrs)*
+                    fn #ident() #body
+                };
+                ItemIR::Item(Box::new(Item::Fn(func)))
+            }
+            EntryModel::Item(item) => ItemIR::Item(item),
+        })
+        .collect();
+    SuiteIR { items }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+
+    use super::*;
+    use crate::suite::analyze::analyze_suite;
+    use crate::suite::parse::parse_suite;
+
+    fn lower(input: proc_macro2::TokenStream) -> SuiteIR {
+        lower_suite(analyze_suite(parse_suite(input).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn blocks_become_tests_and_hooks() {
+        let ir = lower(quote! {
+            #[timeout(100)]
+            it "pushes" (stack: Stack) { stack.push(1); }
+            after_all { cleanup(); }
+        });
+        let text: Vec<_> = ir
+            .items
+            .iter()
+            .map(|item| match item {
+                ItemIR::Item(item) => item.to_token_stream().to_string(),
+                ItemIR::Module { .. } => panic!("unexpected module"),
+            })
+            .collect();
+        assert_eq!(
+            text,
+            [
+                "# [:: tust :: test] # [timeout (100)] fn pushes (stack : Stack) { stack . push \
+                 (1) ; }",
+                "# [:: tust :: after_all] fn after_all () { cleanup () ; }",
+            ]
+        );
+    }
+
+    #[test]
+    fn describe_blocks_become_modules() {
+        let ir = lower(quote!(#[cfg(unix)] describe "a stack" { it "works" {} }));
+        let ItemIR::Module { attrs, ident, body } = &ir.items[0] else {
+            panic!("expected a module");
+        };
+        assert_eq!(ident, "a_stack");
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(body.items.len(), 1);
+    }
+}
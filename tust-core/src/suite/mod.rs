@@ -0,0 +1,12 @@
+//! Pipeline for the `tust::suite!` macro.
+//!
+//! A suite expands to ordinary Rust: each `describe` block becomes a module
+//! named after its description, each `it` block a `#[tust::test]` function,
+//! and each hook block a function with the hook attribute. Test names,
+//! filtering, hooks, and the grouped console output then follow from the
+//! module structure.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
@@ -0,0 +1,202 @@
+//! Stage 1: parse the blocks of a suite.
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
+use syn::{braced, parenthesized, Attribute, Block, FnArg, Ident, Item, LitStr, Result, Token};
+
+use crate::hook::HookKind;
+
+/// The contents of a suite or of one of its `describe` blocks.
+pub struct ParsedSuite {
+    pub entries: Vec<Entry>,
+}
+
+pub enum Entry {
+    /// `describe "description" { ... }`
+    Describe {
+        attrs: Vec<Attribute>,
+        description: LitStr,
+        body: ParsedSuite,
+    },
+    /// `it "description" { ... }`, optionally `async`, with parameters in
+    /// parentheses after the description.
+    It {
+        attrs: Vec<Attribute>,
+        asyncness: Option<Token![async]>,
+        description: LitStr,
+        params: Punctuated<FnArg, Comma>,
+        body: Block,
+    },
+    /// `before_each { ... }` and the other hooks.
+    Hook {
+        attrs: Vec<Attribute>,
+        kind: HookKind,
+        span: Span,
+        body: Block,
+    },
+    /// Any other item, such as a fixture or a helper function, emitted as
+    /// written.
+    Item(Box<Item>),
+}
+
+const HOOKS: [HookKind; 4] = [
+    HookKind::BeforeEach,
+    HookKind::AfterEach,
+    HookKind::BeforeAll,
+    HookKind::AfterAll,
+];
+
+impl Parse for ParsedSuite {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut entries = Vec::new();
+        while !input.is_empty() {
+            entries.push(input.parse()?);
+        }
+        Ok(ParsedSuite { entries })
+    }
+}
+
+impl Parse for Entry {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // Attributes belong to the item after them, so look past them before
+        // deciding what that item is.
+        let ahead = input.fork();
+        ahead.call(Attribute::parse_outer)?;
+        let _: Option<Token![async]> = ahead.parse()?;
+        let keyword = ahead.parse::<Ident>().ok();
+        let is = |name: &str| keyword.as_ref().is_some_and(|keyword| keyword == name);
+
+        if (is("describe") || is("it")) && ahead.peek(LitStr) {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let asyncness: Option<Token![async]> = input.parse()?;
+            let keyword: Ident = input.parse()?;
+            let description: LitStr = input.parse()?;
+            if keyword == "describe" {
+                if let Some(asyncness) = asyncness {
+                    return Err(syn::Error::new(
+                        asyncness.span(),
+                        "`describe` blocks cannot be async; mark each `it` block instead",
+                    ));
+                }
+                let content;
+                braced!(content in input);
+                return Ok(Entry::Describe {
+                    attrs,
+                    description,
+                    body: content.parse()?,
+                });
+            }
+            let params = if input.peek(syn::token::Paren) {
+                let content;
+                parenthesized!(content in input);
+                Punctuated::parse_terminated(&content)?
+            } else {
+                Punctuated::new()
+            };
+            return Ok(Entry::It {
+                attrs,
+                asyncness,
+                description,
+                params,
+                body: input.parse()?,
+            });
+        }
+
+        if let Some(kind) = HOOKS.into_iter().find(|kind| is(kind.attribute())) {
+            if ahead.peek(syn::token::Brace) {
+                let attrs = input.call(Attribute::parse_outer)?;
+                if input.peek(Token![async]) {
+                    return Err(syn::Error::new(input.span(), "hooks cannot be async"));
+                }
+                let span = input.parse::<Ident>()?.span();
+                return Ok(Entry::Hook {
+                    attrs,
+                    kind,
+                    span,
+                    body: input.parse()?,
+                });
+            }
+        }
+
+        Ok(Entry::Item(input.parse()?))
+    }
+}
+
+/// Parses the input of `suite!`.
+pub fn parse_suite(input: TokenStream2) -> Result<ParsedSuite> {
+    syn::parse2(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn parses_blocks_hooks_and_items() {
+        let parsed = parse_suite(quote! {
+            use std::collections::HashMap;
+
+            describe "a stack" {
+                before_each { setup(); }
+
+                #[timeout(100)]
+                it "pushes" { push(); }
+
+                async it "pops" (stack: Stack) { pop(stack).await; }
+            }
+        })
+        .unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert!(matches!(parsed.entries[0], Entry::Item(_)));
+        let Entry::Describe {
+            description, body, ..
+        } = &parsed.entries[1]
+        else {
+            panic!("expected a `describe` block");
+        };
+        assert_eq!(description.value(), "a stack");
+        assert!(matches!(
+            body.entries[0],
+            Entry::Hook {
+                kind: HookKind::BeforeEach,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&body.entries[1], Entry::It { attrs, asyncness: None, .. }
+            if attrs.len() == 1)
+        );
+        assert!(
+            matches!(&body.entries[2], Entry::It { asyncness: Some(_), params, .. }
+            if params.len() == 1)
+        );
+    }
+
+    #[test]
+    fn keywords_without_a_description_are_items() {
+        let parsed = parse_suite(quote!(
+            fn it() {}
+            fn describe() {}
+        ))
+        .unwrap();
+        assert!(parsed
+            .entries
+            .iter()
+            .all(|entry| matches!(entry, Entry::Item(_))));
+    }
+
+    #[test]
+    fn rejects_async_blocks_that_cannot_be() {
+        let error = |input| parse_suite(input).err().unwrap().to_string();
+        assert_eq!(
+            error(quote!(async describe "a stack" {})),
+            "`describe` blocks cannot be async; mark each `it` block instead"
+        );
+        assert_eq!(error(quote!(async before_all {})), "hooks cannot be async");
+    }
+}
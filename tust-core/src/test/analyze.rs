@@ -0,0 +1,1307 @@
+//! Stage 2: validate the parsed input and extract the test model.
+
+use std::path::PathBuf;
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde_json::Value;
+use syn::spanned::Spanned;
+use syn::{Attribute, Error, Expr, Ident, ItemFn, LitInt, LitStr, Result, ReturnType};
+
+use super::data::{self, Format};
+use super::parse::{
+    CaseAttr, EnvVar, ExcludeAttr, FailAttr, LimitsAttr, NamedValue, ParsedTest, SkipAttr,
+    SkipCondition, TestArgs, ValueList,
+};
+use crate::params::{self, Param};
+
+/// One set of values for the case parameters.
+pub struct Case {
+    pub name: Option<Ident>,
+    pub values: Vec<Expr>,
+    /// Text to name the case after if it has no name, instead of its
+    /// values: what a row of a data file holds.
+    pub description: Option<String>,
+}
+
+/// Executor options of an async test, as variant names of the runtime's
+/// `AsyncRuntime` and `Flavor` enums.
+pub struct AsyncModel {
+    pub runtime: Ident,
+    pub flavor: Ident,
+    pub worker_threads: Option<LitInt>,
+}
+
+/// Resource limits of the test's process, in bytes and seconds.
+pub struct LimitsModel {
+    pub memory: Option<u64>,
+    pub cpu_time: Option<u64>,
+    pub span: Span,
+}
+
+/// Options of a test run in stress mode.
+pub struct StressModel {
+    pub repeat: LitInt,
+    pub threads: LitInt,
+}
+
+/// A validated test function.
+pub struct TestModel {
+    /// The user's function, without the attributes consumed by tust.
+    pub func: ItemFn,
+    /// Parameters bound by the values of each case: the leading ones for
+    /// `#[case(...)]` and `cases_from`, and those named for `#[values(...)]`.
+    pub case_params: Vec<Param>,
+    /// Empty for a test without `#[case(...)]`, `#[values(...)]`, or
+    /// `cases_from`.
+    pub cases: Vec<Case>,
+    /// The data file the cases were read from, given by `cases_from`.
+    pub data_file: Option<LitStr>,
+    /// Parameters injected from fixtures.
+    pub fixtures: Vec<Param>,
+    /// Set for `async fn` tests.
+    pub asyncness: Option<AsyncModel>,
+    /// Whether the test must not run concurrently with other tests, as it
+    /// must if it sets environment variables.
+    pub serial: bool,
+    /// Time limit in milliseconds.
+    pub timeout: Option<LitInt>,
+    /// How many times to re-run the test after it fails.
+    pub retries: Option<LitInt>,
+    /// Stress mode: how many times to run the body, and from how many
+    /// threads at once.
+    pub stress: Option<StressModel>,
+    /// Whether the body runs under the model checker enabled in tust.
+    pub model_check: bool,
+    /// Tags selecting the test with `--tag`, in the order written.
+    pub tags: Vec<LitStr>,
+    pub skip: Option<SkipAttr>,
+    pub fail: Option<FailAttr>,
+    /// Environment variables set while the test runs.
+    pub envs: Vec<EnvVar>,
+    /// Whether `--leak-check` leaves the test alone.
+    pub allow_leaks: bool,
+    /// Whether the test runs in a working directory of its own.
+    pub isolated_cwd: bool,
+    /// Tests that must pass before this one runs.
+    pub depends_on: Vec<LitStr>,
+    /// Named locks held while the test runs.
+    pub locks: Vec<LitStr>,
+    /// Limits on the resources of the test's process.
+    pub limits: Option<LimitsModel>,
+    /// The variant of the runtime's `Priority` enum named by `#[priority]`.
+    pub priority: Option<Ident>,
+    /// `#[cfg(...)]` attributes that must also gate every generated item.
+    pub cfgs: Vec<Attribute>,
+}
+
+/// Validates that the function can be registered as a test.
+pub fn analyze_test(parsed: ParsedTest) -> Result<TestModel> {
+    let ParsedTest {
+        args,
+        func,
+        cases,
+        values,
+        excludes,
+        serial,
+        timeout,
+        retry,
+        skip,
+        fail,
+        envs,
+        allow_leaks,
+        depends_on,
+        limits,
+        priority,
+    } = parsed;
+    let sig = &func.sig;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "test functions cannot be generic",
+        ));
+    }
+    let asyncness = analyze_async(&args, sig.asyncness.is_some())?;
+    let timeout = analyze_timeout(timeout, args.timeout)?;
+    let retries = one_of("retry", retry, args.retry)?;
+    if let Some(retries) = &retries {
+        retries.base10_parse::<u32>()?;
+    }
+    let stress = analyze_stress(args.repeat, args.threads)?;
+    if let Some(model_check) = args.model_check {
+        if sig.asyncness.is_some() {
+            return Err(Error::new(
+                model_check,
+                "`model_check` tests cannot be `async`",
+            ));
+        }
+        if stress.is_some() {
+            return Err(Error::new(
+                model_check,
+                "`model_check` cannot be combined with `repeat` or `threads`",
+            ));
+        }
+    }
+    let tags = analyze_tags(args.tags.unwrap_or_default())?;
+    let locks = analyze_locks(args.locks.unwrap_or_default())?;
+    analyze_envs(&envs)?;
+    analyze_depends_on(&depends_on, &sig.ident)?;
+    let limits = limits.map(analyze_limits).transpose()?;
+    let priority = priority.map(analyze_priority).transpose()?;
+    let mut fixtures = params::named_params(&sig.inputs)?;
+    if let (Some(_), Some(list)) = (cases.first(), values.first()) {
+        return Err(Error::new(
+            list.span,
+            "`#[case(...)]` cannot be combined with `#[values(...)]`",
+        ));
+    }
+    if let Some(exclude) = excludes.first().filter(|_| values.is_empty()) {
+        return Err(Error::new(
+            exclude.span,
+            "`#[exclude(...)]` only applies to tests with `#[values(...)]`",
+        ));
+    }
+    let (case_params, cases) = match &args.cases_from {
+        Some(file) if !values.is_empty() => {
+            return Err(Error::new(
+                file.span(),
+                "`#[values(...)]` cannot be combined with `cases_from`",
+            ))
+        }
+        None if !values.is_empty() => matrix_cases(values, &excludes, &mut fixtures)?,
+        Some(file) => {
+            if let Some(case) = cases.first() {
+                return Err(Error::new(
+                    case.span,
+                    "`#[case(...)]` cannot be combined with `cases_from`",
+                ));
+            }
+            if fixtures.is_empty() {
+                return Err(Error::new(
+                    sig.inputs.span(),
+                    "a test with `cases_from` takes each row as its first parameter",
+                ));
+            }
+            (fixtures.drain(..1).collect(), data_cases(file)?)
+        }
+        None => {
+            let case_count = validate_cases(&cases, fixtures.len())?;
+            let case_params = fixtures.drain(..case_count).collect();
+            let cases = cases
+                .into_iter()
+                .map(|case| Case {
+                    name: case.name,
+                    values: case.args,
+                    description: None,
+                })
+                .collect();
+            (case_params, cases)
+        }
+    };
+
+    let cfgs = func
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .cloned()
+        .collect();
+
+    Ok(TestModel {
+        func,
+        case_params,
+        cases,
+        data_file: args.cases_from,
+        fixtures,
+        asyncness,
+        serial: serial.is_some() || args.serial.is_some() || !envs.is_empty(),
+        timeout,
+        retries,
+        stress,
+        model_check: args.model_check.is_some(),
+        tags,
+        skip,
+        fail,
+        envs,
+        allow_leaks: allow_leaks.is_some(),
+        isolated_cwd: args.isolated_cwd.is_some(),
+        depends_on,
+        locks,
+        limits,
+        priority,
+        cfgs,
+    })
+}
+
+/// Rejects what a test run on an embedded target cannot have: fixtures,
+/// which need the standard library, and options that need threads, clocks,
+/// a file system, or an environment. Tests there run one at a time, so
+/// `serial` and `lock` hold without doing anything.
+pub fn check_embedded(model: &TestModel) -> Result<()> {
+    let unsupported = |span: Span, what: &str| {
+        Err(Error::new(
+            span,
+            format!("{} not available on embedded targets", what),
+        ))
+    };
+    if let Some(fixture) = model.fixtures.first() {
+        return unsupported(fixture.ident.span(), "fixtures are");
+    }
+    if let Some(asyncness) = &model.func.sig.asyncness {
+        return unsupported(asyncness.span(), "`async fn` tests are");
+    }
+    if let Some(timeout) = &model.timeout {
+        return unsupported(timeout.span(), "`timeout` is");
+    }
+    if let Some(retries) = &model.retries {
+        return unsupported(retries.span(), "`retry` is");
+    }
+    if let Some(stress) = &model.stress {
+        return unsupported(stress.repeat.span(), "`repeat` and `threads` are");
+    }
+    if let Some(EnvVar { key, .. }) = model.envs.first() {
+        return unsupported(key.span(), "`#[with_env]` and `#[with_locale]` are");
+    }
+    if let Some(SkipAttr {
+        condition: Some(SkipCondition::Env(name)),
+        ..
+    }) = &model.skip
+    {
+        return unsupported(name.span(), "`#[skip_if(env = ...)]` is");
+    }
+    if let Some(dependency) = model.depends_on.first() {
+        return unsupported(dependency.span(), "`#[depends_on]` is");
+    }
+    if let ReturnType::Type(_, ty) = &model.func.sig.output {
+        return unsupported(ty.span(), "tests returning a value are");
+    }
+    if let Some(file) = &model.data_file {
+        return unsupported(file.span(), "`cases_from` is");
+    }
+    if let Some(limits) = &model.limits {
+        return unsupported(limits.span, "`#[limits]` is");
+    }
+    if let Some(priority) = &model.priority {
+        return unsupported(priority.span(), "`#[priority]` is");
+    }
+    let ident = model.func.sig.ident.span();
+    if model.model_check {
+        return unsupported(ident, "`model_check` is");
+    }
+    if model.isolated_cwd {
+        return unsupported(ident, "`isolated_cwd` is");
+    }
+    // Every failure panics there, so `#[should_fail]` does its work.
+    if let Some(FailAttr::ShouldPanic { .. }) = &model.fail {
+        return unsupported(ident, "`#[should_panic]` is");
+    }
+    Ok(())
+}
+
+/// Validates `repeat` and `threads`, defaulting the iterations to one per
+/// thread and the threads to one.
+fn analyze_stress(repeat: Option<LitInt>, threads: Option<LitInt>) -> Result<Option<StressModel>> {
+    for count in repeat.iter().chain(&threads) {
+        if count.base10_parse::<u32>()? == 0 {
+            return Err(Error::new(count.span(), "the count must be at least 1"));
+        }
+    }
+    Ok(match (repeat, threads) {
+        (None, None) => None,
+        (Some(repeat), None) => Some(StressModel {
+            threads: LitInt::new("1", repeat.span()),
+            repeat,
+        }),
+        (None, Some(threads)) => Some(StressModel {
+            repeat: threads.clone(),
+            threads,
+        }),
+        (Some(repeat), Some(threads)) => Some(StressModel { repeat, threads }),
+    })
+}
+
+/// Validates tag names, which the runner's `--tag` expressions must be able
+/// to spell: letters, digits, `_`, `-`, `.`, and `:`.
+fn analyze_tags(tags: Vec<LitStr>) -> Result<Vec<LitStr>> {
+    let mut seen = Vec::with_capacity(tags.len());
+    for tag in &tags {
+        let name = tag.value();
+        if name.is_empty() {
+            return Err(Error::new(tag.span(), "tags cannot be empty"));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')))
+        {
+            return Err(Error::new(
+                tag.span(),
+                format!("tags cannot contain `{}`", c),
+            ));
+        }
+        if seen.contains(&name) {
+            return Err(Error::new(tag.span(), "duplicate tag"));
+        }
+        seen.push(name);
+    }
+    Ok(tags)
+}
+
+/// Validates lock names, which also name lock files: letters, digits, `_`,
+/// `-`, and `.`.
+fn analyze_locks(locks: Vec<LitStr>) -> Result<Vec<LitStr>> {
+    let mut seen = Vec::with_capacity(locks.len());
+    for lock in &locks {
+        let name = lock.value();
+        if name.is_empty() || name.starts_with('.') {
+            return Err(Error::new(
+                lock.span(),
+                "lock names cannot be empty or start with `.`",
+            ));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        {
+            return Err(Error::new(
+                lock.span(),
+                format!("lock names cannot contain `{}`", c),
+            ));
+        }
+        if seen.contains(&name) {
+            return Err(Error::new(lock.span(), "duplicate lock"));
+        }
+        seen.push(name);
+    }
+    Ok(locks)
+}
+
+/// Validates the tests named by `#[depends_on(...)]`: each a test or module
+/// of the test's own module, or a path such as `db::setup` or `crate::setup`
+/// from the crate root, named once and not the test itself.
+fn analyze_depends_on(depends_on: &[LitStr], test: &Ident) -> Result<()> {
+    let mut seen = Vec::with_capacity(depends_on.len());
+    for dependency in depends_on {
+        let name = dependency.value();
+        if name
+            .strip_prefix("crate::")
+            .unwrap_or(&name)
+            .split("::")
+            .any(|segment| syn::parse_str::<Ident>(segment).is_err())
+        {
+            return Err(Error::new(
+                dependency.span(),
+                "expected the name of a test, or a path such as `db::setup`",
+            ));
+        }
+        if *test == name {
+            return Err(Error::new(
+                dependency.span(),
+                "a test cannot depend on itself",
+            ));
+        }
+        if seen.contains(&name) {
+            return Err(Error::new(dependency.span(), "duplicate dependency"));
+        }
+        seen.push(name);
+    }
+    Ok(())
+}
+
+/// Maps the priority of `#[priority(...)]` to its variant of `Priority`.
+fn analyze_priority(priority: Ident) -> Result<Ident> {
+    let variant = match priority.to_string().as_str() {
+        "high" => "High",
+        "normal" => "Normal",
+        "low" => "Low",
+        _ => {
+            return Err(Error::new(
+                priority.span(),
+                "expected `#[priority(high)]`, `#[priority(normal)]`, or `#[priority(low)]`",
+            ))
+        }
+    };
+    Ok(Ident::new(variant, priority.span()))
+}
+
+/// Parses the limits of `#[limits(...)]`: a memory size such as `512MB`,
+/// in bytes or binary multiples of them, and a processor time such as `10s`,
+/// in whole seconds or minutes.
+fn analyze_limits(limits: LimitsAttr) -> Result<LimitsModel> {
+    let memory = match &limits.memory {
+        Some(text) => Some(parse_size(&text.value()).ok_or_else(|| {
+            Error::new(
+                text.span(),
+                "expected a size such as \"512MB\" or \"2GiB\", with a unit of B, KB, MB, or GB",
+            )
+        })?),
+        None => None,
+    };
+    let cpu_time = match &limits.cpu_time {
+        Some(text) => Some(parse_seconds(&text.value()).ok_or_else(|| {
+            Error::new(
+                text.span(),
+                "expected a time such as \"10s\" or \"2m\"; processor time is limited in \
+                 whole seconds",
+            )
+        })?),
+        None => None,
+    };
+    Ok(LimitsModel {
+        memory,
+        cpu_time,
+        span: limits.span,
+    })
+}
+
+/// Parses `512MB`, `2 GiB`, or `4096B` into bytes, where a kilobyte is 1024
+/// bytes. Zero is rejected.
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        _ => return None,
+    };
+    number.checked_mul(1 << shift).filter(|&bytes| bytes > 0)
+}
+
+/// Parses `10s` or `2m` into seconds. Zero is rejected.
+fn parse_seconds(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "s" => number,
+        "m" => number.checked_mul(60)?,
+        _ => return None,
+    };
+    Some(seconds).filter(|&seconds| seconds > 0)
+}
+
+/// Validates the variable names of `#[with_env(...)]`, which `std::env`
+/// accepts only if they are not empty and contain no `=` or NUL.
+fn analyze_envs(envs: &[EnvVar]) -> Result<()> {
+    let mut seen = Vec::with_capacity(envs.len());
+    for EnvVar { key, .. } in envs {
+        let name = key.value();
+        if name.is_empty() {
+            return Err(Error::new(
+                key.span(),
+                "environment variable names cannot be empty",
+            ));
+        }
+        if let Some(c) = name.chars().find(|&c| c == '=' || c == '\0') {
+            return Err(Error::new(
+                key.span(),
+                format!("environment variable names cannot contain {:?}", c),
+            ));
+        }
+        if seen.contains(&name) {
+            return Err(Error::new(key.span(), "variable is already set"));
+        }
+        seen.push(name);
+    }
+    Ok(())
+}
+
+/// Picks a setting given either as the helper attribute `#[name(...)]` or as
+/// the argument `#[tust::test(name = ...)]`, rejecting both at once.
+fn one_of(name: &str, attr: Option<LitInt>, arg: Option<LitInt>) -> Result<Option<LitInt>> {
+    match (attr, arg) {
+        (Some(_), Some(arg)) => Err(Error::new(
+            arg.span(),
+            format!("`{}` is already given by a `#[{}]` attribute", name, name),
+        )),
+        (attr, arg) => Ok(attr.or(arg)),
+    }
+}
+
+/// Validates the time limit, given in milliseconds.
+fn analyze_timeout(attr: Option<LitInt>, arg: Option<LitInt>) -> Result<Option<LitInt>> {
+    let timeout = one_of("timeout", attr, arg)?;
+    if let Some(ms) = &timeout {
+        if ms.base10_parse::<u64>()? == 0 {
+            return Err(Error::new(
+                ms.span(),
+                "the timeout must be at least 1 millisecond",
+            ));
+        }
+    }
+    Ok(timeout)
+}
+
+/// Validates the executor options, which only apply to async tests.
+fn analyze_async(args: &TestArgs, is_async: bool) -> Result<Option<AsyncModel>> {
+    if !is_async {
+        let option = [
+            args.runtime.as_ref().map(|lit| ("runtime", lit.span())),
+            args.flavor.as_ref().map(|lit| ("flavor", lit.span())),
+            args.worker_threads
+                .as_ref()
+                .map(|lit| ("worker_threads", lit.span())),
+        ]
+        .into_iter()
+        .flatten()
+        .next();
+        return match option {
+            Some((name, span)) => Err(Error::new(
+                span,
+                format!("`{}` only applies to `async fn` tests", name),
+            )),
+            None => Ok(None),
+        };
+    }
+
+    let runtime = match &args.runtime {
+        None => "Default",
+        Some(lit) => variant(
+            lit,
+            &[
+                ("tokio", "Tokio"),
+                ("async-std", "AsyncStd"),
+                ("builtin", "Builtin"),
+            ],
+        )?,
+    };
+    let flavor = match &args.flavor {
+        None => "CurrentThread",
+        Some(lit) => variant(
+            lit,
+            &[
+                ("current_thread", "CurrentThread"),
+                ("multi_thread", "MultiThread"),
+            ],
+        )?,
+    };
+    if let Some(threads) = &args.worker_threads {
+        if flavor != "MultiThread" {
+            return Err(Error::new(
+                threads.span(),
+                "`worker_threads` requires `flavor = \"multi_thread\"`",
+            ));
+        }
+        if threads.base10_parse::<usize>()? == 0 {
+            return Err(Error::new(
+                threads.span(),
+                "`worker_threads` must be at least 1",
+            ));
+        }
+    }
+
+    Ok(Some(AsyncModel {
+        runtime: Ident::new(runtime, Span::call_site()),
+        flavor: Ident::new(flavor, Span::call_site()),
+        worker_threads: args.worker_threads.clone(),
+    }))
+}
+
+/// Maps a string argument to the name of an enum variant.
+fn variant(lit: &LitStr, variants: &[(&str, &'static str)]) -> Result<&'static str> {
+    let value = lit.value();
+    variants
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, variant)| *variant)
+        .ok_or_else(|| {
+            let expected: Vec<_> = variants
+                .iter()
+                .map(|(name, _)| format!("\"{}\"", name))
+                .collect();
+            Error::new(
+                lit.span(),
+                format!(
+                    "unknown value `{}`; expected one of {}",
+                    value,
+                    expected.join(", ")
+                ),
+            )
+        })
+}
+
+/// Expands `#[values(...)]` lists into a case per combination of their
+/// values, in the order the function takes the parameters they bind, less
+/// those `excludes` leaves out. Takes the parameters bound out of `params`.
+fn matrix_cases(
+    lists: Vec<ValueList>,
+    excludes: &[ExcludeAttr],
+    params: &mut Vec<Param>,
+) -> Result<(Vec<Param>, Vec<Case>)> {
+    for (index, list) in lists.iter().enumerate() {
+        if !params.iter().any(|param| param.ident == list.ident) {
+            return Err(Error::new(
+                list.span,
+                format!("`{}` is not a parameter of the test", list.ident),
+            ));
+        }
+        if lists[..index].iter().any(|other| other.ident == list.ident) {
+            return Err(Error::new(
+                list.span,
+                format!("`{}` already has values", list.ident),
+            ));
+        }
+        if list.values.is_empty() {
+            return Err(Error::new(list.span, "expected at least one value"));
+        }
+    }
+    let mut case_params = Vec::with_capacity(lists.len());
+    let mut ordered = Vec::with_capacity(lists.len());
+    let mut lists: Vec<Option<ValueList>> = lists.into_iter().map(Some).collect();
+    params.retain(|param| {
+        let Some(list) = lists
+            .iter_mut()
+            .find(|list| list.as_ref().is_some_and(|list| list.ident == param.ident))
+        else {
+            return true;
+        };
+        ordered.extend(list.take());
+        case_params.push(param.clone());
+        false
+    });
+
+    let text = |value: &Expr| value.to_token_stream().to_string();
+    for exclude in excludes {
+        for NamedValue { ident, .. } in &exclude.values {
+            if !ordered.iter().any(|list| list.ident == *ident) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` has no `#[values(...)]`", ident),
+                ));
+            }
+        }
+    }
+    let mut used = vec![false; excludes.len()];
+    let mut cases = Vec::new();
+    // The index of each parameter's value in the current combination,
+    // counting up with the last parameter's changing fastest.
+    let mut indices = vec![0; ordered.len()];
+    loop {
+        let excluded = excludes.iter().enumerate().find(|(_, exclude)| {
+            exclude.values.iter().all(|NamedValue { ident, value }| {
+                ordered.iter().zip(&indices).any(|(list, &at)| {
+                    list.ident == *ident && text(&list.values[at]) == text(value)
+                })
+            })
+        });
+        match excluded {
+            Some((index, _)) => used[index] = true,
+            None => {
+                let values: Vec<Expr> = ordered
+                    .iter()
+                    .zip(&indices)
+                    .map(|(list, &at)| list.values[at].clone())
+                    .collect();
+                let description = ordered
+                    .iter()
+                    .zip(&values)
+                    .map(|(list, value)| format!("{} {}", list.ident, text(value)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                cases.push(Case {
+                    name: None,
+                    values,
+                    description: Some(description),
+                });
+            }
+        }
+        let Some(position) = (0..ordered.len())
+            .rev()
+            .find(|&position| indices[position] + 1 < ordered[position].values.len())
+        else {
+            break;
+        };
+        indices[position] += 1;
+        for index in &mut indices[position + 1..] {
+            *index = 0;
+        }
+    }
+    if let Some((exclude, _)) = excludes.iter().zip(&used).find(|(_, used)| !**used) {
+        return Err(Error::new(
+            exclude.span,
+            "this excludes no combination of the values",
+        ));
+    }
+    if cases.is_empty() {
+        return Err(Error::new(
+            ordered[0].span,
+            "every combination of the values is excluded",
+        ));
+    }
+    Ok((case_params, cases))
+}
+
+/// Reads the rows of the data file at `file`, relative to the crate root,
+/// as cases whose value deserializes the row when the case runs.
+fn data_cases(file: &LitStr) -> Result<Vec<Case>> {
+    let name = file.value();
+    let Some(format) = Format::from_path(&name) else {
+        return Err(Error::new(
+            file.span(),
+            "expected a `.json`, `.yaml`, `.yml`, or `.csv` file",
+        ));
+    };
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let text = std::fs::read_to_string(root.join(&name))
+        .map_err(|err| Error::new(file.span(), format!("cannot read `{}`: {}", name, err)))?;
+    let rows = data::rows(format, &text)
+        .map_err(|err| Error::new(file.span(), format!("cannot read `{}`: {}", name, err)))?;
+    if rows.is_empty() {
+        return Err(Error::new(
+            file.span(),
+            format!("`{}` contains no cases", name),
+        ));
+    }
+    let csv = format == Format::Csv;
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let description = describe_row(&row);
+            let json = row.to_string();
+            let number = index + 1;
+            Case {
+                name: None,
+                values: vec![syn::parse_quote! {
+                    ::tust::__private::data_row(&::tust::__private::DataRow {
+                        file: #file,
+                        row: #number,
+                        json: #json,
+                        csv: #csv,
+                    })
+                }],
+                description: Some(description),
+            }
+        })
+        .collect())
+}
+
+/// What a row's case is named after: its `name` field, if it has one, or
+/// else its values.
+fn describe_row(row: &Value) -> String {
+    match row.get("name") {
+        Some(Value::String(name)) if !name.is_empty() => return name.clone(),
+        _ => {}
+    }
+    fn scalars(value: &Value, texts: &mut Vec<String>) {
+        match value {
+            Value::Null => {}
+            Value::String(text) => texts.push(text.clone()),
+            Value::Array(items) => items.iter().for_each(|item| scalars(item, texts)),
+            Value::Object(fields) => fields.values().for_each(|field| scalars(field, texts)),
+            value => texts.push(value.to_string()),
+        }
+    }
+    let mut texts = Vec::new();
+    scalars(row, &mut texts);
+    texts.join(" ")
+}
+
+/// Checks that every case supplies the same number of values, and no more
+/// than the function has parameters. Returns that number.
+fn validate_cases(cases: &[CaseAttr], param_count: usize) -> Result<usize> {
+    let Some(first) = cases.first() else {
+        return Ok(0);
+    };
+    let expected = first.args.len();
+
+    if expected == 0 {
+        return Err(Error::new(
+            first.span,
+            "a case needs at least one value; values bind to the leading parameters",
+        ));
+    }
+    if expected > param_count {
+        return Err(Error::new(
+            first.span,
+            format!(
+                "case has {} values but the function only has {} parameters",
+                expected, param_count
+            ),
+        ));
+    }
+    for case in &cases[1..] {
+        if case.args.len() != expected {
+            return Err(Error::new(
+                case.span,
+                format!(
+                    "expected {} values like the first case, found {}",
+                    expected,
+                    case.args.len()
+                ),
+            ));
+        }
+    }
+
+    Ok(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream as TokenStream2;
+    use quote::quote;
+
+    use super::*;
+    use crate::test::parse::parse_test;
+
+    fn analyze(attr: TokenStream2, item: TokenStream2) -> Result<TestModel> {
+        analyze_test(parse_test(attr, item)?)
+    }
+
+    fn error(attr: TokenStream2, item: TokenStream2) -> String {
+        analyze(attr, item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn plain_test() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(model.func.sig.ident, "adds");
+        assert!(model.cases.is_empty());
+        assert!(model.fixtures.is_empty());
+        assert!(model.asyncness.is_none());
+        assert!(!model.serial);
+    }
+
+    #[test]
+    fn keeps_cfg_attributes_for_the_generated_items() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                #[cfg(unix)]
+                #[inline]
+                fn adds() {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(model.cfgs.len(), 1);
+        assert!(model.cfgs[0].path().is_ident("cfg"));
+    }
+
+    #[test]
+    fn rejects_generic_tests() {
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    fn adds<T>() {}
+                )
+            ),
+            "test functions cannot be generic"
+        );
+    }
+
+    #[test]
+    fn injects_fixtures_by_parameter_name() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                fn uses(db: Db, url: String) {}
+            ),
+        )
+        .unwrap();
+        let names: Vec<_> = model.fixtures.iter().map(|p| p.ident.to_string()).collect();
+        assert_eq!(names, ["db", "url"]);
+        assert!(model.case_params.is_empty());
+    }
+
+    #[test]
+    fn cases_bind_the_leading_parameters() {
+        let model = analyze(
+            quote!(),
+            quote! {
+                #[case(1, 2)]
+                #[case(3, 4)]
+                fn adds(a: u32, b: u32, db: Db) {}
+            },
+        )
+        .unwrap();
+        let names = |params: &[Param]| -> Vec<String> {
+            params.iter().map(|param| param.ident.to_string()).collect()
+        };
+        assert_eq!(names(&model.case_params), ["a", "b"]);
+        assert_eq!(names(&model.fixtures), ["db"]);
+        assert_eq!(model.cases.len(), 2);
+        assert_eq!(model.cases[1].values.len(), 2);
+    }
+
+    #[test]
+    fn rejects_cases_that_do_not_fit_the_parameters() {
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    #[case()]
+                    fn adds(a: u32) {}
+                )
+            ),
+            "a case needs at least one value; values bind to the leading parameters"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    #[case(1, 2)]
+                    fn adds(a: u32) {}
+                )
+            ),
+            "case has 2 values but the function only has 1 parameters"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                quote!(
+                    #[case(1, 2)]
+                    #[case(3)]
+                    fn adds(a: u32, b: u32) {}
+                )
+            ),
+            "expected 2 values like the first case, found 1"
+        );
+    }
+
+    #[test]
+    fn async_tests_run_on_the_default_executor() {
+        let model = analyze(
+            quote!(),
+            quote!(
+                async fn fetches() {}
+            ),
+        )
+        .unwrap();
+        let asyncness = model.asyncness.unwrap();
+        assert_eq!(asyncness.runtime, "Default");
+        assert_eq!(asyncness.flavor, "CurrentThread");
+        assert!(asyncness.worker_threads.is_none());
+    }
+
+    #[test]
+    fn executor_options() {
+        let model = analyze(
+            quote!(
+                runtime = "tokio",
+                flavor = "multi_thread",
+                worker_threads = 4
+            ),
+            quote!(
+                async fn fetches() {}
+            ),
+        )
+        .unwrap();
+        let asyncness = model.asyncness.unwrap();
+        assert_eq!(asyncness.runtime, "Tokio");
+        assert_eq!(asyncness.flavor, "MultiThread");
+        assert_eq!(asyncness.worker_threads.unwrap().base10_digits(), "4");
+        let runtime = |name: &str| {
+            let attr = quote!(runtime = #name);
+            analyze(
+                attr,
+                quote!(
+                    async fn fetches() {}
+                ),
+            )
+            .unwrap()
+            .asyncness
+            .unwrap()
+            .runtime
+        };
+        assert_eq!(runtime("async-std"), "AsyncStd");
+        assert_eq!(runtime("builtin"), "Builtin");
+    }
+
+    #[test]
+    fn rejects_bad_executor_options() {
+        assert_eq!(
+            error(
+                quote!(runtime = "tokio"),
+                quote!(
+                    fn fetches() {}
+                )
+            ),
+            "`runtime` only applies to `async fn` tests"
+        );
+        assert_eq!(
+            error(
+                quote!(worker_threads = 2),
+                quote!(
+                    fn fetches() {}
+                )
+            ),
+            "`worker_threads` only applies to `async fn` tests"
+        );
+        assert_eq!(
+            error(
+                quote!(runtime = "smol"),
+                quote!(
+                    async fn fetches() {}
+                )
+            ),
+            "unknown value `smol`; expected one of \"tokio\", \"async-std\", \"builtin\""
+        );
+        assert_eq!(
+            error(
+                quote!(worker_threads = 2),
+                quote!(
+                    async fn fetches() {}
+                )
+            ),
+            "`worker_threads` requires `flavor = \"multi_thread\"`"
+        );
+        assert_eq!(
+            error(
+                quote!(flavor = "multi_thread", worker_threads = 0),
+                quote!(
+                    async fn fetches() {}
+                )
+            ),
+            "`worker_threads` must be at least 1"
+        );
+    }
+
+    #[test]
+    fn rejects_tags_the_runner_cannot_select() {
+        let item = || {
+            quote!(
+                fn adds() {}
+            )
+        };
+        assert_eq!(error(quote!(tags("")), item()), "tags cannot be empty");
+        assert_eq!(
+            error(quote!(tags("db slow")), item()),
+            "tags cannot contain ` `"
+        );
+        assert_eq!(
+            error(quote!(tags("a&b")), item()),
+            "tags cannot contain `&`"
+        );
+        assert_eq!(error(quote!(tags("db", "db")), item()), "duplicate tag");
+        let model = analyze(quote!(tags("db", "io.net:v2-x_y")), item()).unwrap();
+        assert_eq!(model.tags.len(), 2);
+    }
+
+    #[test]
+    fn reads_cases_from_data_files() {
+        let path = crate::testing::write(
+            "cases.json",
+            r#"[{"name": "small", "n": 1}, {"n": 2, "tags": ["a", null, true]}]"#,
+        );
+        let model = analyze(
+            quote!(cases_from = #path),
+            quote!(
+                fn doubles(row: Row, db: Db) {}
+            ),
+        )
+        .unwrap();
+        assert_eq!(model.case_params.len(), 1);
+        assert_eq!(model.case_params[0].ident, "row");
+        assert_eq!(model.fixtures.len(), 1);
+        assert_eq!(model.data_file.unwrap().value(), path);
+        let descriptions: Vec<_> = model
+            .cases
+            .iter()
+            .map(|case| case.description.clone().unwrap())
+            .collect();
+        assert_eq!(descriptions, ["small", "2 a true"]);
+        let value = &model.cases[1].values[0];
+        let value = quote!(#value).to_string();
+        assert!(value.contains("row : 2usize"), "{}", value);
+        assert!(value.contains("csv : false"), "{}", value);
+    }
+
+    #[test]
+    fn rejects_bad_data_files() {
+        let item = quote!(
+            fn t(row: Row) {}
+        );
+        assert_eq!(
+            error(quote!(cases_from = "cases.toml"), item.clone()),
+            "expected a `.json`, `.yaml`, `.yml`, or `.csv` file"
+        );
+        let missing = format!("{}/missing.json", std::env::temp_dir().display());
+        assert!(error(quote!(cases_from = #missing), item.clone())
+            .starts_with(&format!("cannot read `{}`: ", missing)));
+        let empty = crate::testing::write("empty.csv", "a,b\n");
+        assert_eq!(
+            error(quote!(cases_from = #empty), item.clone()),
+            format!("`{}` contains no cases", empty)
+        );
+        let malformed = crate::testing::write("malformed.csv", "a,b\n1\n");
+        assert_eq!(
+            error(quote!(cases_from = #malformed), item),
+            format!(
+                "cannot read `{}`: line 2: expected 2 fields like the header, found 1",
+                malformed
+            )
+        );
+    }
+
+    #[test]
+    fn data_files_supply_every_case() {
+        let path = crate::testing::write("only.json", "[1]");
+        assert_eq!(
+            error(
+                quote!(cases_from = #path),
+                quote! {
+                    #[case(1)]
+                    fn t(row: u8) {}
+                }
+            ),
+            "`#[case(...)]` cannot be combined with `cases_from`"
+        );
+        assert_eq!(
+            error(
+                quote!(cases_from = #path),
+                quote! {
+                    #[values(row = [1, 2])]
+                    fn t(row: u8) {}
+                }
+            ),
+            "`#[values(...)]` cannot be combined with `cases_from`"
+        );
+        assert_eq!(
+            error(
+                quote!(cases_from = #path),
+                quote!(
+                    fn t() {}
+                )
+            ),
+            "a test with `cases_from` takes each row as its first parameter"
+        );
+    }
+
+    #[test]
+    fn combines_values_in_parameter_order() {
+        let model = analyze(
+            quote!(),
+            quote! {
+                #[values(b = ["x", "y"])]
+                #[values(a = [1, 2])]
+                #[exclude(a = 2, b = "x")]
+                fn t(a: u8, db: Db, b: &str) {}
+            },
+        )
+        .unwrap();
+        let params: Vec<_> = model
+            .case_params
+            .iter()
+            .map(|param| param.ident.to_string())
+            .collect();
+        assert_eq!(params, ["a", "b"]);
+        assert_eq!(model.fixtures.len(), 1);
+        let descriptions: Vec<_> = model
+            .cases
+            .iter()
+            .map(|case| case.description.clone().unwrap())
+            .collect();
+        assert_eq!(descriptions, ["a 1 b \"x\"", "a 1 b \"y\"", "a 2 b \"y\""]);
+    }
+
+    #[test]
+    fn rejects_bad_value_matrices() {
+        let attr = quote!();
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[values(c = [1])]
+                    fn t(a: u8) {}
+                }
+            ),
+            "`c` is not a parameter of the test"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[values(a = [1], a = [2])]
+                    fn t(a: u8) {}
+                }
+            ),
+            "`a` already has values"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[values(a = [])]
+                    fn t(a: u8) {}
+                }
+            ),
+            "expected at least one value"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[case(1)]
+                    #[values(a = [1])]
+                    fn t(a: u8) {}
+                }
+            ),
+            "`#[case(...)]` cannot be combined with `#[values(...)]`"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_excludes() {
+        let attr = quote!();
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[exclude(a = 1)]
+                    fn t(a: u8) {}
+                }
+            ),
+            "`#[exclude(...)]` only applies to tests with `#[values(...)]`"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[values(a = [1, 2])]
+                    #[exclude(b = 1)]
+                    fn t(a: u8, b: u8) {}
+                }
+            ),
+            "`b` has no `#[values(...)]`"
+        );
+        assert_eq!(
+            error(
+                attr.clone(),
+                quote! {
+                    #[values(a = [1, 2])]
+                    #[exclude(a = 3)]
+                    fn t(a: u8) {}
+                }
+            ),
+            "this excludes no combination of the values"
+        );
+        assert_eq!(
+            error(
+                attr,
+                quote! {
+                    #[values(a = [1])]
+                    #[exclude(a = 1)]
+                    fn t(a: u8) {}
+                }
+            ),
+            "every combination of the values is excluded"
+        );
+    }
+}
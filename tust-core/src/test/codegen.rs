@@ -0,0 +1,728 @@
+//! Stage 4: emit the registry entries, the renamed body, and the libtest
+//! wrappers.
+
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::LitStr;
+
+use super::analyze::{AsyncModel, LimitsModel, StressModel};
+use super::lower::{InstanceIR, TestIR};
+use super::parse::{EnvVar, FailAttr, SkipAttr, SkipCondition};
+use crate::params::{self, Param};
+
+pub fn codegen_test(ir: TestIR) -> TokenStream2 {
+    let TestIR {
+        body,
+        wrapper_module,
+        instances,
+        data_file,
+        fixtures,
+        asyncness,
+        serial,
+        timeout,
+        retries,
+        stress,
+        model_check,
+        tags,
+        skip,
+        fail,
+        envs,
+        allow_leaks,
+        isolated_cwd,
+        depends_on,
+        locks,
+        limits,
+        priority,
+        cfgs,
+        span,
+    } = ir;
+    let body_ident = &body.sig.ident;
+    // Cases may bind any of the parameters, so the body is called with them
+    // by name, in order.
+    let args = params::names(&body.sig.inputs);
+    let resolver = Ident::new("fixtures", Span::call_site());
+    let resolve = params::resolve(&fixtures, &resolver);
+    let requests = params::requests(&fixtures);
+    let location = quote_spanned! {span=>
+        file: ::core::file!(),
+        line: ::core::line!(),
+        column: ::core::column!(),
+    };
+
+    let timeout = match timeout {
+        Some(ms) => quote! {
+            ::core::option::Option::Some(::core::time::Duration::from_millis(#ms))
+        },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    let limits = match limits {
+        Some(LimitsModel {
+            memory, cpu_time, ..
+        }) => {
+            let memory = match memory {
+                Some(bytes) => quote! { ::core::option::Option::Some(#bytes) },
+                None => quote! { ::core::option::Option::None },
+            };
+            let cpu_time = match cpu_time {
+                Some(secs) => quote! {
+                    ::core::option::Option::Some(::core::time::Duration::from_secs(#secs))
+                },
+                None => quote! { ::core::option::Option::None },
+            };
+            quote! {
+                ::tust::__private::Limits {
+                    memory: #memory,
+                    cpu_time: #cpu_time,
+                }
+            }
+        }
+        None => quote! { ::tust::__private::Limits::NONE },
+    };
+    let priority = match priority {
+        Some(variant) => quote! { ::tust::__private::Priority::#variant },
+        None => quote! { ::tust::__private::Priority::Normal },
+    };
+
+    let retries = match retries {
+        Some(retries) => quote! { ::core::option::Option::Some(#retries) },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    let skip_def = skip_def(&skip);
+    let expected = expected_def(&fail);
+
+    // The guard restores the variables once the test body and its fixtures
+    // are dropped, even if the test panics.
+    let set_envs = (!envs.is_empty()).then(|| {
+        let sets = envs.iter().map(|EnvVar { key, value }| {
+            quote_spanned! {value.span()=>
+                __tust_env.set(#key, #value);
+            }
+        });
+        quote! {
+            let mut __tust_env = ::tust::__private::Env::new();
+            #(#sets)*
+        }
+    });
+
+    // libtest cannot skip at run time, but it can ignore a test outright.
+    let ignore = match &skip {
+        Some(SkipAttr {
+            condition: None,
+            reason: Some(reason),
+        }) => quote! { #[ignore = #reason] },
+        Some(SkipAttr {
+            condition: None,
+            reason: None,
+        }) => quote! { #[ignore] },
+        _ => quote!(),
+    };
+
+    // What the test returns decides how it ends; see `IntoTestResult`.
+    let call = |args: TokenStream2| match &asyncness {
+        None => quote! { ::tust::__private::check_result(#body_ident(#args)); },
+        Some(AsyncModel {
+            runtime,
+            flavor,
+            worker_threads,
+        }) => {
+            let worker_threads = match worker_threads {
+                Some(threads) => quote! { ::core::option::Option::Some(#threads) },
+                None => quote! { ::core::option::Option::None },
+            };
+            quote! {
+                ::tust::__private::check_result(::tust::__private::block_on(
+                    &::tust::__private::AsyncConfig {
+                        runtime: ::tust::__private::AsyncRuntime::#runtime,
+                        flavor: ::tust::__private::Flavor::#flavor,
+                        worker_threads: #worker_threads,
+                    },
+                    #body_ident(#args),
+                ));
+            }
+        }
+    };
+
+    let defs = instances.iter().map(|instance| {
+        let InstanceIR {
+            def_ident,
+            name,
+            bindings,
+            ..
+        } = instance;
+        let case_bindings = bindings.iter().map(|(Param { ident, ty }, value)| {
+            quote_spanned! {value.span()=>
+                let #ident: #ty = #value;
+            }
+        });
+        let call = call(quote! { #(#args),* });
+        let once = quote! {
+            #(#case_bindings)*
+            #resolve
+            #call
+            ::core::result::Result::Ok(())
+        };
+        // In stress mode and under a model checker every execution resolves
+        // its own fixtures, while the environment is set once around all of
+        // them.
+        let repeated = |run: TokenStream2| {
+            quote! {
+                fn __once(
+                    #resolver: &mut ::tust::__private::Fixtures,
+                ) -> ::core::result::Result<(), ::tust::__private::FixtureError> {
+                    #once
+                }
+                #run
+            }
+        };
+        let body = match &stress {
+            Some(StressModel { repeat, threads }) => repeated(quote! {
+                ::tust::__private::stress(
+                    &::tust::__private::StressConfig {
+                        repeat: #repeat,
+                        threads: #threads,
+                    },
+                    #resolver,
+                    __once,
+                )
+            }),
+            None if model_check => repeated(quote! {
+                ::tust::__private::model_check(#resolver, __once)
+            }),
+            None => once,
+        };
+
+        quote! {
+            #(#cfgs)*
+            #[doc(hidden)]
+            #[allow(non_upper_case_globals)]
+            const #def_ident: ::tust::__private::TestDef = ::tust::__private::TestDef {
+                name: #name,
+                module_path: ::core::module_path!(),
+                #location
+                fixtures: #requests,
+                serial: #serial,
+                timeout: #timeout,
+                retries: #retries,
+                tags: &[#(#tags),*],
+                skip: #skip_def,
+                expected: #expected,
+                allow_leaks: #allow_leaks,
+                isolated_cwd: #isolated_cwd,
+                depends_on: &[#(#depends_on),*],
+                locks: &[#(#locks),*],
+                limits: #limits,
+                priority: #priority,
+                run: {
+                    fn __run(
+                        #resolver: &mut ::tust::__private::Fixtures,
+                    ) -> ::core::result::Result<(), ::tust::__private::FixtureError> {
+                        #set_envs
+                        #body
+                    }
+                    __run
+                },
+            };
+
+            #(#cfgs)*
+            ::tust::__private::inventory::submit! { #def_ident }
+        }
+    });
+
+    let wrappers = match &wrapper_module {
+        None => {
+            let wrappers = instances
+                .iter()
+                .map(|instance| wrapper(instance, quote!(), &ignore));
+            quote! { #(#cfgs)* #(#wrappers)* }
+        }
+        Some(module) => {
+            let wrappers = instances
+                .iter()
+                .map(|instance| wrapper(instance, quote!(super::), &ignore));
+            quote! {
+                #(#cfgs)*
+                #[cfg(test)]
+                mod #module {
+                    #(#wrappers)*
+                }
+            }
+        }
+    };
+
+    // Rebuilds the tests when their data file changes.
+    let data_file = data_file.map(|path| {
+        quote! {
+            #(#cfgs)*
+            const _: &[u8] =
+                include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path));
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #body
+
+        #(#defs)*
+
+        #wrappers
+
+        #data_file
+    }
+}
+
+/// Emits the registry entries of a test run on an embedded target by
+/// `tust-embedded`, which the user's crate names `tust` there. Only what
+/// [`check_embedded`](super::analyze::check_embedded) accepts reaches here,
+/// and there are no libtest wrappers.
+pub fn codegen_embedded_test(ir: TestIR) -> TokenStream2 {
+    let TestIR {
+        body,
+        instances,
+        tags,
+        skip,
+        fail,
+        cfgs,
+        span,
+        ..
+    } = ir;
+    let body_ident = &body.sig.ident;
+    let args = params::names(&body.sig.inputs);
+    let skip_def = skip_def(&skip);
+    let expected = expected_def(&fail);
+    let location = quote_spanned! {span=>
+        file: ::core::file!(),
+        line: ::core::line!(),
+    };
+
+    let defs = instances.iter().map(|instance| {
+        let InstanceIR {
+            def_ident,
+            name,
+            bindings,
+            ..
+        } = instance;
+        let case_bindings = bindings.iter().map(|(Param { ident, ty }, value)| {
+            quote_spanned! {value.span()=>
+                let #ident: #ty = #value;
+            }
+        });
+        quote! {
+            #(#cfgs)*
+            #[::tust::__private::linkme::distributed_slice(::tust::__private::TESTS)]
+            #[linkme(crate = ::tust::__private::linkme)]
+            #[doc(hidden)]
+            #[allow(non_upper_case_globals)]
+            static #def_ident: ::tust::__private::TestDef = ::tust::__private::TestDef {
+                name: #name,
+                module_path: ::core::module_path!(),
+                #location
+                tags: &[#(#tags),*],
+                skip: #skip_def,
+                expected: #expected,
+                run: {
+                    fn __run() {
+                        #(#case_bindings)*
+                        #body_ident(#(#args),*);
+                    }
+                    __run
+                },
+            };
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #body
+
+        #(#defs)*
+    }
+}
+
+/// Emits the `#[test]` function that runs `instance` under libtest. `prefix`
+/// is the path from the wrapper's module to the registry entry, and `ignore`
+/// is an `#[ignore]` attribute for tests that are always skipped.
+fn wrapper(instance: &InstanceIR, prefix: TokenStream2, ignore: &TokenStream2) -> TokenStream2 {
+    let InstanceIR {
+        def_ident,
+        wrapper_ident,
+        ..
+    } = instance;
+    quote! {
+        #[test]
+        #ignore
+        fn #wrapper_ident() {
+            ::tust::__private::run_libtest(&#prefix #def_ident);
+        }
+    }
+}
+
+/// `Some(value)` or `None`, as an expression.
+fn some_or_none(value: Option<&LitStr>) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { ::core::option::Option::Some(#value) },
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+/// The `Skip` of a test with `skip`.
+fn skip_def(skip: &Option<SkipAttr>) -> TokenStream2 {
+    match skip {
+        None => quote! { ::tust::__private::Skip::Never },
+        Some(SkipAttr {
+            condition: None,
+            reason,
+        }) => {
+            let reason = some_or_none(reason.as_ref());
+            quote! { ::tust::__private::Skip::Always { reason: #reason } }
+        }
+        Some(SkipAttr {
+            condition: Some(condition),
+            reason,
+        }) => {
+            let (test, description) = match condition {
+                SkipCondition::Cfg(predicate) => (
+                    quote! { ::core::cfg!(#predicate) },
+                    format!("cfg({})", predicate),
+                ),
+                SkipCondition::Env(name) => (
+                    quote! {
+                        ::std::env::var_os(#name).is_some_and(|value| !value.is_empty())
+                    },
+                    format!("`{}` is set", name.value()),
+                ),
+            };
+            let reason = match reason {
+                Some(reason) => quote! { #reason },
+                None => quote! { #description },
+            };
+            quote! {
+                ::tust::__private::Skip::If {
+                    condition: || #test,
+                    reason: #reason,
+                }
+            }
+        }
+    }
+}
+
+/// The `ExpectedResult` of a test with `fail`.
+fn expected_def(fail: &Option<FailAttr>) -> TokenStream2 {
+    match fail {
+        None => quote! { ::tust::__private::ExpectedResult::Pass },
+        Some(FailAttr::ShouldFail { expected }) => {
+            let expected = some_or_none(expected.as_ref());
+            quote! { ::tust::__private::ExpectedResult::ShouldFail { expected: #expected } }
+        }
+        Some(FailAttr::ShouldPanic { contains, regex }) => {
+            let contains = some_or_none(contains.as_ref());
+            let regex = some_or_none(regex.as_ref());
+            quote! {
+                ::tust::__private::ExpectedResult::ShouldPanic {
+                    contains: #contains,
+                    regex: #regex,
+                }
+            }
+        }
+        Some(FailAttr::XFail { reason }) => {
+            let reason = some_or_none(reason.as_ref());
+            quote! { ::tust::__private::ExpectedResult::XFail { reason: #reason } }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use quote::{quote, ToTokens};
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::test::lower::tests::lower_with;
+
+    /// The items `#[tust::test]` expands `item` into.
+    pub(crate) fn expand(item: TokenStream2) -> Vec<Item> {
+        expand_with(quote!(), item)
+    }
+
+    /// The items `#[tust::test(attr)]` expands `item` into.
+    pub(crate) fn expand_with(attr: TokenStream2, item: TokenStream2) -> Vec<Item> {
+        syn::parse2::<File>(codegen_test(lower_with(attr, item)))
+            .unwrap()
+            .items
+    }
+
+    /// The registry entry of the test named `name` in `items`, as text.
+    pub(crate) fn entry(items: &[Item], name: &str) -> String {
+        let Item::Const(def) = find(items, &format!("__TUST_TEST_{}", name)) else {
+            panic!("the registry entry is not a `const`");
+        };
+        def.expr.to_token_stream().to_string()
+    }
+
+    /// The item of `items` named `name`.
+    pub(crate) fn find<'a>(items: &'a [Item], name: &str) -> &'a Item {
+        items
+            .iter()
+            .find(|item| match item {
+                Item::Fn(func) => func.sig.ident == name,
+                Item::Const(item) => item.ident == name,
+                Item::Mod(module) => module.ident == name,
+                _ => false,
+            })
+            .unwrap_or_else(|| panic!("no item named `{}`", name))
+    }
+
+    #[test]
+    fn emits_the_body_the_registry_entry_and_the_libtest_wrapper() {
+        let items = expand(quote!(
+            fn adds() {
+                assert_eq!(1 + 1, 2);
+            }
+        ));
+        let Item::Fn(body) = find(&items, "__tust_body_adds") else {
+            panic!("the body is not a function");
+        };
+        assert!(body.attrs.iter().any(|attr| attr.path().is_ident("doc")));
+        let def = entry(&items, "adds");
+        assert!(def.contains("name : \"adds\""), "{}", def);
+        assert!(
+            def.contains("module_path : :: core :: module_path ! ()"),
+            "{}",
+            def
+        );
+        let Item::Fn(wrapper) = find(&items, "adds") else {
+            panic!("the wrapper is not a function");
+        };
+        assert!(wrapper
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("test")));
+        let wrapper = wrapper.block.to_token_stream().to_string();
+        assert!(
+            wrapper.contains("run_libtest (& __TUST_TEST_adds)"),
+            "{}",
+            wrapper
+        );
+        assert!(items.iter().any(|item| matches!(
+            item,
+            Item::Macro(submit) if submit.mac.path.segments.last().unwrap().ident == "submit"
+        )));
+    }
+
+    #[test]
+    fn gates_every_item_on_the_tests_cfg() {
+        let items = expand(quote!(
+            #[cfg(unix)]
+            fn adds() {}
+        ));
+        for item in &items {
+            let attrs = match item {
+                Item::Fn(func) => &func.attrs,
+                Item::Const(def) => &def.attrs,
+                Item::Macro(submit) => &submit.attrs,
+                _ => continue,
+            };
+            assert!(
+                attrs.iter().any(|attr| attr.path().is_ident("cfg")),
+                "{}",
+                item.to_token_stream()
+            );
+        }
+    }
+
+    #[test]
+    fn requests_and_resolves_fixtures() {
+        let items = expand(quote!(
+            fn uses(db: Db) {}
+        ));
+        let def = entry(&items, "uses");
+        assert!(def.contains("FixtureRequest { name : \"db\""), "{}", def);
+        assert!(
+            def.contains("let db : Db = fixtures . get :: < Db > (\"db\") ?"),
+            "{}",
+            def
+        );
+        assert!(def.contains("__tust_body_uses (db)"), "{}", def);
+    }
+
+    #[test]
+    fn groups_the_wrappers_of_cases_in_a_module() {
+        let items = expand(quote! {
+            #[case(1)]
+            #[case(2)]
+            fn doubles(n: u32) {}
+        });
+        let def = entry(&items, "doubles_case_2_2");
+        assert!(def.contains("name : \"doubles::case_2_2\""), "{}", def);
+        assert!(def.contains("let n : u32 = 2 ;"), "{}", def);
+        let Item::Mod(module) = find(&items, "doubles") else {
+            panic!("the wrappers are not in a module");
+        };
+        assert!(module.attrs.iter().any(|attr| attr.path().is_ident("cfg")));
+        let wrappers = module.content.as_ref().unwrap().1.to_vec();
+        assert_eq!(wrappers.len(), 2);
+        let Item::Fn(wrapper) = find(&wrappers, "case_1_1") else {
+            panic!("the wrapper is not a function");
+        };
+        let call = wrapper.block.to_token_stream().to_string();
+        assert!(call.contains("run_libtest (& super :: __TUST_TEST_doubles_case_1_1)"));
+    }
+
+    #[test]
+    fn async_bodies_run_in_block_on() {
+        let items = expand(quote!(
+            async fn fetches() {}
+        ));
+        let def = entry(&items, "fetches");
+        assert!(def.contains(":: tust :: __private :: block_on"), "{}", def);
+        assert!(def.contains("runtime : :: tust :: __private :: AsyncRuntime :: Default"));
+        assert!(def.contains("__tust_body_fetches ()"), "{}", def);
+    }
+
+    #[test]
+    fn registers_tags() {
+        let items = expand_with(
+            quote!(tags("db", "slow")),
+            quote!(
+                fn adds() {}
+            ),
+        );
+        let def = entry(&items, "adds");
+        assert!(def.contains("tags : & [\"db\" , \"slow\"]"), "{}", def);
+        let def = entry(
+            &expand(quote!(
+                fn adds() {}
+            )),
+            "adds",
+        );
+        assert!(def.contains("tags : & []"), "{}", def);
+    }
+
+    #[test]
+    fn tests_always_skipped_are_ignored_by_libtest() {
+        let items = expand(quote!(
+            #[skip(reason = "flaky")]
+            fn t() {}
+        ));
+        let def = entry(&items, "t");
+        assert!(
+            def.contains(
+                "skip : :: tust :: __private :: Skip :: Always { reason : :: core :: \
+                          option :: Option :: Some (\"flaky\") }"
+            ),
+            "{}",
+            def
+        );
+        let Item::Fn(wrapper) = find(&items, "t") else {
+            panic!("the wrapper is not a function");
+        };
+        let ignore = wrapper
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("ignore"));
+        assert_eq!(
+            ignore.unwrap().to_token_stream().to_string(),
+            "# [ignore = \"flaky\"]"
+        );
+    }
+
+    #[test]
+    fn conditions_are_checked_at_run_time() {
+        let items = expand(quote!(
+            #[skip_if(cfg(windows))]
+            fn t() {}
+        ));
+        let def = entry(&items, "t");
+        assert!(
+            def.contains("condition : | | :: core :: cfg ! (windows)"),
+            "{}",
+            def
+        );
+        assert!(def.contains("reason : \"cfg(windows)\""), "{}", def);
+        let items = expand(quote!(
+            #[skip_if(env = "CI")]
+            fn t() {}
+        ));
+        let def = entry(&items, "t");
+        assert!(def.contains(":: std :: env :: var_os (\"CI\")"), "{}", def);
+        assert!(def.contains("reason : \"`CI` is set\""), "{}", def);
+        let Item::Fn(wrapper) = find(&items, "t") else {
+            panic!("the wrapper is not a function");
+        };
+        assert!(!wrapper
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("ignore")));
+    }
+
+    #[test]
+    fn registers_expected_results() {
+        let expected = |item| {
+            let def = entry(&expand(item), "t");
+            let at = def.find("expected :").unwrap();
+            def[at..def.find("allow_leaks").unwrap()].to_string()
+        };
+        assert_eq!(
+            expected(quote!(
+                fn t() {}
+            )),
+            "expected : :: tust :: __private :: ExpectedResult :: Pass , "
+        );
+        assert!(expected(quote!(
+            #[should_fail(expected = "boom")]
+            fn t() {}
+        ))
+        .contains("ShouldFail { expected : :: core :: option :: Option :: Some (\"boom\") }"));
+        assert!(expected(quote!(
+            #[xfail]
+            fn t() {}
+        ))
+        .contains("XFail { reason : :: core :: option :: Option :: None }"));
+    }
+
+    #[test]
+    fn rebuilds_data_driven_tests_when_their_file_changes() {
+        let path = crate::testing::write("rows.json", r#"[{"name": "one"}, {"name": "two"}]"#);
+        let items = expand_with(
+            quote!(cases_from = #path),
+            quote!(
+                fn reads(row: Row) {}
+            ),
+        );
+        let def = entry(&items, "reads_case_2_two");
+        assert!(def.contains("name : \"reads::case_2_two\""), "{}", def);
+        assert!(
+            def.contains("data_row (& :: tust :: __private :: DataRow"),
+            "{}",
+            def
+        );
+        let rebuild = items
+            .iter()
+            .find(|item| matches!(item, Item::Const(def) if def.ident == "_"))
+            .unwrap();
+        assert_eq!(
+            rebuild.to_token_stream().to_string(),
+            quote! {
+                const _: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path));
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn names_combinations_after_their_values() {
+        let items = expand(quote! {
+            #[values(n = [1, 2], flag = [true])]
+            fn checks(n: u32, flag: bool) {}
+        });
+        let def = entry(&items, "checks_case_2_n_2_flag_true");
+        assert!(def.contains("let n : u32 = 2 ;"), "{}", def);
+        assert!(def.contains("let flag : bool = true ;"), "{}", def);
+        let Item::Mod(module) = find(&items, "checks") else {
+            panic!("the wrappers are not in a module");
+        };
+        assert_eq!(module.content.as_ref().unwrap().1.len(), 2);
+    }
+}
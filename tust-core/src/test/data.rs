@@ -0,0 +1,698 @@
+//! Reading the rows of a `cases_from` data file, during analysis.
+//!
+//! Every format is read into JSON values, which the generated code embeds
+//! and the runtime deserializes into the test's parameter. JSON files hold
+//! an array of rows; YAML files a sequence of them, in the block and flow
+//! styles, without anchors, tags, or multiple documents; and CSV files a
+//! header row naming the fields of the rows below it. CSV fields are kept
+//! as strings, which the runtime parses into the type of the field.
+
+use serde_json::{Map, Number, Value};
+
+/// A data file's format, from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the rows of `text`, or describes why they cannot be read.
+pub fn rows(format: Format, text: &str) -> Result<Vec<Value>, String> {
+    let value = match format {
+        Format::Json => serde_json::from_str(text).map_err(|err| err.to_string())?,
+        Format::Yaml => yaml(text)?,
+        Format::Csv => return csv(text),
+    };
+    match value {
+        Value::Array(rows) => Ok(rows),
+        _ => Err("expected a list of cases".to_string()),
+    }
+}
+
+/// Reads a CSV file as objects keyed by its header row, with string values.
+fn csv(text: &str) -> Result<Vec<Value>, String> {
+    let mut records = csv_records(text)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    if let Some(name) = header.iter().find(|name| name.is_empty()) {
+        return Err(format!("line 1: column {:?} has no name", name));
+    }
+    records
+        .map(|(line, fields)| {
+            if fields.len() != header.len() {
+                return Err(format!(
+                    "line {}: expected {} fields like the header, found {}",
+                    line,
+                    header.len(),
+                    fields.len()
+                ));
+            }
+            let row: Map<String, Value> = header
+                .iter()
+                .cloned()
+                .zip(fields.into_iter().map(Value::String))
+                .collect();
+            Ok(Value::Object(row))
+        })
+        .collect()
+}
+
+/// The records of a CSV file, with the lines they start on, as RFC 4180
+/// has them: quoted fields may hold commas, newlines, and doubled quotes.
+/// Blank lines are left out.
+fn csv_records(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            match chars.next() {
+                Some('"') if field.is_empty() && !quoted => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') if chars.peek() == Some(&'"') => {
+                                chars.next();
+                                field.push('"');
+                            }
+                            Some('"') => break,
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                field.push(c);
+                            }
+                            None => return Err(format!("line {}: unclosed quote", start)),
+                        }
+                    }
+                }
+                Some(',') => {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                }
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    break;
+                }
+                Some(c) if quoted => {
+                    return Err(format!(
+                        "line {}: unexpected {:?} after a quoted field",
+                        line, c
+                    ))
+                }
+                Some(c) => field.push(c),
+            }
+        }
+        if fields.is_empty() && field.is_empty() && !quoted {
+            continue;
+        }
+        fields.push(field);
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+/// A line of a YAML file, without its indentation and comment.
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+/// Reads the YAML in `text`.
+fn yaml(text: &str) -> Result<Value, String> {
+    let mut lines = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let content = strip_comment(raw);
+        let trimmed = content.trim_start();
+        if trimmed.trim_end().is_empty() {
+            continue;
+        }
+        if raw.starts_with("---") || raw.starts_with("...") {
+            if lines.is_empty() && raw.trim_end().len() == 3 {
+                continue;
+            }
+            return Err(format!(
+                "line {}: multiple documents are not supported",
+                index + 1
+            ));
+        }
+        if content[..content.len() - trimmed.len()].contains('\t') {
+            return Err(format!("line {}: tabs cannot indent YAML", index + 1));
+        }
+        lines.push(Line {
+            number: index + 1,
+            indent: content.len() - trimmed.len(),
+            text: trimmed.trim_end(),
+        });
+    }
+    let mut parser = Yaml {
+        lines,
+        next: 0,
+        raw: text.lines().collect(),
+    };
+    let Some(first) = parser.lines.first() else {
+        return Ok(Value::Null);
+    };
+    let value = parser.block(first.indent)?;
+    match parser.lines.get(parser.next) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.number)),
+        None => Ok(value),
+    }
+}
+
+/// `line` without a comment: a `#` at its start or after whitespace, and
+/// outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (at, c) in line.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && opens_scalar(previous) => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..at],
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Whether a scalar may start after `c`, so that a quote there opens it.
+fn opens_scalar(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '[' | '{' | ',' | ':' | '-')
+}
+
+struct Yaml<'a> {
+    lines: Vec<Line<'a>>,
+    next: usize,
+    /// Every line of the file, for block scalars, which keep their comments.
+    raw: Vec<&'a str>,
+}
+
+impl Yaml<'_> {
+    /// The node whose lines start at `indent`.
+    fn block(&mut self, indent: usize) -> Result<Value, String> {
+        let line = &self.lines[self.next];
+        if is_item(line.text) {
+            self.sequence(indent)
+        } else if split_key(line.text).is_some() {
+            self.mapping(indent)
+        } else {
+            let (number, text) = (line.number, line.text);
+            self.next += 1;
+            inline(text).map_err(|err| format!("line {}: {}", number, err))
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        while let Some(line) = self.lines.get(self.next) {
+            if line.indent != indent || !is_item(line.text) {
+                break;
+            }
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.next += 1;
+                items.push(self.nested(indent, false)?);
+            } else {
+                // The rest of the line starts a node indented past the dash,
+                // as the lines that continue it are.
+                let line = &mut self.lines[self.next];
+                line.indent += line.text.len() - rest.len();
+                line.text = rest;
+                let indent = line.indent;
+                items.push(self.block(indent)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut entries = Map::new();
+        while let Some(line) = self.lines.get(self.next) {
+            if line.indent != indent || is_item(line.text) {
+                break;
+            }
+            let number = line.number;
+            let Some((key, rest)) = split_key(line.text) else {
+                return Err(format!("line {}: expected `key: value`", number));
+            };
+            let key = match inline(key).map_err(|err| format!("line {}: {}", number, err))? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            self.next += 1;
+            let value = if rest.is_empty() {
+                self.nested(indent, true)?
+            } else if let Some(style) = rest.strip_prefix(['|', '>']) {
+                let folded = rest.starts_with('>');
+                self.block_scalar(indent, folded, style)?
+            } else {
+                inline(rest).map_err(|err| format!("line {}: {}", number, err))?
+            };
+            if entries.insert(key.clone(), value).is_some() {
+                return Err(format!("line {}: duplicate key {:?}", number, key));
+            }
+        }
+        Ok(Value::Object(entries))
+    }
+
+    /// The node on the lines after a `-` or `key:` ending its line at
+    /// `indent`: null if there is none. A mapping's value may be a sequence
+    /// at the key's own indentation.
+    fn nested(&mut self, indent: usize, in_mapping: bool) -> Result<Value, String> {
+        match self.lines.get(self.next) {
+            Some(line) if line.indent > indent => {
+                let indent = line.indent;
+                self.block(indent)
+            }
+            Some(line) if in_mapping && line.indent == indent && is_item(line.text) => {
+                self.sequence(indent)
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// A `|` or `>` scalar: the lines after `key:`, indented past `indent`.
+    fn block_scalar(&mut self, indent: usize, folded: bool, style: &str) -> Result<Value, String> {
+        let start = match self.next.checked_sub(1) {
+            Some(at) => self.lines[at].number,
+            None => 0,
+        };
+        let end = match self.lines[self.next..]
+            .iter()
+            .position(|line| line.indent <= indent)
+        {
+            Some(offset) => {
+                let end = self.lines[self.next + offset].number - 1;
+                self.next += offset;
+                end
+            }
+            None => {
+                self.next = self.lines.len();
+                self.raw.len()
+            }
+        };
+        let lines: Vec<&str> = self.raw[start..end].to_vec();
+        let margin = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let mut lines: Vec<&str> = lines
+            .iter()
+            .map(|line| line.get(margin..).unwrap_or("").trim_end_matches('\r'))
+            .collect();
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        let mut text = if folded {
+            let mut text = String::new();
+            for line in &lines {
+                if line.is_empty() {
+                    text.push('\n');
+                } else {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push(' ');
+                    }
+                    text.push_str(line);
+                }
+            }
+            text
+        } else {
+            lines.join("\n")
+        };
+        if !style.contains('-') && !text.is_empty() {
+            text.push('\n');
+        }
+        Ok(Value::String(text))
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` at its first `: ` outside quotes and brackets, or a
+/// line ending in `key:`.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with(['[', '{']) {
+        return None;
+    }
+    let mut quote = None;
+    for (at, c) in text.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && at == 0 => quote = Some(c),
+            None if c == ':' => {
+                let rest = &text[at + 1..];
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((text[..at].trim_end(), rest.trim_start()));
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Reads a scalar, or a flow sequence or mapping, written on one line.
+fn inline(text: &str) -> Result<Value, String> {
+    let mut flow = Flow { text, at: 0 };
+    let value = flow.value(false)?;
+    flow.skip_spaces();
+    if flow.at < text.len() {
+        return Err(format!("unexpected `{}`", &text[flow.at..]));
+    }
+    Ok(value)
+}
+
+struct Flow<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl<'a> Flow<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.at..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.rest().starts_with(c) {
+            self.at += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// A node; inside brackets, plain scalars end at `,`, `]`, `}`, and `: `.
+    fn value(&mut self, in_flow: bool) -> Result<Value, String> {
+        self.skip_spaces();
+        if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                items.push(self.value(true)?);
+                if !self.eat(',') && !self.rest().starts_with(']') {
+                    return Err("expected `,` or `]` in a flow sequence".to_string());
+                }
+            }
+            return Ok(Value::Array(items));
+        }
+        if self.eat('{') {
+            let mut entries = Map::new();
+            while !self.eat('}') {
+                let key = match self.value(true)? {
+                    Value::String(key) => key,
+                    key => key.to_string(),
+                };
+                let value = if self.eat(':') {
+                    self.value(true)?
+                } else {
+                    Value::Null
+                };
+                entries.insert(key, value);
+                if !self.eat(',') && !self.rest().starts_with('}') {
+                    return Err("expected `,` or `}` in a flow mapping".to_string());
+                }
+            }
+            return Ok(Value::Object(entries));
+        }
+        let rest = self.rest();
+        if rest.starts_with('"') {
+            let (value, length) = double_quoted(rest)?;
+            self.at += length;
+            return Ok(Value::String(value));
+        }
+        if rest.starts_with('\'') {
+            let (value, length) = single_quoted(rest)?;
+            self.at += length;
+            return Ok(Value::String(value));
+        }
+        let end = if in_flow {
+            rest.char_indices()
+                .find(|&(at, c)| {
+                    matches!(c, ',' | ']' | '}')
+                        || (c == ':' && rest[at + 1..].starts_with([' ', ',', ']', '}']))
+                        || (c == ':' && at + 1 == rest.len())
+                })
+                .map_or(rest.len(), |(at, _)| at)
+        } else {
+            rest.len()
+        };
+        let plain = rest[..end].trim_end();
+        self.at += end;
+        Ok(plain_scalar(plain))
+    }
+}
+
+/// A `"..."` scalar at the start of `text`, and its length there.
+fn double_quoted(text: &str) -> Result<(String, usize), String> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, at + 1)),
+            '\\' => {
+                let Some((_, escape)) = chars.next() else {
+                    break;
+                };
+                match escape {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    '0' => value.push('\0'),
+                    '"' | '\\' | '/' | ' ' => value.push(escape),
+                    'x' | 'u' | 'U' => {
+                        let digits = match escape {
+                            'x' => 2,
+                            'u' => 4,
+                            _ => 8,
+                        };
+                        let hex: String = chars.by_ref().take(digits).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape `\\{}{}`", escape, hex))?;
+                        value.push(c);
+                    }
+                    _ => return Err(format!("unknown escape `\\{}`", escape)),
+                }
+            }
+            _ => value.push(c),
+        }
+    }
+    Err("unclosed `\"`".to_string())
+}
+
+/// A `'...'` scalar at the start of `text`, and its length there.
+fn single_quoted(text: &str) -> Result<(String, usize), String> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((at, c)) = chars.next() {
+        if c == '\'' {
+            if chars.peek().is_some_and(|&(_, next)| next == '\'') {
+                chars.next();
+                value.push('\'');
+                continue;
+            }
+            return Ok((value, at + 1));
+        }
+        value.push(c);
+    }
+    Err("unclosed `'`".to_string())
+}
+
+/// An unquoted scalar, resolved as YAML 1.2's core schema does.
+fn plain_scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+        if let Ok(n) = text.trim_start_matches('+').parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        if let Ok(n) = text.trim_start_matches('+').parse::<u64>() {
+            return Value::Number(n.into());
+        }
+    }
+    let is_float = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'-' | b'+'))
+        && digits.bytes().any(|b| b.is_ascii_digit());
+    if is_float {
+        if let Some(n) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn formats_follow_extensions() {
+        assert_eq!(Format::from_path("cases.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("data/cases.YML"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("cases.yaml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("cases.csv"), Some(Format::Csv));
+        assert_eq!(Format::from_path("cases.toml"), None);
+        assert_eq!(Format::from_path("cases"), None);
+    }
+
+    #[test]
+    fn json_holds_a_list_of_rows() {
+        let rows = rows(Format::Json, r#"[{"a": 1}, [2, "b"], null]"#).unwrap();
+        assert_eq!(rows, [json!({"a": 1}), json!([2, "b"]), json!(null)]);
+        assert_eq!(
+            super::rows(Format::Json, r#"{"a": 1}"#).unwrap_err(),
+            "expected a list of cases"
+        );
+        assert!(super::rows(Format::Json, "[1,").is_err());
+    }
+
+    #[test]
+    fn csv_rows_are_keyed_by_the_header() {
+        let text = "name,input,expected\r\n\
+                    plain,1,2\r\n\
+                    \n\
+                    \"quoted, with a comma\",\"say \"\"hi\"\"\",\"two\nlines\"\n";
+        assert_eq!(
+            rows(Format::Csv, text).unwrap(),
+            [
+                json!({"name": "plain", "input": "1", "expected": "2"}),
+                json!({
+                    "name": "quoted, with a comma",
+                    "input": "say \"hi\"",
+                    "expected": "two\nlines",
+                }),
+            ]
+        );
+        assert!(rows(Format::Csv, "").unwrap().is_empty());
+        assert!(rows(Format::Csv, "a,b\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_csv() {
+        let error = |text| rows(Format::Csv, text).unwrap_err();
+        assert_eq!(error("a,,b\n"), "line 1: column \"\" has no name");
+        assert_eq!(
+            error("a,b\n1,2\n3\n"),
+            "line 3: expected 2 fields like the header, found 1"
+        );
+        assert_eq!(error("a\n\"open\n"), "line 2: unclosed quote");
+        assert_eq!(
+            error("a\n\"quoted\"after\n"),
+            "line 2: unexpected 'a' after a quoted field"
+        );
+    }
+
+    #[test]
+    fn yaml_block_sequences_of_mappings() {
+        let text = "---\n\
+                    # The cases.\n\
+                    - name: first # a comment\n  \
+                      input: [1, 2]\n  \
+                      expected: {sum: 3, \"note\": 'it''s'}\n\
+                    - name: \"second\\tcase\"\n  \
+                      input:\n  \
+                      - -1\n  \
+                      - 2.5\n  \
+                      expected: ~\n  \
+                      valid: false\n";
+        assert_eq!(
+            rows(Format::Yaml, text).unwrap(),
+            [
+                json!({
+                    "name": "first",
+                    "input": [1, 2],
+                    "expected": {"sum": 3, "note": "it's"},
+                }),
+                json!({
+                    "name": "second\tcase",
+                    "input": [-1, 2.5],
+                    "expected": null,
+                    "valid": false,
+                }),
+            ]
+        );
+        assert_eq!(
+            rows(Format::Yaml, "[1, two, {a: b}]").unwrap(),
+            [json!(1), json!("two"), json!({"a": "b"})]
+        );
+    }
+
+    #[test]
+    fn yaml_block_scalars() {
+        let text = "- literal: |\n    one\n    two\n  folded: >-\n    one\n    two\n";
+        assert_eq!(
+            rows(Format::Yaml, text).unwrap(),
+            [json!({"literal": "one\ntwo\n", "folded": "one two"})]
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_yaml() {
+        let error = |text| rows(Format::Yaml, text).unwrap_err();
+        assert_eq!(
+            error("- a\n---\n- b\n"),
+            "line 2: multiple documents are not supported"
+        );
+        assert_eq!(
+            error("- a: 1\n\t b: 2\n"),
+            "line 2: tabs cannot indent YAML"
+        );
+        assert_eq!(error("- a: 1\n  a: 2\n"), "line 2: duplicate key \"a\"");
+        assert_eq!(error("- \"open\n"), "line 1: unclosed `\"`");
+        assert_eq!(error("a: 1\n"), "expected a list of cases");
+    }
+
+    #[test]
+    fn plain_scalars_resolve_as_the_core_schema_does() {
+        assert_eq!(plain_scalar("null"), json!(null));
+        assert_eq!(plain_scalar("True"), json!(true));
+        assert_eq!(plain_scalar("+7"), json!(7));
+        assert_eq!(plain_scalar("18446744073709551615"), json!(u64::MAX));
+        assert_eq!(plain_scalar("1e3"), json!(1000.0));
+        assert_eq!(plain_scalar("1.2.3"), json!("1.2.3"));
+        assert_eq!(plain_scalar("yes"), json!("yes"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Stage 3: lower the test model into the items that will be generated.
+
+use proc_macro2::Span;
+use quote::{format_ident, ToTokens};
+use syn::{Attribute, Expr, Ident, ItemFn, LitInt, LitStr};
+
+use super::analyze::{AsyncModel, Case, LimitsModel, StressModel, TestModel};
+use super::parse::{EnvVar, FailAttr, SkipAttr};
+use crate::params::Param;
+
+/// Longest description derived from case values before it is truncated.
+const MAX_CASE_DESCRIPTION: usize = 40;
+
+/// One registered test: the whole test, or one case of a parameterized test.
+pub struct InstanceIR {
+    /// Name of the `const` holding the registry entry.
+    pub def_ident: Ident,
+    /// Name of the `#[test]` wrapper used when running under libtest.
+    pub wrapper_ident: Ident,
+    /// Test name as reported by the runtime, relative to its module.
+    pub name: String,
+    /// Case parameters and the values bound to them.
+    pub bindings: Vec<(Param, Expr)>,
+}
+
+/// Everything codegen needs to emit a registered test.
+pub struct TestIR {
+    /// The user's function, renamed so the libtest wrapper can take its name.
+    pub body: ItemFn,
+    /// For parameterized tests, the module grouping the per-case wrappers.
+    pub wrapper_module: Option<Ident>,
+    pub instances: Vec<InstanceIR>,
+    /// The data file of `cases_from`, relative to the crate root.
+    pub data_file: Option<LitStr>,
+    pub fixtures: Vec<Param>,
+    pub asyncness: Option<AsyncModel>,
+    pub serial: bool,
+    pub timeout: Option<LitInt>,
+    pub retries: Option<LitInt>,
+    pub stress: Option<StressModel>,
+    pub model_check: bool,
+    pub tags: Vec<LitStr>,
+    pub skip: Option<SkipAttr>,
+    pub fail: Option<FailAttr>,
+    pub envs: Vec<EnvVar>,
+    pub allow_leaks: bool,
+    pub isolated_cwd: bool,
+    pub depends_on: Vec<LitStr>,
+    pub locks: Vec<LitStr>,
+    pub limits: Option<LimitsModel>,
+    pub priority: Option<Ident>,
+    pub cfgs: Vec<Attribute>,
+    /// Span used for `file!()`/`line!()` so locations point at the function.
+    pub span: Span,
+}
+
+pub fn lower_test(model: TestModel) -> TestIR {
+    let TestModel {
+        mut func,
+        case_params,
+        cases,
+        data_file,
+        fixtures,
+        asyncness,
+        serial,
+        timeout,
+        retries,
+        stress,
+        model_check,
+        tags,
+        skip,
+        fail,
+        envs,
+        allow_leaks,
+        isolated_cwd,
+        depends_on,
+        locks,
+        limits,
+        priority,
+        cfgs,
+    } = model;
+    let ident = func.sig.ident.clone();
+    func.sig.ident = format_ident!("__tust_body_{}", ident);
+
+    let (wrapper_module, instances) = if cases.is_empty() {
+        let instance = InstanceIR {
+            def_ident: format_ident!("__TUST_TEST_{}", ident),
+            wrapper_ident: ident.clone(),
+            name: ident.to_string(),
+            bindings: Vec::new(),
+        };
+        (None, vec![instance])
+    } else {
+        let instances = cases
+            .into_iter()
+            .enumerate()
+            .map(|(index, case)| lower_case(&ident, &case_params, index, case))
+            .collect();
+        (Some(ident.clone()), instances)
+    };
+
+    TestIR {
+        body: func,
+        wrapper_module,
+        instances,
+        data_file,
+        fixtures,
+        asyncness,
+        serial,
+        timeout,
+        retries,
+        stress,
+        model_check,
+        tags,
+        skip,
+        fail,
+        envs,
+        allow_leaks,
+        isolated_cwd,
+        depends_on,
+        locks,
+        limits,
+        priority,
+        cfgs,
+        span: ident.span(),
+    }
+}
+
+fn lower_case(test: &Ident, params: &[Param], index: usize, case: Case) -> InstanceIR {
+    let description = match (&case.name, &case.description) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(description)) => describe(std::iter::once(description.clone())),
+        (None, None) => describe(
+            case.values
+                .iter()
+                .map(|value| value.to_token_stream().to_string()),
+        ),
+    };
+    let case_ident = if description.is_empty() {
+        format_ident!("case_{}", index + 1)
+    } else {
+        format_ident!("case_{}_{}", index + 1, description)
+    };
+
+    InstanceIR {
+        def_ident: format_ident!("__TUST_TEST_{}_{}", test, case_ident),
+        name: format!("{}::{}", test, case_ident),
+        wrapper_ident: case_ident,
+        bindings: params.iter().cloned().zip(case.values).collect(),
+    }
+}
+
+/// Turns case values into an identifier fragment, e.g. `1, "a b"` → `1_a_b`,
+/// spelling the sign of negative numbers: `-1, -2` → `neg1_neg2`.
+fn describe(values: impl Iterator<Item = String>) -> String {
+    let mut description = String::new();
+    for value in values {
+        // The last character that is not whitespace, if any.
+        let mut previous = None;
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_alphanumeric() {
+                description.push(c);
+            } else if c == '-'
+                && matches!(previous, None | Some('(' | '[' | '{' | ',' | '='))
+                && chars
+                    .clone()
+                    .find(|c: &char| !c.is_whitespace())
+                    .is_some_and(|c| c.is_ascii_digit())
+            {
+                if !description.is_empty() && !description.ends_with('_') {
+                    description.push('_');
+                }
+                description.push_str("neg");
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            } else if !description.is_empty() && !description.ends_with('_') {
+                description.push('_');
+            }
+            if !c.is_whitespace() {
+                previous = Some(c);
+            }
+        }
+        if !description.is_empty() && !description.ends_with('_') {
+            description.push('_');
+        }
+    }
+
+    description.truncate(MAX_CASE_DESCRIPTION);
+    description.trim_end_matches('_').to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+    use crate::test::analyze::analyze_test;
+    use crate::test::parse::parse_test;
+
+    pub(crate) fn lower(item: proc_macro2::TokenStream) -> TestIR {
+        lower_with(quote!(), item)
+    }
+
+    pub(crate) fn lower_with(
+        attr: proc_macro2::TokenStream,
+        item: proc_macro2::TokenStream,
+    ) -> TestIR {
+        lower_test(analyze_test(parse_test(attr, item).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn renames_the_body_and_names_the_entry_after_the_test() {
+        let ir = lower(quote!(
+            fn adds() {}
+        ));
+        assert_eq!(ir.body.sig.ident, "__tust_body_adds");
+        assert!(ir.wrapper_module.is_none());
+        assert_eq!(ir.instances.len(), 1);
+        let instance = &ir.instances[0];
+        assert_eq!(instance.def_ident, "__TUST_TEST_adds");
+        assert_eq!(instance.wrapper_ident, "adds");
+        assert_eq!(instance.name, "adds");
+        assert!(instance.bindings.is_empty());
+    }
+
+    #[test]
+    fn names_cases_after_their_values_or_names() {
+        let ir = lower(quote! {
+            #[case(1, "a b")]
+            #[case::large(1000, "c")]
+            #[case(-1, "")]
+            fn adds(a: i32, b: &str) {}
+        });
+        assert_eq!(ir.wrapper_module.as_ref().unwrap(), "adds");
+        let names: Vec<_> = ir.instances.iter().map(|case| case.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "adds::case_1_1_a_b",
+                "adds::case_2_large",
+                "adds::case_3_neg1"
+            ]
+        );
+        let case = &ir.instances[1];
+        assert_eq!(case.def_ident, "__TUST_TEST_adds_case_2_large");
+        assert_eq!(case.wrapper_ident, "case_2_large");
+        let bound: Vec<_> = case
+            .bindings
+            .iter()
+            .map(|(param, value)| format!("{} = {}", param.ident, value.to_token_stream()))
+            .collect();
+        assert_eq!(bound, ["a = 1000", "b = \"c\""]);
+    }
+
+    #[test]
+    fn spells_the_sign_of_negative_numbers() {
+        let ir = lower(quote! {
+            #[case(-1, -2)]
+            #[case(1, 2)]
+            #[case(-1.5, [-3, 4 - 5])]
+            fn adds(a: i32, b: i32) {}
+        });
+        let names: Vec<_> = ir.instances.iter().map(|case| case.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "adds::case_1_neg1_neg2",
+                "adds::case_2_1_2",
+                "adds::case_3_neg1_5_neg3_4_5",
+            ]
+        );
+    }
+
+    #[test]
+    fn truncates_long_descriptions() {
+        assert_eq!(
+            describe(["a b", "(c)"].map(String::from).into_iter()),
+            "a_b_c"
+        );
+        assert_eq!(describe(std::iter::once("!!".to_string())), "");
+        let long = describe(std::iter::once("x".repeat(100)));
+        assert_eq!(long.len(), MAX_CASE_DESCRIPTION);
+    }
+}
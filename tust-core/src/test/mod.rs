@@ -0,0 +1,7 @@
+//! Pipeline for the `#[tust::test]` attribute.
+
+pub mod analyze;
+pub mod codegen;
+pub mod data;
+pub mod lower;
+pub mod parse;
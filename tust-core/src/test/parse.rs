@@ -0,0 +1,929 @@
+//! Stage 1: parse the attribute arguments and the annotated function.
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Error, Expr, ExprLit, Ident, ItemFn, Lit, LitInt, LitStr, Meta, Result, Token,
+};
+
+/// Arguments given to `#[tust::test(...)]`, kept as written until analysis.
+#[derive(Debug, Default)]
+pub struct TestArgs {
+    /// `runtime = "tokio" | "async-std" | "builtin"`
+    pub runtime: Option<LitStr>,
+    /// `flavor = "current_thread" | "multi_thread"`
+    pub flavor: Option<LitStr>,
+    /// `worker_threads = N`
+    pub worker_threads: Option<LitInt>,
+    /// `serial`
+    pub serial: Option<Span>,
+    /// `timeout = MILLISECONDS`
+    pub timeout: Option<LitInt>,
+    /// `retry = N`
+    pub retry: Option<LitInt>,
+    /// `tags("NAME", ...)`
+    pub tags: Option<Vec<LitStr>>,
+    /// `lock = "NAME"` or `lock("NAME", ...)`
+    pub locks: Option<Vec<LitStr>>,
+    /// `repeat = N`
+    pub repeat: Option<LitInt>,
+    /// `threads = N`
+    pub threads: Option<LitInt>,
+    /// `model_check`
+    pub model_check: Option<Span>,
+    /// `isolated_cwd`
+    pub isolated_cwd: Option<Span>,
+    /// `cases_from = "PATH"`
+    pub cases_from: Option<LitStr>,
+}
+
+impl TestArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> Result<()> {
+        if meta.path.is_ident("runtime") {
+            set_once(&mut self.runtime, &meta)
+        } else if meta.path.is_ident("flavor") {
+            set_once(&mut self.flavor, &meta)
+        } else if meta.path.is_ident("worker_threads") {
+            set_once(&mut self.worker_threads, &meta)
+        } else if meta.path.is_ident("serial") {
+            set_flag(&mut self.serial, &meta)
+        } else if meta.path.is_ident("timeout") {
+            set_once(&mut self.timeout, &meta)
+        } else if meta.path.is_ident("retry") {
+            set_once(&mut self.retry, &meta)
+        } else if meta.path.is_ident("repeat") {
+            set_once(&mut self.repeat, &meta)
+        } else if meta.path.is_ident("threads") {
+            set_once(&mut self.threads, &meta)
+        } else if meta.path.is_ident("model_check") {
+            set_flag(&mut self.model_check, &meta)
+        } else if meta.path.is_ident("isolated_cwd") {
+            set_flag(&mut self.isolated_cwd, &meta)
+        } else if meta.path.is_ident("cases_from") {
+            set_once(&mut self.cases_from, &meta)
+        } else if meta.path.is_ident("tags") {
+            set_list(&mut self.tags, &meta)
+        } else if meta.path.is_ident("lock") {
+            if !meta.input.peek(Token![=]) {
+                return set_list(&mut self.locks, &meta);
+            }
+            if self.locks.is_some() {
+                return Err(meta.error("duplicate argument"));
+            }
+            self.locks = Some(vec![meta.value()?.parse()?]);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `#[tust::test]` argument"))
+        }
+    }
+}
+
+/// Parses `name = value` into `slot`, rejecting a repeated argument.
+fn set_once<T: syn::parse::Parse>(slot: &mut Option<T>, meta: &ParseNestedMeta<'_>) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+    *slot = Some(meta.value()?.parse()?);
+    Ok(())
+}
+
+/// Parses `name(value, ...)` into `slot`, rejecting a repeated argument.
+fn set_list<T: syn::parse::Parse>(
+    slot: &mut Option<Vec<T>>,
+    meta: &ParseNestedMeta<'_>,
+) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+    let content;
+    syn::parenthesized!(content in meta.input);
+    let values = Punctuated::<T, Token![,]>::parse_terminated(&content)?;
+    *slot = Some(values.into_iter().collect());
+    Ok(())
+}
+
+/// Parses a flag such as `serial`, which takes no value.
+fn set_flag(slot: &mut Option<Span>, meta: &ParseNestedMeta<'_>) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+    if !meta.input.is_empty() && !meta.input.peek(Token![,]) {
+        return Err(meta.error("this argument does not take a value"));
+    }
+    *slot = Some(meta.path.span());
+    Ok(())
+}
+
+/// A `#[case(...)]` or `#[case::name(...)]` attribute.
+pub struct CaseAttr {
+    /// Optional description given as `#[case::name(...)]`.
+    pub name: Option<Ident>,
+    pub args: Vec<Expr>,
+    pub span: Span,
+}
+
+impl CaseAttr {
+    fn is_case(attr: &Attribute) -> bool {
+        attr.path()
+            .segments
+            .first()
+            .is_some_and(|segment| segment.ident == "case")
+    }
+
+    fn parse(attr: &Attribute) -> Result<Self> {
+        let segments = &attr.path().segments;
+        let name = match segments.len() {
+            1 => None,
+            2 => Some(segments[1].ident.clone()),
+            _ => {
+                return Err(Error::new(
+                    attr.path().span(),
+                    "expected `#[case(...)]` or `#[case::name(...)]`",
+                ))
+            }
+        };
+        let args = attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+
+        Ok(CaseAttr {
+            name,
+            args: args.into_iter().collect(),
+            span: attr.span(),
+        })
+    }
+}
+
+/// One `name = [value, ...]` list of a `#[values(...)]` attribute.
+pub struct ValueList {
+    pub ident: Ident,
+    pub values: Vec<Expr>,
+    pub span: Span,
+}
+
+impl syn::parse::Parse for ValueList {
+    fn parse(input: syn::parse::ParseStream<'_>) -> Result<Self> {
+        let ident: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let values = match input.parse::<Expr>()? {
+            Expr::Array(array) => array.elems.into_iter().collect(),
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "expected a list of values, such as `[1, 2, 3]`",
+                ))
+            }
+        };
+        Ok(ValueList {
+            span: ident.span(),
+            ident,
+            values,
+        })
+    }
+}
+
+/// An `#[exclude(name = value, ...)]` attribute, leaving out the
+/// combinations of `#[values(...)]` with all of the values given.
+pub struct ExcludeAttr {
+    pub values: Vec<NamedValue>,
+    pub span: Span,
+}
+
+/// One `name = value` pair of an `#[exclude(...)]` attribute.
+pub struct NamedValue {
+    pub ident: Ident,
+    pub value: Expr,
+}
+
+impl syn::parse::Parse for NamedValue {
+    fn parse(input: syn::parse::ParseStream<'_>) -> Result<Self> {
+        let ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        Ok(NamedValue {
+            ident,
+            value: input.parse()?,
+        })
+    }
+}
+
+impl ExcludeAttr {
+    fn parse(attr: &Attribute) -> Result<Self> {
+        let values = attr.parse_args_with(Punctuated::<NamedValue, Token![,]>::parse_terminated)?;
+        if values.is_empty() {
+            return Err(Error::new(
+                attr.span(),
+                "expected `#[exclude(name = value, ...)]`",
+            ));
+        }
+        Ok(ExcludeAttr {
+            values: values.into_iter().collect(),
+            span: attr.span(),
+        })
+    }
+}
+
+/// A `#[skip]`, `#[skip(reason = "...")]`, or `#[skip_if(...)]` attribute.
+pub struct SkipAttr {
+    /// Set for `#[skip_if(...)]`; `#[skip]` always skips.
+    pub condition: Option<SkipCondition>,
+    pub reason: Option<LitStr>,
+}
+
+/// The condition of a `#[skip_if(...)]` attribute.
+pub enum SkipCondition {
+    /// `cfg(...)`, with the predicate as written.
+    Cfg(TokenStream2),
+    /// `env = "NAME"`
+    Env(LitStr),
+}
+
+impl SkipAttr {
+    fn parse(attr: &Attribute) -> Result<Self> {
+        let conditional = attr.path().is_ident("skip_if");
+        let mut skip = SkipAttr {
+            condition: None,
+            reason: None,
+        };
+        if !conditional && matches!(attr.meta, Meta::Path(_)) {
+            return Ok(skip);
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("reason") {
+                set_once(&mut skip.reason, &meta)
+            } else if conditional && (meta.path.is_ident("cfg") || meta.path.is_ident("env")) {
+                if skip.condition.is_some() {
+                    return Err(meta.error("`#[skip_if]` takes a single condition"));
+                }
+                skip.condition = Some(if meta.path.is_ident("cfg") {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    SkipCondition::Cfg(content.parse()?)
+                } else {
+                    SkipCondition::Env(meta.value()?.parse()?)
+                });
+                Ok(())
+            } else if conditional {
+                Err(meta.error("expected `cfg(...)`, `env = \"NAME\"`, or `reason = \"...\"`"))
+            } else {
+                Err(meta.error("expected `reason = \"...\"`"))
+            }
+        })?;
+        if conditional && skip.condition.is_none() {
+            return Err(Error::new(
+                attr.span(),
+                "expected a condition: `#[skip_if(cfg(...))]` or `#[skip_if(env = \"NAME\")]`",
+            ));
+        }
+        Ok(skip)
+    }
+}
+
+/// A `#[should_fail]`, `#[should_panic]`, or `#[xfail]` attribute.
+pub enum FailAttr {
+    /// `#[should_fail]` or `#[should_fail(expected = "...")]`
+    ShouldFail { expected: Option<LitStr> },
+    /// `#[should_panic]`, optionally with `contains = "..."` and
+    /// `regex = "..."`; libtest's `expected = "..."` is taken as `contains`.
+    ShouldPanic {
+        contains: Option<LitStr>,
+        regex: Option<LitStr>,
+    },
+    /// `#[xfail]` or `#[xfail(reason = "...")]`
+    XFail { reason: Option<LitStr> },
+}
+
+impl FailAttr {
+    fn parse(attr: &Attribute) -> Result<Self> {
+        if attr.path().is_ident("should_panic") {
+            return Self::parse_should_panic(attr);
+        }
+        let (key, should_fail) = if attr.path().is_ident("should_fail") {
+            ("expected", true)
+        } else {
+            ("reason", false)
+        };
+        let mut value = None;
+        if !matches!(attr.meta, Meta::Path(_)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(key) {
+                    set_once(&mut value, &meta)
+                } else {
+                    Err(meta.error(format!("expected `{} = \"...\"`", key)))
+                }
+            })?;
+        }
+        Ok(if should_fail {
+            FailAttr::ShouldFail { expected: value }
+        } else {
+            FailAttr::XFail { reason: value }
+        })
+    }
+
+    fn parse_should_panic(attr: &Attribute) -> Result<Self> {
+        let mut contains = None;
+        let mut regex = None;
+        if let Meta::NameValue(name_value) = &attr.meta {
+            // libtest's `#[should_panic = "..."]`.
+            contains = match &name_value.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(text),
+                    ..
+                }) => Some(text.clone()),
+                value => return Err(Error::new(value.span(), "expected a string")),
+            };
+        } else if !matches!(attr.meta, Meta::Path(_)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("contains") || meta.path.is_ident("expected") {
+                    set_once(&mut contains, &meta)
+                } else if meta.path.is_ident("regex") {
+                    set_once(&mut regex, &meta)
+                } else {
+                    Err(meta.error("expected `contains = \"...\"` or `regex = \"...\"`"))
+                }
+            })?;
+        }
+        Ok(FailAttr::ShouldPanic { contains, regex })
+    }
+}
+
+/// A `#[limits(memory = "...", cpu_time = "...")]` attribute, with the
+/// limits as written.
+pub struct LimitsAttr {
+    pub memory: Option<LitStr>,
+    pub cpu_time: Option<LitStr>,
+    pub span: Span,
+}
+
+impl LimitsAttr {
+    fn parse(attr: &Attribute) -> Result<Self> {
+        let mut limits = LimitsAttr {
+            memory: None,
+            cpu_time: None,
+            span: attr.span(),
+        };
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("memory") {
+                set_once(&mut limits.memory, &meta)
+            } else if meta.path.is_ident("cpu_time") {
+                set_once(&mut limits.cpu_time, &meta)
+            } else {
+                Err(meta.error("expected `memory = \"...\"` or `cpu_time = \"...\"`"))
+            }
+        })?;
+        if limits.memory.is_none() && limits.cpu_time.is_none() {
+            return Err(Error::new(
+                attr.span(),
+                "expected `#[limits(memory = \"512MB\", cpu_time = \"10s\")]`",
+            ));
+        }
+        Ok(limits)
+    }
+}
+
+/// One `"KEY" = value` pair of a `#[with_env(...)]` attribute.
+pub struct EnvVar {
+    pub key: LitStr,
+    pub value: Expr,
+}
+
+impl syn::parse::Parse for EnvVar {
+    fn parse(input: syn::parse::ParseStream<'_>) -> Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![=]>()?;
+        Ok(EnvVar {
+            key,
+            value: input.parse()?,
+        })
+    }
+}
+
+/// A `#[with_locale("de_DE.UTF-8", tz = "Europe/Berlin")]` attribute, with
+/// its locale, its time zone, or both.
+pub struct LocaleAttr {
+    pub locale: Option<LitStr>,
+    pub tz: Option<LitStr>,
+}
+
+impl LocaleAttr {
+    fn parse(attr: &Attribute) -> Result<Self> {
+        let expected = "expected `#[with_locale(\"de_DE.UTF-8\", tz = \"Europe/Berlin\")]`";
+        let parser = |input: syn::parse::ParseStream<'_>| {
+            let mut locale = LocaleAttr {
+                locale: None,
+                tz: None,
+            };
+            if input.peek(LitStr) {
+                locale.locale = Some(input.parse()?);
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            if !input.is_empty() {
+                let key: Ident = input.parse()?;
+                if key != "tz" {
+                    return Err(Error::new(key.span(), expected));
+                }
+                input.parse::<Token![=]>()?;
+                locale.tz = Some(input.parse()?);
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            if !input.is_empty() {
+                return Err(input.error(expected));
+            }
+            Ok(locale)
+        };
+        let locale = attr.parse_args_with(parser)?;
+        if locale.locale.is_none() && locale.tz.is_none() {
+            return Err(Error::new(attr.span(), expected));
+        }
+        for value in locale.locale.iter().chain(&locale.tz) {
+            if value.value().is_empty() {
+                return Err(Error::new(
+                    value.span(),
+                    "locales and time zones cannot be empty",
+                ));
+            }
+        }
+        Ok(locale)
+    }
+
+    /// The variables setting the locale and the time zone: `LC_ALL`, which
+    /// takes precedence over the other `LC_*` variables, `LANG`, and `TZ`.
+    fn env_vars(self) -> Vec<EnvVar> {
+        let var = |key: &str, value: &LitStr| EnvVar {
+            key: LitStr::new(key, value.span()),
+            value: Expr::Lit(ExprLit {
+                attrs: Vec::new(),
+                lit: Lit::Str(value.clone()),
+            }),
+        };
+        let mut vars = Vec::new();
+        if let Some(locale) = &self.locale {
+            vars.push(var("LC_ALL", locale));
+            vars.push(var("LANG", locale));
+        }
+        if let Some(tz) = &self.tz {
+            vars.push(var("TZ", tz));
+        }
+        vars
+    }
+}
+
+/// The raw, unvalidated input of a `#[tust::test]` invocation.
+pub struct ParsedTest {
+    pub args: TestArgs,
+    /// The annotated function, with the attributes consumed by tust removed.
+    pub func: ItemFn,
+    pub cases: Vec<CaseAttr>,
+    /// The lists of every `#[values(...)]` attribute, in the order written.
+    pub values: Vec<ValueList>,
+    pub excludes: Vec<ExcludeAttr>,
+    /// Span of a `#[serial]` helper attribute.
+    pub serial: Option<Span>,
+    /// Milliseconds given to a `#[timeout(...)]` helper attribute.
+    pub timeout: Option<LitInt>,
+    /// Count given to a `#[retry(...)]` helper attribute.
+    pub retry: Option<LitInt>,
+    pub skip: Option<SkipAttr>,
+    pub fail: Option<FailAttr>,
+    /// Variables set by `#[with_env(...)]` attributes, in the order written,
+    /// after those a `#[with_locale(...)]` attribute sets.
+    pub envs: Vec<EnvVar>,
+    /// Span of an `#[allow_leaks]` helper attribute.
+    pub allow_leaks: Option<Span>,
+    /// Tests named by `#[depends_on(...)]` attributes, in the order written.
+    pub depends_on: Vec<LitStr>,
+    pub limits: Option<LimitsAttr>,
+    /// Priority named by a `#[priority(...)]` helper attribute.
+    pub priority: Option<Ident>,
+}
+
+/// Parses the attribute arguments and the function the attribute is applied to.
+pub fn parse_test(attr: TokenStream2, item: TokenStream2) -> Result<ParsedTest> {
+    let mut args = TestArgs::default();
+    syn::meta::parser(|meta| args.parse_meta(meta)).parse2(attr)?;
+    let mut func: ItemFn = syn::parse2(item)?;
+
+    let mut cases = Vec::new();
+    let mut values = Vec::new();
+    let mut excludes = Vec::new();
+    let mut serial = None;
+    let mut timeout = None;
+    let mut retry = None;
+    let mut skip = None;
+    let mut fail = None;
+    let mut envs = Vec::new();
+    let mut locale = None;
+    let mut allow_leaks = None;
+    let mut depends_on = Vec::new();
+    let mut limits = None;
+    let mut priority = None;
+    let mut attrs = Vec::with_capacity(func.attrs.len());
+    for attr in func.attrs {
+        if CaseAttr::is_case(&attr) {
+            cases.push(CaseAttr::parse(&attr)?);
+        } else if attr.path().is_ident("values") {
+            let lists =
+                attr.parse_args_with(Punctuated::<ValueList, Token![,]>::parse_terminated)?;
+            if lists.is_empty() {
+                return Err(Error::new(
+                    attr.span(),
+                    "expected `#[values(name = [value, ...], ...)]`",
+                ));
+            }
+            values.extend(lists);
+        } else if attr.path().is_ident("exclude") {
+            excludes.push(ExcludeAttr::parse(&attr)?);
+        } else if attr.path().is_ident("serial") {
+            attr.meta.require_path_only()?;
+            serial = Some(attr.span());
+        } else if attr.path().is_ident("timeout") {
+            if timeout.is_some() {
+                return Err(Error::new(attr.span(), "duplicate `#[timeout]` attribute"));
+            }
+            timeout = Some(attr.parse_args::<LitInt>()?);
+        } else if attr.path().is_ident("retry") {
+            if retry.is_some() {
+                return Err(Error::new(attr.span(), "duplicate `#[retry]` attribute"));
+            }
+            retry = Some(attr.parse_args::<LitInt>()?);
+        } else if attr.path().is_ident("skip") || attr.path().is_ident("skip_if") {
+            if skip.is_some() {
+                return Err(Error::new(
+                    attr.span(),
+                    "a test takes at most one `#[skip]` or `#[skip_if]` attribute",
+                ));
+            }
+            skip = Some(SkipAttr::parse(&attr)?);
+        } else if ["should_fail", "should_panic", "xfail"]
+            .iter()
+            .any(|name| attr.path().is_ident(name))
+        {
+            if fail.is_some() {
+                return Err(Error::new(
+                    attr.span(),
+                    "a test takes at most one `#[should_fail]`, `#[should_panic]`, or `#[xfail]` \
+                     attribute",
+                ));
+            }
+            fail = Some(FailAttr::parse(&attr)?);
+        } else if attr.path().is_ident("with_env") {
+            let vars = attr.parse_args_with(Punctuated::<EnvVar, Token![,]>::parse_terminated)?;
+            if vars.is_empty() {
+                return Err(Error::new(
+                    attr.span(),
+                    "expected `#[with_env(\"KEY\" = value, ...)]`",
+                ));
+            }
+            envs.extend(vars);
+        } else if attr.path().is_ident("with_locale") {
+            if locale.is_some() {
+                return Err(Error::new(
+                    attr.span(),
+                    "duplicate `#[with_locale]` attribute",
+                ));
+            }
+            locale = Some(LocaleAttr::parse(&attr)?);
+        } else if attr.path().is_ident("allow_leaks") {
+            attr.meta.require_path_only()?;
+            allow_leaks = Some(attr.span());
+        } else if attr.path().is_ident("depends_on") {
+            let tests = attr.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated)?;
+            if tests.is_empty() {
+                return Err(Error::new(
+                    attr.span(),
+                    "expected `#[depends_on(\"test\", ...)]`",
+                ));
+            }
+            depends_on.extend(tests);
+        } else if attr.path().is_ident("limits") {
+            if limits.is_some() {
+                return Err(Error::new(attr.span(), "duplicate `#[limits]` attribute"));
+            }
+            limits = Some(LimitsAttr::parse(&attr)?);
+        } else if attr.path().is_ident("priority") {
+            if priority.is_some() {
+                return Err(Error::new(attr.span(), "duplicate `#[priority]` attribute"));
+            }
+            priority = Some(attr.parse_args::<Ident>()?);
+        } else {
+            attrs.push(attr);
+        }
+    }
+    func.attrs = attrs;
+    if let Some(locale) = locale {
+        envs.splice(0..0, locale.env_vars());
+    }
+
+    Ok(ParsedTest {
+        args,
+        func,
+        cases,
+        values,
+        excludes,
+        serial,
+        timeout,
+        retry,
+        skip,
+        fail,
+        envs,
+        allow_leaks,
+        depends_on,
+        limits,
+        priority,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+
+    #[test]
+    fn keeps_attributes_tust_does_not_consume() {
+        let parsed = parse_test(
+            quote!(),
+            quote! {
+                /// Adds.
+                #[inline]
+                #[serial]
+                fn adds() {}
+            },
+        )
+        .unwrap();
+        assert_eq!(parsed.func.sig.ident, "adds");
+        assert_eq!(parsed.func.attrs.len(), 2);
+        assert!(parsed.serial.is_some());
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        let err = parse_test(
+            quote!(frobnicate),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "unsupported `#[tust::test]` argument");
+    }
+
+    #[test]
+    fn rejects_repeated_arguments() {
+        let err = parse_test(
+            quote!(serial, serial),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "duplicate argument");
+        let err = parse_test(
+            quote!(serial = 1),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "this argument does not take a value");
+    }
+
+    #[test]
+    fn rejects_items_that_are_not_functions() {
+        assert!(parse_test(
+            quote!(),
+            quote!(
+                struct Adds;
+            )
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parses_cases() {
+        let parsed = parse_test(
+            quote!(),
+            quote! {
+                #[case(1, 2)]
+                #[case::large(1000, 2000)]
+                fn adds(a: u32, b: u32) {}
+            },
+        )
+        .unwrap();
+        assert_eq!(parsed.cases.len(), 2);
+        assert!(parsed.cases[0].name.is_none());
+        assert_eq!(parsed.cases[0].args.len(), 2);
+        assert_eq!(parsed.cases[1].name.as_ref().unwrap(), "large");
+        assert!(parsed.func.attrs.is_empty());
+    }
+
+    #[test]
+    fn rejects_case_paths_with_more_segments() {
+        let err = parse_test(
+            quote!(),
+            quote!(
+                #[case::a::b(1)]
+                fn adds(a: u32) {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "expected `#[case(...)]` or `#[case::name(...)]`"
+        );
+    }
+
+    #[test]
+    fn parses_tags() {
+        let parsed = parse_test(
+            quote!(tags("db", "slow")),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .unwrap();
+        let tags: Vec<_> = parsed
+            .args
+            .tags
+            .unwrap()
+            .iter()
+            .map(LitStr::value)
+            .collect();
+        assert_eq!(tags, ["db", "slow"]);
+        let err = parse_test(
+            quote!(tags("a"), tags("b")),
+            quote!(
+                fn adds() {}
+            ),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "duplicate argument");
+    }
+
+    fn parse_error(item: TokenStream2) -> String {
+        parse_test(quote!(), item).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_skips() {
+        let skip = |item| parse_test(quote!(), item).unwrap().skip.unwrap();
+        let always = skip(quote!(
+            #[skip]
+            fn t() {}
+        ));
+        assert!(always.condition.is_none() && always.reason.is_none());
+        let always = skip(quote!(
+            #[skip(reason = "flaky")]
+            fn t() {}
+        ));
+        assert_eq!(always.reason.unwrap().value(), "flaky");
+        let cfg = skip(quote!(
+            #[skip_if(cfg(windows))]
+            fn t() {}
+        ));
+        assert!(matches!(cfg.condition, Some(SkipCondition::Cfg(predicate))
+            if predicate.to_string() == "windows"));
+        let env = skip(quote!(
+            #[skip_if(env = "CI", reason = "slow")]
+            fn t() {}
+        ));
+        assert!(matches!(env.condition, Some(SkipCondition::Env(name)) if name.value() == "CI"));
+        assert_eq!(env.reason.unwrap().value(), "slow");
+    }
+
+    #[test]
+    fn rejects_bad_skips() {
+        assert_eq!(
+            parse_error(quote!(
+                #[skip_if(reason = "slow")]
+                fn t() {}
+            )),
+            "expected a condition: `#[skip_if(cfg(...))]` or `#[skip_if(env = \"NAME\")]`"
+        );
+        assert_eq!(
+            parse_error(quote!(
+                #[skip_if(cfg(unix), env = "CI")]
+                fn t() {}
+            )),
+            "`#[skip_if]` takes a single condition"
+        );
+        assert_eq!(
+            parse_error(quote!(
+                #[skip_if(os = "linux")]
+                fn t() {}
+            )),
+            "expected `cfg(...)`, `env = \"NAME\"`, or `reason = \"...\"`"
+        );
+        assert_eq!(
+            parse_error(quote!(
+                #[skip(because = "slow")]
+                fn t() {}
+            )),
+            "expected `reason = \"...\"`"
+        );
+        assert_eq!(
+            parse_error(quote!(
+                #[skip]
+                #[skip_if(cfg(unix))]
+                fn t() {}
+            )),
+            "a test takes at most one `#[skip]` or `#[skip_if]` attribute"
+        );
+    }
+
+    #[test]
+    fn parses_expected_failures() {
+        let fail = |item| parse_test(quote!(), item).unwrap().fail.unwrap();
+        assert!(matches!(
+            fail(quote!(
+                #[should_fail]
+                fn t() {}
+            )),
+            FailAttr::ShouldFail { expected: None }
+        ));
+        assert!(matches!(
+            fail(quote!(#[should_fail(expected = "boom")] fn t() {})),
+            FailAttr::ShouldFail { expected: Some(text) } if text.value() == "boom"
+        ));
+        assert!(matches!(
+            fail(quote!(#[xfail(reason = "#12")] fn t() {})),
+            FailAttr::XFail { reason: Some(text) } if text.value() == "#12"
+        ));
+        assert_eq!(
+            parse_error(quote!(
+                #[xfail(expected = "boom")]
+                fn t() {}
+            )),
+            "expected `reason = \"...\"`"
+        );
+        assert_eq!(
+            parse_error(quote!(
+                #[should_fail]
+                #[xfail]
+                fn t() {}
+            )),
+            "a test takes at most one `#[should_fail]`, `#[should_panic]`, or `#[xfail]` \
+             attribute"
+        );
+    }
+
+    #[test]
+    fn parses_values_and_excludes() {
+        let parsed = parse_test(
+            quote!(),
+            quote! {
+                #[values(a = [1, 2], b = ["x"])]
+                #[values(c = [])]
+                #[exclude(a = 1, b = "x")]
+                fn t(a: u8, b: &str, c: u8) {}
+            },
+        )
+        .unwrap();
+        let lists: Vec<_> = parsed
+            .values
+            .iter()
+            .map(|list| (list.ident.to_string(), list.values.len()))
+            .collect();
+        assert_eq!(lists, [("a".into(), 2), ("b".into(), 1), ("c".into(), 0)]);
+        let excluded: Vec<_> = parsed.excludes[0]
+            .values
+            .iter()
+            .map(|NamedValue { ident, .. }| ident.to_string())
+            .collect();
+        assert_eq!(excluded, ["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_bad_values_and_excludes() {
+        assert_eq!(
+            parse_error(quote! {
+                #[values(a = 1..3)]
+                fn t(a: u8) {}
+            }),
+            "expected a list of values, such as `[1, 2, 3]`"
+        );
+        assert_eq!(
+            parse_error(quote! {
+                #[values()]
+                fn t(a: u8) {}
+            }),
+            "expected `#[values(name = [value, ...], ...)]`"
+        );
+        assert_eq!(
+            parse_error(quote! {
+                #[exclude()]
+                fn t(a: u8) {}
+            }),
+            "expected `#[exclude(name = value, ...)]`"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Stage 2: check that the item is a struct with named fields whose names do
+//! not clash with the builder's own methods.
+
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, Error, Expr, Fields, Generics, Ident, Result, Type, Visibility};
+
+use super::parse::ParsedTestBuilder;
+
+/// Methods of the builder that a field's setter must not be named.
+const RESERVED: &[&str] = &["new", "build"];
+
+/// A validated struct to generate a builder for.
+pub struct TestBuilderModel {
+    /// Attributes of the struct, for the `#[cfg]`s the builder must share.
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub generics: Generics,
+    pub fields: Vec<FieldModel>,
+}
+
+pub struct FieldModel {
+    pub ident: Ident,
+    pub ty: Type,
+    /// What the field is set to unless the test sets it, or `None` for a
+    /// generated value.
+    pub default: Option<Expr>,
+}
+
+pub fn analyze_test_builder(parsed: ParsedTestBuilder) -> Result<TestBuilderModel> {
+    let ParsedTestBuilder { input, defaults } = parsed;
+
+    let fields = match input.data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(fields) => fields.named,
+            Fields::Unnamed(fields) => {
+                return Err(Error::new(
+                    fields.span(),
+                    "`#[derive(TestBuilder)]` requires named fields, to name its setters after",
+                ))
+            }
+            Fields::Unit => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "`#[derive(TestBuilder)]` requires a struct with fields",
+                ))
+            }
+        },
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "`#[derive(TestBuilder)]` supports only structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "`#[derive(TestBuilder)]` supports only structs",
+            ))
+        }
+    };
+    let fields = fields
+        .into_iter()
+        .zip(defaults)
+        .map(|(field, default)| {
+            let ident = field.ident.expect("named fields have names");
+            let name = ident.to_string();
+            if RESERVED.contains(&name.trim_start_matches("r#")) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "`#[derive(TestBuilder)]` cannot generate a setter for `{}`, which \
+                         would clash with the builder's `{}` method",
+                        name, name
+                    ),
+                ));
+            }
+            Ok(FieldModel {
+                ident,
+                ty: field.ty,
+                default,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let attrs = input
+        .attrs
+        .into_iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .collect();
+
+    Ok(TestBuilderModel {
+        attrs,
+        vis: input.vis,
+        ident: input.ident,
+        generics: input.generics,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream as TokenStream2;
+    use quote::quote;
+
+    use super::*;
+    use crate::test_builder::parse::parse_test_builder;
+
+    fn analyze(input: TokenStream2) -> Result<TestBuilderModel> {
+        analyze_test_builder(parse_test_builder(input)?)
+    }
+
+    fn error(input: TokenStream2) -> String {
+        analyze(input).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn keeps_the_fields_and_the_cfgs() {
+        let model = analyze(quote! {
+            #[cfg(test)]
+            #[derive(Debug)]
+            pub struct User {
+                #[test_builder(default = 18)]
+                age: u8,
+                name: String,
+            }
+        })
+        .unwrap();
+        assert_eq!(model.attrs.len(), 1);
+        assert!(model.attrs[0].path().is_ident("cfg"));
+        let fields: Vec<_> = model
+            .fields
+            .iter()
+            .map(|field| (field.ident.to_string(), field.default.is_some()))
+            .collect();
+        assert_eq!(fields, [("age".into(), true), ("name".into(), false)]);
+    }
+
+    #[test]
+    fn requires_a_struct_with_named_fields() {
+        assert_eq!(
+            error(quote!(
+                struct Pair(u8, u8);
+            )),
+            "`#[derive(TestBuilder)]` requires named fields, to name its setters after"
+        );
+        assert_eq!(
+            error(quote!(
+                struct Unit;
+            )),
+            "`#[derive(TestBuilder)]` requires a struct with fields"
+        );
+        assert_eq!(
+            error(quote!(
+                enum Kind {
+                    A,
+                }
+            )),
+            "`#[derive(TestBuilder)]` supports only structs"
+        );
+        assert_eq!(
+            error(quote!(
+                union Bits {
+                    a: u8,
+                }
+            )),
+            "`#[derive(TestBuilder)]` supports only structs"
+        );
+    }
+
+    #[test]
+    fn rejects_fields_named_as_builder_methods() {
+        assert_eq!(
+            error(quote!(
+                struct Job {
+                    build: u32,
+                }
+            )),
+            "`#[derive(TestBuilder)]` cannot generate a setter for `build`, which would clash \
+             with the builder's `build` method"
+        );
+        assert!(analyze(quote!(
+            struct Job {
+                builds: u32,
+            }
+        ))
+        .is_ok());
+    }
+}
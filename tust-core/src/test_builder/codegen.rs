@@ -0,0 +1,178 @@
+//! Stage 4: emit the builder struct and its methods.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use super::lower::{FieldIR, TestBuilderIR};
+
+pub fn codegen_test_builder(ir: TestBuilderIR) -> TokenStream2 {
+    let TestBuilderIR {
+        attrs,
+        vis,
+        ident,
+        builder,
+        generics,
+        fields,
+    } = ir;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let struct_doc = format!(
+        " Builds a [`{}`] for a test, giving the fields the test does not set \
+         values drawn from the test's seeded random number generator.",
+        ident
+    );
+    let build_doc = format!(
+        " Builds the [`{}`], generating the fields that were not set.",
+        ident
+    );
+
+    let names: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let tys: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let setters = fields.iter().map(|FieldIR { ident, ty, .. }| {
+        let doc = format!(" Sets `{}`.", ident);
+        quote! {
+            #[doc = #doc]
+            #vis fn #ident(mut self, value: #ty) -> Self {
+                self.#ident = ::core::option::Option::Some(value);
+                self
+            }
+        }
+    });
+    let values = fields.iter().map(
+        |FieldIR {
+             ident, fallback, ..
+         }| {
+            quote! {
+                #ident: match self.#ident {
+                    ::core::option::Option::Some(value) => value,
+                    ::core::option::Option::None => #fallback,
+                }
+            }
+        },
+    );
+    let bounds = fields
+        .iter()
+        .filter(|field| field.bounded)
+        .map(|FieldIR { ty, .. }| quote!(#ty: ::tust::property::Arbitrary));
+    // Fields given a default may leave the generator unused.
+    let gen = quote! {
+        #[allow(unused_mut, unused_variables)]
+        let mut __tust_gen = ::tust::__private::builder_gen();
+    };
+
+    quote! {
+        #(#attrs)*
+        #[doc = #struct_doc]
+        #vis struct #builder #generics #where_clause {
+            #(#names: ::core::option::Option<#tys>,)*
+        }
+
+        #(#attrs)*
+        impl #impl_generics #builder #ty_generics #where_clause {
+            /// A builder with no field set.
+            #vis fn new() -> Self {
+                #builder {
+                    #(#names: ::core::option::Option::None,)*
+                }
+            }
+
+            #(#setters)*
+
+            #[doc = #build_doc]
+            #vis fn build(self) -> #ident #ty_generics
+            where
+                #(#bounds,)*
+            {
+                #gen
+                #ident {
+                    #(#values,)*
+                }
+            }
+        }
+
+        #(#attrs)*
+        impl #impl_generics ::core::default::Default for #builder #ty_generics #where_clause {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::{File, Item};
+
+    use super::*;
+    use crate::test_builder::lower::tests::lower;
+
+    fn expand(input: TokenStream2) -> Vec<Item> {
+        syn::parse2::<File>(codegen_test_builder(lower(input)))
+            .unwrap()
+            .items
+    }
+
+    #[test]
+    fn emits_the_builder_and_its_methods() {
+        let items = expand(quote! {
+            #[cfg(test)]
+            pub struct User {
+                name: String,
+            }
+        });
+        assert_eq!(items.len(), 3);
+        let Item::Struct(builder) = &items[0] else {
+            panic!("expected the builder struct");
+        };
+        assert_eq!(builder.ident, "UserBuilder");
+        assert!(builder.attrs[0].path().is_ident("cfg"));
+        let field = quote!(#builder).to_string();
+        assert!(
+            field.contains("name : :: core :: option :: Option < String >"),
+            "{}",
+            field
+        );
+        let Item::Impl(methods) = &items[1] else {
+            panic!("expected the builder's methods");
+        };
+        let methods = quote!(#methods).to_string();
+        assert!(methods.contains("pub fn new () -> Self"), "{}", methods);
+        assert!(
+            methods.contains("pub fn name (mut self , value : String) -> Self"),
+            "{}",
+            methods
+        );
+        assert!(
+            methods.contains("pub fn build (self) -> User"),
+            "{}",
+            methods
+        );
+        let Item::Impl(default) = &items[2] else {
+            panic!("expected the `Default` implementation");
+        };
+        let default = default.trait_.as_ref().unwrap().1.clone();
+        assert_eq!(
+            quote!(#default).to_string(),
+            ":: core :: default :: Default"
+        );
+    }
+
+    #[test]
+    fn requires_arbitrary_type_parameters_only_to_build() {
+        let items = expand(quote! {
+            struct Wrapper<T> {
+                value: T,
+            }
+        });
+        let Item::Impl(methods) = &items[1] else {
+            panic!("expected the builder's methods");
+        };
+        let methods = quote!(#methods).to_string();
+        assert!(
+            methods.contains(
+                "fn build (self) -> Wrapper < T > where T : :: tust :: property :: Arbitrary ,"
+            ),
+            "{}",
+            methods
+        );
+    }
+}
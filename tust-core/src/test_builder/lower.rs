@@ -0,0 +1,138 @@
+//! Stage 3: lower the struct model into the builder to generate.
+
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{Attribute, GenericParam, Generics, Ident, Type, Visibility};
+
+use super::analyze::{FieldModel, TestBuilderModel};
+
+pub struct TestBuilderIR {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    /// The struct built.
+    pub ident: Ident,
+    /// The struct's name followed by `Builder`.
+    pub builder: Ident,
+    pub generics: Generics,
+    pub fields: Vec<FieldIR>,
+}
+
+pub struct FieldIR {
+    pub ident: Ident,
+    pub ty: Type,
+    /// The field's value when the test did not set it, drawing from the
+    /// generator named `__tust_gen`.
+    pub fallback: TokenStream2,
+    /// Whether the builder's `build` must require `ty: Arbitrary`, because
+    /// `ty` mentions a type parameter of the struct.
+    pub bounded: bool,
+}
+
+pub fn lower_test_builder(model: TestBuilderModel) -> TestBuilderIR {
+    let TestBuilderModel {
+        attrs,
+        vis,
+        ident,
+        generics,
+        fields,
+    } = model;
+    let builder = format_ident!("{}Builder", ident);
+    let type_params: Vec<String> = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(param.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+    let fields = fields
+        .into_iter()
+        .map(|field| {
+            let FieldModel { ident, ty, default } = field;
+            let (fallback, bounded) = match default {
+                Some(default) => (default.into_token_stream(), false),
+                // Spanned so that a type without an `Arbitrary`
+                // implementation is reported at the field.
+                None => (
+                    quote_spanned! {ty.span()=>
+                        <#ty as ::tust::property::Arbitrary>::arbitrary(&mut __tust_gen)
+                    },
+                    mentions(ty.to_token_stream(), &type_params),
+                ),
+            };
+            FieldIR {
+                ident,
+                ty,
+                fallback,
+                bounded,
+            }
+        })
+        .collect();
+
+    TestBuilderIR {
+        attrs,
+        vis,
+        ident,
+        builder,
+        generics,
+        fields,
+    }
+}
+
+/// Whether `tokens` name one of `type_params`.
+fn mentions(tokens: TokenStream2, type_params: &[String]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => type_params.iter().any(|param| ident == param),
+        TokenTree::Group(group) => mentions(group.stream(), type_params),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::test_builder::analyze::analyze_test_builder;
+    use crate::test_builder::parse::parse_test_builder;
+
+    pub(crate) fn lower(input: TokenStream2) -> TestBuilderIR {
+        lower_test_builder(analyze_test_builder(parse_test_builder(input).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn generates_the_fields_without_defaults() {
+        let ir = lower(quote! {
+            struct User {
+                #[test_builder(default = 18)]
+                age: u8,
+                name: String,
+            }
+        });
+        assert_eq!(ir.builder, "UserBuilder");
+        assert_eq!(ir.fields[0].fallback.to_string(), "18");
+        assert_eq!(
+            ir.fields[1].fallback.to_string(),
+            quote!(<String as ::tust::property::Arbitrary>::arbitrary(
+                &mut __tust_gen
+            ))
+            .to_string()
+        );
+        assert!(ir.fields.iter().all(|field| !field.bounded));
+    }
+
+    #[test]
+    fn bounds_the_fields_mentioning_type_parameters() {
+        let ir = lower(quote! {
+            struct Pair<'a, T, const N: usize> {
+                items: Vec<(T, u8)>,
+                other: [u8; N],
+                #[test_builder(default = ::std::marker::PhantomData)]
+                marker: ::std::marker::PhantomData<&'a T>,
+            }
+        });
+        let bounded: Vec<_> = ir.fields.iter().map(|field| field.bounded).collect();
+        assert_eq!(bounded, [true, false, false]);
+    }
+}
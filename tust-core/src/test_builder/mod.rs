@@ -0,0 +1,6 @@
+//! Pipeline for `#[derive(TestBuilder)]`.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
@@ -0,0 +1,116 @@
+//! Stage 1: parse the struct and the `#[test_builder(...)]` attributes of
+//! its fields.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Error, Expr, Field, Fields, Result};
+
+/// The struct `#[derive(TestBuilder)]` is applied to, with the defaults its
+/// fields were given.
+pub struct ParsedTestBuilder {
+    pub input: DeriveInput,
+    /// The `default = ...` of each field, in order, if it has one.
+    pub defaults: Vec<Option<Expr>>,
+}
+
+/// Parses the item `#[derive(TestBuilder)]` is applied to.
+pub fn parse_test_builder(input: TokenStream2) -> Result<ParsedTestBuilder> {
+    let input: DeriveInput = syn::parse2(input)?;
+    let defaults = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(parse_default)
+                .collect::<Result<_>>()?,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    Ok(ParsedTestBuilder { input, defaults })
+}
+
+/// Parses `#[test_builder(default = expr)]` on `field`.
+fn parse_default(field: &Field) -> Result<Option<Expr>> {
+    let mut default = None;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("test_builder") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("default") {
+                return Err(meta.error("expected `default = ...`"));
+            }
+            if default.is_some() {
+                return Err(meta.error("duplicate `default`"));
+            }
+            default = Some(meta.value()?.parse()?);
+            Ok(())
+        })?;
+        if default.is_none() {
+            return Err(Error::new_spanned(attr, "expected `default = ...`"));
+        }
+    }
+    Ok(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_defaults_of_fields() {
+        let parsed = parse_test_builder(quote! {
+            struct User {
+                #[test_builder(default = 18)]
+                age: u8,
+                name: String,
+            }
+        })
+        .unwrap();
+        let defaults: Vec<_> = parsed
+            .defaults
+            .iter()
+            .map(|default| {
+                default
+                    .as_ref()
+                    .map(|expr| expr.to_token_stream().to_string())
+            })
+            .collect();
+        assert_eq!(defaults, [Some("18".to_string()), None]);
+    }
+
+    #[test]
+    fn rejects_bad_defaults() {
+        let error = |input| parse_test_builder(input).err().unwrap().to_string();
+        assert_eq!(
+            error(quote! {
+                struct User {
+                    #[test_builder(value = 1)]
+                    age: u8,
+                }
+            }),
+            "expected `default = ...`"
+        );
+        assert_eq!(
+            error(quote! {
+                struct User {
+                    #[test_builder()]
+                    age: u8,
+                }
+            }),
+            "expected `default = ...`"
+        );
+        assert_eq!(
+            error(quote! {
+                struct User {
+                    #[test_builder(default = 1, default = 2)]
+                    age: u8,
+                }
+            }),
+            "duplicate `default`"
+        );
+    }
+}
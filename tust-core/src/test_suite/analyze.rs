@@ -0,0 +1,198 @@
+//! Stage 2: check the suite's type parameter, and find the parameter of each
+//! test that receives the value under test.
+
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
+use syn::{
+    Attribute, Block, Error, FnArg, GenericParam, Generics, Ident, ItemFn, Pat, Result, ReturnType,
+    Token, Type, Visibility,
+};
+
+use super::parse::ParsedTestSuite;
+
+/// A validated test suite.
+pub struct TestSuiteModel {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub generics: Generics,
+    pub tests: Vec<SuiteTest>,
+}
+
+/// One test of a suite.
+pub struct SuiteTest {
+    /// Attributes given to the generated test, such as `#[timeout(100)]`.
+    pub attrs: Vec<Attribute>,
+    pub asyncness: Option<Token![async]>,
+    pub ident: Ident,
+    /// The parameters as written, for the generic function holding the body.
+    pub inputs: Punctuated<FnArg, Comma>,
+    /// What each parameter is passed, in order.
+    pub args: Vec<Arg>,
+    pub output: ReturnType,
+    pub body: Block,
+}
+
+/// What a parameter of a suite test is passed.
+pub enum Arg {
+    /// The value under test, built for the test by the suite's caller.
+    Subject,
+    /// A parameter of the generated test, such as a fixture, passed on.
+    Param { ident: Ident, ty: Box<Type> },
+}
+
+pub fn analyze_test_suite(parsed: ParsedTestSuite) -> Result<TestSuiteModel> {
+    let ParsedTestSuite {
+        attrs,
+        vis,
+        ident,
+        generics,
+        tests,
+    } = parsed;
+    let mut params = generics.params.iter();
+    let type_param = match (params.next(), params.next()) {
+        (Some(GenericParam::Type(param)), None) => param.ident.clone(),
+        _ => {
+            return Err(Error::new(
+                generics.span(),
+                "a test suite takes one type parameter, as in `storage_contract<S: Storage>`",
+            ))
+        }
+    };
+    let tests = tests
+        .into_iter()
+        .map(|test| analyze_test(test, &type_param))
+        .collect::<Result<_>>()?;
+    Ok(TestSuiteModel {
+        attrs,
+        vis,
+        ident,
+        generics,
+        tests,
+    })
+}
+
+fn analyze_test(test: ItemFn, type_param: &Ident) -> Result<SuiteTest> {
+    let ItemFn {
+        attrs, sig, block, ..
+    } = test;
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "the tests of a suite cannot be generic themselves; they are generic over the \
+             suite's type parameter",
+        ));
+    }
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        let FnArg::Typed(param) = input else {
+            return Err(Error::new(
+                input.span(),
+                "the tests of a suite cannot take `self`",
+            ));
+        };
+        let is_subject = matches!(
+            &*param.ty,
+            Type::Path(path) if path.qself.is_none() && path.path.is_ident(type_param)
+        );
+        if is_subject {
+            args.push(Arg::Subject);
+            continue;
+        }
+        let Pat::Ident(pat) = &*param.pat else {
+            return Err(Error::new(
+                param.pat.span(),
+                "the parameters of a suite's tests must be names, as those of tests are",
+            ));
+        };
+        args.push(Arg::Param {
+            ident: pat.ident.clone(),
+            ty: param.ty.clone(),
+        });
+    }
+    let subjects = args
+        .iter()
+        .filter(|arg| matches!(arg, Arg::Subject))
+        .count();
+    if subjects != 1 {
+        return Err(Error::new(
+            sig.ident.span(),
+            format!(
+                "the tests of a suite take the value under test as one parameter of type `{}`",
+                type_param
+            ),
+        ));
+    }
+
+    Ok(SuiteTest {
+        attrs,
+        asyncness: sig.asyncness,
+        ident: sig.ident,
+        inputs: sig.inputs,
+        args,
+        output: sig.output,
+        body: *block,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream as TokenStream2;
+    use quote::quote;
+
+    use super::*;
+    use crate::test_suite::parse::parse_test_suite;
+
+    fn analyze(input: TokenStream2) -> Result<TestSuiteModel> {
+        analyze_test_suite(parse_test_suite(input)?)
+    }
+
+    fn error(input: TokenStream2) -> String {
+        analyze(input).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn passes_the_subject_and_fixtures() {
+        let model = analyze(quote! {
+            contract<S: Storage> {
+                fn reads(db: Db, storage: S) {}
+            }
+        })
+        .unwrap();
+        let args = &model.tests[0].args;
+        assert!(matches!(&args[0], Arg::Param { ident, .. } if ident == "db"));
+        assert!(matches!(args[1], Arg::Subject));
+    }
+
+    #[test]
+    fn takes_one_type_parameter() {
+        let message = "a test suite takes one type parameter, as in `storage_contract<S: Storage>`";
+        assert_eq!(error(quote!(contract { fn reads() {} })), message);
+        assert_eq!(error(quote!(contract < S, T > {})), message);
+        assert_eq!(error(quote!(contract<'a> {})), message);
+    }
+
+    #[test]
+    fn rejects_bad_tests() {
+        assert_eq!(
+            error(quote!(contract<S> { fn reads<T>(storage: S) {} })),
+            "the tests of a suite cannot be generic themselves; they are generic over the \
+             suite's type parameter"
+        );
+        assert_eq!(
+            error(quote!(contract<S> { fn reads(self, storage: S) {} })),
+            "the tests of a suite cannot take `self`"
+        );
+        assert_eq!(
+            error(quote!(contract<S> { fn reads(storage: S, (a, b): (u8, u8)) {} })),
+            "the parameters of a suite's tests must be names, as those of tests are"
+        );
+        let message = "the tests of a suite take the value under test as one parameter of type `S`";
+        assert_eq!(error(quote!(contract<S> { fn reads(db: Db) {} })), message);
+        assert_eq!(
+            error(quote!(contract<S> { fn reads(a: S, b: S) {} })),
+            message
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Stage 4: emit the macro instantiating the suite.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::Visibility;
+
+use super::lower::TestSuiteIR;
+
+pub fn codegen_test_suite(ir: TestSuiteIR) -> TokenStream2 {
+    let TestSuiteIR {
+        attrs,
+        vis,
+        ident,
+        tests,
+    } = ir;
+    // A public suite can be instantiated from other crates, and one visible
+    // to a module or the crate by its path from there.
+    let (export, reexport) = match &vis {
+        Visibility::Public(_) => (quote!(#[macro_export]), None),
+        Visibility::Restricted(_) => (
+            quote!(#[allow(unused_macros)]),
+            Some(quote! {
+                #[allow(unused_imports)]
+                #vis use #ident;
+            }),
+        ),
+        Visibility::Inherited => (quote!(#[allow(unused_macros)]), None),
+    };
+    quote! {
+        #(#attrs)*
+        #export
+        macro_rules! #ident {
+            ($module:ident, $subject:expr $(,)?) => {
+                mod $module {
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    #(#tests)*
+                }
+            };
+        }
+        #reexport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_suite::lower::tests::lower;
+
+    fn expand(input: TokenStream2) -> String {
+        codegen_test_suite(lower(input)).to_string()
+    }
+
+    #[test]
+    fn emits_a_macro_defining_a_module_per_instance() {
+        let tokens = expand(quote! {
+            /// The contract.
+            contract<S> {
+                fn reads(storage: S) {}
+            }
+        });
+        assert!(
+            tokens.starts_with(
+                "# [doc = r\" The contract.\"] # [allow (unused_macros)] macro_rules ! contract"
+            ),
+            "{}",
+            tokens
+        );
+        assert!(
+            tokens.contains(
+                "($ module : ident , $ subject : expr $ (,) ?) => { mod $ module { \
+                 # [allow (unused_imports)] use super :: * ; # [:: tust :: test] fn reads ()"
+            ),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn exports_the_macro_as_far_as_the_suite_is_visible() {
+        let public = expand(quote!(pub contract<S> {}));
+        assert!(
+            public.starts_with("# [macro_export] macro_rules ! contract"),
+            "{}",
+            public
+        );
+        let restricted = expand(quote!(pub(crate) contract<S> {}));
+        assert!(
+            restricted.ends_with("# [allow (unused_imports)] pub (crate) use contract ;"),
+            "{}",
+            restricted
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Stage 3: lower each test of the suite into the test function its
+//! instances define, with the instance's expression still to substitute.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Attribute, Ident, Visibility};
+
+use super::analyze::{Arg, SuiteTest, TestSuiteModel};
+
+pub struct TestSuiteIR {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    /// The test functions of an instance, referring to the instance's
+    /// expression as `$subject`.
+    pub tests: Vec<TokenStream2>,
+}
+
+pub fn lower_test_suite(model: TestSuiteModel) -> TestSuiteIR {
+    let TestSuiteModel {
+        attrs,
+        vis,
+        ident,
+        generics,
+        tests,
+    } = model;
+    let (generic_params, _, where_clause) = generics.split_for_impl();
+    let tests = tests
+        .into_iter()
+        .map(|test| {
+            let SuiteTest {
+                attrs,
+                asyncness,
+                ident,
+                inputs,
+                args,
+                output,
+                body,
+            } = test;
+            let params = args.iter().filter_map(|arg| match arg {
+                Arg::Subject => None,
+                Arg::Param { ident, ty } => Some(quote!(#ident: #ty)),
+            });
+            let values = args.iter().map(|arg| match arg {
+                Arg::Subject => quote!($subject),
+                Arg::Param { ident, .. } => quote!(#ident),
+            });
+            let await_ = asyncness.map(|_| quote!(.await));
+            // The body stays generic, so it is checked against the suite's
+            // bounds rather than against each instance's type.
+            quote! {
+                #[::tust::test]
+                #(#attrs)*
+                #asyncness fn #ident(#(#params),*) #output {
+                    #asyncness fn __tust_suite_test #generic_params (#inputs) #output #where_clause #body
+                    __tust_suite_test(#(#values),*) #await_
+                }
+            }
+        })
+        .collect();
+    TestSuiteIR {
+        attrs,
+        vis,
+        ident,
+        tests,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::test_suite::analyze::analyze_test_suite;
+    use crate::test_suite::parse::parse_test_suite;
+
+    pub(crate) fn lower(input: TokenStream2) -> TestSuiteIR {
+        lower_test_suite(analyze_test_suite(parse_test_suite(input).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn calls_the_generic_body_with_the_subject() {
+        let ir = lower(quote! {
+            contract<S: Storage> where S: Clone {
+                #[timeout(100)]
+                fn reads(mut storage: S, db: Db) {}
+            }
+        });
+        assert_eq!(
+            ir.tests[0].to_string(),
+            quote! {
+                #[::tust::test]
+                #[timeout(100)]
+                fn reads(db: Db) {
+                    fn __tust_suite_test<S: Storage>(mut storage: S, db: Db) where S: Clone {}
+                    __tust_suite_test($subject, db)
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn awaits_async_tests() {
+        let ir = lower(quote! {
+            contract<S> {
+                async fn reads(storage: S) {}
+            }
+        });
+        let test = ir.tests[0].to_string();
+        assert!(test.contains("async fn reads ()"), "{}", test);
+        assert!(
+            test.contains("__tust_suite_test ($ subject) . await"),
+            "{}",
+            test
+        );
+    }
+}
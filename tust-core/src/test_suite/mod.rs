@@ -0,0 +1,13 @@
+//! Pipeline for the `tust::test_suite!` macro.
+//!
+//! A test suite is a set of tests generic over one type, usually bounded by
+//! the trait whose contract they check. It expands to a `macro_rules!`
+//! macro named after the suite; each call of that macro, given a module name
+//! and an expression building a value of a concrete type, emits a module
+//! with one `#[tust::test]` function per test of the suite, each calling the
+//! test's body, as a generic function, with a value from that expression.
+
+pub mod analyze;
+pub mod codegen;
+pub mod lower;
+pub mod parse;
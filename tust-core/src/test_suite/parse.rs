@@ -0,0 +1,84 @@
+//! Stage 1: parse the signature of a test suite and its tests.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::parse::{Parse, ParseStream};
+use syn::{braced, Attribute, Generics, Ident, ItemFn, Result, Visibility};
+
+/// `pub name<T: Bound> { fn test(value: T) { ... } ... }`
+pub struct ParsedTestSuite {
+    /// Attributes of the suite, such as its doc comment, given to the
+    /// generated macro.
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    /// The suite's type parameter, with its bounds and `where` clause.
+    pub generics: Generics,
+    pub tests: Vec<ItemFn>,
+}
+
+impl Parse for ParsedTestSuite {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+        let mut generics: Generics = input.parse()?;
+        generics.where_clause = input.parse()?;
+        let content;
+        braced!(content in input);
+        let mut tests = Vec::new();
+        while !content.is_empty() {
+            tests.push(content.parse()?);
+        }
+        Ok(ParsedTestSuite {
+            attrs,
+            vis,
+            ident,
+            generics,
+            tests,
+        })
+    }
+}
+
+/// Parses the input of `test_suite!`.
+pub fn parse_test_suite(input: TokenStream2) -> Result<ParsedTestSuite> {
+    syn::parse2(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_signature_and_the_tests() {
+        let parsed = parse_test_suite(quote! {
+            /// The contract.
+            pub(crate) contract<S> where S: Storage {
+                fn reads(storage: S) {}
+                #[timeout(100)]
+                async fn writes(storage: S) {}
+            }
+        })
+        .unwrap();
+        assert_eq!(parsed.attrs.len(), 1);
+        assert!(matches!(parsed.vis, Visibility::Restricted(_)));
+        assert_eq!(parsed.ident, "contract");
+        assert_eq!(
+            parsed.generics.where_clause.to_token_stream().to_string(),
+            "where S : Storage"
+        );
+        let tests: Vec<_> = parsed
+            .tests
+            .iter()
+            .map(|test| test.sig.ident.to_string())
+            .collect();
+        assert_eq!(tests, ["reads", "writes"]);
+    }
+
+    #[test]
+    fn rejects_anything_but_functions_in_the_suite() {
+        assert!(parse_test_suite(quote!(contract<S> { struct S; })).is_err());
+        assert!(parse_test_suite(quote!(contract<S>)).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+//! Helpers for the tests of the pipelines that read files as they expand.
+
+/// Writes `text` to a file named `name` in a directory of its own, and
+/// returns the file's absolute path, which the expansions read as is.
+pub(crate) fn write(name: &str, text: &str) -> String {
+    let dir = std::env::temp_dir().join(format!(
+        "tust-core-{}-{}",
+        std::process::id(),
+        name.replace(['/', '.'], "_")
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path.to_str().unwrap().to_string()
+}
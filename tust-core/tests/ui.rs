@@ -0,0 +1,10 @@
+//! Compile tests of the macros built on this crate: `valid_*.rs` must
+//! compile and run, and `invalid_*.rs` must fail with the errors in their
+//! `.stderr` files. Set `TRYBUILD=overwrite` to update those.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/invalid_*.rs");
+    t.pass("tests/ui/valid_*.rs");
+}
@@ -0,0 +1,4 @@
+#[tust::test(flavor = "multi_thread")]
+fn waits() {}
+
+fn main() {}
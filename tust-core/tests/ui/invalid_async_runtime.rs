@@ -0,0 +1,4 @@
+#[tust::test(runtime = "smol")]
+async fn awaits() {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use tust::automock;
+
+#[automock]
+pub trait Store {
+    fn new() -> Self;
+}
+
+fn main() {}
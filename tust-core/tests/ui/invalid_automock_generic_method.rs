@@ -0,0 +1,8 @@
+use tust::automock;
+
+#[automock]
+pub trait Store {
+    fn get<K: AsRef<str>>(&self, key: K) -> u32;
+}
+
+fn main() {}
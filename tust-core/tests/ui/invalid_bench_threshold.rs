@@ -0,0 +1,4 @@
+#[tust::bench(threshold = 0)]
+fn sorts() {}
+
+fn main() {}
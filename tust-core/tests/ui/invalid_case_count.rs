@@ -0,0 +1,8 @@
+#[tust::test]
+#[case(1, 2)]
+#[case(3)]
+fn adds(a: i32, b: i32) {
+    assert!(a < b);
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[tust::test]
+#[case(1, 2)]
+fn doubles(n: i32) {
+    assert!(n > 0);
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[tust::test(cases_from = "tests/cases.toml")]
+fn reads(row: u32) {}
+
+fn main() {}
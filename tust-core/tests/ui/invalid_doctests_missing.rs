@@ -0,0 +1,3 @@
+tust::doctests!("tests/ui/missing.md");
+
+fn main() {}
@@ -0,0 +1,3 @@
+tust::doctests!();
+
+fn main() {}
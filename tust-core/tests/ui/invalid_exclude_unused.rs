@@ -0,0 +1,6 @@
+#[tust::test]
+#[values(a = [1, 2])]
+#[exclude(a = 3)]
+fn t(a: u32) {}
+
+fn main() {}
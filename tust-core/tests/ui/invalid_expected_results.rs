@@ -0,0 +1,6 @@
+#[tust::test]
+#[should_fail]
+#[xfail]
+fn fails() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use tust::fixture;
+
+#[fixture]
+fn db() {}
+
+fn main() {}
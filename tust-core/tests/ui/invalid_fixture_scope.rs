@@ -0,0 +1,8 @@
+use tust::fixture;
+
+#[fixture(scope = "module")]
+fn db() -> u32 {
+    1
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[tust::fuzz]
+fn parses(text: &str) {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use tust::after_all;
+
+#[after_all(once)]
+fn stop() {}
+
+fn main() {}
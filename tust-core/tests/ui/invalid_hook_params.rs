@@ -0,0 +1,8 @@
+use tust::before_each;
+
+#[before_each]
+fn set_up(db: u32) {
+    let _ = db;
+}
+
+fn main() {}
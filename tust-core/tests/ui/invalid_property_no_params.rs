@@ -0,0 +1,4 @@
+#[tust::property]
+fn nothing_to_generate() {}
+
+fn main() {}
@@ -0,0 +1,5 @@
+#[tust::test]
+#[skip_if(reason = "slow")]
+fn slow() {}
+
+fn main() {}
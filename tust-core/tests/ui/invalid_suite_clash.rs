@@ -0,0 +1,8 @@
+tust::suite! {
+    describe "a stack" {
+        it "pushes one" {}
+        it "Pushes one!" {}
+    }
+}
+
+fn main() {}
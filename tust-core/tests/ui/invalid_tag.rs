@@ -0,0 +1,4 @@
+#[tust::test(tags("db", "not ok"))]
+fn tagged() {}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[tust::test(frobnicate)]
+fn adds() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[derive(tust::TestBuilder)]
+struct Job {
+    build: u32,
+}
+
+fn main() {}
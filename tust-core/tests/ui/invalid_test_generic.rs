@@ -0,0 +1,4 @@
+#[tust::test]
+fn generic<T>() {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+tust::test_suite! {
+    contract<S> {
+        fn compares(a: S, b: S) {}
+    }
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tust::test]
+fn pair((a, b): (u32, u32)) {
+    assert_eq!(a, b);
+}
+
+fn main() {}
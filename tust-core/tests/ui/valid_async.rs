@@ -0,0 +1,17 @@
+async fn double(n: u32) -> u32 {
+    n * 2
+}
+
+#[tust::test]
+async fn awaits() {
+    assert_eq!(double(21).await, 42);
+}
+
+#[tust::test(runtime = "builtin")]
+#[case(1, 2)]
+#[case(2, 4)]
+async fn awaits_cases(n: u32, doubled: u32) {
+    assert_eq!(double(n).await, doubled);
+}
+
+tust::main!();
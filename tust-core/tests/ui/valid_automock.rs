@@ -0,0 +1,21 @@
+use tust::prelude::*;
+
+#[automock]
+pub trait Store {
+    fn get(&self, key: &str) -> Option<String>;
+    fn put(&mut self, key: String, value: u32);
+}
+
+#[tust::test]
+fn records_expectations() {
+    let mut store = MockStore::new();
+    store
+        .expect_get()
+        .with(eq("a"))
+        .returning(|key| Some(key.to_uppercase()));
+    store.expect_put().times(1);
+    assert_eq!(store.get("a"), Some("A".to_string()));
+    store.put("b".to_string(), 2);
+}
+
+tust::main!();
@@ -0,0 +1,15 @@
+use tust::fixture;
+
+#[fixture]
+fn data() -> Vec<u32> {
+    (0..100).rev().collect()
+}
+
+#[tust::bench(warmup = 1, samples = 3)]
+fn sorts(data: Vec<u32>) {
+    let mut sorted = data.clone();
+    sorted.sort();
+    std::hint::black_box(sorted);
+}
+
+tust::main!();
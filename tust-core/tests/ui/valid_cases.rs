@@ -0,0 +1,8 @@
+#[tust::test]
+#[case(1, 2, 3)]
+#[case::negative(-1, -2, -3)]
+fn adds(a: i32, b: i32, sum: i32) {
+    assert_eq!(a + b, sum);
+}
+
+tust::main!();
@@ -0,0 +1,23 @@
+#[tust::test]
+#[skip(reason = "not ready")]
+fn skipped() {
+    panic!("skipped tests do not run");
+}
+
+#[tust::test]
+#[skip_if(env = "TUST_UI_NEVER_SET")]
+fn runs_unless_set() {}
+
+#[tust::test]
+#[should_fail(expected = "boom")]
+fn fails_as_it_should() {
+    panic!("boom");
+}
+
+#[tust::test]
+#[xfail(reason = "known bug")]
+fn fails_as_expected() {
+    assert_eq!(1 + 1, 3);
+}
+
+tust::main!();
@@ -0,0 +1,45 @@
+use tust::fixture;
+
+#[fixture]
+fn base() -> u32 {
+    40
+}
+
+#[fixture]
+fn answer(base: u32) -> u64 {
+    u64::from(base) + 2
+}
+
+#[fixture(scope = "session")]
+fn greeting() -> String {
+    "hello".to_string()
+}
+
+#[fixture]
+async fn fetched(base: u32) -> u32 {
+    async { base + 1 }.await
+}
+
+#[fixture(scope = "session")]
+async fn connection() -> String {
+    async { "connected".to_string() }.await
+}
+
+#[tust::test]
+fn injects_fixtures(answer: u64, greeting: String) {
+    assert_eq!(answer, 42);
+    assert_eq!(greeting, "hello");
+}
+
+#[tust::test]
+fn awaits_async_fixtures(fetched: u32, connection: String) {
+    assert_eq!(fetched, 41);
+    assert_eq!(connection, "connected");
+}
+
+#[tust::test]
+async fn injects_async_fixtures_into_async_tests(fetched: u32) {
+    assert_eq!(fetched, 41);
+}
+
+tust::main!();
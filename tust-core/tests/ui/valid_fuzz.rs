@@ -0,0 +1,15 @@
+#[tust::fuzz(iterations = 50)]
+fn parses_any_utf8(data: &[u8]) {
+    if let Ok(text) = std::str::from_utf8(data) {
+        assert_eq!(text.len(), data.len());
+    }
+}
+
+#[tust::fuzz(iterations = 50)]
+fn sorting_keeps_the_length(input: Vec<u8>) {
+    let mut sorted = input.clone();
+    sorted.sort();
+    assert_eq!(sorted.len(), input.len());
+}
+
+tust::main!();
@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tust::{after_all, after_each, before_all, before_each};
+
+static SET_UP: AtomicUsize = AtomicUsize::new(0);
+
+#[before_all]
+fn start() {}
+
+#[before_each]
+fn set_up() {
+    SET_UP.fetch_add(1, Ordering::SeqCst);
+}
+
+#[after_each]
+fn tear_down() {}
+
+#[after_all]
+fn stop() {}
+
+#[tust::test]
+fn runs_after_set_up() {
+    assert!(SET_UP.load(Ordering::SeqCst) > 0);
+}
+
+tust::main!();
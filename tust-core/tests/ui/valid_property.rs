@@ -0,0 +1,15 @@
+#[tust::property(cases = 20, seed = 1)]
+fn addition_commutes(a: u16, b: u16) {
+    assert_eq!(u32::from(a) + u32::from(b), u32::from(b) + u32::from(a));
+}
+
+#[tust::property]
+#[timeout(10_000)]
+fn reversing_twice_is_identity(v: Vec<u8>) {
+    let mut twice = v.clone();
+    twice.reverse();
+    twice.reverse();
+    assert_eq!(twice, v);
+}
+
+tust::main!();
@@ -0,0 +1,23 @@
+tust::suite! {
+    fn stack() -> Vec<u32> {
+        vec![1, 2]
+    }
+
+    describe "a stack" {
+        before_each {}
+
+        it "pushes" {
+            let mut stack = stack();
+            stack.push(3);
+            assert_eq!(stack.len(), 3);
+        }
+
+        describe "when empty" {
+            it "pops None" {
+                assert_eq!(Vec::<u32>::new().pop(), None);
+            }
+        }
+    }
+}
+
+tust::main!();
@@ -0,0 +1,11 @@
+#[tust::test(tags("db", "slow"))]
+fn tagged() {}
+
+#[tust::test(tags("net.v2"))]
+#[case(1)]
+#[case(2)]
+fn tagged_cases(n: u32) {
+    assert!(n > 0);
+}
+
+tust::main!();
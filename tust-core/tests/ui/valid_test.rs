@@ -0,0 +1,6 @@
+#[tust::test]
+fn adds() {
+    assert_eq!(1 + 1, 2);
+}
+
+tust::main!();
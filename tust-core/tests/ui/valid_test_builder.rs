@@ -0,0 +1,31 @@
+use tust::TestBuilder;
+
+#[derive(Debug, TestBuilder)]
+struct Order {
+    id: u64,
+    quantity: u32,
+    #[test_builder(default = vec!["gift-wrap".to_string()])]
+    notes: Vec<String>,
+}
+
+#[derive(TestBuilder)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[tust::test]
+fn sets_only_what_the_test_is_about() {
+    let order = OrderBuilder::new().quantity(1_000).build();
+    assert_eq!(order.quantity, 1_000);
+    assert_eq!(order.notes, ["gift-wrap"]);
+    let _ = order.id;
+}
+
+#[tust::test]
+fn generates_generic_fields() {
+    let wrapper = WrapperBuilder::<u8>::default().value(3).build();
+    assert_eq!(wrapper.value, 3);
+    let _: Wrapper<bool> = WrapperBuilder::new().build();
+}
+
+tust::main!();
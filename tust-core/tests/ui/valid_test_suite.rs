@@ -0,0 +1,31 @@
+pub trait Stack {
+    fn push(&mut self, value: u32);
+    fn pop(&mut self) -> Option<u32>;
+}
+
+impl Stack for Vec<u32> {
+    fn push(&mut self, value: u32) {
+        Vec::push(self, value);
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        Vec::pop(self)
+    }
+}
+
+tust::test_suite! {
+    stack_contract<S: Stack> {
+        fn pops_what_it_pushed(mut stack: S) {
+            stack.push(1);
+            assert_eq!(stack.pop(), Some(1));
+        }
+
+        fn starts_empty(mut stack: S) {
+            assert_eq!(stack.pop(), None);
+        }
+    }
+}
+
+stack_contract!(vec, Vec::new());
+
+tust::main!();
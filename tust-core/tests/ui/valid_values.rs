@@ -0,0 +1,8 @@
+#[tust::test]
+#[values(a = [1, 2, 3], b = [10, 20])]
+#[exclude(a = 3, b = 20)]
+fn sums_stay_in_range(a: u32, b: u32) {
+    assert!(a + b <= 22);
+}
+
+tust::main!();
@@ -0,0 +1,11 @@
+//! Puts `tust.x` where the linker finds it, for `-C link-arg=-Ttust.x`.
+
+use std::path::PathBuf;
+use std::{env, fs};
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("Cargo sets OUT_DIR"));
+    fs::write(out.join("tust.x"), include_str!("tust.x")).expect("cannot write tust.x");
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=tust.x");
+}
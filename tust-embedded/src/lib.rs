@@ -0,0 +1,94 @@
+//! Runs tust tests on embedded targets.
+//!
+//! Tests keep the attributes they have on the host: `#[tust::test]` with
+//! `tags(...)`, `#[case(...)]`, `#[skip]`, `#[skip_if(cfg(...))]`,
+//! `#[should_fail]`, and `#[xfail]`, and the `#![no_std]` assertions of
+//! `tust-assertions`. On the target [`run`] runs them one at a time and
+//! reports each over [defmt](https://defmt.ferrous-systems.com), and on the
+//! host `cargo tust embedded` decodes the messages into the same reports a
+//! run on the host gives.
+//!
+//! Depend on this crate under the name `tust`, so that the attribute and the
+//! assertions are spelled as on the host, along with a defmt transport and a
+//! panic handler that logs with defmt:
+//!
+//! ```toml
+//! [dependencies]
+//! tust = { package = "tust-embedded", version = "0.1" }
+//! defmt = "1.0"
+//! defmt-rtt = "1.0"
+//! panic-probe = { version = "1.0", features = ["print-defmt"] }
+//! ```
+//!
+//! Then call [`run`] from the program's entry point, and end the program
+//! the way the tool running it expects:
+//!
+//! ```ignore
+//! #![no_std]
+//! #![no_main]
+//!
+//! use defmt_rtt as _;
+//! use panic_probe as _;
+//!
+//! #[tust::test]
+//! #[case(0, 0)]
+//! #[case(255, 8)]
+//! fn counts_set_bits(value: u8, bits: u32) {
+//!     tust::check_eq!(value.count_ones(), bits);
+//! }
+//!
+//! #[cortex_m_rt::entry]
+//! fn main() -> ! {
+//!     tust::run();
+//!     loop {
+//!         cortex_m::asm::bkpt();
+//!     }
+//! }
+//! ```
+//!
+//! With `cargo tust embedded` in front of the runner Cargo is configured
+//! with, `cargo run` flashes the program, and reports the tests as they run:
+//!
+//! ```toml
+//! # .cargo/config.toml
+//! [target.thumbv7em-none-eabihf]
+//! runner = "cargo tust embedded -- probe-rs run --chip nRF52840_xxAA"
+//! rustflags = ["-C", "link-arg=-Tdefmt.x", "-C", "link-arg=-Ttust.x"]
+//! ```
+//!
+//! `tust.x`, which this crate puts where the linker finds it, keeps the
+//! tests in flash. It expects the memory region of flash to be called
+//! `FLASH`, as `cortex-m-rt` names it; a program whose memory layout names
+//! it differently places the `linkme_TESTS` and `linkm2_TESTS` sections in
+//! its own linker script instead.
+//!
+//! # Limitations
+//!
+//! A panic stops the target, so the run ends at the first test that fails,
+//! and the tests after it are reported as not run. A `#[should_fail]` test
+//! passes by panicking, and so also ends the run. Soft assertions such as
+//! `check_eq!` panic like fatal ones.
+//!
+//! Fixtures, `async fn` tests, and the options that need threads, clocks, a
+//! file system, or environment variables, such as `timeout`, `repeat`, and
+//! `#[with_env]` and `#[with_locale]`, are rejected at compile time. Tests run in the order the
+//! linker placed them, one at a time, so `#[serial]` and `lock` hold
+//! without doing anything.
+
+#![no_std]
+
+pub mod registry;
+mod runner;
+
+pub use registry::{tests, TestDef};
+pub use runner::run;
+pub use tust_assertions::*;
+pub use tust_macros::embedded_test as test;
+
+/// Items used by macro-generated code. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use linkme;
+
+    pub use crate::registry::{ExpectedResult, Skip, TestDef, TESTS};
+}
@@ -0,0 +1,72 @@
+//! Link-time registry of every test annotated with `#[tust::test]`.
+//!
+//! The attribute macro emits one [`TestDef`] per test into a linker section
+//! gathered by [`linkme`], which, unlike the registry on the host, needs no
+//! code run before `main`.
+
+use linkme::distributed_slice;
+
+/// Static description of a registered test.
+///
+/// Values of this type are generated by `#[tust::test]`; they are not meant to
+/// be constructed by hand.
+#[derive(Debug)]
+pub struct TestDef {
+    /// Name of the test relative to its module.
+    pub name: &'static str,
+    /// `module_path!()` of the module the test was declared in.
+    pub module_path: &'static str,
+    /// Source file declaring the test.
+    pub file: &'static str,
+    /// Line of the test function's name.
+    pub line: u32,
+    /// Tags given with `#[tust::test(tags(...))]`.
+    pub tags: &'static [&'static str],
+    /// Whether the test is skipped instead of run.
+    pub skip: Skip,
+    /// Whether the test is expected to pass.
+    pub expected: ExpectedResult,
+    /// Runs the test's body.
+    pub run: fn(),
+}
+
+/// When a test is skipped, as set by `#[skip]` and `#[skip_if]`.
+#[derive(Debug, Clone, Copy)]
+pub enum Skip {
+    /// The test runs.
+    Never,
+    /// The test is never run.
+    Always { reason: Option<&'static str> },
+    /// The test is skipped when `condition` returns true as it is about to
+    /// run.
+    If {
+        condition: fn() -> bool,
+        reason: &'static str,
+    },
+}
+
+/// How a test is expected to end, as set by `#[should_fail]` and `#[xfail]`.
+/// The host judges the outcome against it.
+#[derive(Debug, Clone, Copy)]
+pub enum ExpectedResult {
+    Pass,
+    /// The test passes if it fails, and if `expected` is given, only if the
+    /// panic message contains it; it fails if it passes.
+    ShouldFail {
+        expected: Option<&'static str>,
+    },
+    /// The test is known to be broken: a failure is reported as expected,
+    /// and a pass is an error.
+    XFail {
+        reason: Option<&'static str>,
+    },
+}
+
+/// Every registered test, in the order the linker placed them.
+#[distributed_slice]
+pub static TESTS: [TestDef];
+
+/// Every registered test.
+pub fn tests() -> &'static [TestDef] {
+    &TESTS
+}
@@ -0,0 +1,63 @@
+//! The run of every registered test, reported over defmt in the protocol
+//! that the host decodes; see `tust_runtime::embedded`.
+
+use crate::registry::{self, ExpectedResult, Skip};
+
+/// Runs every registered test, one at a time, reporting each over defmt.
+///
+/// Returns once every test has passed or been skipped; a test that fails
+/// panics, which ends the run. Either way a host decoding the messages
+/// reports the run.
+pub fn run() {
+    let tests = registry::tests();
+    defmt::println!("tust:run {=usize}", tests.len());
+    for test in tests {
+        defmt::println!(
+            "tust:test {=str} {=str} {=u32} {=str}",
+            test.module_path,
+            test.name,
+            test.line,
+            test.file
+        );
+        for tag in test.tags {
+            defmt::println!("tust:tag {=str}", tag);
+        }
+        match test.expected {
+            ExpectedResult::Pass => {}
+            ExpectedResult::ShouldFail { expected: None } => defmt::println!("tust:should_fail"),
+            ExpectedResult::ShouldFail {
+                expected: Some(expected),
+            } => defmt::println!("tust:should_fail {=str}", expected),
+            ExpectedResult::XFail { reason: None } => defmt::println!("tust:xfail"),
+            ExpectedResult::XFail {
+                reason: Some(reason),
+            } => defmt::println!("tust:xfail {=str}", reason),
+        }
+    }
+
+    for (index, test) in tests.iter().enumerate() {
+        defmt::println!("tust:start {=usize}", index);
+        match test.skip {
+            Skip::Never => {}
+            Skip::Always { reason: None } => {
+                defmt::println!("tust:skipped");
+                continue;
+            }
+            Skip::Always {
+                reason: Some(reason),
+            } => {
+                defmt::println!("tust:skipped {=str}", reason);
+                continue;
+            }
+            Skip::If { condition, reason } => {
+                if condition() {
+                    defmt::println!("tust:skipped {=str}", reason);
+                    continue;
+                }
+            }
+        }
+        (test.run)();
+        defmt::println!("tust:ok");
+    }
+    defmt::println!("tust:end");
+}
@@ -0,0 +1,15 @@
+/* Keeps the tests registered by `#[tust::test]` in flash, with the
+   program's constants. Their section is writable, and would otherwise be
+   placed in RAM without being copied there at startup. */
+SECTIONS
+{
+  linkme_TESTS : ALIGN(4)
+  {
+    KEEP(*(linkme_TESTS))
+  } > FLASH
+  linkm2_TESTS : ALIGN(4)
+  {
+    KEEP(*(linkm2_TESTS))
+  } > FLASH
+}
+INSERT AFTER .rodata;
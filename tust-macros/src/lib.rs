@@ -1,6 +1,168 @@
-// Placeholder for procedural macro implementations
-// This crate will contain thin wrappers that convert between
-// proc_macro and proc_macro2 token streams, delegating all
-// logic to tust-core.
+//! Procedural macros for the tust test framework.
+//!
+//! Each macro is a thin wrapper that converts between `proc_macro` and
+//! `proc_macro2` token streams and delegates all logic to `tust-core`.
 
-// TODO: Implement proc macros
+use proc_macro::TokenStream;
+use tust_core::HookKind;
+
+/// Registers a function as a tust test.
+///
+/// See the `tust` crate documentation for the supported arguments.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_test(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a function as a tust test run on an embedded target.
+///
+/// Re-exported as `test` by `tust-embedded`; see its documentation.
+#[proc_macro_attribute]
+pub fn embedded_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_embedded_test(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a function as a property-based test, run against generated
+/// values of its parameters.
+///
+/// See the `tust` crate documentation for the supported arguments.
+#[proc_macro_attribute]
+pub fn property(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_property(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a function as a fuzz target: a test run against a corpus of
+/// inputs and mutations of them.
+///
+/// See the `tust` crate documentation for the supported arguments.
+#[proc_macro_attribute]
+pub fn fuzz(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_fuzz(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a function as a benchmark: a test whose body is measured when
+/// run with `--bench`.
+///
+/// See the `tust` crate documentation for the supported arguments.
+#[proc_macro_attribute]
+pub fn bench(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_bench(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a function as a fixture that tests and other fixtures can
+/// request by parameter name.
+///
+/// See the `tust` crate documentation for the supported arguments.
+#[proc_macro_attribute]
+pub fn fixture(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_fixture(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Runs a function before every test in the enclosing module and its
+/// submodules.
+///
+/// See the `tust` crate documentation for how hooks are ordered.
+#[proc_macro_attribute]
+pub fn before_each(attr: TokenStream, item: TokenStream) -> TokenStream {
+    hook(HookKind::BeforeEach, attr, item)
+}
+
+/// Runs a function after every test in the enclosing module and its
+/// submodules, even if the test failed.
+///
+/// See the `tust` crate documentation for how hooks are ordered.
+#[proc_macro_attribute]
+pub fn after_each(attr: TokenStream, item: TokenStream) -> TokenStream {
+    hook(HookKind::AfterEach, attr, item)
+}
+
+/// Runs a function once before the first test in the enclosing module and
+/// its submodules.
+///
+/// See the `tust` crate documentation for how hooks are ordered.
+#[proc_macro_attribute]
+pub fn before_all(attr: TokenStream, item: TokenStream) -> TokenStream {
+    hook(HookKind::BeforeAll, attr, item)
+}
+
+/// Runs a function once after the last test in the enclosing module and its
+/// submodules, even if tests failed.
+///
+/// See the `tust` crate documentation for how hooks are ordered.
+#[proc_macro_attribute]
+pub fn after_all(attr: TokenStream, item: TokenStream) -> TokenStream {
+    hook(HookKind::AfterAll, attr, item)
+}
+
+/// Generates a mock implementation of a trait, `Mock` followed by the
+/// trait's name, with an expectation API for each method.
+///
+/// See the `tust` crate documentation for how expectations are verified.
+#[proc_macro_attribute]
+pub fn automock(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_automock(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates a builder for a struct, its name followed by `Builder`, that
+/// gives the fields a test does not set seeded random values.
+///
+/// See the `tust` crate documentation for the `#[test_builder]` attribute.
+#[proc_macro_derive(TestBuilder, attributes(test_builder))]
+pub fn test_builder(input: TokenStream) -> TokenStream {
+    tust_core::expand_test_builder(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Declares tests in nested `describe` and `it` blocks, named after their
+/// descriptions.
+///
+/// See the `tust` crate documentation for the supported blocks.
+#[proc_macro]
+pub fn suite(input: TokenStream) -> TokenStream {
+    tust_core::expand_suite(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Declares a suite of tests generic over one type, such as the contract of
+/// a trait, which implementations instantiate for their own type.
+///
+/// See the `tust` crate documentation for the syntax.
+#[proc_macro]
+pub fn test_suite(input: TokenStream) -> TokenStream {
+    tust_core::expand_test_suite(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Runs the fenced Rust examples of markdown files, and of the doc comments
+/// of Rust files, as tests.
+///
+/// See the `tust` crate documentation for the fence attributes understood.
+#[proc_macro]
+pub fn doctests(input: TokenStream) -> TokenStream {
+    tust_core::expand_doctests(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn hook(kind: HookKind, attr: TokenStream, item: TokenStream) -> TokenStream {
+    tust_core::expand_hook(kind, attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
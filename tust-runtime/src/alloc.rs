@@ -0,0 +1,277 @@
+//! An opt-in allocator that counts the heap allocations of a piece of code.
+//!
+//! Install [`TrackingAllocator`] as the global allocator of a test target:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: tust::alloc::TrackingAllocator = tust::alloc::TrackingAllocator::new();
+//! ```
+//!
+//! It forwards every request to the system allocator (or the allocator it
+//! wraps), and counts those made by a thread while that thread runs
+//! [`track`]. Allocations made by other threads, such as a thread pool the
+//! tracked code hands work to, are not counted.
+//!
+//! `assert_no_alloc!` and `assert_allocates_at_most!` build on [`track`].
+//!
+//! # Leak checks
+//!
+//! With `--leak-check`, or [`LEAK_CHECK_ENV`] set, the runner tracks each
+//! test from its `before_each` hooks to the drop of its fixtures after its
+//! `after_each` hooks, and fails a test that passed otherwise but left bytes
+//! allocated. Values the runtime keeps for later, such as session-scoped
+//! fixtures, are not counted. Memory a test allocates and another thread
+//! frees, or that a test keeps on purpose in a static, does count; mark such
+//! tests `#[allow_leaks]`. So does, under libtest, the output a test prints,
+//! which libtest keeps in memory unless run with `--nocapture`; the tust
+//! runner captures output outside the heap.
+
+use crate::registry::TestDef;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable that checks tests for leaks when set to `1` or
+/// `true`; set by the runner's `--leak-check`.
+pub const LEAK_CHECK_ENV: &str = "TUST_LEAK_CHECK";
+
+/// Whether a [`TrackingAllocator`] has served an allocation, and so is the
+/// global allocator.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The counts of the innermost [`track`] running on this thread, if any.
+    static CURRENT: Cell<Option<AllocStats>> = const { Cell::new(None) };
+}
+
+/// A global allocator that counts the allocations made within [`track`],
+/// forwarding them to `A`.
+pub struct TrackingAllocator<A = System> {
+    inner: A,
+}
+
+impl TrackingAllocator {
+    /// A tracking allocator over the system allocator.
+    pub const fn new() -> Self {
+        TrackingAllocator { inner: System }
+    }
+}
+
+impl Default for TrackingAllocator {
+    fn default() -> Self {
+        TrackingAllocator::new()
+    }
+}
+
+impl<A> TrackingAllocator<A> {
+    /// A tracking allocator over `inner`.
+    pub const fn wrap(inner: A) -> Self {
+        TrackingAllocator { inner }
+    }
+}
+
+/// Applies `update` to the counts of the [`track`] running on this thread.
+fn count(update: impl FnOnce(&mut AllocStats)) {
+    INSTALLED.store(true, Ordering::Relaxed);
+    // The thread-local may already be gone while the thread exits.
+    let _ = CURRENT.try_with(|current| {
+        if let Some(mut stats) = current.get() {
+            update(&mut stats);
+            current.set(Some(stats));
+        }
+    });
+}
+
+// SAFETY: every request is forwarded unchanged to `inner`, which upholds the
+// `GlobalAlloc` contract; counting neither allocates nor touches the memory.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(|stats| stats.record_alloc(layout.size()));
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(|stats| stats.record_alloc(layout.size()));
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count(|stats| {
+            stats.deallocations += 1;
+            stats.bytes_deallocated += layout.size() as u64;
+        });
+        self.inner.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(|stats| {
+            stats.reallocations += 1;
+            let old_size = layout.size() as u64;
+            let new_size = new_size as u64;
+            if new_size > old_size {
+                stats.bytes_allocated += new_size - old_size;
+            } else {
+                stats.bytes_deallocated += old_size - new_size;
+            }
+        });
+        self.inner.realloc(ptr, layout, new_size)
+    }
+}
+
+/// The heap activity of a piece of code, as counted by [`track`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// New allocations, not counting reallocations.
+    pub allocations: u64,
+    pub reallocations: u64,
+    pub deallocations: u64,
+    /// Bytes requested by allocations, and by reallocations that grew.
+    pub bytes_allocated: u64,
+    /// Bytes freed by deallocations, and by reallocations that shrank.
+    pub bytes_deallocated: u64,
+}
+
+impl AllocStats {
+    fn record_alloc(&mut self, size: usize) {
+        self.allocations += 1;
+        self.bytes_allocated += size as u64;
+    }
+
+    /// Whether the code allocated or reallocated at all.
+    pub fn allocated(&self) -> bool {
+        self.allocations > 0 || self.reallocations > 0
+    }
+
+    /// Bytes allocated and not freed again, or 0 if the code freed more
+    /// than it allocated.
+    pub fn leaked_bytes(&self) -> u64 {
+        self.bytes_allocated.saturating_sub(self.bytes_deallocated)
+    }
+
+    fn add(&mut self, other: AllocStats) {
+        self.allocations += other.allocations;
+        self.reallocations += other.reallocations;
+        self.deallocations += other.deallocations;
+        self.bytes_allocated += other.bytes_allocated;
+        self.bytes_deallocated += other.bytes_deallocated;
+    }
+}
+
+impl fmt::Display for AllocStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}; {} allocated, {} freed",
+            plural(self.allocations, "allocation"),
+            plural(self.reallocations, "reallocation"),
+            plural(self.deallocations, "deallocation"),
+            plural(self.bytes_allocated, "byte"),
+            self.bytes_deallocated,
+        )
+    }
+}
+
+fn plural(count: u64, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        count => format!("{} {}s", count, noun),
+    }
+}
+
+/// Whether a [`TrackingAllocator`] is the global allocator.
+pub fn is_installed() -> bool {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        // Nothing may have allocated yet.
+        drop(std::hint::black_box(Box::new(0u8)));
+    }
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Restores the counts of an enclosing [`track`] when the inner one ends,
+/// even by panicking.
+struct Scope {
+    outer: Option<AllocStats>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let inner = CURRENT.with(Cell::take).unwrap_or_default();
+        CURRENT.with(|current| {
+            current.set(self.outer.map(|mut outer| {
+                outer.add(inner);
+                outer
+            }))
+        });
+    }
+}
+
+/// Calls `f`, and returns its result along with the allocations this thread
+/// made while it ran. Calls to `track` nest: the outer call counts the
+/// allocations of the inner one too.
+///
+/// # Panics
+///
+/// Panics if no [`TrackingAllocator`] is the global allocator.
+#[track_caller]
+pub fn track<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    assert!(
+        is_installed(),
+        "allocations can only be counted with tust's tracking allocator installed; add\n\
+         \n    #[global_allocator]\n    \
+         static ALLOC: tust::alloc::TrackingAllocator = tust::alloc::TrackingAllocator::new();\n\
+         \nto the test target"
+    );
+    let scope = Scope {
+        outer: CURRENT.with(|current| current.replace(Some(AllocStats::default()))),
+    };
+    let result = f();
+    let stats = CURRENT.with(Cell::get).unwrap_or_default();
+    drop(scope);
+    (result, stats)
+}
+
+/// Calls `f` without counting its allocations in the [`track`] running on
+/// this thread, for values kept beyond it on purpose.
+pub(crate) fn untracked<R>(f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<AllocStats>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = CURRENT.try_with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CURRENT.try_with(Cell::take).ok().flatten());
+    f()
+}
+
+/// Whether tests are checked for leaks; see [Leak checks](self#leak-checks).
+pub fn leak_check_enabled() -> bool {
+    std::env::var(LEAK_CHECK_ENV).is_ok_and(|value| value == "1" || value == "true")
+}
+
+/// Calls `f`, which runs `test`, and describes the memory it leaked if leak
+/// checks are on and `test` is not `#[allow_leaks]`.
+pub(crate) fn check_leaks<R>(test: &TestDef, f: impl FnOnce() -> R) -> (R, Option<String>) {
+    if test.allow_leaks || !leak_check_enabled() || !is_installed() {
+        return (f(), None);
+    }
+    // The standard library and the panic hook are set up on first use and
+    // kept, so set them up before counting.
+    let _ = std::io::stdout();
+    let _ = std::thread::current();
+    crate::panic::install_hook();
+    let (result, stats) = track(f);
+    let leak = (stats.leaked_bytes() > 0).then(|| {
+        format!(
+            "test leaked {}, not freed by the end of its teardown\n  \
+             counted: {}\n  \
+             mark the test #[allow_leaks] if it keeps them on purpose",
+            plural(stats.leaked_bytes(), "byte"),
+            stats
+        )
+    });
+    (result, leak)
+}
@@ -0,0 +1,429 @@
+//! Benchmarks declared with `#[tust::bench]`.
+//!
+//! A benchmark is a test whose body is measured. Run normally, the body runs
+//! once, so a benchmark also checks that the code it measures works. With
+//! `--bench` (which `cargo bench` passes), or [`BENCH_ENV`] set, the body is
+//! instead run repeatedly: first for a warmup period, then in samples of
+//! many iterations each, timed one sample at a time.
+//!
+//! The runner reports the mean, median, and 95th percentile of the time per
+//! iteration, and counts the samples that lie outside Tukey's fences, 1.5
+//! (mild) and 3 (severe) interquartile ranges beyond the quartiles, as
+//! outliers: a busy machine shows up as many outliers.
+//!
+//! The first measurement of a benchmark is saved as its baseline in
+//! `target/tust/bench/` (or the directory named by [`DIR_ENV`]), in a file
+//! named after the crate and path of the benchmark. Later runs
+//! compare their median against the baseline's, and the benchmark fails if it
+//! is slower by more than the threshold: 10% unless the benchmark sets
+//! `threshold` or [`THRESHOLD_ENV`] is set. With [`UPDATE_ENV`] set, the
+//! measurement replaces the baseline instead.
+
+use std::fmt;
+use std::fs;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::alloc;
+use crate::cache;
+use crate::registry::{self, TestDef};
+
+/// Environment variable that measures benchmarks when set to `1` or `true`;
+/// set by the runner's `--bench`.
+pub const BENCH_ENV: &str = "TUST_BENCH";
+
+/// Environment variable that replaces the saved baselines with the new
+/// measurements when set to `1` or `true`.
+pub const UPDATE_ENV: &str = "TUST_UPDATE_BENCHMARKS";
+
+/// Environment variable giving the default regression threshold, as a
+/// percentage of the baseline's median.
+pub const THRESHOLD_ENV: &str = "TUST_BENCH_THRESHOLD";
+
+/// Environment variable naming the directory holding the baselines.
+pub const DIR_ENV: &str = "TUST_BENCH_DIR";
+
+const DEFAULT_WARMUP: Duration = Duration::from_millis(300);
+const DEFAULT_SAMPLES: u32 = 50;
+const DEFAULT_THRESHOLD: f64 = 10.0;
+
+/// Shortest time a sample is run for, so that timer resolution does not
+/// distort fast benchmarks.
+const MIN_SAMPLE_TIME: Duration = Duration::from_millis(10);
+
+/// Benchmarks measured during this run, in the order they finished.
+static RESULTS: Mutex<Vec<BenchResult>> = Mutex::new(Vec::new());
+
+/// Settings given with `#[tust::bench(...)]`.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BenchConfig {
+    pub warmup: Option<Duration>,
+    pub samples: Option<u32>,
+    /// Percentage of the baseline's median beyond which the benchmark fails.
+    pub threshold: Option<f64>,
+}
+
+/// Statistics of the time one iteration of a benchmark took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub samples: usize,
+    /// Iterations run per sample.
+    pub iterations: u64,
+    pub mean: Duration,
+    pub median: Duration,
+    /// 95th percentile.
+    pub p95: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub std_dev: Duration,
+    /// Samples between 1.5 and 3 interquartile ranges outside the quartiles.
+    pub mild_outliers: usize,
+    /// Samples more than 3 interquartile ranges outside the quartiles.
+    pub severe_outliers: usize,
+}
+
+/// A measured benchmark, compared against its baseline.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub test: &'static TestDef,
+    pub stats: Stats,
+    /// Median of the saved baseline, if there was one to compare against.
+    pub baseline: Option<Duration>,
+    /// Regression threshold, as a percentage of the baseline's median.
+    pub threshold: f64,
+    /// Whether this measurement was saved as the new baseline.
+    pub saved: bool,
+}
+
+impl BenchResult {
+    /// How much slower than the baseline the median is, as a percentage;
+    /// negative if it is faster.
+    pub fn change(&self) -> Option<f64> {
+        self.baseline.map(|baseline| {
+            let baseline = baseline.as_secs_f64();
+            (self.stats.median.as_secs_f64() - baseline) / baseline * 100.0
+        })
+    }
+
+    /// Whether the median is slower than the baseline by more than the
+    /// threshold.
+    pub fn regressed(&self) -> bool {
+        self.change().is_some_and(|change| change > self.threshold)
+    }
+}
+
+/// Summarizes the result on one line, e.g. `parser::large: median 1.21ms,
+/// mean 1.25ms ± 40.12µs, p95 1.40ms (50 × 812 iterations, 2 outliers),
+/// +3.1% against the baseline`.
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        write!(
+            f,
+            "{}: median {}, mean {} ± {}, p95 {} ({} × {} iterations",
+            self.test.path(),
+            format_time(stats.median),
+            format_time(stats.mean),
+            format_time(stats.std_dev),
+            format_time(stats.p95),
+            stats.samples,
+            stats.iterations
+        )?;
+        match stats.mild_outliers + stats.severe_outliers {
+            0 => {}
+            1 => write!(f, ", 1 outlier")?,
+            outliers => write!(f, ", {} outliers", outliers)?,
+        }
+        f.write_str(")")?;
+        match self.change() {
+            Some(change) if self.saved => {
+                write!(f, ", {:+.1}%, saved as the new baseline", change)
+            }
+            Some(change) if self.regressed() => write!(
+                f,
+                ", {:+.1}% against the baseline, beyond the {}% threshold",
+                change, self.threshold
+            ),
+            Some(change) => write!(f, ", {:+.1}% against the baseline", change),
+            None => f.write_str(", saved as the baseline"),
+        }
+    }
+}
+
+/// Formats a duration with three significant digits, in the unit that suits
+/// it: `812ns`, `40.1µs`, `1.21ms`, or `2.50s`.
+pub fn format_time(duration: Duration) -> String {
+    let nanos = duration.as_secs_f64() * 1e9;
+    let (value, unit) = if nanos < 1e3 {
+        (nanos, "ns")
+    } else if nanos < 1e6 {
+        (nanos / 1e3, "µs")
+    } else if nanos < 1e9 {
+        (nanos / 1e6, "ms")
+    } else {
+        (nanos / 1e9, "s")
+    };
+    if value < 10.0 {
+        format!("{:.2}{}", value, unit)
+    } else if value < 100.0 {
+        format!("{:.1}{}", value, unit)
+    } else {
+        format!("{:.0}{}", value, unit)
+    }
+}
+
+/// Whether benchmarks are measured rather than run once: [`BENCH_ENV`] is
+/// set.
+pub fn is_enabled() -> bool {
+    env_flag(BENCH_ENV)
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| value == "1" || value == "true")
+}
+
+/// Runs the body of the benchmark being run on this thread: once, or
+/// measured if benchmarks are enabled.
+///
+/// # Panics
+///
+/// Panics if the benchmark regressed, or if no test is running on this
+/// thread.
+#[doc(hidden)]
+#[track_caller]
+pub fn run<R>(config: &BenchConfig, mut body: impl FnMut() -> R) {
+    let Some(test) = registry::current() else {
+        panic!("benchmarks can only be run by the tust runtime");
+    };
+    if !is_enabled() {
+        black_box(body());
+        return;
+    }
+
+    let warmup = config.warmup.unwrap_or(DEFAULT_WARMUP);
+    let start = Instant::now();
+    let mut warmup_iterations: u32 = 0;
+    while warmup_iterations == 0 || start.elapsed() < warmup {
+        black_box(body());
+        warmup_iterations = warmup_iterations.saturating_add(1);
+    }
+    let estimate = start.elapsed() / warmup_iterations;
+    let iterations = if estimate.is_zero() {
+        1_000_000
+    } else {
+        (MIN_SAMPLE_TIME.as_nanos() / estimate.as_nanos()).clamp(1, 1_000_000) as u64
+    };
+
+    let samples: Vec<f64> = (0..config.samples.unwrap_or(DEFAULT_SAMPLES).max(1))
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(body());
+            }
+            start.elapsed().as_secs_f64() / iterations as f64
+        })
+        .collect();
+    let stats = Stats::new(samples, iterations);
+
+    let threshold = config
+        .threshold
+        .or_else(|| std::env::var(THRESHOLD_ENV).ok()?.parse().ok())
+        .unwrap_or(DEFAULT_THRESHOLD);
+    let path = baseline_path(test);
+    let baseline = path.as_ref().and_then(read_baseline);
+    let mut result = BenchResult {
+        test,
+        stats,
+        baseline,
+        threshold,
+        saved: false,
+    };
+    if baseline.is_none() || env_flag(UPDATE_ENV) {
+        if let Some(path) = &path {
+            match write_baseline(path, &stats) {
+                Ok(()) => result.saved = true,
+                Err(err) => eprintln!(
+                    "warning: cannot save the baseline of {} to {}: {}",
+                    test.path(),
+                    path.display(),
+                    err
+                ),
+            }
+        }
+    }
+    let regressed = !result.saved && result.regressed();
+    let message = regressed.then(|| {
+        format!(
+            "benchmark regressed: the median of {} is {:+.1}% against the baseline's {}, \
+             beyond the {}% threshold\n  rerun with {}=1 to accept it as the new baseline",
+            format_time(stats.median),
+            result.change().unwrap_or_default(),
+            format_time(baseline.unwrap_or_default()),
+            threshold,
+            UPDATE_ENV
+        )
+    });
+    record(result);
+    if let Some(message) = message {
+        panic!("{}", message);
+    }
+}
+
+impl Stats {
+    /// The statistics of `samples`, each the time per iteration in seconds
+    /// of a sample of `iterations` iterations.
+    fn new(mut samples: Vec<f64>, iterations: u64) -> Stats {
+        samples.sort_by(f64::total_cmp);
+        let n = samples.len();
+        let quantile = |q: f64| {
+            // Linear interpolation between the closest ranks.
+            let rank = q * (n - 1) as f64;
+            let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+            samples[low] + (samples[high] - samples[low]) * (rank - low as f64)
+        };
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = if n > 1 {
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        let (q1, q3) = (quantile(0.25), quantile(0.75));
+        let iqr = q3 - q1;
+        let outside = |fence: f64| {
+            samples
+                .iter()
+                .filter(|&&s| s < q1 - fence * iqr || s > q3 + fence * iqr)
+                .count()
+        };
+        let severe_outliers = outside(3.0);
+        let seconds = Duration::from_secs_f64;
+        Stats {
+            samples: n,
+            iterations,
+            mean: seconds(mean),
+            median: seconds(quantile(0.5)),
+            p95: seconds(quantile(0.95)),
+            min: seconds(samples[0]),
+            max: seconds(samples[n - 1]),
+            std_dev: seconds(variance.sqrt()),
+            mild_outliers: outside(1.5) - severe_outliers,
+            severe_outliers,
+        }
+    }
+}
+
+/// The baseline file of `test`, named like its snapshots.
+/// The baseline of `test`, named after its crate as well as its path so that
+/// test targets with benchmarks of the same name keep separate baselines.
+fn baseline_path(test: &TestDef) -> Option<PathBuf> {
+    let dir = cache::path(DIR_ENV, "bench")?;
+    let name = format!("{}::{}", test.module_path, test.name);
+    Some(dir.join(format!("{}.json", name.replace("::", "__"))))
+}
+
+fn read_baseline(path: &PathBuf) -> Option<Duration> {
+    let baseline: Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    Some(Duration::from_nanos(baseline["median_ns"].as_u64()?))
+}
+
+fn write_baseline(path: &PathBuf, stats: &Stats) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{:#}\n", stats_to_json(stats)))
+}
+
+fn stats_to_json(stats: &Stats) -> Value {
+    let nanos = |duration: Duration| duration.as_nanos() as u64;
+    json!({
+        "samples": stats.samples,
+        "iterations": stats.iterations,
+        "mean_ns": nanos(stats.mean),
+        "median_ns": nanos(stats.median),
+        "p95_ns": nanos(stats.p95),
+        "min_ns": nanos(stats.min),
+        "max_ns": nanos(stats.max),
+        "std_dev_ns": nanos(stats.std_dev),
+        "mild_outliers": stats.mild_outliers,
+        "severe_outliers": stats.severe_outliers,
+    })
+}
+
+fn stats_from_json(stats: &Value) -> Option<Stats> {
+    let nanos = |key: &str| stats[key].as_u64().map(Duration::from_nanos);
+    let count = |key: &str| stats[key].as_u64().and_then(|n| usize::try_from(n).ok());
+    Some(Stats {
+        samples: count("samples")?,
+        iterations: stats["iterations"].as_u64()?,
+        mean: nanos("mean_ns")?,
+        median: nanos("median_ns")?,
+        p95: nanos("p95_ns")?,
+        min: nanos("min_ns")?,
+        max: nanos("max_ns")?,
+        std_dev: nanos("std_dev_ns")?,
+        mild_outliers: count("mild_outliers")?,
+        severe_outliers: count("severe_outliers")?,
+    })
+}
+
+/// Converts the results of a child process of `--process-per-test` to JSON,
+/// to hand them to the runner.
+pub(crate) fn to_json(results: &[BenchResult]) -> Value {
+    results
+        .iter()
+        .map(|result| {
+            let mut value = stats_to_json(&result.stats);
+            value["baseline_ns"] = json!(result.baseline.map(|b| b.as_nanos() as u64));
+            value["threshold"] = json!(result.threshold);
+            value["saved"] = json!(result.saved);
+            value
+        })
+        .collect()
+}
+
+/// Records the results a child process running `test` handed over.
+pub(crate) fn record_json(test: &'static TestDef, results: &Value) {
+    for value in results.as_array().into_iter().flatten() {
+        let Some(stats) = stats_from_json(value) else {
+            continue;
+        };
+        record(BenchResult {
+            test,
+            stats,
+            baseline: value["baseline_ns"].as_u64().map(Duration::from_nanos),
+            threshold: value["threshold"].as_f64().unwrap_or(DEFAULT_THRESHOLD),
+            saved: value["saved"].as_bool().unwrap_or(false),
+        });
+    }
+}
+
+fn record(result: BenchResult) {
+    alloc::untracked(|| {
+        RESULTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(result)
+    });
+}
+
+/// Removes the results of `test` from those measured so far, and returns
+/// them.
+pub(crate) fn take_results_of(test: &TestDef) -> Vec<BenchResult> {
+    let mut results = RESULTS.lock().unwrap_or_else(|e| e.into_inner());
+    let (taken, kept) = std::mem::take(&mut *results)
+        .into_iter()
+        .partition(|result| std::ptr::eq(result.test, test));
+    *results = kept;
+    taken
+}
+
+/// Returns the benchmarks measured during the run so far, clearing the list.
+pub fn take_results() -> Vec<BenchResult> {
+    std::mem::take(&mut *RESULTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
@@ -0,0 +1,19 @@
+//! Files the runner keeps between runs, in Cargo's target directory.
+
+use std::path::PathBuf;
+
+/// The cache file `name`: the path in the environment variable `env` if it
+/// is set, else `tust/<name>` in the target directory holding the running
+/// binary, if there is one. `None` if `env` is set but empty.
+pub(crate) fn path(env: &str, name: &str) -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(env) {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let exe = std::env::current_exe().ok()?;
+    // Cargo marks its target directory with a `CACHEDIR.TAG` file.
+    let target = exe
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())?;
+    Some(target.join("tust").join(name))
+}
@@ -0,0 +1,331 @@
+//! Capturing the output of tests run in-process.
+//!
+//! While the runner executes tests, the process's stdout and stderr are
+//! redirected into an unlinked temporary file, so output written by a test,
+//! by threads it spawned, or by child processes it started never interleaves
+//! with the runner's report. Each test receives the bytes written between its
+//! start and its end. File descriptors are shared by the whole process, so a
+//! test running at the same time as others, on another thread or in the
+//! background after timing out, may receive their output too; its captured
+//! output then says so. `--jobs 1` or `--process-per-test` separates the
+//! output of each test exactly.
+//!
+//! The reporters write to the original stdout through [`Stdout`], or to the
+//! JavaScript console on `wasm32-unknown-unknown`, and the notes the runner
+//! prints while tests run go to the original stderr through [`Stderr`].
+//! Capturing is only supported on Unix; elsewhere, output is not captured.
+
+use std::io::{self, Write};
+
+/// Prepended to the output of a test that ran alongside other tests.
+#[cfg_attr(not(unix), allow(dead_code))]
+const OVERLAP_NOTE: &str =
+    "note: other tests ran at the same time, and their output may appear below";
+
+/// Writes to the process's stdout, bypassing the capture while it is active.
+pub(crate) struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match imp::real_stdout() {
+            Some(fd) => imp::write_fd(fd, buf),
+            None => uncaptured().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match imp::real_stdout() {
+            Some(_) => Ok(()),
+            None => uncaptured().flush(),
+        }
+    }
+}
+
+/// Writes to the process's stderr, bypassing the capture while it is active.
+pub(crate) struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match imp::real_stderr() {
+            Some(fd) => imp::write_fd(fd, buf),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match imp::real_stderr() {
+            Some(_) => Ok(()),
+            None => io::stderr().flush(),
+        }
+    }
+}
+
+/// Where [`Stdout`] writes while output is not captured.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn uncaptured() -> io::Stdout {
+    io::stdout()
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn uncaptured() -> crate::wasm::Console {
+    crate::wasm::Console
+}
+
+/// The output of one test, from [`Capture::open`] to [`Capture::close`].
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) struct Window {
+    id: u64,
+    start: u64,
+}
+
+pub(crate) use imp::Capture;
+
+/// Tracks which windows were open at the same time.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Default)]
+struct Windows {
+    next_id: u64,
+    /// Open windows, and whether another window was open at the same time.
+    open: Vec<(u64, bool)>,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+impl Windows {
+    fn open(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let overlapped = !self.open.is_empty();
+        for (_, other) in &mut self.open {
+            *other = true;
+        }
+        self.open.push((id, overlapped));
+        id
+    }
+
+    /// Closes window `id`, returning whether it overlapped another window.
+    fn close(&mut self, id: u64) -> bool {
+        match self.open.iter().position(|&(open, _)| open == id) {
+            Some(index) => self.open.remove(index).1,
+            None => false,
+        }
+    }
+}
+
+/// Trims captured `bytes` as the runner prints them, noting whether they may
+/// hold the output of other tests.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn finish(bytes: &[u8], overlapped: bool) -> String {
+    let output = String::from_utf8_lossy(bytes);
+    let output = output.trim_matches('\n').trim_end();
+    if output.is_empty() || !overlapped {
+        return output.to_string();
+    }
+    format!("{}\n{}", OVERLAP_NOTE, output)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::fs::FileExt;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+
+    use super::{finish, Window, Windows};
+
+    /// The original stdout while output is captured, or -1.
+    static REAL_STDOUT: AtomicI32 = AtomicI32::new(-1);
+
+    /// The original stderr while output is captured, or -1.
+    static REAL_STDERR: AtomicI32 = AtomicI32::new(-1);
+
+    pub(super) fn real_stdout() -> Option<RawFd> {
+        let fd = REAL_STDOUT.load(Ordering::Acquire);
+        (fd >= 0).then_some(fd)
+    }
+
+    pub(super) fn real_stderr() -> Option<RawFd> {
+        let fd = REAL_STDERR.load(Ordering::Acquire);
+        (fd >= 0).then_some(fd)
+    }
+
+    pub(super) fn write_fd(fd: RawFd, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is valid for `buf.len()` bytes, and `fd` stays open
+        // while `REAL_STDOUT` or `REAL_STDERR` holds it.
+        let written = unsafe { libc::write(fd, buf.as_ptr().cast(), buf.len()) };
+        if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(written as usize)
+        }
+    }
+
+    fn dup(fd: RawFd) -> io::Result<OwnedFd> {
+        // SAFETY: `dup` has no memory-safety preconditions; a non-negative
+        // result is a new descriptor that nothing else owns.
+        let new = unsafe { libc::dup(fd) };
+        if new < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { OwnedFd::from_raw_fd(new) })
+    }
+
+    fn dup2(from: RawFd, to: RawFd) -> io::Result<()> {
+        // SAFETY: as for `dup`; `to` is one of the standard descriptors.
+        if unsafe { libc::dup2(from, to) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Redirects stdout and stderr until dropped.
+    pub(crate) struct Capture {
+        file: File,
+        stdout: OwnedFd,
+        stderr: OwnedFd,
+        windows: Mutex<Windows>,
+    }
+
+    impl Capture {
+        /// Starts capturing, or explains why output cannot be captured.
+        pub(crate) fn start() -> io::Result<Self> {
+            let path = std::env::temp_dir().join(format!("tust-output-{}", std::process::id()));
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create_new(true)
+                .open(&path)?;
+            std::fs::remove_file(&path)?;
+
+            let _ = io::stdout().flush();
+            let stdout = dup(libc::STDOUT_FILENO)?;
+            let stderr = dup(libc::STDERR_FILENO)?;
+            dup2(file.as_raw_fd(), libc::STDOUT_FILENO)?;
+            if let Err(err) = dup2(file.as_raw_fd(), libc::STDERR_FILENO) {
+                let _ = dup2(stdout.as_raw_fd(), libc::STDOUT_FILENO);
+                return Err(err);
+            }
+            REAL_STDOUT.store(stdout.as_raw_fd(), Ordering::Release);
+            REAL_STDERR.store(stderr.as_raw_fd(), Ordering::Release);
+
+            Ok(Capture {
+                file,
+                stdout,
+                stderr,
+                windows: Mutex::new(Windows::default()),
+            })
+        }
+
+        fn len(&self) -> u64 {
+            self.file.metadata().map_or(0, |metadata| metadata.len())
+        }
+
+        /// Starts capturing the output of a test.
+        pub(crate) fn open(&self) -> Window {
+            let id = self
+                .windows
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .open();
+            Window {
+                id,
+                start: self.len(),
+            }
+        }
+
+        /// Returns the output written since `window` was opened.
+        pub(crate) fn close(&self, window: Window) -> String {
+            // Push out what the test printed without a trailing newline.
+            let _ = io::stdout().flush();
+            let end = self.len();
+            let overlapped = self
+                .windows
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .close(window.id);
+
+            let mut bytes = vec![0; end.saturating_sub(window.start) as usize];
+            match self.file.read_exact_at(&mut bytes, window.start) {
+                Ok(()) => finish(&bytes, overlapped),
+                Err(_) => String::new(),
+            }
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            let _ = io::stdout().flush();
+            REAL_STDOUT.store(-1, Ordering::Release);
+            REAL_STDERR.store(-1, Ordering::Release);
+            let _ = dup2(self.stdout.as_raw_fd(), libc::STDOUT_FILENO);
+            let _ = dup2(self.stderr.as_raw_fd(), libc::STDERR_FILENO);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+
+    use super::Window;
+
+    pub(super) fn real_stdout() -> Option<i32> {
+        None
+    }
+
+    pub(super) fn real_stderr() -> Option<i32> {
+        None
+    }
+
+    pub(super) fn write_fd(_fd: i32, _buf: &[u8]) -> io::Result<usize> {
+        unreachable!("output is never captured on this platform")
+    }
+
+    /// Output capture is not supported on this platform.
+    pub(crate) struct Capture;
+
+    impl Capture {
+        pub(crate) fn start() -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "capturing output is only supported on Unix",
+            ))
+        }
+
+        pub(crate) fn open(&self) -> Window {
+            Window { id: 0, start: 0 }
+        }
+
+        pub(crate) fn close(&self, _window: Window) -> String {
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_overlap_only_if_open_at_the_same_time() {
+        let mut windows = Windows::default();
+        let first = windows.open();
+        assert!(!windows.close(first));
+
+        let first = windows.open();
+        let second = windows.open();
+        assert!(windows.close(first));
+        let third = windows.open();
+        assert!(windows.close(second));
+        assert!(windows.close(third));
+        assert!(!windows.close(third));
+    }
+
+    #[test]
+    fn finish_trims_and_notes_overlaps() {
+        assert_eq!(finish(b"\n\nhello\n  \n", false), "hello");
+        assert_eq!(finish(b"\n", true), "");
+        assert_eq!(finish(b"hello\n", true), format!("{}\nhello", OVERLAP_NOTE));
+    }
+}
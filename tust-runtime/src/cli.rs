@@ -0,0 +1,1012 @@
+//! Command-line arguments accepted by a tust test binary.
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use regex::Regex;
+
+use crate::config::Config;
+use crate::filter::NamePattern;
+use crate::history;
+use crate::quarantine::Quarantine;
+use crate::shard::Shard;
+use crate::strict::Checks;
+use crate::tags::TagExpr;
+
+/// Usage text printed by `--help` and on argument errors.
+pub const USAGE: &str = "\
+Usage: <test-binary> [OPTIONS] [FILTERS...]
+
+Runs every registered tust test whose name contains one of FILTERS or
+matches one of the --filter and --filter-regex patterns.
+
+Options:
+    --exact             Match FILTERS against the full test name exactly
+    --filter GLOB       Run tests whose full name matches GLOB, in which *
+                        matches within one path segment, ** across segments,
+                        and ? one character, such as db::*::insert_*
+                        (repeatable)
+    --filter-regex REGEX
+                        Run tests whose name matches the regular expression
+                        REGEX anywhere (repeatable)
+    --list              Print the selected tests with their locations, tags,
+                        and attributes instead of running them; with
+                        --format terse, only their names, like libtest, and
+                        with --format json, one JSON object per test
+    --schedule          Print the selected tests in the order they would
+                        start in, with their priorities and the durations
+                        recorded in earlier runs, instead of running them
+    --dry-run           Print the selected tests, check that the fixtures
+                        they ask for resolve and that every filter and tag
+                        matches a test, and exit without running them
+    --skip PATTERN      Skip tests whose name contains PATTERN (repeatable)
+    --tag EXPR          Run only tests whose tags match EXPR (repeatable);
+                        EXPR combines tags with !, &&, ||, and parentheses,
+                        such as \"db && !slow\"
+    --skip-tag EXPR     Skip tests whose tags match EXPR (repeatable)
+    --file PATH         Run only tests defined in the source file PATH
+                        (repeatable)
+    --changed-since REF
+                        Run only the tests that executed a file changed since
+                        the Git revision REF, as recorded by
+                        --record-coverage, and the tests never recorded
+    --last-failed       Run only the tests that failed in their last run, or
+                        every test if none did (alias: --lf)
+    --failed-first      Run the tests that failed in their last run first
+                        (alias: --ff)
+    --cached            Skip the tests that passed in an earlier --cached run
+                        with the same options and either the same source
+                        files executed, as recorded by --record-coverage, or
+                        the same test binary
+    --shard K/N         Run only the K-th of N disjoint parts of the selected
+                        tests, to split a run across machines
+    --shard-timings FILE
+                        Balance shards by the durations in FILE, written by
+                        --report timings=FILE, instead of by test name
+    -j, --jobs N        Run up to N tests at once; defaults to
+                        RUST_TEST_THREADS or the number of CPUs
+                        (alias: --test-threads)
+    --color WHEN        Colorize output: auto, always, or never
+    --timeout DURATION  Fail tests running longer than DURATION, such as 500ms,
+                        30s, or 2m (a bare number is milliseconds); overridden
+                        by a test's #[timeout]
+    --retries N         Re-run failed tests up to N more times; a test that
+                        then passes is reported as flaky; overridden by a
+                        test's #[retry]
+    --fail-fast         Stop starting tests after the first failure
+    --max-failures N    Stop starting tests after N failures; tests already
+                        running finish, and the rest are reported as not run
+    --grace-period DURATION
+                        On Ctrl-C, wait up to DURATION for the running tests
+                        to finish before abandoning them (default 10s)
+    --export-fuzz DIR   Write each #[tust::fuzz] target as a cargo-fuzz target
+                        in DIR/fuzz_targets, such as fuzz, instead of running
+                        tests
+    --shuffle[=SEED]    Run tests in a random order, to find tests depending
+                        on others; a failed run prints the SEED replaying
+                        its order
+    --seed N            Seed the random values tests ask for, to reproduce a
+                        run that printed seed N; defaults to TUST_SEED or a
+                        random seed
+    --bench             Measure #[tust::bench] benchmarks instead of running
+                        each once, and compare them against their baselines;
+                        cargo bench passes this
+    --leak-check        Fail tests that leave heap memory allocated after
+                        their teardown; needs tust::alloc::TrackingAllocator
+                        as the global allocator, and #[allow_leaks] exempts
+                        a test
+    --isolated-cwd      Run each test in a fresh working directory of its
+                        own, as #[tust::test(isolated_cwd)] does
+    --strict            Flag passed tests that leave threads, Tokio tasks,
+                        or temporary files behind, or that are slow; the
+                        [strict-checks] table of tust.toml makes each check
+                        warn, fail the test, or do nothing
+    --record-coverage   Record the source files each test executes, for
+                        --changed-since; runs each test in its own process,
+                        and needs a binary built with -C instrument-coverage
+    --mutation-hook     Instead of running tests, answer the requests of a
+                        mutation testing tool, one JSON object per line on
+                        stdin, for the recorded coverage of the tests or for
+                        runs of the tests covering given files
+    --process-per-test  Run each test in its own process, so crashes such as
+                        segfaults and aborts fail only that test
+    --nocapture         Let tests print directly instead of capturing their
+                        output (alias: --no-capture)
+    --show-output       Also print the captured output of passed tests
+    --format FORMAT     Output format: tree (the default: tests grouped by
+                        module, with a progress bar on terminals), pretty
+                        (one line per test, like libtest), terse, json
+                        (newline-delimited events in the style of
+                        libtest's), tap (Test Anything Protocol version
+                        14), or plain (pretty's lines in the order of the
+                        tests, without colors or durations, for comparing
+                        runs' output)
+    --report FORMAT=PATH
+                        Also write a report to PATH (repeatable); FORMAT is
+                        junit (an XML file), html (a directory), timings
+                        (test durations for --shard-timings), or github
+                        (annotations of failures on stdout, and a Markdown
+                        summary appended to PATH, such as
+                        $GITHUB_STEP_SUMMARY; added by default on GitHub
+                        Actions)
+    --slowest N         List the N slowest tests after the run; defaults to
+                        10, and 0 lists none
+    --trends[=N]        List the tests that started failing, became slow, or
+                        keep changing their result, against their last N
+                        recorded runs (20 unless given); with the tree and
+                        pretty formats
+    --output-lines N    Show the last N lines of a failed test's output in
+                        the failure summary; defaults to 20, and 0 shows all
+    --snapshot-dir DIR  Keep snapshots in DIR, relative to the manifest
+                        directory, instead of tests/snapshots
+    --profile NAME      Use the [profile.NAME] section of tust.toml; defaults
+                        to TUST_PROFILE
+    -q, --quiet         Print one character per test instead of one line
+    -h, --help          Print this help and exit
+
+Defaults for these options are read from a tust.toml file in the crate's
+directory or one above it, or from the file named by TUST_CONFIG, and from
+the environment variables RUST_TEST_THREADS, TUST_TIMEOUT, TUST_RETRIES, and
+TUST_SNAPSHOT_DIR, which override the file.";
+
+/// When to colorize console output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colorize when stdout is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Formats of the output printed to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Tests grouped by module, each module printed once all of its tests
+    /// have finished, with a progress bar below them on terminals; one
+    /// character per test with [`Args::quiet`].
+    #[default]
+    Tree,
+    /// One line per test, like libtest; one character per test with
+    /// [`Args::quiet`].
+    Pretty,
+    /// Newline-delimited JSON events, in the style of libtest's
+    /// `--format json`.
+    Json,
+    /// Test Anything Protocol version 14.
+    Tap,
+    /// The lines of [`Pretty`](OutputFormat::Pretty), printed once the run
+    /// ends, sorted by test, without colors, and with every duration shown
+    /// as zero, so that the same results print the same output.
+    Plain,
+}
+
+/// File formats accepted by `--report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// JUnit XML, as read by Jenkins, GitLab, and most CI systems.
+    Junit,
+    /// A static HTML page, written with its run history to a directory.
+    Html,
+    /// The duration of each test, as read by `--shard-timings`.
+    Timings,
+    /// GitHub Actions annotations on stdout, and a Markdown summary appended
+    /// to the path, such as the file named by `GITHUB_STEP_SUMMARY`.
+    Github,
+}
+
+/// A report requested with `--report FORMAT=PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+/// Parsed command-line arguments.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    /// Positional name filters; a test runs if it matches any of them.
+    pub filters: Vec<String>,
+    /// Require filters to match the full test name.
+    pub exact: bool,
+    /// Patterns given with `--filter` and `--filter-regex`; a test also runs
+    /// if it matches any of them. See [`crate::filter`].
+    pub patterns: Vec<NamePattern>,
+    /// Substrings of test names to exclude.
+    pub skip: Vec<String>,
+    /// Tag expressions; if any are given, a test runs only if it matches one.
+    pub tags: Vec<TagExpr>,
+    /// Tag expressions excluding the tests that match any of them.
+    pub skip_tags: Vec<TagExpr>,
+    /// Source files; if any are given, a test runs only if it is defined in
+    /// one of them.
+    pub files: Vec<PathBuf>,
+    /// Run only the tests covering files changed since this Git revision;
+    /// see [`crate::coverage`].
+    pub changed_since: Option<String>,
+    /// Run only the tests that failed in their last run, if any did; see
+    /// [`crate::failures`].
+    pub last_failed: bool,
+    /// Run the tests that failed in their last run first.
+    pub failed_first: bool,
+    /// Skip the tests that passed before with the same inputs; see
+    /// [`crate::passes`].
+    pub cached: bool,
+    /// Run only this part of the selected tests.
+    pub shard: Option<Shard>,
+    /// Timings to balance shards by, instead of test names.
+    pub shard_timings: Option<PathBuf>,
+    /// Maximum number of tests run at once; see [`Args::jobs`].
+    pub jobs: Option<usize>,
+    /// Time limit for tests without a `#[timeout]` of their own.
+    pub timeout: Option<Duration>,
+    /// How many times to re-run a failed test without a `#[retry]` of its
+    /// own.
+    pub retries: u32,
+    /// Stop starting tests once this many have failed; 1 with `--fail-fast`.
+    pub max_failures: Option<usize>,
+    /// How long the running tests are given to finish once the run is
+    /// interrupted; see [`crate::interrupt`].
+    pub grace_period: Option<Duration>,
+    /// Run the tests in a random order.
+    pub shuffle: bool,
+    /// Seed of the order given with `--shuffle=SEED`; random otherwise.
+    pub shuffle_seed: Option<u64>,
+    /// Seed of the run's randomness; see [`crate::rng`].
+    pub seed: Option<u64>,
+    /// Measure benchmarks; see [`crate::bench`].
+    pub bench: bool,
+    /// Fail tests that leak memory; see [`crate::alloc`].
+    pub leak_check: bool,
+    /// Run each test in a working directory of its own; see
+    /// [`crate::sandbox`].
+    pub isolated_cwd: bool,
+    /// Flag tests that leave a mess behind; see [`crate::strict`].
+    pub strict: bool,
+    /// The checks of `strict`.
+    pub strict_checks: Checks,
+    /// Tests whose failures do not fail the run, as set in `tust.toml`; see
+    /// [`crate::quarantine`].
+    pub quarantine: Quarantine,
+    /// Record the files each test covers; see [`crate::coverage`].
+    pub record_coverage: bool,
+    /// Answer mutation testing tools instead of running tests; see
+    /// [`crate::mutation`].
+    pub mutation_hook: bool,
+    /// Run each test in a subprocess of its own.
+    pub process_per_test: bool,
+    /// Let tests print to the terminal instead of capturing their output.
+    pub nocapture: bool,
+    /// Report the captured output of tests that did not fail, too.
+    pub show_output: bool,
+    pub color: ColorChoice,
+    pub format: OutputFormat,
+    /// Reports written in addition to the console output.
+    pub reports: Vec<ReportTarget>,
+    /// How many of the slowest tests to list; see [`Args::slowest`].
+    pub slowest: Option<usize>,
+    /// How many recorded runs of each test to find trends in after the run,
+    /// with `--trends`; see [`crate::history`].
+    pub trends: Option<usize>,
+    /// How many lines of a failed test's output to show; see
+    /// [`Args::output_lines`].
+    pub output_lines: Option<usize>,
+    /// Directory holding snapshots, relative to the manifest directory of
+    /// the crate being tested; see [`crate::snapshot`].
+    pub snapshot_dir: Option<PathBuf>,
+    /// The `tust.toml` profile the defaults were read from; see
+    /// [`crate::config`].
+    pub profile: Option<String>,
+    pub quiet: bool,
+    /// List the selected tests instead of running them.
+    pub list: bool,
+    /// Print the order the selected tests would start in instead of running
+    /// them.
+    pub schedule: bool,
+    /// Check the selected tests and the fixtures they ask for instead of
+    /// running them.
+    pub dry_run: bool,
+    /// Write the fuzz targets to this cargo-fuzz directory instead of running
+    /// tests; see [`crate::fuzz::export`].
+    pub export_fuzz: Option<PathBuf>,
+    pub help: bool,
+}
+
+/// A command line that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgsError(String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+impl Args {
+    /// Parses the arguments of the current process, skipping the binary
+    /// name. Options not given default to the environment variables listed
+    /// in [`USAGE`], and then to the `tust.toml` file of the run, if any.
+    pub fn from_env() -> Result<Self, ArgsError> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let profile = Self::parse(args.iter().cloned())?.profile;
+        let mut defaults = Args::default();
+        match Config::discover(profile.as_deref()) {
+            Ok(Some(config)) => config.apply(&mut defaults),
+            Ok(None) => {}
+            Err(err) => return Err(ArgsError(err.to_string())),
+        }
+        defaults.apply_env()?;
+        Self::parse_onto(defaults, args)
+    }
+
+    /// Parses a list of arguments, not including the binary name.
+    pub fn parse<I, S>(args: I) -> Result<Self, ArgsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::parse_onto(Args::default(), args)
+    }
+
+    /// Sets the options that the environment variables listed in [`USAGE`]
+    /// give.
+    fn apply_env(&mut self) -> Result<(), ArgsError> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        if let Some(jobs) = var("RUST_TEST_THREADS").as_deref().and_then(parse_jobs) {
+            self.jobs = Some(jobs);
+        }
+        if let Some(timeout) = var("TUST_TIMEOUT") {
+            self.timeout = Some(parse_duration(&timeout).ok_or_else(|| {
+                ArgsError(format!(
+                    "invalid TUST_TIMEOUT value `{}`; expected a duration such as 500ms, 30s, or 2m",
+                    timeout
+                ))
+            })?);
+        }
+        if let Some(retries) = var("TUST_RETRIES") {
+            self.retries = retries.parse().map_err(|_| {
+                ArgsError(format!(
+                    "invalid TUST_RETRIES value `{}`; expected a number",
+                    retries
+                ))
+            })?;
+        }
+        if let Some(dir) = std::env::var_os(crate::snapshot::DIR_ENV).filter(|dir| !dir.is_empty())
+        {
+            self.snapshot_dir = Some(PathBuf::from(dir));
+        }
+        Ok(())
+    }
+
+    /// Parses `args` over `defaults`. Lists given on the command line, such
+    /// as `--tag`, replace those of `defaults` rather than extend them.
+    fn parse_onto<I, S>(defaults: Args, args: I) -> Result<Self, ArgsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = defaults;
+        let default_tags = std::mem::take(&mut parsed.tags);
+        let default_skip_tags = std::mem::take(&mut parsed.skip_tags);
+        let default_reports = std::mem::take(&mut parsed.reports);
+        // `quiet` from `defaults` goes with their format, which `--format`
+        // replaces.
+        let default_quiet = std::mem::take(&mut parsed.quiet);
+        let mut format_given = false;
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| match &inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .ok_or_else(|| ArgsError(format!("`{}` requires a value", name))),
+            };
+
+            match flag.as_str() {
+                "--exact" => parsed.exact = true,
+                "--filter" => {
+                    let glob = value("--filter")?;
+                    let glob = glob.parse().map_err(|err| {
+                        ArgsError(format!("invalid `--filter` glob `{}`: {}", glob, err))
+                    })?;
+                    parsed.patterns.push(NamePattern::Glob(glob));
+                }
+                "--filter-regex" => {
+                    let regex = value("--filter-regex")?;
+                    let regex = Regex::new(&regex).map_err(|err| {
+                        ArgsError(format!(
+                            "invalid `--filter-regex` expression `{}`: {}",
+                            regex, err
+                        ))
+                    })?;
+                    parsed.patterns.push(NamePattern::Regex(regex));
+                }
+                "--list" => parsed.list = true,
+                "--schedule" => parsed.schedule = true,
+                "--dry-run" => parsed.dry_run = true,
+                "--skip" => parsed.skip.push(value("--skip")?),
+                "--tag" | "--skip-tag" => {
+                    let expr = value(&flag)?;
+                    let expr = expr.parse().map_err(|err| {
+                        ArgsError(format!("invalid `{}` expression `{}`: {}", flag, expr, err))
+                    })?;
+                    if flag == "--tag" {
+                        parsed.tags.push(expr);
+                    } else {
+                        parsed.skip_tags.push(expr);
+                    }
+                }
+                "--file" => parsed.files.push(PathBuf::from(value("--file")?)),
+                "--changed-since" => parsed.changed_since = Some(value("--changed-since")?),
+                "--last-failed" | "--lf" => parsed.last_failed = true,
+                "--failed-first" | "--ff" => parsed.failed_first = true,
+                "--cached" => parsed.cached = true,
+                "--shard" => {
+                    let shard = value("--shard")?;
+                    parsed.shard = Some(parse_shard(&shard).ok_or_else(|| {
+                        ArgsError(format!(
+                            "invalid `--shard` value `{}`; expected K/N with 1 <= K <= N",
+                            shard
+                        ))
+                    })?);
+                }
+                "--shard-timings" => {
+                    parsed.shard_timings = Some(PathBuf::from(value("--shard-timings")?))
+                }
+                "-j" | "--jobs" | "--test-threads" => {
+                    let jobs = value(&flag)?;
+                    parsed.jobs = Some(parse_jobs(&jobs).ok_or_else(|| {
+                        ArgsError(format!(
+                            "invalid `{}` value `{}`; expected a positive number",
+                            flag, jobs
+                        ))
+                    })?);
+                }
+                "--color" => {
+                    parsed.color = match value("--color")?.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        other => {
+                            return Err(ArgsError(format!(
+                                "invalid `--color` value `{}`; expected auto, always, or never",
+                                other
+                            )))
+                        }
+                    }
+                }
+                "--format" => {
+                    format_given = true;
+                    match value("--format")?.as_str() {
+                        "tree" => parsed.format = OutputFormat::Tree,
+                        "pretty" => parsed.format = OutputFormat::Pretty,
+                        "terse" => {
+                            parsed.format = OutputFormat::Pretty;
+                            parsed.quiet = true;
+                        }
+                        "json" => parsed.format = OutputFormat::Json,
+                        "tap" => parsed.format = OutputFormat::Tap,
+                        "plain" => parsed.format = OutputFormat::Plain,
+                        other => {
+                            return Err(ArgsError(format!(
+                            "invalid `--format` value `{}`; expected tree, pretty, terse, json, tap, or plain",
+                            other
+                        )))
+                        }
+                    }
+                }
+                "--timeout" => {
+                    let timeout = value("--timeout")?;
+                    parsed.timeout = Some(parse_duration(&timeout).ok_or_else(|| {
+                        ArgsError(format!(
+                            "invalid `--timeout` value `{}`; expected a duration such as 500ms, 30s, or 2m",
+                            timeout
+                        ))
+                    })?);
+                }
+                "--retries" => {
+                    let retries = value("--retries")?;
+                    parsed.retries = retries.parse().map_err(|_| {
+                        ArgsError(format!(
+                            "invalid `--retries` value `{}`; expected a number",
+                            retries
+                        ))
+                    })?;
+                }
+                "--fail-fast" => parsed.max_failures = Some(1),
+                "--max-failures" => {
+                    let max = value("--max-failures")?;
+                    parsed.max_failures =
+                        Some(max.parse().ok().filter(|&max| max > 0).ok_or_else(|| {
+                            ArgsError(format!(
+                                "invalid `--max-failures` value `{}`; expected a positive number",
+                                max
+                            ))
+                        })?);
+                }
+                "--grace-period" => {
+                    let grace = value("--grace-period")?;
+                    parsed.grace_period = Some(parse_duration(&grace).ok_or_else(|| {
+                        ArgsError(format!(
+                            "invalid `--grace-period` value `{}`; expected a duration such as 500ms, 30s, or 2m",
+                            grace
+                        ))
+                    })?);
+                }
+                "--shuffle" => {
+                    parsed.shuffle = true;
+                    if let Some(seed) = inline {
+                        parsed.shuffle_seed = Some(seed.parse().map_err(|_| {
+                            ArgsError(format!(
+                                "invalid `--shuffle` seed `{}`; expected a number",
+                                seed
+                            ))
+                        })?);
+                    }
+                }
+                "--seed" => {
+                    let seed = value("--seed")?;
+                    parsed.seed = Some(seed.parse().map_err(|_| {
+                        ArgsError(format!(
+                            "invalid `--seed` value `{}`; expected a number",
+                            seed
+                        ))
+                    })?);
+                }
+                "--bench" => parsed.bench = true,
+                "--leak-check" => parsed.leak_check = true,
+                "--isolated-cwd" => parsed.isolated_cwd = true,
+                "--strict" => parsed.strict = true,
+                "--record-coverage" => parsed.record_coverage = true,
+                "--mutation-hook" => parsed.mutation_hook = true,
+                "--process-per-test" => parsed.process_per_test = true,
+                "--nocapture" | "--no-capture" => parsed.nocapture = true,
+                "--show-output" => parsed.show_output = true,
+                "--report" => parsed.reports.push(parse_report(&value("--report")?)?),
+                "--slowest" => {
+                    let slowest = value("--slowest")?;
+                    parsed.slowest = Some(slowest.parse().map_err(|_| {
+                        ArgsError(format!(
+                            "invalid `--slowest` value `{}`; expected a number",
+                            slowest
+                        ))
+                    })?);
+                }
+                "--trends" => {
+                    parsed.trends = Some(match inline {
+                        Some(runs) => {
+                            runs.parse().ok().filter(|&runs| runs > 1).ok_or_else(|| {
+                                ArgsError(format!(
+                                "invalid `--trends` value `{}`; expected a number of runs above 1",
+                                runs
+                            ))
+                            })?
+                        }
+                        None => history::DEFAULT_TREND_RUNS,
+                    });
+                }
+                "--output-lines" => {
+                    let lines = value("--output-lines")?;
+                    parsed.output_lines = Some(lines.parse().map_err(|_| {
+                        ArgsError(format!(
+                            "invalid `--output-lines` value `{}`; expected a number",
+                            lines
+                        ))
+                    })?);
+                }
+                "--export-fuzz" => {
+                    parsed.export_fuzz = Some(PathBuf::from(value("--export-fuzz")?))
+                }
+                "--snapshot-dir" => {
+                    parsed.snapshot_dir = Some(PathBuf::from(value("--snapshot-dir")?))
+                }
+                "--profile" => parsed.profile = Some(value("--profile")?),
+                "-q" | "--quiet" => parsed.quiet = true,
+                "-h" | "--help" => parsed.help = true,
+                _ if flag.starts_with('-') => {
+                    return Err(ArgsError(format!("unrecognized option `{}`", flag)))
+                }
+                _ => parsed.filters.push(arg),
+            }
+        }
+
+        if !format_given {
+            parsed.quiet |= default_quiet;
+        }
+        if parsed.tags.is_empty() {
+            parsed.tags = default_tags;
+        }
+        if parsed.skip_tags.is_empty() {
+            parsed.skip_tags = default_skip_tags;
+        }
+        if parsed.reports.is_empty() {
+            parsed.reports = default_reports;
+        }
+        if parsed.shard_timings.is_some() && parsed.shard.is_none() {
+            return Err(ArgsError(
+                "`--shard-timings` requires `--shard`".to_string(),
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// Number of tests to run at once: `--jobs`, else the `RUST_TEST_THREADS`
+    /// environment variable, else the available parallelism.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| {
+                std::env::var("RUST_TEST_THREADS")
+                    .ok()
+                    .as_deref()
+                    .and_then(parse_jobs)
+            })
+            .or_else(|| std::thread::available_parallelism().ok().map(Into::into))
+            .unwrap_or(1)
+    }
+
+    /// Whether each test runs in a process of its own: with
+    /// `--process-per-test`, or `--record-coverage`, as coverage is recorded
+    /// per process. Otherwise tests run on threads of the runner's process,
+    /// sharing its session fixtures and hooks.
+    pub fn runs_in_processes(&self) -> bool {
+        self.process_per_test || self.record_coverage
+    }
+
+    /// Number of slowest tests listed after the run: `--slowest`, else 10.
+    pub fn slowest(&self) -> usize {
+        self.slowest.unwrap_or(10)
+    }
+
+    /// Number of trailing lines of a failed test's output shown in the
+    /// failure summary: `--output-lines`, else 20; 0 shows every line.
+    pub fn output_lines(&self) -> usize {
+        self.output_lines.unwrap_or(20)
+    }
+
+    /// Whether a test with the given full name is selected by the filters.
+    pub fn is_selected(&self, name: &str) -> bool {
+        let matches = |pattern: &str| {
+            if self.exact {
+                name == pattern
+            } else {
+                name.contains(pattern)
+            }
+        };
+
+        let included = (self.filters.is_empty() && self.patterns.is_empty())
+            || self.filters.iter().any(|f| matches(f))
+            || self.patterns.iter().any(|p| p.matches(name));
+        included && !self.skip.iter().any(|s| matches(s))
+    }
+
+    /// Whether a test defined in `file`, as given by `file!()`, is selected
+    /// by `--file`. Paths match if one is a suffix of the other, so that
+    /// relative paths match from any directory of the project.
+    pub fn is_selected_by_file(&self, file: &str) -> bool {
+        self.files.is_empty() || self.files.iter().any(|selected| same_file(selected, file))
+    }
+
+    /// Whether a test carrying `tags` is selected by `--tag` and
+    /// `--skip-tag`.
+    pub fn is_selected_by_tags(&self, tags: &[&str]) -> bool {
+        let included = self.tags.is_empty() || self.tags.iter().any(|e| e.matches(tags));
+        included && !self.skip_tags.iter().any(|e| e.matches(tags))
+    }
+}
+
+/// Whether `selected`, given to `--file`, names `file`, as given by
+/// `file!()`; see [`Args::is_selected_by_file`].
+pub(crate) fn same_file(selected: &Path, file: &str) -> bool {
+    let file = components(Path::new(file));
+    let selected = components(selected);
+    let len = selected.len().min(file.len());
+    len > 0 && selected[selected.len() - len..] == file[file.len() - len..]
+}
+
+fn components(path: &Path) -> Vec<Component<'_>> {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+pub(crate) fn parse_jobs(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|&jobs| jobs > 0)
+}
+
+fn parse_shard(value: &str) -> Option<Shard> {
+    let (index, count) = value.split_once('/')?;
+    Shard::new(index.parse().ok()?, count.parse().ok()?)
+}
+
+/// Parses `500ms`, `30s`, `2m`, or a bare number of milliseconds. Zero is
+/// rejected.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "ms"),
+    };
+    let number: u64 = number.parse().ok()?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.checked_mul(60)?),
+        _ => return None,
+    };
+    Some(duration).filter(|duration| !duration.is_zero())
+}
+
+pub(crate) fn parse_report(value: &str) -> Result<ReportTarget, ArgsError> {
+    let (format, path) = value.split_once('=').ok_or_else(|| {
+        ArgsError(format!(
+            "invalid `--report` value `{}`; expected FORMAT=PATH",
+            value
+        ))
+    })?;
+    let format = match format {
+        "junit" => ReportFormat::Junit,
+        "html" => ReportFormat::Html,
+        "timings" => ReportFormat::Timings,
+        "github" => ReportFormat::Github,
+        other => {
+            return Err(ArgsError(format!(
+                "unknown report format `{}`; expected junit, html, timings, or github",
+                other
+            )))
+        }
+    };
+    if path.is_empty() {
+        return Err(ArgsError(
+            "`--report` requires a path after `=`".to_string(),
+        ));
+    }
+    Ok(ReportTarget {
+        format,
+        path: PathBuf::from(path),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::parse(args.iter().copied()).unwrap()
+    }
+
+    fn error(args: &[&str]) -> String {
+        Args::parse(args.iter().copied()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn positional_arguments_are_filters() {
+        let args = parse(&["db", "--exact", "parser::empty"]);
+        assert_eq!(args.filters, ["db", "parser::empty"]);
+        assert!(args.exact);
+        assert!(parse(&[]).filters.is_empty());
+    }
+
+    #[test]
+    fn values_follow_options_or_an_equals_sign() {
+        let args = parse(&[
+            "--skip",
+            "slow",
+            "--skip=flaky",
+            "-j",
+            "4",
+            "--color=always",
+        ]);
+        assert_eq!(args.skip, ["slow", "flaky"]);
+        assert_eq!(args.jobs, Some(4));
+        assert_eq!(args.color, ColorChoice::Always);
+        assert_eq!(parse(&["--test-threads=2"]).jobs, Some(2));
+        // Only options take their value after `=`.
+        assert_eq!(parse(&["a=b"]).filters, ["a=b"]);
+    }
+
+    #[test]
+    fn flags_and_their_aliases() {
+        let args = parse(&["--list", "--nocapture", "--show-output", "-q", "-h"]);
+        assert!(args.list && args.nocapture && args.show_output && args.quiet && args.help);
+        assert!(parse(&["--no-capture"]).nocapture);
+        assert!(parse(&["--quiet"]).quiet);
+        assert!(parse(&["--help"]).help);
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        assert_eq!(error(&["--bogus"]), "unrecognized option `--bogus`");
+        assert_eq!(error(&["-x"]), "unrecognized option `-x`");
+        assert_eq!(error(&["--skip"]), "`--skip` requires a value");
+        assert_eq!(
+            error(&["--jobs", "0"]),
+            "invalid `--jobs` value `0`; expected a positive number"
+        );
+        assert_eq!(
+            error(&["--color", "sometimes"]),
+            "invalid `--color` value `sometimes`; expected auto, always, or never"
+        );
+    }
+
+    #[test]
+    fn jobs_default_to_the_available_parallelism() {
+        assert_eq!(parse(&["-j", "3"]).jobs(), 3);
+        assert_eq!(parse(&["--test-threads", "1"]).jobs(), 1);
+        assert!(parse(&[]).jobs() >= 1);
+        assert_eq!(
+            error(&["--test-threads", "many"]),
+            "invalid `--test-threads` value `many`; expected a positive number"
+        );
+    }
+
+    #[test]
+    fn tests_run_in_processes_only_when_asked() {
+        assert!(parse(&["--process-per-test", "-j", "1"]).runs_in_processes());
+        assert!(parse(&["--record-coverage"]).runs_in_processes());
+        assert!(!parse(&["-j", "1"]).runs_in_processes());
+        assert!(!parse(&["-j", "4"]).runs_in_processes());
+        assert!(!parse(&["-j", "4", "--nocapture"]).runs_in_processes());
+    }
+
+    #[test]
+    fn durations_default_to_milliseconds() {
+        assert_eq!(parse_duration("250"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        for invalid in ["0s", "", "s", "1.5s", "1h", "-1s", "1 s"] {
+            assert_eq!(parse_duration(invalid), None, "{:?}", invalid);
+        }
+        assert_eq!(
+            parse(&["--timeout", "5s"]).timeout,
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            error(&["--timeout", "soon"]),
+            "invalid `--timeout` value `soon`; expected a duration such as 500ms, 30s, or 2m"
+        );
+    }
+
+    #[test]
+    fn retries() {
+        assert_eq!(parse(&[]).retries, 0);
+        assert_eq!(parse(&["--retries", "2"]).retries, 2);
+        assert_eq!(
+            error(&["--retries", "-1"]),
+            "invalid `--retries` value `-1`; expected a number"
+        );
+    }
+
+    #[test]
+    fn shuffle_takes_its_seed_after_an_equals_sign() {
+        let args = parse(&["--shuffle"]);
+        assert!(args.shuffle);
+        assert_eq!(args.shuffle_seed, None);
+        let args = parse(&["--shuffle=42"]);
+        assert!(args.shuffle);
+        assert_eq!(args.shuffle_seed, Some(42));
+        assert_eq!(parse(&["--shuffle", "42"]).filters, ["42"]);
+        assert!(!parse(&[]).shuffle);
+        assert_eq!(
+            error(&["--shuffle=next"]),
+            "invalid `--shuffle` seed `next`; expected a number"
+        );
+    }
+
+    #[test]
+    fn shards_count_from_one() {
+        assert_eq!(parse(&["--shard", "2/3"]).shard, Shard::new(2, 3));
+        for shard in ["0/3", "4/3", "1/0", "1", "a/b", "1/2/3"] {
+            assert_eq!(
+                error(&["--shard", shard]),
+                format!(
+                    "invalid `--shard` value `{}`; expected K/N with 1 <= K <= N",
+                    shard
+                )
+            );
+        }
+        let args = parse(&["--shard=1/2", "--shard-timings", "timings.json"]);
+        assert_eq!(args.shard_timings, Some(PathBuf::from("timings.json")));
+        assert_eq!(
+            error(&["--shard-timings", "timings.json"]),
+            "`--shard-timings` requires `--shard`"
+        );
+    }
+
+    #[test]
+    fn slowest_tests_listed() {
+        assert_eq!(parse(&[]).slowest(), 10);
+        assert_eq!(parse(&["--slowest", "0"]).slowest(), 0);
+        assert_eq!(parse(&["--slowest=3"]).slowest(), 3);
+        assert_eq!(
+            error(&["--slowest", "all"]),
+            "invalid `--slowest` value `all`; expected a number"
+        );
+    }
+
+    #[test]
+    fn fail_fast_stops_after_one_failure() {
+        assert_eq!(parse(&[]).max_failures, None);
+        assert_eq!(parse(&["--fail-fast"]).max_failures, Some(1));
+        assert_eq!(parse(&["--max-failures", "5"]).max_failures, Some(5));
+        assert_eq!(
+            error(&["--max-failures", "0"]),
+            "invalid `--max-failures` value `0`; expected a positive number"
+        );
+    }
+
+    #[test]
+    fn last_failed_and_failed_first_aliases() {
+        assert!(parse(&["--last-failed"]).last_failed);
+        assert!(parse(&["--lf"]).last_failed);
+        assert!(parse(&["--failed-first"]).failed_first);
+        assert!(parse(&["--ff"]).failed_first);
+        let args = parse(&[]);
+        assert!(!args.last_failed && !args.failed_first);
+    }
+
+    #[test]
+    fn selects_tests_by_name() {
+        let all = parse(&[]);
+        assert!(all.is_selected("db::insert"));
+        let args = parse(&["db", "parser", "--skip", "slow"]);
+        assert!(args.is_selected("db::insert"));
+        assert!(args.is_selected("parser::empty"));
+        assert!(!args.is_selected("db::insert_slow"));
+        assert!(!args.is_selected("http::get"));
+        let exact = parse(&["--exact", "db", "db::insert"]);
+        assert!(exact.is_selected("db::insert"));
+        assert!(!exact.is_selected("db::insert_many"));
+    }
+
+    #[test]
+    fn reports_are_requested_by_format_and_path() {
+        let args = parse(&["--report", "junit=target/junit.xml", "--report=junit=b.xml"]);
+        assert_eq!(
+            args.reports,
+            [
+                ReportTarget {
+                    format: ReportFormat::Junit,
+                    path: PathBuf::from("target/junit.xml"),
+                },
+                ReportTarget {
+                    format: ReportFormat::Junit,
+                    path: PathBuf::from("b.xml"),
+                },
+            ]
+        );
+        assert_eq!(
+            error(&["--report", "junit"]),
+            "invalid `--report` value `junit`; expected FORMAT=PATH"
+        );
+        assert_eq!(
+            error(&["--report", "xml=a.xml"]),
+            "unknown report format `xml`; expected junit, html, timings, or github"
+        );
+        assert_eq!(
+            error(&["--report", "junit="]),
+            "`--report` requires a path after `=`"
+        );
+    }
+
+    #[test]
+    fn output_formats() {
+        assert_eq!(parse(&[]).format, OutputFormat::Tree);
+        assert_eq!(parse(&["--format", "pretty"]).format, OutputFormat::Pretty);
+        assert_eq!(parse(&["--format=json"]).format, OutputFormat::Json);
+        let terse = parse(&["--format", "terse"]);
+        assert_eq!(terse.format, OutputFormat::Pretty);
+        assert!(terse.quiet);
+        assert_eq!(
+            error(&["--format", "xml"]),
+            "invalid `--format` value `xml`; expected tree, pretty, terse, json, tap, or plain"
+        );
+    }
+}
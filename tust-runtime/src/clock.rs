@@ -0,0 +1,249 @@
+//! Built-in `clock` fixture: a clock tests can freeze and move forward.
+//!
+//! Code that reads the time through a [`Clock`] rather than
+//! `Instant::now()` can be tested without real sleeps: a test parameter
+//! named `clock` of type [`Clock`] receives a clock that runs with real time
+//! until [frozen](Clock::freeze), after which it moves only when the test
+//! [advances](Clock::advance) it. Clones of a clock share its time, so the
+//! test keeps one and hands clones to the code under test:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn entries_expire(clock: Clock) {
+//!     clock.freeze();
+//!     let cache = Cache::new(clock.clone(), Duration::from_secs(60));
+//!     cache.insert("key", 1);
+//!     clock.advance(Duration::from_secs(61));
+//!     assert_eq!(cache.get("key"), None);
+//! }
+//! ```
+//!
+//! With the `tokio` feature, freezing the clock inside a current-thread Tokio
+//! runtime also pauses Tokio's time, and the frozen clock then moves with
+//! it: Tokio sleeps and timeouts complete as soon as the runtime has nothing
+//! else to do, and the clock reads as if they had waited.
+//! [`Clock::advance_async`] moves both clocks at once.
+
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use web_time::{Instant, SystemTime};
+
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+
+inventory::submit! {
+    FixtureDef {
+        name: "clock",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<Clock>,
+        type_id: TypeId::of::<Clock>,
+        provider: Provider::PerTest(provide_clock),
+    }
+}
+
+fn provide_clock(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    Ok(Box::new(Clock::new()))
+}
+
+/// A clock that runs with real time until frozen. Cloning it shares its
+/// time.
+#[derive(Clone)]
+pub struct Clock {
+    state: Arc<Mutex<State>>,
+    /// When the clock was created, in both of the clock's own readings.
+    origin: (Instant, SystemTime),
+}
+
+struct State {
+    mode: Mode,
+}
+
+enum Mode {
+    /// The clock reads `at` plus the real time elapsed since `since`.
+    Running { at: Instant, since: Instant },
+    /// The clock reads `at`. If it follows Tokio's paused time, it reads `at`
+    /// plus the Tokio time elapsed since `tokio`.
+    Frozen {
+        at: Instant,
+        #[cfg(feature = "tokio")]
+        tokio: Option<tokio::time::Instant>,
+    },
+}
+
+impl Clock {
+    /// A clock reading the real time.
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Clock {
+            state: Arc::new(Mutex::new(State {
+                mode: Mode::Running {
+                    at: now,
+                    since: now,
+                },
+            })),
+            origin: (now, SystemTime::now()),
+        }
+    }
+
+    /// The clock's current time.
+    pub fn now(&self) -> Instant {
+        self.state().now()
+    }
+
+    /// The clock's current time as wall-clock time: the real wall-clock time
+    /// when the clock was created, moved on by as much as the clock has.
+    pub fn system_time(&self) -> SystemTime {
+        self.origin.1 + self.now().duration_since(self.origin.0)
+    }
+
+    /// How much time the clock has moved on since `earlier`, or zero if
+    /// `earlier` is later than the clock's time.
+    pub fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+    /// Stops the clock; it moves only when advanced, or with Tokio's paused
+    /// time. Does nothing if it is already frozen.
+    pub fn freeze(&self) {
+        let mut state = self.state();
+        if let Mode::Running { .. } = state.mode {
+            state.mode = Mode::Frozen {
+                at: state.now(),
+                #[cfg(feature = "tokio")]
+                tokio: tokio_time::pause(),
+            };
+        }
+    }
+
+    /// Starts a frozen clock again from the time it reads, resuming Tokio's
+    /// time if freezing the clock paused it.
+    pub fn resume(&self) {
+        let mut state = self.state();
+        if let Mode::Frozen { .. } = state.mode {
+            let at = state.now();
+            #[cfg(feature = "tokio")]
+            if let Mode::Frozen { tokio: Some(_), .. } = state.mode {
+                tokio_time::resume();
+            }
+            state.mode = Mode::Running {
+                at,
+                since: Instant::now(),
+            };
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        matches!(self.state().mode, Mode::Frozen { .. })
+    }
+
+    /// Moves the clock forward by `duration`, whether it is running or
+    /// frozen. Tokio's time is left alone; see [`advance_async`] to move it
+    /// too.
+    ///
+    /// [`advance_async`]: Clock::advance_async
+    pub fn advance(&self, duration: Duration) {
+        match &mut self.state().mode {
+            Mode::Running { at, .. } | Mode::Frozen { at, .. } => *at += duration,
+        }
+    }
+
+    /// Waits `duration` on the clock: a frozen clock is advanced by it at
+    /// once, while a running one blocks the thread for that long.
+    pub fn sleep(&self, duration: Duration) {
+        if self.is_frozen() {
+            self.advance(duration);
+        } else {
+            std::thread::sleep(duration);
+        }
+    }
+
+    /// Moves the clock forward by `duration`, and with it Tokio's time if
+    /// the clock follows it, firing the Tokio timers due by then.
+    #[cfg(feature = "tokio")]
+    pub async fn advance_async(&self, duration: Duration) {
+        let follows_tokio = matches!(self.state().mode, Mode::Frozen { tokio: Some(_), .. });
+        if follows_tokio {
+            tokio::time::advance(duration).await;
+        } else {
+            self.advance(duration);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock")
+            .field("elapsed", &self.now().duration_since(self.origin.0))
+            .field("frozen", &self.is_frozen())
+            .finish()
+    }
+}
+
+impl State {
+    fn now(&self) -> Instant {
+        match self.mode {
+            Mode::Running { at, since } => at + since.elapsed(),
+            #[cfg(feature = "tokio")]
+            Mode::Frozen {
+                at,
+                tokio: Some(tokio),
+            } => at + tokio::time::Instant::now().duration_since(tokio),
+            Mode::Frozen { at, .. } => at,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_time {
+    use std::time::Instant;
+
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    /// Pauses Tokio's time, if running on a current-thread runtime, and
+    /// returns the Tokio time it is paused at; `None` outside of such a
+    /// runtime, where time cannot be paused.
+    pub(super) fn pause() -> Option<tokio::time::Instant> {
+        let handle = Handle::try_current().ok()?;
+        if handle.runtime_flavor() != RuntimeFlavor::CurrentThread {
+            return None;
+        }
+        if !is_paused() {
+            tokio::time::pause();
+        }
+        Some(tokio::time::Instant::now())
+    }
+
+    /// Resumes Tokio's time, if it is still paused on the current runtime.
+    pub(super) fn resume() {
+        if Handle::try_current().is_ok() && is_paused() {
+            tokio::time::resume();
+        }
+    }
+
+    /// Whether Tokio's time is paused. Paused time only moves when the
+    /// runtime advances it, never while this function runs, whereas running
+    /// time moves with the real time.
+    fn is_paused() -> bool {
+        let before = tokio::time::Instant::now();
+        let real = Instant::now();
+        while Instant::now() <= real {
+            std::hint::spin_loop();
+        }
+        tokio::time::Instant::now() == before
+    }
+}
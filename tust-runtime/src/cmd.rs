@@ -0,0 +1,938 @@
+//! Running programs from tests and asserting on how they exit and what they
+//! print.
+//!
+//! [`cmd`] starts building a [`Cmd`]: the program, its arguments, its
+//! environment and what it reads from stdin. [`Cmd::run`] runs it to
+//! completion and returns its [`Output`], whose assertions chain:
+//!
+//! ```ignore
+//! use tust::cmd::{cmd, Cmd};
+//!
+//! #[tust::test]
+//! fn greets_by_name() {
+//!     Cmd::cargo_bin("greet")
+//!         .arg("--name")
+//!         .arg("Ada")
+//!         .env("NO_COLOR", "1")
+//!         .run()
+//!         .success()
+//!         .stdout_eq("Hello, Ada!\n")
+//!         .stderr_is_empty();
+//! }
+//!
+//! #[tust::test]
+//! fn counts_lines() {
+//!     let output = cmd("wc").arg("-l").stdin("one\ntwo\n").run();
+//!     output.success().stdout_matches(r"^\s*2\s*$");
+//!     assert_snapshot!(output.stdout());
+//! }
+//! ```
+//!
+//! A failed assertion panics with the command line, how the program exited,
+//! and everything it printed. The program's output is also written to the
+//! test's own output, after a `$ command` line, so it shows among the
+//! captured output of a failing test and is discarded with that of a passing
+//! one. For snapshots, pass [`Output::stdout`] or [`Output::stderr`] to
+//! `assert_snapshot!`.
+//!
+//! For interactive programs, [`Cmd::spawn`] starts the program and returns a
+//! [`Session`] that sends it input and waits, up to a timeout, for the output
+//! the test expects, as `expect` does:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn adds_numbers() {
+//!     let mut repl = Cmd::cargo_bin("calc").spawn();
+//!     repl.expect("> ");
+//!     repl.send_line("1 + 2");
+//!     repl.expect("3\n> ");
+//!     repl.send_line("quit");
+//!     repl.wait().success();
+//! }
+//! ```
+//!
+//! A failed expectation panics with the transcript of the session: the lines
+//! sent, prefixed with `>`, and the output received, prefixed with `<`.
+
+use std::ffi::OsStr;
+use std::fmt::{self, Write as _};
+use std::io::{self, Read, Write as _};
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use regex::Regex;
+use web_time::Instant;
+
+use crate::diff;
+use crate::outcome::TestFailure;
+use crate::process;
+
+/// Starts building a command running `program`, looked up in `PATH` unless
+/// it is a path.
+pub fn cmd(program: impl AsRef<OsStr>) -> Cmd {
+    Cmd::new(program)
+}
+
+/// A program to run, with its arguments, environment and input.
+#[derive(Debug)]
+pub struct Cmd {
+    command: Command,
+    stdin: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+impl Cmd {
+    /// A command running `program`, looked up in `PATH` unless it is a path.
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Cmd {
+            command: Command::new(program),
+            stdin: None,
+            timeout: None,
+        }
+    }
+
+    /// A command running the binary `name` of the package under test, built
+    /// by Cargo next to the test binary.
+    pub fn cargo_bin(name: &str) -> Self {
+        Self::new(cargo_bin_path(name))
+    }
+
+    /// Appends an argument.
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Appends arguments.
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command.args(args);
+        self
+    }
+
+    /// Sets an environment variable of the program.
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Removes an environment variable the program would inherit.
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.command.env_remove(key);
+        self
+    }
+
+    /// Starts the program with no environment variables but those set with
+    /// [`env`](Self::env).
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.command.env_clear();
+        self
+    }
+
+    /// Runs the program in `dir` rather than the current directory.
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.command.current_dir(dir);
+        self
+    }
+
+    /// Writes `input` to the program's stdin, then closes it, unless the
+    /// program is [spawned](Self::spawn), which keeps it open. Without input,
+    /// stdin is empty.
+    pub fn stdin(&mut self, input: impl Into<Vec<u8>>) -> &mut Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    /// Kills the program if it runs longer than `timeout`.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs the program to completion, and writes what it printed to the
+    /// test's output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program cannot be started.
+    #[track_caller]
+    pub fn run(&mut self) -> Output {
+        let line = command_line(&self.command);
+        let caller = Location::caller();
+        let mut child = self
+            .command
+            .stdin(if self.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|err| panic!("cannot run `{}`: {}", line, err));
+        let writer = child
+            .stdin
+            .take()
+            .zip(self.stdin.clone())
+            .map(|(mut pipe, input)| {
+                // A program that exits without reading its input closes the pipe,
+                // which is not an error of the test.
+                thread::spawn(move || drop(pipe.write_all(&input)))
+            });
+        let stdout = process::read_in_background(child.stdout.take());
+        let stderr = process::read_in_background(child.stderr.take());
+        let status = process::wait(&mut child, self.timeout).unwrap_or_else(|err| {
+            panic!("cannot wait for `{}` started at {}: {}", line, caller, err)
+        });
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+        let output = Output {
+            line,
+            status,
+            timeout: self.timeout,
+            stdout: String::from_utf8_lossy(&process::join(stdout)).into_owned(),
+            stderr: String::from_utf8_lossy(&process::join(stderr)).into_owned(),
+        };
+        println!("$ {}", output.line);
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+        output
+    }
+
+    /// Starts the program for a test to converse with through the returned
+    /// [`Session`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program cannot be started.
+    #[track_caller]
+    pub fn spawn(&mut self) -> Session {
+        let line = command_line(&self.command);
+        let mut child = self
+            .command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|err| panic!("cannot run `{}`: {}", line, err));
+        let mut stdin = child.stdin.take();
+        if let (Some(pipe), Some(input)) = (&mut stdin, &self.stdin) {
+            let _ = pipe.write_all(input);
+        }
+        Session {
+            line,
+            stdin,
+            stdout: Stream::read(child.stdout.take()),
+            stderr: Stream::read(child.stderr.take()),
+            child,
+            consumed: 0,
+            transcript: Vec::new(),
+            expect_timeout: EXPECT_TIMEOUT,
+            timeout: self.timeout,
+            finished: false,
+        }
+    }
+}
+
+/// How a program run by [`Cmd::run`] exited, and what it printed, with
+/// assertions on them that return the output for chaining.
+#[derive(Debug, Clone)]
+pub struct Output {
+    line: String,
+    /// `None` if the program was killed after its timeout.
+    status: Option<ExitStatus>,
+    timeout: Option<Duration>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Output {
+    /// The exit status, or `None` if the program was killed after its
+    /// timeout.
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status
+    }
+
+    /// The exit code, or `None` if the program was killed.
+    pub fn code(&self) -> Option<i32> {
+        self.status.and_then(|status| status.code())
+    }
+
+    /// What the program wrote to stdout, with invalid UTF-8 replaced.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// What the program wrote to stderr, with invalid UTF-8 replaced.
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// Asserts that the program exited successfully.
+    #[track_caller]
+    pub fn success(&self) -> &Self {
+        if !self.status.is_some_and(|status| status.success()) {
+            self.fail("expected it to succeed", None);
+        }
+        self
+    }
+
+    /// Asserts that the program exited unsuccessfully, rather than being
+    /// killed after its timeout.
+    #[track_caller]
+    pub fn failure(&self) -> &Self {
+        if !self.status.is_some_and(|status| !status.success()) {
+            self.fail("expected it to fail", None);
+        }
+        self
+    }
+
+    /// Asserts that the program exited with `code`.
+    #[track_caller]
+    pub fn exit_code(&self, code: i32) -> &Self {
+        if self.code() != Some(code) {
+            self.fail(&format!("expected exit code {}", code), None);
+        }
+        self
+    }
+
+    /// Asserts that stdout is exactly `expected`.
+    #[track_caller]
+    pub fn stdout_eq(&self, expected: &str) -> &Self {
+        self.check_eq("stdout", &self.stdout, expected)
+    }
+
+    /// Asserts that stdout contains `needle`.
+    #[track_caller]
+    pub fn stdout_contains(&self, needle: &str) -> &Self {
+        self.check_contains("stdout", &self.stdout, needle)
+    }
+
+    /// Asserts that stdout matches the regular expression `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    #[track_caller]
+    pub fn stdout_matches(&self, pattern: &str) -> &Self {
+        self.check_matches("stdout", &self.stdout, pattern)
+    }
+
+    /// Asserts that nothing was written to stdout.
+    #[track_caller]
+    pub fn stdout_is_empty(&self) -> &Self {
+        self.check_eq("stdout", &self.stdout, "")
+    }
+
+    /// Asserts that stderr is exactly `expected`.
+    #[track_caller]
+    pub fn stderr_eq(&self, expected: &str) -> &Self {
+        self.check_eq("stderr", &self.stderr, expected)
+    }
+
+    /// Asserts that stderr contains `needle`.
+    #[track_caller]
+    pub fn stderr_contains(&self, needle: &str) -> &Self {
+        self.check_contains("stderr", &self.stderr, needle)
+    }
+
+    /// Asserts that stderr matches the regular expression `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    #[track_caller]
+    pub fn stderr_matches(&self, pattern: &str) -> &Self {
+        self.check_matches("stderr", &self.stderr, pattern)
+    }
+
+    /// Asserts that nothing was written to stderr.
+    #[track_caller]
+    pub fn stderr_is_empty(&self) -> &Self {
+        self.check_eq("stderr", &self.stderr, "")
+    }
+
+    #[track_caller]
+    fn check_eq(&self, stream: &str, actual: &str, expected: &str) -> &Self {
+        if actual != expected {
+            let diff = if diff::wants_diff(actual, expected) {
+                (expected.to_string(), actual.to_string())
+            } else {
+                (format!("{:?}", expected), format!("{:?}", actual))
+            };
+            self.fail(
+                &format!("expected {} to be exactly the given text", stream),
+                Some((stream, diff)),
+            );
+        }
+        self
+    }
+
+    #[track_caller]
+    fn check_contains(&self, stream: &str, actual: &str, needle: &str) -> &Self {
+        if !actual.contains(needle) {
+            self.fail(
+                &format!("expected {} to contain {:?}", stream, needle),
+                None,
+            );
+        }
+        self
+    }
+
+    #[track_caller]
+    fn check_matches(&self, stream: &str, actual: &str, pattern: &str) -> &Self {
+        let regex = Regex::new(pattern)
+            .unwrap_or_else(|err| panic!("invalid regular expression {:?}: {}", pattern, err));
+        if !regex.is_match(actual) {
+            self.fail(&format!("expected {} to match /{}/", stream, pattern), None);
+        }
+        self
+    }
+
+    /// Fails the test with `expectation`, how the program exited, what it
+    /// printed, and the expected and actual text of `diff`'s stream.
+    #[track_caller]
+    fn fail(&self, expectation: &str, diff: Option<(&str, (String, String))>) -> ! {
+        let exit = match (self.status, self.timeout) {
+            (Some(status), _) => match status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => format!("exited with {}", status),
+            },
+            (None, Some(timeout)) => format!("was killed after {:?}", timeout),
+            (None, None) => "was killed".to_string(),
+        };
+        let mut message = format!("`{}` {}, {}", self.line, exit, expectation);
+        for (stream, text) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if text.is_empty() {
+                let _ = write!(message, "\n\n{}: (empty)", stream);
+            } else {
+                let _ = write!(message, "\n\n{}:\n{}", stream, text.trim_end_matches('\n'));
+            }
+        }
+        let failure = TestFailure::new(message);
+        match diff {
+            Some((stream, (expected, actual))) => failure
+                .with_labeled_diff("expected", stream, expected, actual)
+                .raise(),
+            None => failure.raise(),
+        }
+    }
+}
+
+/// How long [`Session::expect`] waits for output unless told otherwise.
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Output of a running program, filled by a thread reading its pipe.
+#[derive(Default)]
+struct Stream {
+    state: Mutex<StreamState>,
+    grew: Condvar,
+}
+
+#[derive(Default)]
+struct StreamState {
+    bytes: Vec<u8>,
+    closed: bool,
+}
+
+impl Stream {
+    /// Reads `pipe` into a new stream until it closes.
+    fn read(pipe: Option<impl Read + Send + 'static>) -> Arc<Stream> {
+        let stream = Arc::new(Stream::default());
+        let Some(mut pipe) = pipe else {
+            stream.close();
+            return stream;
+        };
+        let filled = Arc::clone(&stream);
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let mut state = filled.lock();
+                        state.bytes.extend_from_slice(&buf[..n]);
+                        filled.grew.notify_all();
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            filled.close();
+        });
+        stream
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StreamState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.grew.notify_all();
+    }
+
+    /// Everything read so far, with invalid UTF-8 replaced.
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.lock().bytes).into_owned()
+    }
+}
+
+/// A line of a [`Session`]'s transcript.
+#[derive(Debug, Clone)]
+enum Event {
+    Sent(String),
+    Received(String),
+}
+
+/// A running program a test converses with, as started by [`Cmd::spawn`]:
+/// it sends input, waits for output, and finally waits for the program to
+/// exit.
+///
+/// The session keeps a transcript of what was sent and received. A failed
+/// expectation panics with it, and it is written to the test's output when
+/// the session ends. A program still running when the session is dropped is
+/// killed.
+pub struct Session {
+    line: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: Arc<Stream>,
+    stderr: Arc<Stream>,
+    /// How much of stdout earlier expectations consumed, in bytes.
+    consumed: usize,
+    transcript: Vec<Event>,
+    expect_timeout: Duration,
+    timeout: Option<Duration>,
+    finished: bool,
+}
+
+impl Session {
+    /// Sets how long later expectations wait for their output, ten seconds
+    /// unless set.
+    pub fn expect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.expect_timeout = timeout;
+        self
+    }
+
+    /// Writes `text` to the program's stdin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if stdin was closed or the program stopped reading it.
+    #[track_caller]
+    pub fn send(&mut self, text: &str) -> &mut Self {
+        self.transcript.push(Event::Sent(text.to_string()));
+        let written = match &mut self.stdin {
+            Some(stdin) => stdin
+                .write_all(text.as_bytes())
+                .and_then(|()| stdin.flush()),
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "stdin was closed",
+            )),
+        };
+        if let Err(err) = written {
+            self.fail(&format!("cannot write to its stdin: {}", err));
+        }
+        self
+    }
+
+    /// Writes `line` and a newline to the program's stdin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if stdin was closed or the program stopped reading it.
+    #[track_caller]
+    pub fn send_line(&mut self, line: &str) -> &mut Self {
+        self.send(&format!("{}\n", line))
+    }
+
+    /// Closes the program's stdin, as typing Ctrl-D at a terminal does.
+    pub fn close_stdin(&mut self) -> &mut Self {
+        self.stdin = None;
+        self
+    }
+
+    /// Waits until the program prints `needle`, and returns what it printed
+    /// since the previous expectation, up to and including `needle`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the transcript if the program closes stdout, or the
+    /// expectation times out, before printing `needle`.
+    #[track_caller]
+    pub fn expect(&mut self, needle: &str) -> String {
+        let expectation = format!("expected it to print {:?}", needle);
+        self.wait_for(&expectation, |text| {
+            text.find(needle).map(|start| start + needle.len())
+        })
+    }
+
+    /// Waits until what the program prints matches the regular expression
+    /// `pattern`, and returns what it printed since the previous
+    /// expectation, up to the end of the match.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression, and with the
+    /// transcript if the program closes stdout, or the expectation times out,
+    /// before printing a match.
+    #[track_caller]
+    pub fn expect_matches(&mut self, pattern: &str) -> String {
+        let regex = Regex::new(pattern)
+            .unwrap_or_else(|err| panic!("invalid regular expression {:?}: {}", pattern, err));
+        let expectation = format!("expected it to print a match of /{}/", pattern);
+        self.wait_for(&expectation, |text| {
+            regex.find(text).map(|found| found.end())
+        })
+    }
+
+    /// Closes stdin, waits for the program to exit, and returns its output,
+    /// everything it printed, for the assertions of [`Output`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if waiting for the program fails.
+    #[track_caller]
+    pub fn wait(mut self) -> Output {
+        self.stdin = None;
+        let status = process::wait(&mut self.child, self.timeout)
+            .unwrap_or_else(|err| panic!("cannot wait for `{}`: {}", self.line, err));
+        for stream in [&self.stdout, &self.stderr] {
+            let mut state = stream.lock();
+            while !state.closed {
+                state = stream
+                    .grew
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        self.record_unconsumed();
+        self.finish();
+        Output {
+            line: self.line.clone(),
+            status,
+            timeout: self.timeout,
+            stdout: self.stdout.text(),
+            stderr: self.stderr.text(),
+        }
+    }
+
+    /// Waits until `find`, given what stdout printed since the previous
+    /// expectation, returns the end of a match, and consumes stdout up to it.
+    #[track_caller]
+    fn wait_for(&mut self, expectation: &str, find: impl Fn(&str) -> Option<usize>) -> String {
+        let deadline = Instant::now() + self.expect_timeout;
+        let stream = Arc::clone(&self.stdout);
+        let mut state = stream.lock();
+        loop {
+            let text = String::from_utf8_lossy(&state.bytes[self.consumed..]).into_owned();
+            if let Some(end) = find(&text) {
+                let received = text[..end].to_string();
+                self.consumed += received.len();
+                drop(state);
+                self.transcript.push(Event::Received(received.clone()));
+                return received;
+            }
+            let now = Instant::now();
+            if state.closed || now >= deadline {
+                let closed = state.closed;
+                drop(state);
+                self.record_unconsumed();
+                let reason = if closed {
+                    "but it closed stdout".to_string()
+                } else {
+                    format!("within {:?}", self.expect_timeout)
+                };
+                self.fail(&format!("{} {}", expectation, reason));
+            }
+            state = stream
+                .grew
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Adds what stdout printed after the last expectation to the
+    /// transcript.
+    fn record_unconsumed(&mut self) {
+        let rest = {
+            let state = self.stdout.lock();
+            String::from_utf8_lossy(&state.bytes[self.consumed..]).into_owned()
+        };
+        if !rest.is_empty() {
+            self.consumed = self.stdout.lock().bytes.len();
+            self.transcript.push(Event::Received(rest));
+        }
+    }
+
+    /// The transcript, with each line sent prefixed by `> ` and each line
+    /// received by `< `, followed by stderr.
+    fn render_transcript(&self) -> String {
+        let mut out = String::new();
+        for event in &self.transcript {
+            let (prefix, text) = match event {
+                Event::Sent(text) => ("> ", text),
+                Event::Received(text) => ("< ", text),
+            };
+            for line in text.lines() {
+                let _ = writeln!(out, "{}{}", prefix, line);
+            }
+        }
+        let stderr = self.stderr.text();
+        if !stderr.is_empty() {
+            let _ = write!(out, "\nstderr:\n{}", stderr);
+        }
+        out
+    }
+
+    /// Writes the transcript to the test's output, once.
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        println!("$ {}", self.line);
+        print!("{}", self.render_transcript());
+    }
+
+    #[track_caller]
+    fn fail(&mut self, expectation: &str) -> ! {
+        let transcript = self.render_transcript();
+        self.finish();
+        TestFailure::new(format!(
+            "`{}` {}\n\ntranscript:\n{}",
+            self.line,
+            expectation,
+            transcript.trim_end_matches('\n')
+        ))
+        .raise();
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.stdin = None;
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+        self.record_unconsumed();
+        self.finish();
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("command", &self.line)
+            .field("pid", &self.child.id())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The program and arguments of `command`, as a shell would take them.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|word| {
+            let word = word.to_string_lossy();
+            if !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+            {
+                word.into_owned()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Where Cargo puts the binary `name`: the directory of the test binary, or
+/// its parent for test binaries in `deps`.
+fn cargo_bin_path(name: &str) -> PathBuf {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    let Some(mut dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    else {
+        return PathBuf::from(file);
+    };
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir.join(file)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use crate::panic::catch;
+
+    fn failure_of(f: impl FnOnce()) -> TestFailure {
+        catch(f).expect_err("the assertion passed")
+    }
+
+    #[test]
+    fn exit_statuses_are_asserted() {
+        cmd("true").run().success().exit_code(0);
+        cmd("false").run().failure().exit_code(1);
+        let output = cmd("sh").args(["-c", "exit 3"]).run();
+        assert_eq!(output.code(), Some(3));
+        assert!(!output.status().unwrap().success());
+        output.failure().exit_code(3);
+    }
+
+    #[test]
+    fn output_is_captured() {
+        cmd("echo")
+            .arg("hello")
+            .run()
+            .stdout_eq("hello\n")
+            .stderr_is_empty();
+        cmd("sh")
+            .args(["-c", "echo oops >&2"])
+            .run()
+            .stdout_is_empty()
+            .stderr_eq("oops\n")
+            .stderr_contains("oop")
+            .stderr_matches(r"^o+ps\n$");
+        cmd("cat")
+            .stdin("one\ntwo\n")
+            .run()
+            .stdout_matches(r"(?m)^two$")
+            .stdout_contains("one\nt");
+        cmd("sh")
+            .args(["-c", "echo $GREETING"])
+            .env_clear()
+            .env("GREETING", "hi")
+            .run()
+            .stdout_eq("hi\n");
+    }
+
+    #[test]
+    fn failures_show_how_the_program_exited_and_what_it_printed() {
+        assert_eq!(
+            failure_of(|| {
+                cmd("false").run().success();
+            })
+            .message,
+            "`false` exited with code 1, expected it to succeed\n\nstdout: (empty)\n\n\
+             stderr: (empty)"
+        );
+        assert_eq!(
+            failure_of(|| {
+                cmd("true").run().exit_code(2);
+            })
+            .message,
+            "`true` exited with code 0, expected exit code 2\n\nstdout: (empty)\n\n\
+             stderr: (empty)"
+        );
+        let script = "echo out; echo err >&2";
+        assert_eq!(
+            failure_of(|| {
+                cmd("sh").args(["-c", script]).run().stdout_contains("in");
+            })
+            .message,
+            "`sh -c 'echo out; echo err >&2'` exited with code 0, expected stdout to contain \
+             \"in\"\n\nstdout:\nout\n\nstderr:\nerr"
+        );
+        let failure = failure_of(|| {
+            cmd("echo").arg("hi").run().stdout_eq("bye\n");
+        });
+        assert_eq!(
+            failure.message,
+            "`echo hi` exited with code 0, expected stdout to be exactly the given text\n\n\
+             stdout:\nhi\n\nstderr: (empty)"
+        );
+        let diff = failure.diff.unwrap();
+        assert_eq!(diff.labels, ("expected".to_string(), "stdout".to_string()));
+        assert_eq!((diff.left.as_str(), diff.right.as_str()), ("bye\n", "hi\n"));
+    }
+
+    #[test]
+    fn programs_running_too_long_are_killed() {
+        let mut sleep = cmd("sleep");
+        let output = sleep.arg("5").timeout(Duration::from_millis(50)).run();
+        assert_eq!(output.status(), None);
+        assert_eq!(
+            failure_of(|| {
+                output.failure();
+            })
+            .message,
+            "`sleep 5` was killed after 50ms, expected it to fail\n\nstdout: (empty)\n\n\
+             stderr: (empty)"
+        );
+    }
+
+    #[test]
+    fn command_lines_are_quoted_as_for_a_shell() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo 'a b'", ""]);
+        assert_eq!(command_line(&command), r"sh -c 'echo '\''a b'\''' ''");
+        assert_eq!(
+            failure_of(|| {
+                cmd("/nonexistent/tust").run();
+            })
+            .message,
+            "cannot run `/nonexistent/tust`: No such file or directory (os error 2)"
+        );
+    }
+
+    #[test]
+    fn sessions_converse_with_the_program() {
+        let mut session = cmd("cat").spawn();
+        session.send_line("ping");
+        assert_eq!(session.expect("ping\n"), "ping\n");
+        session.send("one two");
+        assert_eq!(session.expect_matches(r"\w+ "), "one ");
+        session.close_stdin();
+        session.wait().success().stdout_eq("ping\none two");
+    }
+
+    #[test]
+    fn failed_expectations_show_the_transcript() {
+        let mut session = cmd("echo").arg("ready").spawn();
+        let failure = failure_of(|| {
+            session.expect("steady");
+        });
+        assert_eq!(
+            failure.message,
+            "`echo ready` expected it to print \"steady\" but it closed stdout\n\n\
+             transcript:\n< ready"
+        );
+        let mut session = cmd("sh").args(["-c", "echo warm >&2; cat"]).spawn();
+        // Stderr is shown as far as it was read when the expectation fails.
+        while session.stderr.text().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        session
+            .expect_timeout(Duration::from_millis(200))
+            .send_line("ping");
+        assert_eq!(
+            failure_of(|| {
+                session.expect("pong");
+            })
+            .message,
+            "`sh -c 'echo warm >&2; cat'` expected it to print \"pong\" within 200ms\n\n\
+             transcript:\n> ping\n< ping\n\nstderr:\nwarm"
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Runner defaults read from a `tust.toml` file.
+//!
+//! The runner looks for `tust.toml` in the manifest directory of the crate
+//! being tested and then in each directory above it, so one file at the
+//! workspace root serves every crate; `TUST_CONFIG` names a file explicitly.
+//! Keys match the command-line options:
+//!
+//! ```toml
+//! jobs = 8
+//! timeout = "30s"
+//! retries = 1
+//! format = "pretty"
+//! reports = ["junit=target/tust/junit.xml"]
+//! skip-tags = ["slow"]
+//! snapshot-dir = "tests/__snapshots__"
+//!
+//! [profile.ci]
+//! retries = 2
+//! fail-fast = true
+//! tags = ["!flaky"]
+//! ```
+//!
+//! The accepted keys are `jobs`, `timeout` (a duration such as `"500ms"`,
+//! `"30s"`, or `"2m"`, or a number of milliseconds), `retries`, `fail-fast`,
+//! `max-failures`, `format`, `reports`, `tags`, `skip-tags`, `slowest`,
+//! `output-lines`, `process-per-test`, `show-output`, `leak-check`,
+//! `isolated-cwd`, `strict`, and `snapshot-dir`, which is relative to the
+//! manifest directory of the crate being tested. A `[strict-checks]` table
+//! configures the checks of `strict`, as described in [`crate::strict`], and
+//! a `[quarantine]` table the tests whose failures do not fail the run, as
+//! described in [`crate::quarantine`].
+//!
+//! A `[profile.NAME]` section, selected with `--profile NAME` or the
+//! `TUST_PROFILE` environment variable, overrides the keys at the top of
+//! the file; lists such as `tags` are replaced rather than extended.
+//! Environment variables (`RUST_TEST_THREADS`, `TUST_TIMEOUT`,
+//! `TUST_RETRIES`, and `TUST_SNAPSHOT_DIR`) override the file, and
+//! command-line options override both.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use toml::{Table, Value};
+
+use crate::cli::{self, Args, OutputFormat, ReportTarget};
+use crate::quarantine::Quarantine;
+use crate::strict::{Checks, Level};
+use crate::tags::TagExpr;
+
+/// Name of the configuration file.
+pub const FILE_NAME: &str = "tust.toml";
+
+/// Environment variable naming the configuration file to use instead of
+/// searching for one.
+pub const CONFIG_ENV: &str = "TUST_CONFIG";
+
+/// Environment variable selecting the profile when `--profile` is not
+/// given.
+pub const PROFILE_ENV: &str = "TUST_PROFILE";
+
+/// Settings read from a configuration file, with its selected profile
+/// applied. Unset settings are `None` or empty.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// The file the settings were read from.
+    pub path: PathBuf,
+    pub jobs: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub retries: Option<u32>,
+    pub max_failures: Option<usize>,
+    pub format: Option<OutputFormat>,
+    /// Set by `format = "terse"`.
+    pub quiet: bool,
+    pub reports: Vec<ReportTarget>,
+    pub tags: Vec<TagExpr>,
+    pub skip_tags: Vec<TagExpr>,
+    pub slowest: Option<usize>,
+    pub output_lines: Option<usize>,
+    pub process_per_test: Option<bool>,
+    pub show_output: Option<bool>,
+    pub leak_check: Option<bool>,
+    pub isolated_cwd: Option<bool>,
+    pub strict: Option<bool>,
+    pub strict_checks: Option<Checks>,
+    pub quarantine: Option<Quarantine>,
+    pub snapshot_dir: Option<PathBuf>,
+}
+
+/// A configuration file that cannot be read or is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    path: PathBuf,
+    message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration of the current run: from the file named by
+    /// [`CONFIG_ENV`], or else the first [`FILE_NAME`] found from the
+    /// `CARGO_MANIFEST_DIR` of the run (or the working directory) upwards.
+    /// `profile` defaults to [`PROFILE_ENV`].
+    ///
+    /// Returns `None` if there is no file and no profile was asked for.
+    pub fn discover(profile: Option<&str>) -> Result<Option<Config>, ConfigError> {
+        let env_profile = std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty());
+        let profile = profile.or(env_profile.as_deref());
+        let path = match std::env::var_os(CONFIG_ENV) {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => std::env::var_os("CARGO_MANIFEST_DIR")
+                .map(PathBuf::from)
+                .or_else(|| std::env::current_dir().ok())
+                .and_then(|dir| find(&dir)),
+        };
+        match (path, profile) {
+            (Some(path), profile) => Config::load(&path, profile).map(Some),
+            (None, Some(profile)) => Err(ConfigError {
+                path: PathBuf::from(FILE_NAME),
+                message: format!("profile `{}` was selected, but no file was found", profile),
+            }),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Reads the file at `path`, applying `profile` if given.
+    pub fn load(path: &Path, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let error = |message: String| ConfigError {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let mut table: Table = text.parse().map_err(|err| error(format!("{}", err)))?;
+
+        let profiles = match table.remove("profile") {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => return Err(error("`profile` must be a table of profiles".to_string())),
+            None => Table::new(),
+        };
+        // Every profile is checked, so that mistakes in one show up before
+        // it is first used.
+        for (name, overrides) in &profiles {
+            let Value::Table(overrides) = overrides else {
+                return Err(error(format!("`profile.{}` must be a table", name)));
+            };
+            let mut scratch = Config::default();
+            for (key, value) in overrides {
+                scratch
+                    .set(key, value)
+                    .map_err(|message| error(format!("in `profile.{}`: {}", name, message)))?;
+            }
+        }
+        if let Some(name) = profile {
+            match profiles.get(name) {
+                Some(Value::Table(overrides)) => {
+                    table.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())))
+                }
+                _ => return Err(error(format!("there is no profile `{}`", name))),
+            }
+        }
+
+        let mut config = Config {
+            path: path.to_path_buf(),
+            ..Config::default()
+        };
+        for (key, value) in &table {
+            config.set(key, value).map_err(error)?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "jobs" => self.jobs = Some(positive(key, value)?),
+            "timeout" => self.timeout = Some(duration(key, value)?),
+            "retries" => {
+                let retries = value
+                    .as_integer()
+                    .and_then(|retries| u32::try_from(retries).ok());
+                self.retries = Some(
+                    retries.ok_or_else(|| format!("`retries` must be a number, not {}", value))?,
+                );
+            }
+            "fail-fast" => {
+                if boolean(key, value)? {
+                    self.max_failures = Some(1);
+                }
+            }
+            "max-failures" => self.max_failures = Some(positive(key, value)?),
+            "format" => {
+                let format = value
+                    .as_str()
+                    .ok_or_else(|| format!("`format` must be a string, not {}", value))?;
+                (self.format, self.quiet) = match format {
+                    "tree" => (Some(OutputFormat::Tree), false),
+                    "pretty" => (Some(OutputFormat::Pretty), false),
+                    "terse" => (Some(OutputFormat::Pretty), true),
+                    "json" => (Some(OutputFormat::Json), false),
+                    "tap" => (Some(OutputFormat::Tap), false),
+                    "plain" => (Some(OutputFormat::Plain), false),
+                    other => {
+                        return Err(format!(
+                        "invalid `format` value `{}`; expected tree, pretty, terse, json, tap, or plain",
+                        other
+                    ))
+                    }
+                };
+            }
+            "reports" => {
+                self.reports = strings(key, value)?
+                    .iter()
+                    .map(|report| cli::parse_report(report).map_err(|err| err.to_string()))
+                    .collect::<Result<_, _>>()?
+            }
+            "tags" => self.tags = tag_exprs(key, value)?,
+            "skip-tags" => self.skip_tags = tag_exprs(key, value)?,
+            "slowest" => {
+                let slowest = value
+                    .as_integer()
+                    .and_then(|slowest| usize::try_from(slowest).ok());
+                self.slowest = Some(
+                    slowest.ok_or_else(|| format!("`slowest` must be a number, not {}", value))?,
+                );
+            }
+            "output-lines" => {
+                let lines = value
+                    .as_integer()
+                    .and_then(|lines| usize::try_from(lines).ok());
+                self.output_lines =
+                    Some(lines.ok_or_else(|| {
+                        format!("`output-lines` must be a number, not {}", value)
+                    })?);
+            }
+            "process-per-test" => self.process_per_test = Some(boolean(key, value)?),
+            "show-output" => self.show_output = Some(boolean(key, value)?),
+            "leak-check" => self.leak_check = Some(boolean(key, value)?),
+            "isolated-cwd" => self.isolated_cwd = Some(boolean(key, value)?),
+            "strict" => self.strict = Some(boolean(key, value)?),
+            "strict-checks" => self.strict_checks = Some(strict_checks(value)?),
+            "quarantine" => self.quarantine = Some(quarantine(value)?),
+            "snapshot-dir" => {
+                let dir = value
+                    .as_str()
+                    .filter(|dir| !dir.is_empty())
+                    .ok_or_else(|| format!("`snapshot-dir` must be a path, not {}", value))?;
+                self.snapshot_dir = Some(PathBuf::from(dir));
+            }
+            other => return Err(format!("unknown key `{}`", other)),
+        }
+        Ok(())
+    }
+
+    /// Sets the options of `args` that this configuration sets.
+    pub fn apply(&self, args: &mut Args) {
+        args.jobs = self.jobs.or(args.jobs);
+        args.timeout = self.timeout.or(args.timeout);
+        args.retries = self.retries.unwrap_or(args.retries);
+        args.max_failures = self.max_failures.or(args.max_failures);
+        if let Some(format) = self.format {
+            args.format = format;
+            args.quiet = self.quiet;
+        }
+        if !self.reports.is_empty() {
+            args.reports = self.reports.clone();
+        }
+        if !self.tags.is_empty() {
+            args.tags = self.tags.clone();
+        }
+        if !self.skip_tags.is_empty() {
+            args.skip_tags = self.skip_tags.clone();
+        }
+        args.slowest = self.slowest.or(args.slowest);
+        args.output_lines = self.output_lines.or(args.output_lines);
+        args.process_per_test = self.process_per_test.unwrap_or(args.process_per_test);
+        args.show_output = self.show_output.unwrap_or(args.show_output);
+        args.leak_check = self.leak_check.unwrap_or(args.leak_check);
+        args.isolated_cwd = self.isolated_cwd.unwrap_or(args.isolated_cwd);
+        args.strict = self.strict.unwrap_or(args.strict);
+        if let Some(checks) = &self.strict_checks {
+            args.strict_checks = checks.clone();
+        }
+        if let Some(quarantine) = &self.quarantine {
+            args.quarantine = quarantine.clone();
+        }
+        args.snapshot_dir = self.snapshot_dir.clone().or(args.snapshot_dir.take());
+    }
+}
+
+/// The first [`FILE_NAME`] in `dir` or a directory above it.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+fn positive(key: &str, value: &Value) -> Result<usize, String> {
+    value
+        .as_integer()
+        .and_then(|number| usize::try_from(number).ok())
+        .filter(|&number| number > 0)
+        .ok_or_else(|| format!("`{}` must be a positive number, not {}", key, value))
+}
+
+/// A duration such as `"30s"`, or a number of milliseconds.
+fn duration(key: &str, value: &Value) -> Result<Duration, String> {
+    let duration = match value {
+        Value::String(duration) => cli::parse_duration(duration),
+        Value::Integer(ms) => u64::try_from(*ms)
+            .ok()
+            .map(Duration::from_millis)
+            .filter(|duration| !duration.is_zero()),
+        _ => None,
+    };
+    duration.ok_or_else(|| {
+        format!(
+            "`{}` must be a duration such as \"500ms\", \"30s\", or \"2m\", not {}",
+            key, value
+        )
+    })
+}
+
+/// The `[strict-checks]` table; checks it leaves out warn.
+fn strict_checks(value: &Value) -> Result<Checks, String> {
+    let Value::Table(table) = value else {
+        return Err(format!("`strict-checks` must be a table, not {}", value));
+    };
+    let mut checks = Checks::default();
+    for (key, value) in table {
+        let level = || {
+            value.as_str().and_then(Level::parse).ok_or_else(|| {
+                format!(
+                    "`strict-checks.{}` must be \"warn\", \"fail\", or \"off\", not {}",
+                    key, value
+                )
+            })
+        };
+        match key.as_str() {
+            "threads" => checks.threads = level()?,
+            "tokio-tasks" => checks.tokio_tasks = level()?,
+            "temp-files" => checks.temp_files = level()?,
+            "slow" => checks.slow = level()?,
+            "slow-threshold" => {
+                checks.slow_threshold = duration("strict-checks.slow-threshold", value)?
+            }
+            other => return Err(format!("unknown key `strict-checks.{}`", other)),
+        }
+    }
+    Ok(checks)
+}
+
+/// The `[quarantine]` table.
+pub(crate) fn quarantine(value: &Value) -> Result<Quarantine, String> {
+    let Value::Table(table) = value else {
+        return Err(format!("`quarantine` must be a table, not {}", value));
+    };
+    let mut quarantine = Quarantine::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "tests" => {
+                quarantine.tests = strings("quarantine.tests", value)?
+                    .iter()
+                    .map(|glob| {
+                        glob.parse().map_err(|err| {
+                            format!("invalid `quarantine.tests` glob `{}`: {}", glob, err)
+                        })
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            "stable-after" => {
+                let runs = positive("quarantine.stable-after", value)?;
+                quarantine.stable_after = u32::try_from(runs).unwrap_or(u32::MAX);
+            }
+            other => return Err(format!("unknown key `quarantine.{}`", other)),
+        }
+    }
+    Ok(quarantine)
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("`{}` must be `true` or `false`, not {}", key, value))
+}
+
+/// A list of strings, or a single string as a list of one.
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let error = || format!("`{}` must be a list of strings, not {}", key, value);
+    match value {
+        Value::String(string) => Ok(vec![string.clone()]),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string).ok_or_else(error))
+            .collect(),
+        _ => Err(error()),
+    }
+}
+
+fn tag_exprs(key: &str, value: &Value) -> Result<Vec<TagExpr>, String> {
+    strings(key, value)?
+        .iter()
+        .map(|expr| {
+            expr.parse()
+                .map_err(|err| format!("invalid `{}` expression `{}`: {}", key, expr, err))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cli::ReportFormat;
+
+    /// Loads `text` as a configuration file named after `name`, with
+    /// `profile` applied.
+    fn load(name: &str, text: &str, profile: Option<&str>) -> Result<Config, String> {
+        let path =
+            std::env::temp_dir().join(format!("tust-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let config = Config::load(&path, profile);
+        fs::remove_file(&path).unwrap();
+        config.map_err(|err| err.message)
+    }
+
+    fn tags(exprs: &[&str]) -> Vec<TagExpr> {
+        exprs.iter().map(|expr| expr.parse().unwrap()).collect()
+    }
+
+    const FILE: &str = r#"
+jobs = 8
+timeout = "30s"
+retries = 1
+format = "terse"
+reports = ["junit=target/tust/junit.xml"]
+skip-tags = ["slow"]
+snapshot-dir = "tests/__snapshots__"
+
+[profile.ci]
+retries = 2
+fail-fast = true
+skip-tags = ["flaky", "net"]
+
+[profile.quick]
+timeout = 500
+"#;
+
+    #[test]
+    fn reads_the_keys_at_the_top_of_the_file() {
+        let config = load("top", FILE, None).unwrap();
+        assert_eq!(config.jobs, Some(8));
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(config.retries, Some(1));
+        assert_eq!(config.max_failures, None);
+        assert_eq!(config.format, Some(OutputFormat::Pretty));
+        assert!(config.quiet);
+        assert_eq!(
+            config.reports,
+            [ReportTarget {
+                format: ReportFormat::Junit,
+                path: PathBuf::from("target/tust/junit.xml"),
+            }]
+        );
+        assert_eq!(config.skip_tags, tags(&["slow"]));
+        assert_eq!(
+            config.snapshot_dir,
+            Some(PathBuf::from("tests/__snapshots__"))
+        );
+    }
+
+    #[test]
+    fn profiles_override_the_keys_at_the_top() {
+        let config = load("ci", FILE, Some("ci")).unwrap();
+        assert_eq!(config.retries, Some(2));
+        assert_eq!(config.max_failures, Some(1));
+        // Lists are replaced, not extended.
+        assert_eq!(config.skip_tags, tags(&["flaky", "net"]));
+        // Keys the profile leaves out keep their values.
+        assert_eq!(config.jobs, Some(8));
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+
+        let config = load("quick", FILE, Some("quick")).unwrap();
+        assert_eq!(config.timeout, Some(Duration::from_millis(500)));
+        assert_eq!(config.retries, Some(1));
+    }
+
+    #[test]
+    fn unknown_profiles_are_errors() {
+        assert_eq!(
+            load("unknown-profile", FILE, Some("nightly")).unwrap_err(),
+            "there is no profile `nightly`"
+        );
+    }
+
+    #[test]
+    fn mistakes_in_unused_profiles_are_errors() {
+        let text = "jobs = 2\n[profile.ci]\nretry = 2\n";
+        assert_eq!(
+            load("unused-profile", text, None).unwrap_err(),
+            "in `profile.ci`: unknown key `retry`"
+        );
+        assert_eq!(
+            load("bad-profile", "[profile]\nci = 1\n", None).unwrap_err(),
+            "`profile.ci` must be a table"
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        for (text, message) in [
+            ("jobs = 0", "`jobs` must be a positive number, not 0"),
+            (
+                "timeout = \"soon\"",
+                "`timeout` must be a duration such as \"500ms\", \"30s\", or \"2m\", not \"soon\"",
+            ),
+            ("retries = -1", "`retries` must be a number, not -1"),
+            (
+                "fail-fast = 1",
+                "`fail-fast` must be `true` or `false`, not 1",
+            ),
+            (
+                "format = \"xml\"",
+                "invalid `format` value `xml`; expected tree, pretty, terse, json, tap, or plain",
+            ),
+            ("tags = [1]", "`tags` must be a list of strings, not [1]"),
+            ("colour = \"never\"", "unknown key `colour`"),
+        ] {
+            assert_eq!(
+                load("invalid", text, None).unwrap_err(),
+                message,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        let path = std::env::temp_dir().join(format!("tust-config-missing-{}", std::process::id()));
+        let err = Config::load(&path, None).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(&format!("{}: ", path.display())),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn the_configuration_sets_only_what_it_sets() {
+        let config = load("apply", "retries = 3\nslowest = 0\n", None).unwrap();
+        let mut args = Args {
+            jobs: Some(4),
+            retries: 1,
+            ..Args::default()
+        };
+        config.apply(&mut args);
+        assert_eq!(args.retries, 3);
+        assert_eq!(args.slowest, Some(0));
+        assert_eq!(args.jobs, Some(4));
+        assert_eq!(args.timeout, None);
+    }
+
+    #[test]
+    fn the_file_is_found_in_a_directory_above() {
+        let root = std::env::temp_dir().join(format!("tust-config-find-{}", std::process::id()));
+        let nested = root.join("crates").join("inner");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find(&nested), None);
+        fs::write(root.join(FILE_NAME), "").unwrap();
+        assert_eq!(find(&nested), Some(root.join(FILE_NAME)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,644 @@
+//! Docker containers for tests of code that talks to real services, such as
+//! a database or a message broker.
+//!
+//! [`Container::builder`] describes the container: its image, environment,
+//! the ports to publish, and what shows that it is ready. [`start`] runs it
+//! with the `docker` command line, waits for it to be ready, and returns a
+//! [`Container`] telling the test where to connect. The container is
+//! removed once the last clone of the value is dropped.
+//!
+//! Starting a container takes seconds, so a session-scoped fixture usually
+//! provides it, started once per run and shared by every test that asks for
+//! it:
+//!
+//! ```ignore
+//! use tust::container::{Container, Wait};
+//!
+//! #[fixture(scope = "session")]
+//! fn postgres() -> Container {
+//!     Container::builder("postgres:16")
+//!         .env("POSTGRES_PASSWORD", "secret")
+//!         .port(5432)
+//!         .wait_for(Wait::log("database system is ready to accept connections"))
+//!         .start()
+//!         .expect("cannot start postgres")
+//! }
+//!
+//! #[tust::test]
+//! fn connects(postgres: Container) {
+//!     let url = format!("postgres://postgres:secret@{}/postgres", postgres.address(5432));
+//!     // ...
+//! }
+//! ```
+//!
+//! With `--process-per-test`, each test's process starts its own session
+//! fixtures, and so its own container.
+//!
+//! Containers are labelled with the process that started them. Should that
+//! process die without removing them, say because the runner killed it on a
+//! timeout, the next container started on the same machine removes them.
+//!
+//! [`DOCKER_ENV`] names another command line compatible with `docker`, such
+//! as `podman`. When `DOCKER_HOST` points at a remote daemon over TCP,
+//! published ports are reached on that host.
+//!
+//! [`start`]: ContainerBuilder::start
+
+use std::fmt;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Once};
+use std::thread;
+use std::time::Duration;
+
+use web_time::Instant;
+
+/// Environment variable naming the command that runs containers; `docker`
+/// if unset.
+pub const DOCKER_ENV: &str = "TUST_DOCKER";
+
+/// Label marking the containers tust starts with their owner, as
+/// `<host>/<pid>`.
+const OWNER_LABEL: &str = "dev.tust.owner";
+
+/// How long [`ContainerBuilder::start`] waits for a container to be ready
+/// unless told otherwise.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often readiness is checked while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What shows that a container is ready to be used.
+#[derive(Debug, Clone)]
+pub enum Wait {
+    /// The container's stdout or stderr contains the text.
+    Log(String),
+    /// The published container port accepts TCP connections.
+    Port(u16),
+    /// The time has passed since the container started.
+    Delay(Duration),
+}
+
+impl Wait {
+    /// Waits until the container's output contains `text`.
+    pub fn log(text: impl Into<String>) -> Self {
+        Wait::Log(text.into())
+    }
+
+    /// Waits until the published container port `port` accepts connections.
+    pub fn port(port: u16) -> Self {
+        Wait::Port(port)
+    }
+}
+
+/// A container to start; see [`Container::builder`].
+#[derive(Debug, Clone)]
+pub struct ContainerBuilder {
+    image: String,
+    env: Vec<(String, String)>,
+    ports: Vec<u16>,
+    command: Vec<String>,
+    waits: Vec<Wait>,
+    startup_timeout: Duration,
+}
+
+impl ContainerBuilder {
+    /// Sets the environment variable `key` in the container.
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Publishes the container's TCP port `port` on a free port of the host;
+    /// [`Container::port`] tells which.
+    pub fn port(mut self, port: u16) -> Self {
+        self.ports.push(port);
+        self
+    }
+
+    /// Runs `command` instead of the image's default command.
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Waits for `wait` before the container counts as started. Several
+    /// waits are met in the order given.
+    pub fn wait_for(mut self, wait: Wait) -> Self {
+        self.waits.push(wait);
+        self
+    }
+
+    /// How long to wait for the container to be ready, not counting pulling
+    /// its image; 60 seconds unless set.
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Starts the container and waits for it to be ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `docker` command cannot be run or fails, or
+    /// if the container exits or is not ready within the startup timeout; the
+    /// container is removed then.
+    pub fn start(self) -> io::Result<Container> {
+        static REAP: Once = Once::new();
+        REAP.call_once(reap_orphans);
+
+        let mut args: Vec<String> = vec![
+            "run".into(),
+            "--detach".into(),
+            "--label".into(),
+            format!("{}={}", OWNER_LABEL, owner()),
+        ];
+        for (key, value) in &self.env {
+            args.extend(["--env".into(), format!("{}={}", key, value)]);
+        }
+        for port in &self.ports {
+            args.extend(["--publish".into(), port.to_string()]);
+        }
+        args.push(self.image.clone());
+        args.extend(self.command.iter().cloned());
+        let id = docker(&args)?.trim().to_string();
+        let started = Instant::now();
+        let mut container = Container {
+            inner: Arc::new(Inner {
+                id,
+                image: self.image,
+                host: host(),
+                ports: Vec::with_capacity(self.ports.len()),
+            }),
+        };
+        let inner = Arc::get_mut(&mut container.inner).expect("the container is not shared yet");
+        for &port in &self.ports {
+            let published = docker(&["port", &inner.id, &format!("{}/tcp", port)])?;
+            let host_port = published
+                .lines()
+                .find_map(|line| line.rsplit(':').next()?.trim().parse().ok())
+                .ok_or_else(|| {
+                    error(format!(
+                        "cannot tell where port {} of {} is published: {:?}",
+                        port, inner.image, published
+                    ))
+                })?;
+            inner.ports.push((port, host_port));
+        }
+        let deadline = started + self.startup_timeout;
+        for wait in &self.waits {
+            container.wait(wait, started, deadline)?;
+        }
+        Ok(container)
+    }
+}
+
+/// A running container, removed when the last clone of it is dropped.
+#[derive(Clone)]
+pub struct Container {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: String,
+    image: String,
+    /// Where published ports are reached.
+    host: String,
+    /// Each published container port, with the host port it is published on.
+    ports: Vec<(u16, u16)>,
+}
+
+impl Container {
+    /// Describes a container running `image`, such as `"redis:7"`.
+    pub fn builder(image: impl Into<String>) -> ContainerBuilder {
+        ContainerBuilder {
+            image: image.into(),
+            env: Vec::new(),
+            ports: Vec::new(),
+            command: Vec::new(),
+            waits: Vec::new(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+        }
+    }
+
+    /// The container's ID.
+    pub fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    pub fn image(&self) -> &str {
+        &self.inner.image
+    }
+
+    /// The host on which published ports are reached, usually `127.0.0.1`.
+    pub fn host(&self) -> &str {
+        &self.inner.host
+    }
+
+    /// The host port on which the container's `port` is published.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` was not published with [`ContainerBuilder::port`].
+    #[track_caller]
+    pub fn port(&self, port: u16) -> u16 {
+        self.inner
+            .ports
+            .iter()
+            .find(|(container_port, _)| *container_port == port)
+            .map(|&(_, host_port)| host_port)
+            .unwrap_or_else(|| {
+                panic!(
+                    "port {} of {} is not published; publish it with `.port({})`",
+                    port, self.inner.image, port
+                )
+            })
+    }
+
+    /// `host:port` at which the container's `port` is reached, such as
+    /// `127.0.0.1:49153`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` was not published with [`ContainerBuilder::port`].
+    #[track_caller]
+    pub fn address(&self, port: u16) -> String {
+        format!("{}:{}", self.inner.host, self.port(port))
+    }
+
+    /// What the container has printed so far, stdout and stderr together.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `docker logs` fails.
+    pub fn logs(&self) -> io::Result<String> {
+        let output = run(&["logs", &self.inner.id])?;
+        let mut logs = String::from_utf8_lossy(&output.stdout).into_owned();
+        logs.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(logs)
+    }
+
+    /// Waits until `wait` is met, the container exits, or `deadline` passes.
+    fn wait(&self, wait: &Wait, started: Instant, deadline: Instant) -> io::Result<()> {
+        loop {
+            let ready = match wait {
+                Wait::Log(text) => self.logs()?.contains(text.as_str()),
+                Wait::Port(port) => {
+                    let address = self.address(*port);
+                    address
+                        .to_socket_addrs()?
+                        .any(|address| TcpStream::connect_timeout(&address, POLL_INTERVAL).is_ok())
+                }
+                Wait::Delay(delay) => started.elapsed() >= *delay,
+            };
+            if ready {
+                return Ok(());
+            }
+            let running = docker(&["inspect", "--format", "{{.State.Running}}", self.id()])?;
+            if running.trim() != "true" {
+                return Err(self.not_ready("exited", wait));
+            }
+            if Instant::now() >= deadline {
+                return Err(self.not_ready("timed out", wait));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn not_ready(&self, what: &str, wait: &Wait) -> io::Error {
+        let waiting_for = match wait {
+            Wait::Log(text) => format!("its output to contain {:?}", text),
+            Wait::Port(port) => format!("port {} to accept connections", port),
+            Wait::Delay(delay) => format!("{:?} to pass", delay),
+        };
+        let logs = self.logs().unwrap_or_default();
+        error(format!(
+            "container {} of {} {} while waiting for {}; its output:\n{}",
+            self.inner.id, self.inner.image, what, waiting_for, logs
+        ))
+    }
+}
+
+impl fmt::Debug for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Container")
+            .field("id", &self.inner.id)
+            .field("image", &self.inner.image)
+            .field("ports", &self.inner.ports)
+            .finish()
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Err(err) = docker(&["rm", "--force", "--volumes", &self.id]) {
+            eprintln!(
+                "warning: cannot remove container {} of {}: {}",
+                self.id, self.image, err
+            );
+        }
+    }
+}
+
+fn error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+/// The command running containers.
+fn program() -> String {
+    std::env::var(DOCKER_ENV)
+        .ok()
+        .filter(|program| !program.is_empty())
+        .unwrap_or_else(|| "docker".to_string())
+}
+
+fn run<S: AsRef<str>>(args: &[S]) -> io::Result<Output> {
+    let program = program();
+    Command::new(&program)
+        .args(args.iter().map(AsRef::as_ref))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| error(format!("cannot run `{}`: {}", program, err)))
+}
+
+/// Runs the container command with `args`, and returns its stdout.
+fn docker<S: AsRef<str>>(args: &[S]) -> io::Result<String> {
+    let output = run(args)?;
+    if !output.status.success() {
+        let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+        return Err(error(format!(
+            "`{} {}` failed: {}",
+            program(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Where published ports are reached: the host of a `tcp://` `DOCKER_HOST`,
+/// or else this machine.
+fn host() -> String {
+    std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|url| {
+            let rest = url.strip_prefix("tcp://")?;
+            let authority = rest.split('/').next()?;
+            let host = match authority.rsplit_once(':') {
+                Some((host, _port)) => host,
+                None => authority,
+            };
+            (!host.is_empty()).then(|| host.to_string())
+        })
+        .unwrap_or_else(|| "127.0.0.1".to_string())
+}
+
+/// The value of [`OWNER_LABEL`] for this process.
+fn owner() -> String {
+    format!("{}/{}", imp::hostname(), std::process::id())
+}
+
+/// Removes the containers started on this machine by processes that are no
+/// longer running.
+fn reap_orphans() {
+    let filter = format!("label={}", OWNER_LABEL);
+    let format = format!("{{{{.ID}}}} {{{{.Label \"{}\"}}}}", OWNER_LABEL);
+    let Ok(listing) = docker(&["ps", "--all", "--filter", &filter, "--format", &format]) else {
+        return;
+    };
+    let hostname = imp::hostname();
+    for line in listing.lines() {
+        let Some((id, owner)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some((host, pid)) = owner.rsplit_once('/') else {
+            continue;
+        };
+        let Ok(pid) = pid.parse::<u32>() else {
+            continue;
+        };
+        if host == hostname && pid != std::process::id() && !imp::is_running(pid) {
+            let _ = docker(&["rm", "--force", "--volumes", id]);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    pub(super) fn hostname() -> String {
+        let mut buffer = [0u8; 256];
+        // SAFETY: `buffer` is valid for writes of its length.
+        if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+            return String::new();
+        }
+        let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        String::from_utf8_lossy(&buffer[..end]).into_owned()
+    }
+
+    pub(super) fn is_running(pid: u32) -> bool {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return true;
+        };
+        // SAFETY: signal 0 only checks that the process exists.
+        let exists = unsafe { libc::kill(pid, 0) } == 0;
+        exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub(super) fn hostname() -> String {
+        std::env::var("COMPUTERNAME").unwrap_or_default()
+    }
+
+    /// Whether a process runs cannot be told here, so orphans are kept.
+    pub(super) fn is_running(_pid: u32) -> bool {
+        true
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    /// A stand-in for `docker` that records its arguments and answers
+    /// `inspect` with the contents of `running` and `ps` with those of `ps`.
+    const FAKE_DOCKER: &str = r#"#!/bin/sh
+dir=$(dirname "$0")
+echo "$*" >> "$dir/calls"
+case "$1" in
+    run) echo c0ffee ;;
+    port) echo "0.0.0.0:49153" ;;
+    logs) echo "ready to accept connections" ;;
+    inspect) cat "$dir/running" ;;
+    ps) cat "$dir/ps" ;;
+esac
+"#;
+
+    /// Tests that point [`DOCKER_ENV`] at a fake take turns.
+    static DOCKER: Mutex<()> = Mutex::new(());
+
+    struct FakeDocker {
+        dir: PathBuf,
+        _turn: MutexGuard<'static, ()>,
+    }
+
+    impl FakeDocker {
+        fn new(name: &str) -> Self {
+            let turn = DOCKER.lock().unwrap_or_else(PoisonError::into_inner);
+            let dir = std::env::temp_dir().join(format!(
+                "tust-container-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let script = dir.join("docker");
+            fs::write(&script, FAKE_DOCKER).unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            fs::write(dir.join("running"), "true\n").unwrap();
+            std::env::set_var(DOCKER_ENV, &script);
+            FakeDocker { dir, _turn: turn }
+        }
+
+        /// The commands run so far, but for the listing of orphans that the
+        /// first container of the process starts with.
+        fn calls(&self) -> Vec<String> {
+            fs::read_to_string(self.dir.join("calls"))
+                .unwrap_or_default()
+                .lines()
+                .filter(|call| !call.starts_with("ps "))
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    impl Drop for FakeDocker {
+        fn drop(&mut self) {
+            std::env::remove_var(DOCKER_ENV);
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn starts_the_described_container() {
+        let docker = FakeDocker::new("start");
+        let container = Container::builder("postgres:16")
+            .env("POSTGRES_PASSWORD", "secret")
+            .port(5432)
+            .command(["postgres", "-c", "fsync=off"])
+            .wait_for(Wait::log("ready to accept connections"))
+            .start()
+            .unwrap();
+        assert_eq!(container.id(), "c0ffee");
+        assert_eq!(container.port(5432), 49153);
+        assert_eq!(container.address(5432), format!("{}:49153", host()));
+        let calls = docker.calls();
+        assert_eq!(
+            calls[0],
+            format!(
+                "run --detach --label {}={} --env POSTGRES_PASSWORD=secret --publish 5432 \
+                 postgres:16 postgres -c fsync=off",
+                OWNER_LABEL,
+                owner()
+            )
+        );
+        assert_eq!(calls[1], "port c0ffee 5432/tcp");
+        drop(container);
+    }
+
+    #[test]
+    fn clones_share_the_container_until_the_last_is_dropped() {
+        let docker = FakeDocker::new("clones");
+        let container = Container::builder("redis:7").start().unwrap();
+        let clone = container.clone();
+        drop(container);
+        assert!(!docker.calls().iter().any(|call| call.starts_with("rm ")));
+        assert_eq!(clone.id(), "c0ffee");
+        drop(clone);
+        let removals: Vec<String> = docker
+            .calls()
+            .into_iter()
+            .filter(|call| call.starts_with("rm "))
+            .collect();
+        assert_eq!(removals, ["rm --force --volumes c0ffee"]);
+    }
+
+    #[test]
+    fn a_container_that_exits_before_it_is_ready_is_removed() {
+        let docker = FakeDocker::new("exits");
+        fs::write(docker.dir.join("running"), "false\n").unwrap();
+        let err = Container::builder("redis:7")
+            .wait_for(Wait::log("never printed"))
+            .start()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "container c0ffee of redis:7 exited while waiting for its output to contain \
+             \"never printed\"; its output:\nready to accept connections\n"
+        );
+        assert_eq!(
+            docker.calls().last().unwrap(),
+            "rm --force --volumes c0ffee"
+        );
+    }
+
+    #[test]
+    fn a_container_that_is_not_ready_in_time_is_removed() {
+        let docker = FakeDocker::new("timeout");
+        let err = Container::builder("redis:7")
+            .wait_for(Wait::log("never printed"))
+            .startup_timeout(Duration::ZERO)
+            .start()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(" timed out while waiting for "),
+            "{}",
+            err
+        );
+        assert_eq!(
+            docker.calls().last().unwrap(),
+            "rm --force --volumes c0ffee"
+        );
+    }
+
+    #[test]
+    fn orphans_of_exited_processes_on_this_machine_are_removed() {
+        let docker = FakeDocker::new("orphans");
+        let mut exited = Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        let hostname = imp::hostname();
+        let listing = [
+            format!("dead {}/{}", hostname, exited.id()),
+            format!("mine {}/{}", hostname, std::process::id()),
+            format!("remote elsewhere-{}/{}", hostname, exited.id()),
+            "unlabelled ".to_string(),
+        ];
+        fs::write(docker.dir.join("ps"), listing.join("\n")).unwrap();
+        reap_orphans();
+        assert_eq!(docker.calls(), ["rm --force --volumes dead"]);
+    }
+
+    #[test]
+    fn unpublished_ports_panic_with_a_hint() {
+        let docker = FakeDocker::new("unpublished");
+        let container = Container::builder("redis:7").start().unwrap();
+        let failure = crate::panic::catch(|| container.port(6379)).unwrap_err();
+        assert_eq!(
+            failure.message,
+            "port 6379 of redis:7 is not published; publish it with `.port(6379)`"
+        );
+        drop(container);
+        drop(docker);
+    }
+}
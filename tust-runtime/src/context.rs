@@ -0,0 +1,587 @@
+//! Built-in `ctx` fixture: the running test's [`TestContext`].
+//!
+//! A test parameter named `ctx` of type [`TestContext`] receives the context
+//! of the test, and helper libraries called from a test reach the same one
+//! with [`TestContext::current`], without the test handing it down. The
+//! context names the test and its tags, and gives it
+//!
+//! - an [artifact directory](TestContext::artifact_dir), for files to look at
+//!   after the run, such as screenshots or logs: `tust/artifacts/<test>` in
+//!   Cargo's target directory, or under the directory [`ARTIFACT_DIR_ENV`]
+//!   names. It is emptied the first time a run asks for it, and kept after;
+//! - a [scratch directory](TestContext::scratch_dir), a [`TempDir`] removed
+//!   when the test ends;
+//! - [random numbers](TestContext::rng), as [`rng`](crate::rng::rng) gives;
+//! - [cleanups](TestContext::add_cleanup), to undo what the test changed,
+//!   such as global state a helper set up, without a fixture to drop;
+//! - [attachments](TestContext::attach): files written to its artifact
+//!   directory that the reports of the run link to, such as the screenshot
+//!   of a browser test or the images an image comparison differed on;
+//! - [steps](TestContext::step): named phases of the test, timed, that a
+//!   failure during one is attributed to.
+//!
+//! ```ignore
+//! /// Points the global logger at a file of the test's, until the test ends.
+//! pub fn capture_logs() -> PathBuf {
+//!     let ctx = TestContext::current().expect("capture_logs is for tests");
+//!     let path = ctx.artifact_dir().join("log.txt");
+//!     logger::set_output(&path);
+//!     ctx.add_cleanup(logger::reset);
+//!     path
+//! }
+//! ```
+//!
+//! Cleanups run once the test's body returns, whether it passed or not,
+//! before its `after_each` hooks, the most recently added first. One that
+//! panics fails the test, and the others still run.
+//!
+//! The HTML report links to a test's attachments and shows those that are
+//! images, the JUnit report lists them in the `[[ATTACHMENT|path]]` lines
+//! CI servers read from `system-out`, and the JSON events of tests carry
+//! them under `attachments`, as do [`TestOutcome`](crate::TestOutcome)s for
+//! reporters of their own.
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn renders_the_login_page(ctx: TestContext) {
+//!     let page = browser().open("/login");
+//!     ctx.attach("login.png", page.screenshot());
+//!     assert!(page.contains("Sign in"));
+//! }
+//! ```
+//!
+//! Steps tell where a slow test spends its time, and where it failed. A
+//! failure during a step, whether the test panicked or recorded a soft
+//! failure, notes the step under `step` in its metadata, with the steps it
+//! is nested in, as in `import > load data`; so does the failure of a test
+//! that timed out during one, unless it ran in a process of its own. The
+//! tree the console prints lists the steps of each test below it, with their
+//! durations, and the JSON, JUnit, and HTML reports carry them, as do
+//! [`TestOutcome`](crate::TestOutcome)s.
+//!
+//! ```ignore
+//! #[tust::test]
+//! async fn imports_the_catalog(ctx: TestContext) {
+//!     let rows = ctx.step("load data", || read_csv("catalog.csv"));
+//!     let db = ctx.step_async("start database", Database::start()).await;
+//!     ctx.step_async("import", db.import(&rows)).await;
+//! }
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::alloc;
+use crate::cache;
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+use crate::outcome::TestFailure;
+use crate::panic;
+use crate::registry::{self, TestDef};
+use crate::rng::{self, Rng};
+use crate::temp::TempDir;
+
+/// Environment variable naming the directory holding the artifact
+/// directories of tests.
+pub const ARTIFACT_DIR_ENV: &str = "TUST_ARTIFACT_DIR";
+
+inventory::submit! {
+    FixtureDef {
+        name: "ctx",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<TestContext>,
+        type_id: TypeId::of::<TestContext>,
+        provider: Provider::PerTest(provide_context),
+    }
+}
+
+fn provide_context(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let ctx = TestContext::current().expect("the `ctx` fixture is only given to a running test");
+    Ok(Box::new(ctx))
+}
+
+/// What the context keeps for each running test.
+#[derive(Default)]
+struct State {
+    cleanups: Vec<Cleanup>,
+    scratch: Option<TempDir>,
+}
+
+struct Cleanup {
+    run: Box<dyn FnOnce() + Send>,
+    added_at: &'static std::panic::Location<'static>,
+}
+
+static STATES: Mutex<Vec<(&'static TestDef, State)>> = Mutex::new(Vec::new());
+
+/// Attachments of the tests running, until their outcome takes them.
+static ATTACHMENTS: Mutex<Vec<(&'static TestDef, Attachment)>> = Mutex::new(Vec::new());
+
+/// Steps of the tests running, in the order they started, until their
+/// outcome takes them.
+static STEPS: Mutex<Vec<Recording>> = Mutex::new(Vec::new());
+
+/// A step as it is recorded, with when it started while it runs.
+struct Recording {
+    test: &'static TestDef,
+    id: u64,
+    step: Step,
+    started: Option<Instant>,
+}
+
+/// Artifact directories emptied so far in this run.
+static ARTIFACT_DIRS: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// The context of a running test. Copies refer to the same test.
+#[derive(Clone, Copy)]
+pub struct TestContext {
+    test: &'static TestDef,
+}
+
+impl TestContext {
+    /// The context of the test running on this thread, if any.
+    pub fn current() -> Option<TestContext> {
+        registry::current().map(|test| TestContext { test })
+    }
+
+    /// The test's registry entry.
+    pub fn test(&self) -> &'static TestDef {
+        self.test
+    }
+
+    /// The name of the test function.
+    pub fn name(&self) -> &'static str {
+        self.test.name
+    }
+
+    /// The full name of the test, as [`TestDef::path`] gives it.
+    pub fn path(&self) -> String {
+        self.test.path()
+    }
+
+    pub fn tags(&self) -> &'static [&'static str] {
+        self.test.tags
+    }
+
+    /// The test's artifact directory, created if need be, and emptied the
+    /// first time this run asks for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be created or emptied.
+    pub fn artifact_dir(&self) -> PathBuf {
+        let root = cache::path(ARTIFACT_DIR_ENV, "artifacts")
+            .unwrap_or_else(|| std::env::temp_dir().join("tust-artifacts"));
+        let dir = self.test.path().split("::").fold(root, |dir, part| {
+            let part: String = part
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '_' || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            dir.join(part)
+        });
+        let first = alloc::untracked(|| {
+            ARTIFACT_DIRS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_or_insert_with(HashSet::new)
+                .insert(dir.clone())
+        });
+        if first && dir.exists() {
+            fs::remove_dir_all(&dir).unwrap_or_else(|err| {
+                panic!(
+                    "cannot empty the artifact directory {}: {}",
+                    dir.display(),
+                    err
+                )
+            });
+        }
+        fs::create_dir_all(&dir).unwrap_or_else(|err| {
+            panic!(
+                "cannot create the artifact directory {}: {}",
+                dir.display(),
+                err
+            )
+        });
+        dir
+    }
+
+    /// The test's scratch directory, the same for the whole test, created
+    /// on first use and removed, after the cleanups have run, when the test
+    /// ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be created.
+    pub fn scratch_dir(&self) -> PathBuf {
+        alloc::untracked(|| {
+            self.with_state(|state| {
+                let scratch = state.scratch.get_or_insert_with(|| {
+                    TempDir::new()
+                        .unwrap_or_else(|err| panic!("cannot create a scratch directory: {}", err))
+                });
+                scratch.path().to_path_buf()
+            })
+        })
+    }
+
+    /// Writes `contents` to the file `name` of the test's artifact directory
+    /// and attaches it to the test's outcome, returning its path. `name` may
+    /// name a file in a subdirectory, such as `pages/login.png`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a relative path within the directory, or if
+    /// the file cannot be written.
+    #[track_caller]
+    pub fn attach(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.attachment_path(name);
+        fs::write(&path, contents)
+            .unwrap_or_else(|err| panic!("cannot write {}: {}", path.display(), err));
+        self.record(name, &path);
+        path
+    }
+
+    /// Copies the file at `path` into the test's artifact directory, under
+    /// its file name, and attaches the copy to the test's outcome, returning
+    /// its path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` has no file name, or if the file cannot be copied.
+    #[track_caller]
+    pub fn attach_file(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .unwrap_or_else(|| panic!("cannot attach {}: it has no file name", path.display()))
+            .to_string_lossy()
+            .into_owned();
+        let copy = self.attachment_path(&name);
+        fs::copy(path, &copy).unwrap_or_else(|err| {
+            panic!(
+                "cannot copy {} to {}: {}",
+                path.display(),
+                copy.display(),
+                err
+            )
+        });
+        self.record(&name, &copy);
+        copy
+    }
+
+    /// The path of the attachment `name`, with its directory created.
+    #[track_caller]
+    fn attachment_path(&self, name: &str) -> PathBuf {
+        let relative = Path::new(name);
+        let within = relative
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)));
+        if name.is_empty() || !within {
+            panic!(
+                "cannot attach `{}`: attachments are named by a relative path within the \
+                 artifact directory",
+                name
+            );
+        }
+        let path = self.artifact_dir().join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|err| panic!("cannot create {}: {}", parent.display(), err));
+        }
+        path
+    }
+
+    fn record(&self, name: &str, path: &Path) {
+        let attachment = Attachment {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+        };
+        alloc::untracked(|| {
+            let mut attachments = ATTACHMENTS.lock().unwrap_or_else(|e| e.into_inner());
+            // Attaching a file again replaces it.
+            attachments.retain(|(test, attached)| {
+                !(std::ptr::eq(*test, self.test) && attached.path == attachment.path)
+            });
+            attachments.push((self.test, attachment));
+        });
+    }
+
+    /// Runs `f` as the step `name` of the test, timing it, and returns what
+    /// it returns; see [the module](self). Steps may be nested.
+    pub fn step<R>(&self, name: &str, f: impl FnOnce() -> R) -> R {
+        let _step = self.start_step(name);
+        f()
+    }
+
+    /// Like [`step`](Self::step), for a future, which the step awaits.
+    pub async fn step_async<F: Future>(&self, name: &str, future: F) -> F::Output {
+        let _step = self.start_step(name);
+        future.await
+    }
+
+    fn start_step(&self, name: &str) -> StepGuard {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        alloc::untracked(|| {
+            let mut steps = STEPS.lock().unwrap_or_else(|e| e.into_inner());
+            let depth = steps
+                .iter()
+                .filter(|recording| {
+                    std::ptr::eq(recording.test, self.test) && recording.started.is_some()
+                })
+                .count();
+            steps.push(Recording {
+                test: self.test,
+                id,
+                step: Step {
+                    name: name.to_string(),
+                    depth,
+                    duration: Duration::ZERO,
+                    failed: false,
+                },
+                started: Some(Instant::now()),
+            });
+        });
+        StepGuard { id }
+    }
+
+    /// A generator for the test; see [`rng`](crate::rng::rng).
+    pub fn rng(&self) -> Rng {
+        rng::rng()
+    }
+
+    /// Runs `cleanup` when the test ends; see [the module](self).
+    #[track_caller]
+    pub fn add_cleanup(&self, cleanup: impl FnOnce() + Send + 'static) {
+        let cleanup = Cleanup {
+            run: Box::new(cleanup),
+            added_at: std::panic::Location::caller(),
+        };
+        alloc::untracked(|| self.with_state(|state| state.cleanups.push(cleanup)));
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut states = STATES.lock().unwrap_or_else(|e| e.into_inner());
+        let index = match states
+            .iter()
+            .position(|(test, _)| std::ptr::eq(*test, self.test))
+        {
+            Some(index) => index,
+            None => {
+                states.push((self.test, State::default()));
+                states.len() - 1
+            }
+        };
+        f(&mut states[index].1)
+    }
+}
+
+/// A file a test attached to its outcome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The name the test gave it, such as `screenshot.png`.
+    pub name: String,
+    /// Where it is, in the test's artifact directory.
+    pub path: PathBuf,
+}
+
+/// A named phase of a test, timed with [`TestContext::step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The name the test gave it, such as `load data`.
+    pub name: String,
+    /// How many steps it ran within: 0 for a step of the test itself.
+    pub depth: usize,
+    pub duration: Duration,
+    /// Whether the test failed during the step, or ended before it did.
+    pub failed: bool,
+}
+
+/// Ends a step when dropped, failing it if the test is panicking.
+struct StepGuard {
+    id: u64,
+}
+
+impl Drop for StepGuard {
+    fn drop(&mut self) {
+        let panicking = std::thread::panicking();
+        alloc::untracked(|| {
+            let mut steps = STEPS.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(recording) = steps.iter_mut().find(|recording| recording.id == self.id) {
+                if let Some(started) = recording.started.take() {
+                    recording.step.duration = started.elapsed();
+                }
+                recording.step.failed |= panicking;
+            }
+        });
+    }
+}
+
+impl fmt::Debug for TestContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestContext")
+            .field("test", &self.test.path())
+            .finish()
+    }
+}
+
+/// Runs the cleanups of `test`, most recent first, and removes its scratch
+/// directory, returning the failures of the cleanups that panicked.
+pub(crate) fn finish(test: &'static TestDef) -> Vec<TestFailure> {
+    let ctx = TestContext { test };
+    let mut failures = Vec::new();
+    // Cleanups may add cleanups of their own, so take them one at a time.
+    while let Some(cleanup) = alloc::untracked(|| ctx.with_state(|state| state.cleanups.pop())) {
+        if let Err(mut failure) = panic::catch(cleanup.run) {
+            failure.message = format!(
+                "cleanup added at {}:{} failed: {}",
+                cleanup.added_at.file(),
+                cleanup.added_at.line(),
+                failure.message
+            );
+            failures.push(failure);
+        }
+    }
+    alloc::untracked(|| {
+        let state = {
+            let mut states = STATES.lock().unwrap_or_else(|e| e.into_inner());
+            states
+                .iter()
+                .position(|(of, _)| std::ptr::eq(*of, test))
+                .map(|index| states.swap_remove(index).1)
+        };
+        drop(state);
+    });
+    failures
+}
+
+/// Removes the attachments of `test` recorded so far, and returns them.
+pub(crate) fn take_attachments(test: &TestDef) -> Vec<Attachment> {
+    let mut attachments = ATTACHMENTS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut taken = Vec::new();
+    attachments.retain(|(of, attachment)| {
+        let mine = std::ptr::eq(*of, test);
+        if mine {
+            taken.push(attachment.clone());
+        }
+        !mine
+    });
+    taken
+}
+
+pub(crate) fn to_json(attachments: &[Attachment]) -> Value {
+    attachments
+        .iter()
+        .map(|attachment| json!({ "name": attachment.name, "path": attachment.path }))
+        .collect()
+}
+
+/// Records the attachments a child process running `test` handed over.
+pub(crate) fn record_json(test: &'static TestDef, attachments: &Value) {
+    for attachment in attachments.as_array().into_iter().flatten() {
+        if let (Some(name), Some(path)) = (attachment["name"].as_str(), attachment["path"].as_str())
+        {
+            TestContext { test }.record(name, Path::new(path));
+        }
+    }
+}
+
+/// Notes the steps `test` is running in `failure`'s metadata, as `step`,
+/// outermost first, as in `import > load data`, unless it notes a step
+/// already; `fail` also fails them.
+pub(crate) fn note_step(test: &TestDef, failure: &mut TestFailure, fail: bool) {
+    alloc::untracked(|| {
+        let mut steps = STEPS.lock().unwrap_or_else(|e| e.into_inner());
+        let running: Vec<String> = steps
+            .iter_mut()
+            .filter(|recording| std::ptr::eq(recording.test, test) && recording.started.is_some())
+            .map(|recording| {
+                recording.step.failed |= fail;
+                recording.step.name.clone()
+            })
+            .collect();
+        let noted = failure.metadata.iter().any(|(label, _)| label == "step");
+        if !running.is_empty() && !noted {
+            failure
+                .metadata
+                .push(("step".to_string(), running.join(" > ")));
+        }
+    })
+}
+
+/// Removes the steps of `test` recorded so far, and returns them. Steps
+/// still running, as those of a test that timed out, end failed.
+pub(crate) fn take_steps(test: &TestDef) -> Vec<Step> {
+    let mut steps = STEPS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut taken = Vec::new();
+    steps.retain(|recording| {
+        let mine = std::ptr::eq(recording.test, test);
+        if mine {
+            let mut step = recording.step.clone();
+            if let Some(started) = recording.started {
+                step.duration = started.elapsed();
+                step.failed = true;
+            }
+            taken.push(step);
+        }
+        !mine
+    });
+    taken
+}
+
+pub(crate) fn steps_to_json(steps: &[Step]) -> Value {
+    steps
+        .iter()
+        .map(|step| {
+            json!({
+                "name": step.name,
+                "depth": step.depth,
+                "exec_time": step.duration.as_secs_f64(),
+                "failed": step.failed,
+            })
+        })
+        .collect()
+}
+
+/// Records the steps a child process running `test` handed over.
+pub(crate) fn record_steps_json(test: &'static TestDef, steps: &Value) {
+    let mut recorded = STEPS.lock().unwrap_or_else(|e| e.into_inner());
+    for step in steps.as_array().into_iter().flatten() {
+        let Some(name) = step["name"].as_str() else {
+            continue;
+        };
+        recorded.push(Recording {
+            test,
+            id: u64::MAX,
+            step: Step {
+                name: name.to_string(),
+                depth: step["depth"].as_u64().unwrap_or(0) as usize,
+                duration: step["exec_time"]
+                    .as_f64()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .unwrap_or_default(),
+                failed: step["failed"].as_bool() == Some(true),
+            },
+            started: None,
+        });
+    }
+}
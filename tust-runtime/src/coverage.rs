@@ -0,0 +1,564 @@
+//! Selecting tests by the source files they cover.
+//!
+//! `--record-coverage` runs each test in its own process, as
+//! `--process-per-test` does, with LLVM's profile written to a directory of
+//! the test's own, and then asks `llvm-cov` which source files the test
+//! executed. The files of every test are kept in `tust/coverage.json` in
+//! Cargo's target directory, or the file named by [`MAP_ENV`]; tests that did
+//! not run keep the files recorded for them before.
+//!
+//! The test binary must be built with LLVM's source-based coverage, and the
+//! LLVM tools matching the compiler installed:
+//!
+//! ```text
+//! rustup component add llvm-tools-preview
+//! RUSTFLAGS="-C instrument-coverage" cargo test --test integration -- --record-coverage
+//! ```
+//!
+//! `llvm-profdata` and `llvm-cov` are looked for in the environment variables
+//! `LLVM_PROFDATA` and `LLVM_COV`, then among the compiler's own tools, then
+//! on the `PATH`.
+//!
+//! `--changed-since REF` then runs only the tests that covered a file that
+//! differs from the Git revision `REF`, committed or not, along with the
+//! tests that no coverage was recorded for.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::outcome::TestOutcome;
+use crate::registry::TestDef;
+use crate::timings;
+
+/// Environment variable naming the coverage map; if set but empty, coverage
+/// is neither read nor recorded.
+pub const MAP_ENV: &str = "TUST_COVERAGE_MAP";
+
+/// Whether this run records coverage, set by the runner for
+/// `--record-coverage`.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn start_recording() {
+    RECORDING.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// The source files each test executed, as absolute paths.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageMap {
+    tests: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl CoverageMap {
+    /// Reads the map recorded in `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tests = value["tests"].as_object().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "expected a `tests` object")
+        })?;
+        Ok(CoverageMap {
+            tests: tests
+                .iter()
+                .map(|(test, files)| {
+                    let files = files
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|file| Some(PathBuf::from(file.as_str()?)))
+                        .collect();
+                    (test.clone(), files)
+                })
+                .collect(),
+        })
+    }
+
+    /// Writes the map to `path`, creating its directory if needed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(&json!({ "tests": self.tests }))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        text.push('\n');
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty()
+    }
+
+    /// The files `test` executed, if its coverage was recorded.
+    pub fn files(&self, test: &TestDef) -> Option<&BTreeSet<PathBuf>> {
+        self.tests.get(&timings::key(test))
+    }
+
+    /// Records that `test` executed `files`.
+    pub fn record(&mut self, test: &TestDef, files: BTreeSet<PathBuf>) {
+        self.tests.insert(timings::key(test), files);
+    }
+}
+
+/// The map file: [`MAP_ENV`] if set, else `tust/coverage.json` in the target
+/// directory holding the running binary, if there is one.
+pub fn map_path() -> Option<PathBuf> {
+    cache::path(MAP_ENV, "coverage.json")
+}
+
+/// The directory the profiles of `test` are written to while its coverage
+/// is recorded.
+pub(crate) fn profile_dir(test: &TestDef) -> Option<PathBuf> {
+    let map = map_path()?;
+    let name = timings::key(test).replace("::", "__");
+    Some(map.with_file_name("coverage").join(name))
+}
+
+/// The tests selected by `--changed-since`: those whose recorded files
+/// changed, and those without a record.
+#[derive(Debug, Clone)]
+pub struct Affected {
+    map: CoverageMap,
+    changed: BTreeSet<PathBuf>,
+}
+
+impl Affected {
+    /// Compares the working tree against the Git revision `git_ref`, and
+    /// reads the coverage map.
+    pub fn since(git_ref: &str) -> Result<Self, String> {
+        let map = map_path()
+            .and_then(|path| CoverageMap::read(&path).ok())
+            .unwrap_or_default();
+        let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let root = git(&dir, &["rev-parse", "--show-toplevel"])?;
+        let root = PathBuf::from(root.trim_end());
+        let changed = git(&root, &["diff", "--name-only", git_ref, "--"])?
+            .lines()
+            .map(|file| canonical(root.join(file)))
+            .collect();
+        Ok(Affected { map, changed })
+    }
+
+    /// Whether no coverage has been recorded, so that every test is
+    /// affected.
+    pub fn is_unknown(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains(&self, test: &TestDef) -> bool {
+        match self.map.files(test) {
+            Some(files) => !files.is_disjoint(&self.changed),
+            None => true,
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("cannot run git: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads the profiles written by the tests of `outcomes` that ran, and
+/// records the files each executed in the coverage map.
+pub(crate) fn update_map(outcomes: &[TestOutcome]) {
+    let Some(path) = map_path() else {
+        return;
+    };
+    let tools = match Tools::find() {
+        Ok(tools) => tools,
+        Err(err) => {
+            eprintln!("warning: coverage was not recorded: {}", err);
+            return;
+        }
+    };
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => {
+            eprintln!("warning: coverage was not recorded: {}", err);
+            return;
+        }
+    };
+    let mut map = CoverageMap::read(&path).unwrap_or_default();
+    let mut profiled = false;
+    let mut recorded = false;
+    for outcome in outcomes.iter().filter(|outcome| outcome.attempts > 0) {
+        let Some(dir) = profile_dir(outcome.test) else {
+            continue;
+        };
+        match tools.covered_files(&exe, &dir) {
+            Ok(Some(files)) => {
+                map.record(outcome.test, files);
+                (profiled, recorded) = (true, true);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                profiled = true;
+                eprintln!(
+                    "warning: coverage of {} was not recorded: {}",
+                    outcome.test.path(),
+                    err
+                )
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+    if !profiled && outcomes.iter().any(|outcome| outcome.attempts > 0) {
+        eprintln!(
+            "warning: the tests wrote no coverage profiles; build them with \
+             RUSTFLAGS=\"-C instrument-coverage\""
+        );
+    }
+    if !recorded {
+        return;
+    }
+    if let Err(err) = map.write(&path) {
+        eprintln!(
+            "warning: failed to record test coverage in {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+/// The LLVM tools reading coverage profiles.
+struct Tools {
+    profdata: PathBuf,
+    cov: PathBuf,
+}
+
+impl Tools {
+    fn find() -> Result<Self, String> {
+        let rustc_bin = rustc_tools_dir();
+        let find = |env: &str, name: &str| -> Result<PathBuf, String> {
+            if let Some(path) = std::env::var_os(env).filter(|path| !path.is_empty()) {
+                return Ok(PathBuf::from(path));
+            }
+            if let Some(path) = rustc_bin
+                .as_ref()
+                .map(|dir| dir.join(name))
+                .filter(|path| path.is_file())
+            {
+                return Ok(path);
+            }
+            let on_path = std::env::var_os("PATH")
+                .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file());
+            on_path.ok_or_else(|| {
+                format!(
+                    "cannot find `{}`; install it with `rustup component add \
+                     llvm-tools-preview`, or name it in {}",
+                    name, env
+                )
+            })
+        };
+        Ok(Tools {
+            profdata: find("LLVM_PROFDATA", "llvm-profdata")?,
+            cov: find("LLVM_COV", "llvm-cov")?,
+        })
+    }
+
+    /// The source files executed according to the profiles in `dir`, or
+    /// `None` if there are none.
+    fn covered_files(&self, exe: &Path, dir: &Path) -> Result<Option<BTreeSet<PathBuf>>, String> {
+        let profiles: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "profraw"))
+                .collect(),
+            Err(_) => return Ok(None),
+        };
+        if profiles.is_empty() {
+            return Ok(None);
+        }
+        let profdata = dir.join("test.profdata");
+        run(Command::new(&self.profdata)
+            .args(["merge", "-sparse", "-o"])
+            .arg(&profdata)
+            .args(&profiles))?;
+        let lcov = run(Command::new(&self.cov)
+            .args([
+                "export",
+                "-format=lcov",
+                "-skip-functions",
+                "-instr-profile",
+            ])
+            .arg(&profdata)
+            .arg(exe))?;
+        Ok(Some(
+            parse_lcov(&lcov).into_iter().filter_map(resolve).collect(),
+        ))
+    }
+}
+
+/// The `bin` directory of the compiler's LLVM tools, installed by the
+/// `llvm-tools-preview` component.
+fn rustc_tools_dir() -> Option<PathBuf> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = |arg: &str| {
+        let output = Command::new(&rustc).arg(arg).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let sysroot = output("--print=sysroot")?;
+    let version = output("-vV")?;
+    let host = version
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))?;
+    Some(
+        Path::new(sysroot.trim_end())
+            .join("lib/rustlib")
+            .join(host)
+            .join("bin"),
+    )
+}
+
+fn run(command: &mut Command) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|err| format!("cannot run {:?}: {}", command.get_program(), err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The absolute path of a file named in a coverage report, or `None` if it
+/// is not on this machine, as the standard library's sources may not be. A
+/// relative path is relative to the directory Cargo built from: the crate's
+/// or the workspace's.
+fn resolve(file: PathBuf) -> Option<PathBuf> {
+    if file.is_absolute() {
+        return file.is_file().then(|| canonical(file));
+    }
+    let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())?;
+    dir.ancestors()
+        .map(|dir| dir.join(&file))
+        .find(|path| path.is_file())
+        .map(canonical)
+}
+
+pub(crate) fn canonical(path: PathBuf) -> PathBuf {
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// The files of an LCOV report with at least one line hit.
+fn parse_lcov(lcov: &str) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    let mut file = None;
+    for line in lcov.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            file = Some(PathBuf::from(path));
+        } else if let Some(hit) = line.strip_prefix("LH:") {
+            if hit.trim().parse::<u64>().is_ok_and(|hit| hit > 0) {
+                files.extend(file.take());
+            }
+        } else if line == "end_of_record" {
+            file = None;
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            "app::parser",
+            name,
+            "src/lib.rs",
+            1,
+        )))
+    }
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tust-coverage-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn manifest_file(file: &str) -> PathBuf {
+        canonical(Path::new(env!("CARGO_MANIFEST_DIR")).join(file))
+    }
+
+    #[test]
+    fn attributes_the_files_with_lines_hit() {
+        let lcov = "\
+SF:src/lib.rs
+DA:1,1
+LH:1
+end_of_record
+SF:src/unused.rs
+DA:1,0
+LH:0
+end_of_record
+SF:src/no_summary.rs
+end_of_record
+SF:/rustc/library/core/src/option.rs
+LH:3
+end_of_record
+";
+        assert_eq!(
+            parse_lcov(lcov),
+            BTreeSet::from([
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("/rustc/library/core/src/option.rs"),
+            ])
+        );
+    }
+
+    #[test]
+    fn resolves_the_files_on_this_machine() {
+        let lib = manifest_file("src/lib.rs");
+        assert_eq!(resolve(PathBuf::from("src/lib.rs")), Some(lib.clone()));
+        // Relative to the workspace rather than the crate.
+        assert_eq!(
+            resolve(PathBuf::from("tust-runtime/src/lib.rs")),
+            Some(lib.clone())
+        );
+        assert_eq!(resolve(lib.clone()), Some(lib));
+        assert_eq!(
+            resolve(PathBuf::from("/rustc/library/core/src/option.rs")),
+            None
+        );
+        assert_eq!(resolve(PathBuf::from("src/no_such_file.rs")), None);
+    }
+
+    #[test]
+    fn map_round_trips_and_selects_affected_tests() {
+        let dir = temp_dir("map");
+        let path = dir.join("nested").join("coverage.json");
+        let (parses, lexes, untracked) = (def("parses"), def("lexes"), def("untracked"));
+        let mut map = CoverageMap::default();
+        assert!(map.is_empty());
+        map.record(parses, BTreeSet::from([PathBuf::from("/src/parser.rs")]));
+        map.record(
+            lexes,
+            BTreeSet::from([
+                PathBuf::from("/src/lexer.rs"),
+                PathBuf::from("/src/token.rs"),
+            ]),
+        );
+        map.write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"tests\": {\n    \"app::parser::lexes\": [\n      \"/src/lexer.rs\",\n      \
+             \"/src/token.rs\"\n    ],\n    \"app::parser::parses\": [\n      \
+             \"/src/parser.rs\"\n    ]\n  }\n}\n"
+        );
+
+        let map = CoverageMap::read(&path).unwrap();
+        assert_eq!(map.files(lexes).map(BTreeSet::len), Some(2));
+        assert_eq!(map.files(untracked), None);
+        let affected = Affected {
+            map,
+            changed: BTreeSet::from([PathBuf::from("/src/token.rs")]),
+        };
+        assert!(!affected.is_unknown());
+        assert!(affected.contains(lexes));
+        assert!(!affected.contains(parses));
+        assert!(affected.contains(untracked));
+
+        fs::write(&path, "{\"files\": {}}").unwrap();
+        let err = CoverageMap::read(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "expected a `tests` object");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// `covered_files` merges every profile of the directory, and reads the
+    /// files from the report on the merged profile.
+    #[cfg(unix)]
+    #[test]
+    fn merges_the_profiles_of_a_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("merge");
+        let script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        // `merge -sparse -o OUT PROFILES...`: writes the profiles merged to OUT.
+        let profdata = script(
+            "llvm-profdata",
+            r#"out=$4; shift 4; printf '%s\n' "$@" > "$out""#,
+        );
+        // `export ... -instr-profile PROFDATA EXE`: reports on PROFDATA.
+        let cov = script(
+            "llvm-cov",
+            r#"test -s "$5" || { echo "no merged profile" >&2; exit 1; }
+printf 'SF:src/lib.rs\nLH:2\nend_of_record\nSF:src/gone.rs\nLH:1\nend_of_record\n'"#,
+        );
+        let tools = Tools { profdata, cov };
+        let profiles = dir.join("profiles");
+        let exe = Path::new("test-binary");
+
+        assert_eq!(tools.covered_files(exe, &dir.join("missing")), Ok(None));
+        fs::create_dir_all(&profiles).unwrap();
+        fs::write(profiles.join("notes.txt"), "").unwrap();
+        assert_eq!(tools.covered_files(exe, &profiles), Ok(None));
+
+        for profile in ["1.profraw", "2.profraw"] {
+            fs::write(profiles.join(profile), "").unwrap();
+        }
+        assert_eq!(
+            tools.covered_files(exe, &profiles),
+            Ok(Some(BTreeSet::from([manifest_file("src/lib.rs")])))
+        );
+        let mut merged: Vec<String> = fs::read_to_string(profiles.join("test.profdata"))
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit('/').next().unwrap().to_string())
+            .collect();
+        merged.sort();
+        assert_eq!(merged, ["1.profraw", "2.profraw"]);
+
+        let failing = Tools {
+            profdata: script("failing", "echo 'bad profile' >&2; exit 1"),
+            cov: tools.cov.clone(),
+        };
+        let err = failing.covered_files(exe, &profiles).unwrap_err();
+        assert!(err.ends_with("failing\" failed: bad profile"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
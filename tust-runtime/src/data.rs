@@ -0,0 +1,206 @@
+//! Cases read from data files by `#[tust::test(cases_from = "...")]`.
+//!
+//! The attribute reads the file as the test is compiled, and the crate is
+//! rebuilt when the file changes. Each row becomes a case of its own, named
+//! after the row's `name` field if it has one and its values if not, and is
+//! deserialized into the test's first parameter when the case runs, so that
+//! a row that does not fit fails only its own case.
+//!
+//! JSON files hold an array of rows, and YAML files a sequence of them; the
+//! rows deserialize as serde reads them. CSV files have a header row naming
+//! the fields of the rows below it. Their fields are text, parsed into the
+//! type the parameter has for them: numbers, `bool`s, `char`s, strings, and
+//! enum variants without data, by name, where an `Option` is `None` for an
+//! empty field.
+
+use std::any::type_name;
+
+use serde::de::value::{Error, MapDeserializer, StringDeserializer};
+use serde::de::{
+    DeserializeOwned, Deserializer, Error as _, IntoDeserializer, Unexpected, Visitor,
+};
+use serde_json::Value;
+
+use crate::outcome::TestFailure;
+
+/// A row of a data file, embedded in the test by `cases_from`.
+///
+/// Values of this type are generated by `#[tust::test]`; they are not meant to
+/// be constructed by hand.
+#[derive(Debug, Clone, Copy)]
+pub struct DataRow {
+    /// The data file, relative to the crate root.
+    pub file: &'static str,
+    /// The row's place in the file, from 1.
+    pub row: usize,
+    /// The row, as JSON.
+    pub json: &'static str,
+    /// Whether the row comes from a CSV file, so that its fields are strings
+    /// to parse.
+    pub csv: bool,
+}
+
+/// Deserializes `row` into the type of the parameter it binds to, failing
+/// the test if it does not fit.
+#[track_caller]
+pub fn row<T: DeserializeOwned>(row: &DataRow) -> T {
+    let value: Value = serde_json::from_str(row.json).expect("rows are embedded as JSON");
+    let result = match value {
+        Value::Object(fields) if row.csv => {
+            let fields = fields.into_iter().map(|(name, value)| {
+                let text = match value {
+                    Value::String(text) => text,
+                    value => value.to_string(),
+                };
+                (name, Field(text))
+            });
+            T::deserialize(MapDeserializer::<_, Error>::new(fields)).map_err(|err| err.to_string())
+        }
+        value => T::deserialize(value).map_err(|err| err.to_string()),
+    };
+    match result {
+        Ok(value) => value,
+        Err(err) => TestFailure::new(format!(
+            "cannot read row {} of `{}` as `{}`: {}",
+            row.row,
+            row.file,
+            type_name::<T>(),
+            err
+        ))
+        .with_metadata("row", row.json)
+        .raise(),
+    }
+}
+
+/// A field of a CSV row, parsed as the type deserializing it asks for.
+struct Field(String);
+
+impl<'de> IntoDeserializer<'de, Error> for Field {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Parses the field with `FromStr` for the `deserialize_*` methods named.
+macro_rules! parse_field {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.trim().parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(Error::invalid_value(Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Field {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    parse_field! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        variant.deserialize_enum(name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn data_row(json: &'static str, csv: bool) -> DataRow {
+        DataRow {
+            file: "cases.csv",
+            row: 3,
+            json,
+            csv,
+        }
+    }
+
+    #[test]
+    fn json_rows_deserialize_as_serde_reads_them() {
+        let value: (u8, String, Vec<bool>) = row(&data_row(r#"[1, "a", [true]]"#, false));
+        assert_eq!(value, (1, "a".to_string(), vec![true]));
+    }
+
+    #[test]
+    fn csv_fields_parse_into_the_type_asked_for() {
+        let numbers: BTreeMap<String, i64> = row(&data_row(r#"{"a": " -1", "b": "2"}"#, true));
+        assert_eq!(numbers, BTreeMap::from([("a".into(), -1), ("b".into(), 2)]));
+        let options: BTreeMap<String, Option<bool>> =
+            row(&data_row(r#"{"a": "", "b": "true"}"#, true));
+        assert_eq!(options["a"], None);
+        assert_eq!(options["b"], Some(true));
+        let texts: BTreeMap<String, String> = row(&data_row(r#"{"a": "007"}"#, true));
+        assert_eq!(texts["a"], "007");
+    }
+
+    #[test]
+    fn rows_that_do_not_fit_fail_the_test() {
+        let err = std::panic::catch_unwind(|| {
+            row::<BTreeMap<String, u8>>(&data_row(r#"{"a": "many"}"#, true))
+        })
+        .unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(
+            message.starts_with("cannot read row 3 of `cases.csv` as `"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("invalid value: string \"many\""),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Database connections for tests that roll back everything they write.
+//!
+//! A `TestDb` opens a connection and begins a transaction on it that is never
+//! committed: when the test ends and the value is dropped, the connection
+//! closes and the database rolls the transaction back, so every test starts
+//! from the same data without truncation scripts. Give it migrations and they
+//! run once per run for each database URL, on a connection of their own and
+//! committed, before the first test connects; tests connecting meanwhile wait
+//! for them. With `--process-per-test`, the test processes agree on which of
+//! them migrates through files in a directory the runner names in
+//! [`MIGRATIONS_ENV`], so the migrations still run once.
+//!
+//! `sqlx::TestDb` is available with the `sqlx` feature and
+//! `diesel::TestDb` with the `diesel` feature. Neither enables a database
+//! driver: enable the one you use in your own dependency on `sqlx` or
+//! `diesel`.
+//!
+//! Every test gets its own connection, so each test must be able to open
+//! one: an in-memory SQLite database, for one, is private to its connection
+//! and needs a shared-cache URL such as `sqlite:file:tests?mode=memory&cache=shared`
+//! to see the migrated schema. Statements that commit implicitly, such as
+//! DDL on MySQL, escape the rollback.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::process;
+
+#[cfg(feature = "diesel")]
+pub mod diesel;
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
+
+/// Whether the migrations of a database URL ran, or are running.
+#[derive(Clone, Copy, PartialEq)]
+enum Migration {
+    Running,
+    Done,
+}
+
+static MIGRATIONS: Mutex<Vec<(String, Migration)>> = Mutex::new(Vec::new());
+static MIGRATED: Condvar = Condvar::new();
+
+/// Directory in which the test processes of a run record the databases they
+/// migrate, set by the runner for the processes it starts.
+pub(crate) const MIGRATIONS_ENV: &str = "__TUST_MIGRATIONS";
+/// How often a process waiting for another's migrations checks on them.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The directory the processes this one starts record their migrations in.
+pub(crate) fn migrations_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("tust-{}-migrations", process::id()))
+}
+
+/// Removes the records of the migrations of this run's test processes.
+pub(crate) fn clean_up() {
+    let _ = fs::remove_dir_all(migrations_dir());
+}
+
+/// The right to migrate a database, held by the test that migrates it first;
+/// dropping it without [`finish`](Claim::finish) lets the next test try.
+pub(crate) struct Claim {
+    url: String,
+    done: bool,
+    lock: Option<Lock>,
+}
+
+impl Claim {
+    /// Records that the migrations of the database ran.
+    pub(crate) fn finish(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(lock) = self.lock.take() {
+            lock.release(self.done);
+        }
+        let mut migrations = MIGRATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.done {
+            if let Some(entry) = migrations.iter_mut().find(|(url, _)| *url == self.url) {
+                entry.1 = Migration::Done;
+            }
+        } else {
+            migrations.retain(|(url, _)| *url != self.url);
+        }
+        MIGRATED.notify_all();
+    }
+}
+
+/// The claim to migrate the database at `url`, unless its migrations
+/// already ran in this run; waits while another test runs them.
+pub(crate) fn claim(url: &str) -> Option<Claim> {
+    let mut claim = claim_in_process(url)?;
+    if let Some(dir) = std::env::var_os(MIGRATIONS_ENV) {
+        match Lock::acquire(Path::new(&dir), url) {
+            Some(lock) => claim.lock = Some(lock),
+            None => {
+                // Another process migrated the database.
+                claim.done = true;
+                return None;
+            }
+        }
+    }
+    Some(claim)
+}
+
+/// The claim to migrate the database at `url` among the threads of this
+/// process.
+fn claim_in_process(url: &str) -> Option<Claim> {
+    let mut migrations = MIGRATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        match migrations.iter().find(|(other, _)| other == url) {
+            Some((_, Migration::Done)) => return None,
+            Some((_, Migration::Running)) => {
+                migrations = MIGRATED
+                    .wait(migrations)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            None => {
+                migrations.push((url.to_string(), Migration::Running));
+                return Some(Claim {
+                    url: url.to_string(),
+                    done: false,
+                    lock: None,
+                });
+            }
+        }
+    }
+}
+
+/// The claim to migrate a database among the processes of a run: a lock file
+/// holding the id of the process migrating it. The process that finishes
+/// the migrations leaves a second file, which tells the others they ran.
+struct Lock {
+    lock: PathBuf,
+    done: PathBuf,
+}
+
+impl Lock {
+    /// Claims the migrations of the database at `url` among the processes
+    /// recording them in `dir`, unless one of them already ran them; waits
+    /// while another process runs them.
+    fn acquire(dir: &Path, url: &str) -> Option<Lock> {
+        let name = file_stem(url);
+        let lock = Lock {
+            lock: dir.join(format!("{}.lock", name)),
+            done: dir.join(format!("{}.done", name)),
+        };
+        let _ = fs::create_dir_all(dir);
+        loop {
+            if lock.done.exists() {
+                return None;
+            }
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock.lock)
+            {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", process::id());
+                    // The last holder may have finished since the check.
+                    if lock.done.exists() {
+                        let _ = fs::remove_file(&lock.lock);
+                        return None;
+                    }
+                    return Some(lock);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if holder_exited(&lock.lock) {
+                        let _ = fs::remove_file(&lock.lock);
+                    } else {
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+                // Without a place to record them in, the migrations run
+                // in every process.
+                Err(_) => return Some(lock),
+            }
+        }
+    }
+
+    /// Gives up the claim, recording whether the migrations ran.
+    fn release(self, done: bool) {
+        if done {
+            let _ = fs::write(&self.done, "");
+        }
+        let _ = fs::remove_file(&self.lock);
+    }
+}
+
+/// The name of the files recording the migrations of the database at `url`.
+fn file_stem(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether the process holding the lock file at `path` exited without
+/// releasing it, having crashed or been killed for its test's timeout.
+fn holder_exited(path: &Path) -> bool {
+    // The holder writes its id just after creating the file.
+    let Some(id) = fs::read_to_string(path)
+        .ok()
+        .and_then(|id| id.parse::<u32>().ok())
+    else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        // SAFETY: `kill` has no memory-safety preconditions, and signal 0
+        // only checks that the process exists.
+        let alive = unsafe { libc::kill(id as libc::pid_t, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+        !alive
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = id;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tust-db-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn migrations_run_once_per_database() {
+        claim("db://once")
+            .expect("the first test migrates")
+            .finish();
+        assert!(claim("db://once").is_none());
+        assert!(claim("db://once-other").is_some());
+    }
+
+    #[test]
+    fn unfinished_migrations_are_tried_again() {
+        drop(claim("db://failing").expect("the first test migrates"));
+        claim("db://failing")
+            .expect("the next test migrates")
+            .finish();
+        assert!(claim("db://failing").is_none());
+    }
+
+    #[test]
+    fn tests_wait_for_running_migrations() {
+        let first = claim("db://waited").expect("the first test migrates");
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn(move || sender.send(claim("db://waited").is_none()));
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        first.finish();
+        assert_eq!(receiver.recv().ok(), Some(true));
+        waiter.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn processes_migrate_once() {
+        let dir = scratch_dir("once");
+        let lock = Lock::acquire(&dir, "db://processes").expect("the first process migrates");
+        lock.release(true);
+        assert!(Lock::acquire(&dir, "db://processes").is_none());
+        Lock::acquire(&dir, "db://processes-other")
+            .expect("another database is migrated")
+            .release(false);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn processes_wait_for_running_migrations() {
+        let dir = scratch_dir("waited");
+        let lock = Lock::acquire(&dir, "db://waited").expect("the first process migrates");
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn({
+            let dir = dir.clone();
+            move || sender.send(Lock::acquire(&dir, "db://waited").is_none())
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        lock.release(true);
+        assert_eq!(receiver.recv().ok(), Some(true));
+        waiter.join().unwrap().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unfinished_migrations_of_a_process_are_tried_again() {
+        let dir = scratch_dir("failing");
+        Lock::acquire(&dir, "db://failing")
+            .expect("the first process migrates")
+            .release(false);
+        Lock::acquire(&dir, "db://failing")
+            .expect("the next process migrates")
+            .release(true);
+        assert!(Lock::acquire(&dir, "db://failing").is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn locks_of_exited_processes_are_taken_over() {
+        let dir = scratch_dir("exited");
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        let lock = dir.join(format!("{}.lock", file_stem("db://exited")));
+        fs::write(lock, exited.id().to_string()).unwrap();
+        Lock::acquire(&dir, "db://exited")
+            .expect("the lock of the exited process is taken over")
+            .release(true);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
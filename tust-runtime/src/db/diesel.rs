@@ -0,0 +1,74 @@
+//! [`TestDb`] for Diesel connections.
+//!
+//! ```ignore
+//! use diesel::PgConnection;
+//! use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+//! use tust::db::diesel::TestDb;
+//!
+//! const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+//!
+//! #[fixture]
+//! fn db() -> TestDb<PgConnection> {
+//!     TestDb::connect_migrated(&std::env::var("DATABASE_URL").unwrap(), |conn| {
+//!         conn.run_pending_migrations(MIGRATIONS).map(drop)
+//!     })
+//!     .expect("cannot connect to the test database")
+//! }
+//!
+//! #[tust::test]
+//! fn creates_a_user(mut db: TestDb<PgConnection>) {
+//!     diesel::insert_into(users::table)
+//!         .values(users::name.eq("Ada"))
+//!         .execute(&mut *db)
+//!         .unwrap();
+//! }
+//! ```
+
+use std::error::Error;
+use std::ops::{Deref, DerefMut};
+
+use diesel::{Connection, ConnectionResult};
+
+/// A Diesel connection in a transaction that is rolled back when the value is
+/// dropped; it dereferences to the connection.
+pub struct TestDb<C: Connection> {
+    conn: C,
+}
+
+impl<C: Connection> TestDb<C> {
+    /// Connects to the database at `url` and begins the transaction.
+    pub fn connect(url: &str) -> ConnectionResult<Self> {
+        let mut conn = C::establish(url)?;
+        conn.begin_test_transaction()
+            .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+        Ok(TestDb { conn })
+    }
+
+    /// Like [`connect`](Self::connect), after calling `migrate` with a
+    /// connection of its own, unless it already ran successfully for `url`
+    /// in this run.
+    pub fn connect_migrated(
+        url: &str,
+        migrate: impl FnOnce(&mut C) -> Result<(), Box<dyn Error + Send + Sync>>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if let Some(claim) = super::claim(url) {
+            migrate(&mut C::establish(url)?)?;
+            claim.finish();
+        }
+        Ok(Self::connect(url)?)
+    }
+}
+
+impl<C: Connection> Deref for TestDb<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.conn
+    }
+}
+
+impl<C: Connection> DerefMut for TestDb<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.conn
+    }
+}
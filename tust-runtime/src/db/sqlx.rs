@@ -0,0 +1,93 @@
+//! [`TestDb`] for sqlx connections.
+//!
+//! A `TestDb` is a plain value, so a sync fixture can create it; it connects
+//! on the first call to [`conn`](TestDb::conn), inside the async test.
+//!
+//! ```ignore
+//! use sqlx::migrate::Migrator;
+//! use sqlx::Postgres;
+//! use tust::db::sqlx::TestDb;
+//!
+//! static MIGRATOR: Migrator = sqlx::migrate!();
+//!
+//! #[fixture]
+//! fn db() -> TestDb<Postgres> {
+//!     TestDb::new(std::env::var("DATABASE_URL").unwrap()).migrate(&MIGRATOR)
+//! }
+//!
+//! #[tust::test]
+//! async fn creates_a_user(mut db: TestDb<Postgres>) {
+//!     let conn = db.conn().await.unwrap();
+//!     sqlx::query("INSERT INTO users (name) VALUES ('Ada')")
+//!         .execute(&mut *conn)
+//!         .await
+//!         .unwrap();
+//! }
+//! ```
+
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{Acquire, Connection, Database, TransactionManager};
+
+/// A sqlx connection in a transaction that is rolled back when the value is
+/// dropped.
+pub struct TestDb<DB: Database> {
+    url: String,
+    migrator: Option<&'static Migrator>,
+    conn: Option<DB::Connection>,
+}
+
+impl<DB: Database> TestDb<DB> {
+    /// A connection to the database at `url`, not opened yet.
+    pub fn new(url: impl Into<String>) -> Self {
+        TestDb {
+            url: url.into(),
+            migrator: None,
+            conn: None,
+        }
+    }
+
+    /// Runs `migrator` before connecting, unless it already ran successfully
+    /// for this URL in this run.
+    pub fn migrate(mut self, migrator: &'static Migrator) -> Self {
+        self.migrator = Some(migrator);
+        self
+    }
+
+    /// The URL of the database.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl<DB: Database> TestDb<DB>
+where
+    DB::Connection: Migrate,
+    for<'c> &'c mut DB::Connection: Acquire<'c, Database = DB>,
+{
+    /// The connection, opened, migrated and in its transaction on the first
+    /// call.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of connecting, migrating, or beginning the
+    /// transaction; the next call tries again.
+    pub async fn conn(&mut self) -> Result<&mut DB::Connection, sqlx::Error> {
+        if self.conn.is_none() {
+            if let Some(migrator) = self.migrator {
+                if let Some(claim) = super::claim(&self.url) {
+                    let mut conn = DB::Connection::connect(&self.url).await?;
+                    migrator
+                        .run(&mut conn)
+                        .await
+                        .map_err(|err| sqlx::Error::Migrate(Box::new(err)))?;
+                    conn.close().await?;
+                    claim.finish();
+                }
+            }
+            let mut conn = DB::Connection::connect(&self.url).await?;
+            DB::TransactionManager::begin(&mut conn, None).await?;
+            self.conn = Some(conn);
+        }
+        Ok(self.conn.as_mut().expect("connected above"))
+    }
+}
@@ -0,0 +1,293 @@
+//! Tests that run after other tests, as declared with `#[depends_on]`.
+//!
+//! A dependency names a test, or a module of tests, relative to the module
+//! of the test declaring it, or from the crate root if it contains `::`, in
+//! the form printed by the runner: `setup_schema` or `db::setup_schema`.
+//! `crate::` may start a path, and must to name a test at the crate root from
+//! another module. A dependency on a module is a dependency on every test in
+//! it.
+
+use crate::registry::TestDef;
+
+/// The path of the test or module `dependency` names for `test`.
+fn resolve(test: &TestDef, dependency: &str) -> String {
+    if let Some(path) = dependency.strip_prefix("crate::") {
+        return path.to_string();
+    }
+    if dependency.contains("::") {
+        return dependency.to_string();
+    }
+    match test.module_path.split_once("::") {
+        Some((_crate, module)) => format!("{}::{}", module, dependency),
+        None => dependency.to_string(),
+    }
+}
+
+/// Whether the test at `candidate`, a test path, is the test or in the module
+/// at `path`.
+fn is_named(path: &str, candidate: &str) -> bool {
+    candidate
+        .strip_prefix(path)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// For each of `tests`, the indices of the tests among them it depends on.
+fn indices(tests: &[&'static TestDef]) -> Vec<Vec<usize>> {
+    let paths: Vec<String> = tests.iter().map(|test| test.path()).collect();
+    tests
+        .iter()
+        .enumerate()
+        .map(|(index, test)| {
+            let mut dependencies = Vec::new();
+            for dependency in test.depends_on {
+                let path = resolve(test, dependency);
+                dependencies.extend(
+                    (0..tests.len())
+                        .filter(|&other| other != index && is_named(&path, &paths[other])),
+                );
+            }
+            dependencies.sort_unstable();
+            dependencies.dedup();
+            dependencies
+        })
+        .collect()
+}
+
+/// Checks that every dependency of `all`, the registered tests, names a test
+/// and that no test depends on itself through others.
+pub(crate) fn check(all: &[&'static TestDef]) -> Result<(), String> {
+    let paths: Vec<String> = all.iter().map(|test| test.path()).collect();
+    for (test, path) in all.iter().zip(&paths) {
+        for dependency in test.depends_on {
+            let resolved = resolve(test, dependency);
+            if !paths
+                .iter()
+                .any(|other| other != path && is_named(&resolved, other))
+            {
+                return Err(format!(
+                    "test `{}` depends on `{}`, which names no other test",
+                    path, resolved
+                ));
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        New,
+        Active,
+        Done,
+    }
+
+    fn visit(
+        index: usize,
+        dependencies: &[Vec<usize>],
+        visits: &mut [Visit],
+        stack: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match visits[index] {
+            Visit::Done => return Ok(()),
+            Visit::Active => {
+                let start = stack.iter().position(|&other| other == index).unwrap_or(0);
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(index);
+                return Err(cycle);
+            }
+            Visit::New => {}
+        }
+        visits[index] = Visit::Active;
+        stack.push(index);
+        for &dependency in &dependencies[index] {
+            visit(dependency, dependencies, visits, stack)?;
+        }
+        stack.pop();
+        visits[index] = Visit::Done;
+        Ok(())
+    }
+
+    let dependencies = indices(all);
+    let mut visits = vec![Visit::New; all.len()];
+    for index in 0..all.len() {
+        if let Err(cycle) = visit(index, &dependencies, &mut visits, &mut Vec::new()) {
+            let names: Vec<&str> = cycle.iter().map(|&index| paths[index].as_str()).collect();
+            return Err(format!(
+                "tests depend on each other in a cycle: {}",
+                names.join(" -> ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `selected` with the tests of `all` they depend on, directly or not,
+/// added, in the order of `all`.
+pub(crate) fn with_dependencies(
+    selected: Vec<&'static TestDef>,
+    all: &[&'static TestDef],
+) -> Vec<&'static TestDef> {
+    if selected.iter().all(|test| test.depends_on.is_empty()) {
+        return selected;
+    }
+    let dependencies = indices(all);
+    let mut included: Vec<bool> = all
+        .iter()
+        .map(|test| selected.iter().any(|other| std::ptr::eq(*test, *other)))
+        .collect();
+    let mut pending: Vec<usize> = (0..all.len()).filter(|&index| included[index]).collect();
+    while let Some(index) = pending.pop() {
+        for &dependency in &dependencies[index] {
+            if !included[dependency] {
+                included[dependency] = true;
+                pending.push(dependency);
+            }
+        }
+    }
+    all.iter()
+        .zip(included)
+        .filter_map(|(test, included)| included.then_some(*test))
+        .collect()
+}
+
+/// For each of `tests`, the index of the first test of its group: the tests
+/// among `tests` it depends on or that depend on it, directly or not.
+pub(crate) fn groups(tests: &[&'static TestDef]) -> Vec<usize> {
+    fn first(groups: &mut [usize], mut index: usize) -> usize {
+        while groups[index] != index {
+            groups[index] = groups[groups[index]];
+            index = groups[index];
+        }
+        index
+    }
+
+    let mut groups: Vec<usize> = (0..tests.len()).collect();
+    if tests.iter().all(|test| test.depends_on.is_empty()) {
+        return groups;
+    }
+    for (index, dependencies) in indices(tests).into_iter().enumerate() {
+        for dependency in dependencies {
+            let (a, b) = (first(&mut groups, index), first(&mut groups, dependency));
+            groups[a.max(b)] = a.min(b);
+        }
+    }
+    (0..tests.len())
+        .map(|index| first(&mut groups, index))
+        .collect()
+}
+
+/// Moves each of `tests` after the tests it depends on, keeping the order of
+/// `tests` otherwise.
+pub(crate) fn order(tests: &mut Vec<&'static TestDef>) {
+    fn place(
+        index: usize,
+        dependencies: &[Vec<usize>],
+        placed: &mut [bool],
+        order: &mut Vec<usize>,
+    ) {
+        if placed[index] {
+            return;
+        }
+        placed[index] = true;
+        for &dependency in &dependencies[index] {
+            place(dependency, dependencies, placed, order);
+        }
+        order.push(index);
+    }
+
+    if tests.iter().all(|test| test.depends_on.is_empty()) {
+        return;
+    }
+    let dependencies = indices(tests);
+    let mut placed = vec![false; tests.len()];
+    let mut order = Vec::with_capacity(tests.len());
+    for index in 0..tests.len() {
+        place(index, &dependencies, &mut placed, &mut order);
+    }
+    *tests = order.into_iter().map(|index| tests[index]).collect();
+}
+
+/// For each of `tests`, ordered by [`order`], the indices of the earlier
+/// tests it depends on.
+pub(crate) fn earlier(tests: &[&'static TestDef]) -> Vec<Vec<usize>> {
+    let mut dependencies = indices(tests);
+    for (index, dependencies) in dependencies.iter_mut().enumerate() {
+        dependencies.retain(|&dependency| dependency < index);
+    }
+    dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(name: &'static str, depends_on: &'static [&'static str]) -> &'static TestDef {
+        Box::leak(Box::new(TestDef {
+            depends_on,
+            ..TestDef::ran_elsewhere("krate::db", name, "t.rs", 1)
+        }))
+    }
+
+    fn names(tests: &[&'static TestDef]) -> Vec<&'static str> {
+        tests.iter().map(|test| test.name).collect()
+    }
+
+    #[test]
+    fn groups_join_tests_depending_on_each_other() {
+        let tests = [
+            test("a", &[]),
+            test("b", &[]),
+            test("c", &["a"]),
+            test("d", &["b"]),
+            test("e", &["c", "d"]),
+            test("f", &[]),
+        ];
+        assert_eq!(groups(&tests), [0, 0, 0, 0, 0, 5]);
+        assert_eq!(groups(&tests[..4]), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn with_dependencies_adds_them_in_order() {
+        let all = [
+            test("a", &[]),
+            test("b", &["a"]),
+            test("c", &["b"]),
+            test("d", &[]),
+        ];
+        assert_eq!(
+            names(&with_dependencies(vec![all[2]], &all)),
+            ["a", "b", "c"]
+        );
+        assert_eq!(names(&with_dependencies(vec![all[3]], &all)), ["d"]);
+    }
+
+    #[test]
+    fn dependencies_resolve_relative_to_the_module() {
+        let tests = [
+            test("setup", &[]),
+            test("uses_module", &["crate::db"]),
+            test("uses_path", &["db::setup"]),
+        ];
+        assert_eq!(resolve(tests[0], "setup"), "db::setup");
+        assert_eq!(indices(&tests), [vec![], vec![0, 2], vec![0]]);
+    }
+
+    #[test]
+    fn check_finds_missing_tests_and_cycles() {
+        let missing = [test("a", &["nothing"])];
+        assert_eq!(
+            check(&missing).unwrap_err(),
+            "test `db::a` depends on `db::nothing`, which names no other test"
+        );
+        let cycle = [test("a", &["b"]), test("b", &["a"])];
+        assert_eq!(
+            check(&cycle).unwrap_err(),
+            "tests depend on each other in a cycle: db::a -> db::b -> db::a"
+        );
+    }
+
+    #[test]
+    fn order_puts_dependencies_first() {
+        let mut tests = vec![test("c", &["b"]), test("b", &["a"]), test("a", &[])];
+        order(&mut tests);
+        assert_eq!(names(&tests), ["a", "b", "c"]);
+    }
+}
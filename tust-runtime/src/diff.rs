@@ -0,0 +1,60 @@
+//! Line-by-line diffs of rendered values.
+
+use std::fmt::Write;
+
+use colored::Colorize;
+use similar::{ChangeTag, TextDiff};
+
+/// Unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// Formats a colored, line-by-line diff from `left` to `right`.
+///
+/// Removed lines are prefixed with `-` and added lines with `+`; within a
+/// changed line the differing segments are emphasized. Long runs of unchanged
+/// lines are collapsed.
+pub fn format_diff(left: &str, right: &str) -> String {
+    format_labeled_diff("left", "right", left, right)
+}
+
+/// Like [`format_diff`], naming the two sides `left_label` and `right_label`
+/// in the header.
+pub fn format_labeled_diff(left_label: &str, right_label: &str, left: &str, right: &str) -> String {
+    let diff = TextDiff::from_lines(left, right);
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", format!("--- {}", left_label).red());
+    let _ = writeln!(out, "{}", format!("+++ {}", right_label).green());
+
+    for (index, group) in diff.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        if index > 0 {
+            let _ = writeln!(out, "{}", "...".dimmed());
+        }
+        for op in group {
+            for change in diff.iter_inline_changes(op) {
+                let (sign, paint): (&str, fn(&str) -> colored::ColoredString) = match change.tag() {
+                    ChangeTag::Delete => ("-", |s| s.red()),
+                    ChangeTag::Insert => ("+", |s| s.green()),
+                    ChangeTag::Equal => (" ", |s| s.normal()),
+                };
+                let _ = write!(out, "{}", paint(sign));
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    let value = value.trim_end_matches('\n');
+                    if emphasized {
+                        let _ = write!(out, "{}", paint(value).bold().underline());
+                    } else {
+                        let _ = write!(out, "{}", paint(value));
+                    }
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+/// Whether rendered values are large enough that a diff reads better than
+/// printing them side by side.
+pub fn wants_diff(left: &str, right: &str) -> bool {
+    left.contains('\n') || right.contains('\n')
+}
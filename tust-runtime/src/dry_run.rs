@@ -0,0 +1,127 @@
+//! The checks made by `--dry-run`.
+//!
+//! A dry run selects tests as a run would, prints them as `--list` does,
+//! with each case of a parameterized test on its own line, and then checks
+//! what would fail before any test body runs:
+//!
+//! - that the fixtures each test asks for, and the fixtures those depend on,
+//!   are registered, are unambiguous, have the parameter's type, and do not
+//!   depend on themselves;
+//! - that every `--filter`, `--filter-regex`, filter, and `--skip` matches
+//!   the name of some test, and every `--file` the file of one;
+//! - that every tag named by `--tag` and `--skip-tag` is carried by some
+//!   test, which catches misspelled tags.
+//!
+//! ```text
+//! db::insert_one     tests/db.rs:12  [db, slow]
+//! parser::case_1     tests/parser.rs:8
+//! parser::case_2     tests/parser.rs:8
+//!
+//! 3 tests
+//! error: db::insert_one: no fixture named `pool` is registered (requested as `Pool`)
+//! warning: the tag `sloww` of --tag is carried by no test
+//! dry run: 3 tests would run; 1 problem
+//! ```
+//!
+//! The problems are printed to stderr, so that `--format json` keeps the
+//! list on stdout readable. The run fails if a fixture would not resolve;
+//! filters and tags matching nothing are only warned about. Tests that are
+//! ignored are not checked, as they would not run; `skip_if` conditions are
+//! not evaluated, so tests they skip are. Fixtures a [`crate::fixture::Fixture`]
+//! resolves in its `setup` are only known once it runs, and are not checked.
+
+use crate::cli::{self, Args};
+use crate::filter::NamePattern;
+use crate::fixture;
+use crate::list;
+use crate::registry::{Skip, TestDef};
+use crate::runner::{EXIT_SUCCESS, EXIT_TESTS_FAILED};
+
+/// Prints `selected`, the tests of `all` that `args` selects, and the
+/// problems a run of them would hit before its first test body runs.
+/// Returns the exit code of the dry run.
+pub(crate) fn run(args: &Args, all: &[&'static TestDef], selected: &[&'static TestDef]) -> u8 {
+    list::print(args, selected);
+
+    let mut errors = 0;
+    for test in selected {
+        if matches!(test.skip, Skip::Always { .. }) {
+            continue;
+        }
+        if let Err(err) = fixture::check(test.module_path, test.fixtures) {
+            eprintln!("error: {}: {}", test.path(), err);
+            errors += 1;
+        }
+    }
+    let warnings = unmatched(args, all);
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let problems = errors + warnings.len();
+    eprintln!(
+        "dry run: {} {} would run; {}",
+        selected.len(),
+        if selected.len() == 1 { "test" } else { "tests" },
+        match problems {
+            0 => "no problems".to_string(),
+            1 => "1 problem".to_string(),
+            _ => format!("{} problems", problems),
+        }
+    );
+    if errors > 0 {
+        EXIT_TESTS_FAILED
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
+/// The options of `args` that select or skip none of `all`.
+fn unmatched(args: &Args, all: &[&'static TestDef]) -> Vec<String> {
+    let names: Vec<_> = all.iter().map(|test| test.path()).collect();
+    let matches = |filter: &str| {
+        names.iter().any(|name| {
+            if args.exact {
+                name == filter
+            } else {
+                name.contains(filter)
+            }
+        })
+    };
+
+    let mut unmatched = Vec::new();
+    for filter in args.filters.iter().filter(|filter| !matches(filter)) {
+        unmatched.push(format!("the filter `{}` matches no test", filter));
+    }
+    for pattern in &args.patterns {
+        if !names.iter().any(|name| pattern.matches(name)) {
+            unmatched.push(match pattern {
+                NamePattern::Glob(glob) => format!("--filter `{}` matches no test", glob),
+                NamePattern::Regex(regex) => {
+                    format!("--filter-regex `{}` matches no test", regex.as_str())
+                }
+            });
+        }
+    }
+    for skip in args.skip.iter().filter(|skip| !matches(skip)) {
+        unmatched.push(format!("--skip `{}` matches no test", skip));
+    }
+    for file in &args.files {
+        if !all.iter().any(|test| cli::same_file(file, test.file)) {
+            unmatched.push(format!("--file `{}` defines no test", file.display()));
+        }
+    }
+    for (option, exprs) in [("--tag", &args.tags), ("--skip-tag", &args.skip_tags)] {
+        let mut seen = Vec::new();
+        for tag in exprs.iter().flat_map(|expr| expr.tags()) {
+            if !seen.contains(&tag) && !all.iter().any(|test| test.tags.contains(&tag)) {
+                unmatched.push(format!(
+                    "the tag `{}` of {} is carried by no test",
+                    tag, option
+                ));
+            }
+            seen.push(tag);
+        }
+    }
+    unmatched
+}
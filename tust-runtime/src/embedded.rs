@@ -0,0 +1,290 @@
+//! Reports of tests run on an embedded target with `tust-embedded`.
+//!
+//! On the target, tests report over [defmt](https://defmt.ferrous-systems.com),
+//! usually carried over RTT to a host tool such as `probe-rs run` that prints
+//! the decoded messages. [`decode`] reads what that tool prints and reports
+//! the run with the reporters the runner's options ask for, so the run looks
+//! like one on the host; `cargo tust embedded` runs the tool and decodes its
+//! output.
+//!
+//! The target first announces every test, then runs them one at a time, in
+//! messages starting with `tust:`:
+//!
+//! ```text
+//! tust:run 2
+//! tust:test firmware::tests parses_header 12 src/tests.rs
+//! tust:tag parser
+//! tust:test firmware::tests rejects_overflow 20 src/tests.rs
+//! tust:should_fail overflow
+//! tust:start 0
+//! tust:ok
+//! tust:start 1
+//! tust:skipped not on this board
+//! tust:end
+//! ```
+//!
+//! `tust:test` gives the module path, name, line, and file of a test, and
+//! the `tust:tag`, `tust:should_fail`, and `tust:xfail` that follow it its
+//! attributes; `tust:start` names a test by its place in that list. The
+//! tool may put a prefix, such as a timestamp, before `tust:`. Other lines
+//! are the output of the test running, or, between tests, passed on to
+//! standard error.
+//!
+//! A panic stops the target: a run that ends while a test is running fails
+//! that test with what it printed from the panic message on, and leaves the
+//! tests after it not run. A `#[should_fail]` test that panics therefore
+//! passes, but ends the run.
+
+use std::io::BufRead;
+use std::process::ExitCode;
+
+use web_time::Instant;
+
+use crate::cli::Args;
+use crate::outcome::{RunSummary, TestFailure, TestOutcome, TestStatus};
+use crate::registry::{ExpectedResult, TestDef};
+use crate::report::{Reporter, Reporters};
+use crate::runner::{self, EXIT_SUCCESS, EXIT_TESTS_FAILED};
+
+/// Marks the messages of the protocol.
+const PREFIX: &str = "tust:";
+
+/// Reads the messages of a run on an embedded target from `input`, reports
+/// it as `args` asks, and returns the exit code of the run.
+///
+/// Only the options choosing how a run is reported apply, such as
+/// `--format`, `--report`, `--color`, and `--show-output`; the target runs
+/// every test it has.
+pub fn decode(input: impl BufRead, args: &Args) -> ExitCode {
+    let mut run = Run {
+        reporter: Reporters(runner::reporters(args)),
+        show_output: args.show_output,
+        announced: None,
+        pending: Vec::new(),
+        tests: Vec::new(),
+        started: false,
+        running: None,
+        outcomes: Vec::new(),
+        summary: RunSummary::default(),
+        start: Instant::now(),
+        ended: false,
+    };
+    for line in input.lines() {
+        match line {
+            Ok(line) => run.line(line.trim_end()),
+            Err(err) => {
+                eprintln!("error: cannot read the target's messages: {}", err);
+                break;
+            }
+        }
+        if run.ended {
+            break;
+        }
+    }
+    run.finish()
+}
+
+/// A run being decoded.
+struct Run {
+    reporter: Reporters,
+    show_output: bool,
+    /// The number of tests given by `tust:run`.
+    announced: Option<usize>,
+    /// Tests announced and not yet started, with their tags.
+    pending: Vec<(TestDef, Vec<&'static str>)>,
+    /// Every test, once the first one starts.
+    tests: Vec<&'static TestDef>,
+    /// Whether the start of the run has been reported.
+    started: bool,
+    running: Option<Running>,
+    outcomes: Vec<TestOutcome>,
+    summary: RunSummary,
+    start: Instant,
+    /// Whether the target sent `tust:end`.
+    ended: bool,
+}
+
+/// The test running on the target.
+struct Running {
+    test: &'static TestDef,
+    start: Instant,
+    output: String,
+}
+
+impl Run {
+    fn line(&mut self, line: &str) {
+        let Some(message) = line.find(PREFIX).map(|at| &line[at + PREFIX.len()..]) else {
+            match &mut self.running {
+                Some(running) => {
+                    running.output.push_str(line);
+                    running.output.push('\n');
+                }
+                None => eprintln!("{}", line),
+            }
+            return;
+        };
+        let (kind, rest) = message.split_once(' ').unwrap_or((message, ""));
+        match kind {
+            "run" => self.announced = rest.parse().ok(),
+            "test" => match announce(rest) {
+                Some(test) => self.pending.push((test, Vec::new())),
+                None => eprintln!("warning: cannot read the test in `{}`", line),
+            },
+            "tag" => {
+                if let Some((_, tags)) = self.pending.last_mut() {
+                    tags.push(leak(rest));
+                }
+            }
+            "should_fail" | "xfail" => {
+                if let Some((test, _)) = self.pending.last_mut() {
+                    let text = (!rest.is_empty()).then(|| leak(rest));
+                    test.expected = if kind == "xfail" {
+                        ExpectedResult::XFail { reason: text }
+                    } else {
+                        ExpectedResult::ShouldFail { expected: text }
+                    };
+                }
+            }
+            "start" => {
+                self.start_run();
+                match rest
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| self.tests.get(index))
+                {
+                    Some(test) => {
+                        self.running = Some(Running {
+                            test,
+                            start: Instant::now(),
+                            output: String::new(),
+                        });
+                    }
+                    None => eprintln!("warning: the target started an unknown test: `{}`", line),
+                }
+            }
+            "ok" => self.finish_test(TestStatus::Passed),
+            "skipped" => {
+                let reason = (!rest.is_empty()).then(|| rest.to_string());
+                self.finish_test(TestStatus::Skipped(reason));
+            }
+            "end" => self.ended = true,
+            _ => eprintln!("warning: unknown message from the target: `{}`", line),
+        }
+    }
+
+    /// Reports the start of the run, once every test is announced.
+    fn start_run(&mut self) {
+        if self.started {
+            return;
+        }
+        for (mut test, tags) in self.pending.drain(..) {
+            test.tags = Box::leak(tags.into_boxed_slice());
+            self.tests.push(Box::leak(Box::new(test)));
+        }
+        self.reporter.on_run_start(&self.tests);
+        self.start = Instant::now();
+        self.started = true;
+    }
+
+    /// Reports the test running as having ended with `status`.
+    fn finish_test(&mut self, status: TestStatus) {
+        let Some(Running {
+            test,
+            start,
+            output,
+        }) = self.running.take()
+        else {
+            return;
+        };
+        let skipped = matches!(status, TestStatus::Skipped(_));
+        let outcome = TestOutcome {
+            test,
+            status,
+            duration: start.elapsed(),
+            output,
+            attempts: if skipped { 0 } else { 1 },
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        };
+        let mut outcome = if skipped {
+            outcome
+        } else {
+            runner::check_expected(outcome)
+        };
+        if !self.show_output && !outcome.status.is_failure() {
+            outcome.output.clear();
+        }
+        self.reporter.on_progress(test);
+        self.reporter.on_test_start(test);
+        self.summary.record(&outcome);
+        self.reporter.on_test_finish(&outcome);
+        self.outcomes.push(outcome);
+    }
+
+    fn finish(mut self) -> ExitCode {
+        let Some(announced) = self.announced else {
+            eprintln!(
+                "error: the target reported no tests; its program must call \
+                 `tust_embedded::run()` and log with defmt"
+            );
+            return ExitCode::from(EXIT_TESTS_FAILED);
+        };
+        self.start_run();
+        let mut note = None;
+        if let Some(running) = &mut self.running {
+            let output = std::mem::take(&mut running.output);
+            let (before, panic) = match output.find("panicked") {
+                Some(at) => output.split_at(output[..at].rfind('\n').map_or(0, |end| end + 1)),
+                None => (output.as_str(), ""),
+            };
+            let message = match panic.trim_end() {
+                "" => "the target stopped while the test was running".to_string(),
+                panic => panic.to_string(),
+            };
+            running.output = before.to_string();
+            note = Some(format!(
+                "note: panics stop the target, so the run ended when `{}` panicked",
+                running.test.path()
+            ));
+            self.finish_test(TestStatus::Failed(vec![TestFailure::new(message)]));
+        } else if !self.ended {
+            note = Some(format!(
+                "note: the target stopped after {} of its {} tests",
+                self.outcomes.len(),
+                announced
+            ));
+        }
+
+        self.summary.not_run = self.tests.len().saturating_sub(self.outcomes.len());
+        self.summary.duration = self.start.elapsed();
+        self.reporter.on_run_end(&self.outcomes, &self.summary);
+        if let Some(note) = note {
+            eprintln!("{}", note);
+        }
+        if self.summary.not_run == 0 && self.summary.is_success() {
+            ExitCode::from(EXIT_SUCCESS)
+        } else {
+            ExitCode::from(EXIT_TESTS_FAILED)
+        }
+    }
+}
+
+/// The test announced by `tust:test` with `rest`.
+fn announce(rest: &str) -> Option<TestDef> {
+    let mut fields = rest.splitn(4, ' ');
+    let module_path = fields.next()?;
+    let name = fields.next()?;
+    let line = fields.next()?.parse().ok()?;
+    let file = fields.next()?;
+    Some(TestDef::ran_elsewhere(
+        leak(module_path),
+        leak(name),
+        leak(file),
+        line,
+    ))
+}
+
+/// Tests live as long as the decoding process, like registered ones do.
+fn leak(text: &str) -> &'static str {
+    Box::leak(text.to_string().into_boxed_str())
+}
@@ -0,0 +1,124 @@
+//! Built-in `env` fixture: environment variables changed for one test.
+//!
+//! A test parameter named `env` of type [`Env`] receives a guard through
+//! which the test sets and removes environment variables; every variable it
+//! touches gets its original value back when the test ends, whether it passed
+//! or not. `#[with_env("KEY" = value)]` below `#[tust::test]` sets variables
+//! the same way before the test's fixtures are created, and
+//! `#[with_locale("de_DE.UTF-8", tz = "Europe/Berlin")]` sets `LC_ALL`,
+//! `LANG`, and `TZ` for the locale and the time zone.
+//!
+//! The environment is shared by the whole process, so tests that request an
+//! [`Env`], directly or through their fixtures, and tests with `#[with_env]`
+//! or `#[with_locale]` run serially, like `#[serial]` tests.
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn reads_the_log_level(mut env: Env) {
+//!     env.set("LOG_LEVEL", "debug");
+//!     env.remove("NO_COLOR");
+//!     assert_eq!(Config::from_env().log_level, Level::Debug);
+//! }
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+
+use crate::fixture::{self, FixtureDef, FixtureError, FixtureRequest, Fixtures, Provider};
+use crate::registry::TestDef;
+
+inventory::submit! {
+    FixtureDef {
+        name: "env",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<Env>,
+        type_id: TypeId::of::<Env>,
+        provider: Provider::PerTest(provide_env),
+    }
+}
+
+fn provide_env(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    Ok(Box::new(Env::new()))
+}
+
+/// Sets and removes environment variables, restoring the original value of
+/// each when dropped.
+#[derive(Debug, Default)]
+pub struct Env {
+    /// Every variable changed so far, with its value before the first change.
+    saved: Vec<(OsString, Option<OsString>)>,
+}
+
+impl Env {
+    /// A guard that has not changed anything yet.
+    pub fn new() -> Self {
+        Env { saved: Vec::new() }
+    }
+
+    /// Sets the variable `key` to `value` until the guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is empty or contains `=` or a NUL character, or if
+    /// `value` contains a NUL character.
+    pub fn set(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.save(key);
+        std::env::set_var(key, value);
+    }
+
+    /// Removes the variable `key` until the guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is empty or contains `=` or a NUL character.
+    pub fn remove(&mut self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.save(key);
+        std::env::remove_var(key);
+    }
+
+    fn save(&mut self, key: &OsStr) {
+        if !self.saved.iter().any(|(saved, _)| saved == key) {
+            self.saved.push((key.to_owned(), std::env::var_os(key)));
+        }
+    }
+}
+
+impl Drop for Env {
+    fn drop(&mut self) {
+        for (key, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
+            }
+        }
+    }
+}
+
+/// Whether `test` is given an [`Env`], directly or through its fixtures, and
+/// so changes the environment.
+pub(crate) fn used_by(test: &TestDef) -> bool {
+    let mut seen = HashSet::new();
+    requests_env(test.fixtures, test.module_path, &mut seen)
+}
+
+fn requests_env(
+    requests: &[FixtureRequest],
+    module: &str,
+    seen: &mut HashSet<*const FixtureDef>,
+) -> bool {
+    requests.iter().any(|request| {
+        if (request.type_id)() == TypeId::of::<Env>() {
+            return true;
+        }
+        match fixture::find(request.name, module, (request.type_name)()) {
+            Ok(def) if seen.insert(def) => requests_env(def.dependencies, def.module_path, seen),
+            _ => false,
+        }
+    })
+}
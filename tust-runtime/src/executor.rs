@@ -0,0 +1,148 @@
+//! Executors for `async` tests.
+//!
+//! `#[tust::test]` on an `async fn` blocks on the test's future with
+//! [`block_on`]. Which executor runs it is decided by the test's
+//! `runtime = "..."` argument, falling back to Tokio when the `tokio` feature
+//! is enabled, then async-std when the `async-std` feature is enabled, and
+//! finally a minimal built-in executor that can drive runtime-agnostic
+//! futures on the test's thread.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Which async runtime executes a test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncRuntime {
+    /// The first available of Tokio, async-std, and the built-in executor.
+    Default,
+    Tokio,
+    AsyncStd,
+    /// The built-in single-threaded executor. It provides no timers or I/O.
+    Builtin,
+}
+
+/// Scheduler flavor, as understood by Tokio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    CurrentThread,
+    MultiThread,
+}
+
+/// Executor options given to `#[tust::test(...)]` on an async test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsyncConfig {
+    pub runtime: AsyncRuntime,
+    pub flavor: Flavor,
+    /// Worker threads of a multi-threaded runtime; defaults to the number of
+    /// CPUs.
+    pub worker_threads: Option<usize>,
+}
+
+impl AsyncConfig {
+    pub const DEFAULT: AsyncConfig = AsyncConfig {
+        runtime: AsyncRuntime::Default,
+        flavor: Flavor::CurrentThread,
+        worker_threads: None,
+    };
+}
+
+impl Default for AsyncConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Runs `future` to completion on the executor selected by `config`.
+///
+/// # Panics
+///
+/// Panics if the selected runtime's feature is not enabled, or if the
+/// built-in executor is asked for a multi-threaded flavor.
+pub fn block_on<F: Future>(config: &AsyncConfig, future: F) -> F::Output {
+    match resolve(config.runtime) {
+        AsyncRuntime::Tokio => tokio_block_on(config, future),
+        AsyncRuntime::AsyncStd => async_std_block_on(future),
+        AsyncRuntime::Builtin | AsyncRuntime::Default => {
+            assert!(
+                config.flavor == Flavor::CurrentThread,
+                "the `multi_thread` flavor needs the `tokio` feature of tust"
+            );
+            builtin_block_on(future)
+        }
+    }
+}
+
+fn resolve(runtime: AsyncRuntime) -> AsyncRuntime {
+    match runtime {
+        AsyncRuntime::Default if cfg!(feature = "tokio") => AsyncRuntime::Tokio,
+        AsyncRuntime::Default if cfg!(feature = "async-std") => AsyncRuntime::AsyncStd,
+        AsyncRuntime::Default => AsyncRuntime::Builtin,
+        runtime => runtime,
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn tokio_block_on<F: Future>(config: &AsyncConfig, future: F) -> F::Output {
+    let mut builder = match config.flavor {
+        Flavor::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+        Flavor::MultiThread => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            if let Some(threads) = config.worker_threads {
+                builder.worker_threads(threads);
+            }
+            builder
+        }
+    };
+    let spawns = crate::strict::TaskSpawns::hook(&mut builder);
+    let runtime = builder
+        .enable_all()
+        .build()
+        .expect("failed to build the Tokio runtime for an async test");
+    let output = runtime.block_on(future);
+    crate::strict::tokio_tasks_left(runtime.metrics().num_alive_tasks(), spawns);
+    output
+}
+
+#[cfg(not(feature = "tokio"))]
+fn tokio_block_on<F: Future>(_config: &AsyncConfig, _future: F) -> F::Output {
+    panic!("this test requires the `tokio` feature of tust");
+}
+
+#[cfg(feature = "async-std")]
+fn async_std_block_on<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
+
+#[cfg(not(feature = "async-std"))]
+fn async_std_block_on<F: Future>(_future: F) -> F::Output {
+    panic!("this test requires the `async-std` feature of tust");
+}
+
+/// Wakes the thread blocked in [`builtin_block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn builtin_block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! The tests that failed the last time they ran.
+//!
+//! After every run, the runner records which of the tests it ran failed, in
+//! `tust/last-failed.json` in Cargo's target directory, or the file named by
+//! [`CACHE_ENV`]. Tests that did not run keep their recorded state, so the
+//! file holds the tests that failed the last time they ran. `--last-failed`
+//! then runs only those tests, or every test if none of them failed, and
+//! `--failed-first` runs them before the others, so that a run checking a fix
+//! reports on it first.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::outcome::TestOutcome;
+use crate::registry::TestDef;
+use crate::timings;
+
+/// Environment variable naming the cache file; if set but empty, failures
+/// are neither read nor recorded.
+pub const CACHE_ENV: &str = "TUST_LAST_FAILED";
+
+/// Tests that failed the last time they ran.
+#[derive(Debug, Clone, Default)]
+pub struct Failures {
+    tests: BTreeSet<String>,
+}
+
+impl Failures {
+    /// Reads the tests recorded in `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tests = value["failed"].as_array().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "expected a `failed` array")
+        })?;
+        Ok(Failures {
+            tests: tests
+                .iter()
+                .filter_map(|test| Some(test.as_str()?.to_string()))
+                .collect(),
+        })
+    }
+
+    /// Writes the tests to `path`, creating its directory if needed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(&json!({ "failed": self.tests }))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        text.push('\n');
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    pub fn contains(&self, test: &TestDef) -> bool {
+        self.tests.contains(&timings::key(test))
+    }
+
+    /// Records whether each test in `outcomes` that ran failed.
+    pub(crate) fn record(&mut self, outcomes: &[TestOutcome]) {
+        for outcome in outcomes.iter().filter(|outcome| outcome.attempts > 0) {
+            let key = timings::key(outcome.test);
+            if outcome.status.is_failure() {
+                self.tests.insert(key);
+            } else {
+                self.tests.remove(&key);
+            }
+        }
+    }
+}
+
+/// The cache file: [`CACHE_ENV`] if set, else `tust/last-failed.json` in
+/// the target directory holding the running binary, if there is one.
+pub fn cache_path() -> Option<PathBuf> {
+    cache::path(CACHE_ENV, "last-failed.json")
+}
+
+/// The failures recorded in the cache, if any.
+pub(crate) fn load_cache() -> Failures {
+    cache_path()
+        .and_then(|path| Failures::read(&path).ok())
+        .unwrap_or_default()
+}
+
+/// Records the failures of `outcomes` in the cache.
+pub(crate) fn update_cache(outcomes: &[TestOutcome]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    let mut failures = Failures::read(&path).unwrap_or_default();
+    failures.record(outcomes);
+    if let Err(err) = failures.write(&path) {
+        eprintln!(
+            "warning: failed to record test failures in {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::outcome::{TestFailure, TestStatus};
+
+    fn test(name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            "krate::db",
+            name,
+            "t.rs",
+            1,
+        )))
+    }
+
+    fn outcome(test: &'static TestDef, failed: bool, attempts: u32) -> TestOutcome {
+        TestOutcome {
+            test,
+            status: if failed {
+                TestStatus::Failed(vec![TestFailure::new("failed")])
+            } else {
+                TestStatus::Passed
+            },
+            duration: Duration::ZERO,
+            output: String::new(),
+            attempts,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_tests_that_failed_their_last_run() {
+        let (insert, query, fixed) = (test("insert"), test("query"), test("fixed"));
+        let mut failures = Failures::default();
+        failures.record(&[outcome(query, true, 1), outcome(fixed, true, 1)]);
+        failures.record(&[
+            outcome(insert, false, 1),
+            outcome(fixed, false, 2),
+            outcome(query, false, 0),
+        ]);
+        assert!(!failures.contains(insert));
+        assert!(!failures.contains(fixed));
+        // Skipped this time, so still failing as of its last run.
+        assert!(failures.contains(query));
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let dir = std::env::temp_dir().join(format!("tust-failures-{}", std::process::id()));
+        let path = dir.join("nested").join("last-failed.json");
+        let query = test("query");
+        let mut failures = Failures::default();
+        failures.record(&[outcome(query, true, 1)]);
+        failures.write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"failed\": [\n    \"krate::db::query\"\n  ]\n}\n"
+        );
+        assert!(Failures::read(&path).unwrap().contains(query));
+
+        fs::write(&path, r#"{"failed": "krate::db::query"}"#).unwrap();
+        assert_eq!(
+            Failures::read(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+//! Name patterns accepted by `--filter` and `--filter-regex`.
+//!
+//! A glob matches a test's full name, such as `db::users::insert_one`: `*`
+//! matches any characters within one path segment, `**` any characters
+//! across segments, and `?` one character other than `:`. So
+//! `db::*::insert_*` matches `db::users::insert_one` but not
+//! `db::users::batch::insert_many`, which `db::**::insert_*` matches.
+//!
+//! A regular expression matches anywhere in the name unless anchored with `^`
+//! and `$`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// A pattern selecting tests by their full name.
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    Glob(Glob),
+    Regex(Regex),
+}
+
+impl NamePattern {
+    /// Whether the test named `name` matches the pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Glob(glob) => glob.matches(name),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// A parsed glob, matched against whole test names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    One,
+    /// `*`
+    Segment,
+    /// `**`
+    Any,
+}
+
+impl Glob {
+    /// Whether `name` matches the glob from start to end.
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        matches(&self.tokens, &name)
+    }
+}
+
+/// Whether `tokens` match the whole of `name`, in time proportional to the
+/// number of tokens times the length of the name, however many stars the
+/// pattern has.
+fn matches(tokens: &[Token], name: &[char]) -> bool {
+    // `next[at]` holds whether the tokens after the current one match
+    // `name[at..]`, starting with no tokens, which match only the end.
+    let mut next: Vec<bool> = (0..=name.len()).map(|at| at == name.len()).collect();
+    let mut current = vec![false; name.len() + 1];
+    for token in tokens.iter().rev() {
+        for at in (0..=name.len()).rev() {
+            let c = name.get(at).copied();
+            current[at] = match token {
+                Token::Char(expected) => c == Some(*expected) && next[at + 1],
+                Token::One => c.is_some_and(|c| c != ':') && next[at + 1],
+                Token::Segment => next[at] || (c.is_some_and(|c| c != ':') && current[at + 1]),
+                Token::Any => next[at] || (c.is_some() && current[at + 1]),
+            };
+        }
+        std::mem::swap(&mut next, &mut current);
+    }
+    next[0]
+}
+
+impl FromStr for Glob {
+    type Err = GlobError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if pattern.is_empty() {
+            return Err(GlobError("the pattern is empty".to_string()));
+        }
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' if chars.next_if_eq(&'*').is_some() => {
+                    if chars.peek() == Some(&'*') {
+                        return Err(GlobError("`***` is not a pattern".to_string()));
+                    }
+                    Token::Any
+                }
+                '*' => Token::Segment,
+                '?' => Token::One,
+                c => Token::Char(c),
+            });
+        }
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            tokens,
+        })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// A glob that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobError(String);
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for GlobError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn star_matches_within_one_segment() {
+        let pattern = glob("db::*::insert_*");
+        assert!(pattern.matches("db::users::insert_one"));
+        assert!(pattern.matches("db::::insert_"));
+        assert!(!pattern.matches("db::users::batch::insert_many"));
+        assert!(!pattern.matches("db::users::update_one"));
+        assert!(glob("*").matches("insert"));
+        assert!(!glob("*").matches("db::insert"));
+    }
+
+    #[test]
+    fn double_star_matches_across_segments() {
+        let pattern = glob("db::**::insert_*");
+        assert!(pattern.matches("db::users::insert_one"));
+        assert!(pattern.matches("db::users::batch::insert_many"));
+        assert!(!pattern.matches("db::insert_one"));
+        assert!(glob("**").matches("a::b::c"));
+        assert!(glob("db::**").matches("db::"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character_other_than_a_colon() {
+        assert!(glob("case_?").matches("case_1"));
+        assert!(!glob("case_?").matches("case_12"));
+        assert!(!glob("case_?").matches("case_"));
+        assert!(!glob("a?:b").matches("a::b"));
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(glob("parser").matches("parser"));
+        assert!(!glob("parser").matches("parser::empty"));
+        assert!(!glob("empty").matches("parser::empty"));
+    }
+
+    #[test]
+    fn rejects_empty_and_triple_star_patterns() {
+        assert_eq!(
+            "".parse::<Glob>().unwrap_err().to_string(),
+            "the pattern is empty"
+        );
+        assert_eq!(
+            "a::***".parse::<Glob>().unwrap_err().to_string(),
+            "`***` is not a pattern"
+        );
+        assert_eq!(glob("a::**").to_string(), "a::**");
+    }
+
+    #[test]
+    fn many_stars_match_long_names_quickly() {
+        let pattern = glob("**a**a**a**a**a**a**a**b");
+        let name = "a".repeat(5_000);
+        let start = std::time::Instant::now();
+        assert!(!pattern.matches(&name));
+        assert!(pattern.matches(&format!("{}b", name)));
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_unless_anchored() {
+        let pattern = NamePattern::Regex(Regex::new("insert_(one|two)").unwrap());
+        assert!(pattern.matches("db::users::insert_one"));
+        let anchored = NamePattern::Regex(Regex::new("^insert$").unwrap());
+        assert!(!anchored.matches("db::insert"));
+    }
+}
@@ -0,0 +1,693 @@
+//! Fixture registry and dependency-injecting resolver.
+//!
+//! `#[fixture]` registers a [`FixtureDef`] for every fixture function. When a
+//! test runs, each of its parameters is resolved by name through
+//! [`Fixtures::get`], which constructs the fixture (resolving the fixture's own
+//! parameters first) or, for session-scoped fixtures, clones the cached value.
+//!
+//! When several fixtures share a name, the one declared in the module closest
+//! to the requesting test or fixture wins.
+//!
+//! Crates that publish fixtures for others to use, such as a client for a
+//! service started once per run, implement [`Fixture`], or [`AsyncFixture`]
+//! when setting the value up awaits, and register the type under a name with
+//! `tust::register_fixture!`. The trait says how the value is set up from
+//! the fixtures it depends on, which it gets with [`Fixtures::get`], how it
+//! is torn down, and its scope:
+//!
+//! ```ignore
+//! #[derive(Clone)]
+//! pub struct Redis {
+//!     container: Container,
+//!     url: String,
+//! }
+//!
+//! impl Fixture for Redis {
+//!     const SCOPE: Scope = Scope::Session;
+//!
+//!     fn setup(_: &mut Fixtures) -> Result<Self, FixtureError> {
+//!         let container = Container::builder("redis:7")
+//!             .port(6379)
+//!             .start()
+//!             .expect("cannot start redis");
+//!         let url = format!("redis://{}", container.address(6379));
+//!         Ok(Redis { container, url })
+//!     }
+//!
+//!     fn teardown(self) {
+//!         let _ = redis::Client::open(self.url).and_then(|c| c.flushall());
+//!     }
+//! }
+//!
+//! tust::register_fixture!(redis: Redis);
+//! ```
+//!
+//! A test of a crate depending on the one above then takes a `redis: Redis`
+//! parameter. A fixture of the same name declared in the test's own crate
+//! is closer to the test, and replaces the registered one.
+//!
+//! Each test, or the run for a session-scoped fixture, gets a clone of the
+//! value that was set up, and `teardown` gets the value itself: when the
+//! test ends, with its cleanups, or when the run ends, after the session
+//! fixtures created later. An async fixture's setup and teardown run on an
+//! executor of their own, before the test's executor starts, so the value
+//! must not need the runtime that set it up to stay alive.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::alloc;
+use crate::context::TestContext;
+use crate::executor::{self, AsyncConfig};
+
+/// How long a fixture value lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// A fresh value is created for every parameter that requests it.
+    Test,
+    /// Created once per run and cloned for every parameter that requests it.
+    /// Dropped when the run ends.
+    Session,
+}
+
+/// A request for a fixture made by a test or fixture parameter.
+#[derive(Debug)]
+pub struct FixtureRequest {
+    /// Parameter name, which selects the fixture.
+    pub name: &'static str,
+    /// Parameter type.
+    pub type_name: fn() -> &'static str,
+    pub type_id: fn() -> TypeId,
+}
+
+/// Creates fixture values, according to the fixture's scope.
+pub enum Provider {
+    PerTest(fn(&mut Fixtures) -> Result<Box<dyn Any>, FixtureError>),
+    PerSession {
+        create: fn(&mut Fixtures) -> Result<Box<dyn Any + Send>, FixtureError>,
+        clone: fn(&(dyn Any + Send)) -> Box<dyn Any>,
+    },
+}
+
+impl fmt::Debug for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provider::PerTest(_) => f.write_str("PerTest"),
+            Provider::PerSession { .. } => f.write_str("PerSession"),
+        }
+    }
+}
+
+/// Static description of a registered fixture.
+///
+/// Values of this type are generated by `#[fixture]`; they are not meant to
+/// be constructed by hand.
+#[derive(Debug)]
+pub struct FixtureDef {
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// The fixture function's own parameters.
+    pub dependencies: &'static [FixtureRequest],
+    /// Type of the provided value.
+    pub type_name: fn() -> &'static str,
+    pub type_id: fn() -> TypeId,
+    pub provider: Provider,
+}
+
+inventory::collect!(FixtureDef);
+
+impl FixtureDef {
+    pub fn scope(&self) -> Scope {
+        match self.provider {
+            Provider::PerTest(_) => Scope::Test,
+            Provider::PerSession { .. } => Scope::Session,
+        }
+    }
+
+    fn key(&'static self) -> usize {
+        self as *const FixtureDef as usize
+    }
+}
+
+/// Why a fixture could not be provided.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureError {
+    /// No fixture with the requested name is registered.
+    Missing { name: String, type_name: String },
+    /// Several fixtures with the requested name are equally close to the
+    /// requesting module.
+    Ambiguous { name: String, modules: Vec<String> },
+    /// The fixture provides a different type than the parameter expects.
+    TypeMismatch {
+        name: String,
+        expected: String,
+        provided: String,
+    },
+    /// The fixture depends on itself, directly or transitively. Holds the
+    /// chain of fixture names, starting and ending with the same fixture.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::Missing { name, type_name } => write!(
+                f,
+                "no fixture named `{}` is registered (requested as `{}`)",
+                name, type_name
+            ),
+            FixtureError::Ambiguous { name, modules } => write!(
+                f,
+                "fixture `{}` is ambiguous; it is defined in {}",
+                name,
+                modules
+                    .iter()
+                    .map(|module| format!("`{}`", module))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            FixtureError::TypeMismatch {
+                name,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "fixture `{}` provides `{}` but the parameter has type `{}`",
+                name, provided, expected
+            ),
+            FixtureError::Cycle(chain) => {
+                write!(f, "fixture dependency cycle: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+/// Picks the fixture named `name` that is closest to `module`: the one whose
+/// module is the longest prefix of `module`, or the only one with that name.
+pub(crate) fn find(
+    name: &str,
+    module: &str,
+    request_type: &str,
+) -> Result<&'static FixtureDef, FixtureError> {
+    let candidates: Vec<&'static FixtureDef> = inventory::iter::<FixtureDef>
+        .into_iter()
+        .filter(|def| def.name == name)
+        .collect();
+
+    let encloses = |def: &FixtureDef| {
+        module == def.module_path
+            || module
+                .strip_prefix(def.module_path)
+                .is_some_and(|rest| rest.starts_with("::"))
+    };
+    let closest = candidates
+        .iter()
+        .filter(|def| encloses(def))
+        .map(|def| def.module_path.len())
+        .max();
+    let matching: Vec<_> = match closest {
+        Some(len) => candidates
+            .iter()
+            .copied()
+            .filter(|def| encloses(def) && def.module_path.len() == len)
+            .collect(),
+        None => candidates,
+    };
+
+    match matching.as_slice() {
+        [def] => Ok(def),
+        [] => Err(FixtureError::Missing {
+            name: name.to_string(),
+            type_name: request_type.to_string(),
+        }),
+        defs => Err(FixtureError::Ambiguous {
+            name: name.to_string(),
+            modules: defs.iter().map(|def| def.module_path.to_string()).collect(),
+        }),
+    }
+}
+
+/// The fixture a parameter named `name` of type `type_name` asks for from
+/// `module`, while the fixtures of `stack` are being created, outermost
+/// first.
+fn resolve(
+    name: &str,
+    module: &str,
+    type_name: &str,
+    type_id: TypeId,
+    stack: &[&'static FixtureDef],
+) -> Result<&'static FixtureDef, FixtureError> {
+    let def = find(name, module, type_name)?;
+    if (def.type_id)() != type_id {
+        return Err(FixtureError::TypeMismatch {
+            name: name.to_string(),
+            expected: type_name.to_string(),
+            provided: (def.type_name)().to_string(),
+        });
+    }
+    if let Some(start) = stack.iter().position(|entry| entry.key() == def.key()) {
+        let mut chain: Vec<_> = stack[start..]
+            .iter()
+            .map(|entry| entry.name.to_string())
+            .collect();
+        chain.push(def.name.to_string());
+        return Err(FixtureError::Cycle(chain));
+    }
+    Ok(def)
+}
+
+/// Checks, without creating any values, that the fixtures `requests` asks
+/// for from `module`, and those they depend on, would resolve. Only the
+/// dependencies a `#[fixture]` declares as parameters are known; those a
+/// [`Fixture`] resolves in its `setup` are not.
+pub(crate) fn check(
+    module: &'static str,
+    requests: &'static [FixtureRequest],
+) -> Result<(), FixtureError> {
+    fn check_all(
+        module: &'static str,
+        requests: &'static [FixtureRequest],
+        stack: &mut Vec<&'static FixtureDef>,
+    ) -> Result<(), FixtureError> {
+        for request in requests {
+            let def = resolve(
+                request.name,
+                module,
+                (request.type_name)(),
+                (request.type_id)(),
+                stack,
+            )?;
+            stack.push(def);
+            let checked = check_all(def.module_path, def.dependencies, stack);
+            stack.pop();
+            checked?;
+        }
+        Ok(())
+    }
+
+    check_all(module, requests, &mut Vec::new())
+}
+
+type SessionSlot = Arc<Mutex<Option<Box<dyn Any + Send>>>>;
+
+/// Values of session-scoped fixtures, keyed by their definition, plus the
+/// order in which they were created so they can be dropped in reverse.
+#[derive(Default)]
+struct SessionCache {
+    slots: HashMap<usize, SessionSlot>,
+    created: Vec<SessionSlot>,
+}
+
+static SESSION: Mutex<Option<SessionCache>> = Mutex::new(None);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn session_slot(def: &'static FixtureDef) -> SessionSlot {
+    let mut cache = lock(&SESSION);
+    let cache = cache.get_or_insert_with(SessionCache::default);
+    cache.slots.entry(def.key()).or_default().clone()
+}
+
+fn record_session_value(slot: &SessionSlot) {
+    let mut cache = lock(&SESSION);
+    cache
+        .get_or_insert_with(SessionCache::default)
+        .created
+        .push(Arc::clone(slot));
+}
+
+/// Drops every cached session-scoped fixture value, most recently created
+/// first.
+pub(crate) fn teardown_session() {
+    let cache = lock(&SESSION).take();
+    if let Some(cache) = cache {
+        for slot in cache.created.iter().rev() {
+            let value = lock(slot).take();
+            drop(value);
+        }
+    }
+}
+
+/// Resolves fixtures for a single test.
+pub struct Fixtures {
+    /// Module the test was declared in.
+    module: &'static str,
+    /// Fixtures currently being constructed, outermost first.
+    stack: Vec<&'static FixtureDef>,
+}
+
+impl Fixtures {
+    /// Creates a resolver for a test declared in `module`.
+    pub fn new(module: &'static str) -> Self {
+        Fixtures {
+            module,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Module the test was declared in.
+    pub(crate) fn module(&self) -> &'static str {
+        self.module
+    }
+
+    /// Provides the value of the fixture named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the fixture or one of its dependencies is missing, ambiguous,
+    /// of the wrong type, or part of a dependency cycle.
+    pub fn get<T: 'static>(&mut self, name: &'static str) -> Result<T, FixtureError> {
+        let module = self.stack.last().map_or(self.module, |def| def.module_path);
+        let def = resolve(
+            name,
+            module,
+            std::any::type_name::<T>(),
+            TypeId::of::<T>(),
+            &self.stack,
+        )?;
+
+        self.stack.push(def);
+        let value = self.provide(def);
+        self.stack.pop();
+
+        let value = value?
+            .downcast::<T>()
+            .expect("fixture provider returned a value of another type");
+        Ok(*value)
+    }
+
+    fn provide(&mut self, def: &'static FixtureDef) -> Result<Box<dyn Any>, FixtureError> {
+        match def.provider {
+            Provider::PerTest(create) => create(self),
+            Provider::PerSession { create, clone } => {
+                // The value outlives the test, so it is no leak of the test's.
+                let slot = alloc::untracked(|| session_slot(def));
+                let mut value = lock(&slot);
+                if value.is_none() {
+                    *value = Some(alloc::untracked(|| create(self))?);
+                    alloc::untracked(|| record_session_value(&slot));
+                }
+                Ok(clone(
+                    value.as_deref().expect("session value was just created"),
+                ))
+            }
+        }
+    }
+}
+
+/// A fixture another crate can register with `tust::register_fixture!`;
+/// see [the module](self).
+pub trait Fixture: Clone + Send + 'static {
+    /// How long the value lives.
+    const SCOPE: Scope = Scope::Test;
+
+    /// Sets up the value, resolving the fixtures it depends on with
+    /// [`Fixtures::get`]. Panics to fail the tests that requested it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if one of the fixtures it depends on cannot be provided.
+    fn setup(fixtures: &mut Fixtures) -> Result<Self, FixtureError>;
+
+    /// Tears the value down once its scope ends. Dropping it is the default.
+    fn teardown(self) {}
+}
+
+/// A future of a method of [`AsyncFixture`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// A [`Fixture`] whose setup and teardown are async.
+pub trait AsyncFixture: Clone + Send + 'static {
+    /// How long the value lives.
+    const SCOPE: Scope = Scope::Test;
+
+    /// The executor setup and teardown run on.
+    const RUNTIME: AsyncConfig = AsyncConfig::DEFAULT;
+
+    /// Sets up the value, as [`Fixture::setup`] does.
+    fn setup(fixtures: &mut Fixtures) -> BoxFuture<'_, Result<Self, FixtureError>>;
+
+    /// Tears the value down once its scope ends. Dropping it is the default.
+    fn teardown(self) -> BoxFuture<'static, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// The scope, setup, and teardown of a [`Fixture`] or [`AsyncFixture`].
+trait Lifecycle: 'static {
+    type Value: Clone + Send + 'static;
+    const SCOPE: Scope;
+
+    fn setup(fixtures: &mut Fixtures) -> Result<Self::Value, FixtureError>;
+    fn teardown(value: Self::Value);
+}
+
+struct Blocking<T>(PhantomData<fn() -> T>);
+
+impl<T: Fixture> Lifecycle for Blocking<T> {
+    type Value = T;
+    const SCOPE: Scope = T::SCOPE;
+
+    fn setup(fixtures: &mut Fixtures) -> Result<T, FixtureError> {
+        T::setup(fixtures)
+    }
+
+    fn teardown(value: T) {
+        value.teardown();
+    }
+}
+
+struct Async<T>(PhantomData<fn() -> T>);
+
+impl<T: AsyncFixture> Lifecycle for Async<T> {
+    type Value = T;
+    const SCOPE: Scope = T::SCOPE;
+
+    fn setup(fixtures: &mut Fixtures) -> Result<T, FixtureError> {
+        executor::block_on(&T::RUNTIME, T::setup(fixtures))
+    }
+
+    fn teardown(value: T) {
+        executor::block_on(&T::RUNTIME, value.teardown());
+    }
+}
+
+/// Owns the value a fixture set up, and tears it down when dropped.
+struct Teardown<L: Lifecycle>(Option<L::Value>);
+
+impl<L: Lifecycle> Drop for Teardown<L> {
+    fn drop(&mut self) {
+        if let Some(value) = self.0.take() {
+            L::teardown(value);
+        }
+    }
+}
+
+/// The provider of the [`Fixture`] `T`.
+pub const fn provider<T: Fixture>() -> Provider {
+    lifecycle_provider::<Blocking<T>>()
+}
+
+/// The provider of the [`AsyncFixture`] `T`.
+pub const fn async_provider<T: AsyncFixture>() -> Provider {
+    lifecycle_provider::<Async<T>>()
+}
+
+const fn lifecycle_provider<L: Lifecycle>() -> Provider {
+    match L::SCOPE {
+        Scope::Test => Provider::PerTest(create_per_test::<L>),
+        Scope::Session => Provider::PerSession {
+            create: create_per_session::<L>,
+            clone: clone_per_session::<L>,
+        },
+    }
+}
+
+fn create_per_test<L: Lifecycle>(fixtures: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let value = L::setup(fixtures)?;
+    // Outside a test there is no end to tear the value down at.
+    if let Some(ctx) = TestContext::current() {
+        let teardown = Teardown::<L>(Some(value.clone()));
+        ctx.add_cleanup(move || drop(teardown));
+    }
+    Ok(Box::new(value))
+}
+
+fn create_per_session<L: Lifecycle>(
+    fixtures: &mut Fixtures,
+) -> Result<Box<dyn Any + Send>, FixtureError> {
+    Ok(Box::new(Teardown::<L>(Some(L::setup(fixtures)?))))
+}
+
+fn clone_per_session<L: Lifecycle>(value: &(dyn Any + Send)) -> Box<dyn Any> {
+    let teardown = value
+        .downcast_ref::<Teardown<L>>()
+        .expect("session fixture cache holds a value of another type");
+    Box::new(teardown.0.clone().expect("session fixture was torn down"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+        unreachable!("checking fixtures creates no values")
+    }
+
+    const fn fixture(
+        name: &'static str,
+        module_path: &'static str,
+        dependencies: &'static [FixtureRequest],
+        type_name: fn() -> &'static str,
+        type_id: fn() -> TypeId,
+    ) -> FixtureDef {
+        FixtureDef {
+            name,
+            module_path,
+            file: "src/app.rs",
+            line: 1,
+            dependencies,
+            type_name,
+            type_id,
+            provider: Provider::PerTest(create),
+        }
+    }
+
+    const fn request(
+        name: &'static str,
+        type_name: fn() -> &'static str,
+        type_id: fn() -> TypeId,
+    ) -> FixtureRequest {
+        FixtureRequest {
+            name,
+            type_name,
+            type_id,
+        }
+    }
+
+    inventory::submit! {
+        fixture("check_port", "app", &[], std::any::type_name::<u16>, TypeId::of::<u16>)
+    }
+    inventory::submit! {
+        fixture(
+            "check_url",
+            "app",
+            &[request("check_port", std::any::type_name::<u16>, TypeId::of::<u16>)],
+            std::any::type_name::<String>,
+            TypeId::of::<String>,
+        )
+    }
+    inventory::submit! {
+        fixture("check_shared", "app::a", &[], std::any::type_name::<u16>, TypeId::of::<u16>)
+    }
+    inventory::submit! {
+        fixture("check_shared", "app::b", &[], std::any::type_name::<u16>, TypeId::of::<u16>)
+    }
+    inventory::submit! {
+        fixture(
+            "check_loop",
+            "app",
+            &[request("check_back", std::any::type_name::<u16>, TypeId::of::<u16>)],
+            std::any::type_name::<u16>,
+            TypeId::of::<u16>,
+        )
+    }
+    inventory::submit! {
+        fixture(
+            "check_back",
+            "app",
+            &[request("check_loop", std::any::type_name::<u16>, TypeId::of::<u16>)],
+            std::any::type_name::<u16>,
+            TypeId::of::<u16>,
+        )
+    }
+    inventory::submit! {
+        fixture(
+            "check_broken",
+            "app",
+            &[request("check_gone", std::any::type_name::<u16>, TypeId::of::<u16>)],
+            std::any::type_name::<u16>,
+            TypeId::of::<u16>,
+        )
+    }
+
+    /// Checks the fixtures a test in `module` asks for with its parameters
+    /// `requests`, each a name and `u16` or `String`.
+    fn check_test(module: &'static str, requests: &[(&'static str, &str)]) -> Result<(), String> {
+        let requests: Vec<_> = requests
+            .iter()
+            .map(|&(name, ty)| match ty {
+                "u16" => request(name, std::any::type_name::<u16>, TypeId::of::<u16>),
+                _ => request(name, std::any::type_name::<String>, TypeId::of::<String>),
+            })
+            .collect();
+        check(module, Vec::leak(requests)).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn checks_fixtures_and_their_dependencies() {
+        assert_eq!(check_test("app::tests", &[]), Ok(()));
+        assert_eq!(
+            check_test(
+                "app::tests",
+                &[("check_url", "String"), ("check_port", "u16")]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check_test("app::tests", &[("check_broken", "u16")]),
+            Err("no fixture named `check_gone` is registered (requested as `u16`)".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_what_would_not_resolve() {
+        assert_eq!(
+            check_test("app", &[("check_missing", "String")]),
+            Err(
+                "no fixture named `check_missing` is registered (requested as \
+                 `alloc::string::String`)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_test("app", &[("check_port", "String")]),
+            Err(
+                "fixture `check_port` provides `u16` but the parameter has type \
+                 `alloc::string::String`"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_test("app", &[("check_loop", "u16")]),
+            Err("fixture dependency cycle: check_loop -> check_back -> check_loop".to_string())
+        );
+    }
+
+    #[test]
+    fn the_closest_of_fixtures_sharing_a_name_is_checked() {
+        assert_eq!(
+            check_test("app::a::tests", &[("check_shared", "u16")]),
+            Ok(())
+        );
+        let err = check_test("app", &[("check_shared", "u16")]).unwrap_err();
+        assert!(
+            err.starts_with("fixture `check_shared` is ambiguous; it is defined in ")
+                && err.contains("`app::a`")
+                && err.contains("`app::b`"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,454 @@
+//! Fuzz testing behind `#[tust::fuzz]`.
+//!
+//! A fuzz target is a test taking one input, either the raw bytes as `&[u8]`
+//! or a value of a type implementing [`Arbitrary`], generated from the bytes
+//! by [`from_bytes`]. Each run first replays every input saved in the
+//! target's corpus and artifact directories, and then tries mutations of
+//! them, derived from the run seed, until the target's budget of iterations
+//! or time is spent. An input on which the target panics or fails an
+//! assertion is shrunk, saved as an artifact, and fails the test, so it is
+//! replayed first on every later run.
+//!
+//! The directories follow the layout of cargo-fuzz, relative to the manifest
+//! directory of the crate under test: inputs are read from
+//! `fuzz/corpus/<target>` unless the target names another directory, and
+//! failing inputs are written to `fuzz/artifacts/<target>/crash-<hash>`.
+//! Inputs slower than [`SLOW_INPUT`] are written there as `slow-unit-<hash>`
+//! without failing the test. Without coverage instrumentation the runner
+//! cannot tell which passing inputs are interesting, so it adds none to the
+//! corpus; running the exported target under cargo-fuzz grows the same
+//! corpus directory, which every test run then replays.
+//!
+//! `--export-fuzz DIR` writes each target as a cargo-fuzz target,
+//! `DIR/fuzz_targets/<target>.rs`, and prints the `[[bin]]` entries to add to
+//! `DIR/Cargo.toml`; see [`export`].
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::outcome::TestFailure;
+use crate::property::{self, Arbitrary, Gen};
+use crate::rng::{self, Rng};
+use crate::soft;
+
+/// Environment variable setting the number of mutated inputs tried by
+/// targets that do not set `iterations` themselves.
+pub const ITERATIONS_ENV: &str = "TUST_FUZZ_ITERATIONS";
+
+/// Environment variable setting, as `500ms`, `30s`, or `2m`, how long targets
+/// that do not set `time` themselves try mutated inputs.
+pub const TIME_ENV: &str = "TUST_FUZZ_TIME";
+
+/// Mutated inputs tried when neither the target nor [`ITERATIONS_ENV`] sets a
+/// number.
+pub const DEFAULT_ITERATIONS: u64 = 10_000;
+
+/// How long mutated inputs are tried when neither the target nor
+/// [`TIME_ENV`] sets a time.
+pub const DEFAULT_TIME: Duration = Duration::from_secs(1);
+
+/// Inputs the target takes longer than this on are saved as slow.
+pub const SLOW_INPUT: Duration = Duration::from_millis(100);
+
+/// The longest input mutations produce.
+const MAX_LEN: usize = 4096;
+
+/// Settings of one `#[tust::fuzz]`.
+#[derive(Debug, Clone, Copy)]
+pub struct FuzzConfig {
+    /// The name of the target, its function's.
+    pub name: &'static str,
+    /// `CARGO_MANIFEST_DIR` of the crate defining the target.
+    pub manifest_dir: &'static str,
+    /// `iterations = N`
+    pub iterations: Option<u64>,
+    /// `time = MILLISECONDS`
+    pub time: Option<u64>,
+    /// `corpus = "dir"`, relative to the manifest directory.
+    pub corpus: Option<&'static str>,
+}
+
+/// A fuzz target, registered by `#[tust::fuzz]` for [`export`].
+#[derive(Debug)]
+pub struct FuzzDef {
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// The target's function, without its attributes.
+    pub source: &'static str,
+    /// The type of the target's input, as written.
+    pub input: &'static str,
+    /// Whether the input is `&[u8]`.
+    pub bytes: bool,
+}
+
+inventory::collect!(FuzzDef);
+
+/// A value of `T` generated from `bytes`, as a fuzz target taking `T` receives
+/// it; see [`Gen::from_bytes`].
+pub fn from_bytes<T: Arbitrary>(bytes: &[u8]) -> T {
+    T::arbitrary(&mut Gen::from_bytes(bytes))
+}
+
+/// Fuzzes a target taking raw bytes, recording a failure for the current test
+/// with the shrunk input the target fails on.
+#[track_caller]
+pub fn check_bytes(config: &FuzzConfig, target: impl Fn(&[u8])) {
+    fuzz(config, &target, &|bytes| format_bytes(bytes));
+}
+
+/// Fuzzes a target taking values of `T` generated from bytes, recording a
+/// failure for the current test with the input the target fails on.
+#[track_caller]
+pub fn check<T: Arbitrary>(config: &FuzzConfig, target: impl Fn(T)) {
+    fuzz(config, &|bytes| target(from_bytes(bytes)), &|bytes| {
+        format!("{:?}", from_bytes::<T>(bytes))
+    });
+}
+
+#[track_caller]
+fn fuzz(config: &FuzzConfig, target: &dyn Fn(&[u8]), describe: &dyn Fn(&[u8]) -> String) {
+    let root = Path::new(config.manifest_dir);
+    let corpus_dir = root.join(
+        config
+            .corpus
+            .map_or_else(|| Path::new("fuzz/corpus").join(config.name), PathBuf::from),
+    );
+    let artifact_dir = root.join("fuzz/artifacts").join(config.name);
+
+    let mut corpus = Vec::new();
+    for dir in [&artifact_dir, &corpus_dir] {
+        for path in inputs(dir) {
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    eprintln!("warning: cannot read {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            if let Some(failure) = run(target, &bytes) {
+                soft::record(saved_failure(&path, root, &describe(&bytes), failure));
+                return;
+            }
+            corpus.push(bytes);
+        }
+    }
+    if corpus.is_empty() {
+        corpus.push(Vec::new());
+    }
+
+    let iterations = config
+        .iterations
+        .or_else(|| env_value(ITERATIONS_ENV, |value| value.parse().ok()))
+        .unwrap_or(DEFAULT_ITERATIONS);
+    let time = config
+        .time
+        .map(Duration::from_millis)
+        .or_else(|| env_value(TIME_ENV, crate::cli::parse_duration))
+        .unwrap_or(DEFAULT_TIME);
+    let mut rng = rng::rng();
+    let deadline = Instant::now() + time;
+    for iteration in 0..iterations {
+        if Instant::now() >= deadline {
+            break;
+        }
+        let input = mutate(&mut rng, &corpus);
+        let started = Instant::now();
+        let failure = run(target, &input);
+        if failure.is_none() && started.elapsed() > SLOW_INPUT {
+            if let Some(path) = save(&artifact_dir, "slow-unit", &input) {
+                eprintln!(
+                    "note: fuzz target `{}` took {:?} on an input, saved as {}",
+                    config.name,
+                    started.elapsed(),
+                    relative(&path, root).display()
+                );
+            }
+        }
+        if let Some(failure) = failure {
+            let (input, failure, shrinks) =
+                property::shrink(&|input: Vec<u8>| target(&input), input, failure);
+            let saved = save(&artifact_dir, "crash", &input);
+            soft::record(new_failure(
+                iteration,
+                shrinks,
+                saved.as_deref().map(|path| relative(path, root)),
+                &describe(&input),
+                failure,
+            ));
+            return;
+        }
+    }
+}
+
+/// Runs `target` on `input`, returning its first failure.
+fn run(target: &dyn Fn(&[u8]), input: &[u8]) -> Option<TestFailure> {
+    property::run_case(&|input: &[u8]| target(input), input)
+}
+
+/// The files in `dir`, sorted by name; none if it does not exist.
+fn inputs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// A new input derived from a random input of `corpus`.
+fn mutate(rng: &mut Rng, corpus: &[Vec<u8>]) -> Vec<u8> {
+    let mut input = rng.choose(corpus).clone();
+    let mutations = 1 + rng.below(4);
+    for _ in 0..mutations {
+        let len = input.len() as u64;
+        match rng.below(7) {
+            // Flip a bit.
+            0 if len > 0 => {
+                let index = rng.below(len) as usize;
+                input[index] ^= 1 << rng.below(8);
+            }
+            // Replace a byte, often with a boundary value.
+            1 if len > 0 => {
+                let index = rng.below(len) as usize;
+                input[index] = if rng.one_in(2) {
+                    *rng.choose(&[0, 1, 0x7f, 0x80, 0xff])
+                } else {
+                    rng.next_u64() as u8
+                };
+            }
+            // Remove a run of bytes.
+            2 if len > 0 => {
+                let start = rng.below(len) as usize;
+                let end = start + 1 + rng.below((len - start as u64).min(16)) as usize;
+                input.drain(start..end.min(input.len()));
+            }
+            // Duplicate a run of bytes.
+            3 if len > 0 => {
+                let start = rng.below(len) as usize;
+                let end = (start + 1 + rng.below(16) as usize).min(input.len());
+                let run = input[start..end].to_vec();
+                let at = rng.below(len + 1) as usize;
+                input.splice(at..at, run);
+            }
+            // Splice in part of another input.
+            4 => {
+                let other = rng.choose(corpus);
+                if !other.is_empty() {
+                    let start = rng.below(other.len() as u64) as usize;
+                    let end = start + 1 + rng.below((other.len() - start) as u64) as usize;
+                    let at = rng.below(len + 1) as usize;
+                    input.splice(at..at, other[start..end].iter().copied());
+                }
+            }
+            // Insert random bytes.
+            _ => {
+                let at = rng.below(len + 1) as usize;
+                let count = 1 + rng.below(8) as usize;
+                let bytes: Vec<u8> = (0..count).map(|_| rng.next_u64() as u8).collect();
+                input.splice(at..at, bytes);
+            }
+        }
+    }
+    input.truncate(MAX_LEN);
+    input
+}
+
+/// Writes `input` to `dir` as `<prefix>-<hash>`, returning its path.
+fn save(dir: &Path, prefix: &str, input: &[u8]) -> Option<PathBuf> {
+    let hash = input.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let path = dir.join(format!("{}-{:016x}", prefix, hash));
+    match fs::create_dir_all(dir).and_then(|()| fs::write(&path, input)) {
+        Ok(()) => Some(path),
+        Err(err) => {
+            eprintln!("warning: cannot save {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn relative<'a>(path: &'a Path, root: &Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// The failure of a saved input, replayed from `path`.
+fn saved_failure(path: &Path, root: &Path, input: &str, failure: TestFailure) -> TestFailure {
+    let message = format!(
+        "fuzz target failed on the saved input {}:\n    input = {}\n{}",
+        relative(path, root).display(),
+        input.replace('\n', "\n    "),
+        failure.message
+    );
+    TestFailure { message, ..failure }
+}
+
+/// The failure of a new input, found after `iteration` passing ones.
+fn new_failure(
+    iteration: u64,
+    shrinks: u32,
+    saved: Option<&Path>,
+    input: &str,
+    failure: TestFailure,
+) -> TestFailure {
+    let mut message = format!(
+        "fuzz target failed after {} passing mutated {}",
+        iteration,
+        if iteration == 1 { "input" } else { "inputs" }
+    );
+    if shrinks > 0 {
+        let _ = write!(
+            message,
+            " (shrunk {} {})",
+            shrinks,
+            if shrinks == 1 { "time" } else { "times" }
+        );
+    }
+    let _ = write!(
+        message,
+        ":\n    input = {}\n{}",
+        input.replace('\n', "\n    "),
+        failure.message
+    );
+    match saved {
+        Some(path) => {
+            let _ = write!(
+                message,
+                "\nthe input was saved as {}, and is replayed by every run until removed",
+                path.display()
+            );
+        }
+        None => {
+            let _ = write!(
+                message,
+                "\nreproduce with {}={}",
+                rng::SEED_ENV,
+                rng::run_seed()
+            );
+        }
+    }
+    TestFailure { message, ..failure }
+}
+
+/// `bytes` as a byte string literal, shortened if long.
+fn format_bytes(bytes: &[u8]) -> String {
+    const SHOWN: usize = 256;
+    let mut out = String::from("b\"");
+    for &byte in bytes.iter().take(SHOWN) {
+        out.extend(std::ascii::escape_default(byte).map(char::from));
+    }
+    out.push('"');
+    if bytes.len() > SHOWN {
+        let _ = write!(out, " ... ({} bytes)", bytes.len());
+    }
+    out
+}
+
+fn env_value<T>(name: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    let parsed = parse(value.trim());
+    if parsed.is_none() {
+        eprintln!("warning: ignoring {}={:?}, which is not valid", name, value);
+    }
+    parsed
+}
+
+/// Writes every registered fuzz target to `dir` as a cargo-fuzz target, and
+/// prints the `[[bin]]` entries declaring them. Targets already exported
+/// are kept, so that edits to them survive.
+///
+/// # Errors
+///
+/// Returns an error if two targets share a name, or a file cannot be
+/// written.
+pub fn export(dir: &Path) -> io::Result<()> {
+    let mut targets: Vec<&FuzzDef> = inventory::iter::<FuzzDef>().collect();
+    targets.sort_by_key(|target| (target.name, target.module_path));
+    if let Some(pair) = targets.windows(2).find(|pair| pair[0].name == pair[1].name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "fuzz targets `{}::{}` and `{}::{}` share a name; rename one to export them",
+                pair[0].module_path, pair[0].name, pair[1].module_path, pair[1].name
+            ),
+        ));
+    }
+    let targets_dir = dir.join("fuzz_targets");
+    fs::create_dir_all(&targets_dir)?;
+    let mut entries = String::new();
+    for target in &targets {
+        let path = targets_dir.join(format!("{}.rs", target.name));
+        if path.exists() {
+            println!("kept {}", path.display());
+        } else {
+            fs::write(&path, exported_source(target))?;
+            // Formatting is a courtesy; the file compiles either way.
+            let _ = Command::new("rustfmt")
+                .arg("--edition=2021")
+                .arg(&path)
+                .output();
+            println!("wrote {}", path.display());
+        }
+        let _ = write!(
+            entries,
+            "\n[[bin]]\nname = \"{}\"\npath = \"fuzz_targets/{}.rs\"\ntest = false\ndoc = false\nbench = false\n",
+            target.name, target.name
+        );
+    }
+    if targets.is_empty() {
+        println!("no fuzz targets to export");
+    } else {
+        println!(
+            "\nDeclare the targets in {}, as created by `cargo fuzz init`, if it does not yet:\n{}",
+            dir.join("Cargo.toml").display(),
+            entries
+        );
+    }
+    Ok(())
+}
+
+/// The cargo-fuzz target running `target`.
+fn exported_source(target: &FuzzDef) -> String {
+    let call = if target.bytes {
+        format!("{}(data)", target.name)
+    } else {
+        format!(
+            "{}(tust::fuzz::from_bytes::<{}>(data))",
+            target.name, target.input
+        )
+    };
+    format!(
+        "#![no_main]\n\
+         //! Exported by `--export-fuzz` from `{}::{}`, {}:{}.\n\
+         //! Add the `use` declarations the target needs{}.\n\
+         \n\
+         use libfuzzer_sys::fuzz_target;\n\
+         \n\
+         {}\n\
+         \n\
+         fuzz_target!(|data: &[u8]| {});\n",
+        target.module_path,
+        target.name,
+        target.file,
+        target.line,
+        if target.bytes {
+            ""
+        } else {
+            ", and `tust` to the dependencies of the fuzz crate"
+        },
+        target.source,
+        call
+    )
+}
@@ -0,0 +1,146 @@
+//! Golden files behind `assert_matches_file!`.
+//!
+//! A golden file is a checked-in file holding the exact expected output of a
+//! test, at a path chosen by the test relative to the manifest directory of
+//! the crate being tested. Line endings and trailing newlines are ignored
+//! when comparing, so golden files survive editors and `core.autocrlf`.
+//!
+//! A value that differs from its golden file, or a golden file that does not
+//! exist, fails the assertion. With `TUST_UPDATE_GOLDEN=1` set, the file is
+//! rewritten with the value instead and the assertion passes; the runner
+//! lists every rewritten file at the end of the run.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::alloc;
+
+/// Environment variable that rewrites golden files when set to `1` or
+/// `true`.
+pub const UPDATE_ENV: &str = "TUST_UPDATE_GOLDEN";
+
+/// Golden files rewritten during this run, in the order they were written.
+static CHANGES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A value to compare against a golden file.
+#[derive(Debug, Clone, Copy)]
+pub struct GoldenAssertion<'a> {
+    /// `CARGO_MANIFEST_DIR` of the crate making the assertion.
+    pub manifest_dir: &'a str,
+    /// Path of the golden file, relative to `manifest_dir`.
+    pub path: &'a str,
+    /// Source text of the asserted expression, for failure messages.
+    pub expression: &'a str,
+    /// The rendered value.
+    pub contents: &'a str,
+}
+
+/// Result of comparing a value against its golden file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    /// The value matches the golden file.
+    Matched,
+    /// The golden file was rewritten because [`UPDATE_ENV`] is set.
+    Updated { path: PathBuf },
+    /// The value differs from the golden file's contents, `expected`.
+    Mismatched { path: PathBuf, expected: String },
+    /// The golden file does not exist.
+    Missing { path: PathBuf },
+}
+
+/// Failure to read or write a golden file.
+#[derive(Debug)]
+pub struct GoldenError {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot access golden file {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for GoldenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Compares `assertion` against its golden file, rewriting the file if
+/// [`UPDATE_ENV`] is set.
+pub fn check(assertion: &GoldenAssertion<'_>) -> Result<GoldenOutcome, GoldenError> {
+    let root = Path::new(assertion.manifest_dir);
+    let path = root.join(assertion.path);
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(file) => Some(file),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(GoldenError { path, error }),
+    };
+    if expected.as_deref().map(normalize) == Some(normalize(assertion.contents)) {
+        return Ok(GoldenOutcome::Matched);
+    }
+
+    if update_requested() {
+        write(&path, assertion.contents)?;
+        record(root, &path);
+        return Ok(GoldenOutcome::Updated { path });
+    }
+    Ok(match expected {
+        Some(expected) => GoldenOutcome::Mismatched {
+            path,
+            expected: normalize(&expected),
+        },
+        None => GoldenOutcome::Missing { path },
+    })
+}
+
+/// Returns the golden files rewritten during the run so far, relative to the
+/// manifest directory where possible, clearing the list.
+pub fn take_changes() -> Vec<PathBuf> {
+    std::mem::take(&mut *CHANGES.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Whether [`UPDATE_ENV`] asks for golden files to be rewritten.
+pub fn update_requested() -> bool {
+    std::env::var(UPDATE_ENV).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// The text golden files are compared by: line endings are normalized and
+/// trailing newlines dropped.
+fn normalize(contents: &str) -> String {
+    contents
+        .replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), GoldenError> {
+    let result = match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).and_then(|()| fs::write(path, contents)),
+        None => fs::write(path, contents),
+    };
+    result.map_err(|error| GoldenError {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn record(root: &Path, path: &Path) {
+    alloc::untracked(|| {
+        let path = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+        let mut changes = CHANGES.lock().unwrap_or_else(|e| e.into_inner());
+        if !changes.contains(&path) {
+            changes.push(path);
+        }
+    })
+}
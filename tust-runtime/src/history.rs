@@ -0,0 +1,500 @@
+//! The results of earlier runs, and the trends `--trends` finds in them.
+//!
+//! After every run, the runner appends the status and duration of each test
+//! that ran to `tust/history.jsonl` in Cargo's target directory, or the file
+//! named by [`CACHE_ENV`]: one JSON object per run and line, mapping each
+//! test, by its module path including the crate name, to its result. The
+//! test binaries of a package append to the same file, and the file keeps
+//! the last [`MAX_RUNS`] runs.
+//!
+//! ```text
+//! {"timestamp":1760400000,"tests":{"app::parser::nested":{"status":"passed","duration":0.012}}}
+//! ```
+//!
+//! With `--trends[=N]`, the runner compares each test that ran against its
+//! last N runs, 20 unless given, and lists after the run:
+//!
+//! - the tests failing now that passed in their previous run;
+//! - the tests that passed now, but took at least twice as long as the
+//!   median of their earlier passing runs, and 50ms more;
+//! - the tests whose result keeps changing: those that, counting this run,
+//!   were flaky or changed between passing and failing at least three
+//!   times.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::SystemTime;
+
+use crate::cache;
+use crate::outcome::{TestOutcome, TestStatus};
+use crate::timings;
+
+/// Environment variable naming the history file; if set but empty, runs are
+/// neither read nor recorded.
+pub const CACHE_ENV: &str = "TUST_HISTORY";
+
+/// Runs kept in the history file, of all the test binaries writing to it.
+pub const MAX_RUNS: usize = 500;
+
+/// Runs of each test that `--trends` looks at unless given a number.
+pub const DEFAULT_TREND_RUNS: usize = 20;
+
+/// How many times a test must be flaky or change its result to be listed
+/// as flaky by `--trends`.
+const FLAKY_CHANGES: usize = 3;
+
+/// How much longer than its median a test must take to be listed as slow,
+/// besides taking twice as long.
+const SLOWDOWN: Duration = Duration::from_millis(50);
+
+/// The result of a test in one run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    /// Passed after retries.
+    Flaky,
+    Failed,
+    Skipped,
+    ExpectedFailure,
+}
+
+impl Status {
+    fn of(outcome: &TestOutcome) -> Self {
+        match outcome.status {
+            TestStatus::Passed if outcome.is_flaky() => Status::Flaky,
+            TestStatus::Passed => Status::Passed,
+            TestStatus::Failed(_) => Status::Failed,
+            TestStatus::Skipped(_) => Status::Skipped,
+            TestStatus::ExpectedFailure(_) => Status::ExpectedFailure,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Status::Passed => "passed",
+            Status::Flaky => "flaky",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+            Status::ExpectedFailure => "xfail",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        Some(match label {
+            "passed" => Status::Passed,
+            "flaky" => Status::Flaky,
+            "failed" => Status::Failed,
+            "skipped" => Status::Skipped,
+            "xfail" => Status::ExpectedFailure,
+            _ => return None,
+        })
+    }
+
+    fn is_pass(self) -> bool {
+        matches!(self, Status::Passed | Status::Flaky)
+    }
+}
+
+/// One run of a test binary, as recorded in the history.
+#[derive(Debug, Clone)]
+pub struct Run {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The result and duration of each test that ran, by module path.
+    pub tests: BTreeMap<String, (Status, Duration)>,
+}
+
+impl Run {
+    /// The run of the tests of `outcomes` that ran, now.
+    pub(crate) fn new(outcomes: &[TestOutcome]) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let tests = outcomes
+            .iter()
+            .filter(|outcome| outcome.attempts > 0)
+            .map(|outcome| {
+                let result = (Status::of(outcome), outcome.duration);
+                (timings::key(outcome.test), result)
+            })
+            .collect();
+        Run { timestamp, tests }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let tests = value["tests"]
+            .as_object()?
+            .iter()
+            .filter_map(|(test, result)| {
+                let status = Status::from_label(result["status"].as_str()?)?;
+                let secs = result["duration"]
+                    .as_f64()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)?;
+                Some((test.clone(), (status, Duration::from_secs_f64(secs))))
+            })
+            .collect();
+        Some(Run {
+            timestamp: value["timestamp"].as_u64().unwrap_or(0),
+            tests,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let tests: serde_json::Map<String, Value> = self
+            .tests
+            .iter()
+            .map(|(test, (result, duration))| {
+                let result =
+                    json!({ "status": result.label(), "duration": duration.as_secs_f64() });
+                (test.clone(), result)
+            })
+            .collect();
+        let mut line = json!({ "timestamp": self.timestamp, "tests": tests }).to_string();
+        line.push('\n');
+        line
+    }
+}
+
+/// Reads the runs recorded in `path`, oldest first, skipping lines that are
+/// not runs.
+pub fn read(path: &Path) -> io::Result<Vec<Run>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(Run::parse)
+        .collect())
+}
+
+/// Appends `run` to the history in `path`, creating its directory if needed,
+/// and drops the oldest runs beyond [`MAX_RUNS`].
+pub fn append(path: &Path, run: &Run) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(run.to_line().as_bytes())?;
+    let text = fs::read_to_string(path)?;
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() > MAX_RUNS {
+        let mut kept = lines[lines.len() - MAX_RUNS..].join("\n");
+        kept.push('\n');
+        fs::write(path, kept)?;
+    }
+    Ok(())
+}
+
+/// The history file: [`CACHE_ENV`] if set, else `tust/history.jsonl` in the
+/// target directory holding the running binary, if there is one.
+pub fn cache_path() -> Option<PathBuf> {
+    cache::path(CACHE_ENV, "history.jsonl")
+}
+
+/// The runs recorded in the history file, if any.
+pub(crate) fn load_cache() -> Vec<Run> {
+    cache_path()
+        .and_then(|path| read(&path).ok())
+        .unwrap_or_default()
+}
+
+/// Appends the run of `outcomes` to the history file.
+pub(crate) fn update_cache(outcomes: &[TestOutcome]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    if let Err(err) = append(&path, &Run::new(outcomes)) {
+        eprintln!(
+            "warning: failed to record the run in {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+/// The trends of the tests of a run against their earlier runs.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Trends {
+    /// Tests failing now that passed in their previous run, with how many
+    /// runs in a row they had passed.
+    pub(crate) newly_failing: Vec<(String, usize)>,
+    /// Tests that became slow, with their duration now and the median of
+    /// their earlier passing runs.
+    pub(crate) newly_slow: Vec<(String, Duration, Duration)>,
+    /// Tests whose result keeps changing, with how many of their runs were
+    /// flaky, how many times they changed between passing and failing, and
+    /// how many runs were looked at.
+    pub(crate) flaky: Vec<(String, usize, usize, usize)>,
+    /// How many tests of the run had earlier runs to compare against.
+    pub(crate) compared: usize,
+}
+
+impl Trends {
+    /// Compares each test of `current` against its last `runs` runs in
+    /// `history`, counting `current` as one of them.
+    pub(crate) fn new(history: &[Run], current: &Run, runs: usize) -> Self {
+        let mut trends = Trends::default();
+        for (test, &(result, duration)) in &current.tests {
+            let mut results: Vec<(Status, Duration)> = history
+                .iter()
+                .rev()
+                .filter_map(|run| run.tests.get(test).copied())
+                .take(runs.saturating_sub(1))
+                .collect();
+            results.reverse();
+            trends.compared += usize::from(!results.is_empty());
+            let earlier: Vec<(Status, Duration)> = results
+                .iter()
+                .copied()
+                .filter(|(result, _)| result.is_pass() || *result == Status::Failed)
+                .collect();
+
+            if result == Status::Failed && earlier.last().is_some_and(|(last, _)| last.is_pass()) {
+                let passes = earlier
+                    .iter()
+                    .rev()
+                    .take_while(|(result, _)| result.is_pass())
+                    .count();
+                trends.newly_failing.push((test.clone(), passes));
+            }
+
+            if result.is_pass() {
+                let mut passing: Vec<Duration> = earlier
+                    .iter()
+                    .filter(|(result, _)| result.is_pass())
+                    .map(|&(_, duration)| duration)
+                    .collect();
+                if passing.len() >= 3 {
+                    passing.sort();
+                    let median = passing[passing.len() / 2];
+                    if duration >= median * 2 && duration >= median + SLOWDOWN {
+                        trends.newly_slow.push((test.clone(), duration, median));
+                    }
+                }
+            }
+
+            let mut counted = earlier;
+            if result.is_pass() || result == Status::Failed {
+                counted.push((result, duration));
+            }
+            let flaky = counted
+                .iter()
+                .filter(|(result, _)| *result == Status::Flaky)
+                .count();
+            let changes = counted
+                .windows(2)
+                .filter(|pair| pair[0].0.is_pass() != pair[1].0.is_pass())
+                .count();
+            if flaky + changes >= FLAKY_CHANGES {
+                trends
+                    .flaky
+                    .push((test.clone(), flaky, changes, results.len() + 1));
+            }
+        }
+        trends
+            .newly_slow
+            .sort_by_key(|(_, duration, median)| std::cmp::Reverse(*duration - *median));
+        trends
+            .flaky
+            .sort_by_key(|(_, flaky, changes, _)| std::cmp::Reverse(flaky + changes));
+        trends
+    }
+
+    /// Whether no test has a trend.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.newly_failing.is_empty() && self.newly_slow.is_empty() && self.flaky.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::outcome::TestFailure;
+    use crate::registry::TestDef;
+
+    fn outcome(name: &'static str, status: TestStatus, attempts: u32, ms: u64) -> TestOutcome {
+        TestOutcome {
+            test: Box::leak(Box::new(TestDef::ran_elsewhere(
+                "app::parser",
+                name,
+                "src/lib.rs",
+                1,
+            ))),
+            status,
+            duration: Duration::from_millis(ms),
+            output: String::new(),
+            attempts,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// A run of the single test `app::t` with `status`, taking `ms`.
+    fn run(status: Status, ms: u64) -> Run {
+        Run {
+            timestamp: 0,
+            tests: BTreeMap::from([("app::t".to_string(), (status, Duration::from_millis(ms)))]),
+        }
+    }
+
+    fn runs(statuses: &[Status]) -> Vec<Run> {
+        statuses.iter().map(|&status| run(status, 10)).collect()
+    }
+
+    #[test]
+    fn records_the_tests_that_ran() {
+        let failed = TestStatus::Failed(vec![TestFailure::new("boom")]);
+        let run = Run::new(&[
+            outcome("passes", TestStatus::Passed, 1, 12),
+            outcome("retried", TestStatus::Passed, 2, 5),
+            outcome("fails", failed, 1, 7),
+            outcome("skips", TestStatus::Skipped(None), 1, 0),
+            outcome("never_ran", TestStatus::Skipped(None), 0, 0),
+        ]);
+        assert!(run.timestamp > 0);
+        let statuses: Vec<(&str, Status)> = run
+            .tests
+            .iter()
+            .map(|(test, (status, _))| (test.as_str(), *status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("app::parser::fails", Status::Failed),
+                ("app::parser::passes", Status::Passed),
+                ("app::parser::retried", Status::Flaky),
+                ("app::parser::skips", Status::Skipped),
+            ]
+        );
+        assert_eq!(
+            run.tests["app::parser::passes"].1,
+            Duration::from_millis(12)
+        );
+    }
+
+    #[test]
+    fn runs_round_trip_through_the_history_file() {
+        let dir = std::env::temp_dir().join(format!("tust-history-{}", std::process::id()));
+        let path = dir.join("nested").join("history.jsonl");
+        let mut first = run(Status::Passed, 12);
+        first.timestamp = 1_760_400_000;
+        append(&path, &first).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"timestamp\":1760400000,\
+             \"tests\":{\"app::t\":{\"status\":\"passed\",\"duration\":0.012}}}\n"
+        );
+
+        let mut text = fs::read_to_string(&path).unwrap();
+        text.push_str("not a run\n");
+        text.push_str(r#"{"tests":{"app::t":{"status":"lost","duration":1},"app::u":{"status":"xfail","duration":0.5}}}"#);
+        text.push('\n');
+        fs::write(&path, text).unwrap();
+        let read = read(&path).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].timestamp, 1_760_400_000);
+        assert_eq!(
+            read[0].tests["app::t"],
+            (Status::Passed, Duration::from_millis(12))
+        );
+        assert_eq!(read[1].timestamp, 0);
+        assert_eq!(
+            read[1].tests.iter().collect::<Vec<_>>(),
+            [(
+                &"app::u".to_string(),
+                &(Status::ExpectedFailure, Duration::from_millis(500))
+            )]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_last_runs() {
+        let path =
+            std::env::temp_dir().join(format!("tust-history-max-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        for timestamp in 0..MAX_RUNS as u64 + 3 {
+            let mut run = run(Status::Passed, 1);
+            run.timestamp = timestamp;
+            append(&path, &run).unwrap();
+        }
+        let runs = read(&path).unwrap();
+        assert_eq!(runs.len(), MAX_RUNS);
+        assert_eq!(runs[0].timestamp, 3);
+        assert_eq!(runs[MAX_RUNS - 1].timestamp, MAX_RUNS as u64 + 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finds_the_tests_that_started_failing() {
+        use Status::*;
+        let trends = Trends::new(
+            &runs(&[Failed, Passed, Skipped, Flaky]),
+            &run(Failed, 10),
+            20,
+        );
+        assert_eq!(trends.newly_failing, [("app::t".to_string(), 2)]);
+        assert_eq!(trends.compared, 1);
+
+        let trends = Trends::new(&runs(&[Passed, Failed]), &run(Failed, 10), 20);
+        assert!(trends.newly_failing.is_empty());
+        let trends = Trends::new(&[], &run(Failed, 10), 20);
+        assert!(trends.is_empty());
+        assert_eq!(trends.compared, 0);
+
+        // Only the last passing run is in the window of two.
+        let trends = Trends::new(&runs(&[Passed, Passed, Passed]), &run(Failed, 10), 2);
+        assert_eq!(trends.newly_failing, [("app::t".to_string(), 1)]);
+    }
+
+    #[test]
+    fn finds_the_tests_that_became_slow() {
+        let history: Vec<Run> = [10, 30, 20, 500]
+            .iter()
+            .map(|&ms| run(Status::Passed, ms))
+            .collect();
+        let trends = Trends::new(&history, &run(Status::Passed, 80), 20);
+        let ms = Duration::from_millis;
+        assert_eq!(trends.newly_slow, [("app::t".to_string(), ms(80), ms(30))]);
+
+        // Twice the median, but not 50ms more.
+        assert!(Trends::new(&history, &run(Status::Passed, 70), 20).is_empty());
+        // Fewer than three earlier passing runs.
+        assert!(Trends::new(&history[..2], &run(Status::Passed, 900), 20).is_empty());
+        // A failure is not slow.
+        let trends = Trends::new(&history, &run(Status::Failed, 900), 20);
+        assert!(trends.newly_slow.is_empty());
+    }
+
+    #[test]
+    fn finds_the_tests_whose_result_keeps_changing() {
+        use Status::*;
+        let trends = Trends::new(
+            &runs(&[Passed, Failed, Skipped, Passed]),
+            &run(Failed, 10),
+            20,
+        );
+        assert_eq!(trends.flaky, [("app::t".to_string(), 0, 3, 5)]);
+
+        let trends = Trends::new(&runs(&[Flaky, Passed, Flaky]), &run(Flaky, 10), 20);
+        assert_eq!(trends.flaky, [("app::t".to_string(), 3, 0, 4)]);
+
+        let trends = Trends::new(&runs(&[Passed, Failed]), &run(Passed, 10), 20);
+        assert!(trends.flaky.is_empty());
+        // The changes fall outside a window of three runs.
+        let trends = Trends::new(
+            &runs(&[Failed, Passed, Failed, Passed, Passed]),
+            &run(Passed, 10),
+            3,
+        );
+        assert!(trends.flaky.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! Setup and teardown hooks, declared with `#[before_each]`, `#[after_each]`,
+//! `#[before_all]`, and `#[after_all]`.
+//!
+//! A hook applies to every test in the module declaring it and in that
+//! module's submodules; hooks declared at the crate root apply to the whole
+//! run. Before hooks of enclosing modules run before those of inner modules,
+//! and hooks of one module run in declaration order. After hooks run in the
+//! reverse order, and run even if the test, or a hook before it, failed.
+//!
+//! `before_all` hooks of a module run once, before the first of its tests
+//! starts; if one fails, every test in the module fails without running.
+//! `after_all` hooks run once the last of its tests has finished, and their
+//! failures are reported on that test. With `--process-per-test`, a test's
+//! process runs both around its one test, as it creates session fixtures,
+//! so that the test sees what the hooks set up.
+
+use std::sync::Mutex;
+
+use crate::outcome::TestFailure;
+use crate::panic;
+use crate::registry::TestDef;
+
+/// Static description of a registered hook.
+///
+/// Values of this type are generated by the hook attributes; they are not
+/// meant to be constructed by hand.
+#[derive(Debug)]
+pub struct HookDef {
+    pub kind: HookKind,
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub run: fn(),
+}
+
+inventory::collect!(HookDef);
+
+/// When a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    BeforeEach,
+    AfterEach,
+    BeforeAll,
+    AfterAll,
+}
+
+impl HookKind {
+    fn attribute(self) -> &'static str {
+        match self {
+            HookKind::BeforeEach => "before_each",
+            HookKind::AfterEach => "after_each",
+            HookKind::BeforeAll => "before_all",
+            HookKind::AfterAll => "after_all",
+        }
+    }
+}
+
+impl HookDef {
+    /// Full name of the hook, in the same form as [`TestDef::path`].
+    pub fn path(&self) -> String {
+        match self.module_path.split_once("::") {
+            Some((_crate, module)) => format!("{}::{}", module, self.name),
+            None => self.name.to_string(),
+        }
+    }
+
+    /// Runs the hook, naming it in the failure if it panics.
+    fn call(&self) -> Result<(), TestFailure> {
+        panic::catch(self.run).map_err(|mut failure| {
+            failure.message = format!(
+                "#[{}] hook `{}` failed: {}",
+                self.kind.attribute(),
+                self.path(),
+                failure.message
+            );
+            failure
+        })
+    }
+}
+
+/// Whether `module` is `outer` or one of its submodules.
+fn encloses(outer: &str, module: &str) -> bool {
+    module
+        .strip_prefix(outer)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// The hooks of `kind` applying to tests in `module`, in the order they run.
+fn hooks(kind: HookKind, module: &str) -> Vec<&'static HookDef> {
+    let mut hooks: Vec<&'static HookDef> = inventory::iter::<HookDef>
+        .into_iter()
+        .filter(|hook| hook.kind == kind && encloses(hook.module_path, module))
+        .collect();
+    hooks.sort_by_key(|hook| (hook.module_path.len(), hook.file, hook.line));
+    if matches!(kind, HookKind::AfterEach | HookKind::AfterAll) {
+        hooks.reverse();
+    }
+    hooks
+}
+
+/// Runs the `before_each` hooks of `test`, stopping at the first failure.
+pub(crate) fn before_each(test: &TestDef) -> Result<(), TestFailure> {
+    hooks(HookKind::BeforeEach, test.module_path)
+        .iter()
+        .try_for_each(|hook| hook.call())
+}
+
+/// Runs every `after_each` hook of `test`, returning their failures.
+pub(crate) fn after_each(test: &TestDef) -> Vec<TestFailure> {
+    hooks(HookKind::AfterEach, test.module_path)
+        .iter()
+        .filter_map(|hook| hook.call().err())
+        .collect()
+}
+
+/// The `before_all` and `after_all` hooks of a run, grouped by the module
+/// declaring them.
+pub(crate) struct Scopes {
+    /// Outer modules first.
+    scopes: Vec<Scope>,
+}
+
+struct Scope {
+    module: &'static str,
+    state: Mutex<ScopeState>,
+}
+
+struct ScopeState {
+    /// Tests in the module that have yet to finish.
+    remaining: usize,
+    /// The result of the `before_all` hooks, once they ran.
+    setup: Option<Result<(), TestFailure>>,
+}
+
+impl Scopes {
+    /// Prepares the hooks applying to `tests`, the tests that are to run.
+    pub(crate) fn new(tests: &[&'static TestDef]) -> Self {
+        let mut modules: Vec<&'static str> = inventory::iter::<HookDef>
+            .into_iter()
+            .filter(|hook| matches!(hook.kind, HookKind::BeforeAll | HookKind::AfterAll))
+            .map(|hook| hook.module_path)
+            .collect();
+        modules.sort_by_key(|module| (module.len(), *module));
+        modules.dedup();
+
+        let scopes = modules
+            .into_iter()
+            .filter_map(|module| {
+                let remaining = tests
+                    .iter()
+                    .filter(|test| encloses(module, test.module_path))
+                    .count();
+                (remaining > 0).then(|| Scope {
+                    module,
+                    state: Mutex::new(ScopeState {
+                        remaining,
+                        setup: None,
+                    }),
+                })
+            })
+            .collect();
+        Scopes { scopes }
+    }
+
+    fn enclosing<'a>(&'a self, test: &'a TestDef) -> impl DoubleEndedIterator<Item = &'a Scope> {
+        self.scopes
+            .iter()
+            .filter(move |scope| encloses(scope.module, test.module_path))
+    }
+
+    /// Runs the `before_all` hooks `test` needs that have not run yet, from
+    /// the outermost module in. Fails if any of them failed, now or earlier.
+    pub(crate) fn enter(&self, test: &TestDef) -> Result<(), TestFailure> {
+        for scope in self.enclosing(test) {
+            let mut state = scope.state.lock().unwrap_or_else(|e| e.into_inner());
+            let setup = state.setup.get_or_insert_with(|| {
+                hooks(HookKind::BeforeAll, scope.module)
+                    .iter()
+                    .filter(|hook| hook.module_path == scope.module)
+                    .try_for_each(|hook| hook.call())
+            });
+            setup.clone()?;
+        }
+        Ok(())
+    }
+
+    /// Records that `test` finished, or was skipped, and runs the `after_all`
+    /// hooks of every module it was the last test in, from the innermost
+    /// module out. Returns their failures.
+    pub(crate) fn leave(&self, test: &TestDef) -> Vec<TestFailure> {
+        let mut failures = Vec::new();
+        for scope in self.enclosing(test).rev() {
+            let mut state = scope.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.remaining = state.remaining.saturating_sub(1);
+            // A module none of whose tests ran was never set up.
+            if state.remaining == 0 && state.setup.is_some() {
+                failures.extend(
+                    hooks(HookKind::AfterAll, scope.module)
+                        .iter()
+                        .filter(|hook| hook.module_path == scope.module)
+                        .filter_map(|hook| hook.call().err()),
+                );
+            }
+        }
+        failures
+    }
+}
@@ -0,0 +1,780 @@
+//! Built-in `http_server` fixture: a local HTTP server answering requests
+//! as the test expects.
+//!
+//! A test parameter named `http_server` of type [`MockHttpServer`] receives a
+//! server listening on a free port of `127.0.0.1`, started for the test and
+//! stopped once the value is dropped at the end of it. The test registers
+//! the requests it expects, each with the response to send, and points the
+//! code under test at [`MockHttpServer::url`]:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn fetches_the_user(http_server: MockHttpServer) {
+//!     http_server
+//!         .expect("GET", "/users/1")
+//!         .respond(200, r#"{"name": "Ada"}"#);
+//!     let client = Client::new(http_server.url());
+//!     assert_eq!(client.user(1).name, "Ada");
+//! }
+//! ```
+//!
+//! A request is answered by the first expectation, in the order they were
+//! added, that accepts it and has not yet received as many requests as it
+//! expects. A request no expectation accepts is answered with `404 Not
+//! Found`, listing the expectations.
+//!
+//! When the server is dropped, or earlier by [`MockHttpServer::verify`],
+//! each expectation that received fewer requests than it expects, and each
+//! request no expectation accepted, fails the test, which keeps running as
+//! after a soft assertion. An expectation expects exactly one request unless
+//! told otherwise.
+//!
+//! The server speaks HTTP/1.1 without TLS, and reads request bodies sent
+//! with `Content-Length` or chunked encoding.
+
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::Location as CallerLocation;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+use crate::outcome::{Location, TestFailure};
+use crate::registry;
+use crate::soft;
+
+/// How long a connection may stay idle before the server closes it.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+inventory::submit! {
+    FixtureDef {
+        name: "http_server",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<MockHttpServer>,
+        type_id: TypeId::of::<MockHttpServer>,
+        provider: Provider::PerTest(provide_http_server),
+    }
+}
+
+fn provide_http_server(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let server = MockHttpServer::start()
+        .unwrap_or_else(|err| panic!("cannot start the mock HTTP server: {}", err));
+    Ok(Box::new(server))
+}
+
+/// A local HTTP server answering the requests it is told to expect.
+pub struct MockHttpServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    stopping: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct State {
+    expectations: Vec<Expectation>,
+    received: Vec<Request>,
+    /// Requests no expectation accepted.
+    unexpected: Vec<Request>,
+}
+
+impl MockHttpServer {
+    /// Starts a server on a free port of `127.0.0.1`.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let state = Arc::clone(&state);
+            let stopping = Arc::clone(&stopping);
+            thread::Builder::new()
+                .name(format!("tust-http-{}", addr.port()))
+                .spawn(move || accept(listener, state, stopping))?
+        };
+        Ok(MockHttpServer {
+            addr,
+            state,
+            stopping,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// The server's base URL, e.g. `http://127.0.0.1:41234`, without a
+    /// trailing slash.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The URL of `path` on the server, e.g. `http://127.0.0.1:41234/users`.
+    pub fn url_for(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            format!("{}{}", self.url(), path)
+        } else {
+            format!("{}/{}", self.url(), path)
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Expects a request with `method`, such as `"GET"`, for `path`. A path
+    /// with a query string must match the request's exactly; one without
+    /// matches requests with any query string. The expectation answers with
+    /// `200 OK` and an empty body until told otherwise.
+    #[track_caller]
+    pub fn expect(&self, method: &str, path: &str) -> ExpectationBuilder<'_> {
+        let mut state = self.state();
+        state.expectations.push(Expectation {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: None,
+            response: Response::new(200),
+            times: Some(1),
+            calls: 0,
+            location: CallerLocation::caller(),
+        });
+        let index = state.expectations.len() - 1;
+        ExpectationBuilder {
+            server: self,
+            index,
+        }
+    }
+
+    /// Every request the server has received so far, in order.
+    pub fn received_requests(&self) -> Vec<Request> {
+        self.state().received.clone()
+    }
+
+    /// Fails the current test for every expectation that received fewer
+    /// requests than it expects and every request no expectation accepted,
+    /// then removes the expectations and forgets those requests.
+    #[track_caller]
+    pub fn verify(&self) {
+        let (expectations, unexpected) = {
+            let mut state = self.state();
+            (
+                std::mem::take(&mut state.expectations),
+                std::mem::take(&mut state.unexpected),
+            )
+        };
+        for expectation in &expectations {
+            let Some(times) = expectation.times else {
+                continue;
+            };
+            if expectation.calls >= times {
+                continue;
+            }
+            let location = expectation.location;
+            let message = format!(
+                "expected the mock HTTP server to receive {} {}, but it received {}",
+                requests(times),
+                expectation,
+                match expectation.calls {
+                    0 => "none".to_string(),
+                    calls => calls.to_string(),
+                }
+            );
+            soft::record(TestFailure {
+                location: Some(Location {
+                    file: location.file().to_string(),
+                    line: location.line(),
+                    column: location.column(),
+                }),
+                ..TestFailure::new(message)
+            });
+        }
+        // Nothing in the test caused an unexpected request, so point at
+        // the test itself.
+        let test = registry::current().map(|test| Location {
+            file: test.file.to_string(),
+            line: test.line,
+            column: 1,
+        });
+        for request in unexpected {
+            let message = format!(
+                "the mock HTTP server received an unexpected request: {} {}",
+                request.method, request.path
+            );
+            soft::record(TestFailure {
+                location: test.clone(),
+                ..TestFailure::new(message)
+            });
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for MockHttpServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MockHttpServer").field(&self.addr).finish()
+    }
+}
+
+impl Drop for MockHttpServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the acceptor, which is blocked waiting for a connection.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        if !thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+fn requests(count: usize) -> String {
+    match count {
+        1 => "1 request".to_string(),
+        count => format!("{} requests", count),
+    }
+}
+
+/// Configures an expectation added by [`MockHttpServer::expect`].
+pub struct ExpectationBuilder<'a> {
+    server: &'a MockHttpServer,
+    index: usize,
+}
+
+impl ExpectationBuilder<'_> {
+    /// Accepts only requests with header `name`, compared case-insensitively,
+    /// set to `value`.
+    pub fn header(self, name: &str, value: &str) -> Self {
+        self.update(|expectation| {
+            expectation
+                .headers
+                .push((name.to_string(), value.to_string()))
+        })
+    }
+
+    /// Accepts only requests whose body is `body`.
+    pub fn body(self, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        self.update(|expectation| expectation.body = Some(body))
+    }
+
+    /// Answers with `status` and `body`.
+    pub fn respond(self, status: u16, body: impl Into<Vec<u8>>) -> Self {
+        self.respond_with(Response::new(status).body(body))
+    }
+
+    /// Answers with `response`.
+    pub fn respond_with(self, response: Response) -> Self {
+        self.update(|expectation| expectation.response = response)
+    }
+
+    /// Expects exactly `times` requests.
+    pub fn times(self, times: usize) -> Self {
+        self.update(|expectation| expectation.times = Some(times))
+    }
+
+    /// Answers any number of requests, including none, as a stub that is
+    /// not verified.
+    pub fn optional(self) -> Self {
+        self.update(|expectation| expectation.times = None)
+    }
+
+    fn update(self, f: impl FnOnce(&mut Expectation)) -> Self {
+        if let Some(expectation) = self.server.state().expectations.get_mut(self.index) {
+            f(expectation);
+        }
+        self
+    }
+}
+
+struct Expectation {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    response: Response,
+    /// The number of requests expected, or `None` for any number.
+    times: Option<usize>,
+    calls: usize,
+    location: &'static CallerLocation<'static>,
+}
+
+impl Expectation {
+    fn accepts(&self, request: &Request) -> bool {
+        let path = if self.path.contains('?') {
+            request.path.as_str()
+        } else {
+            request.path.split('?').next().unwrap_or_default()
+        };
+        self.method == request.method
+            && self.path == path
+            && self
+                .headers
+                .iter()
+                .all(|(name, value)| request.header(name) == Some(value.as_str()))
+            && self
+                .body
+                .as_ref()
+                .map_or(true, |body| *body == request.body)
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.times.is_some_and(|times| self.calls >= times)
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{} {}`", self.method, self.path)?;
+        for (name, value) in &self.headers {
+            write!(f, " with `{}: {}`", name, value)?;
+        }
+        if self.body.is_some() {
+            f.write_str(" with the expected body")?;
+        }
+        Ok(())
+    }
+}
+
+/// An HTTP request received by a [`MockHttpServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// The method, such as `GET`.
+    pub method: String,
+    /// The request target, such as `/users?page=2`.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as text, with invalid UTF-8 replaced.
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// An HTTP response sent by a [`MockHttpServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// A response with `status`, no headers, and an empty body.
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `200 OK` response with `body` and a JSON content type.
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Response::new(200)
+            .header("Content-Type", "application/json")
+            .body(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn write_to(&self, stream: &mut impl Write, close: bool) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        if close {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+fn accept(listener: TcpListener, state: Arc<Mutex<State>>, stopping: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else { continue };
+        let state = Arc::clone(&state);
+        let _ = thread::Builder::new()
+            .name("tust-http-connection".to_string())
+            .spawn(move || {
+                let _ = serve(stream, &state);
+            });
+    }
+}
+
+/// Answers the requests of one connection until the client closes it.
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_request(&mut reader)? {
+        let close = request
+            .header("Connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        let response = answer(state, request);
+        response.write_to(&mut writer, close)?;
+        if close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn answer(state: &Mutex<State>, request: Request) -> Response {
+    let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+    state.received.push(request.clone());
+    let found = state
+        .expectations
+        .iter()
+        .position(|expectation| !expectation.is_saturated() && expectation.accepts(&request));
+    if let Some(index) = found {
+        let expectation = &mut state.expectations[index];
+        expectation.calls += 1;
+        return expectation.response.clone();
+    }
+
+    let mut body = format!(
+        "no expectation of the mock HTTP server accepts {} {}\n",
+        request.method, request.path
+    );
+    for expectation in &state.expectations {
+        body.push_str(&format!(
+            "  expected {}, received {} of {}\n",
+            expectation,
+            expectation.calls,
+            match expectation.times {
+                Some(times) => requests(times),
+                None => "any number".to_string(),
+            }
+        ));
+    }
+    state.unexpected.push(request);
+    Response::new(404)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(body)
+}
+
+/// Reads one request, or `None` once the client closes the connection.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed request line",
+        ));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+
+    if request
+        .header("Transfer-Encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        request.body = read_chunked(reader)?;
+    } else if let Some(length) = request.header("Content-Length") {
+        let length: usize = length
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed Content-Length"))?;
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        request.body = body;
+    }
+    Ok(Some(request))
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed chunk size"))?;
+        if size == 0 {
+            // Skip any trailers, up to the blank line ending the body.
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    /// Sends a request made of the lines of `head` and `body` on a new
+    /// connection and reads the response until the server closes it.
+    fn send(server: &MockHttpServer, head: &[&str], body: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        let mut request = head.join("\r\n");
+        request.push_str("\r\nConnection: close\r\n\r\n");
+        request.push_str(body);
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn get(server: &MockHttpServer, path: &str) -> String {
+        send(
+            server,
+            &[&format!("GET {} HTTP/1.1", path), "Host: localhost"],
+            "",
+        )
+    }
+
+    #[test]
+    fn expected_requests_get_their_response() {
+        let server = MockHttpServer::start().unwrap();
+        server
+            .expect("get", "/users/1")
+            .respond_with(Response::json(r#"{"name": "Ada"}"#).header("X-Id", "1"));
+        server.expect("GET", "/search").respond(201, "found");
+        assert_eq!(
+            get(&server, "/users/1"),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Id: 1\r\n\
+             Content-Length: 15\r\nConnection: close\r\n\r\n{\"name\": \"Ada\"}"
+        );
+        assert!(get(&server, "/search?q=ada").ends_with("\r\n\r\nfound"));
+        let received = server.received_requests();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[1].path, "/search?q=ada");
+        assert_eq!(received[1].header("host"), Some("localhost"));
+        assert_eq!(server.url_for("users"), format!("{}/users", server.url()));
+        assert_eq!(server.url_for("/users"), format!("{}/users", server.url()));
+    }
+
+    #[test]
+    fn headers_and_bodies_are_matched() {
+        let server = MockHttpServer::start().unwrap();
+        server
+            .expect("POST", "/users")
+            .header("content-type", "application/json")
+            .body("{}")
+            .respond(201, "");
+        server
+            .expect("PUT", "/users/1")
+            .body("Ada")
+            .respond(204, "");
+        let post = ["POST /users HTTP/1.1", "Content-Type: application/json"];
+        let response = send(&server, &[post[0], post[1], "Content-Length: 2"], "{}");
+        assert!(
+            response.starts_with("HTTP/1.1 201 Created\r\n"),
+            "{}",
+            response
+        );
+        let put = ["PUT /users/1 HTTP/1.1", "Transfer-Encoding: chunked"];
+        let response = send(
+            &server,
+            &put,
+            "2;part\r\nAd\r\n1\r\ne\r\n0\r\nX-Trailer: y\r\n\r\n",
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        let response = send(&server, &put, "1\r\nA\r\n2\r\nda\r\n0\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 204 No Content\r\n"),
+            "{}",
+            response
+        );
+        let received = server.received_requests();
+        assert_eq!(received[1].body_text(), "Ade");
+        assert_eq!(received[2].body_text(), "Ada");
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].message,
+            "the mock HTTP server received an unexpected request: PUT /users/1"
+        );
+    }
+
+    #[test]
+    fn unexpected_requests_list_the_expectations() {
+        let server = MockHttpServer::start().unwrap();
+        let expected_at = line!() + 2;
+        server
+            .expect("GET", "/a")
+            .header("Accept", "text/plain")
+            .times(2);
+        server.expect("POST", "/b").body("x").optional();
+        let response = get(&server, "/c");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.ends_with(
+                "\r\n\r\nno expectation of the mock HTTP server accepts GET /c\n  \
+                 expected `GET /a` with `Accept: text/plain`, received 0 of 2 requests\n  \
+                 expected `POST /b` with the expected body, received 0 of any number\n"
+            ),
+            "{}",
+            response
+        );
+        let ((), failures) = soft::collect(|| server.verify());
+        let messages: Vec<_> = failures.iter().map(|failure| &failure.message).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected the mock HTTP server to receive 2 requests `GET /a` with \
+                 `Accept: text/plain`, but it received none",
+                "the mock HTTP server received an unexpected request: GET /c",
+            ]
+        );
+        let location = failures[0].location.as_ref().unwrap();
+        assert_eq!(
+            (location.file.as_str(), location.line),
+            (file!(), expected_at)
+        );
+        // Verifying forgets both.
+        let ((), failures) = soft::collect(|| drop(server));
+        assert!(failures.is_empty());
+    }
+
+    #[test]
+    fn saturated_expectations_give_way_to_later_ones() {
+        let server = MockHttpServer::start().unwrap();
+        server.expect("GET", "/token").respond(200, "first");
+        server.expect("GET", "/token").optional().respond(429, "");
+        server.expect("GET", "/later").times(2);
+        assert!(get(&server, "/token").ends_with("\r\n\r\nfirst"));
+        assert!(get(&server, "/token").starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+        assert!(get(&server, "/token").starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+        get(&server, "/later");
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].message,
+            "expected the mock HTTP server to receive 2 requests `GET /later`, but it received 1"
+        );
+    }
+
+    #[test]
+    fn connections_stay_open_between_requests() {
+        let server = MockHttpServer::start().unwrap();
+        server.expect("GET", "/ping").times(2).respond(200, "pong");
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream
+            .write_all(b"GET /ping HTTP/1.1\r\n\r\nGET /ping HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).unwrap();
+        assert_eq!(responses.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[test]
+    fn malformed_requests_close_the_connection() {
+        let server = MockHttpServer::start().unwrap();
+        for request in [
+            "nonsense\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: many\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+        ] {
+            let mut stream = TcpStream::connect(server.addr()).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert_eq!(response, "", "{:?}", request);
+        }
+        assert!(server.received_requests().is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+//! Stopping a run gracefully on Ctrl-C.
+//!
+//! On Unix, the first `SIGINT` or `SIGTERM` the runner receives stops it
+//! from starting further tests, as `--max-failures` does. The tests already
+//! running are given a grace period to finish, [`GRACE_PERIOD`] unless
+//! `--grace-period` says otherwise; then the `after_all` hooks and fixtures
+//! are torn down, the reporters write their reports, and the run exits with
+//! [`EXIT_INTERRUPTED`](crate::runner::EXIT_INTERRUPTED), so that no
+//! container or temporary directory is left behind.
+//!
+//! Tests still running after the grace period fail as abandoned, and the run
+//! ends without waiting for them, after tearing down the session fixtures
+//! and killing the processes of `--process-per-test`. A second signal stops
+//! the run at once, without tearing anything down.
+//!
+//! A long test can end early by checking [`requested`]:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn survives_a_day_of_traffic() {
+//!     let server = Server::start();
+//!     for request in traffic::day() {
+//!         if interrupt::requested() {
+//!             return;
+//!         }
+//!         server.handle(request);
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+/// How long the tests running when the run is interrupted are given to
+/// finish unless `--grace-period` says otherwise.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// The signal that interrupted the run, or 0.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Whether the run has been interrupted.
+pub fn requested() -> bool {
+    SIGNAL.load(Ordering::SeqCst) != 0
+}
+
+/// Stops the run on the first `SIGINT` or `SIGTERM`, and the process on the
+/// second.
+pub(crate) fn install() {
+    imp::install();
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::sync::atomic::Ordering;
+
+    use super::SIGNAL;
+
+    pub(super) fn install() {
+        let handler: extern "C" fn(libc::c_int) = handle;
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: `handle` only calls async-signal-safe functions.
+            unsafe {
+                libc::signal(signal, handler as libc::sighandler_t);
+            }
+        }
+    }
+
+    extern "C" fn handle(signal: libc::c_int) {
+        if SIGNAL
+            .compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // Interrupted again: die of the signal, as if never handled.
+            // SAFETY: `signal` and `raise` are async-signal-safe.
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub(super) fn install() {}
+}
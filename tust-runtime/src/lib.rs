@@ -1,4 +1,237 @@
-// Runtime support for tust test framework
-// Includes fixture management, diff formatting, async runtime detection, etc.
+//! Runtime support for the tust test framework.
+//!
+//! Hosts the test registry populated by `#[tust::test]`, the test runner used
+//! by `harness = false` binaries, and the helpers that generated code calls
+//! into.
 
-// TODO: Implement runtime utilities
+pub mod alloc;
+pub mod bench;
+pub mod cli;
+pub mod clock;
+pub mod cmd;
+pub mod config;
+pub mod container;
+pub mod context;
+pub mod coverage;
+pub mod data;
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+pub mod db;
+pub mod depends;
+pub mod diff;
+pub mod embedded;
+pub mod env;
+pub mod executor;
+pub mod failures;
+pub mod filter;
+pub mod fixture;
+pub mod fuzz;
+pub mod golden;
+pub mod history;
+pub mod hooks;
+pub mod http;
+pub mod interrupt;
+pub mod locks;
+pub mod model;
+pub mod mutation;
+pub mod outcome;
+pub mod overrides;
+pub mod passes;
+pub mod property;
+pub mod quarantine;
+pub mod registry;
+pub mod report;
+pub mod rerun;
+pub mod rng;
+pub mod runner;
+pub mod sandbox;
+pub mod scenario;
+pub mod shard;
+pub mod shared;
+pub mod snapshot;
+pub mod socket;
+pub mod soft;
+pub mod stress;
+pub mod strict;
+pub mod tags;
+pub mod temp;
+pub mod timings;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+pub mod workspace;
+
+mod cache;
+mod capture;
+mod dry_run;
+mod list;
+mod panic;
+mod png;
+mod process;
+#[cfg(target_family = "wasm")]
+mod wasm;
+
+pub use cli::Args;
+pub use clock::Clock;
+pub use cmd::{cmd, Cmd};
+pub use config::Config;
+pub use container::Container;
+pub use context::{Attachment, Step, TestContext};
+pub use env::Env;
+pub use executor::{block_on, AsyncConfig, AsyncRuntime, Flavor};
+pub use fixture::{AsyncFixture, Fixture, FixtureError, Fixtures, Scope};
+pub use http::MockHttpServer;
+pub use outcome::{
+    FailureDiff, FailureKind, IntoTestResult, Location, RunSummary, TestFailure, TestOutcome,
+    TestResult, TestStatus,
+};
+pub use registry::{tests, TestDef};
+pub use report::Reporter;
+pub use rng::{rng, Rng};
+pub use runner::{run, run_with_args};
+pub use sandbox::FsSandbox;
+pub use scenario::Scenario;
+pub use shared::{MappedFile, SharedData};
+pub use socket::MockSocketServer;
+pub use temp::{TempDir, TempFile};
+#[cfg(feature = "tracing")]
+pub use tracing::{init_tracing, init_tracing_at};
+
+/// Items used by macro-generated code. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use std::sync::{OnceLock, PoisonError, RwLock};
+
+    use crate::hooks::Scopes;
+    use crate::runner;
+
+    pub use inventory;
+
+    pub use crate::bench::{run as run_bench, BenchConfig};
+    pub use crate::data::{row as data_row, DataRow};
+    pub use crate::env::Env;
+    pub use crate::executor::{block_on, AsyncConfig, AsyncRuntime, Flavor};
+    pub use crate::fixture::{
+        async_provider as async_fixture_provider, provider as fixture_provider, FixtureDef,
+        FixtureError, FixtureRequest, Fixtures, Provider,
+    };
+    pub use crate::fuzz::{
+        check as check_fuzz, check_bytes as check_fuzz_bytes, FuzzConfig, FuzzDef,
+    };
+    pub use crate::hooks::{HookDef, HookKind};
+    pub use crate::model::check as model_check;
+    pub use crate::panic::catch as catch_panic;
+    pub use crate::property::{builder_gen, check as check_property, PropertyConfig};
+    pub use crate::registry::{ExpectedResult, Limits, Priority, Skip, TestDef};
+    pub use crate::rerun::{set_built, Built};
+    pub use crate::stress::{run as stress, StressConfig};
+
+    /// Serial tests hold this exclusively; every other test shares it.
+    static SERIAL: RwLock<()> = RwLock::new(());
+
+    /// `before_all` and `after_all` hooks of the tests libtest may run. libtest
+    /// does not say which tests it selected, so `after_all` hooks run only once
+    /// every test of their module has run.
+    static SCOPES: OnceLock<Scopes> = OnceLock::new();
+
+    /// Runs a registered test from the `#[test]` wrapper emitted for libtest.
+    pub fn run_libtest(test: &'static TestDef) {
+        // libtest knows nothing of `serial`, so enforce it here.
+        let serial = test.runs_serially();
+        let _exclusive = serial.then(|| SERIAL.write().unwrap_or_else(PoisonError::into_inner));
+        let _shared = (!serial).then(|| SERIAL.read().unwrap_or_else(PoisonError::into_inner));
+        let _locks = crate::locks::hold(test);
+        let scopes = SCOPES.get_or_init(|| {
+            let tests: Vec<_> = crate::registry::tests()
+                .into_iter()
+                .filter(|test| !matches!(test.skip, Skip::Always { .. }))
+                .collect();
+            Scopes::new(&tests)
+        });
+        // libtest ignores tests that are always skipped, and runs them only
+        // when asked to with `--ignored`.
+        if let Skip::If { condition, reason } = test.skip {
+            if condition() {
+                println!("skipped: {}", reason);
+                let mut outcome = runner::skipped(test).expect("the skip condition held");
+                runner::add_failures(&mut outcome, scopes.leave(test));
+                fail_libtest(outcome);
+                return;
+            }
+        }
+        let outcome = runner::run_in_scopes(test, scopes, || {
+            runner::run_with_retries(
+                test,
+                test.retries.unwrap_or(0),
+                test.timeout,
+                runner::run_test_with_timeout,
+            )
+        });
+        if let crate::TestStatus::Skipped(Some(reason)) = &outcome.status {
+            println!("skipped at run time: {}", reason);
+        }
+        // libtest has no place for them but the test's output.
+        for result in crate::bench::take_results_of(test) {
+            println!("bench {}", result);
+        }
+        fail_libtest(outcome);
+    }
+
+    /// What a documentation example's `main`, or its code ending in
+    /// `Ok::<(), E>(())`, returns.
+    pub trait ExampleResult {
+        #[track_caller]
+        fn check(self);
+    }
+
+    impl ExampleResult for () {
+        fn check(self) {}
+    }
+
+    impl<E: std::fmt::Debug> ExampleResult for Result<(), E> {
+        #[track_caller]
+        fn check(self) {
+            if let Err(err) = self {
+                panic!("the example returned an error: {:?}", err);
+            }
+        }
+    }
+
+    /// Ends the test whose function returned `result` as it says. A
+    /// failure it returns is recorded like a soft one: the test did not
+    /// panic with it.
+    #[track_caller]
+    pub fn check_result(result: impl crate::IntoTestResult) {
+        match result.into_test_result() {
+            crate::TestResult::Passed => {}
+            crate::TestResult::Skipped(reason) => {
+                if let Some(test) = crate::registry::current() {
+                    crate::outcome::request_skip(test, reason);
+                }
+            }
+            crate::TestResult::Failed(failure) => crate::soft::record(failure),
+        }
+    }
+
+    /// Fails the example from `doctests!` if it returned an error.
+    #[track_caller]
+    pub fn check_example(result: impl ExampleResult) {
+        result.check();
+    }
+
+    /// Panics with every failure of `outcome`, if it failed, and with the
+    /// run seed if the test asked for randomness.
+    fn fail_libtest(outcome: crate::TestOutcome) {
+        if let crate::TestStatus::Failed(failures) = outcome.status {
+            let mut messages: Vec<String> = failures.iter().map(ToString::to_string).collect();
+            if crate::rng::used_by(outcome.test) {
+                let seed = crate::rng::run_seed();
+                messages.push(format!(
+                    "random seed: {} (rerun with {}={} to reproduce)",
+                    seed,
+                    crate::rng::SEED_ENV,
+                    seed
+                ));
+            }
+            panic!("{}", messages.join("\n\n"));
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! The inventory printed by `--list`.
+//!
+//! The console format prints one test per line with its location, tags, and
+//! attributes:
+//!
+//! ```text
+//! db::insert_one     tests/db.rs:12  [db, slow]  serial, timeout 30s
+//! parser::empty      tests/parser.rs:8
+//! parser::unicode    tests/parser.rs:20  skipped (not ready)
+//!
+//! 3 tests
+//! ```
+//!
+//! `--format terse` (or `-q`) prints libtest's `name: test` lines instead,
+//! for tools that already read them.
+//!
+//! `--format json` prints one JSON object per line, following libtest's
+//! unstable `--list --format json` output and adding the test's module,
+//! tags, and attributes:
+//!
+//! ```text
+//! { "type": "suite", "event": "discovery" }
+//! { "type": "test", "event": "discovered", "name": "db::insert_one", "module": "db",
+//!   "source_path": "tests/db.rs", "start_line": 12, "start_col": 4, "tags": ["db", "slow"],
+//!   "serial": true, "locks": [], "timeout_ms": 30000, "retries": null, "ignore": false,
+//!   "ignore_message": "", "skip_if": false, "should_fail": false, "expected_message": null,
+//!   "should_panic": false, "panic_contains": null, "panic_regex": null, "xfail": false,
+//!   "xfail_reason": null, "allow_leaks": false, "isolated_cwd": false, "depends_on": [],
+//!   "priority": "normal", "fixtures": [] }
+//! { "type": "suite", "event": "completed", "tests": 3, "ignored": 1 }
+//! ```
+//!
+//! `ignore` marks tests that never run, and `skip_if` those skipped when a
+//! condition holds as the run starts; `ignore_message` gives the reason of
+//! either.
+//!
+//! `--schedule` prints the selected tests in the order the runner would
+//! start them in, with their priority and the duration recorded in earlier
+//! runs, which decide that order:
+//!
+//! ```text
+//! 1  high    0.412s  checkout::charges_card
+//! 2  normal  2.031s  search::reindexes       serial
+//! 3  normal  0.017s  parser::empty
+//! 4  low          -  report::renders_pdf     failed last run
+//!
+//! 4 tests, run 8 at a time
+//! ```
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use serde_json::json;
+
+use crate::cli::{Args, OutputFormat};
+use crate::failures::Failures;
+use crate::registry::{ExpectedResult, Priority, Skip, TestDef};
+use crate::runner::format_duration;
+use crate::timings::Timings;
+
+/// Prints `tests`, the tests `args` selects, in the format `args` asks for.
+pub(crate) fn print(args: &Args, tests: &[&'static TestDef]) {
+    let mut out = String::new();
+    match args.format {
+        OutputFormat::Json => write_json(&mut out, tests),
+        _ if args.quiet => {
+            for test in tests {
+                let _ = writeln!(out, "{}: test", test.path());
+            }
+            write_count(&mut out, tests.len());
+        }
+        _ => write_console(&mut out, tests),
+    }
+    // Ignore errors, such as when piped into `head`.
+    let _ = std::io::stdout().lock().write_all(out.as_bytes());
+}
+
+/// Prints `tests` in the order they start in, with what put them there:
+/// their priority, their duration in earlier runs from `timings`, and
+/// whether they failed in their last run, from `failures`.
+pub(crate) fn print_schedule(
+    args: &Args,
+    tests: &[&'static TestDef],
+    timings: &Timings,
+    failures: &Failures,
+    shuffle_seed: Option<u64>,
+) {
+    let rows: Vec<[String; 5]> = tests
+        .iter()
+        .enumerate()
+        .map(|(index, test)| {
+            let mut notes = Vec::new();
+            if test.serial {
+                notes.push("serial");
+            }
+            if args.failed_first && failures.contains(test) {
+                notes.push("failed last run");
+            }
+            [
+                (index + 1).to_string(),
+                test.priority.name().to_string(),
+                timings.get(test).map_or_else(
+                    || "-".to_string(),
+                    |duration| format!("{:.3}s", duration.as_secs_f64()),
+                ),
+                test.path(),
+                notes.join(", "),
+            ]
+        })
+        .collect();
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let widths = [width(0), width(1), width(2), width(3)];
+    let mut out = String::new();
+    for [position, priority, duration, name, notes] in &rows {
+        let line = format!(
+            "{:>w0$}  {:w1$}  {:>w2$}  {:w3$}  {}",
+            position,
+            priority,
+            duration,
+            name,
+            notes,
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    let count = match tests.len() {
+        1 => "1 test".to_string(),
+        count => format!("{} tests", count),
+    };
+    let jobs = match args.jobs() {
+        1 => "run one at a time".to_string(),
+        jobs => format!("run {} at a time", jobs),
+    };
+    let _ = match shuffle_seed {
+        Some(seed) => writeln!(out, "\n{}, shuffled with seed {}, {}", count, seed, jobs),
+        None => writeln!(out, "\n{}, {}", count, jobs),
+    };
+    // Ignore errors, such as when piped into `head`.
+    let _ = std::io::stdout().lock().write_all(out.as_bytes());
+}
+
+fn write_count(out: &mut String, count: usize) {
+    let _ = match count {
+        1 => writeln!(out, "\n1 test"),
+        count => writeln!(out, "\n{} tests", count),
+    };
+}
+
+fn write_console(out: &mut String, tests: &[&'static TestDef]) {
+    let rows: Vec<(String, String, String)> = tests
+        .iter()
+        .map(|test| {
+            let mut details = Vec::new();
+            if !test.tags.is_empty() {
+                details.push(format!("[{}]", test.tags.join(", ")));
+            }
+            let attributes = attributes(test);
+            if !attributes.is_empty() {
+                details.push(attributes.join(", "));
+            }
+            (
+                test.path(),
+                format!("{}:{}", test.file, test.line),
+                details.join("  "),
+            )
+        })
+        .collect();
+    let name_width = rows.iter().map(|row| row.0.chars().count()).max();
+    let location_width = rows.iter().map(|row| row.1.chars().count()).max();
+    for (name, location, details) in &rows {
+        let line = format!(
+            "{:name_width$}  {:location_width$}  {}",
+            name,
+            location,
+            details,
+            name_width = name_width.unwrap_or(0),
+            location_width = location_width.unwrap_or(0),
+        );
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    write_count(out, tests.len());
+}
+
+/// The attributes of `test` that change how it runs, as written in the
+/// console listing.
+fn attributes(test: &TestDef) -> Vec<String> {
+    let mut attributes = Vec::new();
+    if test.serial {
+        attributes.push("serial".to_string());
+    }
+    if !test.locks.is_empty() {
+        attributes.push(format!("locks {}", test.locks.join(", ")));
+    }
+    if let Some(timeout) = test.timeout {
+        attributes.push(format!("timeout {}", format_duration(timeout)));
+    }
+    match test.retries {
+        Some(1) => attributes.push("1 retry".to_string()),
+        Some(retries) => attributes.push(format!("{} retries", retries)),
+        None => {}
+    }
+    match test.skip {
+        Skip::Never => {}
+        Skip::Always { reason: None } => attributes.push("skipped".to_string()),
+        Skip::Always {
+            reason: Some(reason),
+        } => attributes.push(format!("skipped ({})", reason)),
+        Skip::If { reason, .. } => attributes.push(format!("skipped if {}", reason)),
+    }
+    match test.expected {
+        ExpectedResult::Pass => {}
+        ExpectedResult::ShouldFail { expected: None } => attributes.push("should fail".to_string()),
+        ExpectedResult::ShouldFail {
+            expected: Some(expected),
+        } => attributes.push(format!("should fail with {:?}", expected)),
+        ExpectedResult::ShouldPanic { contains, regex } => {
+            let mut attribute = "should panic".to_string();
+            if let Some(contains) = contains {
+                attribute.push_str(&format!(" with {:?}", contains));
+            }
+            if let Some(regex) = regex {
+                attribute.push_str(&format!(" matching /{}/", regex));
+            }
+            attributes.push(attribute);
+        }
+        ExpectedResult::XFail { reason: None } => attributes.push("xfail".to_string()),
+        ExpectedResult::XFail {
+            reason: Some(reason),
+        } => attributes.push(format!("xfail ({})", reason)),
+    }
+    if test.priority != Priority::Normal {
+        attributes.push(format!("priority {}", test.priority.name()));
+    }
+    if test.allow_leaks {
+        attributes.push("allows leaks".to_string());
+    }
+    if test.isolated_cwd {
+        attributes.push("isolated cwd".to_string());
+    }
+    if !test.depends_on.is_empty() {
+        attributes.push(format!("depends on {}", test.depends_on.join(", ")));
+    }
+    if !test.fixtures.is_empty() {
+        let names: Vec<&str> = test.fixtures.iter().map(|fixture| fixture.name).collect();
+        attributes.push(format!("fixtures {}", names.join(", ")));
+    }
+    attributes
+}
+
+fn write_json(out: &mut String, tests: &[&'static TestDef]) {
+    let _ = writeln!(out, "{}", json!({ "type": "suite", "event": "discovery" }));
+    let mut ignored = 0;
+    for test in tests {
+        let (ignore, skip_if, ignore_message) = match test.skip {
+            Skip::Never => (false, false, ""),
+            Skip::Always { reason } => (true, false, reason.unwrap_or("")),
+            Skip::If { reason, .. } => (false, true, reason),
+        };
+        ignored += usize::from(ignore);
+        let (should_fail, expected_message) = match test.expected {
+            ExpectedResult::ShouldFail { expected } => (true, expected),
+            _ => (false, None),
+        };
+        let (should_panic, panic_contains, panic_regex) = match test.expected {
+            ExpectedResult::ShouldPanic { contains, regex } => (true, contains, regex),
+            _ => (false, None, None),
+        };
+        let (xfail, xfail_reason) = match test.expected {
+            ExpectedResult::XFail { reason } => (true, reason),
+            _ => (false, None),
+        };
+        let module = test
+            .module_path
+            .split_once("::")
+            .map_or("", |(_crate, module)| module);
+        let fixtures: Vec<_> = test
+            .fixtures
+            .iter()
+            .map(|fixture| json!({ "name": fixture.name, "type": (fixture.type_name)() }))
+            .collect();
+        let _ = writeln!(
+            out,
+            "{}",
+            json!({
+                "type": "test",
+                "event": "discovered",
+                "name": test.path(),
+                "module": module,
+                "source_path": test.file,
+                "start_line": test.line,
+                "start_col": test.column,
+                "tags": test.tags,
+                "serial": test.serial,
+                "locks": test.locks,
+                "timeout_ms": test.timeout.map(|timeout| timeout.as_millis() as u64),
+                "retries": test.retries,
+                "ignore": ignore,
+                "ignore_message": ignore_message,
+                "skip_if": skip_if,
+                "should_fail": should_fail,
+                "expected_message": expected_message,
+                "should_panic": should_panic,
+                "panic_contains": panic_contains,
+                "panic_regex": panic_regex,
+                "xfail": xfail,
+                "xfail_reason": xfail_reason,
+                "allow_leaks": test.allow_leaks,
+                "isolated_cwd": test.isolated_cwd,
+                "depends_on": test.depends_on,
+                "priority": test.priority.name(),
+                "fixtures": fixtures,
+            })
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}",
+        json!({
+            "type": "suite",
+            "event": "completed",
+            "tests": tests.len(),
+            "ignored": ignored,
+        })
+    );
+}
@@ -0,0 +1,146 @@
+//! Named locks that keep tests using the same external resource, such as a
+//! database, from running at the same time, as set by
+//! `#[tust::test(lock = "database")]`.
+//!
+//! The runner never starts a test while another test holding one of its
+//! locks runs, and starts other tests in the meantime. The locks are also
+//! files, `target/tust/locks/<name>.lock` unless [`DIR_ENV`] names another
+//! directory, locked while a test holding them runs, so that tests of
+//! different test binaries, or of runs started at the same time, wait for
+//! each other too; on platforms other than Unix, locks only apply within a
+//! process.
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, OnceLock, PoisonError};
+
+use crate::cache;
+use crate::registry::TestDef;
+
+/// Environment variable naming the directory of the lock files, to share
+/// locks between checkouts, say; empty to lock within each process only.
+pub const DIR_ENV: &str = "TUST_LOCK_DIR";
+
+/// Locks held by tests of this process.
+static HELD: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+static RELEASED: Condvar = Condvar::new();
+
+/// The locks of a test, released on drop.
+pub(crate) struct Held {
+    names: &'static [&'static str],
+    /// Dropping a file unlocks it.
+    _files: Vec<File>,
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+        for name in self.names {
+            if let Some(index) = held.iter().position(|held| held == name) {
+                held.swap_remove(index);
+            }
+        }
+        RELEASED.notify_all();
+    }
+}
+
+/// Waits until no other test holds the locks of `test`, and takes them.
+pub(crate) fn hold(test: &'static TestDef) -> Held {
+    if test.locks.is_empty() {
+        return Held {
+            names: test.locks,
+            _files: Vec::new(),
+        };
+    }
+    {
+        let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+        while test.locks.iter().any(|name| held.contains(name)) {
+            held = RELEASED.wait(held).unwrap_or_else(PoisonError::into_inner);
+        }
+        held.extend(test.locks);
+    }
+    // Always lock the files in the same order, so that two tests can never
+    // each hold a file the other waits for.
+    let mut names = test.locks.to_vec();
+    names.sort_unstable();
+    let files = match dir() {
+        Some(dir) => names
+            .iter()
+            .filter_map(|name| imp::lock(&dir.join(format!("{}.lock", name))))
+            .collect(),
+        None => Vec::new(),
+    };
+    Held {
+        names: test.locks,
+        _files: files,
+    }
+}
+
+/// The directory of the lock files, created if need be, if locks are to
+/// apply across processes.
+fn dir() -> Option<&'static PathBuf> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = cache::path(DIR_ENV, "locks")?;
+        match std::fs::create_dir_all(&dir) {
+            Ok(()) => Some(dir),
+            Err(err) => {
+                eprintln!(
+                    "warning: cannot create {}, so test locks only apply within this process: {}",
+                    dir.display(),
+                    err
+                );
+                None
+            }
+        }
+    })
+    .as_ref()
+}
+
+/// Whether `test` takes one of the locks in `held`, and so would wait for
+/// the tests holding them.
+pub(crate) fn conflicts(test: &TestDef, held: &[&'static str]) -> bool {
+    test.locks.iter().any(|name| held.contains(name))
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{File, OpenOptions};
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+
+    /// Opens and locks the file at `path`, waiting for other processes to
+    /// unlock it.
+    pub(super) fn lock(path: &Path) -> Option<File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|err| eprintln!("warning: cannot open {}: {}", path.display(), err))
+            .ok()?;
+        loop {
+            // SAFETY: `flock` has no memory-safety preconditions, and the
+            // descriptor stays open while `file` lives.
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                return Some(file);
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                eprintln!("warning: cannot lock {}: {}", path.display(), err);
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::fs::File;
+    use std::path::Path;
+
+    /// File locks are only taken on Unix.
+    pub(super) fn lock(_path: &Path) -> Option<File> {
+        None
+    }
+}
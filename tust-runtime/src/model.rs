@@ -0,0 +1,117 @@
+//! Model checking: exploring the thread interleavings of a concurrent test.
+//!
+//! A `#[tust::test(model_check)]` test runs its body under the model checker
+//! enabled by a feature of tust:
+//!
+//! - `loom` runs it under `loom::model`, which explores every interleaving
+//!   of the threads and atomics the body creates through `loom`'s types, up
+//!   to the bounds set by `loom`'s `LOOM_*` environment variables;
+//! - `shuttle` runs it under `shuttle::check_random`, which tries
+//!   [`MODEL_ITERATIONS_ENV`] random schedules of the threads the body
+//!   creates through `shuttle`'s types, [`DEFAULT_ITERATIONS`] by default.
+//!
+//! With both features, `loom` is used. With neither, the body runs once, as
+//! any other test, so the same test also runs in builds without a checker.
+//! Each execution of the body receives fresh fixtures.
+//!
+//! Only code built on the checker's types is explored: the code under test
+//! typically imports `loom::sync` or `shuttle::sync` instead of `std::sync`
+//! behind a `cfg`.
+
+use crate::fixture::{FixtureError, Fixtures};
+
+/// Environment variable setting how many schedules `shuttle` tries.
+pub const MODEL_ITERATIONS_ENV: &str = "TUST_MODEL_ITERATIONS";
+
+/// How many schedules `shuttle` tries when [`MODEL_ITERATIONS_ENV`] is not
+/// set.
+pub const DEFAULT_ITERATIONS: usize = 1000;
+
+/// The model checker tests run under, if a feature enables one.
+pub fn checker() -> Option<&'static str> {
+    if cfg!(feature = "loom") {
+        Some("loom")
+    } else if cfg!(feature = "shuttle") {
+        Some("shuttle")
+    } else {
+        None
+    }
+}
+
+/// Runs `once` under the enabled model checker, or once with `fixtures`
+/// without one.
+///
+/// # Errors
+///
+/// Returns the error of the first execution whose fixtures could not be
+/// provided.
+pub fn check(
+    fixtures: &mut Fixtures,
+    once: fn(&mut Fixtures) -> Result<(), FixtureError>,
+) -> Result<(), FixtureError> {
+    imp::check(fixtures, once)
+}
+
+#[cfg(any(feature = "loom", feature = "shuttle"))]
+mod imp {
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    use crate::fixture::{FixtureError, Fixtures};
+
+    pub(super) fn check(
+        fixtures: &mut Fixtures,
+        once: fn(&mut Fixtures) -> Result<(), FixtureError>,
+    ) -> Result<(), FixtureError> {
+        let module = fixtures.module();
+        let error = Arc::new(Mutex::new(None));
+        let execution = {
+            let error = Arc::clone(&error);
+            move || {
+                if let Err(err) = once(&mut Fixtures::new(module)) {
+                    error
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get_or_insert(err);
+                }
+            }
+        };
+        explore(execution);
+        let error = error.lock().unwrap_or_else(PoisonError::into_inner).take();
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "loom")]
+    fn explore(execution: impl Fn() + Send + Sync + 'static) {
+        loom::model(execution);
+    }
+
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    fn explore(execution: impl Fn() + Send + Sync + 'static) {
+        let iterations = match std::env::var(super::MODEL_ITERATIONS_ENV) {
+            Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+                panic!(
+                    "{} must be a number of schedules, not {:?}",
+                    super::MODEL_ITERATIONS_ENV,
+                    value
+                )
+            }),
+            Err(_) => super::DEFAULT_ITERATIONS,
+        };
+        shuttle::check_random(execution, iterations);
+    }
+}
+
+#[cfg(not(any(feature = "loom", feature = "shuttle")))]
+mod imp {
+    use crate::fixture::{FixtureError, Fixtures};
+
+    pub(super) fn check(
+        fixtures: &mut Fixtures,
+        once: fn(&mut Fixtures) -> Result<(), FixtureError>,
+    ) -> Result<(), FixtureError> {
+        once(fixtures)
+    }
+}
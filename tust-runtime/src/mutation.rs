@@ -0,0 +1,410 @@
+//! Answering mutation testing tools, with `--mutation-hook`.
+//!
+//! A mutation testing tool changes the code under test one small mutation at
+//! a time, and checks that some test then fails. With `--mutation-hook`, the
+//! test binary runs no tests of its own accord: it reads requests from
+//! stdin, one JSON object per line, and answers each with one line of JSON
+//! on stdout, until stdin ends. A tool asks which tests cover which source
+//! files, and then runs only the tests covering the file it mutated:
+//!
+//! ```text
+//! > { "command": "coverage" }
+//! < { "type": "coverage", "tests": { "lexer::eof": ["/src/parser/src/lexer.rs"] }, "unrecorded": ["io::reads"] }
+//! > { "command": "run", "files": ["src/lexer.rs"], "timeout": 2.5 }
+//! < { "type": "run", "ok": false, "passed": 1, "failed": 1, "skipped": 0, "not_run": 3, "exec_time": 0.31, "tests": [...] }
+//! ```
+//!
+//! The coverage is what `--record-coverage` recorded; see [`crate::coverage`].
+//! `unrecorded` lists the tests it recorded nothing for.
+//!
+//! A `run` runs the tests that covered any of its `files`, relative to the
+//! current directory, and the tests its `tests` names, or every test if it
+//! gives neither; tests without recorded coverage run for any file. Only
+//! the tests the command line selects run, along with the tests they depend
+//! on. Each test runs in a process of its own, so that a mutation crashing
+//! or hanging a test fails only that test, and `timeout`, in seconds, limits
+//! the tests without a `#[timeout]` in place of `--timeout`. A failure is
+//! enough to tell that a mutation was caught, so no further test starts
+//! after the first unless the request says `"all": true`.
+//!
+//! Each entry of `tests` gives a test's `name`, its `exec_time`, and its
+//! `event`: `ok`, `failed`, `timeout` if it ran out its time limit, or
+//! `ignored`; a failed test's carries the message of its first failure in
+//! `message`. An `id` given with a request is repeated in its answer, and a
+//! request that cannot be answered is answered with
+//! `{ "type": "error", "message": ... }`.
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::capture::Stdout;
+use crate::cli::Args;
+use crate::coverage::{self, CoverageMap};
+use crate::depends;
+use crate::hooks::Scopes;
+use crate::interrupt;
+use crate::outcome::{RunSummary, TestOutcome, TestStatus};
+use crate::process;
+use crate::registry::TestDef;
+use crate::runner::{self, Event, Settings, EXIT_INTERRUPTED, EXIT_SUCCESS};
+
+/// Answers the requests read from stdin about the tests of `all` until
+/// stdin ends, returning the exit code.
+pub(crate) fn serve(args: &Args, all: &[&'static TestDef]) -> u8 {
+    interrupt::install();
+    let map = coverage::map_path()
+        .and_then(|path| CoverageMap::read(&path).ok())
+        .unwrap_or_default();
+    let selectable: Vec<_> = all
+        .iter()
+        .copied()
+        .filter(|test| {
+            args.is_selected(&test.path())
+                && args.is_selected_by_tags(test.tags)
+                && args.is_selected_by_file(test.file)
+        })
+        .collect();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let mut answer = match request["command"].as_str() {
+                    Some("coverage") => coverage_of(&selectable, &map),
+                    Some("run") => run(args, all, &selectable, &map, &request),
+                    Some(command) => error(format!(
+                        "unknown command `{}`; expected `coverage` or `run`",
+                        command
+                    )),
+                    None => error("the request has no `command`".to_string()),
+                };
+                if let Some(id) = request.get("id") {
+                    answer["id"] = id.clone();
+                }
+                answer
+            }
+            Err(err) => error(format!("the request is not JSON: {}", err)),
+        };
+        let mut out = Stdout;
+        let _ = writeln!(out, "{}", answer);
+        let _ = out.flush();
+        if interrupt::requested() {
+            return EXIT_INTERRUPTED;
+        }
+    }
+    EXIT_SUCCESS
+}
+
+fn error(message: String) -> Value {
+    json!({ "type": "error", "message": message })
+}
+
+/// The answer to a `coverage` request.
+fn coverage_of(tests: &[&'static TestDef], map: &CoverageMap) -> Value {
+    let mut recorded = serde_json::Map::new();
+    let mut unrecorded = Vec::new();
+    for test in tests {
+        match map.files(test) {
+            Some(files) => {
+                recorded.insert(test.path(), json!(files));
+            }
+            None => unrecorded.push(test.path()),
+        }
+    }
+    json!({ "type": "coverage", "tests": recorded, "unrecorded": unrecorded })
+}
+
+/// The answer to a `run` request.
+fn run(
+    args: &Args,
+    all: &[&'static TestDef],
+    selectable: &[&'static TestDef],
+    map: &CoverageMap,
+    request: &Value,
+) -> Value {
+    let dir = std::env::current_dir().unwrap_or_default();
+    let request = match Request::parse(request, args.timeout, &dir) {
+        Ok(request) => request,
+        Err(message) => return error(message),
+    };
+    let selected = depends::with_dependencies(
+        selectable
+            .iter()
+            .copied()
+            .filter(|test| request.wants(test, map))
+            .collect(),
+        all,
+    );
+
+    let settings = Settings {
+        jobs: args.jobs(),
+        timeout: request.timeout,
+        retries: args.retries,
+        max_failures: (!request.everything).then_some(1),
+        grace_period: args.grace_period.unwrap_or(interrupt::GRACE_PERIOD),
+        capture: None,
+    };
+    let start = Instant::now();
+    let mut summary = RunSummary::default();
+    let mut events = Vec::with_capacity(selected.len());
+    let not_run = runner::run_parallel(
+        &selected,
+        &settings,
+        // Run by each test's process.
+        &Scopes::new(&[]),
+        process::run_in_process,
+        &mut |event| match event {
+            Event::Started(_) | Event::Finished(_) => {}
+            Event::Outcome(outcome) => {
+                summary.record(&outcome);
+                events.push(event_of(&outcome, outcome.test.timeout.or(request.timeout)));
+            }
+            Event::Abandoned(_) => std::process::exit(EXIT_INTERRUPTED.into()),
+        },
+    );
+    json!({
+        "type": "run",
+        "ok": summary.failed == 0,
+        "passed": summary.passed,
+        "failed": summary.failed,
+        "skipped": summary.skipped,
+        "not_run": not_run,
+        "exec_time": start.elapsed().as_secs_f64(),
+        "tests": events,
+    })
+}
+
+/// What a `run` request asks for.
+#[derive(Debug)]
+struct Request {
+    /// The files whose tests to run, as absolute paths.
+    files: Option<BTreeSet<PathBuf>>,
+    /// The tests to run whatever they cover, by name.
+    names: Option<Vec<String>>,
+    /// The time limit of the tests without a `#[timeout]`.
+    timeout: Option<Duration>,
+    /// Whether to go on running tests after the first failure.
+    everything: bool,
+}
+
+impl Request {
+    /// Reads `request`, its files relative to `dir`, its tests limited to
+    /// `timeout` unless it gives its own.
+    fn parse(request: &Value, timeout: Option<Duration>, dir: &Path) -> Result<Self, String> {
+        let strings = |field: &str| -> Result<Option<Vec<String>>, String> {
+            match &request[field] {
+                Value::Null => Ok(None),
+                Value::Array(values) => values
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .map(Some)
+                    .ok_or_else(|| format!("`{}` must be an array of strings", field)),
+                _ => Err(format!("`{}` must be an array of strings", field)),
+            }
+        };
+        let files = strings("files")?;
+        let names = strings("tests")?;
+        let timeout = match &request["timeout"] {
+            Value::Null => timeout,
+            value => Some(
+                value
+                    .as_f64()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or("`timeout` must be a number of seconds")?,
+            ),
+        };
+        let everything = match &request["all"] {
+            Value::Null => false,
+            Value::Bool(everything) => *everything,
+            _ => return Err("`all` must be a boolean".to_string()),
+        };
+        let files = files.map(|files| {
+            files
+                .iter()
+                .map(|file| coverage::canonical(dir.join(file)))
+                .collect()
+        });
+        Ok(Request {
+            files,
+            names,
+            timeout,
+            everything,
+        })
+    }
+
+    /// Whether `test` is to run, as `map` records what it covers.
+    fn wants(&self, test: &TestDef, map: &CoverageMap) -> bool {
+        let named = self
+            .names
+            .as_ref()
+            .is_some_and(|names| names.contains(&test.path()));
+        let covers = self.files.as_ref().is_some_and(|files| {
+            map.files(test)
+                .map_or(true, |covered| !covered.is_disjoint(files))
+        });
+        named || covers || (self.files.is_none() && self.names.is_none())
+    }
+}
+
+/// The entry of a test's outcome in the answer to a `run` request, for a
+/// test given `limit` to run in.
+fn event_of(outcome: &TestOutcome, limit: Option<Duration>) -> Value {
+    let mut event = json!({
+        "name": outcome.test.path(),
+        "event": "ok",
+        "exec_time": outcome.duration.as_secs_f64(),
+    });
+    match &outcome.status {
+        TestStatus::Failed(failures) => {
+            let timed_out = limit.is_some_and(|limit| outcome.duration >= limit);
+            event["event"] = json!(if timed_out { "timeout" } else { "failed" });
+            if let Some(failure) = failures.first() {
+                event["message"] = json!(failure.message);
+            }
+        }
+        TestStatus::Skipped(_) => event["event"] = json!("ignored"),
+        TestStatus::Passed | TestStatus::ExpectedFailure(_) => {}
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::outcome::TestFailure;
+
+    fn def(name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            "app::lexer",
+            name,
+            "src/lexer.rs",
+            1,
+        )))
+    }
+
+    fn outcome(status: TestStatus, ms: u64) -> TestOutcome {
+        TestOutcome {
+            test: def("eof"),
+            status,
+            duration: Duration::from_millis(ms),
+            output: String::new(),
+            attempts: 1,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn parse(request: &str) -> Result<Request, String> {
+        let request = serde_json::from_str(request).unwrap();
+        Request::parse(&request, Some(Duration::from_secs(60)), Path::new("/src"))
+    }
+
+    #[test]
+    fn answers_which_tests_cover_which_files() {
+        let (eof, idents, io) = (def("eof"), def("idents"), def("io"));
+        let mut map = CoverageMap::default();
+        map.record(eof, BTreeSet::from([PathBuf::from("/src/lexer.rs")]));
+        map.record(idents, BTreeSet::new());
+        assert_eq!(
+            coverage_of(&[eof, idents, io], &map),
+            json!({
+                "type": "coverage",
+                "tests": { "lexer::eof": ["/src/lexer.rs"], "lexer::idents": [] },
+                "unrecorded": ["lexer::io"],
+            })
+        );
+    }
+
+    #[test]
+    fn selects_the_tests_covering_the_mutated_files() {
+        let (eof, idents, io) = (def("eof"), def("idents"), def("io"));
+        let mut map = CoverageMap::default();
+        map.record(eof, BTreeSet::from([PathBuf::from("/src/lexer.rs")]));
+        map.record(idents, BTreeSet::from([PathBuf::from("/src/ident.rs")]));
+        let wanted = |request: &str| -> Vec<&str> {
+            let request = parse(request).unwrap();
+            [eof, idents, io]
+                .into_iter()
+                .filter(|test| request.wants(test, &map))
+                .map(|test| test.name)
+                .collect()
+        };
+        // `io` has no recorded coverage, so runs for any file.
+        assert_eq!(wanted(r#"{ "files": ["lexer.rs"] }"#), ["eof", "io"]);
+        assert_eq!(wanted(r#"{ "files": ["main.rs"] }"#), ["io"]);
+        assert_eq!(wanted(r#"{ "tests": ["lexer::idents"] }"#), ["idents"]);
+        assert_eq!(wanted("{}"), ["eof", "idents", "io"]);
+    }
+
+    #[test]
+    fn reads_run_requests() {
+        let request = parse("{}").unwrap();
+        assert_eq!(request.timeout, Some(Duration::from_secs(60)));
+        assert!(!request.everything);
+        let request = parse(r#"{ "files": ["a.rs"], "timeout": 2.5, "all": true }"#).unwrap();
+        assert_eq!(
+            request.files,
+            Some(BTreeSet::from([PathBuf::from("/src/a.rs")]))
+        );
+        assert_eq!(request.timeout, Some(Duration::from_millis(2500)));
+        assert!(request.everything);
+
+        for (request, err) in [
+            (
+                r#"{ "files": "a.rs" }"#,
+                "`files` must be an array of strings",
+            ),
+            (r#"{ "tests": [1] }"#, "`tests` must be an array of strings"),
+            (
+                r#"{ "timeout": -1 }"#,
+                "`timeout` must be a number of seconds",
+            ),
+            (
+                r#"{ "timeout": "2s" }"#,
+                "`timeout` must be a number of seconds",
+            ),
+            (r#"{ "all": "yes" }"#, "`all` must be a boolean"),
+        ] {
+            assert_eq!(parse(request).unwrap_err(), err, "{}", request);
+        }
+    }
+
+    /// A mutation is caught, or killed, by a failing or timed out test, and
+    /// survives tests that pass or are skipped.
+    #[test]
+    fn reports_whether_each_test_caught_the_mutation() {
+        let limit = Some(Duration::from_secs(1));
+        let failed = || TestStatus::Failed(vec![TestFailure::new("expected 2, got 3")]);
+        assert_eq!(
+            event_of(&outcome(failed(), 20), limit),
+            json!({
+                "name": "lexer::eof",
+                "event": "failed",
+                "exec_time": 0.02,
+                "message": "expected 2, got 3",
+            })
+        );
+        assert_eq!(
+            event_of(&outcome(failed(), 1000), limit)["event"],
+            "timeout"
+        );
+        assert_eq!(event_of(&outcome(failed(), 5000), None)["event"], "failed");
+        assert_eq!(
+            event_of(&outcome(TestStatus::Passed, 20), limit),
+            json!({ "name": "lexer::eof", "event": "ok", "exec_time": 0.02 })
+        );
+        let skipped = TestStatus::Skipped(Some("no network".into()));
+        assert_eq!(event_of(&outcome(skipped, 0), limit)["event"], "ignored");
+    }
+}
@@ -0,0 +1,441 @@
+//! Results of executing tests.
+//!
+//! A test function returns `()`, a [`TestResult`], or a `Result` of either
+//! with an error implementing `Debug`; see [`IntoTestResult`]. A test that
+//! can only tell once it runs that it cannot run, such as one missing the
+//! credentials of a service, returns [`TestResult::Skipped`] with the reason,
+//! and is reported as skipped at run time, apart from the tests its
+//! attributes skip:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn uploads_to_the_bucket() -> TestResult {
+//!     let Ok(key) = std::env::var("BUCKET_KEY") else {
+//!         return TestResult::Skipped("BUCKET_KEY is not set".into());
+//!     };
+//!     Bucket::connect(&key).upload("hello.txt", b"hello");
+//!     TestResult::Passed
+//! }
+//! ```
+
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::context::{Attachment, Step};
+use crate::registry::TestDef;
+use crate::{alloc, diff, panic, quarantine};
+
+/// Source location of a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Why a test failed.
+///
+/// Assertions raise failures with [`raise`](Self::raise), or record them with
+/// [`soft::record`](crate::soft::record), rather than panicking with a
+/// message, so that reporters receive the values that differ and the facts
+/// noted about the failure, not only their rendering. Panics of other code
+/// become failures holding just their message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    pub message: String,
+    /// The two values that should have been equal, as rendered by the
+    /// assertion.
+    pub diff: Option<Box<FailureDiff>>,
+    pub location: Option<Location>,
+    /// Facts about the failure as label and value, such as the seed or the
+    /// input that made it happen, in the order they were added.
+    pub metadata: Vec<(String, String)>,
+    /// Backtrace of the panic, captured when `RUST_BACKTRACE` enables it.
+    pub backtrace: Option<String>,
+    /// Whether the test panicked with the failure, rather than recording it
+    /// as a soft failure or having the runner find it.
+    pub panicked: bool,
+    /// What kind of failure it is, as reports classify it.
+    pub kind: FailureKind,
+}
+
+/// What kind of failure a [`TestFailure`] is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The test panicked other than by failing an assertion.
+    Panic,
+    /// An assertion failed, ending the test.
+    Assertion,
+    /// A soft check, such as `check!`, failed and let the test continue.
+    Check,
+    /// Any other failure, such as a timeout, a leak, or an error the test
+    /// returned.
+    #[default]
+    Error,
+}
+
+impl FailureKind {
+    /// The kind as reports name it.
+    pub fn name(self) -> &'static str {
+        match self {
+            FailureKind::Panic => "panic",
+            FailureKind::Assertion => "assertion",
+            FailureKind::Check => "check",
+            FailureKind::Error => "error",
+        }
+    }
+
+    /// The kind [`name`](Self::name) gives `name`.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [
+            FailureKind::Panic,
+            FailureKind::Assertion,
+            FailureKind::Check,
+            FailureKind::Error,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/// The values compared by a failed assertion, rendered as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureDiff {
+    /// Names of the two sides, such as `left` and `right`, or `expected` and
+    /// `actual`.
+    pub labels: (String, String),
+    pub left: String,
+    pub right: String,
+}
+
+impl TestFailure {
+    /// Creates a failure without a source location.
+    pub fn new(message: impl Into<String>) -> Self {
+        TestFailure {
+            message: message.into(),
+            diff: None,
+            location: None,
+            metadata: Vec::new(),
+            backtrace: None,
+            panicked: false,
+            kind: FailureKind::Error,
+        }
+    }
+
+    /// Adds the values `left` and `right`, which should have been equal.
+    pub fn with_diff(self, left: impl Into<String>, right: impl Into<String>) -> Self {
+        self.with_labeled_diff("left", "right", left, right)
+    }
+
+    /// Like [`with_diff`](Self::with_diff), naming the two sides.
+    pub fn with_labeled_diff(
+        self,
+        left_label: impl Into<String>,
+        right_label: impl Into<String>,
+        left: impl Into<String>,
+        right: impl Into<String>,
+    ) -> Self {
+        TestFailure {
+            diff: Some(Box::new(FailureDiff {
+                labels: (left_label.into(), right_label.into()),
+                left: left.into(),
+                right: right.into(),
+            })),
+            ..self
+        }
+    }
+
+    /// Adds a fact about the failure, shown below its message.
+    pub fn with_metadata(mut self, label: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.metadata.push((label.into(), value.to_string()));
+        self
+    }
+
+    /// Ends the current test with the failure, pointing at the caller unless
+    /// the failure has a location.
+    ///
+    /// The failure unwinds the test as a panic whose payload is the failure
+    /// itself, so the runtime keeps its structure. Where the runtime does not
+    /// catch panics, such as on threads a test spawned, the panic message is
+    /// the failure's rendering instead.
+    #[track_caller]
+    pub fn raise(self) -> ! {
+        if panic::is_catching() {
+            std::panic::panic_any(self)
+        } else {
+            panic!("{}", self.description())
+        }
+    }
+
+    /// The message followed by the diff and the metadata, as reporters show
+    /// them above the location.
+    pub fn description(&self) -> String {
+        let mut out = self.message.clone();
+        if let Some(diff) = &self.diff {
+            let _ = write!(out, "{}", diff);
+        }
+        let width = self
+            .metadata
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (label, value) in &self.metadata {
+            let _ = write!(out, "\n  {:>width$}: {}", label, value, width = width);
+        }
+        out
+    }
+}
+
+impl From<String> for TestFailure {
+    fn from(message: String) -> Self {
+        TestFailure::new(message)
+    }
+}
+
+impl From<&str> for TestFailure {
+    fn from(message: &str) -> Self {
+        TestFailure::new(message)
+    }
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description())?;
+        if let Some(location) = &self.location {
+            write!(f, "\n  at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the values side by side, or as a diff if they span lines, on the
+/// lines after a failure's message.
+impl fmt::Display for FailureDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (left_label, right_label) = &self.labels;
+        if diff::wants_diff(&self.left, &self.right) {
+            let rendered =
+                diff::format_labeled_diff(left_label, right_label, &self.left, &self.right);
+            write!(f, "\n\n{}", rendered.trim_end())
+        } else {
+            let width = left_label.chars().count().max(right_label.chars().count()) + 1;
+            write!(
+                f,
+                "\n{:>width$}: {}\n{:>width$}: {}",
+                left_label,
+                self.left,
+                right_label,
+                self.right,
+                width = width
+            )
+        }
+    }
+}
+
+/// Final status of a single test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    /// Every failure of the test, in the order they happened. Never empty.
+    Failed(Vec<TestFailure>),
+    /// The test was not run; holds the reason, if one was given.
+    Skipped(Option<String>),
+    /// The test is marked `#[xfail]` and failed as expected; holds the
+    /// reason, if one was given.
+    ExpectedFailure(Option<String>),
+}
+
+impl TestStatus {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TestStatus::Failed(_))
+    }
+}
+
+/// What a test function returns to end as other than passed; see
+/// [the module](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestResult {
+    Passed,
+    /// The test decided at run time not to run; holds the reason.
+    Skipped(String),
+    Failed(TestFailure),
+}
+
+/// What a test function may return: `()`, a [`TestResult`], or a `Result`
+/// of either whose error, if it returns one, fails the test.
+pub trait IntoTestResult {
+    fn into_test_result(self) -> TestResult;
+}
+
+impl IntoTestResult for () {
+    fn into_test_result(self) -> TestResult {
+        TestResult::Passed
+    }
+}
+
+impl IntoTestResult for TestResult {
+    fn into_test_result(self) -> TestResult {
+        self
+    }
+}
+
+impl<T: IntoTestResult, E: fmt::Debug> IntoTestResult for Result<T, E> {
+    fn into_test_result(self) -> TestResult {
+        match self {
+            Ok(result) => result.into_test_result(),
+            Err(err) => TestResult::Failed(TestFailure::new(format!(
+                "the test returned an error: {:?}",
+                err
+            ))),
+        }
+    }
+}
+
+/// Skips the tests running asked for, until their outcome takes them.
+static SKIP_REQUESTS: Mutex<Vec<(&'static TestDef, String)>> = Mutex::new(Vec::new());
+
+/// Tests whose last outcome was a skip they decided on at run time.
+static SKIPPED_AT_RUN_TIME: Mutex<Vec<&'static TestDef>> = Mutex::new(Vec::new());
+
+/// Makes the attempt at `test` running end skipped for `reason`, unless it
+/// fails.
+pub(crate) fn request_skip(test: &'static TestDef, reason: String) {
+    alloc::untracked(|| {
+        let mut requests = SKIP_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+        requests.retain(|(of, _)| !std::ptr::eq(*of, test));
+        requests.push((test, reason));
+    });
+}
+
+/// The status of an attempt at `test` that ended with `failures`: skipped,
+/// if it asked to be and did not fail.
+pub(crate) fn status_of(test: &'static TestDef, failures: Vec<TestFailure>) -> TestStatus {
+    let reason = {
+        let mut requests = SKIP_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+        requests
+            .iter()
+            .position(|(of, _)| std::ptr::eq(*of, test))
+            .map(|index| requests.swap_remove(index).1)
+    };
+    let skipped = reason.is_some() && failures.is_empty();
+    let mut marked = SKIPPED_AT_RUN_TIME
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    marked.retain(|of| !std::ptr::eq(*of, test));
+    if skipped {
+        marked.push(test);
+    }
+    match reason {
+        _ if !failures.is_empty() => TestStatus::Failed(failures),
+        Some(reason) => TestStatus::Skipped(Some(reason)),
+        None => TestStatus::Passed,
+    }
+}
+
+/// The result of executing one test.
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    pub test: &'static TestDef,
+    pub status: TestStatus,
+    pub duration: Duration,
+    /// Output the test printed, if it was captured; empty otherwise.
+    /// Reporters show it only for failed tests.
+    pub output: String,
+    /// How many times the test was run: 0 if it was skipped, more than 1 if
+    /// it was retried after failing. The status and output are those of the
+    /// last attempt.
+    pub attempts: u32,
+    /// Files the last attempt attached, with
+    /// [`TestContext::attach`](crate::context::TestContext::attach).
+    pub attachments: Vec<Attachment>,
+    /// Steps the last attempt ran, with
+    /// [`TestContext::step`](crate::context::TestContext::step), in the order
+    /// they started.
+    pub steps: Vec<Step>,
+}
+
+impl TestOutcome {
+    /// Whether the test failed at first but passed when retried.
+    pub fn is_flaky(&self) -> bool {
+        self.attempts > 1 && self.status == TestStatus::Passed
+    }
+
+    /// Whether the test skipped itself at run time, returning
+    /// [`TestResult::Skipped`], rather than being skipped by its attributes
+    /// or its dependencies.
+    pub fn is_skipped_at_run_time(&self) -> bool {
+        matches!(self.status, TestStatus::Skipped(_))
+            && SKIPPED_AT_RUN_TIME
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .any(|of| std::ptr::eq(*of, self.test))
+    }
+}
+
+/// Aggregate counts for a whole run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub passed: usize,
+    /// Failed tests, apart from those in quarantine.
+    pub failed: usize,
+    /// Failed tests in quarantine, which do not fail the run; see
+    /// [`crate::quarantine`].
+    pub quarantined: usize,
+    /// Tests in quarantine that have passed long enough to leave it, by
+    /// name.
+    pub stable_quarantined: Vec<String>,
+    /// Passed tests that needed more than one attempt; included in `passed`.
+    pub flaky: usize,
+    pub skipped: usize,
+    /// Tests that skipped themselves at run time; included in `skipped`.
+    pub skipped_at_run_time: usize,
+    /// Tests marked `#[xfail]` that failed as expected.
+    pub expected_failures: usize,
+    /// Tests that were registered but not selected by the filters.
+    pub filtered_out: usize,
+    /// Selected tests that did not run because `--max-failures` tests had
+    /// already failed, or because the run was interrupted.
+    pub not_run: usize,
+    /// Whether the run was interrupted by a signal, such as Ctrl-C; see
+    /// [`crate::interrupt`].
+    pub interrupted: bool,
+    pub duration: Duration,
+    /// The run seed, if any test asked for randomness.
+    pub seed: Option<u64>,
+    /// The seed the tests were shuffled with, if they were.
+    pub shuffle_seed: Option<u64>,
+}
+
+impl RunSummary {
+    /// Whether the run should be reported as successful.
+    pub fn is_success(&self) -> bool {
+        self.failed == 0 && !self.interrupted
+    }
+
+    pub(crate) fn record(&mut self, outcome: &TestOutcome) {
+        match outcome.status {
+            TestStatus::Passed => self.passed += 1,
+            TestStatus::Failed(_) if quarantine::is_quarantined(outcome.test) => {
+                self.quarantined += 1
+            }
+            TestStatus::Failed(_) => self.failed += 1,
+            TestStatus::Skipped(_) => self.skipped += 1,
+            TestStatus::ExpectedFailure(_) => self.expected_failures += 1,
+        }
+        if outcome.is_flaky() {
+            self.flaky += 1;
+        }
+        if outcome.is_skipped_at_run_time() {
+            self.skipped_at_run_time += 1;
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Replacements a test registers for the services of the code under test.
+//!
+//! Code that looks its dependencies up with [`resolve_or_else`] rather than
+//! building them itself gets the test's replacement while a test registered
+//! one with [`register`], and its own otherwise. Replacements are keyed by
+//! the type they stand in for, usually a trait object, and belong to the test
+//! that registered them: tests running at the same time see only their own,
+//! and each test's are dropped once it ends, after its `after_each` hooks,
+//! so there is no global state to reset between tests.
+//!
+//! ```ignore
+//! pub trait Mailer: Send + Sync {
+//!     fn send(&self, to: &str, body: &str);
+//! }
+//!
+//! pub fn welcome(user: &str) {
+//!     let mailer = overrides::resolve_or_else::<dyn Mailer>(|| Arc::new(SmtpMailer));
+//!     mailer.send(user, "Welcome!");
+//! }
+//!
+//! #[tust::test]
+//! fn welcomes_new_users() {
+//!     let mailer = Arc::new(FakeMailer::default());
+//!     overrides::register::<dyn Mailer>(mailer.clone());
+//!     welcome("ada@example.com");
+//!     assert_eq!(mailer.sent(), ["ada@example.com"]);
+//! }
+//! ```
+//!
+//! Lookups find the test running on the current thread, as
+//! [`TestContext::current`](crate::TestContext::current) does: on threads
+//! the test spawned, and outside of tests, they find no replacements.
+
+use std::any::{Any, TypeId};
+use std::sync::{Arc, Mutex};
+
+use crate::alloc;
+use crate::registry::{self, TestDef};
+
+/// A registered replacement: the test it belongs to, the type it stands in
+/// for, and an `Arc` of that type.
+type Entry = (&'static TestDef, TypeId, Box<dyn Any + Send + Sync>);
+
+static OVERRIDES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Registers `replacement` for `T`, for the rest of the test running on
+/// this thread, replacing any registered before.
+///
+/// # Panics
+///
+/// Panics outside of a test.
+#[track_caller]
+pub fn register<T: ?Sized + Send + Sync + 'static>(replacement: Arc<T>) {
+    let test = registry::current().expect("overrides can only be registered by a running test");
+    let replacement: Box<dyn Any + Send + Sync> = Box::new(replacement);
+    let replaced = alloc::untracked(|| {
+        let mut overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+        let existing = overrides
+            .iter()
+            .position(|(of, type_id, _)| std::ptr::eq(*of, test) && *type_id == TypeId::of::<T>());
+        match existing {
+            Some(index) => Some(std::mem::replace(&mut overrides[index].2, replacement)),
+            None => {
+                overrides.push((test, TypeId::of::<T>(), replacement));
+                None
+            }
+        }
+    });
+    // Dropped outside of the lock, in case its drop resolves overrides.
+    drop(replaced);
+}
+
+/// The replacement for `T` registered by the test running on this thread,
+/// if any.
+pub fn resolve<T: ?Sized + Send + Sync + 'static>() -> Option<Arc<T>> {
+    let test = registry::current()?;
+    let overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+    overrides
+        .iter()
+        .find(|(of, type_id, _)| std::ptr::eq(*of, test) && *type_id == TypeId::of::<T>())
+        .and_then(|(_, _, replacement)| replacement.downcast_ref::<Arc<T>>())
+        .cloned()
+}
+
+/// The replacement for `T` registered by the test running on this thread,
+/// or else what `default` returns.
+pub fn resolve_or_else<T: ?Sized + Send + Sync + 'static>(
+    default: impl FnOnce() -> Arc<T>,
+) -> Arc<T> {
+    resolve().unwrap_or_else(default)
+}
+
+/// Drops the replacements `test` registered.
+pub(crate) fn finish(test: &'static TestDef) {
+    let mut removed: Vec<Entry> = alloc::untracked(|| {
+        let mut overrides = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+        let mut removed = Vec::new();
+        let mut index = 0;
+        while index < overrides.len() {
+            if std::ptr::eq(overrides[index].0, test) {
+                removed.push(overrides.swap_remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        removed
+    });
+    // The test made the replacements, so they are freed as its memory; the
+    // list that held them is not.
+    removed.clear();
+    alloc::untracked(|| drop(removed));
+}
@@ -0,0 +1,237 @@
+//! Turns panics inside tests into [`TestFailure`]s.
+//!
+//! The runtime installs a panic hook that, on threads currently executing a
+//! test, records the panic message and location instead of printing them,
+//! or the failure itself when the payload is a [`TestFailure`] raised by an
+//! assertion.
+//! Panics on any other thread are forwarded to the previously installed hook.
+//! On WebAssembly, where panics abort, a panicking test ends the run; see
+//! the `wasm` module.
+
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use crate::outcome::{FailureKind, Location, TestFailure};
+
+thread_local! {
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    static LAST_PANIC: RefCell<Option<TestFailure>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(Cell::get) {
+                return previous(info);
+            }
+            let mut failure = match info.payload().downcast_ref::<TestFailure>() {
+                Some(failure) => failure.clone(),
+                None => message_failure(info.payload()),
+            };
+            if failure.location.is_none() {
+                failure.location = info.location().map(|location| Location {
+                    file: location.file().to_string(),
+                    line: location.line(),
+                    column: location.column(),
+                });
+            }
+            failure.backtrace = capture_backtrace();
+            failure.panicked = true;
+            // Failures raised as payloads come from assertions.
+            if failure.kind == FailureKind::Error {
+                failure.kind = FailureKind::Assertion;
+            }
+            // Steps fail as the panic unwinds them, unless it is caught.
+            if let Some(test) = crate::registry::current() {
+                crate::context::note_step(test, &mut failure, false);
+            }
+            // The panic cannot be caught: it aborts once the hook returns.
+            #[cfg(target_family = "wasm")]
+            if let Some(test) = crate::registry::current() {
+                crate::wasm::abort(test, failure);
+            }
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(failure));
+        }));
+    });
+}
+
+fn capture_backtrace() -> Option<String> {
+    let backtrace = Backtrace::capture();
+    if backtrace.status() != BacktraceStatus::Captured {
+        return None;
+    }
+    let backtrace = backtrace.to_string();
+    if std::env::var("RUST_BACKTRACE").is_ok_and(|value| value == "full") {
+        Some(backtrace)
+    } else {
+        Some(shorten(&backtrace, &[]))
+    }
+}
+
+/// The backtrace of the code spawning a task, captured from a hook `runtime`
+/// calls while spawning it, whatever `RUST_BACKTRACE` says. Unless it is
+/// `full`, the backtrace is short, and leaves out `runtime`'s frames too.
+#[cfg(all(feature = "tokio", tokio_unstable))]
+pub(crate) fn spawn_backtrace(backtrace: &Backtrace, runtime: &str) -> String {
+    let backtrace = backtrace.to_string();
+    if std::env::var("RUST_BACKTRACE").is_ok_and(|value| value == "full") {
+        backtrace
+    } else {
+        shorten(&backtrace, &[runtime])
+    }
+}
+
+/// Crates whose frames are left out of short backtraces: the standard
+/// library's and tust's own.
+const FRAMEWORK_CRATES: &[&str] = &[
+    "std",
+    "core",
+    "alloc",
+    "__rustc",
+    "tust",
+    "tust_runtime",
+    "tust_assertions",
+];
+
+/// Keeps the frames between the panic machinery and the test's registry
+/// entry, like the short backtraces printed by the default panic hook, and
+/// leaves out the frames of the standard library and of tust in between.
+/// The frames are renumbered, and tests' bodies named as written. The frames
+/// of the crates `also_hidden` are left out as well.
+fn shorten(backtrace: &str, also_hidden: &[&str]) -> String {
+    let mut frames: Vec<String> = Vec::new();
+    for line in backtrace.lines() {
+        let is_frame_start = line
+            .trim_start()
+            .split_once(": ")
+            .is_some_and(|(index, _)| index.chars().all(|c| c.is_ascii_digit()));
+        match frames.last_mut() {
+            Some(frame) if !is_frame_start => {
+                frame.push('\n');
+                frame.push_str(line);
+            }
+            _ => frames.push(line.to_string()),
+        }
+    }
+
+    let start = frames
+        .iter()
+        .position(|frame| frame.contains("__rust_end_short_backtrace"))
+        .map_or(0, |index| index + 1);
+    let end = frames
+        .iter()
+        .position(|frame| frame.contains("::__TUST_TEST_"))
+        .map(|index| index + 1)
+        .or_else(|| {
+            frames
+                .iter()
+                .position(|frame| frame.contains("__rust_begin_short_backtrace"))
+        })
+        .unwrap_or(frames.len())
+        .max(start);
+    let frames = &frames[start..end];
+
+    let own: Vec<&String> = frames
+        .iter()
+        .filter(|frame| !is_framework_frame(frame, also_hidden))
+        .collect();
+    if own.is_empty() {
+        return frames.join("\n");
+    }
+    let mut short: Vec<String> = own
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let (_, rest) = frame
+                .trim_start()
+                .split_once(": ")
+                .unwrap_or(("", frame.as_str()));
+            format!("{:>4}: {}", index, rest.replace("__tust_body_", ""))
+        })
+        .collect();
+    if own.len() < frames.len() {
+        let crates = match also_hidden {
+            [] => "the standard library and tust".to_string(),
+            names => format!("the standard library, tust, and {}", names.join(", ")),
+        };
+        short.push(format!(
+            "note: {} frames of {} are hidden; set RUST_BACKTRACE=full to see them",
+            frames.len() - own.len(),
+            crates
+        ));
+    }
+    short.join("\n")
+}
+
+/// Whether the function of `frame` belongs to one of the
+/// [`FRAMEWORK_CRATES`] or to `also_hidden`, or is one of the functions
+/// generated for a test.
+fn is_framework_frame(frame: &str, also_hidden: &[&str]) -> bool {
+    let function = frame
+        .lines()
+        .next()
+        .and_then(|line| line.trim_start().split_once(": "))
+        .map_or("", |(_, function)| function);
+    if function.contains("::__TUST_TEST_") || function.starts_with("rust_begin_unwind") {
+        return true;
+    }
+    let path = function.trim_start_matches('<');
+    FRAMEWORK_CRATES.iter().chain(also_hidden).any(|name| {
+        path.strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with("::"))
+    })
+}
+
+/// Extracts the message of a panic payload.
+pub(crate) fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(failure) = payload.downcast_ref::<TestFailure>() {
+        failure.message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Whether a panic on this thread would be caught by [`catch`].
+pub(crate) fn is_catching() -> bool {
+    CATCHING.with(Cell::get)
+}
+
+/// Runs `f`, converting a panic into a [`TestFailure`].
+pub fn catch<R, F: FnOnce() -> R>(f: F) -> Result<R, TestFailure> {
+    install_hook();
+    let was_catching = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(was_catching));
+
+    result.map_err(|payload| TestFailure {
+        panicked: true,
+        ..LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| message_failure(payload.as_ref()))
+    })
+}
+
+/// The failure of a panic whose payload is not a [`TestFailure`]: an
+/// assertion of the standard library's, judging by its message, or another
+/// panic.
+fn message_failure(payload: &(dyn Any + Send)) -> TestFailure {
+    let message = payload_message(payload);
+    let kind = if message.starts_with("assertion") {
+        FailureKind::Assertion
+    } else {
+        FailureKind::Panic
+    };
+    TestFailure {
+        kind,
+        ..TestFailure::new(message)
+    }
+}
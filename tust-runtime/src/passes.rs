@@ -0,0 +1,325 @@
+//! Skipping the tests that passed before with the same inputs, with
+//! `--cached`.
+//!
+//! Runs with `--cached` record each test that passed along with a
+//! fingerprint of its inputs: the options that change which tests run and
+//! how, the test binary, and the contents of the source files the test
+//! executed, if `--record-coverage` recorded them. The passes are kept in
+//! `tust/passed.json` in Cargo's target directory, or the file named by
+//! [`CACHE_ENV`]. Later runs with `--cached` then skip the selected tests
+//! whose fingerprint matches their recorded pass, counting them as filtered
+//! out. A test thus runs again once the binary is rebuilt, or the options
+//! select other tests, and a test with recorded coverage also once a file it
+//! executed changes.
+//!
+//! The fingerprint does not cover what a test reads as it runs, such as
+//! data files, environment variables, or services, nor code it did not
+//! execute when its coverage was recorded; a test depending on them may pass
+//! when cached and fail when run.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::cli::Args;
+use crate::coverage::{self, CoverageMap};
+use crate::outcome::{TestOutcome, TestStatus};
+use crate::registry::TestDef;
+use crate::timings;
+
+/// Environment variable naming the cache file; if set but empty, passes are
+/// neither read nor recorded.
+pub const CACHE_ENV: &str = "TUST_PASSED";
+
+/// Tests that passed, with the fingerprints of the runs they passed in.
+#[derive(Debug, Clone, Default)]
+pub struct Passes {
+    tests: BTreeMap<String, String>,
+}
+
+impl Passes {
+    /// Reads the passes recorded in `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tests = value["passed"].as_object().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "expected a `passed` object")
+        })?;
+        Ok(Passes {
+            tests: tests
+                .iter()
+                .filter_map(|(test, fingerprint)| {
+                    Some((test.clone(), fingerprint.as_str()?.to_string()))
+                })
+                .collect(),
+        })
+    }
+
+    /// Writes the passes to `path`, creating its directory if needed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(&json!({ "passed": self.tests }))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        text.push('\n');
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    /// Whether `test` passed in a run with `fingerprint`.
+    pub fn contains(&self, test: &TestDef, fingerprint: &str) -> bool {
+        self.tests.get(&timings::key(test)).map(String::as_str) == Some(fingerprint)
+    }
+}
+
+/// The cache file: [`CACHE_ENV`] if set, else `tust/passed.json` in the
+/// target directory holding the running binary, if there is one.
+pub fn cache_path() -> Option<PathBuf> {
+    cache::path(CACHE_ENV, "passed.json")
+}
+
+/// The recorded passes and what the tests of this run would run, for
+/// `--cached`.
+pub(crate) struct Cached {
+    path: PathBuf,
+    passes: Passes,
+    /// Hash of the test binary.
+    binary: u64,
+    /// Hash of the options changing how tests run.
+    options: u64,
+    coverage: CoverageMap,
+}
+
+impl Cached {
+    /// Reads the recorded passes and fingerprints the run `args` sets up,
+    /// warning and returning `None` if the binary cannot be read.
+    pub(crate) fn load(args: &Args) -> Option<Self> {
+        let path = cache_path()?;
+        let binary = match std::env::current_exe().and_then(|exe| hash_file(&exe)) {
+            Ok(binary) => binary,
+            Err(err) => {
+                eprintln!(
+                    "warning: cannot read the test binary for --cached; running every test: {}",
+                    err
+                );
+                return None;
+            }
+        };
+        Some(Cached {
+            passes: Passes::read(&path).unwrap_or_default(),
+            path,
+            binary,
+            options: hash_options(args),
+            coverage: coverage::map_path()
+                .and_then(|path| CoverageMap::read(&path).ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether `test` passed before in a run like this one.
+    pub(crate) fn passed(&self, test: &TestDef) -> bool {
+        self.passes.contains(test, &self.fingerprint(test))
+    }
+
+    /// Records the tests of `outcomes` that ran, keeping those that passed
+    /// and forgetting the others.
+    pub(crate) fn update(mut self, outcomes: &[TestOutcome]) {
+        for outcome in outcomes.iter().filter(|outcome| outcome.attempts > 0) {
+            let key = timings::key(outcome.test);
+            // A flaky pass needed retries, and may not pass again.
+            if matches!(outcome.status, TestStatus::Passed) && outcome.attempts == 1 {
+                let fingerprint = self.fingerprint(outcome.test);
+                self.passes.tests.insert(key, fingerprint);
+            } else {
+                self.passes.tests.remove(&key);
+            }
+        }
+        if let Err(err) = self.passes.write(&self.path) {
+            eprintln!(
+                "warning: failed to record passed tests in {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    /// The fingerprint of the inputs of `test` in this run.
+    fn fingerprint(&self, test: &TestDef) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.options.hash(&mut hasher);
+        timings::key(test).hash(&mut hasher);
+        self.binary.hash(&mut hasher);
+        for file in self.coverage.files(test).into_iter().flatten() {
+            file.hash(&mut hasher);
+            hash_file(file).ok().hash(&mut hasher);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Hashes the options of `args` that change which tests run and how: a
+/// test depending on another, or sharing a fixture with it, may pass only
+/// when the other one runs.
+fn hash_options(args: &Args) -> u64 {
+    let options = (
+        (&args.filters, args.exact, &args.patterns, &args.skip),
+        args.timeout,
+        args.seed,
+        args.bench,
+        args.leak_check,
+        args.isolated_cwd,
+        args.strict.then_some(&args.strict_checks),
+        args.runs_in_processes(),
+        &args.snapshot_dir,
+        &args.profile,
+    );
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", options).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes the contents of `path`. The standard library's hasher may change
+/// between Rust releases, which only runs the tests again.
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buffer[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    fn def(name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            "app::parser",
+            name,
+            "src/lib.rs",
+            1,
+        )))
+    }
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().copied()).unwrap()
+    }
+
+    fn cached(binary: u64, coverage: CoverageMap) -> Cached {
+        Cached {
+            path: PathBuf::new(),
+            passes: Passes::default(),
+            binary,
+            options: hash_options(&Args::default()),
+            coverage,
+        }
+    }
+
+    #[test]
+    fn fingerprints_the_binary_and_the_files_covered() {
+        let dir = std::env::temp_dir().join(format!("tust-passes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("lexer.rs");
+        fs::write(&source, "fn lex() {}").unwrap();
+        let (covered, other) = (def("covered"), def("other"));
+        let mut coverage = CoverageMap::default();
+        coverage.record(covered, BTreeSet::from([source.clone()]));
+
+        let before = cached(1, coverage.clone());
+        let rebuilt = cached(2, coverage.clone());
+        assert_eq!(
+            before.fingerprint(covered),
+            cached(1, coverage.clone()).fingerprint(covered)
+        );
+        assert_ne!(before.fingerprint(covered), before.fingerprint(other));
+        // Rebuilding the binary runs every test again, covered or not.
+        assert_ne!(before.fingerprint(covered), rebuilt.fingerprint(covered));
+        assert_ne!(before.fingerprint(other), rebuilt.fingerprint(other));
+
+        let fingerprint = before.fingerprint(covered);
+        fs::write(&source, "fn lex() { todo!() }").unwrap();
+        assert_ne!(before.fingerprint(covered), fingerprint);
+        assert_eq!(
+            before.fingerprint(other),
+            cached(1, coverage).fingerprint(other)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_the_options_selecting_tests() {
+        let options = hash_options(&args(&[]));
+        assert_eq!(hash_options(&args(&["--quiet", "--jobs", "4"])), options);
+        for changed in [
+            &["parser"][..],
+            &["parser", "--exact"],
+            &["--filter", "parser::*"],
+            &["--skip", "slow"],
+            &["--timeout", "5s"],
+            &["--seed", "7"],
+        ] {
+            assert_ne!(hash_options(&args(changed)), options, "{:?}", changed);
+        }
+        assert_ne!(
+            hash_options(&args(&["--skip", "slow"])),
+            hash_options(&args(&["--skip", "flaky"]))
+        );
+    }
+
+    #[test]
+    fn records_the_tests_that_passed_at_once() {
+        let path = std::env::temp_dir().join(format!("tust-passes-{}.json", std::process::id()));
+        let (passes, retried, fails, never_ran) = (
+            def("passes"),
+            def("retried"),
+            def("fails"),
+            def("never_ran"),
+        );
+        let outcome = |test, status, attempts| TestOutcome {
+            test,
+            status,
+            duration: std::time::Duration::ZERO,
+            output: String::new(),
+            attempts,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        };
+        let mut run = cached(1, CoverageMap::default());
+        run.path = path.clone();
+        run.passes
+            .tests
+            .insert(timings::key(fails), "stale".to_string());
+        run.passes
+            .tests
+            .insert(timings::key(never_ran), "kept".to_string());
+        let fingerprint = run.fingerprint(passes);
+        run.update(&[
+            outcome(passes, TestStatus::Passed, 1),
+            outcome(retried, TestStatus::Passed, 2),
+            outcome(fails, TestStatus::Failed(Vec::new()), 1),
+            outcome(never_ran, TestStatus::Passed, 0),
+        ]);
+
+        let recorded = Passes::read(&path).unwrap();
+        assert!(recorded.contains(passes, &fingerprint));
+        assert!(!recorded.contains(retried, &fingerprint));
+        assert!(!recorded.contains(fails, "stale"));
+        assert!(recorded.contains(never_ran, "kept"));
+        assert_eq!(recorded.tests.len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
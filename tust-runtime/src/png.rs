@@ -0,0 +1,465 @@
+//! Just enough of PNG to compare image snapshots: decoding the images
+//! common encoders write, and encoding the diff images of mismatches.
+//!
+//! Decoding takes non-interlaced images of every color type and bit depth,
+//! and converts them to 8-bit RGBA; 16-bit samples keep their high byte.
+
+use std::fmt;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// An image as 8-bit RGBA pixels, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) rgba: Vec<u8>,
+}
+
+impl Image {
+    /// The RGBA value of the pixel at `index`, counted row by row.
+    pub(crate) fn pixel(&self, index: usize) -> [u8; 4] {
+        let at = index * 4;
+        [
+            self.rgba[at],
+            self.rgba[at + 1],
+            self.rgba[at + 2],
+            self.rgba[at + 3],
+        ]
+    }
+}
+
+/// Why bytes could not be decoded as a PNG image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DecodeError(String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn error<T>(message: impl Into<String>) -> Result<T, DecodeError> {
+    Err(DecodeError(message.into()))
+}
+
+/// The header of an image, from its `IHDR` chunk.
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Bytes per complete pixel, at least 1, as the filters count them.
+    fn filter_unit(&self) -> usize {
+        (self.channels() * usize::from(self.bit_depth) / 8).max(1)
+    }
+
+    fn row_bytes(&self) -> usize {
+        (self.width as usize * self.channels() * usize::from(self.bit_depth) + 7) / 8
+    }
+}
+
+/// Decodes the PNG image in `bytes`.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Image, DecodeError> {
+    let Some(mut rest) = bytes.strip_prefix(SIGNATURE.as_slice()) else {
+        return error("it does not start with the PNG signature");
+    };
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut data = Vec::new();
+    loop {
+        if rest.len() < 12 {
+            return error("it ends before its IEND chunk");
+        }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind = &rest[4..8];
+        let Some(body) = rest.get(8..8 + length) else {
+            return error("a chunk runs past the end of the file");
+        };
+        rest = rest.get(12 + length..).unwrap_or_default();
+        match kind {
+            b"IHDR" => {
+                if body.len() != 13 {
+                    return error("its IHDR chunk is malformed");
+                }
+                let read = |at: usize| {
+                    u32::from_be_bytes([body[at], body[at + 1], body[at + 2], body[at + 3]])
+                };
+                if body[12] != 0 {
+                    return error("interlaced images are not supported");
+                }
+                let parsed = Header {
+                    width: read(0),
+                    height: read(4),
+                    bit_depth: body[8],
+                    color_type: body[9],
+                };
+                let valid = match parsed.color_type {
+                    0 => matches!(parsed.bit_depth, 1 | 2 | 4 | 8 | 16),
+                    3 => matches!(parsed.bit_depth, 1 | 2 | 4 | 8),
+                    2 | 4 | 6 => matches!(parsed.bit_depth, 8 | 16),
+                    _ => false,
+                };
+                if !valid {
+                    return error(format!(
+                        "color type {} with bit depth {} is not valid PNG",
+                        parsed.color_type, parsed.bit_depth
+                    ));
+                }
+                header = Some(parsed);
+            }
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let Some(header) = header else {
+        return error("it has no IHDR chunk");
+    };
+    let Ok(raw) = decompress_to_vec_zlib(&data) else {
+        return error("its image data is not valid zlib data");
+    };
+    let rows = unfilter(&header, &raw)?;
+    to_rgba(&header, &rows, palette, transparency)
+}
+
+/// Undoes the filters of the scanlines in `raw`, returning the rows without
+/// their filter bytes.
+fn unfilter(header: &Header, raw: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let row_bytes = header.row_bytes();
+    let unit = header.filter_unit();
+    let height = header.height as usize;
+    if raw.len() < (row_bytes + 1) * height {
+        return error("its image data is shorter than the image");
+    }
+    let mut rows = vec![0; row_bytes * height];
+    for y in 0..height {
+        let line = &raw[y * (row_bytes + 1)..(y + 1) * (row_bytes + 1)];
+        let (filter, line) = (line[0], &line[1..]);
+        let (done, current) = rows.split_at_mut(y * row_bytes);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * row_bytes..])
+        };
+        let current = &mut current[..row_bytes];
+        for x in 0..row_bytes {
+            let left = if x >= unit { current[x - unit] } else { 0 };
+            let up = previous.map_or(0, |previous| previous[x]);
+            let up_left = match previous {
+                Some(previous) if x >= unit => previous[x - unit],
+                _ => 0,
+            };
+            current[x] = match filter {
+                0 => line[x],
+                1 => line[x].wrapping_add(left),
+                2 => line[x].wrapping_add(up),
+                3 => line[x].wrapping_add(((u16::from(left) + u16::from(up)) / 2) as u8),
+                4 => line[x].wrapping_add(paeth(left, up, up_left)),
+                _ => return error(format!("row {} has the unknown filter type {}", y, filter)),
+            };
+        }
+    }
+    Ok(rows)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |value: u8| (estimate - i16::from(value)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn to_rgba(
+    header: &Header,
+    rows: &[u8],
+    palette: &[u8],
+    transparency: &[u8],
+) -> Result<Image, DecodeError> {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let row_bytes = header.row_bytes();
+    let depth = usize::from(header.bit_depth);
+    let channels = header.channels();
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = &rows[y * row_bytes..(y + 1) * row_bytes];
+        // The `index`th sample of the row, as stored.
+        let raw = |index: usize| -> u16 {
+            match depth {
+                16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+                8 => u16::from(row[index]),
+                _ => {
+                    let bit = index * depth;
+                    let shift = 8 - depth - bit % 8;
+                    u16::from((row[bit / 8] >> shift) & ((1 << depth) - 1) as u8)
+                }
+            }
+        };
+        // The `index`th sample of the row, scaled to 8 bits.
+        let sample = |index: usize| -> u8 {
+            match depth {
+                16 => (raw(index) >> 8) as u8,
+                8 => raw(index) as u8,
+                _ => (raw(index) * 255 / ((1 << depth) - 1)) as u8,
+            }
+        };
+        for x in 0..width {
+            let first = x * channels;
+            let pixel = match header.color_type {
+                0 => {
+                    let gray = sample(first);
+                    let key = (transparency.len() >= 2)
+                        .then(|| u16::from_be_bytes([transparency[0], transparency[1]]));
+                    let alpha = if key == Some(raw(first)) { 0 } else { 255 };
+                    [gray, gray, gray, alpha]
+                }
+                2 => {
+                    let key = (transparency.len() >= 6).then(|| {
+                        [0, 2, 4]
+                            .map(|at| u16::from_be_bytes([transparency[at], transparency[at + 1]]))
+                    });
+                    let stored = [raw(first), raw(first + 1), raw(first + 2)];
+                    let alpha = if key == Some(stored) { 0 } else { 255 };
+                    [sample(first), sample(first + 1), sample(first + 2), alpha]
+                }
+                3 => {
+                    let index = raw(first) as usize;
+                    let Some(color) = palette.get(index * 3..index * 3 + 3) else {
+                        return error(format!("a pixel uses the missing palette entry {}", index));
+                    };
+                    let alpha = transparency.get(index).copied().unwrap_or(255);
+                    [color[0], color[1], color[2], alpha]
+                }
+                4 => {
+                    let gray = sample(first);
+                    [gray, gray, gray, sample(first + 1)]
+                }
+                _ => [
+                    sample(first),
+                    sample(first + 1),
+                    sample(first + 2),
+                    sample(first + 3),
+                ],
+            };
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        rgba,
+    })
+}
+
+/// Encodes `image` as an 8-bit RGBA PNG.
+pub(crate) fn encode(image: &Image) -> Vec<u8> {
+    let row_bytes = image.width as usize * 4;
+    let mut raw = Vec::with_capacity((row_bytes + 1) * image.height as usize);
+    for row in image.rgba.chunks(row_bytes.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &compress_to_vec_zlib(&raw, 6));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(kind.iter().chain(data));
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 PNG checks chunks with.
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PNG of `header`, an `IHDR` body, and the filtered scanlines `raw`,
+    /// with the chunks of `extra` before the image data, which is split in
+    /// two chunks as some encoders do.
+    fn png(header: [u8; 13], extra: &[(&[u8; 4], &[u8])], raw: &[u8]) -> Vec<u8> {
+        let data = compress_to_vec_zlib(raw, 9);
+        let (first, second) = data.split_at(data.len() / 2);
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        for (kind, body) in extra {
+            write_chunk(&mut png, kind, body);
+        }
+        write_chunk(&mut png, b"IDAT", first);
+        write_chunk(&mut png, b"IDAT", second);
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> [u8; 13] {
+        let mut header = [0; 13];
+        header[..4].copy_from_slice(&width.to_be_bytes());
+        header[4..8].copy_from_slice(&height.to_be_bytes());
+        header[8] = bit_depth;
+        header[9] = color_type;
+        header
+    }
+
+    #[test]
+    fn encoded_images_decode_to_themselves() {
+        let image = Image {
+            width: 3,
+            height: 2,
+            rgba: (0..24).map(|byte| byte * 10).collect(),
+        };
+        let encoded = encode(&image);
+        assert!(encoded.starts_with(SIGNATURE));
+        assert_eq!(decode(&encoded), Ok(image));
+        let empty = Image {
+            width: 0,
+            height: 0,
+            rgba: Vec::new(),
+        };
+        assert_eq!(decode(&encode(&empty)), Ok(empty));
+    }
+
+    #[test]
+    fn chunks_carry_the_crc_of_png() {
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+        let encoded = encode(&Image {
+            width: 0,
+            height: 0,
+            rgba: Vec::new(),
+        });
+        assert!(encoded.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+    }
+
+    #[test]
+    fn every_filter_is_undone() {
+        // Three gray rows with the Sub, Up, Average, and Paeth filters, of
+        // the values 10, 20, 30 and 40, 50, 60 and 70, 80, 90.
+        let raw = [
+            1,
+            10,
+            10,
+            10, //
+            2,
+            30,
+            30,
+            30, //
+            3,
+            70 - 20,
+            80 - 60,
+            90 - 70, //
+            4,
+            0,
+            0,
+            0,
+        ];
+        let decoded = decode(&png(header(3, 4, 8, 0), &[], &raw)).unwrap();
+        let grays: Vec<u8> = (0..12).map(|index| decoded.pixel(index)[0]).collect();
+        assert_eq!(grays, [10, 20, 30, 40, 50, 60, 70, 80, 90, 70, 80, 90]);
+    }
+
+    #[test]
+    fn palettes_and_transparency_become_rgba() {
+        let palette: &[u8] = &[255, 0, 0, 0, 0, 255];
+        // A 1-bit row of indexes 0, 1, 1, 0, with entry 1 transparent.
+        let raw = [0, 0b0110_0000];
+        let extra: &[(&[u8; 4], &[u8])] = &[(b"PLTE", palette), (b"tRNS", &[255, 0])];
+        let decoded = decode(&png(header(4, 1, 1, 3), extra, &raw)).unwrap();
+        assert_eq!(decoded.pixel(0), [255, 0, 0, 255]);
+        assert_eq!(decoded.pixel(1), [0, 0, 255, 0]);
+        assert_eq!(decoded.pixel(3), [255, 0, 0, 255]);
+
+        // 16-bit gray with alpha keeps the high bytes.
+        let raw = [0, 0x12, 0x34, 0x80, 0x00];
+        let decoded = decode(&png(header(1, 1, 16, 4), &[], &raw)).unwrap();
+        assert_eq!(decoded.pixel(0), [0x12, 0x12, 0x12, 0x80]);
+
+        // 2-bit gray scales to 8 bits, with a transparent key.
+        let raw = [0, 0b1101_0000];
+        let extra: &[(&[u8; 4], &[u8])] = &[(b"tRNS", &[0, 1])];
+        let decoded = decode(&png(header(2, 1, 2, 0), extra, &raw)).unwrap();
+        assert_eq!(decoded.pixel(0), [255, 255, 255, 255]);
+        assert_eq!(decoded.pixel(1), [85, 85, 85, 0]);
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        let error = |bytes: &[u8]| decode(bytes).unwrap_err().to_string();
+        assert_eq!(error(b"GIF89a"), "it does not start with the PNG signature");
+        let mut truncated = encode(&Image {
+            width: 1,
+            height: 1,
+            rgba: vec![0; 4],
+        });
+        truncated.truncate(truncated.len() - 12);
+        assert_eq!(error(&truncated), "it ends before its IEND chunk");
+        let mut interlaced = header(1, 1, 8, 0);
+        interlaced[12] = 1;
+        assert_eq!(
+            error(&png(interlaced, &[], &[0, 0])),
+            "interlaced images are not supported"
+        );
+        assert_eq!(
+            error(&png(header(1, 1, 16, 3), &[], &[0, 0, 0])),
+            "color type 3 with bit depth 16 is not valid PNG"
+        );
+        assert_eq!(
+            error(&png(header(1, 2, 8, 0), &[], &[0, 0])),
+            "its image data is shorter than the image"
+        );
+        assert_eq!(
+            error(&png(header(1, 1, 8, 0), &[], &[9, 0])),
+            "row 0 has the unknown filter type 9"
+        );
+        assert_eq!(
+            error(&png(header(1, 1, 8, 3), &[], &[0, 2])),
+            "a pixel uses the missing palette entry 2"
+        );
+    }
+}
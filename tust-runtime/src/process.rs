@@ -0,0 +1,539 @@
+//! Process-per-test isolation, enabled with `--process-per-test`.
+//!
+//! The runner re-executes its own binary once per test, naming the test to
+//! run in [`CHILD_TEST_ENV`]. The child runs that single test and writes its
+//! failures as JSON to the file named in [`CHILD_RESULT_ENV`]; a child that
+//! exits without writing the file crashed, and the test fails with its exit
+//! code or signal. The child's output is captured into
+//! [`TestOutcome::output`].
+//!
+//! Each child creates its own session fixtures and drops them when the test
+//! finishes, and runs the `before_all` and `after_all` hooks of the test's
+//! modules around it. Database migrations still run once per run; see
+//! [`db`](crate::db).
+//!
+//! On Unix, the [`Limits`] of a test set by `#[limits(...)]` become resource
+//! limits of its child: `memory` of its address space and `cpu_time` of the
+//! processor time it uses. A child that fails to allocate past its memory
+//! limit aborts, and one past its processor time is stopped with `SIGXCPU`;
+//! either fails the test as exceeding that limit, rather than leaving the
+//! machine to reclaim the memory by killing other processes.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitCode, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::alloc;
+use crate::bench;
+use crate::context;
+use crate::coverage;
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+use crate::db;
+use crate::fixture;
+use crate::hooks::Scopes;
+use crate::interrupt;
+use crate::outcome::{
+    self, FailureDiff, FailureKind, Location, TestFailure, TestOutcome, TestStatus,
+};
+use crate::panic;
+use crate::registry::{self, Limits, TestDef};
+use crate::rng;
+use crate::runner;
+use crate::shared;
+use crate::strict;
+
+/// Path of the test a child process runs.
+const CHILD_TEST_ENV: &str = "__TUST_CHILD_TEST";
+/// File a child process writes its result to.
+const CHILD_RESULT_ENV: &str = "__TUST_CHILD_RESULT";
+/// How often a child with a time limit is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The ids of the running children.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Runs `test` in a new process running the current binary, killing the
+/// process if it runs longer than `timeout`.
+pub(crate) fn run_in_process(test: &'static TestDef, timeout: Option<Duration>) -> TestOutcome {
+    run_child_process(test, timeout, true)
+}
+
+/// Like [`run_in_process`], but lets the process print to the runner's
+/// stdout and stderr.
+pub(crate) fn run_in_process_uncaptured(
+    test: &'static TestDef,
+    timeout: Option<Duration>,
+) -> TestOutcome {
+    run_child_process(test, timeout, false)
+}
+
+fn run_child_process(
+    test: &'static TestDef,
+    timeout: Option<Duration>,
+    capture: bool,
+) -> TestOutcome {
+    let start = Instant::now();
+    let (status, output) = match spawn(test, timeout, capture) {
+        Ok(result) => result,
+        Err(message) => (
+            TestStatus::Failed(vec![TestFailure::new(message)]),
+            String::new(),
+        ),
+    };
+    TestOutcome {
+        test,
+        status,
+        duration: start.elapsed(),
+        output,
+        attempts: 1,
+        attachments: context::take_attachments(test),
+        steps: context::take_steps(test),
+    }
+}
+
+/// Runs `test` in a child process, returning its status and, if `capture`
+/// is set, its output.
+fn spawn(
+    test: &'static TestDef,
+    timeout: Option<Duration>,
+    capture: bool,
+) -> Result<(TestStatus, String), String> {
+    static NEXT_RESULT: AtomicUsize = AtomicUsize::new(0);
+
+    let exe = std::env::current_exe()
+        .map_err(|err| format!("cannot locate the test binary to re-run: {}", err))?;
+    let result_path = std::env::temp_dir().join(format!(
+        "tust-{}-{}.json",
+        id(),
+        NEXT_RESULT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let output = || {
+        if capture {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        }
+    };
+    let mut command = Command::new(exe);
+    #[cfg(unix)]
+    set_limits(&mut command, test.limits);
+    if coverage::is_recording() {
+        if let Some(dir) = coverage::profile_dir(test) {
+            // Only the profiles of the last attempt count.
+            let _ = fs::remove_dir_all(&dir);
+            command.env("LLVM_PROFILE_FILE", dir.join("%p-%m.profraw"));
+        }
+    }
+    #[cfg(any(feature = "sqlx", feature = "diesel"))]
+    command.env(db::MIGRATIONS_ENV, db::migrations_dir());
+    let mut child = command
+        .env(CHILD_TEST_ENV, test.path())
+        .env(CHILD_RESULT_ENV, &result_path)
+        .env(rng::SEED_ENV, rng::run_seed().to_string())
+        .stdin(Stdio::null())
+        .stdout(output())
+        .stderr(output())
+        .spawn()
+        .map_err(|err| format!("failed to start the test process: {}", err))?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let id = child.id();
+    alloc::untracked(|| CHILDREN.lock().unwrap_or_else(|e| e.into_inner()).push(id));
+    let status = wait(&mut child, timeout);
+    alloc::untracked(|| {
+        CHILDREN
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|&child| child != id)
+    });
+    let status = status.map_err(|err| format!("failed to wait for the test process: {}", err))?;
+    let captured = captured_output(&join(stdout), &join(stderr));
+
+    let result = fs::read_to_string(&result_path);
+    let _ = fs::remove_file(&result_path);
+    let failures = match (status, result) {
+        (None, _) => vec![TestFailure::new(format!(
+            "test timed out after {}; its process was killed",
+            runner::format_duration(timeout.unwrap_or_default())
+        ))],
+        (Some(_), Ok(result)) => parse_result(test, &result).ok_or_else(|| {
+            format!(
+                "the test process wrote a malformed result to {}",
+                result_path.display()
+            )
+        })?,
+        (Some(status), Err(_)) => vec![match exceeded_limit(test.limits, status, &captured) {
+            Some(message) => TestFailure::new(message),
+            None => TestFailure::new(format!("the test process {}", describe_exit(status))),
+        }],
+    };
+
+    Ok((outcome::status_of(test, failures), captured))
+}
+
+/// Makes `limits` the resource limits of the process `command` starts.
+#[cfg(unix)]
+fn set_limits(command: &mut Command, limits: Limits) {
+    use std::os::unix::process::CommandExt;
+
+    if limits.is_none() {
+        return;
+    }
+    let limit = |resource, soft: u64, hard: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        // SAFETY: `setrlimit` only reads `limit`, which outlives the call.
+        if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    // SAFETY: the closure runs in the forked child before it executes the
+    // binary, and only calls `setrlimit`, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = limits.memory {
+                limit(libc::RLIMIT_AS, bytes, bytes)?;
+            }
+            if let Some(time) = limits.cpu_time {
+                // Past the soft limit the child gets `SIGXCPU`; the hard
+                // limit kills it if it ignores that.
+                let secs = time.as_secs();
+                limit(libc::RLIMIT_CPU, secs, secs + 1)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Describes how the child exceeded one of its `limits`, if its exit shows
+/// it did.
+fn exceeded_limit(limits: Limits, status: ExitStatus, output: &str) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        let signal = status.signal()?;
+        if let Some(time) = limits.cpu_time {
+            if signal == libc::SIGXCPU || signal == libc::SIGKILL {
+                return Some(format!(
+                    "CPU time limit of {} exceeded; the test process was stopped",
+                    runner::format_duration(time)
+                ));
+            }
+        }
+        // Failing to allocate aborts, after the allocator says so.
+        if let Some(bytes) = limits.memory {
+            if signal == libc::SIGABRT && output.contains("memory allocation of") {
+                return Some(format!(
+                    "memory limit of {} exceeded; the test process was stopped",
+                    format_bytes(bytes)
+                ));
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (limits, status, output);
+    None
+}
+
+/// Formats `bytes` in the largest binary unit dividing it.
+fn format_bytes(bytes: u64) -> String {
+    for (shift, unit) in [(30, "GiB"), (20, "MiB"), (10, "KiB")] {
+        if bytes % (1 << shift) == 0 {
+            return format!("{} {}", bytes >> shift, unit);
+        }
+    }
+    format!("{} bytes", bytes)
+}
+
+/// The id of this process, or 0 on WebAssembly, which has no processes.
+pub(crate) fn id() -> u32 {
+    if cfg!(target_family = "wasm") {
+        0
+    } else {
+        std::process::id()
+    }
+}
+
+/// Kills the running children, whose tests the run is abandoning.
+pub(crate) fn kill_children() {
+    #[cfg(unix)]
+    for &id in CHILDREN.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        // SAFETY: `kill` has no memory-safety preconditions.
+        unsafe {
+            libc::kill(id as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+/// Waits for `child` to exit, killing it once `timeout` has passed. Returns
+/// `None` if it was killed.
+pub(crate) fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let Some(limit) = timeout else {
+        return child.wait().map(Some);
+    };
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            // The child may exit between the check and the kill.
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+pub(crate) fn read_in_background(
+    pipe: Option<impl Read + Send + 'static>,
+) -> Option<JoinHandle<Vec<u8>>> {
+    let mut pipe = pipe?;
+    Some(thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    }))
+}
+
+pub(crate) fn join(reader: Option<JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default()
+}
+
+/// If this process was started by [`run_in_process`], runs the requested
+/// test and returns the exit code to exit with.
+pub(crate) fn run_child() -> Option<ExitCode> {
+    let path = std::env::var(CHILD_TEST_ENV).ok()?;
+    let result_path = PathBuf::from(std::env::var_os(CHILD_RESULT_ENV)?);
+
+    let Some(test) = registry::tests()
+        .into_iter()
+        .find(|test| test.path() == path)
+    else {
+        eprintln!("error: no test named `{}`", path);
+        return Some(ExitCode::from(runner::EXIT_USAGE));
+    };
+    // Ctrl-C reaches the children too; each finishes its test, unless the
+    // runner kills it first.
+    interrupt::install();
+    // Run on a thread named after the test, as the in-process runner does,
+    // after the `before_all` hooks of its modules, which the runner leaves
+    // to its children so that the test sees what they set up.
+    let outcome = thread::Builder::new()
+        .name(path)
+        .spawn(move || {
+            runner::run_in_scopes(test, &Scopes::new(&[test]), || runner::run_test(test))
+        })
+        .map(|handle| handle.join());
+    let outcome = match outcome {
+        Ok(Ok(outcome)) => outcome,
+        _ => return Some(ExitCode::FAILURE),
+    };
+    if let Err(failure) = panic::catch(fixture::teardown_session) {
+        eprintln!("warning: session fixture teardown panicked: {}", failure);
+    }
+
+    match write_result(&result_path, &outcome) {
+        Ok(()) => Some(ExitCode::SUCCESS),
+        Err(err) => {
+            eprintln!("error: cannot write {}: {}", result_path.display(), err);
+            Some(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn write_result(path: &Path, outcome: &TestOutcome) -> io::Result<()> {
+    let failures: Vec<Value> = match &outcome.status {
+        TestStatus::Failed(failures) => failures
+            .iter()
+            .map(|failure| {
+                json!({
+                    "message": failure.message,
+                    "location": failure.location.as_ref().map(|location| json!({
+                        "file": location.file,
+                        "line": location.line,
+                        "column": location.column,
+                    })),
+                    "diff": failure.diff.as_ref().map(|diff| json!({
+                        "labels": [diff.labels.0, diff.labels.1],
+                        "left": diff.left,
+                        "right": diff.right,
+                    })),
+                    "metadata": failure.metadata,
+                    "backtrace": failure.backtrace,
+                    "panicked": failure.panicked,
+                    "kind": failure.kind.name(),
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let result = json!({
+        "failures": failures,
+        "random": rng::was_used(),
+        "benchmarks": bench::to_json(&bench::take_results()),
+        "strict": strict::to_json(&strict::take_warnings()),
+        "shared_data": shared::to_json(&shared::take_loads()),
+        "attachments": context::to_json(&outcome.attachments),
+        "steps": context::steps_to_json(&outcome.steps),
+        "skipped": match &outcome.status {
+            TestStatus::Skipped(reason) if outcome.is_skipped_at_run_time() => json!(reason),
+            _ => Value::Null,
+        },
+    });
+    fs::write(path, result.to_string())
+}
+
+/// Parses the failures a child process wrote, noting whether its test asked
+/// for randomness and recording the benchmarks it measured, the warnings of
+/// strict mode, the shared data it loaded, the files it attached, the steps
+/// it ran, and whether it skipped itself.
+fn parse_result(test: &'static TestDef, result: &str) -> Option<Vec<TestFailure>> {
+    let result: Value = serde_json::from_str(result).ok()?;
+    if result["random"].as_bool() == Some(true) {
+        rng::mark_used(test);
+    }
+    bench::record_json(test, &result["benchmarks"]);
+    strict::record_json(test, &result["strict"]);
+    shared::record_json(test, &result["shared_data"]);
+    context::record_json(test, &result["attachments"]);
+    context::record_steps_json(test, &result["steps"]);
+    if let Some(reason) = result["skipped"].as_str() {
+        outcome::request_skip(test, reason.to_string());
+    }
+    result["failures"]
+        .as_array()?
+        .iter()
+        .map(|failure| {
+            let location = match &failure["location"] {
+                Value::Null => None,
+                location => Some(Location {
+                    file: location["file"].as_str()?.to_string(),
+                    line: u32::try_from(location["line"].as_u64()?).ok()?,
+                    column: u32::try_from(location["column"].as_u64()?).ok()?,
+                }),
+            };
+            let diff = match &failure["diff"] {
+                Value::Null => None,
+                diff => Some(Box::new(FailureDiff {
+                    labels: (
+                        diff["labels"][0].as_str()?.to_string(),
+                        diff["labels"][1].as_str()?.to_string(),
+                    ),
+                    left: diff["left"].as_str()?.to_string(),
+                    right: diff["right"].as_str()?.to_string(),
+                })),
+            };
+            let metadata = match &failure["metadata"] {
+                Value::Array(metadata) => metadata
+                    .iter()
+                    .map(|entry| {
+                        Some((
+                            entry[0].as_str()?.to_string(),
+                            entry[1].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect::<Option<_>>()?,
+                _ => Vec::new(),
+            };
+            Some(TestFailure {
+                message: failure["message"].as_str()?.to_string(),
+                diff,
+                location,
+                metadata,
+                backtrace: failure["backtrace"].as_str().map(str::to_string),
+                panicked: failure["panicked"].as_bool() == Some(true),
+                kind: failure["kind"]
+                    .as_str()
+                    .and_then(FailureKind::from_name)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn captured_output(stdout: &[u8], stderr: &[u8]) -> String {
+    let mut captured = String::from_utf8_lossy(stdout).into_owned();
+    captured.push_str(&String::from_utf8_lossy(stderr));
+    captured.trim_matches('\n').trim_end().to_string()
+}
+
+fn describe_exit(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return match signal_name(signal) {
+                Some(name) => format!("was terminated by signal {} ({})", signal, name),
+                None => format!("was terminated by signal {}", signal),
+            };
+        }
+    }
+    match status.code() {
+        Some(code) => format!("exited with code {} before reporting a result", code),
+        None => format!("exited before reporting a result ({})", status),
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        libc::SIGILL => "SIGILL",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_stdout_then_stderr_without_blank_lines() {
+        assert_eq!(captured_output(b"\nout\n", b"err\n\n"), "out\nerr");
+        assert_eq!(captured_output(b"", b""), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn describes_how_a_child_exited() {
+        use std::os::unix::process::ExitStatusExt;
+
+        assert_eq!(
+            describe_exit(ExitStatus::from_raw(libc::SIGABRT)),
+            "was terminated by signal 6 (SIGABRT)"
+        );
+        assert_eq!(
+            describe_exit(ExitStatus::from_raw(libc::SIGUSR1)),
+            format!("was terminated by signal {}", libc::SIGUSR1)
+        );
+        assert_eq!(
+            describe_exit(ExitStatus::from_raw(3 << 8)),
+            "exited with code 3 before reporting a result"
+        );
+    }
+}
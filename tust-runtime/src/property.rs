@@ -0,0 +1,594 @@
+//! Property-based testing behind `#[tust::property]`.
+//!
+//! A property is a test whose parameters are generated rather than injected:
+//! each case draws fresh inputs from their [`Arbitrary`] implementations and
+//! runs the body. The first failing case is shrunk, by repeatedly trying the
+//! simpler inputs [`Arbitrary::shrink`] proposes, to a minimal
+//! counterexample, which is reported together with the seed that reproduces
+//! it.
+//!
+//! Inputs are generated from a single seed, derived from the run seed unless
+//! the property sets one, so a failure is reproduced exactly by the run's
+//! `--seed` or by setting [`SEED_ENV`] to the reported seed. [`CASES_ENV`]
+//! changes the number of cases for properties that do not set one.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::outcome::TestFailure;
+use crate::panic;
+use crate::rng::{self, Rng};
+use crate::soft;
+
+/// Environment variable fixing the seed of every property.
+pub const SEED_ENV: &str = "TUST_PROPERTY_SEED";
+
+/// Environment variable setting the number of cases of properties that do not
+/// set `cases` themselves.
+pub const CASES_ENV: &str = "TUST_PROPERTY_CASES";
+
+/// Number of cases run when neither the property nor [`CASES_ENV`] sets one.
+pub const DEFAULT_CASES: u32 = 100;
+
+/// Upper bound on the inputs tried while shrinking a counterexample.
+const MAX_SHRINK_STEPS: u32 = 10_000;
+
+/// The largest size inputs are generated with; see [`Gen::size`].
+const MAX_SIZE: usize = 100;
+
+/// The size of the values `#[derive(TestBuilder)]` builders give the fields
+/// a test does not set, small enough to read in a failure message.
+const BUILDER_SIZE: usize = 10;
+
+/// Types whose values a property can be checked against.
+pub trait Arbitrary: Clone + fmt::Debug + 'static {
+    /// Generates a value, typically no larger than [`Gen::size`].
+    fn arbitrary(g: &mut Gen) -> Self;
+
+    /// Simpler values than `self`, simplest first. Shrinking tries them in
+    /// order and continues from the first one that still fails, so every
+    /// value must be strictly simpler than `self`.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Source of randomness for [`Arbitrary::arbitrary`], deterministic for a
+/// given seed. Dereferences to the underlying [`Rng`].
+#[derive(Debug, Clone)]
+pub struct Gen {
+    rng: Rng,
+    size: usize,
+}
+
+impl Gen {
+    pub fn new(seed: u64) -> Self {
+        Gen {
+            rng: Rng::new(seed),
+            size: MAX_SIZE,
+        }
+    }
+
+    /// A generator driven by `bytes`, as a fuzzer supplies them: the same
+    /// bytes always give the same values, and fewer bytes smaller ones.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        // FNV-1a, so that every byte changes the seed.
+        let seed = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        Gen {
+            rng: Rng::new(seed),
+            size: bytes.len().min(MAX_SIZE),
+        }
+    }
+
+    /// How large generated values should be: collections hold at most this
+    /// many elements and numbers mostly stay within it. Grows over the cases
+    /// of a property, so early cases try small inputs.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Deref for Gen {
+    type Target = Rng;
+
+    fn deref(&self) -> &Rng {
+        &self.rng
+    }
+}
+
+impl DerefMut for Gen {
+    fn deref_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+}
+
+/// A generator for the fields a `#[derive(TestBuilder)]` builder was not
+/// given, seeded from the current test's [`rng`](rng::rng) so that it gives
+/// the same values in every run with the same seed.
+pub fn builder_gen() -> Gen {
+    Gen {
+        rng: Rng::new(rng::rng().next_u64()),
+        size: BUILDER_SIZE,
+    }
+}
+
+/// Settings of one `#[tust::property]`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PropertyConfig {
+    /// `cases = N`
+    pub cases: Option<u32>,
+    /// `seed = N`
+    pub seed: Option<u64>,
+}
+
+/// Checks `property` against generated inputs, recording a failure for the
+/// current test with the minimal counterexample found.
+///
+/// `describe` names and formats the inputs of a counterexample.
+#[track_caller]
+pub fn check<T: Arbitrary>(
+    config: &PropertyConfig,
+    property: impl Fn(T),
+    describe: impl Fn(&T) -> Vec<(&'static str, String)>,
+) {
+    let seed = config
+        .seed
+        .or_else(|| env_number(SEED_ENV))
+        .unwrap_or_else(|| rng::rng().next_u64());
+    let cases = config
+        .cases
+        .or_else(|| env_number(CASES_ENV))
+        .unwrap_or(DEFAULT_CASES);
+
+    let mut g = Gen::new(seed);
+    for case in 0..cases {
+        g.size = size_for(case, cases);
+        let input = T::arbitrary(&mut g);
+        if let Some(failure) = run_case(&property, input.clone()) {
+            let (input, failure, shrinks) = shrink(&property, input, failure);
+            soft::record(counterexample(
+                seed,
+                case,
+                &describe(&input),
+                shrinks,
+                failure,
+            ));
+            return;
+        }
+    }
+}
+
+/// Runs one case, returning its first failure.
+pub(crate) fn run_case<T>(property: &impl Fn(T), input: T) -> Option<TestFailure> {
+    let (result, failures) = soft::collect(|| panic::catch(|| property(input)));
+    result.err().or_else(|| failures.into_iter().next())
+}
+
+/// Shrinks a failing `input` greedily, returning the simplest failing input
+/// found, its failure, and how many times it was shrunk.
+pub(crate) fn shrink<T: Arbitrary>(
+    property: &impl Fn(T),
+    mut input: T,
+    mut failure: TestFailure,
+) -> (T, TestFailure, u32) {
+    let mut shrinks = 0;
+    let mut steps = 0;
+    'simpler: loop {
+        for candidate in input.shrink() {
+            steps += 1;
+            if steps > MAX_SHRINK_STEPS {
+                break 'simpler;
+            }
+            if let Some(candidate_failure) = run_case(property, candidate.clone()) {
+                input = candidate;
+                failure = candidate_failure;
+                shrinks += 1;
+                continue 'simpler;
+            }
+        }
+        break;
+    }
+    (input, failure, shrinks)
+}
+
+fn counterexample(
+    seed: u64,
+    case: u32,
+    inputs: &[(&'static str, String)],
+    shrinks: u32,
+    failure: TestFailure,
+) -> TestFailure {
+    let mut message = format!(
+        "property failed after {} passing {}; minimal counterexample",
+        case,
+        if case == 1 { "case" } else { "cases" }
+    );
+    if shrinks > 0 {
+        message.push_str(&format!(
+            " (shrunk {} {})",
+            shrinks,
+            if shrinks == 1 { "time" } else { "times" }
+        ));
+    }
+    message.push(':');
+    for (name, value) in inputs {
+        message.push_str(&format!(
+            "\n    {} = {}",
+            name,
+            value.replace('\n', "\n    ")
+        ));
+    }
+    message.push_str(&format!(
+        "\n{}\nreproduce with {}={}",
+        failure.message, SEED_ENV, seed
+    ));
+    TestFailure { message, ..failure }
+}
+
+/// Sizes grow linearly from 0 to [`MAX_SIZE`] over the cases.
+fn size_for(case: u32, cases: u32) -> usize {
+    if cases <= 1 {
+        return MAX_SIZE;
+    }
+    (case as usize * MAX_SIZE) / (cases as usize - 1)
+}
+
+fn env_number<N: std::str::FromStr>(name: &str) -> Option<N> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            eprintln!(
+                "warning: ignoring {}={:?}, which is not a number",
+                name, value
+            );
+            None
+        }
+    }
+}
+
+impl Arbitrary for bool {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.one_in(2)
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.then_some(false).into_iter())
+    }
+}
+
+/// Candidates between 0 and `x`, closest to 0 first.
+fn shrink_toward_zero(x: i128) -> Vec<i128> {
+    if x == 0 {
+        return Vec::new();
+    }
+    let mut candidates = vec![0];
+    if x < 0 {
+        candidates.push(-x);
+    }
+    let mut delta = x / 2;
+    while delta != 0 {
+        let candidate = x - delta;
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+        delta /= 2;
+    }
+    candidates
+}
+
+macro_rules! arbitrary_int {
+    ($($ty:ty),*) => {$(
+        impl Arbitrary for $ty {
+            fn arbitrary(g: &mut Gen) -> Self {
+                if g.one_in(10) {
+                    #[allow(unused_comparisons)]
+                    let edges: &[$ty] = if <$ty>::MIN < 0 {
+                        &[0, 1, <$ty>::MIN, <$ty>::MAX, (0 as $ty).wrapping_sub(1)]
+                    } else {
+                        &[0, 1, <$ty>::MAX]
+                    };
+                    return *g.choose(edges);
+                }
+                if g.one_in(10) {
+                    return g.next_u64() as $ty;
+                }
+                let size = g.size() as u64;
+                let magnitude = g.below(size + 1) as $ty;
+                #[allow(unused_comparisons)]
+                if <$ty>::MIN < 0 && g.one_in(2) {
+                    (0 as $ty).wrapping_sub(magnitude)
+                } else {
+                    magnitude
+                }
+            }
+
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                Box::new(
+                    shrink_toward_zero(*self as i128)
+                        .into_iter()
+                        .filter_map(|candidate| <$ty>::try_from(candidate).ok()),
+                )
+            }
+        }
+    )*};
+}
+
+arbitrary_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+macro_rules! arbitrary_float {
+    ($($ty:ty),*) => {$(
+        impl Arbitrary for $ty {
+            fn arbitrary(g: &mut Gen) -> Self {
+                if g.one_in(10) {
+                    return *g.choose(&[
+                        0.0,
+                        -0.0,
+                        1.0,
+                        -1.0,
+                        <$ty>::MIN_POSITIVE,
+                        <$ty>::MAX,
+                        <$ty>::MIN,
+                    ]);
+                }
+                let size = g.size() as $ty;
+                let unit = g.next_u64() as $ty / u64::MAX as $ty;
+                (unit * 2.0 - 1.0) * size
+            }
+
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                let x = *self;
+                let mut candidates = Vec::new();
+                if x != 0.0 && x.is_finite() {
+                    candidates.push(0.0);
+                    if x < 0.0 {
+                        candidates.push(-x);
+                    }
+                    if x.trunc() != x {
+                        candidates.push(x.trunc());
+                    }
+                    if x.abs() > 1.0 {
+                        candidates.push((x / 2.0).trunc());
+                    }
+                }
+                Box::new(candidates.into_iter())
+            }
+        }
+    )*};
+}
+
+arbitrary_float!(f32, f64);
+
+impl Arbitrary for char {
+    fn arbitrary(g: &mut Gen) -> Self {
+        if g.one_in(10) {
+            loop {
+                if let Some(c) = char::from_u32(g.below(0x11_0000) as u32) {
+                    return c;
+                }
+            }
+        }
+        // Printable ASCII.
+        (b' ' + g.below(95) as u8) as char
+    }
+
+    /// Shrinks toward `'a'`.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let offset = *self as i128 - 'a' as i128;
+        Box::new(
+            shrink_toward_zero(offset)
+                .into_iter()
+                .filter_map(|offset| u32::try_from(offset + 'a' as i128).ok())
+                .filter_map(char::from_u32),
+        )
+    }
+}
+
+impl Arbitrary for String {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<char>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let chars: Vec<char> = self.chars().collect();
+        Box::new(chars.shrink().map(|chars| chars.into_iter().collect()))
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Vec<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let size = g.size() as u64;
+        let len = g.below(size + 1);
+        (0..len).map(|_| T::arbitrary(g)).collect()
+    }
+
+    /// Tries removing ever smaller runs of elements, then shrinking single
+    /// elements.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let mut candidates = Vec::new();
+        if !self.is_empty() {
+            candidates.push(Vec::new());
+        }
+        let mut run = self.len() / 2;
+        while run > 0 {
+            let mut start = 0;
+            while start + run <= self.len() {
+                let mut shorter = self[..start].to_vec();
+                shorter.extend_from_slice(&self[start + run..]);
+                if !shorter.is_empty() {
+                    candidates.push(shorter);
+                }
+                start += run;
+            }
+            run /= 2;
+        }
+        let elements = self.clone();
+        let simpler_elements = (0..self.len()).flat_map(move |index| {
+            let elements = elements.clone();
+            elements[index].shrink().map(move |element| {
+                let mut simpler = elements.clone();
+                simpler[index] = element;
+                simpler
+            })
+        });
+        Box::new(candidates.into_iter().chain(simpler_elements))
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Option<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        if g.one_in(4) {
+            None
+        } else {
+            Some(T::arbitrary(g))
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        match self {
+            None => Box::new(std::iter::empty()),
+            Some(value) => Box::new(std::iter::once(None).chain(value.shrink().map(Some))),
+        }
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Box<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Box::new(T::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new((**self).shrink().map(Box::new))
+    }
+}
+
+macro_rules! arbitrary_tuple {
+    ($(($($name:ident $index:tt),+))*) => {$(
+        impl<$($name: Arbitrary),+> Arbitrary for ($($name,)+) {
+            fn arbitrary(g: &mut Gen) -> Self {
+                ($($name::arbitrary(g),)+)
+            }
+
+            /// Shrinks one element at a time, leftmost first.
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                let candidates = std::iter::empty::<Self>();
+                $(
+                    let tuple = self.clone();
+                    let candidates = candidates.chain(self.$index.shrink().map(move |element| {
+                        let mut simpler = tuple.clone();
+                        simpler.$index = element;
+                        simpler
+                    }));
+                )+
+                Box::new(candidates)
+            }
+        }
+    )*};
+}
+
+arbitrary_tuple! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: PropertyConfig = PropertyConfig {
+        cases: Some(100),
+        seed: Some(7),
+    };
+
+    /// Checks `property` with [`CONFIG`], returning the failure it records.
+    fn check_failure<T: Arbitrary>(property: impl Fn(T)) -> Option<TestFailure> {
+        let describe = |input: &T| vec![("input", format!("{:?}", input))];
+        let ((), failures) = soft::collect(|| check(&CONFIG, property, describe));
+        assert!(failures.len() <= 1);
+        failures.into_iter().next()
+    }
+
+    fn too_large(x: u32) {
+        assert!(x <= 10, "{} is too large", x);
+    }
+
+    #[test]
+    fn counterexamples_shrink_to_the_smallest_failing_input() {
+        let failure = check_failure(too_large).expect("the property fails");
+        let (head, tail) = failure.message.split_once(" (shrunk ").unwrap();
+        assert!(head.starts_with("property failed after "), "{}", head);
+        assert!(
+            tail.ends_with(
+                ":\n    input = 11\n11 is too large\nreproduce with TUST_PROPERTY_SEED=7"
+            ),
+            "{}",
+            tail
+        );
+
+        let failure = run_case(&too_large, 1000).unwrap();
+        let (input, failure, shrinks) = shrink(&too_large, 1000, failure);
+        assert_eq!((input, failure.message.as_str()), (11, "11 is too large"));
+        assert!(shrinks > 1);
+    }
+
+    #[test]
+    fn containers_and_negative_numbers_shrink_too() {
+        let has_large = |xs: Vec<u32>| assert!(xs.iter().all(|&x| x <= 10));
+        let failure = check_failure(has_large).expect("the property fails");
+        assert!(
+            failure.message.contains("\n    input = [11]\n"),
+            "{}",
+            failure.message
+        );
+
+        let too_small = |x: i32| assert!(x >= -10);
+        let failure = run_case(&too_small, -1000).unwrap();
+        assert_eq!(shrink(&too_small, -1000, failure).0, -11);
+
+        let both = |(a, b): (u8, bool)| assert!(a < 3 || !b);
+        let failure = run_case(&both, (200, true)).unwrap();
+        assert_eq!(shrink(&both, (200, true), failure).0, (3, true));
+    }
+
+    #[test]
+    fn soft_failures_fail_a_case() {
+        let recorded = |x: u8| {
+            if x > 10 {
+                soft::record(TestFailure::new(format!("{} is too large", x)));
+            }
+        };
+        let failure = check_failure(recorded).expect("the property fails");
+        assert!(failure
+            .message
+            .contains("\n    input = 11\n11 is too large\n"));
+    }
+
+    #[test]
+    fn passing_properties_record_nothing() {
+        assert!(check_failure(|x: u8| assert!(u32::from(x) <= 255)).is_none());
+    }
+
+    #[test]
+    fn a_seed_reproduces_its_counterexample() {
+        let first = check_failure(too_large).unwrap();
+        let second = check_failure(too_large).unwrap();
+        assert_eq!(first.message, second.message);
+    }
+
+    #[test]
+    fn numbers_shrink_toward_zero_by_halves() {
+        assert_eq!(shrink_toward_zero(0), Vec::<i128>::new());
+        assert_eq!(shrink_toward_zero(16), [0, 8, 12, 14, 15]);
+        assert_eq!(shrink_toward_zero(-4), [0, 4, -2, -3]);
+        assert_eq!(size_for(0, 100), 0);
+        assert_eq!(size_for(99, 100), MAX_SIZE);
+    }
+}
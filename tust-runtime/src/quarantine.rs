@@ -0,0 +1,356 @@
+//! Quarantined tests, which run but cannot fail the run.
+//!
+//! The `[quarantine]` table of `tust.toml` names them with globs, as
+//! `--filter` takes them:
+//!
+//! ```toml
+//! [quarantine]
+//! tests = ["db::replica::*", "net::reconnects_after_timeout"]
+//! stable-after = 10
+//! ```
+//!
+//! A quarantined test runs and is reported as usual, but its failure is
+//! counted as quarantined rather than failed: it does not fail the run, and
+//! does not count towards `--max-failures`. The console lists quarantined
+//! failures apart from the others, and the other reporters mark them.
+//!
+//! After every run, the runner records how many runs in a row each
+//! quarantined test passed without retries, in `tust/quarantine.json` in
+//! Cargo's target directory, or the file named by [`CACHE_ENV`]. The tests
+//! that passed each of their last `stable-after` runs, 10 unless set, are
+//! listed at the end of the run, so that they can leave the quarantine.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::filter::Glob;
+use crate::outcome::{TestOutcome, TestStatus};
+use crate::registry::TestDef;
+use crate::timings;
+
+/// Environment variable naming the file of passing streaks; if set but
+/// empty, streaks are neither read nor recorded.
+pub const CACHE_ENV: &str = "TUST_QUARANTINE";
+
+/// How many runs in a row a quarantined test passes before it is listed as
+/// stable, unless `stable-after` says otherwise.
+pub const DEFAULT_STABLE_AFTER: u32 = 10;
+
+/// The quarantine of the run.
+static ACTIVE: OnceLock<Quarantine> = OnceLock::new();
+
+/// The tests in quarantine, as read from the `[quarantine]` table.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    /// Globs matching the full names of the quarantined tests.
+    pub tests: Vec<Glob>,
+    /// Passing runs in a row after which a test is listed as stable.
+    pub stable_after: u32,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Quarantine {
+            tests: Vec::new(),
+            stable_after: DEFAULT_STABLE_AFTER,
+        }
+    }
+}
+
+impl Quarantine {
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty()
+    }
+
+    pub fn contains(&self, test: &TestDef) -> bool {
+        let path = test.path();
+        self.tests.iter().any(|glob| glob.matches(&path))
+    }
+}
+
+/// Puts the tests of `quarantine` in quarantine for the rest of the run.
+pub(crate) fn enable(quarantine: &Quarantine) {
+    if !quarantine.is_empty() {
+        let _ = ACTIVE.set(quarantine.clone());
+    }
+}
+
+/// Whether `test` is in quarantine in this run.
+pub fn is_quarantined(test: &TestDef) -> bool {
+    ACTIVE
+        .get()
+        .is_some_and(|quarantine| quarantine.contains(test))
+}
+
+/// Whether `outcome` is a failure of a quarantined test.
+pub fn is_quarantined_failure(outcome: &TestOutcome) -> bool {
+    outcome.status.is_failure() && is_quarantined(outcome.test)
+}
+
+/// How many runs in a row each quarantined test passed.
+#[derive(Debug, Clone, Default)]
+pub struct Streaks {
+    tests: BTreeMap<String, u32>,
+}
+
+impl Streaks {
+    /// Reads the streaks recorded in `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tests = value["passing"].as_object().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "expected a `passing` object")
+        })?;
+        Ok(Streaks {
+            tests: tests
+                .iter()
+                .filter_map(|(test, runs)| {
+                    Some((test.clone(), u32::try_from(runs.as_u64()?).ok()?))
+                })
+                .collect(),
+        })
+    }
+
+    /// Writes the streaks to `path`, creating its directory if needed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(&json!({ "passing": self.tests }))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        text.push('\n');
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    /// How many runs in a row `test` passed.
+    pub fn passing(&self, test: &TestDef) -> u32 {
+        self.tests.get(&timings::key(test)).copied().unwrap_or(0)
+    }
+
+    /// Extends the streaks of the tests of `outcomes` in `quarantine` that
+    /// passed, and ends those of the others that ran.
+    pub(crate) fn record(&mut self, quarantine: &Quarantine, outcomes: &[TestOutcome]) {
+        for outcome in outcomes.iter().filter(|outcome| outcome.attempts > 0) {
+            if !quarantine.contains(outcome.test) {
+                continue;
+            }
+            let runs = self.tests.entry(timings::key(outcome.test)).or_insert(0);
+            if outcome.status == TestStatus::Passed && outcome.attempts == 1 {
+                *runs += 1;
+            } else {
+                *runs = 0;
+            }
+        }
+    }
+}
+
+/// The streaks file: [`CACHE_ENV`] if set, else `tust/quarantine.json` in
+/// the target directory holding the running binary, if there is one.
+pub fn cache_path() -> Option<PathBuf> {
+    cache::path(CACHE_ENV, "quarantine.json")
+}
+
+/// Records the streaks of the quarantined tests of `outcomes`, returning the
+/// names of those that ran and have now passed long enough to be stable.
+pub(crate) fn update_cache(outcomes: &[TestOutcome]) -> Vec<String> {
+    let (Some(quarantine), Some(path)) = (ACTIVE.get(), cache_path()) else {
+        return Vec::new();
+    };
+    let mut streaks = Streaks::read(&path).unwrap_or_default();
+    streaks.record(quarantine, outcomes);
+    if let Err(err) = streaks.write(&path) {
+        eprintln!(
+            "warning: failed to record quarantined tests in {}: {}",
+            path.display(),
+            err
+        );
+    }
+    outcomes
+        .iter()
+        .filter(|outcome| outcome.attempts > 0 && quarantine.contains(outcome.test))
+        .filter(|outcome| streaks.passing(outcome.test) >= quarantine.stable_after)
+        .map(|outcome| outcome.test.path())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::outcome::{RunSummary, TestFailure};
+
+    fn def(module_path: &'static str, name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            module_path,
+            name,
+            "src/lib.rs",
+            1,
+        )))
+    }
+
+    fn outcome(test: &'static TestDef, status: TestStatus, attempts: u32) -> TestOutcome {
+        TestOutcome {
+            test,
+            status,
+            duration: Duration::ZERO,
+            output: String::new(),
+            attempts,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn failed() -> TestStatus {
+        TestStatus::Failed(vec![TestFailure::new("boom")])
+    }
+
+    fn quarantine(globs: &[&str]) -> Quarantine {
+        Quarantine {
+            tests: globs.iter().map(|glob| glob.parse().unwrap()).collect(),
+            ..Quarantine::default()
+        }
+    }
+
+    #[test]
+    fn parses_the_quarantine_table() {
+        let table = |text: &str| {
+            let table: toml::Table = text.parse().unwrap();
+            crate::config::quarantine(&table["quarantine"])
+        };
+        let parsed = table(
+            "[quarantine]\ntests = [\"db::replica::*\", \"net::reconnects\"]\nstable-after = 3",
+        )
+        .unwrap();
+        assert_eq!(parsed.stable_after, 3);
+        assert!(parsed.contains(def("app::db::replica", "lags")));
+        assert!(parsed.contains(def("app::net", "reconnects")));
+        assert!(!parsed.contains(def("app::net", "reconnects_twice")));
+        assert!(!parsed.contains(def("app::db", "replica")));
+
+        let defaults = table("[quarantine]\ntests = []").unwrap();
+        assert!(defaults.is_empty());
+        assert_eq!(defaults.stable_after, DEFAULT_STABLE_AFTER);
+
+        assert_eq!(
+            table("[quarantine]\ntests = [\"a::***\"]").unwrap_err(),
+            "invalid `quarantine.tests` glob `a::***`: `***` is not a pattern"
+        );
+        assert_eq!(
+            table("[quarantine]\nstable = 3").unwrap_err(),
+            "unknown key `quarantine.stable`"
+        );
+        assert!(table("quarantine = 3")
+            .unwrap_err()
+            .starts_with("`quarantine` must be a table"));
+    }
+
+    #[test]
+    fn quarantined_failures_do_not_fail_the_run() {
+        enable(&quarantine(&["quarantine_only::*"]));
+        let flaky = def("app::quarantine_only", "flaky");
+        let other = def("app::elsewhere", "broken");
+
+        assert!(is_quarantined(flaky));
+        assert!(!is_quarantined(other));
+        assert!(is_quarantined_failure(&outcome(flaky, failed(), 1)));
+        assert!(!is_quarantined_failure(&outcome(
+            flaky,
+            TestStatus::Passed,
+            1
+        )));
+        assert!(!is_quarantined_failure(&outcome(other, failed(), 1)));
+
+        let mut summary = RunSummary::default();
+        summary.record(&outcome(flaky, failed(), 1));
+        summary.record(&outcome(flaky, TestStatus::Passed, 1));
+        assert_eq!(
+            (summary.quarantined, summary.failed, summary.passed),
+            (1, 0, 1)
+        );
+        assert!(summary.is_success());
+
+        summary.record(&outcome(other, failed(), 1));
+        assert_eq!((summary.quarantined, summary.failed), (1, 1));
+        assert!(!summary.is_success());
+    }
+
+    #[test]
+    fn records_passing_streaks_of_quarantined_tests() {
+        let quarantine = quarantine(&["flaky::*"]);
+        let steady = def("app::flaky", "steady");
+        let retried = def("app::flaky", "retried");
+        let skipped = def("app::flaky", "skipped");
+        let outside = def("app::stable", "steady");
+
+        let mut streaks = Streaks::default();
+        for _ in 0..3 {
+            streaks.record(
+                &quarantine,
+                &[
+                    outcome(steady, TestStatus::Passed, 1),
+                    outcome(retried, TestStatus::Passed, 1),
+                    outcome(skipped, TestStatus::Passed, 1),
+                    outcome(outside, TestStatus::Passed, 1),
+                ],
+            );
+        }
+        assert_eq!(streaks.passing(steady), 3);
+        assert_eq!(streaks.passing(outside), 0);
+
+        streaks.record(
+            &quarantine,
+            &[
+                // Not run, as after `--max-failures`: the streak stands.
+                outcome(steady, TestStatus::Passed, 0),
+                outcome(retried, TestStatus::Passed, 2),
+                outcome(skipped, TestStatus::Skipped(None), 1),
+            ],
+        );
+        assert_eq!(streaks.passing(steady), 3);
+        assert_eq!(streaks.passing(retried), 0);
+        assert_eq!(streaks.passing(skipped), 0);
+
+        streaks.record(&quarantine, &[outcome(steady, failed(), 1)]);
+        assert_eq!(streaks.passing(steady), 0);
+    }
+
+    #[test]
+    fn streaks_round_trip_through_their_file() {
+        let dir = std::env::temp_dir().join(format!("tust-quarantine-{}", std::process::id()));
+        let path = dir.join("nested").join("quarantine.json");
+        let test = def("app::flaky", "steady");
+        let mut streaks = Streaks::default();
+        streaks.record(
+            &quarantine(&["flaky::*"]),
+            &[outcome(test, TestStatus::Passed, 1)],
+        );
+        streaks.write(&path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"passing\": {\n    \"app::flaky::steady\": 1\n  }\n}\n"
+        );
+        assert_eq!(Streaks::read(&path).unwrap().passing(test), 1);
+
+        fs::write(&path, "{\"passing\": []}").unwrap();
+        let err = Streaks::read(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "expected a `passing` object");
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(
+            Streaks::read(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
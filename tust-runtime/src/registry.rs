@@ -0,0 +1,231 @@
+//! Link-time registry of every test annotated with `#[tust::test]`.
+//!
+//! The attribute macro emits one [`TestDef`] per test and submits it to this
+//! registry, so the runtime can discover tests without libtest's harness.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use crate::fixture::{FixtureError, FixtureRequest, Fixtures};
+
+/// Static description of a registered test.
+///
+/// Values of this type are generated by `#[tust::test]`; they are not meant to
+/// be constructed by hand.
+#[derive(Debug)]
+pub struct TestDef {
+    /// Name of the test relative to its module.
+    pub name: &'static str,
+    /// `module_path!()` of the module the test was declared in.
+    pub module_path: &'static str,
+    /// Source file declaring the test.
+    pub file: &'static str,
+    /// Line of the test function's name.
+    pub line: u32,
+    /// Column of the test function's name.
+    pub column: u32,
+    /// Fixtures injected into the test's parameters.
+    pub fixtures: &'static [FixtureRequest],
+    /// Whether the test must not run concurrently with any other test. Tests
+    /// using an [`Env`](crate::env::Env) run serially even if this is unset;
+    /// see [`TestDef::runs_serially`].
+    pub serial: bool,
+    /// Time limit overriding the runner's `--timeout`.
+    pub timeout: Option<Duration>,
+    /// How many times to re-run the test after a failure, overriding the
+    /// runner's `--retries`.
+    pub retries: Option<u32>,
+    /// Tags given with `#[tust::test(tags(...))]`, matched by `--tag`.
+    pub tags: &'static [&'static str],
+    /// Whether the test is skipped instead of run.
+    pub skip: Skip,
+    /// Whether the test is expected to pass.
+    pub expected: ExpectedResult,
+    /// Whether `--leak-check` leaves the test alone, as set by
+    /// `#[allow_leaks]`.
+    pub allow_leaks: bool,
+    /// Whether the test runs in a working directory of its own, as set by
+    /// `#[tust::test(isolated_cwd)]`; see [`crate::sandbox`].
+    pub isolated_cwd: bool,
+    /// Tests, or modules of tests, that must pass before this one runs, as
+    /// named by `#[depends_on(...)]`; see [`crate::depends`].
+    pub depends_on: &'static [&'static str],
+    /// Named locks the test holds while it runs, as set by
+    /// `#[tust::test(lock = "...")]`; see [`crate::locks`].
+    pub locks: &'static [&'static str],
+    /// Resources the test's process may use, as set by `#[limits(...)]`;
+    /// enforced with `--process-per-test`.
+    pub limits: Limits,
+    /// When the test starts relative to the others, as set by
+    /// `#[priority(...)]`.
+    pub priority: Priority,
+    /// Resolves the test's fixtures and runs its body.
+    pub run: fn(&mut Fixtures) -> Result<(), FixtureError>,
+}
+
+/// When a test is skipped, as set by `#[skip]` and `#[skip_if]`.
+#[derive(Debug, Clone, Copy)]
+pub enum Skip {
+    /// The test runs.
+    Never,
+    /// The test is never run by the tust runner; libtest ignores it.
+    Always { reason: Option<&'static str> },
+    /// The test is skipped when `condition` returns true as the run starts.
+    If {
+        condition: fn() -> bool,
+        reason: &'static str,
+    },
+}
+
+/// How a test is expected to end, as set by `#[should_fail]`,
+/// `#[should_panic]`, and `#[xfail]`.
+#[derive(Debug, Clone, Copy)]
+pub enum ExpectedResult {
+    Pass,
+    /// The test passes if it fails, and if `expected` is given, only if a
+    /// failure message contains it; it fails if it passes.
+    ShouldFail {
+        expected: Option<&'static str>,
+    },
+    /// The test passes if it panics, and only if the panic's message
+    /// contains `contains` and matches `regex`, where given; it fails if it
+    /// passes, or fails only with soft failures.
+    ShouldPanic {
+        contains: Option<&'static str>,
+        regex: Option<&'static str>,
+    },
+    /// The test is known to be broken: a failure is reported as expected,
+    /// and a pass is an error.
+    XFail {
+        reason: Option<&'static str>,
+    },
+}
+
+/// Limits on the resources of a test's process, as set by
+/// `#[limits(memory = "512MB", cpu_time = "10s")]`.
+///
+/// With `--process-per-test`, they are set as the process's resource limits
+/// on Unix: `memory` limits its address space, so that allocating beyond it
+/// fails, and `cpu_time` the processor time it may use, after which it is
+/// stopped. Either fails only the test, as exceeding its limit. Tests run in
+/// the runner's own process cannot be limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytes of address space.
+    pub memory: Option<u64>,
+    /// Processor time, in whole seconds.
+    pub cpu_time: Option<Duration>,
+}
+
+impl Limits {
+    /// No limits.
+    pub const NONE: Limits = Limits {
+        memory: None,
+        cpu_time: None,
+    };
+
+    pub fn is_none(&self) -> bool {
+        *self == Limits::NONE
+    }
+}
+
+/// How early a test starts, as set by `#[priority(high)]` and
+/// `#[priority(low)]`.
+///
+/// Unless the run is shuffled, the runner starts the tests of a higher
+/// priority before those of a lower one, such as the tests most likely to
+/// catch a regression, so that their failures are reported early. Within a
+/// priority, parallel runs start the tests that took longest in earlier runs
+/// first; see [`crate::timings`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// The priority as `#[priority(...)]` names it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+inventory::collect!(TestDef);
+
+thread_local! {
+    static CURRENT: Cell<Option<&'static TestDef>> = const { Cell::new(None) };
+}
+
+impl TestDef {
+    /// Full name of the test, in the same form libtest uses: the module path
+    /// without the crate name, followed by the test name.
+    pub fn path(&self) -> String {
+        match self.module_path.split_once("::") {
+            Some((_crate, module)) => format!("{}::{}", module, self.name),
+            None => self.name.to_string(),
+        }
+    }
+
+    /// A test that ran in another program, such as on an embedded target,
+    /// with nothing but its name and place known. Running it does nothing.
+    pub(crate) fn ran_elsewhere(
+        module_path: &'static str,
+        name: &'static str,
+        file: &'static str,
+        line: u32,
+    ) -> Self {
+        TestDef {
+            name,
+            module_path,
+            file,
+            line,
+            column: 1,
+            fixtures: &[],
+            serial: false,
+            timeout: None,
+            retries: None,
+            tags: &[],
+            skip: Skip::Never,
+            expected: ExpectedResult::Pass,
+            allow_leaks: false,
+            isolated_cwd: false,
+            depends_on: &[],
+            locks: &[],
+            limits: Limits::NONE,
+            priority: Priority::Normal,
+            run: |_| Ok(()),
+        }
+    }
+
+    /// Whether the test must run alone: it is marked serial, or it changes
+    /// environment variables through an [`Env`](crate::env::Env).
+    pub fn runs_serially(&self) -> bool {
+        self.serial || crate::env::used_by(self)
+    }
+}
+
+/// Returns every registered test, sorted by [`TestDef::path`].
+pub fn tests() -> Vec<&'static TestDef> {
+    let mut tests: Vec<_> = inventory::iter::<TestDef>.into_iter().collect();
+    tests.sort_by_cached_key(|test| test.path());
+    tests
+}
+
+/// Returns the test the runtime is executing on the current thread, if any.
+pub fn current() -> Option<&'static TestDef> {
+    CURRENT.with(Cell::get)
+}
+
+/// Runs `f` with `test` as the current test. `f` must not unwind.
+pub(crate) fn with_current<R>(test: &'static TestDef, f: impl FnOnce() -> R) -> R {
+    let outer = CURRENT.with(|current| current.replace(Some(test)));
+    let result = f();
+    CURRENT.with(|current| current.set(outer));
+    result
+}
@@ -0,0 +1,1036 @@
+//! Human-readable output: tests grouped by module with a progress bar, or
+//! one line or one character per test in the style of libtest.
+//!
+//! The plain style prints libtest's lines once the run ends, in the order of
+//! the tests' paths rather than the order they finished in, with every
+//! duration zeroed, timings of benchmarks included. Colors are turned off
+//! for it, so that runs with the same results and seeds print the same
+//! output, for snapshots of it and for tools that parse it.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use colored::Colorize;
+
+use super::Reporter;
+use crate::bench::{self, BenchResult};
+use crate::capture::Stdout;
+use crate::outcome::{RunSummary, TestOutcome, TestStatus};
+use crate::quarantine;
+use crate::registry::TestDef;
+use crate::rerun;
+use crate::shared::DataLoad;
+use crate::snapshot::{self, ChangeKind, SnapshotChange};
+use crate::strict::StrictWarning;
+
+/// Width of the progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Shortest duration shown next to a test in a module group.
+const SHOWN_DURATION: Duration = Duration::from_millis(100);
+
+/// How a [`ConsoleReporter`] reports each test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Style {
+    /// A group of lines per module, printed once every test of the module
+    /// has finished.
+    Tree,
+    /// One line per test, like libtest.
+    Lines,
+    /// One character per test.
+    Dots,
+    /// One line per test, like [`Style::Lines`], printed once the run ends;
+    /// see [the module](self).
+    Plain,
+}
+
+/// Prints the tests as they finish and a failure digest at the end of the
+/// run, to stdout unless given another writer.
+pub(crate) struct ConsoleReporter<W = Stdout> {
+    out: W,
+    style: Style,
+    /// Whether to keep a progress bar below the output; only for
+    /// [`Style::Tree`].
+    live: bool,
+    /// How many of the slowest tests to list at the end of the run.
+    slowest: usize,
+    /// How many trailing lines of a failed test's output the digest shows,
+    /// or 0 for all of them.
+    output_lines: usize,
+    total: usize,
+    /// Tests finished so far, in the order they finished.
+    done: usize,
+    failed: usize,
+    /// How many tests of each module have not been reported yet.
+    remaining: HashMap<&'static str, usize>,
+    /// The lines of the modules whose groups are not printed yet.
+    groups: Vec<(&'static str, Vec<String>)>,
+    /// The module whose group was printed last.
+    printed_module: &'static str,
+    snapshot_changes: Vec<SnapshotChange>,
+    golden_changes: Vec<PathBuf>,
+    bench_results: Vec<BenchResult>,
+    strict_warnings: Vec<StrictWarning>,
+    shared_data: Vec<DataLoad>,
+}
+
+impl ConsoleReporter {
+    /// `live` draws a progress bar, and must only be set if stdout is a
+    /// terminal that nothing else writes to.
+    pub(crate) fn new(style: Style, live: bool, slowest: usize, output_lines: usize) -> Self {
+        Self::with_writer(Stdout, style, live, slowest, output_lines)
+    }
+}
+
+impl<W: Write> ConsoleReporter<W> {
+    /// Like [`ConsoleReporter::new`], writing to `out`.
+    pub(crate) fn with_writer(
+        out: W,
+        style: Style,
+        live: bool,
+        slowest: usize,
+        output_lines: usize,
+    ) -> Self {
+        ConsoleReporter {
+            out,
+            style,
+            live: live && style == Style::Tree,
+            slowest,
+            output_lines,
+            total: 0,
+            done: 0,
+            failed: 0,
+            remaining: HashMap::new(),
+            groups: Vec::new(),
+            printed_module: "",
+            snapshot_changes: Vec::new(),
+            golden_changes: Vec::new(),
+            bench_results: Vec::new(),
+            strict_warnings: Vec::new(),
+            shared_data: Vec::new(),
+        }
+    }
+
+    fn draw_progress(&mut self) {
+        if !self.live {
+            return;
+        }
+        let filled = (self.done * BAR_WIDTH).checked_div(self.total).unwrap_or(0);
+        let bar = "█".repeat(filled);
+        let bar = if self.failed > 0 {
+            bar.red()
+        } else {
+            bar.green()
+        };
+        let failed = match self.failed {
+            0 => String::new(),
+            failed => format!(", {}", format!("{} failed", failed).red()),
+        };
+        let _ = write!(
+            self.out,
+            "\r\x1b[2K{}{} {}/{}{}",
+            bar,
+            "░".repeat(BAR_WIDTH - filled).dimmed(),
+            self.done,
+            self.total,
+            failed
+        );
+        let _ = self.out.flush();
+    }
+
+    fn clear_progress(&mut self) {
+        if self.live {
+            let _ = write!(self.out, "\r\x1b[2K");
+        }
+    }
+
+    /// Adds the test's line, and those of its steps, to its module's group,
+    /// printing the group if it is complete.
+    fn group(&mut self, outcome: &TestOutcome) {
+        let module = module_of(outcome.test);
+        let line = format!(
+            "{} {}{}",
+            Self::symbol(outcome),
+            outcome.test.name,
+            Self::detail(outcome)
+        );
+        let mut lines = vec![line];
+        for step in &outcome.steps {
+            let symbol = if step.failed {
+                "✗".red()
+            } else {
+                "✓".green()
+            };
+            let duration = format!("{:.2}s", step.duration.as_secs_f64());
+            lines.push(format!(
+                "{}{} {} {}",
+                "  ".repeat(step.depth + 1),
+                symbol,
+                step.name,
+                duration.dimmed()
+            ));
+        }
+        match self.groups.iter_mut().find(|(name, _)| *name == module) {
+            Some((_, group)) => group.extend(lines),
+            None => self.groups.push((module, lines)),
+        }
+        let remaining = self.remaining.entry(module).or_insert(1);
+        *remaining -= 1;
+        if *remaining == 0 {
+            let index = self.groups.iter().position(|(name, _)| *name == module);
+            if let Some(index) = index {
+                let (module, lines) = self.groups.remove(index);
+                self.print_group(module, &lines);
+            }
+        }
+    }
+
+    /// Prints the headers of the modules from the last printed one to
+    /// `module`, indented by depth, then `lines` below them.
+    fn print_group(&mut self, module: &'static str, lines: &[String]) {
+        let segments: Vec<&str> = module.split("::").filter(|s| !s.is_empty()).collect();
+        let common = segments
+            .iter()
+            .zip(self.printed_module.split("::").filter(|s| !s.is_empty()))
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        // Reprint the module itself when its group is split in two.
+        let common = common.min(segments.len().saturating_sub(1));
+        for (depth, segment) in segments.iter().enumerate().skip(common) {
+            let _ = writeln!(self.out, "{}{}", "  ".repeat(depth), segment.bold());
+        }
+        for line in lines {
+            let _ = writeln!(self.out, "{}{}", "  ".repeat(segments.len()), line);
+        }
+        self.printed_module = module;
+    }
+
+    fn symbol(outcome: &TestOutcome) -> colored::ColoredString {
+        match outcome.status {
+            TestStatus::Passed if outcome.is_flaky() => "✓".yellow(),
+            TestStatus::Passed => "✓".green(),
+            TestStatus::Failed(_) if quarantine::is_quarantined(outcome.test) => "✗".yellow(),
+            TestStatus::Failed(_) => "✗".red(),
+            TestStatus::Skipped(_) => "○".yellow(),
+            TestStatus::ExpectedFailure(_) => "✗".yellow(),
+        }
+    }
+
+    /// What follows a test's name in its module group: its status unless it
+    /// simply passed, and how long it took if that was long.
+    fn detail(outcome: &TestOutcome) -> String {
+        let status = match outcome.status {
+            TestStatus::Passed if outcome.is_flaky() => {
+                Some(format!("flaky, passed on attempt {}", outcome.attempts).yellow())
+            }
+            TestStatus::Passed => None,
+            TestStatus::Failed(_) if quarantine::is_quarantined(outcome.test) => {
+                Some("quarantined".yellow())
+            }
+            TestStatus::Failed(_) if outcome.attempts > 1 => {
+                Some(format!("{} attempts", outcome.attempts).red())
+            }
+            TestStatus::Failed(_) => None,
+            TestStatus::Skipped(ref reason) if outcome.is_skipped_at_run_time() => {
+                Some(with_reason("skipped at run time", reason).yellow())
+            }
+            TestStatus::Skipped(ref reason) => Some(with_reason("skipped", reason).yellow()),
+            TestStatus::ExpectedFailure(ref reason) => Some(with_reason("xfail", reason).yellow()),
+        };
+        let mut detail = String::new();
+        if let Some(status) = status {
+            detail = format!(" ({})", status);
+        }
+        if outcome.attempts > 0 && outcome.duration >= SHOWN_DURATION {
+            let duration = format!("{:.2}s", outcome.duration.as_secs_f64());
+            detail = format!("{} {}", detail, duration.dimmed());
+        }
+        detail
+    }
+
+    /// Lists every failure, numbered, with its message and output indented
+    /// below the test's name.
+    fn print_failure_digest(&mut self, outcomes: &[TestOutcome]) {
+        let failed: Vec<_> = outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.status {
+                TestStatus::Failed(failures) => Some((outcome, failures)),
+                _ => None,
+            })
+            .collect();
+        if failed.is_empty() {
+            return;
+        }
+        let _ = writeln!(self.out, "\n{}", "failures:".red());
+        for (number, (outcome, failures)) in failed.iter().enumerate() {
+            let _ = writeln!(
+                self.out,
+                "\n  {} {}{}",
+                format!("{})", number + 1).red(),
+                outcome.test.path().bold(),
+                quarantined_label(outcome.test)
+            );
+            for failure in failures.iter() {
+                let _ = writeln!(self.out, "{}", indent(&failure.to_string()));
+                if let Some(backtrace) = &failure.backtrace {
+                    let _ = writeln!(
+                        self.out,
+                        "{}",
+                        indent(&format!("stack backtrace:\n{}", backtrace))
+                    );
+                }
+            }
+            let _ = writeln!(
+                self.out,
+                "     {} {}",
+                "rerun:".dimmed(),
+                rerun::command(outcome.test)
+            );
+            if !outcome.output.is_empty() {
+                let lines: Vec<&str> = outcome.output.trim_end().lines().collect();
+                let shown = match self.output_lines {
+                    0 => lines.len(),
+                    limit => limit.min(lines.len()),
+                };
+                let heading = if shown < lines.len() {
+                    format!("output (last {} of {} lines):", shown, lines.len())
+                } else {
+                    "output:".to_string()
+                };
+                let _ = writeln!(self.out, "     {}", heading.dimmed());
+                let tail = lines[lines.len() - shown..].join("\n");
+                let _ = writeln!(self.out, "{}", indent(&tail));
+            }
+            if !outcome.attachments.is_empty() {
+                let _ = writeln!(self.out, "     {}", "attachments:".dimmed());
+                for attachment in &outcome.attachments {
+                    let _ = writeln!(
+                        self.out,
+                        "{}",
+                        indent(&attachment.path.display().to_string())
+                    );
+                }
+            }
+        }
+    }
+
+    /// Lists every failure in the style of libtest.
+    fn print_failures(&mut self, outcomes: &[TestOutcome]) {
+        let failures: Vec<_> = outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.status {
+                TestStatus::Failed(failures) => Some((
+                    format!("{}{}", outcome.test.path(), quarantined_label(outcome.test)),
+                    failures,
+                    &outcome.output,
+                    outcome.test,
+                )),
+                _ => None,
+            })
+            .collect();
+
+        if !failures.is_empty() {
+            let _ = writeln!(self.out, "\nfailures:\n");
+            for (name, failures, output, test) in &failures {
+                let _ = writeln!(self.out, "---- {} ----", name);
+                if !output.is_empty() {
+                    let _ = writeln!(self.out, "{}\n", output.trim_end());
+                }
+                for failure in failures.iter() {
+                    let _ = writeln!(self.out, "{}\n", failure);
+                    if let Some(backtrace) = &failure.backtrace {
+                        let _ = writeln!(self.out, "stack backtrace:\n{}\n", backtrace);
+                    }
+                }
+                let _ = writeln!(self.out, "rerun: {}\n", rerun::command(test));
+            }
+            let _ = writeln!(self.out, "failures:");
+            for (name, ..) in &failures {
+                let _ = writeln!(self.out, "    {}", name);
+            }
+        }
+    }
+
+    fn print_snapshot_changes(&mut self) {
+        let (pending, accepted): (Vec<_>, Vec<_>) = self
+            .snapshot_changes
+            .iter()
+            .partition(|change| change.kind == ChangeKind::Pending);
+
+        if !accepted.is_empty() {
+            let _ = writeln!(
+                self.out,
+                "{}",
+                format!("accepted {}:", snapshots(accepted.len())).green()
+            );
+            for change in &accepted {
+                let _ = writeln!(self.out, "    {}", change.path.display());
+            }
+            let _ = writeln!(self.out);
+        }
+        if !pending.is_empty() {
+            let _ = writeln!(
+                self.out,
+                "{}",
+                format!("{} pending review:", snapshots(pending.len())).yellow()
+            );
+            for change in &pending {
+                let _ = writeln!(self.out, "    {}", change.path.display());
+            }
+            let _ = writeln!(
+                self.out,
+                "\nrename them over the snapshots, dropping the `new` from their names, or rerun\n\
+                 with {}=1 to accept them; delete them to reject them\n",
+                snapshot::UPDATE_ENV,
+            );
+        }
+    }
+
+    fn print_golden_changes(&mut self) {
+        if self.golden_changes.is_empty() {
+            return;
+        }
+        let noun = if self.golden_changes.len() == 1 {
+            "golden file"
+        } else {
+            "golden files"
+        };
+        let _ = writeln!(
+            self.out,
+            "{}",
+            format!("updated {} {}:", self.golden_changes.len(), noun).green()
+        );
+        for path in &self.golden_changes {
+            let _ = writeln!(self.out, "    {}", path.display());
+        }
+        let _ = writeln!(self.out);
+    }
+
+    fn print_slowest(&mut self, outcomes: &[TestOutcome]) {
+        let mut ran: Vec<_> = outcomes
+            .iter()
+            .filter(|outcome| outcome.attempts > 0)
+            .collect();
+        if self.slowest == 0 || ran.is_empty() {
+            return;
+        }
+        ran.sort_by_key(|outcome| std::cmp::Reverse(outcome.duration));
+        ran.truncate(self.slowest);
+        let _ = writeln!(
+            self.out,
+            "
+slowest tests:"
+        );
+        for outcome in ran {
+            let _ = writeln!(
+                self.out,
+                "    {:>8.3}s  {}",
+                outcome.duration.as_secs_f64(),
+                outcome.test.path()
+            );
+        }
+    }
+
+    /// Lists the shared data sets loaded, with how long the loads took, in
+    /// all, and what was loaded.
+    fn print_shared_data(&mut self) {
+        if self.shared_data.is_empty() {
+            return;
+        }
+        // Loaded once per process with `--process-per-test`.
+        let mut loads: Vec<(&DataLoad, Duration, usize)> = Vec::new();
+        for load in &self.shared_data {
+            match loads.iter_mut().find(|(first, ..)| first.name == load.name) {
+                Some((_, total, count)) => {
+                    *total += load.duration;
+                    *count += 1;
+                }
+                None => loads.push((load, load.duration, 1)),
+            }
+        }
+        let _ = writeln!(self.out, "\nshared data:");
+        for (load, total, count) in loads {
+            let mut details = Vec::new();
+            match (load.bytes, load.mapped) {
+                (Some(bytes), true) => details.push(format!("{}, memory-mapped", size(bytes))),
+                (Some(bytes), false) => details.push(size(bytes)),
+                (None, _) => {}
+            }
+            let mut line = format!("    {:>8.3}s  {}", total.as_secs_f64(), load.name);
+            if !details.is_empty() {
+                line.push_str(&format!(" ({})", details.join(", ")));
+            }
+            match (count, load.test) {
+                (1, Some(test)) => line.push_str(&format!(", loaded by {}", test.path())),
+                (1, None) => {}
+                (count, _) => line.push_str(&format!(", loaded {} times", count)),
+            }
+            let _ = writeln!(self.out, "{}", line);
+        }
+    }
+
+    /// Lists the measured benchmarks with their statistics and their change
+    /// against their baselines.
+    fn print_bench_results(&mut self) {
+        if self.bench_results.is_empty() {
+            return;
+        }
+        let _ = writeln!(self.out, "\nbenchmarks:");
+        let width = self
+            .bench_results
+            .iter()
+            .map(|result| result.test.path().chars().count())
+            .max()
+            .unwrap_or(0);
+        for result in &self.bench_results {
+            let stats = &result.stats;
+            let outliers = match stats.mild_outliers + stats.severe_outliers {
+                0 => String::new(),
+                1 => "  1 outlier".to_string(),
+                outliers => format!("  {} outliers", outliers),
+            };
+            let change = match result.change() {
+                _ if result.saved && result.baseline.is_none() => "new baseline".dimmed(),
+                Some(change) if result.saved => format!("{:+.1}%, new baseline", change).dimmed(),
+                Some(change) if result.regressed() => format!("{:+.1}% (regressed)", change).red(),
+                Some(change) if change < -result.threshold => format!("{:+.1}%", change).green(),
+                Some(change) => format!("{:+.1}%", change).normal(),
+                None => "".normal(),
+            };
+            let _ = writeln!(
+                self.out,
+                "    {:width$}  median {}  mean {} ± {}  p95 {}{}  {}",
+                result.test.path(),
+                bench::format_time(stats.median),
+                bench::format_time(stats.mean),
+                bench::format_time(stats.std_dev),
+                bench::format_time(stats.p95),
+                outliers,
+                change,
+                width = width
+            );
+        }
+    }
+
+    fn status_label(outcome: &TestOutcome) -> colored::ColoredString {
+        match outcome.status {
+            TestStatus::Passed if outcome.is_flaky() => {
+                format!("ok (flaky, passed on attempt {})", outcome.attempts).yellow()
+            }
+            TestStatus::Passed => "ok".green(),
+            TestStatus::Failed(_) if quarantine::is_quarantined(outcome.test) => {
+                "FAILED (quarantined)".yellow()
+            }
+            TestStatus::Failed(_) if outcome.attempts > 1 => {
+                format!("FAILED ({} attempts)", outcome.attempts).red()
+            }
+            TestStatus::Failed(_) => "FAILED".red(),
+            TestStatus::Skipped(ref reason) if outcome.is_skipped_at_run_time() => {
+                with_reason("skipped at run time", reason).yellow()
+            }
+            TestStatus::Skipped(ref reason) => with_reason("skipped", reason).yellow(),
+            TestStatus::ExpectedFailure(ref reason) => with_reason("xfail", reason).yellow(),
+        }
+    }
+}
+
+// Console output is best effort: a closed stdout must not abort the run.
+impl<W: Write> Reporter for ConsoleReporter<W> {
+    fn on_run_start(&mut self, tests: &[&'static TestDef]) {
+        let noun = if tests.len() == 1 { "test" } else { "tests" };
+        let _ = writeln!(self.out, "\nrunning {} {}", tests.len(), noun);
+        self.total = tests.len();
+        for test in tests {
+            *self.remaining.entry(module_of(test)).or_insert(0) += 1;
+        }
+        self.draw_progress();
+    }
+
+    fn on_progress(&mut self, _test: &'static TestDef) {
+        self.done += 1;
+        self.draw_progress();
+    }
+
+    fn on_test_finish(&mut self, outcome: &TestOutcome) {
+        self.failed += usize::from(outcome.status.is_failure());
+        if self.style == Style::Plain {
+            return;
+        }
+        if self.style == Style::Tree {
+            self.clear_progress();
+            self.group(outcome);
+            self.draw_progress();
+            return;
+        }
+        if self.style == Style::Dots {
+            let mark = match outcome.status {
+                TestStatus::Passed if outcome.is_flaky() => ".".yellow(),
+                TestStatus::Passed => ".".green(),
+                TestStatus::Failed(_) if quarantine::is_quarantined(outcome.test) => "q".yellow(),
+                TestStatus::Failed(_) => "F".red(),
+                TestStatus::Skipped(_) => "s".yellow(),
+                TestStatus::ExpectedFailure(_) => "x".yellow(),
+            };
+            let _ = write!(self.out, "{}", mark);
+        } else {
+            // Tests running in parallel start out of order, so each line is
+            // written once its test has finished.
+            let _ = writeln!(
+                self.out,
+                "test {} ... {}",
+                outcome.test.path(),
+                Self::status_label(outcome)
+            );
+        }
+        let _ = self.out.flush();
+    }
+
+    fn on_snapshot_changes(&mut self, changes: &[SnapshotChange]) {
+        self.snapshot_changes = changes.to_vec();
+    }
+
+    fn on_golden_changes(&mut self, paths: &[PathBuf]) {
+        self.golden_changes = paths.to_vec();
+    }
+
+    fn on_bench_results(&mut self, results: &[BenchResult]) {
+        self.bench_results = results.to_vec();
+        if self.style == Style::Plain {
+            for result in &mut self.bench_results {
+                let stats = &mut result.stats;
+                stats.mean = Duration::ZERO;
+                stats.median = Duration::ZERO;
+                stats.p95 = Duration::ZERO;
+                stats.min = Duration::ZERO;
+                stats.max = Duration::ZERO;
+                stats.std_dev = Duration::ZERO;
+                stats.mild_outliers = 0;
+                stats.severe_outliers = 0;
+                result.baseline = None;
+            }
+        }
+    }
+
+    fn on_strict_warnings(&mut self, warnings: &[StrictWarning]) {
+        self.strict_warnings = warnings.to_vec();
+    }
+
+    fn on_shared_data(&mut self, loads: &[DataLoad]) {
+        self.shared_data = loads.to_vec();
+        if self.style == Style::Plain {
+            for load in &mut self.shared_data {
+                load.duration = Duration::ZERO;
+            }
+        }
+    }
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary) {
+        let plain;
+        let (outcomes, summary) = if self.style == Style::Plain {
+            let mut outcomes = outcomes.to_vec();
+            outcomes.sort_by_cached_key(|outcome| outcome.test.path());
+            for outcome in &mut outcomes {
+                outcome.duration = Duration::ZERO;
+            }
+            let summary = RunSummary {
+                duration: Duration::ZERO,
+                ..summary.clone()
+            };
+            plain = (outcomes, summary);
+            (&plain.0[..], &plain.1)
+        } else {
+            (outcomes, summary)
+        };
+        match self.style {
+            Style::Tree => {
+                self.clear_progress();
+                // Groups of modules with tests that were not run.
+                for (module, lines) in std::mem::take(&mut self.groups) {
+                    self.print_group(module, &lines);
+                }
+            }
+            Style::Dots => {
+                let _ = writeln!(self.out);
+            }
+            Style::Lines => {}
+            Style::Plain => {
+                for outcome in outcomes {
+                    let _ = writeln!(
+                        self.out,
+                        "test {} ... {}",
+                        outcome.test.path(),
+                        Self::status_label(outcome)
+                    );
+                }
+            }
+        }
+
+        let successes: Vec<_> = outcomes
+            .iter()
+            .filter(|outcome| !outcome.status.is_failure() && !outcome.output.is_empty())
+            .collect();
+        if !successes.is_empty() {
+            let _ = writeln!(self.out, "\nsuccesses:\n");
+            for outcome in &successes {
+                let _ = writeln!(self.out, "---- {} output ----", outcome.test.path());
+                let _ = writeln!(self.out, "{}\n", outcome.output);
+            }
+            let _ = writeln!(self.out, "successes:");
+            for outcome in &successes {
+                let _ = writeln!(self.out, "    {}", outcome.test.path());
+            }
+        }
+
+        match self.style {
+            Style::Tree => self.print_failure_digest(outcomes),
+            Style::Lines | Style::Dots | Style::Plain => self.print_failures(outcomes),
+        }
+
+        let flaky: Vec<_> = outcomes.iter().filter(|o| o.is_flaky()).collect();
+        if !flaky.is_empty() {
+            let _ = writeln!(self.out, "\n{}", "flaky tests:".yellow());
+            for outcome in &flaky {
+                let _ = writeln!(
+                    self.out,
+                    "    {} (passed on attempt {})",
+                    outcome.test.path(),
+                    outcome.attempts
+                );
+            }
+        }
+
+        let quarantined: Vec<_> = outcomes
+            .iter()
+            .filter(|outcome| quarantine::is_quarantined_failure(outcome))
+            .collect();
+        if !quarantined.is_empty() {
+            let _ = writeln!(
+                self.out,
+                "\n{}",
+                "quarantined failures, which do not fail the run:".yellow()
+            );
+            for outcome in &quarantined {
+                let _ = writeln!(self.out, "    {}", outcome.test.path());
+            }
+        }
+        if !summary.stable_quarantined.is_empty() {
+            let _ = writeln!(
+                self.out,
+                "\n{}",
+                "quarantined tests passing in each of their recent runs, which may leave the \
+                 quarantine:"
+                    .green()
+            );
+            for name in &summary.stable_quarantined {
+                let _ = writeln!(self.out, "    {}", name);
+            }
+        }
+
+        if !self.strict_warnings.is_empty() {
+            let _ = writeln!(self.out, "\n{}", "strict mode warnings:".yellow());
+            for warning in &self.strict_warnings {
+                let _ = writeln!(
+                    self.out,
+                    "    {} {} (`{}` check)",
+                    warning.test.path(),
+                    warning.message,
+                    warning.check
+                );
+                for backtrace in &warning.spawned_at {
+                    let _ = writeln!(
+                        self.out,
+                        "{}",
+                        indent(&format!("task spawned at:\n{}", backtrace))
+                    );
+                }
+            }
+        }
+
+        self.print_slowest(outcomes);
+        self.print_shared_data();
+        self.print_bench_results();
+
+        if summary.interrupted || summary.not_run > 0 {
+            let _ = writeln!(
+                self.out,
+                "\n{}",
+                format!(
+                    "{}; {} {} not run",
+                    if summary.interrupted {
+                        "interrupted".to_string()
+                    } else {
+                        format!(
+                            "stopped after {} {}",
+                            summary.failed,
+                            if summary.failed == 1 {
+                                "failure"
+                            } else {
+                                "failures"
+                            }
+                        )
+                    },
+                    summary.not_run,
+                    if summary.not_run == 1 {
+                        "test was"
+                    } else {
+                        "tests were"
+                    }
+                )
+                .red()
+            );
+        }
+
+        if let (Some(seed), false) = (summary.shuffle_seed, summary.is_success()) {
+            let _ = writeln!(
+                self.out,
+                "\ntests ran in shuffled order (rerun with --shuffle={} to replay it)",
+                seed
+            );
+        }
+        if let Some(seed) = summary.seed {
+            let _ = writeln!(
+                self.out,
+                "\nrandom seed: {} (rerun with --seed {} to reproduce)",
+                seed, seed
+            );
+        }
+
+        let result = if summary.is_success() {
+            "ok".green()
+        } else {
+            "FAILED".red()
+        };
+        let _ = writeln!(
+            self.out,
+            "\ntest result: {}. {} passed{}; {} failed;{} {} skipped{};{}{} {} filtered out; finished in {:.2}s\n",
+            result,
+            summary.passed,
+            if summary.flaky > 0 {
+                format!(" ({} flaky)", summary.flaky)
+            } else {
+                String::new()
+            },
+            summary.failed,
+            if summary.quarantined > 0 {
+                format!(" {} quarantined;", summary.quarantined)
+            } else {
+                String::new()
+            },
+            summary.skipped,
+            if summary.skipped_at_run_time > 0 {
+                format!(" ({} at run time)", summary.skipped_at_run_time)
+            } else {
+                String::new()
+            },
+            if summary.expected_failures > 0 {
+                format!(" {} xfailed;", summary.expected_failures)
+            } else {
+                String::new()
+            },
+            if summary.not_run > 0 {
+                format!(" {} not run;", summary.not_run)
+            } else {
+                String::new()
+            },
+            summary.filtered_out,
+            summary.duration.as_secs_f64(),
+        );
+        self.print_snapshot_changes();
+        self.print_golden_changes();
+        let _ = self.out.flush();
+    }
+}
+
+/// The path of the module declaring `test`, without the crate's name; empty
+/// at the crate root.
+fn module_of(test: &TestDef) -> &'static str {
+    match test.module_path.split_once("::") {
+        Some((_crate, module)) => module,
+        None => "",
+    }
+}
+
+/// Indents every line of `text` below a numbered entry of the failure
+/// digest.
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("     {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Marks the failure of a quarantined test in the failure lists.
+fn quarantined_label(test: &TestDef) -> String {
+    if quarantine::is_quarantined(test) {
+        format!(" {}", "(quarantined)".yellow())
+    } else {
+        String::new()
+    }
+}
+
+/// `label`, followed by `reason` if there is one.
+fn with_reason(label: &str, reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("{}, {}", label, reason),
+        None => label.to_string(),
+    }
+}
+
+/// Formats `bytes` in the largest binary unit it reaches, to one decimal.
+fn size(bytes: u64) -> String {
+    for (shift, unit) in [(30, "GiB"), (20, "MiB"), (10, "KiB")] {
+        if bytes >= 1 << shift {
+            return format!("{:.1} {}", bytes as f64 / (1u64 << shift) as f64, unit);
+        }
+    }
+    format!("{} bytes", bytes)
+}
+
+fn snapshots(count: usize) -> String {
+    let noun = if count == 1 { "snapshot" } else { "snapshots" };
+    format!("{} {}", count, noun)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::TestFailure;
+
+    fn outcome(module: &'static str, name: &'static str, status: TestStatus) -> TestOutcome {
+        let test = Box::leak(Box::new(TestDef::ran_elsewhere(module, name, "t.rs", 1)));
+        TestOutcome {
+            attempts: u32::from(!matches!(status, TestStatus::Skipped(_))),
+            test,
+            status,
+            duration: Duration::ZERO,
+            output: String::new(),
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn failed(message: &str) -> TestStatus {
+        TestStatus::Failed(vec![TestFailure::new(message)])
+    }
+
+    /// Reports `outcomes` in `style`, returning what was printed without its
+    /// colors and with the test binary in rerun commands as `<binary>`.
+    fn print(style: Style, output_lines: usize, outcomes: &[TestOutcome]) -> String {
+        let mut reporter = ConsoleReporter::with_writer(Vec::new(), style, false, 0, output_lines);
+        let tests: Vec<_> = outcomes.iter().map(|outcome| outcome.test).collect();
+        let mut summary = RunSummary::default();
+        reporter.on_run_start(&tests);
+        for outcome in outcomes {
+            reporter.on_progress(outcome.test);
+            reporter.on_test_finish(outcome);
+            summary.record(outcome);
+        }
+        reporter.on_run_end(outcomes, &summary);
+        let printed = super::super::strip_ansi(&String::from_utf8(reporter.out).unwrap());
+        let binary = std::env::args().next().unwrap();
+        printed.replace(&binary, "<binary>")
+    }
+
+    #[test]
+    fn lines_report_each_test_and_the_failures() {
+        let mut fails = outcome("krate::pages", "fails", failed("expected 1, found 2"));
+        fails.output = "printed\n".to_string();
+        let outcomes = [
+            outcome("krate::pages", "renders", TestStatus::Passed),
+            fails,
+            outcome(
+                "krate",
+                "waits",
+                TestStatus::Skipped(Some("needs docker".to_string())),
+            ),
+        ];
+        assert_eq!(
+            print(Style::Lines, 0, &outcomes),
+            "\nrunning 3 tests\n\
+             test pages::renders ... ok\n\
+             test pages::fails ... FAILED\n\
+             test waits ... skipped, needs docker\n\
+             \nfailures:\n\n\
+             ---- pages::fails ----\nprinted\n\n\
+             expected 1, found 2\n\n\
+             rerun: <binary> --exact pages::fails\n\n\
+             failures:\n    pages::fails\n\
+             \ntest result: FAILED. 1 passed; 1 failed; 1 skipped; 0 filtered out; finished in \
+             0.00s\n\n"
+        );
+    }
+
+    #[test]
+    fn trees_group_tests_by_module_and_digest_the_failures() {
+        let mut fails = outcome("krate::pages", "fails", failed("expected 1\nfound 2"));
+        fails.output = "one\ntwo\nthree\n".to_string();
+        fails.attempts = 2;
+        let mut flaky = outcome("krate::pages::nested", "flaky", TestStatus::Passed);
+        flaky.attempts = 3;
+        let outcomes = [
+            outcome("krate::pages", "renders", TestStatus::Passed),
+            flaky,
+            fails,
+            outcome("krate", "xfails", TestStatus::ExpectedFailure(None)),
+        ];
+        assert_eq!(
+            print(Style::Tree, 2, &outcomes),
+            "\nrunning 4 tests\n\
+             pages\n  nested\n    ✓ flaky (flaky, passed on attempt 3)\n\
+             pages\n  ✓ renders\n  ✗ fails (2 attempts)\n\
+             ✗ xfails (xfail)\n\
+             \nfailures:\n\n  1) pages::fails\n     expected 1\n     found 2\n\
+             \u{20}    rerun: <binary> --exact pages::fails\n\
+             \u{20}    output (last 2 of 3 lines):\n     two\n     three\n\
+             \nflaky tests:\n    pages::nested::flaky (passed on attempt 3)\n\
+             \ntest result: FAILED. 2 passed (1 flaky); 1 failed; 0 skipped; 1 xfailed; 0 \
+             filtered out; finished in 0.00s\n\n"
+        );
+    }
+
+    #[test]
+    fn dots_mark_each_test() {
+        let outcomes = [
+            outcome("krate", "passes", TestStatus::Passed),
+            outcome("krate", "fails", failed("broken")),
+            outcome("krate", "skips", TestStatus::Skipped(None)),
+            outcome("krate", "xfails", TestStatus::ExpectedFailure(None)),
+        ];
+        let printed = print(Style::Dots, 0, &outcomes);
+        assert!(printed.starts_with("\nrunning 4 tests\n.Fsx\n\nfailures:\n\n---- fails ----\n"));
+        assert!(printed.ends_with(
+            "\ntest result: FAILED. 1 passed; 1 failed; 1 skipped; 1 xfailed; 0 filtered out; \
+             finished in 0.00s\n\n"
+        ));
+    }
+
+    #[test]
+    fn plain_output_is_sorted_and_passing_runs_succeed() {
+        let mut printing = outcome("krate", "b_prints", TestStatus::Passed);
+        printing.output = "hello".to_string();
+        printing.duration = Duration::from_secs(3);
+        let outcomes = [printing, outcome("krate", "a_passes", TestStatus::Passed)];
+        assert_eq!(
+            print(Style::Plain, 0, &outcomes),
+            "\nrunning 2 tests\n\
+             test a_passes ... ok\n\
+             test b_prints ... ok\n\
+             \nsuccesses:\n\n---- b_prints output ----\nhello\n\nsuccesses:\n    b_prints\n\
+             \ntest result: ok. 2 passed; 0 failed; 0 skipped; 0 filtered out; finished in \
+             0.00s\n\n"
+        );
+    }
+
+    #[test]
+    fn sizes_and_indents() {
+        assert_eq!(size(512), "512 bytes");
+        assert_eq!(size(3 << 19), "1.5 MiB");
+        assert_eq!(indent("a\nb"), "     a\n     b");
+        assert_eq!(
+            with_reason("skipped", &Some("why".to_string())),
+            "skipped, why"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! GitHub Actions annotations and job summaries.
+//!
+//! Once the run ends, every failure is printed to stdout as an `::error`
+//! workflow command at its location, or at the test's when it has none, so
+//! that GitHub shows it on the lines of the pull request's diff; failures
+//! of quarantined tests are `::warning`s instead. A flaky test gets a
+//! `::warning` at the test. The annotation's title names the
+//! test, and its message is the failure's description without colors.
+//!
+//! A Markdown summary is then appended to the report's path, which in a
+//! workflow is the file named by `GITHUB_STEP_SUMMARY`: a table counting the
+//! tests by result, a table of the failed and flaky tests with the first
+//! line of their first failure, and the quarantined tests that have passed
+//! long enough to leave the quarantine. Appending lets every test binary of
+//! a step add its own summary.
+//!
+//! ```text
+//! ::error file=tests/parser.rs,line=120,col=9,title=parser%3A%3Anested failed::assertion `depth == 2` failed%0A  left: 3%0A right: 2
+//! ```
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{strip_ansi, Reporter};
+use crate::capture::Stdout;
+use crate::outcome::{RunSummary, TestOutcome, TestStatus};
+use crate::quarantine;
+
+/// Prints annotations to stdout and appends a summary to a file once the
+/// run ends.
+pub(crate) struct GithubReporter {
+    summary_path: PathBuf,
+}
+
+impl GithubReporter {
+    pub(crate) fn new(summary_path: PathBuf) -> Self {
+        GithubReporter { summary_path }
+    }
+}
+
+impl Reporter for GithubReporter {
+    fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary) {
+        let mut out = Stdout;
+        let _ = out.write_all(annotations(outcomes).as_bytes());
+        let _ = out.flush();
+
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.summary_path)
+            .and_then(|mut file| file.write_all(render_summary(outcomes, summary).as_bytes()));
+        if let Err(err) = appended {
+            eprintln!(
+                "warning: failed to write the GitHub summary to {}: {}",
+                self.summary_path.display(),
+                err
+            );
+        }
+    }
+}
+
+fn annotations(outcomes: &[TestOutcome]) -> String {
+    let mut commands = String::new();
+    for outcome in outcomes {
+        let test = outcome.test;
+        let title = escape_property(&test.path());
+        match &outcome.status {
+            TestStatus::Failed(failures) => {
+                let (command, result) = if quarantine::is_quarantined(test) {
+                    ("warning", "failed in quarantine")
+                } else {
+                    ("error", "failed")
+                };
+                for failure in failures {
+                    let (file, line, column) = match &failure.location {
+                        Some(location) => (location.file.as_str(), location.line, location.column),
+                        None => (test.file, test.line, test.column),
+                    };
+                    let _ = writeln!(
+                        commands,
+                        "::{} file={},line={},col={},title={} {}::{}",
+                        command,
+                        escape_property(&workspace_path(file)),
+                        line,
+                        column,
+                        title,
+                        result,
+                        escape_data(&strip_ansi(&failure.description()))
+                    );
+                }
+            }
+            TestStatus::Passed if outcome.is_flaky() => {
+                let _ = writeln!(
+                    commands,
+                    "::warning file={},line={},col={},title={} is flaky::passed after {} attempts",
+                    escape_property(&workspace_path(test.file)),
+                    test.line,
+                    test.column,
+                    title,
+                    outcome.attempts
+                );
+            }
+            _ => {}
+        }
+    }
+    commands
+}
+
+fn render_summary(outcomes: &[TestOutcome], summary: &RunSummary) -> String {
+    let krate = outcomes
+        .first()
+        .and_then(|outcome| outcome.test.module_path.split("::").next())
+        .unwrap_or("tust");
+    let mut markdown = String::new();
+    let verdict = if summary.is_success() {
+        "passed"
+    } else {
+        "failed"
+    };
+    let _ = writeln!(markdown, "### Tests of `{}` {}\n", krate, verdict);
+    let _ = writeln!(
+        markdown,
+        "| Passed | Failed | Quarantined | Flaky | Skipped | Expected failures | Not run | Duration |"
+    );
+    let _ = writeln!(
+        markdown,
+        "| ---: | ---: | ---: | ---: | ---: | ---: | ---: | ---: |"
+    );
+    let _ = writeln!(
+        markdown,
+        "| {} | {} | {} | {} | {} | {} | {} | {:.2}s |\n",
+        summary.passed,
+        summary.failed,
+        summary.quarantined,
+        summary.flaky,
+        summary.skipped,
+        summary.expected_failures,
+        summary.not_run,
+        summary.duration.as_secs_f64()
+    );
+
+    let notable: Vec<_> = outcomes
+        .iter()
+        .filter(|outcome| outcome.status.is_failure() || outcome.is_flaky())
+        .collect();
+    if !notable.is_empty() {
+        let _ = writeln!(markdown, "| Test | Result | Details |");
+        let _ = writeln!(markdown, "| --- | --- | --- |");
+        for outcome in notable {
+            let (result, details) = match &outcome.status {
+                TestStatus::Failed(failures) => (
+                    if quarantine::is_quarantined(outcome.test) {
+                        "⚠️ quarantined"
+                    } else {
+                        "❌ failed"
+                    },
+                    failures
+                        .first()
+                        .map(|failure| {
+                            let message = strip_ansi(&failure.message);
+                            message.lines().next().unwrap_or_default().to_string()
+                        })
+                        .unwrap_or_default(),
+                ),
+                _ => (
+                    "⚠️ flaky",
+                    format!("passed after {} attempts", outcome.attempts),
+                ),
+            };
+            let _ = writeln!(
+                markdown,
+                "| `{}` | {} | {} |",
+                outcome.test.path(),
+                result,
+                escape_cell(&details)
+            );
+        }
+        markdown.push('\n');
+    }
+    if !summary.stable_quarantined.is_empty() {
+        let _ = writeln!(
+            markdown,
+            "Quarantined tests passing in each of their recent runs, which may leave the \
+             quarantine:\n"
+        );
+        for name in &summary.stable_quarantined {
+            let _ = writeln!(markdown, "- `{}`", name);
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// `file` relative to the repository checked out by the workflow, if it is
+/// an absolute path inside it.
+fn workspace_path(file: &str) -> String {
+    let workspace = std::env::var_os("GITHUB_WORKSPACE").map(PathBuf::from);
+    match workspace {
+        Some(workspace) => match Path::new(file).strip_prefix(&workspace) {
+            Ok(relative) => relative.to_string_lossy().into_owned(),
+            Err(_) => file.to_string(),
+        },
+        None => file.to_string(),
+    }
+    .replace('\\', "/")
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command, such as its title.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Escapes text for a cell of a Markdown table.
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::outcome::{Location, TestFailure};
+    use crate::registry::TestDef;
+
+    fn outcome(name: &'static str, status: TestStatus) -> TestOutcome {
+        let test = Box::leak(Box::new(TestDef::ran_elsewhere(
+            "krate::parser",
+            name,
+            "tests/parser.rs",
+            40,
+        )));
+        TestOutcome {
+            test,
+            status,
+            duration: Duration::ZERO,
+            output: String::new(),
+            attempts: 1,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn summary_of(outcomes: &[TestOutcome]) -> RunSummary {
+        let mut summary = RunSummary::default();
+        for outcome in outcomes {
+            summary.record(outcome);
+        }
+        summary
+    }
+
+    #[test]
+    fn failures_are_errors_at_their_location() {
+        let located = TestFailure {
+            location: Some(Location {
+                file: "tests/parser.rs".to_string(),
+                line: 120,
+                column: 9,
+            }),
+            ..TestFailure::new("assertion `depth == 2` failed").with_diff("3", "2")
+        };
+        let unlocated = TestFailure::new("\x1b[31m100% broken\x1b[0m\r\n");
+        let outcomes = [
+            outcome("nested", TestStatus::Failed(vec![located, unlocated])),
+            outcome("passes", TestStatus::Passed),
+        ];
+        assert_eq!(
+            annotations(&outcomes),
+            "::error file=tests/parser.rs,line=120,col=9,title=parser%3A%3Anested \
+             failed::assertion `depth == 2` failed%0A  left: 3%0A right: 2\n\
+             ::error file=tests/parser.rs,line=40,col=1,title=parser%3A%3Anested \
+             failed::100%25 broken%0D%0A\n"
+        );
+    }
+
+    #[test]
+    fn flaky_tests_are_warnings() {
+        let mut flaky = outcome("flaky", TestStatus::Passed);
+        flaky.attempts = 3;
+        assert_eq!(
+            annotations(&[flaky]),
+            "::warning file=tests/parser.rs,line=40,col=1,title=parser%3A%3Aflaky is \
+             flaky::passed after 3 attempts\n"
+        );
+    }
+
+    #[test]
+    fn commands_escape_their_data_and_properties() {
+        assert_eq!(escape_data("50%\r\nnext"), "50%25%0D%0Anext");
+        assert_eq!(escape_property("a::b, c%\n"), "a%3A%3Ab%2C c%25%0A");
+        assert_eq!(escape_cell("a | b"), "a \\| b");
+    }
+
+    #[test]
+    fn summaries_count_and_list_the_notable_tests() {
+        let mut flaky = outcome("flaky", TestStatus::Passed);
+        flaky.attempts = 2;
+        let failure = TestFailure::new("expected `a|b`\nfound `c`");
+        let outcomes = [
+            outcome("passes", TestStatus::Passed),
+            outcome("fails", TestStatus::Failed(vec![failure])),
+            flaky,
+            outcome("skips", TestStatus::Skipped(None)),
+        ];
+        assert_eq!(
+            render_summary(&outcomes, &summary_of(&outcomes)),
+            "### Tests of `krate` failed\n\n\
+             | Passed | Failed | Quarantined | Flaky | Skipped | Expected failures | Not run | \
+             Duration |\n\
+             | ---: | ---: | ---: | ---: | ---: | ---: | ---: | ---: |\n\
+             | 2 | 1 | 0 | 1 | 1 | 0 | 0 | 0.00s |\n\n\
+             | Test | Result | Details |\n\
+             | --- | --- | --- |\n\
+             | `parser::fails` | ❌ failed | expected `a\\|b` |\n\
+             | `parser::flaky` | ⚠️ flaky | passed after 2 attempts |\n\n"
+        );
+    }
+
+    #[test]
+    fn summaries_are_appended() {
+        let path = std::env::temp_dir().join(format!("tust-github-{}.md", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let outcomes = [outcome("passes", TestStatus::Passed)];
+        let summary = summary_of(&outcomes);
+        let mut reporter = GithubReporter::new(path.clone());
+        reporter.on_run_end(&outcomes, &summary);
+        reporter.on_run_end(&outcomes, &summary);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.matches("### Tests of `krate` passed\n").count(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
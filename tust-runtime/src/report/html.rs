@@ -0,0 +1,889 @@
+//! Static HTML reports, for reading a run's results in a browser.
+//!
+//! `--report html=DIR` writes `DIR/index.html`, a single self-contained page
+//! with the run's counts, a table of every test with its status and duration,
+//! and, under each test that failed, printed output, ran steps, or attached
+//! files, its failures, diffs, the command that reruns it alone, its steps
+//! with their durations, captured output, and links to its attachments,
+//! showing those that are images. The table can be filtered by status and searched
+//! by name.
+//!
+//! Each report also records the results of its run in `DIR/history.json`. A
+//! later report written to the same directory compares against them: it
+//! lists the tests that started or stopped failing since the previous run,
+//! shows how each test's duration changed, and draws a pass/fail matrix of
+//! each test over the recent runs.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::SystemTime;
+
+use super::{strip_ansi, Reporter};
+use crate::outcome::{RunSummary, TestFailure, TestOutcome, TestStatus};
+use crate::rerun;
+
+/// Runs kept in the history, including the current one.
+const MAX_HISTORY: usize = 20;
+
+/// Writes an HTML report to a directory once the run ends.
+pub(crate) struct HtmlReporter {
+    dir: PathBuf,
+}
+
+impl HtmlReporter {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        HtmlReporter { dir }
+    }
+}
+
+impl Reporter for HtmlReporter {
+    fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary) {
+        let history_path = self.dir.join("history.json");
+        let mut history = read_history(&history_path);
+        history.push(Run::new(outcomes, summary));
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+        }
+
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| {
+                fs::write(
+                    self.dir.join("index.html"),
+                    render(&self.dir, outcomes, summary, &history),
+                )
+            })
+            .and_then(|()| fs::write(&history_path, write_history(&history)));
+        if let Err(err) = written {
+            eprintln!(
+                "warning: failed to write HTML report to {}: {}",
+                self.dir.display(),
+                err
+            );
+        }
+    }
+}
+
+/// The results of one run, as recorded in the history.
+struct Run {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    duration: f64,
+    /// Status label and duration in seconds, by test path.
+    tests: HashMap<String, (String, f64)>,
+}
+
+impl Run {
+    fn new(outcomes: &[TestOutcome], summary: &RunSummary) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let tests = outcomes
+            .iter()
+            .map(|outcome| {
+                (
+                    outcome.test.path(),
+                    (
+                        status_label(outcome).to_string(),
+                        outcome.duration.as_secs_f64(),
+                    ),
+                )
+            })
+            .collect();
+        Run {
+            timestamp,
+            duration: summary.duration.as_secs_f64(),
+            tests,
+        }
+    }
+}
+
+/// Reads the runs recorded in `path`, ignoring a missing or unreadable file.
+fn read_history(path: &Path) -> Vec<Run> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let Ok(value) = serde_json::from_str::<Value>(&text) else {
+        eprintln!(
+            "warning: ignoring {}, which is not a tust report history",
+            path.display()
+        );
+        return Vec::new();
+    };
+    let runs = value["runs"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    runs.iter()
+        .map(|run| Run {
+            timestamp: run["timestamp"].as_u64().unwrap_or(0),
+            duration: run["duration"].as_f64().unwrap_or(0.0),
+            tests: run["tests"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(path, test)| {
+                    let status = test["status"].as_str().unwrap_or_default().to_string();
+                    let duration = test["duration"].as_f64().unwrap_or(0.0);
+                    (path.clone(), (status, duration))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn write_history(history: &[Run]) -> String {
+    let runs: Vec<Value> = history
+        .iter()
+        .map(|run| {
+            let mut tests: Vec<_> = run.tests.iter().collect();
+            tests.sort_by_key(|&(path, _)| path);
+            let tests: serde_json::Map<String, Value> = tests
+                .into_iter()
+                .map(|(path, (status, duration))| {
+                    (
+                        path.clone(),
+                        json!({ "status": status, "duration": duration }),
+                    )
+                })
+                .collect();
+            json!({
+                "timestamp": run.timestamp,
+                "duration": run.duration,
+                "tests": tests,
+            })
+        })
+        .collect();
+    let mut text = serde_json::to_string_pretty(&json!({ "runs": runs })).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+fn status_label(outcome: &TestOutcome) -> &'static str {
+    match outcome.status {
+        TestStatus::Passed if outcome.is_flaky() => "flaky",
+        TestStatus::Passed => "passed",
+        TestStatus::Failed(_) => "failed",
+        TestStatus::Skipped(_) => "skipped",
+        TestStatus::ExpectedFailure(_) => "xfail",
+    }
+}
+
+/// Whether a status label from the history is a failure.
+fn is_failure(status: &str) -> bool {
+    status == "failed"
+}
+
+/// The page of the report written to `dir`.
+fn render(dir: &Path, outcomes: &[TestOutcome], summary: &RunSummary, history: &[Run]) -> String {
+    let (current, earlier) = history.split_last().expect("the history holds this run");
+    let previous = earlier.last();
+
+    let mut html = String::new();
+    let title = if summary.is_success() {
+        "tust report: passed"
+    } else {
+        "tust report: FAILED"
+    };
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        title, STYLE
+    );
+    let _ = writeln!(
+        html,
+        "<h1 class=\"{}\">{}</h1>",
+        if summary.is_success() {
+            "passed"
+        } else {
+            "failed"
+        },
+        title
+    );
+    write_summary(&mut html, summary, current, previous);
+    if let Some(previous) = previous {
+        write_changes(&mut html, outcomes, previous);
+    }
+    write_tests(&mut html, dir, outcomes, earlier);
+    let _ = write!(html, "<script>{}</script>\n</body>\n</html>\n", SCRIPT);
+    html
+}
+
+fn write_summary(html: &mut String, summary: &RunSummary, current: &Run, previous: Option<&Run>) {
+    html.push_str("<p class=\"summary\">");
+    let counts = [
+        ("passed", summary.passed),
+        ("failed", summary.failed),
+        ("quarantined", summary.quarantined),
+        ("flaky", summary.flaky),
+        ("skipped", summary.skipped),
+        ("xfail", summary.expected_failures),
+        ("filtered out", summary.filtered_out),
+        ("not run", summary.not_run),
+    ];
+    for (label, count) in counts {
+        let class = label.replace(' ', "-");
+        let _ = write!(
+            html,
+            "<span class=\"count {}\">{} {}</span> ",
+            class, count, label
+        );
+    }
+    let _ = write!(
+        html,
+        "<span class=\"count\">finished in {}</span>",
+        seconds(summary.duration)
+    );
+    if let Some(previous) = previous {
+        let _ = write!(
+            html,
+            " <span class=\"count\">previous run: {} ({})</span>",
+            seconds(Duration::from_secs_f64(previous.duration.max(0.0))),
+            format_timestamp(previous.timestamp)
+        );
+    }
+    html.push_str("</p>\n");
+
+    let mut notes = vec![format!("run at {}", format_timestamp(current.timestamp))];
+    if let Some(seed) = summary.seed {
+        notes.push(format!("random seed {}", seed));
+    }
+    if let Some(seed) = summary.shuffle_seed {
+        notes.push(format!("shuffle seed {}", seed));
+    }
+    let _ = writeln!(html, "<p class=\"notes\">{}</p>", escape(&notes.join(", ")));
+}
+
+/// Lists the tests whose failing changed since the `previous` run.
+fn write_changes(html: &mut String, outcomes: &[TestOutcome], previous: &Run) {
+    let mut newly_failing = Vec::new();
+    let mut fixed = Vec::new();
+    let mut added = Vec::new();
+    for outcome in outcomes {
+        let path = outcome.test.path();
+        match previous.tests.get(&path) {
+            Some((status, _)) => {
+                let failed = outcome.status.is_failure();
+                if failed && !is_failure(status) {
+                    newly_failing.push(path);
+                } else if !failed && is_failure(status) {
+                    fixed.push(path);
+                }
+            }
+            None => added.push(path),
+        }
+    }
+    if newly_failing.is_empty() && fixed.is_empty() && added.is_empty() {
+        html.push_str(
+            "<p class=\"notes\">no tests started or stopped failing since the previous run</p>\n",
+        );
+        return;
+    }
+
+    html.push_str("<h2>Since the previous run</h2>\n<ul class=\"changes\">\n");
+    let groups = [
+        ("failed", "now failing", newly_failing),
+        ("passed", "fixed", fixed),
+        ("new", "new", added),
+    ];
+    for (class, label, paths) in groups {
+        for path in paths {
+            let _ = writeln!(
+                html,
+                "<li><span class=\"badge {}\">{}</span> <a href=\"#{}\">{}</a></li>",
+                class,
+                label,
+                anchor(&path),
+                escape(&path)
+            );
+        }
+    }
+    html.push_str("</ul>\n");
+}
+
+fn write_tests(html: &mut String, dir: &Path, outcomes: &[TestOutcome], earlier: &[Run]) {
+    html.push_str(
+        "<h2>Tests</h2>\n<div class=\"controls\">\
+         <input id=\"search\" type=\"search\" placeholder=\"Filter by name\">",
+    );
+    for (status, label) in [
+        ("all", "all"),
+        ("failed", "failed"),
+        ("passed", "passed"),
+        ("flaky", "flaky"),
+        ("skipped", "skipped"),
+        ("xfail", "xfail"),
+    ] {
+        let _ = write!(
+            html,
+            "<button data-status=\"{}\"{}>{}</button>",
+            status,
+            if status == "all" {
+                " class=\"selected\""
+            } else {
+                ""
+            },
+            label
+        );
+    }
+    html.push_str(
+        "</div>\n<table id=\"tests\">\n<thead><tr><th>Status</th><th>Test</th><th>Duration</th>",
+    );
+    if let Some(first) = earlier.first() {
+        let _ = write!(
+            html,
+            "<th>Change</th><th title=\"Oldest run first; the last column is this run\">History ({} runs, since {})</th>",
+            earlier.len() + 1,
+            escape(&format_timestamp(first.timestamp))
+        );
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+
+    for outcome in outcomes {
+        write_test(html, dir, outcome, earlier);
+    }
+    html.push_str("</tbody>\n</table>\n");
+}
+
+fn write_test(html: &mut String, dir: &Path, outcome: &TestOutcome, earlier: &[Run]) {
+    let path = outcome.test.path();
+    let status = status_label(outcome);
+    let _ = write!(
+        html,
+        "<tr id=\"{}\" class=\"test\" data-status=\"{}\" data-name=\"{}\">\
+         <td><span class=\"badge {}\">{}</span></td><td class=\"name\">{}",
+        anchor(&path),
+        status,
+        escape(&path.to_lowercase()),
+        status,
+        status,
+        escape(&path)
+    );
+    if outcome.attempts > 1 {
+        let _ = write!(
+            html,
+            " <span class=\"attempts\">({} attempts)</span>",
+            outcome.attempts
+        );
+    }
+    let _ = write!(
+        html,
+        "</td><td class=\"duration\">{}</td>",
+        seconds(outcome.duration)
+    );
+
+    if let Some(previous) = earlier.last() {
+        html.push_str("<td class=\"duration\">");
+        if let Some((_, before)) = previous.tests.get(&path) {
+            let now = outcome.duration.as_secs_f64();
+            let delta = now - before;
+            let class = if delta > 0.01 && now > before * 1.5 {
+                "slower"
+            } else if delta < -0.01 && now < before / 1.5 {
+                "faster"
+            } else {
+                "same"
+            };
+            let sign = if delta < 0.0 { "-" } else { "+" };
+            let _ = write!(
+                html,
+                "<span class=\"{}\">{}{}</span>",
+                class,
+                sign,
+                seconds(Duration::from_secs_f64(delta.abs()))
+            );
+        }
+        html.push_str("</td><td class=\"history\">");
+        for run in earlier {
+            match run.tests.get(&path) {
+                Some((status, duration)) => {
+                    let _ = write!(
+                        html,
+                        "<span class=\"cell {}\" title=\"{}: {} in {}\"></span>",
+                        escape(status),
+                        escape(&format_timestamp(run.timestamp)),
+                        escape(status),
+                        seconds(Duration::from_secs_f64(duration.max(0.0)))
+                    );
+                }
+                None => html.push_str("<span class=\"cell absent\" title=\"did not run\"></span>"),
+            }
+        }
+        let _ = write!(
+            html,
+            "<span class=\"cell {} current\" title=\"this run: {}\"></span></td>",
+            status, status
+        );
+    }
+    html.push_str("</tr>\n");
+
+    let details = details(dir, outcome);
+    if !details.is_empty() {
+        let _ = writeln!(
+            html,
+            "<tr class=\"details\" data-status=\"{}\" data-name=\"{}\"><td></td><td colspan=\"{}\">{}</td></tr>",
+            status,
+            escape(&path.to_lowercase()),
+            if earlier.is_empty() { 2 } else { 4 },
+            details
+        );
+    }
+}
+
+/// The failures, skip reason, steps, output, and attachments of a test, as
+/// HTML in the report written to `dir`.
+fn details(dir: &Path, outcome: &TestOutcome) -> String {
+    let mut html = String::new();
+    let test = outcome.test;
+    match &outcome.status {
+        TestStatus::Failed(failures) => {
+            for failure in failures {
+                write_failure(&mut html, failure);
+            }
+            let _ = write!(
+                html,
+                "<p>rerun: <code>{}</code></p>",
+                escape(&rerun::command(test))
+            );
+        }
+        TestStatus::Skipped(Some(reason)) => {
+            let _ = write!(html, "<p>skipped: {}</p>", escape(reason));
+        }
+        TestStatus::ExpectedFailure(reason) => {
+            let _ = write!(
+                html,
+                "<p>failed as expected{}</p>",
+                reason
+                    .as_deref()
+                    .map(|reason| format!(": {}", escape(reason)))
+                    .unwrap_or_default()
+            );
+        }
+        _ => {}
+    }
+    if !outcome.steps.is_empty() {
+        html.push_str("<table class=\"steps\">");
+        for step in &outcome.steps {
+            let _ = write!(
+                html,
+                "<tr class=\"{}\"><td style=\"padding-left: {:.1}em\">{}</td>\
+                 <td class=\"duration\">{}</td></tr>",
+                if step.failed { "failed" } else { "passed" },
+                0.6 + 1.2 * step.depth as f64,
+                escape(&step.name),
+                seconds(step.duration)
+            );
+        }
+        html.push_str("</table>");
+    }
+    if !outcome.output.is_empty() {
+        let _ = write!(
+            html,
+            "<details{}><summary>Captured output</summary><pre>{}</pre></details>",
+            if outcome.status.is_failure() {
+                " open"
+            } else {
+                ""
+            },
+            escape(&strip_ansi(&outcome.output))
+        );
+    }
+    if !outcome.attachments.is_empty() {
+        html.push_str("<ul class=\"attachments\">");
+        for attachment in &outcome.attachments {
+            let href = escape(&link(dir, &attachment.path));
+            let _ = write!(
+                html,
+                "<li><a href=\"{}\">{}</a>",
+                href,
+                escape(&attachment.name)
+            );
+            if is_image(&attachment.path) {
+                let _ = write!(
+                    html,
+                    "<br><a href=\"{}\"><img src=\"{}\" alt=\"{}\"></a>",
+                    href,
+                    href,
+                    escape(&attachment.name)
+                );
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+    }
+    if !html.is_empty() {
+        let _ = write!(
+            html,
+            "<p class=\"location\">defined at {}:{}</p>",
+            escape(test.file),
+            test.line
+        );
+    }
+    html
+}
+
+/// The URL of the file at `path` from the report written to `dir`: a
+/// relative one where both are on the same file system root, so that the
+/// report and the artifacts can be moved together.
+fn link(dir: &Path, path: &Path) -> String {
+    let (Ok(dir), Ok(path)) = (dir.canonicalize(), path.canonicalize()) else {
+        return path.display().to_string();
+    };
+    let dir: Vec<_> = dir.components().collect();
+    let target: Vec<_> = path.components().collect();
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return format!("file://{}", path.display());
+    }
+    let mut parts: Vec<String> = vec!["..".to_string(); dir.len() - common];
+    parts.extend(
+        target[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+fn is_image(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some("png" | "jpg" | "jpeg" | "gif" | "svg" | "webp")
+    )
+}
+
+fn write_failure(html: &mut String, failure: &TestFailure) {
+    html.push_str("<pre class=\"failure\">");
+    html.push_str(&highlight_diffs(&strip_ansi(&failure.description())));
+    if let Some(location) = &failure.location {
+        let _ = write!(html, "\n  at {}", escape(&location.to_string()));
+    }
+    html.push_str("</pre>");
+    if let Some(backtrace) = &failure.backtrace {
+        let _ = write!(
+            html,
+            "<details><summary>Backtrace</summary><pre>{}</pre></details>",
+            escape(backtrace.trim_end())
+        );
+    }
+}
+
+/// Escapes `message`, marking up the removed and added lines of the diffs it
+/// contains, which start with a `--- ` and a `+++ ` header line.
+fn highlight_diffs(message: &str) -> String {
+    let mut html = String::new();
+    let mut in_diff = false;
+    let mut lines = message.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with("--- ") && lines.peek().is_some_and(|next| next.starts_with("+++ ")) {
+            in_diff = true;
+        } else if in_diff
+            && !(line.starts_with(['-', '+', ' ']) || line == "..." || line.is_empty())
+        {
+            in_diff = false;
+        }
+        let class = match line.chars().next() {
+            Some('-') if in_diff => Some("removed"),
+            Some('+') if in_diff => Some("added"),
+            _ => None,
+        };
+        match class {
+            Some(class) => {
+                let _ = write!(html, "<span class=\"{}\">{}</span>", class, escape(line));
+            }
+            None => html.push_str(&escape(line)),
+        }
+        if lines.peek().is_some() {
+            html.push('\n');
+        }
+    }
+    html
+}
+
+fn seconds(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 1.0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.2}s", secs)
+    }
+}
+
+/// Formats seconds since the Unix epoch as a UTC date and time.
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let time = timestamp % 86_400;
+    // Civil date from days since 1970-01-01, after Howard Hinnant.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60
+    )
+}
+
+/// An `id` for the row of the test at `path`.
+fn anchor(path: &str) -> String {
+    let id: String = path
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("test-{}", id)
+}
+
+/// Escapes text for use in HTML attributes and content.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1.passed { color: #1a7f37; }
+h1.failed { color: #cf222e; }
+.summary .count { margin-right: 1em; }
+.count.failed { color: #cf222e; font-weight: bold; }
+.count.passed { color: #1a7f37; }
+.notes, .location, .attempts { color: #666; }
+.controls { margin: 1em 0; }
+.controls input { padding: 0.3em; margin-right: 1em; width: 20em; }
+.controls button { margin-right: 0.3em; padding: 0.3em 0.8em; border: 1px solid #ccc; background: #f6f8fa; cursor: pointer; }
+.controls button.selected { background: #0969da; color: white; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #eee; vertical-align: top; }
+tr.details td { border-bottom: 1px solid #ddd; }
+td.duration { text-align: right; white-space: nowrap; }
+td.name { font-family: monospace; }
+.badge { display: inline-block; min-width: 4em; text-align: center; border-radius: 3px; padding: 0 0.4em; color: white; background: #888; }
+.badge.passed { background: #1a7f37; }
+.badge.failed { background: #cf222e; }
+.badge.flaky { background: #bf8700; }
+.badge.new { background: #0969da; }
+.slower { color: #cf222e; }
+.faster { color: #1a7f37; }
+.same { color: #666; }
+.cell { display: inline-block; width: 0.8em; height: 1em; margin-right: 1px; background: #ddd; }
+.cell.passed { background: #1a7f37; }
+.cell.failed { background: #cf222e; }
+.cell.flaky { background: #bf8700; }
+.cell.skipped, .cell.xfail { background: #8c959f; }
+.cell.absent { background: transparent; border: 1px dashed #ccc; box-sizing: border-box; }
+.cell.current { outline: 2px solid #222; }
+pre { background: #f6f8fa; padding: 0.6em; overflow-x: auto; }
+pre.failure { border-left: 3px solid #cf222e; }
+.removed { color: #cf222e; }
+.added { color: #1a7f37; }
+ul.changes { list-style: none; padding: 0; }
+ul.attachments { padding-left: 1.2em; }
+table.steps { width: auto; margin: 0.4em 0; }
+table.steps td { border-bottom: none; padding-top: 0.1em; padding-bottom: 0.1em; }
+table.steps tr.failed td { color: #cf222e; }
+ul.attachments img { max-width: 100%; max-height: 24em; margin: 0.4em 0; border: 1px solid #ddd; }
+";
+
+const SCRIPT: &str = "
+(function () {
+  var search = document.getElementById('search');
+  var buttons = document.querySelectorAll('.controls button');
+  var status = 'all';
+  function update() {
+    var text = search.value.toLowerCase();
+    document.querySelectorAll('#tests tbody tr').forEach(function (row) {
+      var shown = (status === 'all' || row.dataset.status === status)
+        && row.dataset.name.indexOf(text) !== -1;
+      row.style.display = shown ? '' : 'none';
+    });
+  }
+  buttons.forEach(function (button) {
+    button.addEventListener('click', function () {
+      buttons.forEach(function (other) { other.classList.remove('selected'); });
+      button.classList.add('selected');
+      status = button.dataset.status;
+      update();
+    });
+  });
+  search.addEventListener('input', update);
+})();
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::TestDef;
+
+    fn outcome(name: &'static str, status: TestStatus, output: &str) -> TestOutcome {
+        let test = Box::leak(Box::new(TestDef::ran_elsewhere(
+            "krate::pages",
+            name,
+            "tests/pages.rs",
+            7,
+        )));
+        TestOutcome {
+            test,
+            status,
+            duration: Duration::from_millis(5),
+            output: output.to_string(),
+            attempts: 1,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Writes the report of `outcomes` to `dir`, returning the page.
+    fn report(dir: &Path, outcomes: &[TestOutcome]) -> String {
+        let mut summary = RunSummary::default();
+        for outcome in outcomes {
+            summary.record(outcome);
+        }
+        HtmlReporter::new(dir.to_path_buf()).on_run_end(outcomes, &summary);
+        fs::read_to_string(dir.join("index.html")).unwrap()
+    }
+
+    fn report_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tust-html-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn renders_each_status_with_names_and_output_escaped() {
+        let dir = report_dir("statuses");
+        let failure = TestFailure::new("expected <b> & \"c\"");
+        let html = report(
+            &dir,
+            &[
+                outcome("renders", TestStatus::Passed, ""),
+                outcome(
+                    "weird<T>",
+                    TestStatus::Failed(vec![failure]),
+                    "printed \x1b[31m<script>'x'</script>\x1b[0m\n",
+                ),
+                outcome(
+                    "waits",
+                    TestStatus::Skipped(Some("needs <docker>".to_string())),
+                    "",
+                ),
+            ],
+        );
+        assert!(html.contains("<title>tust report: FAILED</title>"));
+        assert!(html.contains("<span class=\"count passed\">1 passed</span>"));
+        assert!(html.contains("<span class=\"count failed\">1 failed</span>"));
+        assert!(html.contains("<span class=\"count skipped\">1 skipped</span>"));
+        assert!(html.contains(
+            "<tr id=\"test-pages--renders\" class=\"test\" data-status=\"passed\" \
+             data-name=\"pages::renders\"><td><span class=\"badge passed\">passed</span></td>\
+             <td class=\"name\">pages::renders</td><td class=\"duration\">5ms</td></tr>\n"
+        ));
+        assert!(html.contains(
+            "<tr id=\"test-pages--weird-T-\" class=\"test\" data-status=\"failed\" \
+             data-name=\"pages::weird&lt;t&gt;\"><td><span class=\"badge failed\">failed</span>\
+             </td><td class=\"name\">pages::weird&lt;T&gt;</td>"
+        ));
+        assert!(
+            html.contains("<pre class=\"failure\">expected &lt;b&gt; &amp; &quot;c&quot;</pre>")
+        );
+        assert!(html.contains(
+            "<details open><summary>Captured output</summary><pre>printed \
+             &lt;script&gt;&#39;x&#39;&lt;/script&gt;\n</pre></details>"
+        ));
+        assert!(html.contains("<p>skipped: needs &lt;docker&gt;</p>"));
+        assert!(html.contains("<p class=\"location\">defined at tests/pages.rs:7</p>"));
+        assert!(!html.contains("<b>") && !html.contains("<docker>"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn later_reports_compare_against_the_history() {
+        let dir = report_dir("history");
+        let failed = || TestStatus::Failed(vec![TestFailure::new("broken")]);
+        let first = report(
+            &dir,
+            &[
+                outcome("stays", TestStatus::Passed, ""),
+                outcome("fixed", failed(), ""),
+            ],
+        );
+        assert!(first.contains("<title>tust report: FAILED</title>"));
+        assert!(!first.contains("Since the previous run"));
+
+        let second = report(
+            &dir,
+            &[
+                outcome("stays", failed(), ""),
+                outcome("fixed", TestStatus::Passed, ""),
+                outcome("added", TestStatus::Passed, ""),
+            ],
+        );
+        assert!(second.contains(
+            "<li><span class=\"badge failed\">now failing</span> \
+             <a href=\"#test-pages--stays\">pages::stays</a></li>\n\
+             <li><span class=\"badge passed\">fixed</span> \
+             <a href=\"#test-pages--fixed\">pages::fixed</a></li>\n\
+             <li><span class=\"badge new\">new</span> \
+             <a href=\"#test-pages--added\">pages::added</a></li>\n"
+        ));
+        assert!(second.contains("<th>Change</th><th title=\"Oldest run first; the last column is this run\">History (2 runs"));
+        assert!(second.contains("<span class=\"same\">+0ms</span>"));
+        assert!(second.contains("<span class=\"cell absent\" title=\"did not run\"></span>"));
+
+        let history = read_history(&dir.join("history.json"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[0].tests["pages::fixed"],
+            ("failed".to_string(), 0.005)
+        );
+        assert_eq!(history[1].tests["pages::fixed"].0, "passed");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn diffs_in_failures_are_highlighted() {
+        assert_eq!(
+            highlight_diffs("values differ\n--- left\n+++ right\n-a <\n+b\n same\nafter"),
+            "values differ\n<span class=\"removed\">--- left</span>\n\
+             <span class=\"added\">+++ right</span>\n<span class=\"removed\">-a &lt;</span>\n\
+             <span class=\"added\">+b</span>\n same\nafter"
+        );
+        assert_eq!(highlight_diffs("-not a diff"), "-not a diff");
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(
+            format_timestamp(951_782_400 + 3_660),
+            "2000-02-29 01:01 UTC"
+        );
+        assert_eq!(seconds(Duration::from_millis(1_500)), "1.50s");
+    }
+}
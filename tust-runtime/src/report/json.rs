@@ -0,0 +1,268 @@
+//! Newline-delimited JSON events for IDEs and other tools.
+//!
+//! The events follow libtest's unstable `--format json` output, so tools
+//! that understand it can consume tust runs unchanged:
+//!
+//! ```text
+//! { "type": "suite", "event": "started", "test_count": 2 }
+//! { "type": "test", "event": "started", "name": "parser::empty" }
+//! { "type": "test", "name": "parser::empty", "event": "ok", "exec_time": 0.001 }
+//! { "type": "suite", "event": "ok", "passed": 2, "failed": 0, ... }
+//! ```
+//!
+//! Skipped tests are reported as `ignored`, with their reason in `message`,
+//! and those that skipped themselves at run time are marked
+//! `"skipped_at_run_time": true`; the suite event counts them under
+//! `skipped_at_run_time` as well as `ignored`.
+//! A test marked `#[xfail]` that failed as expected is reported as `ok` and
+//! marked `"xfail": true`.
+//!
+//! A test that needed retries carries the number of times it ran in
+//! `attempts`, and a passed one is additionally marked `"flaky": true`. A
+//! test in quarantine is marked `"quarantined": true`; the suite event
+//! counts its failure under `quarantined` rather than `failed`, and lists
+//! the quarantined tests that have passed long enough to leave the
+//! quarantine under `stable_quarantined`.
+//!
+//! The final suite event carries the run `seed` if any test asked for
+//! randomness, and the `shuffle_seed` if the tests ran in shuffled order;
+//! each is `null` otherwise. Its `not_run` counts the tests that did not run
+//! because `--max-failures` was reached or the run was interrupted, and its
+//! `interrupted` says whether it was.
+//!
+//! Benchmarks measured with `--bench` are reported after the tests, before
+//! the final suite event, as libtest's are, with their times in
+//! nanoseconds:
+//!
+//! ```text
+//! { "type": "bench", "name": "parser::large", "median": 1210000, "deviation": 40120, ... }
+//! ```
+//!
+//! Tests flagged by a strict mode check set to warn are reported after the
+//! benchmarks, one event per warning:
+//!
+//! ```text
+//! { "type": "strict", "name": "pool::grows", "check": "threads", "message": "left 1 thread ...", "spawned_at": [] }
+//! ```
+//!
+//! `spawned_at` holds the backtraces of where the Tokio tasks a test left
+//! running were spawned, when Tokio records them.
+//!
+//! Each load of a [`SharedData`](crate::shared::SharedData) follows, with
+//! its time in seconds and the test that asked for the data:
+//!
+//! ```text
+//! { "type": "shared_data", "name": "corpus", "load_time": 4.182, "bytes": null, "mapped": false, "test": "classifier::classifies" }
+//! ```
+//!
+//! A failed test's event carries its captured output and failure report in
+//! `stdout`, as libtest does, and additionally lists each failure under
+//! `failures` with its message, location, `metadata` as label and value
+//! pairs, its `kind`, one of `assertion`, `check` (a soft check), `panic`,
+//! and `error` (such as a timeout), and, for a failed comparison, the
+//! compared values under `diff`, along with the command that reruns only
+//! that test under `rerun`; see [`crate::rerun`].
+//! With `--show-output`, other tests' events carry their captured output in
+//! `stdout` too. A test that attached files lists them, by name and path,
+//! under `attachments`, and a test that ran steps lists them under `steps`,
+//! in the order they started, with how many steps each ran within:
+//!
+//! ```text
+//! { "name": "load data", "depth": 0, "exec_time": 1.204, "failed": false }
+//! ```
+
+use std::io::Write;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::Reporter;
+use crate::bench::BenchResult;
+use crate::capture::Stdout;
+use crate::context;
+use crate::outcome::{RunSummary, TestOutcome, TestStatus};
+use crate::quarantine;
+use crate::registry::TestDef;
+use crate::rerun;
+use crate::shared::DataLoad;
+use crate::strict::StrictWarning;
+
+/// Prints one JSON object per line to stdout.
+pub(crate) struct JsonReporter {
+    out: Stdout,
+}
+
+impl JsonReporter {
+    pub(crate) fn new() -> Self {
+        JsonReporter { out: Stdout }
+    }
+
+    fn emit(&mut self, event: Value) {
+        let _ = self.out.write_all(format!("{}\n", event).as_bytes());
+        let _ = self.out.flush();
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn on_run_start(&mut self, tests: &[&'static TestDef]) {
+        self.emit(json!({
+            "type": "suite",
+            "event": "started",
+            "test_count": tests.len(),
+        }));
+    }
+
+    fn on_test_start(&mut self, test: &'static TestDef) {
+        self.emit(json!({
+            "type": "test",
+            "event": "started",
+            "name": test.path(),
+        }));
+    }
+
+    fn on_test_finish(&mut self, outcome: &TestOutcome) {
+        let mut event = json!({
+            "type": "test",
+            "name": outcome.test.path(),
+            "event": "ok",
+            "exec_time": outcome.duration.as_secs_f64(),
+        });
+        if outcome.attempts > 1 {
+            event["attempts"] = json!(outcome.attempts);
+        }
+        if outcome.is_flaky() {
+            event["flaky"] = json!(true);
+        }
+        if quarantine::is_quarantined(outcome.test) {
+            event["quarantined"] = json!(true);
+        }
+        if !outcome.attachments.is_empty() {
+            event["attachments"] = context::to_json(&outcome.attachments);
+        }
+        if !outcome.steps.is_empty() {
+            event["steps"] = context::steps_to_json(&outcome.steps);
+        }
+        match &outcome.status {
+            TestStatus::Skipped(reason) => {
+                event["event"] = json!("ignored");
+                if outcome.is_skipped_at_run_time() {
+                    event["skipped_at_run_time"] = json!(true);
+                }
+                if let Some(reason) = reason {
+                    event["message"] = json!(reason);
+                }
+            }
+            TestStatus::ExpectedFailure(reason) => {
+                event["xfail"] = json!(true);
+                if let Some(reason) = reason {
+                    event["message"] = json!(reason);
+                }
+            }
+            _ => {}
+        }
+        if let TestStatus::Failed(failures) = &outcome.status {
+            let report: Vec<String> = failures.iter().map(ToString::to_string).collect();
+            let failures: Vec<Value> = failures
+                .iter()
+                .map(|failure| {
+                    json!({
+                        "message": failure.message,
+                        "file": failure.location.as_ref().map(|l| &l.file),
+                        "line": failure.location.as_ref().map(|l| l.line),
+                        "column": failure.location.as_ref().map(|l| l.column),
+                        "diff": failure.diff.as_ref().map(|diff| json!({
+                            "labels": [diff.labels.0, diff.labels.1],
+                            "left": diff.left,
+                            "right": diff.right,
+                        })),
+                        "metadata": failure.metadata,
+                        "kind": failure.kind.name(),
+                    })
+                })
+                .collect();
+            event["event"] = json!("failed");
+            let mut stdout = outcome.output.clone();
+            if !stdout.is_empty() && !stdout.ends_with('\n') {
+                stdout.push('\n');
+            }
+            stdout.push_str(&report.join("\n\n"));
+            stdout.push('\n');
+            event["stdout"] = json!(stdout);
+            event["failures"] = json!(failures);
+            event["rerun"] = json!(rerun::command(outcome.test));
+        } else if !outcome.output.is_empty() {
+            event["stdout"] = json!(format!("{}\n", outcome.output));
+        }
+        self.emit(event);
+    }
+
+    fn on_bench_results(&mut self, results: &[BenchResult]) {
+        let nanos = |duration: Duration| duration.as_nanos() as u64;
+        for result in results {
+            let stats = &result.stats;
+            self.emit(json!({
+                "type": "bench",
+                "name": result.test.path(),
+                "median": nanos(stats.median),
+                "deviation": nanos(stats.std_dev),
+                "mean": nanos(stats.mean),
+                "p95": nanos(stats.p95),
+                "min": nanos(stats.min),
+                "max": nanos(stats.max),
+                "samples": stats.samples,
+                "iterations": stats.iterations,
+                "outliers": stats.mild_outliers + stats.severe_outliers,
+                "baseline": result.baseline.map(nanos),
+                "change": result.change(),
+                "regressed": result.regressed(),
+            }));
+        }
+    }
+
+    fn on_strict_warnings(&mut self, warnings: &[StrictWarning]) {
+        for warning in warnings {
+            self.emit(json!({
+                "type": "strict",
+                "name": warning.test.path(),
+                "check": warning.check,
+                "message": warning.message,
+                "spawned_at": warning.spawned_at,
+            }));
+        }
+    }
+
+    fn on_shared_data(&mut self, loads: &[DataLoad]) {
+        for load in loads {
+            self.emit(json!({
+                "type": "shared_data",
+                "name": load.name,
+                "load_time": load.duration.as_secs_f64(),
+                "bytes": load.bytes,
+                "mapped": load.mapped,
+                "test": load.test.map(TestDef::path),
+            }));
+        }
+    }
+
+    fn on_run_end(&mut self, _outcomes: &[TestOutcome], summary: &RunSummary) {
+        self.emit(json!({
+            "type": "suite",
+            "event": if summary.is_success() { "ok" } else { "failed" },
+            "passed": summary.passed,
+            "failed": summary.failed,
+            "quarantined": summary.quarantined,
+            "stable_quarantined": summary.stable_quarantined,
+            "flaky": summary.flaky,
+            "ignored": summary.skipped,
+            "skipped_at_run_time": summary.skipped_at_run_time,
+            "xfailed": summary.expected_failures,
+            "measured": 0,
+            "filtered_out": summary.filtered_out,
+            "not_run": summary.not_run,
+            "interrupted": summary.interrupted,
+            "exec_time": summary.duration.as_secs_f64(),
+            "seed": summary.seed,
+            "shuffle_seed": summary.shuffle_seed,
+        }));
+    }
+}
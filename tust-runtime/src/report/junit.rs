@@ -0,0 +1,405 @@
+//! JUnit XML reports, as consumed by Jenkins, GitLab, and other CI systems.
+//!
+//! One `<testsuite>` is written per crate, holding one `<testcase>` per test
+//! that ran. A failed test gets a single `<failure>` element: its `message`
+//! attribute is the first line of the first failure, and its text holds every
+//! failure in full, with locations and backtraces. A test that was retried
+//! records its number of attempts in an `attempts` property, and a flaky one
+//! additionally a `flaky` property. A test in quarantine has a `quarantined`
+//! property, and its failure is not counted in the `failures` attributes. A
+//! failed test has a `rerun` property holding the command that reruns only
+//! it; see [`crate::rerun`]. The `type` of the `<failure>` element is the
+//! kind of the first failure: `assertion` for a failed assertion, `check`
+//! for a failed soft check, `panic` for another panic, and `error` for
+//! anything else, such as a timeout. Each step a test ran has a `step`
+//! property, such as `import > load data (1.204s, failed)`. Skipped tests
+//! get a `<skipped>` element, and tests that skipped themselves at run time
+//! also a `skipped_at_run_time` property. Tests marked `#[xfail]` that
+//! failed as expected count as passed, with an `xfail` property and a
+//! `failed as expected` note in `<system-out>`, followed by the reason
+//! given, if any.
+//! Captured output goes in `<system-out>`, for failed tests and, with
+//! `--show-output`, for every test, followed by a `[[ATTACHMENT|path]]` line
+//! for each file the test attached, which Jenkins and GitLab show with the
+//! test.
+//! If any test asked for randomness, every `<testsuite>` records the run seed
+//! in a `seed` property, and if the tests ran in shuffled order, the shuffle
+//! seed in a `shuffle_seed` property.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{strip_ansi, Reporter};
+use crate::outcome::{FailureKind, RunSummary, TestFailure, TestOutcome, TestStatus};
+use crate::quarantine;
+use crate::rerun;
+
+/// Writes a JUnit XML report to a file once the run ends.
+pub(crate) struct JunitReporter {
+    path: PathBuf,
+}
+
+impl JunitReporter {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        JunitReporter { path }
+    }
+}
+
+impl Reporter for JunitReporter {
+    fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary) {
+        let xml = render(outcomes, summary);
+        let written = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                fs::create_dir_all(dir).and_then(|()| fs::write(&self.path, xml))
+            }
+            _ => fs::write(&self.path, xml),
+        };
+        if let Err(err) = written {
+            eprintln!(
+                "warning: failed to write JUnit report to {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+fn render(outcomes: &[TestOutcome], summary: &RunSummary) -> String {
+    let mut suites: BTreeMap<&str, Vec<&TestOutcome>> = BTreeMap::new();
+    for outcome in outcomes {
+        let krate = outcome
+            .test
+            .module_path
+            .split("::")
+            .next()
+            .unwrap_or_default();
+        suites.entry(krate).or_default().push(outcome);
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"tust\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{}\">",
+        outcomes.len(),
+        summary.failed,
+        summary.skipped,
+        seconds(summary.duration),
+    );
+
+    for (name, outcomes) in suites {
+        let failures = outcomes
+            .iter()
+            .filter(|o| o.status.is_failure() && !quarantine::is_quarantined(o.test))
+            .count();
+        let skipped = outcomes
+            .iter()
+            .filter(|o| matches!(o.status, TestStatus::Skipped(_)))
+            .count();
+        let time: Duration = outcomes.iter().map(|o| o.duration).sum();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{}\">",
+            escape(name),
+            outcomes.len(),
+            failures,
+            skipped,
+            seconds(time),
+        );
+        let properties: Vec<_> = [
+            ("seed", summary.seed),
+            ("shuffle_seed", summary.shuffle_seed),
+        ]
+        .into_iter()
+        .filter_map(|(name, seed)| Some((name, seed?)))
+        .collect();
+        if !properties.is_empty() {
+            xml.push_str("    <properties>\n");
+            for (name, seed) in properties {
+                let _ = writeln!(
+                    xml,
+                    "      <property name=\"{}\" value=\"{}\"/>",
+                    name, seed
+                );
+            }
+            xml.push_str("    </properties>\n");
+        }
+        for outcome in outcomes {
+            write_case(&mut xml, outcome);
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn write_case(xml: &mut String, outcome: &TestOutcome) {
+    let test = outcome.test;
+    let _ = write!(
+        xml,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\" file=\"{}\" line=\"{}\"",
+        escape(test.name),
+        escape(test.module_path),
+        seconds(outcome.duration),
+        escape(test.file),
+        test.line,
+    );
+
+    let quarantined = quarantine::is_quarantined(test);
+    if outcome.attempts <= 1
+        && outcome.status == TestStatus::Passed
+        && outcome.output.is_empty()
+        && outcome.attachments.is_empty()
+        && outcome.steps.is_empty()
+        && !quarantined
+    {
+        xml.push_str("/>\n");
+        return;
+    }
+    xml.push_str(">\n");
+    let failed = matches!(outcome.status, TestStatus::Failed(_));
+    let xfail = matches!(outcome.status, TestStatus::ExpectedFailure(_));
+    let skipped_at_run_time = outcome.is_skipped_at_run_time();
+    if outcome.attempts > 1
+        || quarantined
+        || failed
+        || xfail
+        || skipped_at_run_time
+        || !outcome.steps.is_empty()
+    {
+        xml.push_str("      <properties>\n");
+        if outcome.attempts > 1 {
+            let _ = writeln!(
+                xml,
+                "        <property name=\"attempts\" value=\"{}\"/>",
+                outcome.attempts
+            );
+        }
+        if outcome.is_flaky() {
+            xml.push_str("        <property name=\"flaky\" value=\"true\"/>\n");
+        }
+        if quarantined {
+            xml.push_str("        <property name=\"quarantined\" value=\"true\"/>\n");
+        }
+        if xfail {
+            xml.push_str("        <property name=\"xfail\" value=\"true\"/>\n");
+        }
+        if skipped_at_run_time {
+            xml.push_str("        <property name=\"skipped_at_run_time\" value=\"true\"/>\n");
+        }
+        if failed {
+            let _ = writeln!(
+                xml,
+                "        <property name=\"rerun\" value=\"{}\"/>",
+                escape(&rerun::command(test))
+            );
+        }
+        let mut within: Vec<&str> = Vec::new();
+        for step in &outcome.steps {
+            within.truncate(step.depth);
+            within.push(&step.name);
+            let _ = writeln!(
+                xml,
+                "        <property name=\"step\" value=\"{} ({}s{})\"/>",
+                escape(&within.join(" > ")),
+                seconds(step.duration),
+                if step.failed { ", failed" } else { "" }
+            );
+        }
+        xml.push_str("      </properties>\n");
+    }
+    if let TestStatus::Skipped(reason) = &outcome.status {
+        write_skipped(xml, reason.as_deref());
+    }
+    if let TestStatus::Failed(failures) = &outcome.status {
+        let message = failures
+            .first()
+            .and_then(|failure| {
+                strip_ansi(&failure.message)
+                    .lines()
+                    .next()
+                    .map(str::to_string)
+            })
+            .unwrap_or_default();
+        let details = failures
+            .iter()
+            .map(describe)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let kind = failures.first().map_or(FailureKind::Error, |f| f.kind);
+        let _ = writeln!(
+            xml,
+            "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+            escape(&message),
+            kind.name(),
+            escape(&details),
+        );
+    }
+    let mut system_out = match &outcome.status {
+        TestStatus::ExpectedFailure(reason) => {
+            let mut note = match reason {
+                Some(reason) => format!("failed as expected: {}", reason),
+                None => "failed as expected".to_string(),
+            };
+            if !outcome.output.is_empty() {
+                note.push('\n');
+            }
+            note
+        }
+        _ => String::new(),
+    };
+    system_out.push_str(&strip_ansi(&outcome.output));
+    for attachment in &outcome.attachments {
+        if !system_out.is_empty() {
+            system_out.push('\n');
+        }
+        let _ = write!(system_out, "[[ATTACHMENT|{}]]", attachment.path.display());
+    }
+    if !system_out.is_empty() {
+        let _ = writeln!(
+            xml,
+            "      <system-out>{}</system-out>",
+            escape(&system_out)
+        );
+    }
+    xml.push_str("    </testcase>\n");
+}
+
+fn write_skipped(xml: &mut String, message: Option<&str>) {
+    match message {
+        Some(message) => {
+            let _ = writeln!(xml, "      <skipped message=\"{}\"/>", escape(message));
+        }
+        None => xml.push_str("      <skipped/>\n"),
+    }
+}
+
+fn describe(failure: &TestFailure) -> String {
+    let mut text = strip_ansi(&failure.to_string());
+    if let Some(backtrace) = &failure.backtrace {
+        let _ = write!(text, "\n\nstack backtrace:\n{}", backtrace.trim_end());
+    }
+    text
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// Escapes text for use in XML attributes and content, dropping characters
+/// that XML 1.0 cannot represent.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::FailureKind;
+    use crate::registry::TestDef;
+
+    fn outcome(name: &'static str, status: TestStatus) -> TestOutcome {
+        let test = Box::leak(Box::new(TestDef::ran_elsewhere("krate", name, "t.rs", 1)));
+        TestOutcome {
+            test,
+            status,
+            duration: Duration::ZERO,
+            output: String::new(),
+            attempts: 1,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn failure(kind: FailureKind) -> TestFailure {
+        TestFailure {
+            kind,
+            ..TestFailure::new("failed")
+        }
+    }
+
+    #[test]
+    fn failure_type_is_the_kind_of_the_first_failure() {
+        let outcomes = [
+            outcome(
+                "asserts",
+                TestStatus::Failed(vec![failure(FailureKind::Assertion)]),
+            ),
+            outcome(
+                "checks",
+                TestStatus::Failed(vec![
+                    failure(FailureKind::Check),
+                    failure(FailureKind::Panic),
+                ]),
+            ),
+            outcome(
+                "panics",
+                TestStatus::Failed(vec![failure(FailureKind::Panic)]),
+            ),
+        ];
+        let mut summary = RunSummary::default();
+        for outcome in &outcomes {
+            summary.record(outcome);
+        }
+        let xml = render(&outcomes, &summary);
+        let types: Vec<_> = xml
+            .match_indices("type=\"")
+            .map(|(at, _)| xml[at + 6..].split('"').next().unwrap())
+            .collect();
+        assert_eq!(types, ["assertion", "check", "panic"]);
+    }
+
+    #[test]
+    fn expected_failures_pass_with_a_note() {
+        let outcomes = [outcome(
+            "xfails",
+            TestStatus::ExpectedFailure(Some("known bug".to_string())),
+        )];
+        let mut summary = RunSummary::default();
+        summary.record(&outcomes[0]);
+        let xml = render(&outcomes, &summary);
+        assert!(xml.contains("failures=\"0\" errors=\"0\" skipped=\"0\""));
+        assert!(!xml.contains("<skipped"));
+        assert!(xml.contains("<property name=\"xfail\" value=\"true\"/>"));
+        assert!(xml.contains("<system-out>failed as expected: known bug</system-out>"));
+    }
+
+    #[test]
+    fn skipped_tests_are_counted_and_marked() {
+        let outcomes = [outcome(
+            "skips",
+            TestStatus::Skipped(Some("not ready".to_string())),
+        )];
+        let mut summary = RunSummary::default();
+        summary.record(&outcomes[0]);
+        let xml = render(&outcomes, &summary);
+        assert!(xml.contains("skipped=\"1\""));
+        assert!(xml.contains("<skipped message=\"not ready\"/>"));
+    }
+
+    #[test]
+    fn escapes_markup_and_drops_control_characters() {
+        assert_eq!(
+            escape("a < b && \"c\"\u{1}"),
+            "a &lt; b &amp;&amp; &quot;c&quot;"
+        );
+    }
+}
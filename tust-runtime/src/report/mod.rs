@@ -0,0 +1,250 @@
+//! Reporters turn test lifecycle events into output.
+//!
+//! Besides the built-in console, JSON, TAP, JUnit, HTML, timings, and GitHub
+//! Actions reporters, the runner feeds every event to the reporters that test
+//! binaries register with [`register_reporter!`](crate::register_reporter),
+//! so a run can also notify a chat channel or emit metrics:
+//!
+//! ```
+//! use tust_runtime::report::Reporter;
+//! use tust_runtime::{RunSummary, TestOutcome};
+//!
+//! #[derive(Default)]
+//! struct FailureCounter {
+//!     failures: usize,
+//! }
+//!
+//! impl Reporter for FailureCounter {
+//!     fn on_test_finish(&mut self, outcome: &TestOutcome) {
+//!         self.failures += usize::from(outcome.status.is_failure());
+//!     }
+//!
+//!     fn on_run_end(&mut self, _outcomes: &[TestOutcome], summary: &RunSummary) {
+//!         eprintln!("{} failures in {:.1}s", self.failures, summary.duration.as_secs_f64());
+//!     }
+//! }
+//!
+//! tust_runtime::register_reporter!(FailureCounter::default);
+//! ```
+//!
+//! Registered reporters run alongside the output chosen with `--format` and
+//! the reports requested with `--report`, in no particular order among
+//! themselves. Tests run by libtest, without `harness = false`, report to
+//! libtest only.
+
+mod console;
+mod github;
+mod html;
+mod json;
+mod junit;
+mod tap;
+mod timings;
+mod trends;
+
+pub(crate) use console::{ConsoleReporter, Style};
+pub(crate) use github::GithubReporter;
+pub(crate) use html::HtmlReporter;
+pub(crate) use json::JsonReporter;
+pub(crate) use junit::JunitReporter;
+pub(crate) use tap::TapReporter;
+pub(crate) use timings::TimingsReporter;
+pub(crate) use trends::TrendsReporter;
+
+use std::path::PathBuf;
+
+use crate::bench::BenchResult;
+use crate::outcome::{RunSummary, TestOutcome};
+use crate::registry::TestDef;
+use crate::shared::DataLoad;
+use crate::snapshot::SnapshotChange;
+use crate::strict::StrictWarning;
+
+/// Receives events as a run progresses.
+///
+/// Every method is called on the thread that started the run, in the order
+/// documented on each; only [`on_test_finish`](Reporter::on_test_finish) and
+/// [`on_run_end`](Reporter::on_run_end) must be implemented.
+pub trait Reporter {
+    /// Called once with every test selected to run.
+    fn on_run_start(&mut self, _tests: &[&'static TestDef]) {}
+
+    /// Called as each test starts, before its body runs, in the order in
+    /// which tests start; under parallel execution, several tests start
+    /// before the first finishes. A test skipped without running starts just
+    /// before it is skipped.
+    fn on_test_start(&mut self, _test: &'static TestDef) {}
+
+    /// Called as soon as a test has finished, in the order in which tests
+    /// finish, for progress displays. Under parallel execution, this may be
+    /// long before the test's [`on_test_finish`](Reporter::on_test_finish).
+    fn on_progress(&mut self, _test: &'static TestDef) {}
+
+    /// Called when a test has finished executing.
+    fn on_test_finish(&mut self, outcome: &TestOutcome);
+
+    /// Called once after every test has finished, before
+    /// [`on_run_end`](Reporter::on_run_end), with the snapshots written
+    /// during the run.
+    fn on_snapshot_changes(&mut self, _changes: &[SnapshotChange]) {}
+
+    /// Called once after every test has finished, before
+    /// [`on_run_end`](Reporter::on_run_end), with the golden files
+    /// rewritten during the run.
+    fn on_golden_changes(&mut self, _paths: &[PathBuf]) {}
+
+    /// Called once after every test has finished, before
+    /// [`on_run_end`](Reporter::on_run_end), with the benchmarks measured
+    /// during the run; see [`crate::bench`].
+    fn on_bench_results(&mut self, _results: &[BenchResult]) {}
+
+    /// Called once after every test has finished, before
+    /// [`on_run_end`](Reporter::on_run_end), with the tests strict mode
+    /// warned about; see [`crate::strict`].
+    fn on_strict_warnings(&mut self, _warnings: &[StrictWarning]) {}
+
+    /// Called once after every test has finished, before
+    /// [`on_run_end`](Reporter::on_run_end), with the shared data sets
+    /// loaded during the run; see [`crate::shared`].
+    fn on_shared_data(&mut self, _loads: &[DataLoad]) {}
+
+    /// Called once after every test has finished.
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary);
+}
+
+/// A reporter registered with [`register_reporter!`](crate::register_reporter).
+#[doc(hidden)]
+pub struct ReporterDef {
+    /// Creates the reporter when a run starts.
+    pub create: fn() -> Box<dyn Reporter>,
+}
+
+inventory::collect!(ReporterDef);
+
+/// Creates one of each registered reporter.
+pub(crate) fn registered() -> Vec<Box<dyn Reporter>> {
+    inventory::iter::<ReporterDef>
+        .into_iter()
+        .map(|def| (def.create)())
+        .collect()
+}
+
+/// Registers a [`Reporter`] for every run of the test binary by the tust
+/// runner. Takes a function or closure creating the reporter, which is
+/// called once when the run starts.
+///
+/// ```
+/// # use tust_runtime::report::Reporter;
+/// # use tust_runtime::{RunSummary, TestOutcome};
+/// struct Metrics {
+///     endpoint: String,
+/// }
+///
+/// # impl Reporter for Metrics {
+/// #     fn on_test_finish(&mut self, _: &TestOutcome) {}
+/// #     fn on_run_end(&mut self, _: &[TestOutcome], _: &RunSummary) {}
+/// # }
+/// tust_runtime::register_reporter!(|| Metrics {
+///     endpoint: std::env::var("METRICS_URL").unwrap_or_default(),
+/// });
+/// ```
+#[macro_export]
+macro_rules! register_reporter {
+    ($create:expr $(,)?) => {
+        const _: () = {
+            fn create() -> ::std::boxed::Box<dyn $crate::report::Reporter> {
+                ::std::boxed::Box::new(($create)())
+            }
+
+            $crate::__private::inventory::submit! {
+                $crate::report::ReporterDef { create }
+            }
+        };
+    };
+}
+
+/// Forwards every event to each of several reporters, in order.
+pub(crate) struct Reporters(pub(crate) Vec<Box<dyn Reporter>>);
+
+impl Reporter for Reporters {
+    fn on_run_start(&mut self, tests: &[&'static TestDef]) {
+        for reporter in &mut self.0 {
+            reporter.on_run_start(tests);
+        }
+    }
+
+    fn on_test_start(&mut self, test: &'static TestDef) {
+        for reporter in &mut self.0 {
+            reporter.on_test_start(test);
+        }
+    }
+
+    fn on_progress(&mut self, test: &'static TestDef) {
+        for reporter in &mut self.0 {
+            reporter.on_progress(test);
+        }
+    }
+
+    fn on_test_finish(&mut self, outcome: &TestOutcome) {
+        for reporter in &mut self.0 {
+            reporter.on_test_finish(outcome);
+        }
+    }
+
+    fn on_snapshot_changes(&mut self, changes: &[SnapshotChange]) {
+        for reporter in &mut self.0 {
+            reporter.on_snapshot_changes(changes);
+        }
+    }
+
+    fn on_golden_changes(&mut self, paths: &[PathBuf]) {
+        for reporter in &mut self.0 {
+            reporter.on_golden_changes(paths);
+        }
+    }
+
+    fn on_bench_results(&mut self, results: &[BenchResult]) {
+        for reporter in &mut self.0 {
+            reporter.on_bench_results(results);
+        }
+    }
+
+    fn on_strict_warnings(&mut self, warnings: &[StrictWarning]) {
+        for reporter in &mut self.0 {
+            reporter.on_strict_warnings(warnings);
+        }
+    }
+
+    fn on_shared_data(&mut self, loads: &[DataLoad]) {
+        for reporter in &mut self.0 {
+            reporter.on_shared_data(loads);
+        }
+    }
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], summary: &RunSummary) {
+        for reporter in &mut self.0 {
+            reporter.on_run_end(outcomes, summary);
+        }
+    }
+}
+
+/// Removes the ANSI color sequences that diffs and assertion messages may
+/// contain, for reports that are not printed to a terminal.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // A CSI sequence ends with a byte in `@`..=`~`.
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
@@ -0,0 +1,211 @@
+//! Test Anything Protocol (TAP) version 14 output.
+//!
+//! Each test is a test point named after its path. A failed test point is
+//! followed by a YAML diagnostic block holding every failure:
+//!
+//! A skipped test point carries a `# SKIP` directive, and a test marked
+//! `#[xfail]` that failed as expected is `not ok` with a `# TODO` directive,
+//! which TAP consumers do not count as a failure. So is a failed test in
+//! quarantine, with a `# TODO quarantined` directive.
+//!
+//! A test that needed retries also reports its `attempts`, and with
+//! `--show-output` a passed test reports its captured `output`; a passed test
+//! point has a diagnostic block only for these. If any test asked for
+//! randomness, a comment before the summary gives the run seed, and if the
+//! tests ran in shuffled order, another gives the shuffle seed. A run stopped
+//! by `--max-failures` ends with a `Bail out!` after the summary.
+//!
+//! ```text
+//! TAP version 14
+//! 1..2
+//! ok 1 - parser::empty
+//! not ok 2 - parser::nested
+//!   ---
+//!   duration_ms: 0.412
+//!   failures:
+//!     - message: "assertion `depth == 2` failed"
+//!       at:
+//!         file: "src/parser.rs"
+//!         line: 120
+//!         column: 9
+//!   ...
+//! ```
+
+use std::io::Write;
+
+use super::Reporter;
+use crate::capture::Stdout;
+use crate::outcome::{RunSummary, TestOutcome, TestStatus};
+use crate::quarantine;
+use crate::registry::TestDef;
+
+/// Prints a TAP stream to stdout.
+pub(crate) struct TapReporter {
+    out: Stdout,
+    next: usize,
+}
+
+impl TapReporter {
+    pub(crate) fn new() -> Self {
+        TapReporter {
+            out: Stdout,
+            next: 1,
+        }
+    }
+}
+
+impl Reporter for TapReporter {
+    fn on_run_start(&mut self, tests: &[&'static TestDef]) {
+        let _ = writeln!(self.out, "TAP version 14\n1..{}", tests.len());
+        let _ = self.out.flush();
+    }
+
+    fn on_test_finish(&mut self, outcome: &TestOutcome) {
+        let number = self.next;
+        self.next += 1;
+        let name = escape_description(&outcome.test.path());
+        let out = &mut self.out;
+
+        match &outcome.status {
+            TestStatus::Passed => {
+                let _ = writeln!(out, "ok {} - {}", number, name);
+                if outcome.is_flaky() || !outcome.output.is_empty() {
+                    let _ = writeln!(out, "  ---");
+                    if outcome.is_flaky() {
+                        let _ = writeln!(out, "  attempts: {}", outcome.attempts);
+                    }
+                    if !outcome.output.is_empty() {
+                        let _ = writeln!(out, "  output: {}", yaml_string(&outcome.output));
+                    }
+                    let _ = writeln!(out, "  ...");
+                }
+            }
+            TestStatus::Skipped(reason) => {
+                let _ = writeln!(out, "ok {} - {} # SKIP{}", number, name, directive(reason));
+            }
+            TestStatus::ExpectedFailure(reason) => {
+                let _ = writeln!(
+                    out,
+                    "not ok {} - {} # TODO{}",
+                    number,
+                    name,
+                    directive(reason)
+                );
+            }
+            TestStatus::Failed(failures) => {
+                let directive = if quarantine::is_quarantined(outcome.test) {
+                    " # TODO quarantined"
+                } else {
+                    ""
+                };
+                let _ = writeln!(out, "not ok {} - {}{}", number, name, directive);
+                let _ = writeln!(out, "  ---");
+                let _ = writeln!(
+                    out,
+                    "  duration_ms: {:.3}",
+                    outcome.duration.as_secs_f64() * 1000.0
+                );
+                if outcome.attempts > 1 {
+                    let _ = writeln!(out, "  attempts: {}", outcome.attempts);
+                }
+                let _ = writeln!(out, "  failures:");
+                for failure in failures {
+                    let _ = writeln!(
+                        out,
+                        "    - message: {}",
+                        yaml_string(&failure.description())
+                    );
+                    if let Some(location) = &failure.location {
+                        let _ = writeln!(out, "      at:");
+                        let _ = writeln!(out, "        file: {}", yaml_string(&location.file));
+                        let _ = writeln!(out, "        line: {}", location.line);
+                        let _ = writeln!(out, "        column: {}", location.column);
+                    }
+                }
+                if !outcome.output.is_empty() {
+                    let _ = writeln!(out, "  output: {}", yaml_string(&outcome.output));
+                }
+                let _ = writeln!(out, "  ...");
+            }
+        }
+        let _ = out.flush();
+    }
+
+    fn on_run_end(&mut self, _outcomes: &[TestOutcome], summary: &RunSummary) {
+        if let Some(seed) = summary.seed {
+            let _ = writeln!(self.out, "# random seed {}", seed);
+        }
+        if let Some(seed) = summary.shuffle_seed {
+            let _ = writeln!(self.out, "# shuffle seed {}", seed);
+        }
+        let _ = writeln!(
+            self.out,
+            "# passed {}, failed {}, quarantined {}, flaky {}, skipped {}, xfailed {}, filtered out {}, finished in {:.2}s",
+            summary.passed,
+            summary.failed,
+            summary.quarantined,
+            summary.flaky,
+            summary.skipped,
+            summary.expected_failures,
+            summary.filtered_out,
+            summary.duration.as_secs_f64(),
+        );
+        if summary.interrupted {
+            let _ = writeln!(
+                self.out,
+                "Bail out! interrupted, with {} not run",
+                summary.not_run
+            );
+        } else if summary.not_run > 0 {
+            // The plan promised tests that will never report.
+            let _ = writeln!(
+                self.out,
+                "Bail out! stopped after {} failed, with {} not run",
+                summary.failed, summary.not_run
+            );
+        }
+        let _ = self.out.flush();
+    }
+}
+
+/// Escapes the characters that TAP gives a meaning in test point
+/// descriptions.
+fn escape_description(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('#', "\\#")
+}
+
+/// The text following a `# SKIP` or `# TODO` directive.
+fn directive(reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!(" {}", escape_description(reason)),
+        None => String::new(),
+    }
+}
+
+/// Quotes a YAML scalar as a JSON string. YAML accepts it, and so do the
+/// minimal "YAMLish" parsers of TAP consumers, which reject block scalars.
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_directives_in_descriptions() {
+        assert_eq!(escape_description("case_#1"), "case_\\#1");
+        assert_eq!(escape_description("a\\b"), "a\\\\b");
+        assert_eq!(directive(&None), "");
+        assert_eq!(directive(&Some("needs #42".to_string())), " needs \\#42");
+    }
+
+    #[test]
+    fn quotes_yaml_strings_on_one_line() {
+        assert_eq!(yaml_string("plain"), "\"plain\"");
+        assert_eq!(
+            yaml_string("two\nlines \"quoted\""),
+            "\"two\\nlines \\\"quoted\\\"\""
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Timing reports, read back by `--shard-timings` to balance shards.
+//!
+//! The report has the format of the runner's [timings
+//! cache](crate::timings), and like it keeps the tests recorded in an
+//! existing file that did not run, so the reports of several shards can be
+//! written to the same file.
+
+use std::path::PathBuf;
+
+use super::Reporter;
+use crate::outcome::{RunSummary, TestOutcome};
+use crate::timings::Timings;
+
+/// Writes the duration of every test that ran to a file once the run ends.
+pub(crate) struct TimingsReporter {
+    path: PathBuf,
+}
+
+impl TimingsReporter {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        TimingsReporter { path }
+    }
+}
+
+impl Reporter for TimingsReporter {
+    fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], _summary: &RunSummary) {
+        let mut timings = Timings::read(&self.path).unwrap_or_default();
+        timings.record(outcomes);
+        if let Err(err) = timings.write(&self.path) {
+            eprintln!(
+                "warning: failed to write timings to {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
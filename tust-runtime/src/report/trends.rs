@@ -0,0 +1,177 @@
+//! The trends of `--trends`: the tests that started failing, became slow,
+//! or keep changing their result, against their recorded runs.
+//!
+//! See [`crate::history`] for how runs are recorded and what counts as each
+//! trend.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use colored::Colorize;
+
+use super::Reporter;
+use crate::capture::Stdout;
+use crate::history::{self, Run, Trends};
+use crate::outcome::{RunSummary, TestOutcome};
+use crate::timings;
+
+/// Prints the trends of the run once it ends.
+pub(crate) struct TrendsReporter {
+    /// Runs of each test to look at, counting this one.
+    runs: usize,
+}
+
+impl TrendsReporter {
+    pub(crate) fn new(runs: usize) -> Self {
+        TrendsReporter { runs }
+    }
+}
+
+impl Reporter for TrendsReporter {
+    fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+
+    fn on_run_end(&mut self, outcomes: &[TestOutcome], _summary: &RunSummary) {
+        let trends = Trends::new(&history::load_cache(), &Run::new(outcomes), self.runs);
+        let paths: HashMap<String, String> = outcomes
+            .iter()
+            .map(|outcome| (timings::key(outcome.test), outcome.test.path()))
+            .collect();
+        let path = |key: &String| paths.get(key).unwrap_or(key).clone();
+        write_trends(&mut Stdout, &trends, self.runs, path);
+    }
+}
+
+/// Writes `trends` over the last `runs` runs of each test to `out`, naming
+/// each test by `path` of its key.
+fn write_trends(
+    out: &mut impl Write,
+    trends: &Trends,
+    runs: usize,
+    path: impl Fn(&String) -> String,
+) {
+    if trends.compared == 0 {
+        let _ = writeln!(
+            out,
+            "\ntrends: no earlier runs of these tests are recorded yet"
+        );
+        return;
+    }
+    if trends.is_empty() {
+        let _ = writeln!(
+            out,
+            "\ntrends: no test started failing, became slow, or was flaky in its last {} runs",
+            runs
+        );
+        return;
+    }
+    let _ = writeln!(out, "\ntrends over the last {} runs of each test:", runs);
+    if !trends.newly_failing.is_empty() {
+        let _ = writeln!(out, "  {}", "newly failing:".red());
+        for (key, passes) in &trends.newly_failing {
+            let runs = match passes {
+                1 => "run".to_string(),
+                passes => format!("{} runs", passes),
+            };
+            let _ = writeln!(out, "    {} (passed in its previous {})", path(key), runs);
+        }
+    }
+    if !trends.newly_slow.is_empty() {
+        let _ = writeln!(out, "  {}", "newly slow:".yellow());
+        for (key, duration, median) in &trends.newly_slow {
+            let _ = writeln!(
+                out,
+                "    {:>8.3}s  {} (median {:.3}s)",
+                duration.as_secs_f64(),
+                path(key),
+                median.as_secs_f64()
+            );
+        }
+    }
+    if !trends.flaky.is_empty() {
+        let _ = writeln!(out, "  {}", "flaky:".yellow());
+        for (key, flaky, changes, runs) in &trends.flaky {
+            let mut counts = Vec::new();
+            if *flaky > 0 {
+                counts.push(format!("flaky {}", times(*flaky)));
+            }
+            if *changes > 0 {
+                counts.push(format!(
+                    "changed between passing and failing {}",
+                    times(*changes)
+                ));
+            }
+            let _ = writeln!(
+                out,
+                "    {} ({} in {} runs)",
+                path(key),
+                counts.join(" and "),
+                runs
+            );
+        }
+    }
+}
+
+fn times(count: usize) -> String {
+    match count {
+        1 => "once".to_string(),
+        count => format!("{} times", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn print(trends: &Trends, runs: usize) -> String {
+        let mut out = Vec::new();
+        write_trends(&mut out, trends, runs, |key| key.replace("app::", ""));
+        super::super::strip_ansi(&String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn prints_each_trend() {
+        let trends = Trends {
+            newly_failing: vec![("app::parser::a".into(), 1), ("app::parser::b".into(), 4)],
+            newly_slow: vec![(
+                "app::io::read".into(),
+                Duration::from_millis(1250),
+                Duration::from_millis(300),
+            )],
+            flaky: vec![
+                ("app::net::connect".into(), 2, 1, 20),
+                ("app::net::retry".into(), 0, 3, 7),
+            ],
+            compared: 5,
+        };
+        assert_eq!(
+            print(&trends, 20),
+            "\ntrends over the last 20 runs of each test:\n  \
+             newly failing:\n    \
+             parser::a (passed in its previous run)\n    \
+             parser::b (passed in its previous 4 runs)\n  \
+             newly slow:\n       \
+             1.250s  io::read (median 0.300s)\n  \
+             flaky:\n    \
+             net::connect (flaky 2 times and changed between passing and failing once in 20 runs)\n    \
+             net::retry (changed between passing and failing 3 times in 7 runs)\n"
+        );
+    }
+
+    #[test]
+    fn says_when_there_is_nothing_to_compare_or_report() {
+        assert_eq!(
+            print(&Trends::default(), 20),
+            "\ntrends: no earlier runs of these tests are recorded yet\n"
+        );
+        let trends = Trends {
+            compared: 3,
+            ..Trends::default()
+        };
+        assert_eq!(
+            print(&trends, 5),
+            "\ntrends: no test started failing, became slow, or was flaky in its last 5 runs\n"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Commands that rerun a single failed test.
+//!
+//! The reporters give each failed test a command that reruns only that
+//! test, selected by its full name with `--exact`, so that a case of a
+//! parameterized test reruns alone. The command carries what else the
+//! failure may depend on: the run seed, if the test asked for randomness,
+//! and the run's `--process-per-test` and `--timeout`.
+//!
+//! ```text
+//! rerun: cargo test -p parser --test integration -- --exact tokens::case_3_unicode --seed 8077014924821388086
+//! ```
+//!
+//! The command names the package and target the binary was built from,
+//! which `tust::main!()` records at compile time: `--lib` for the unit tests
+//! of a library, `--bin NAME` and `--example NAME` for those of a binary or
+//! an example, and `--test NAME` and `--bench NAME` for integration tests
+//! and benchmarks. A binary whose `main` calls [`crate::run`] itself, or
+//! that was not built by Cargo, is named by its path instead. Cargo features
+//! the test was built with are not known to the binary, and are left out. The tests of other binaries, whose reports
+//! `cargo tust workspace` merges, keep the commands their binaries gave.
+
+use std::path::{Component, Path};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::cli::Args;
+use crate::registry::TestDef;
+use crate::rng;
+use crate::runner;
+
+/// The options of the run that the commands repeat.
+#[derive(Debug, Default)]
+struct Options {
+    process_per_test: bool,
+    timeout: Option<Duration>,
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+/// What Cargo said, when it compiled the binary, of the target `main` is in.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct Built {
+    /// `CARGO_PKG_NAME`.
+    pub package: Option<&'static str>,
+    /// `CARGO_CRATE_NAME`, the target's name with `-` made `_`.
+    pub crate_name: Option<&'static str>,
+    /// `CARGO_BIN_NAME`, set for binaries and examples.
+    pub bin_name: Option<&'static str>,
+    /// Whether `CARGO_TARGET_TMPDIR` was set, as it is for integration tests
+    /// and benchmarks.
+    pub target_tmpdir: bool,
+    /// The file `main` is defined in.
+    pub file: &'static str,
+}
+
+static BUILT: OnceLock<Built> = OnceLock::new();
+
+/// The Cargo target a test binary was built from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target<'a> {
+    Lib,
+    Bin(&'a str),
+    Example(&'a str),
+    Test(&'a str),
+    Bench(&'a str),
+}
+
+impl<'a> Target<'a> {
+    /// The target `built` describes, if it says enough to name one.
+    fn of(built: &Built) -> Option<Target<'static>> {
+        // The nearest of these directories says what kind of target the
+        // file belongs to.
+        let dir = Path::new(built.file)
+            .components()
+            .rev()
+            .find_map(|component| match component {
+                Component::Normal(dir) => ["src", "tests", "benches", "examples"]
+                    .into_iter()
+                    .find(|kind| dir == *kind),
+                _ => None,
+            });
+        match (built.bin_name, built.target_tmpdir, built.crate_name) {
+            (Some(name), _, _) if dir == Some("examples") => Some(Target::Example(name)),
+            (Some(name), _, _) => Some(Target::Bin(name)),
+            (None, true, Some(name)) if dir == Some("benches") => Some(Target::Bench(name)),
+            (None, true, Some(name)) => Some(Target::Test(name)),
+            (None, true, None) => None,
+            (None, false, _) => Some(Target::Lib),
+        }
+    }
+
+    /// The arguments of `cargo test` that select this target.
+    fn args(self) -> Vec<&'a str> {
+        match self {
+            Target::Lib => vec!["--lib"],
+            Target::Bin(name) => vec!["--bin", name],
+            Target::Example(name) => vec!["--example", name],
+            Target::Test(name) => vec!["--test", name],
+            Target::Bench(name) => vec!["--bench", name],
+        }
+    }
+}
+
+/// The commands of tests that ran in other binaries.
+static RECORDED: Mutex<Vec<(&'static TestDef, String)>> = Mutex::new(Vec::new());
+
+/// Makes the commands repeat the options of the run `args` describes.
+pub(crate) fn enable(args: &Args) {
+    let _ = OPTIONS.set(Options {
+        process_per_test: args.process_per_test,
+        timeout: args.timeout,
+    });
+}
+
+/// Records the target the binary was built from, for the commands to name.
+#[doc(hidden)]
+pub fn set_built(built: Built) {
+    let _ = BUILT.set(built);
+}
+
+/// Makes `command` the one that reruns `test`, which ran in another binary.
+pub(crate) fn record(test: &'static TestDef, command: String) {
+    RECORDED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((test, command));
+}
+
+/// The command that reruns only `test`, as it ran in this run.
+pub fn command(test: &TestDef) -> String {
+    let recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, command)) = recorded.iter().find(|(of, _)| std::ptr::eq(*of, test)) {
+        return command.clone();
+    }
+    drop(recorded);
+    let options = OPTIONS.get_or_init(Options::default);
+    let binary = std::env::args().next().unwrap_or_default();
+    let mut words = match BUILT.get() {
+        Some(built) => program(built.package, Target::of(built), &binary),
+        None => vec![binary],
+    };
+    words.extend(["--exact".to_string(), test.path()]);
+    if rng::used_by(test) {
+        words.extend(["--seed".to_string(), rng::run_seed().to_string()]);
+    }
+    if options.process_per_test {
+        words.push("--process-per-test".to_string());
+    }
+    if let Some(timeout) = options.timeout.filter(|_| test.timeout.is_none()) {
+        words.extend(["--timeout".to_string(), runner::format_duration(timeout)]);
+    }
+    words
+        .iter()
+        .map(|word| quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The words that run the tests of `binary`, built from `target` of
+/// `package`, up to its arguments.
+fn program(package: Option<&str>, target: Option<Target>, binary: &str) -> Vec<String> {
+    match (package, target) {
+        (Some(package), Some(target)) => {
+            let mut words = vec!["cargo", "test", "-p", package];
+            words.extend(target.args());
+            words.push("--");
+            words.into_iter().map(str::to_string).collect()
+        }
+        _ => vec![binary.to_string()],
+    }
+}
+
+/// `word` quoted for POSIX shells, if it needs to be.
+fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-:./=@%+,".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built(bin_name: Option<&'static str>, target_tmpdir: bool, file: &'static str) -> Built {
+        Built {
+            package: Some("parser"),
+            crate_name: Some("integration"),
+            bin_name,
+            target_tmpdir,
+            file,
+        }
+    }
+
+    #[test]
+    fn targets() {
+        let of = |bin_name, target_tmpdir, file| Target::of(&built(bin_name, target_tmpdir, file));
+        assert_eq!(of(None, false, "src/lib.rs"), Some(Target::Lib));
+        assert_eq!(
+            of(Some("cli"), false, "src/main.rs"),
+            Some(Target::Bin("cli"))
+        );
+        assert_eq!(
+            of(Some("cli"), false, "src/bin/cli.rs"),
+            Some(Target::Bin("cli"))
+        );
+        assert_eq!(
+            of(Some("demo"), false, "examples/demo.rs"),
+            Some(Target::Example("demo"))
+        );
+        assert_eq!(
+            of(None, true, "tests/integration.rs"),
+            Some(Target::Test("integration"))
+        );
+        assert_eq!(
+            of(None, true, "tests/integration/main.rs"),
+            Some(Target::Test("integration"))
+        );
+        assert_eq!(
+            of(None, true, "benches/integration.rs"),
+            Some(Target::Bench("integration"))
+        );
+    }
+
+    #[test]
+    fn nearest_directory_decides_the_kind() {
+        let of = |bin_name, target_tmpdir, file| Target::of(&built(bin_name, target_tmpdir, file));
+        // A workspace member under `examples/` is not itself an example.
+        assert_eq!(
+            of(Some("cli"), false, "examples/cli/src/main.rs"),
+            Some(Target::Bin("cli"))
+        );
+        assert_eq!(
+            of(None, true, "benches/parser/tests/integration.rs"),
+            Some(Target::Test("integration"))
+        );
+    }
+
+    #[test]
+    fn unnamed_integration_target() {
+        let built = Built {
+            crate_name: None,
+            ..built(None, true, "tests/integration.rs")
+        };
+        assert_eq!(Target::of(&built), None);
+    }
+
+    #[test]
+    fn programs() {
+        let binary = "target/debug/deps/integration-0123abcd";
+        let program = |target| program(Some("parser"), Some(target), binary).join(" ");
+        assert_eq!(program(Target::Lib), "cargo test -p parser --lib --");
+        assert_eq!(
+            program(Target::Bin("cli")),
+            "cargo test -p parser --bin cli --"
+        );
+        assert_eq!(
+            program(Target::Example("demo")),
+            "cargo test -p parser --example demo --"
+        );
+        assert_eq!(
+            program(Target::Test("it")),
+            "cargo test -p parser --test it --"
+        );
+        assert_eq!(
+            program(Target::Bench("speed")),
+            "cargo test -p parser --bench speed --"
+        );
+    }
+
+    #[test]
+    fn unknown_programs_are_their_binary() {
+        let binary = "target/debug/deps/integration-0123abcd";
+        assert_eq!(program(None, Some(Target::Lib), binary), [binary]);
+        assert_eq!(program(Some("parser"), None, binary), [binary]);
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("tokens::case_3"), "tokens::case_3");
+        assert_eq!(quote("--timeout=1.5s"), "--timeout=1.5s");
+        assert_eq!(
+            quote("target/debug/deps/it-0123"),
+            "target/debug/deps/it-0123"
+        );
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote("case_\"quoted\""), "'case_\"quoted\"'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+}
@@ -0,0 +1,186 @@
+//! Seeded randomness for tests, reproducible from a single run seed.
+//!
+//! Every run has a seed: the one given with `--seed` or in [`SEED_ENV`], or
+//! else a random one. [`rng`] hands out generators derived from the run seed
+//! and the current test's name, so a test sees the same random values in
+//! every run with the same seed, regardless of which other tests run or in
+//! which order. Runs in which a test asked for randomness report their seed.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use web_time::SystemTime;
+
+use crate::alloc;
+use crate::process;
+use crate::registry::{self, TestDef};
+use crate::stress;
+
+/// Environment variable setting the run seed, for test binaries run without
+/// the tust runner's `--seed`.
+pub const SEED_ENV: &str = "TUST_SEED";
+
+static RUN_SEED: OnceLock<u64> = OnceLock::new();
+
+/// Whether any test asked for randomness during the run.
+static USED: AtomicBool = AtomicBool::new(false);
+
+/// Generators handed out so far, per test.
+static STREAMS: Mutex<Option<HashMap<String, u64>>> = Mutex::new(None);
+
+/// A small, fast pseudo-random number generator (SplitMix64). Not suitable
+/// for cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..bound`, or 0 if `bound` is 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /// A value in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns `true` with a probability of one in `n`.
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+
+    /// One of `choices`, which must not be empty.
+    pub fn choose<'a, T>(&mut self, choices: &'a [T]) -> &'a T {
+        &choices[self.below(choices.len() as u64) as usize]
+    }
+
+    /// Shuffles `items` in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i as u64 + 1) as usize);
+        }
+    }
+}
+
+/// A generator for the current test, seeded from the run seed and the test's
+/// name. Each call in a test returns a differently seeded generator, but the
+/// same sequence of generators in every run with the same seed. In a
+/// [stress](crate::stress) test, the sequence is that of the iteration.
+pub fn rng() -> Rng {
+    USED.store(true, Ordering::Relaxed);
+    let test = registry::current().map(TestDef::path).unwrap_or_default();
+    let stream = alloc::untracked(|| {
+        let mut streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+        let count = streams
+            .get_or_insert_with(HashMap::new)
+            .entry(test.clone())
+            .or_insert(0);
+        *count += 1;
+        *count
+    });
+    match stress::next_stream() {
+        Some((iteration, stream)) => Rng::new(derive(
+            run_seed(),
+            &format!("{}#{}", test, iteration),
+            stream,
+        )),
+        None => Rng::new(derive(run_seed(), &test, stream)),
+    }
+}
+
+/// The seed of this run.
+pub fn run_seed() -> u64 {
+    *RUN_SEED.get_or_init(|| match std::env::var(SEED_ENV) {
+        Ok(seed) => seed.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "warning: ignoring {}={:?}, which is not a number",
+                SEED_ENV, seed
+            );
+            random_seed()
+        }),
+        Err(_) => random_seed(),
+    })
+}
+
+/// Sets the run seed, unless a test already used it.
+pub(crate) fn set_run_seed(seed: u64) {
+    let _ = RUN_SEED.set(seed);
+}
+
+/// Whether a test asked for randomness during the run.
+pub(crate) fn was_used() -> bool {
+    USED.load(Ordering::Relaxed)
+}
+
+/// Whether `test` asked for randomness the last time it ran.
+pub(crate) fn used_by(test: &TestDef) -> bool {
+    let streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+    streams
+        .as_ref()
+        .is_some_and(|streams| streams.contains_key(&test.path()))
+}
+
+/// Records that `test` asked for randomness in a child process.
+pub(crate) fn mark_used(test: &TestDef) {
+    USED.store(true, Ordering::Relaxed);
+    alloc::untracked(|| {
+        let mut streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+        streams
+            .get_or_insert_with(HashMap::new)
+            .entry(test.path())
+            .or_insert(0);
+    });
+}
+
+/// Restarts the generators of `test`, so that a retried test sees the same
+/// random values again.
+pub(crate) fn restart(test: &TestDef) {
+    let mut streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(streams) = streams.as_mut() {
+        streams.remove(&test.path());
+    }
+}
+
+/// Mixes `seed`, `test`, and `stream` into a new seed. Uses FNV-1a rather
+/// than `std`'s hashers, whose output may change between Rust releases.
+fn derive(seed: u64, test: &str, stream: u64) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in seed
+        .to_le_bytes()
+        .into_iter()
+        .chain(test.bytes())
+        .chain(stream.to_le_bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Rng::new(hash).next_u64()
+}
+
+pub(crate) fn random_seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    SystemTime::now().hash(&mut hasher);
+    process::id().hash(&mut hasher);
+    hasher.finish()
+}
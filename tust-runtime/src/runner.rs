@@ -0,0 +1,1300 @@
+//! The tust test runner, used by binaries built with `harness = false`.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use regex::Regex;
+use web_time::Instant;
+
+use crate::alloc;
+use crate::bench;
+use crate::capture::{Capture, Stderr};
+use crate::cli::{Args, ColorChoice, OutputFormat, ReportFormat, USAGE};
+use crate::context;
+use crate::coverage::{self, Affected};
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+use crate::db;
+use crate::depends;
+use crate::dry_run;
+use crate::failures::{self, Failures};
+use crate::fixture::{self, Fixtures};
+use crate::fuzz;
+use crate::golden;
+use crate::history;
+use crate::hooks::{self, Scopes};
+use crate::interrupt;
+use crate::list;
+use crate::locks;
+use crate::mutation;
+use crate::outcome::{self, Location, RunSummary, TestFailure, TestOutcome, TestStatus};
+use crate::overrides;
+use crate::panic;
+use crate::passes::Cached;
+use crate::process;
+use crate::quarantine;
+use crate::registry::{self, ExpectedResult, Skip, TestDef};
+use crate::report::{
+    self, ConsoleReporter, GithubReporter, HtmlReporter, JsonReporter, JunitReporter, Reporter,
+    Reporters, Style, TapReporter, TimingsReporter, TrendsReporter,
+};
+use crate::rerun;
+use crate::rng::{self, Rng};
+use crate::sandbox::{self, IsolatedCwd};
+use crate::shared;
+use crate::snapshot;
+use crate::soft;
+use crate::strict::{self, Watch};
+use crate::timings::{self, Timings};
+
+/// Exit code of a run in which every test passed.
+pub const EXIT_SUCCESS: u8 = 0;
+/// Exit code of a run in which at least one test failed.
+pub const EXIT_TESTS_FAILED: u8 = 1;
+/// Exit code used when the command line could not be parsed.
+pub const EXIT_USAGE: u8 = 2;
+/// Exit code of a run interrupted by a signal, such as Ctrl-C; see
+/// [`crate::interrupt`].
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+/// How often the runner checks for an interruption while tests run.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs every registered test using the current process's arguments.
+///
+/// This is the entry point behind `tust::main!()`.
+pub fn run() -> ExitCode {
+    if let Some(code) = process::run_child() {
+        return code;
+    }
+    match Args::from_env() {
+        Ok(args) => run_with_args(&args),
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            ExitCode::from(EXIT_USAGE)
+        }
+    }
+}
+
+/// Runs every registered test selected by `args`.
+pub fn run_with_args(args: &Args) -> ExitCode {
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::from(EXIT_SUCCESS);
+    }
+
+    if args.bench {
+        // Read by benchmarks, in this process and in those of
+        // `--process-per-test`.
+        std::env::set_var(bench::BENCH_ENV, "1");
+    }
+    if args.leak_check {
+        if !alloc::is_installed() {
+            eprintln!(
+                "error: --leak-check needs tust's tracking allocator; install it with\n\n    \
+                 #[global_allocator]\n    \
+                 static ALLOC: tust::alloc::TrackingAllocator = tust::alloc::TrackingAllocator::new();"
+            );
+            return ExitCode::from(EXIT_USAGE);
+        }
+        // Read by each test, in this process and in those of
+        // `--process-per-test`.
+        std::env::set_var(alloc::LEAK_CHECK_ENV, "1");
+    }
+    if args.isolated_cwd {
+        // Read by each test, in this process and in those of
+        // `--process-per-test`.
+        std::env::set_var(sandbox::ISOLATED_CWD_ENV, "1");
+    }
+    quarantine::enable(&args.quarantine);
+    rerun::enable(args);
+    if args.strict {
+        strict::enable(&args.strict_checks, args.jobs(), args.runs_in_processes());
+    }
+    if cfg!(target_family = "wasm")
+        && (args.process_per_test || args.record_coverage || args.mutation_hook)
+    {
+        eprintln!(
+            "error: --process-per-test, --record-coverage, and --mutation-hook run tests in \
+             processes of their own, which WebAssembly does not have"
+        );
+        return ExitCode::from(EXIT_USAGE);
+    }
+    if let Some(dir) = &args.snapshot_dir {
+        // Read by snapshot assertions, in this process and in those of
+        // `--process-per-test`.
+        std::env::set_var(snapshot::DIR_ENV, dir);
+    }
+    let timings = match &args.shard_timings {
+        Some(path) => match Timings::read(path) {
+            Ok(timings) => Some(timings),
+            Err(err) => {
+                eprintln!(
+                    "error: cannot read shard timings from {}: {}",
+                    path.display(),
+                    err
+                );
+                return ExitCode::from(EXIT_USAGE);
+            }
+        },
+        None => None,
+    };
+    let affected = match &args.changed_since {
+        Some(git_ref) => match Affected::since(git_ref) {
+            Ok(affected) => Some(affected),
+            Err(err) => {
+                eprintln!("error: cannot find the changes since {}: {}", git_ref, err);
+                return ExitCode::from(EXIT_USAGE);
+            }
+        },
+        None => None,
+    };
+    if args.record_coverage {
+        coverage::start_recording();
+    }
+    let all = registry::tests();
+    if let Err(err) = depends::check(&all) {
+        eprintln!("error: {}", err);
+        return ExitCode::from(EXIT_USAGE);
+    }
+    if args.list {
+        list(args, &all, timings.as_ref(), affected.as_ref());
+        return ExitCode::from(EXIT_SUCCESS);
+    }
+    if args.schedule {
+        schedule(args, &all, timings.as_ref(), affected.as_ref());
+        return ExitCode::from(EXIT_SUCCESS);
+    }
+    if args.dry_run {
+        return ExitCode::from(dry_run(args, &all, timings.as_ref(), affected.as_ref()));
+    }
+    if args.mutation_hook {
+        return ExitCode::from(mutation::serve(args, &all));
+    }
+    if let Some(dir) = &args.export_fuzz {
+        return match fuzz::export(dir) {
+            Ok(()) => ExitCode::from(EXIT_SUCCESS),
+            Err(err) => {
+                eprintln!("error: cannot export the fuzz targets: {}", err);
+                ExitCode::from(EXIT_USAGE)
+            }
+        };
+    }
+
+    let summary = execute(
+        args,
+        &all,
+        timings.as_ref(),
+        affected.as_ref(),
+        &mut Reporters(reporters(args)),
+    );
+
+    let code = exit_code(&summary);
+    #[cfg(target_family = "wasm")]
+    crate::wasm::finish(code);
+    ExitCode::from(code)
+}
+
+/// The exit code of a run ending with `summary`.
+fn exit_code(summary: &RunSummary) -> u8 {
+    if summary.interrupted {
+        EXIT_INTERRUPTED
+    } else if summary.is_success() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_TESTS_FAILED
+    }
+}
+
+/// The reporters `args` asks for, with colors configured to suit them.
+pub(crate) fn reporters(args: &Args) -> Vec<Box<dyn Reporter>> {
+    let mut reporters: Vec<Box<dyn Reporter>> = match args.format {
+        OutputFormat::Tree | OutputFormat::Pretty => {
+            configure_color(args.color);
+            let style = match args.format {
+                _ if args.quiet => Style::Dots,
+                // On WebAssembly a panic can end the run at any test, so
+                // tests are reported as they finish rather than by module.
+                OutputFormat::Tree if cfg!(not(target_family = "wasm")) => Style::Tree,
+                _ => Style::Lines,
+            };
+            // Tests print straight to the terminal without capturing.
+            let live = std::io::stdout().is_terminal()
+                && !args.nocapture
+                && std::env::var_os("TERM").map_or(true, |term| term != "dumb");
+            let console = Box::new(ConsoleReporter::new(
+                style,
+                live,
+                args.slowest(),
+                args.output_lines(),
+            ));
+            let mut reporters: Vec<Box<dyn Reporter>> = vec![console];
+            if let Some(runs) = args.trends {
+                reporters.push(Box::new(TrendsReporter::new(runs)));
+            }
+            reporters
+        }
+        OutputFormat::Json => {
+            configure_color(ColorChoice::Never);
+            vec![Box::new(JsonReporter::new())]
+        }
+        OutputFormat::Tap => {
+            configure_color(ColorChoice::Never);
+            vec![Box::new(TapReporter::new())]
+        }
+        OutputFormat::Plain => {
+            configure_color(ColorChoice::Never);
+            vec![Box::new(ConsoleReporter::new(
+                Style::Plain,
+                false,
+                args.slowest(),
+                args.output_lines(),
+            ))]
+        }
+    };
+    reporters.extend(report::registered());
+    for target in &args.reports {
+        match target.format {
+            ReportFormat::Junit => {
+                reporters.push(Box::new(JunitReporter::new(target.path.clone())))
+            }
+            ReportFormat::Html => reporters.push(Box::new(HtmlReporter::new(target.path.clone()))),
+            ReportFormat::Timings => {
+                reporters.push(Box::new(TimingsReporter::new(target.path.clone())))
+            }
+            ReportFormat::Github => {
+                reporters.push(Box::new(GithubReporter::new(target.path.clone())))
+            }
+        }
+    }
+    // On GitHub Actions, failures are annotated without asking, unless the
+    // output is meant for a program.
+    let on_github = std::env::var_os("GITHUB_ACTIONS").is_some_and(|value| value == "true");
+    let requested = args
+        .reports
+        .iter()
+        .any(|target| target.format == ReportFormat::Github);
+    if on_github && !requested && matches!(args.format, OutputFormat::Tree | OutputFormat::Pretty) {
+        if let Some(path) = std::env::var_os("GITHUB_STEP_SUMMARY").filter(|path| !path.is_empty())
+        {
+            reporters.push(Box::new(GithubReporter::new(PathBuf::from(path))));
+        }
+    }
+    reporters
+}
+
+/// Prints the tests `args` selects; see [`crate::list`].
+fn list(
+    args: &Args,
+    all: &[&'static TestDef],
+    timings: Option<&Timings>,
+    affected: Option<&Affected>,
+) {
+    let failures = if args.last_failed {
+        failures::load_cache()
+    } else {
+        Failures::default()
+    };
+    let cached = if args.cached {
+        Cached::load(args)
+    } else {
+        None
+    };
+    list::print(
+        args,
+        &select(args, all, timings, affected, &failures, cached.as_ref()),
+    );
+}
+
+/// Prints the tests `args` selects and checks their fixtures; see
+/// [`crate::dry_run`].
+fn dry_run(
+    args: &Args,
+    all: &[&'static TestDef],
+    timings: Option<&Timings>,
+    affected: Option<&Affected>,
+) -> u8 {
+    let failures = if args.last_failed {
+        failures::load_cache()
+    } else {
+        Failures::default()
+    };
+    let cached = if args.cached {
+        Cached::load(args)
+    } else {
+        None
+    };
+    let selected = select(args, all, timings, affected, &failures, cached.as_ref());
+    dry_run::run(args, all, &selected)
+}
+
+/// Prints the tests `args` selects in the order they would start in; see
+/// [`list::print_schedule`].
+fn schedule(
+    args: &Args,
+    all: &[&'static TestDef],
+    timings: Option<&Timings>,
+    affected: Option<&Affected>,
+) {
+    let failures = if args.last_failed || args.failed_first {
+        failures::load_cache()
+    } else {
+        Failures::default()
+    };
+    let cached = if args.cached {
+        Cached::load(args)
+    } else {
+        None
+    };
+    let mut selected = select(args, all, timings, affected, &failures, cached.as_ref());
+    let recorded = timings::load_cache();
+    let shuffle_seed = order(args, &mut selected, &recorded, &failures);
+    list::print_schedule(args, &selected, &recorded, &failures, shuffle_seed);
+}
+
+/// The tests of `all` that `args` selects by name, tag, file, changes, last
+/// failure, earlier passes, and shard, in their original order, along with
+/// the tests they depend on.
+fn select(
+    args: &Args,
+    all: &[&'static TestDef],
+    timings: Option<&Timings>,
+    affected: Option<&Affected>,
+    failures: &Failures,
+    cached: Option<&Cached>,
+) -> Vec<&'static TestDef> {
+    let mut selected: Vec<_> = all
+        .iter()
+        .copied()
+        .filter(|test| {
+            args.is_selected(&test.path())
+                && args.is_selected_by_tags(test.tags)
+                && args.is_selected_by_file(test.file)
+        })
+        .collect();
+    if let Some(affected) = affected {
+        if affected.is_unknown() {
+            if !(args.list || args.schedule || args.dry_run) {
+                eprintln!(
+                    "note: no test coverage has been recorded with --record-coverage; \
+                     running every selected test"
+                );
+            }
+        } else {
+            selected.retain(|test| affected.contains(test));
+        }
+    }
+    if args.last_failed {
+        if selected.iter().any(|test| failures.contains(test)) {
+            selected.retain(|test| failures.contains(test));
+        } else if !(args.list || args.schedule || args.dry_run) {
+            eprintln!("note: none of the selected tests failed in its last run; running them all");
+        }
+    }
+    if let Some(cached) = cached {
+        let before = selected.len();
+        selected.retain(|test| !cached.passed(test));
+        let skipped = before - selected.len();
+        if skipped > 0 && !(args.list || args.schedule || args.dry_run) {
+            eprintln!(
+                "note: {} {} before with the same inputs; not running {} (--cached)",
+                skipped,
+                if skipped == 1 {
+                    "test passed"
+                } else {
+                    "tests passed"
+                },
+                if skipped == 1 { "it" } else { "them" }
+            );
+        }
+    }
+    // Dependencies are added first, so that every shard sees the same tests
+    // and can keep tests that depend on each other together.
+    let selected = depends::with_dependencies(selected, all);
+    match args.shard {
+        Some(shard) => shard.select(&selected, timings),
+        None => selected,
+    }
+}
+
+fn configure_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => std::io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    colored::control::set_override(enabled);
+}
+
+fn execute(
+    args: &Args,
+    all: &[&'static TestDef],
+    timings: Option<&Timings>,
+    affected: Option<&Affected>,
+    reporter: &mut dyn Reporter,
+) -> RunSummary {
+    let start = Instant::now();
+    interrupt::install();
+    if let Some(seed) = args.seed {
+        rng::set_run_seed(seed);
+    }
+    let failures = if args.last_failed || args.failed_first {
+        failures::load_cache()
+    } else {
+        Failures::default()
+    };
+    let mut cached = if args.cached {
+        Cached::load(args)
+    } else {
+        None
+    };
+    let mut selected = select(args, all, timings, affected, &failures, cached.as_ref());
+    let jobs = args.jobs();
+    let mut summary = RunSummary {
+        filtered_out: all.len() - selected.len(),
+        shuffle_seed: order(args, &mut selected, &timings::load_cache(), &failures),
+        ..RunSummary::default()
+    };
+    let mut outcomes = Vec::with_capacity(selected.len());
+
+    reporter.on_run_start(&selected);
+    #[cfg(target_family = "wasm")]
+    crate::wasm::start(
+        if args.quiet {
+            Style::Dots
+        } else {
+            Style::Lines
+        },
+        args.output_lines(),
+        selected.len(),
+        &summary,
+    );
+    let mut report = |event: Event| match event {
+        Event::Started(test) => reporter.on_test_start(test),
+        Event::Finished(test) => reporter.on_progress(test),
+        Event::Outcome(mut outcome) => {
+            if !args.show_output && !outcome.status.is_failure() {
+                outcome.output.clear();
+            }
+            summary.record(&outcome);
+            reporter.on_test_finish(&outcome);
+            #[cfg(target_family = "wasm")]
+            crate::wasm::record(&outcome);
+            outcomes.push(outcome);
+        }
+        Event::Abandoned(not_run) => {
+            summary.not_run = not_run;
+            finish(
+                args,
+                reporter,
+                &outcomes,
+                &mut summary,
+                start,
+                cached.take(),
+            );
+            std::process::exit(EXIT_INTERRUPTED.into());
+        }
+    };
+    // Coverage is recorded per process.
+    let process_per_test = args.runs_in_processes();
+    let run: RunFn = match (process_per_test, args.nocapture) {
+        (true, false) => process::run_in_process,
+        (true, true) => process::run_in_process_uncaptured,
+        (false, _) => run_test_with_timeout,
+    };
+    let limited = selected
+        .iter()
+        .filter(|test| !test.limits.is_none())
+        .count();
+    if limited > 0 && !(process_per_test && cfg!(unix)) {
+        eprintln!(
+            "note: {} {} resource limits, which are enforced only with --process-per-test on Unix",
+            limited,
+            if limited == 1 {
+                "test has"
+            } else {
+                "tests have"
+            }
+        );
+    }
+    // Child processes have their output captured through pipes instead, and
+    // WebAssembly has no file descriptors to redirect.
+    let capture = if args.nocapture || process_per_test || cfg!(target_family = "wasm") {
+        None
+    } else {
+        Capture::start()
+            .map_err(|err| eprintln!("warning: cannot capture test output: {}", err))
+            .ok()
+    };
+    let settings = Settings {
+        jobs,
+        timeout: args.timeout,
+        retries: args.retries,
+        max_failures: args.max_failures,
+        grace_period: args.grace_period.unwrap_or(interrupt::GRACE_PERIOD),
+        capture: capture.as_ref(),
+    };
+    // Each test's process runs the `before_all` and `after_all` hooks its
+    // test needs; see `process::run_child`.
+    let scopes = Scopes::new(if process_per_test { &[] } else { &selected });
+    let not_run = run_parallel(&selected, &settings, &scopes, run, &mut report);
+    summary.not_run = not_run;
+    #[cfg_attr(not(unix), allow(clippy::drop_non_drop))]
+    drop(capture);
+    finish(args, reporter, &outcomes, &mut summary, start, cached);
+    summary
+}
+
+/// Tears down the session fixtures once the tests have run, reports the
+/// run, and updates the caches of later runs.
+fn finish(
+    args: &Args,
+    reporter: &mut dyn Reporter,
+    outcomes: &[TestOutcome],
+    summary: &mut RunSummary,
+    start: Instant,
+    cached: Option<Cached>,
+) {
+    if let Err(failure) = panic::catch(fixture::teardown_session) {
+        eprintln!("warning: session fixture teardown panicked: {}", failure);
+    }
+    #[cfg(any(feature = "sqlx", feature = "diesel"))]
+    if args.runs_in_processes() {
+        db::clean_up();
+    }
+    summary.interrupted = interrupt::requested();
+    summary.duration = start.elapsed();
+    summary.seed = rng::was_used().then(rng::run_seed);
+    reporter.on_snapshot_changes(&snapshot::take_changes());
+    reporter.on_golden_changes(&golden::take_changes());
+    reporter.on_bench_results(&bench::take_results());
+    reporter.on_strict_warnings(&strict::take_warnings());
+    reporter.on_shared_data(&shared::take_loads());
+    summary.stable_quarantined = quarantine::update_cache(outcomes);
+    reporter.on_run_end(outcomes, summary);
+    timings::update_cache(outcomes);
+    failures::update_cache(outcomes);
+    history::update_cache(outcomes);
+    if args.record_coverage {
+        coverage::update_map(outcomes);
+    }
+    if let Some(cached) = cached {
+        cached.update(outcomes);
+    }
+}
+
+/// Whether `outcome` fails the run: whether it is a failure of a test that
+/// is not in quarantine.
+fn fails_run(outcome: &TestOutcome) -> bool {
+    outcome.status.is_failure() && !quarantine::is_quarantined(outcome.test)
+}
+
+/// Puts `tests` in the order they start in, returning the seed they were
+/// shuffled with, if `args` asks for a random order.
+///
+/// Unless shuffled, tests start by [`Priority`](registry::Priority), highest first, and in
+/// parallel runs by how long they took in earlier runs, slowest first, so
+/// that no long test starts near the end of the run. With `--failed-first`,
+/// the tests that failed last time start before the others, and tests always
+/// start after those they depend on.
+fn order(
+    args: &Args,
+    tests: &mut Vec<&'static TestDef>,
+    timings: &Timings,
+    failures: &Failures,
+) -> Option<u64> {
+    let shuffle_seed = if args.shuffle {
+        let seed = args.shuffle_seed.unwrap_or_else(rng::random_seed);
+        Rng::new(seed).shuffle(tests);
+        Some(seed)
+    } else {
+        if args.jobs() > 1 {
+            start_slowest_first(tests, timings);
+        }
+        tests.sort_by_key(|test| std::cmp::Reverse(test.priority));
+        None
+    };
+    if args.failed_first {
+        tests.sort_by_key(|test| !failures.contains(test));
+    }
+    depends::order(tests);
+    shuffle_seed
+}
+
+/// Orders `tests` by how long they took in earlier runs, slowest first, so
+/// that no long test starts near the end of a parallel run. Tests without a
+/// recorded duration are placed as if they took an average time.
+fn start_slowest_first(tests: &mut [&'static TestDef], timings: &Timings) {
+    if timings.is_empty() {
+        return;
+    }
+    let average = timings.average(tests);
+    tests.sort_by_cached_key(|test| std::cmp::Reverse(timings.get(test).unwrap_or(average)));
+}
+
+/// Executes a test within the given time limit.
+pub(crate) type RunFn = fn(&'static TestDef, Option<Duration>) -> TestOutcome;
+
+/// Run-wide defaults for how tests are executed.
+pub(crate) struct Settings<'a> {
+    /// Maximum number of tests run at once.
+    pub(crate) jobs: usize,
+    /// Time limit for tests without one of their own.
+    pub(crate) timeout: Option<Duration>,
+    /// Retries for tests without a retry count of their own.
+    pub(crate) retries: u32,
+    /// Failures after which no further test starts.
+    pub(crate) max_failures: Option<usize>,
+    /// How long running tests are given to finish once the run is
+    /// interrupted.
+    pub(crate) grace_period: Duration,
+    /// Where the output of in-process tests goes, unless it is not captured.
+    pub(crate) capture: Option<&'a Capture>,
+}
+
+/// What [`run_parallel`] passes to its `report` callback.
+pub(crate) enum Event {
+    /// A test is starting: its thread runs it once the callback returns, or
+    /// it is skipped.
+    Started(&'static TestDef),
+    /// A test has finished, possibly before tests earlier in the order.
+    Finished(&'static TestDef),
+    /// The outcome of the next test in the order.
+    Outcome(TestOutcome),
+    /// The run was interrupted, and tests still running after the grace
+    /// period are abandoned, with this many tests not run. Every outcome has
+    /// been passed before; the callback ends the process instead of
+    /// returning, as the abandoned tests cannot be waited for.
+    Abandoned(usize),
+}
+
+/// Runs `tests` with `run` on up to `settings.jobs` threads at once, one
+/// thread per test named after it. Each test is passed to `report` as
+/// [`Event::Started`] just before it runs, as
+/// [`Event::Finished`] as soon as it finishes, and its outcome as
+/// [`Event::Outcome`] in the order of `tests`, no matter the order in which
+/// the tests finish. A serial test starts only once every earlier test has
+/// finished, and runs alone. A test that depends on others starts only once
+/// they have finished, and is skipped unless they all passed. A test needing
+/// a lock that a running test holds is passed over until the lock is free,
+/// and later tests start in the meantime.
+///
+/// Once `settings.max_failures` tests have failed, or the run is
+/// interrupted, no further test starts; the tests already running finish,
+/// and the number of tests that never started is returned. Tests still
+/// running `settings.grace_period` after the interruption fail as abandoned,
+/// and are passed to `report` with [`Event::Abandoned`].
+pub(crate) fn run_parallel(
+    tests: &[&'static TestDef],
+    settings: &Settings<'_>,
+    scopes: &Scopes,
+    run: RunFn,
+    report: &mut dyn FnMut(Event),
+) -> usize {
+    let Settings {
+        jobs,
+        timeout,
+        retries,
+        max_failures,
+        grace_period,
+        capture,
+    } = *settings;
+    let (sender, receiver) = mpsc::channel();
+    let mut finished: Vec<Option<TestOutcome>> = tests.iter().map(|_| None).collect();
+    let dependencies = depends::earlier(tests);
+    // How each test ended, for the tests depending on it.
+    let mut ended: Vec<Option<Ended>> = tests.iter().map(|_| None).collect();
+    let mut next_to_start = 0;
+    let mut next_to_report = 0;
+    let mut running = 0;
+    let mut serial_running = false;
+    let mut failures = 0;
+    // The locks of the running tests.
+    let mut held: Vec<&'static str> = Vec::new();
+    // Tests passed over while a running test holds one of their locks.
+    let mut deferred: Vec<usize> = Vec::new();
+    // Tests passed over that will never run.
+    let mut dropped: Vec<bool> = tests.iter().map(|_| false).collect();
+    let mut not_run = 0;
+    // Tests from here on are not run.
+    let mut end = tests.len();
+    // When the running tests are abandoned, if the run was interrupted.
+    let mut abandon_at = None;
+
+    thread::scope(|scope| {
+        while next_to_report < end {
+            if abandon_at.is_none() && interrupt::requested() {
+                abandon_at = Some(Instant::now() + grace_period);
+                if running > 0 {
+                    let _ = writeln!(
+                        Stderr,
+                        "\ninterrupted: waiting up to {} for {} running {} to finish; \
+                         interrupt again to stop at once",
+                        format_duration(grace_period),
+                        running,
+                        if running == 1 { "test" } else { "tests" }
+                    );
+                }
+            }
+            let stop = abandon_at.is_some() || max_failures.is_some_and(|max| failures >= max);
+            if end == tests.len() && stop {
+                end = next_to_start;
+                not_run = tests.len() - end + deferred.len();
+                for index in deferred.drain(..).chain(end..tests.len()) {
+                    dropped[index] = true;
+                    for failure in scopes.leave(tests[index]) {
+                        eprintln!("warning: after_all hook failed: {}", failure);
+                    }
+                }
+            }
+            loop {
+                // The first test passed over whose locks are free, or else
+                // the next test in order.
+                let free = deferred
+                    .iter()
+                    .position(|&index| !locks::conflicts(tests[index], &held));
+                let index = match free {
+                    Some(position) => deferred[position],
+                    None if next_to_start < end => next_to_start,
+                    None => break,
+                };
+                let test = tests[index];
+                if free.is_none() {
+                    let dependencies = &dependencies[index];
+                    if dependencies.iter().any(|&index| ended[index].is_none()) {
+                        break;
+                    }
+                    let unmet = dependencies.iter().find_map(|&index| {
+                        ended[index]
+                            .filter(|&ended| ended != Ended::Passed)
+                            .map(|ended| (index, ended))
+                    });
+                    let skip = match unmet {
+                        Some((index, ended)) => {
+                            Some(skipped_for_dependency(test, tests[index], ended))
+                        }
+                        None => skipped(test),
+                    };
+                    if let Some(mut outcome) = skip {
+                        add_failures(&mut outcome, scopes.leave(test));
+                        failures += usize::from(fails_run(&outcome));
+                        report(Event::Started(test));
+                        report(Event::Finished(test));
+                        ended[index] = Some(Ended::of(&outcome.status));
+                        finished[index] = Some(outcome);
+                        next_to_start += 1;
+                        continue;
+                    }
+                    if locks::conflicts(test, &held) {
+                        deferred.push(index);
+                        next_to_start += 1;
+                        continue;
+                    }
+                }
+                let can_start =
+                    running < jobs && !serial_running && (!test.runs_serially() || running == 0);
+                if !can_start {
+                    break;
+                }
+                match free {
+                    Some(position) => {
+                        deferred.remove(position);
+                    }
+                    None => next_to_start += 1,
+                }
+                let job = move || {
+                    let timeout = test.timeout.or(timeout);
+                    let retries = test.retries.unwrap_or(retries);
+                    let held_locks = locks::hold(test);
+                    let window = capture.map(Capture::open);
+                    let mut outcome = run_in_scopes(test, scopes, || {
+                        run_with_retries(test, retries, timeout, run)
+                    });
+                    drop(held_locks);
+                    strict::check_duration(&mut outcome);
+                    if let (Some(capture), Some(window)) = (capture, window) {
+                        outcome.output = capture.close(window);
+                    }
+                    outcome
+                };
+                let spawned = thread::Builder::new()
+                    .name(test.path())
+                    .spawn_scoped(scope, {
+                        let sender = sender.clone();
+                        move || {
+                            // The test runs once its start is reported.
+                            let (begin, begun) = mpsc::channel();
+                            let _ = sender.send(Message::Started(index, begin));
+                            let _ = begun.recv();
+                            let _ = sender.send(Message::Finished(index, job()));
+                        }
+                    });
+                match spawned {
+                    Ok(_) => {
+                        running += 1;
+                        serial_running = test.runs_serially();
+                        held.extend(test.locks);
+                    }
+                    // Without threads, as on WebAssembly, tests run one after
+                    // the other on this one, each reported before the next
+                    // starts.
+                    Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                        report(Event::Started(test));
+                        let outcome = job();
+                        failures += usize::from(fails_run(&outcome));
+                        report(Event::Finished(test));
+                        ended[index] = Some(Ended::of(&outcome.status));
+                        finished[index] = Some(outcome);
+                        break;
+                    }
+                    Err(err) => {
+                        let message = format!("failed to spawn test thread: {}", err);
+                        let mut outcome = failed(test, message, Duration::ZERO);
+                        add_failures(&mut outcome, scopes.leave(test));
+                        failures += 1;
+                        report(Event::Started(test));
+                        report(Event::Finished(test));
+                        ended[index] = Some(Ended::of(&outcome.status));
+                        finished[index] = Some(outcome);
+                    }
+                }
+            }
+
+            while next_to_report < end {
+                if dropped[next_to_report] {
+                    next_to_report += 1;
+                } else if let Some(outcome) = finished[next_to_report].take() {
+                    report(Event::Outcome(outcome));
+                    next_to_report += 1;
+                } else {
+                    break;
+                }
+            }
+
+            if running > 0 {
+                let wait = match abandon_at {
+                    Some(at) => at.saturating_duration_since(Instant::now()),
+                    None => INTERRUPT_POLL_INTERVAL,
+                };
+                let (index, outcome) = match receiver.recv_timeout(wait) {
+                    Ok(Message::Started(index, begin)) => {
+                        report(Event::Started(tests[index]));
+                        let _ = begin.send(());
+                        continue;
+                    }
+                    Ok(Message::Finished(index, outcome)) => (index, outcome),
+                    Err(RecvTimeoutError::Timeout) if abandon_at.is_none() => continue,
+                    Err(RecvTimeoutError::Timeout) => {
+                        let message = format!(
+                            "test abandoned: still running {} after the run was interrupted",
+                            format_duration(grace_period)
+                        );
+                        for index in next_to_report..end {
+                            if ended[index].is_none() && !dropped[index] {
+                                ended[index] = Some(Ended::Failed);
+                                finished[index] =
+                                    Some(failed(tests[index], message.clone(), grace_period));
+                            }
+                        }
+                        for outcome in finished[next_to_report..end].iter_mut() {
+                            if let Some(outcome) = outcome.take() {
+                                report(Event::Outcome(outcome));
+                            }
+                        }
+                        process::kill_children();
+                        report(Event::Abandoned(not_run));
+                        unreachable!("the run ends once its tests are abandoned");
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        unreachable!("test threads report their outcome before exiting")
+                    }
+                };
+                running -= 1;
+                serial_running = false;
+                for name in tests[index].locks {
+                    if let Some(position) = held.iter().position(|held| held == name) {
+                        held.swap_remove(position);
+                    }
+                }
+                failures += usize::from(fails_run(&outcome));
+                report(Event::Finished(outcome.test));
+                ended[index] = Some(Ended::of(&outcome.status));
+                finished[index] = Some(outcome);
+            }
+        }
+    });
+    not_run
+}
+
+/// What the thread running a test sends [`run_parallel`], with the index of
+/// the test.
+enum Message {
+    /// The test is about to run, once the sender is sent to.
+    Started(usize, mpsc::Sender<()>),
+    Finished(usize, TestOutcome),
+}
+
+/// How a test ended, as far as the tests depending on it are concerned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ended {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl Ended {
+    fn of(status: &TestStatus) -> Ended {
+        match status {
+            TestStatus::Passed => Ended::Passed,
+            TestStatus::Failed(_) | TestStatus::ExpectedFailure(_) => Ended::Failed,
+            TestStatus::Skipped(_) => Ended::Skipped,
+        }
+    }
+}
+
+/// An outcome skipping `test` because `dependency` did not pass.
+fn skipped_for_dependency(
+    test: &'static TestDef,
+    dependency: &'static TestDef,
+    ended: Ended,
+) -> TestOutcome {
+    let reason = match ended {
+        Ended::Skipped => "dependency skipped",
+        _ => "dependency failed",
+    };
+    TestOutcome {
+        test,
+        status: TestStatus::Skipped(Some(format!("{}: {}", reason, dependency.path()))),
+        duration: Duration::ZERO,
+        output: String::new(),
+        attempts: 0,
+        attachments: Vec::new(),
+        steps: Vec::new(),
+    }
+}
+
+/// Executes a test with `run` after the `before_all` hooks it needs, then
+/// runs the `after_all` hooks of the modules it was the last test in.
+pub(crate) fn run_in_scopes(
+    test: &'static TestDef,
+    scopes: &Scopes,
+    run: impl FnOnce() -> TestOutcome,
+) -> TestOutcome {
+    let mut outcome = match scopes.enter(test) {
+        Ok(()) => run(),
+        Err(failure) => TestOutcome {
+            test,
+            status: TestStatus::Failed(vec![failure]),
+            duration: Duration::ZERO,
+            output: String::new(),
+            attempts: 1,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        },
+    };
+    add_failures(&mut outcome, scopes.leave(test));
+    outcome
+}
+
+/// Fails `outcome` with `failures`, after any failures it already has.
+pub(crate) fn add_failures(outcome: &mut TestOutcome, failures: Vec<TestFailure>) {
+    if failures.is_empty() {
+        return;
+    }
+    match &mut outcome.status {
+        TestStatus::Failed(existing) => existing.extend(failures),
+        status => *status = TestStatus::Failed(failures),
+    }
+}
+
+/// Executes a test with `run`, running it again after a failure up to
+/// `retries` more times. The outcome is that of the last attempt.
+pub(crate) fn run_with_retries(
+    test: &'static TestDef,
+    retries: u32,
+    timeout: Option<Duration>,
+    run: RunFn,
+) -> TestOutcome {
+    let mut attempts = 1;
+    let mut outcome = check_expected(run(test, timeout));
+    while outcome.status.is_failure() && attempts <= retries {
+        attempts += 1;
+        outcome = check_expected(run(test, timeout));
+    }
+    outcome.attempts = attempts;
+    outcome
+}
+
+/// An outcome skipping `test`, if it is to be skipped.
+pub(crate) fn skipped(test: &'static TestDef) -> Option<TestOutcome> {
+    let reason = match test.skip {
+        Skip::Never => return None,
+        Skip::Always { reason } => reason,
+        Skip::If { condition, reason } => {
+            if !condition() {
+                return None;
+            }
+            Some(reason)
+        }
+    };
+    Some(TestOutcome {
+        test,
+        status: TestStatus::Skipped(reason.map(str::to_string)),
+        duration: Duration::ZERO,
+        output: String::new(),
+        attempts: 0,
+        attachments: Vec::new(),
+        steps: Vec::new(),
+    })
+}
+
+/// Judges an outcome against the test's `#[should_fail]`, `#[should_panic]`,
+/// or `#[xfail]`.
+pub(crate) fn check_expected(mut outcome: TestOutcome) -> TestOutcome {
+    let test = outcome.test;
+    let unexpected_pass = |message: &str| {
+        TestStatus::Failed(vec![TestFailure {
+            location: Some(Location {
+                file: test.file.to_string(),
+                line: test.line,
+                column: test.column,
+            }),
+            ..TestFailure::new(message)
+        }])
+    };
+    outcome.status = match (test.expected, outcome.status) {
+        (ExpectedResult::Pass, status) => status,
+        (ExpectedResult::ShouldFail { .. }, TestStatus::Passed) => {
+            unexpected_pass("test passed, but it is marked #[should_fail]")
+        }
+        (ExpectedResult::ShouldFail { expected }, TestStatus::Failed(failures)) => match expected {
+            Some(expected) if !failures.iter().any(|f| f.description().contains(expected)) => {
+                let mut mismatch = vec![TestFailure::new(format!(
+                    "test failed as expected, but no failure message contains `{}`",
+                    expected
+                ))];
+                mismatch.extend(failures);
+                TestStatus::Failed(mismatch)
+            }
+            _ => {
+                outcome.output.clear();
+                TestStatus::Passed
+            }
+        },
+        (ExpectedResult::ShouldPanic { .. }, TestStatus::Passed) => {
+            unexpected_pass("test passed, but it is marked #[should_panic]")
+        }
+        (ExpectedResult::ShouldPanic { contains, regex }, TestStatus::Failed(mut failures)) => {
+            match failures.iter().position(|failure| failure.panicked) {
+                None => {
+                    failures.insert(
+                        0,
+                        TestFailure::new(
+                            "test failed without panicking, but it is marked #[should_panic]",
+                        ),
+                    );
+                    TestStatus::Failed(failures)
+                }
+                Some(index) => match panic_mismatch(&failures[index], contains, regex) {
+                    Some(mismatch) => {
+                        failures.insert(0, TestFailure::new(mismatch));
+                        TestStatus::Failed(failures)
+                    }
+                    // Soft failures before the panic still fail the test.
+                    None => {
+                        failures.remove(index);
+                        if failures.is_empty() {
+                            outcome.output.clear();
+                            TestStatus::Passed
+                        } else {
+                            TestStatus::Failed(failures)
+                        }
+                    }
+                },
+            }
+        }
+        (ExpectedResult::XFail { .. }, TestStatus::Passed) => {
+            unexpected_pass("test passed, but it is marked #[xfail]; remove the attribute")
+        }
+        (ExpectedResult::XFail { reason }, TestStatus::Failed(_)) => {
+            outcome.output.clear();
+            TestStatus::ExpectedFailure(reason.map(str::to_string))
+        }
+        (_, status) => status,
+    };
+    outcome
+}
+
+/// Why `panic` is not the panic a `#[should_panic]` test expects, if it is
+/// not.
+fn panic_mismatch(
+    panic: &TestFailure,
+    contains: Option<&str>,
+    regex: Option<&str>,
+) -> Option<String> {
+    let message = panic.description();
+    if let Some(contains) = contains.filter(|contains| !message.contains(contains)) {
+        return Some(format!(
+            "test panicked as expected, but its message does not contain `{}`",
+            contains
+        ));
+    }
+    let pattern = regex?;
+    match Regex::new(pattern) {
+        Ok(regex) if regex.is_match(&message) => None,
+        Ok(_) => Some(format!(
+            "test panicked as expected, but its message does not match `{}`",
+            pattern
+        )),
+        Err(err) => Some(format!("invalid `regex` of #[should_panic]: {}", err)),
+    }
+}
+
+/// Executes a single test on a thread of its own, failing it if it runs
+/// longer than `timeout`. A test that times out cannot be stopped; it is
+/// left running in the background.
+pub(crate) fn run_test_with_timeout(
+    test: &'static TestDef,
+    timeout: Option<Duration>,
+) -> TestOutcome {
+    let Some(limit) = timeout else {
+        return run_test(test);
+    };
+    let (sender, receiver) = mpsc::channel();
+    let spawned = thread::Builder::new().name(test.path()).spawn(move || {
+        let _ = sender.send(run_test(test));
+    });
+    if let Err(err) = &spawned {
+        // Without threads, as on WebAssembly, the limit cannot be enforced.
+        if err.kind() == io::ErrorKind::Unsupported {
+            return run_test(test);
+        }
+        return failed(
+            test,
+            format!("failed to spawn test thread: {}", err),
+            Duration::ZERO,
+        );
+    }
+
+    match receiver.recv_timeout(limit) {
+        Ok(outcome) => outcome,
+        Err(RecvTimeoutError::Timeout) => {
+            let message = format!(
+                "test timed out after {}; it is still running in the background",
+                format_duration(limit)
+            );
+            let mut failure = TestFailure::new(message);
+            context::note_step(test, &mut failure, false);
+            TestOutcome {
+                test,
+                status: TestStatus::Failed(vec![failure]),
+                duration: limit,
+                output: String::new(),
+                attempts: 1,
+                attachments: Vec::new(),
+                steps: context::take_steps(test),
+            }
+        }
+        Err(RecvTimeoutError::Disconnected) => failed(
+            test,
+            "test thread exited without reporting an outcome".to_string(),
+            Duration::ZERO,
+        ),
+    }
+}
+
+/// An outcome failing `test` with `message`.
+pub(crate) fn failed(test: &'static TestDef, message: String, duration: Duration) -> TestOutcome {
+    TestOutcome {
+        test,
+        status: TestStatus::Failed(vec![TestFailure::new(message)]),
+        duration,
+        output: String::new(),
+        attempts: 1,
+        attachments: Vec::new(),
+        steps: Vec::new(),
+    }
+}
+
+/// Formats a time limit the way `--timeout` accepts it, e.g. `250ms` or `5s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 && duration.as_secs() > 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Executes a single test in the current thread.
+pub(crate) fn run_test(test: &'static TestDef) -> TestOutcome {
+    let start = Instant::now();
+    snapshot::restart_numbering(test);
+    rng::restart(test);
+    let mut failures = Vec::new();
+    registry::with_current(test, || {
+        let watch = Watch::start();
+        let isolated = match IsolatedCwd::enter(test) {
+            Ok(isolated) => isolated,
+            Err(err) => {
+                failures.push(TestFailure::new(format!(
+                    "cannot give the test a working directory of its own: {}",
+                    err
+                )));
+                return;
+            }
+        };
+        let ((), leak) = alloc::check_leaks(test, || {
+            let mut fixtures = Fixtures::new(test.module_path);
+            let (setup, soft) = soft::collect(|| hooks::before_each(test));
+            failures.extend(soft);
+            match setup {
+                Ok(()) => {
+                    let (result, soft) =
+                        soft::collect(|| panic::catch(|| (test.run)(&mut fixtures)));
+                    failures.extend(soft);
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(err)) => {
+                            failures.push(TestFailure::new(format!("fixture error: {}", err)))
+                        }
+                        Err(failure) => failures.push(failure),
+                    }
+                }
+                Err(failure) => failures.push(failure),
+            }
+            let (cleanups, soft) = soft::collect(|| context::finish(test));
+            failures.extend(soft);
+            failures.extend(cleanups);
+            let (teardown, soft) = soft::collect(|| hooks::after_each(test));
+            failures.extend(soft);
+            failures.extend(teardown);
+            overrides::finish(test);
+        });
+        // A failed test may well leave memory behind, from its panic if
+        // nothing else.
+        if let Some(leak) = leak.filter(|_| failures.is_empty()) {
+            failures.push(TestFailure::new(leak));
+        }
+        if let Some(isolated) = isolated {
+            isolated.leave(test, !failures.is_empty());
+        }
+        if let Some(watch) = watch {
+            let leftovers = watch.finish(test, failures.is_empty());
+            failures.extend(leftovers);
+        }
+        #[cfg(feature = "tracing")]
+        crate::tracing::finish(!failures.is_empty());
+    });
+    TestOutcome {
+        test,
+        status: outcome::status_of(test, failures),
+        duration: start.elapsed(),
+        output: String::new(),
+        attempts: 1,
+        attachments: context::take_attachments(test),
+        steps: context::take_steps(test),
+    }
+}
@@ -0,0 +1,413 @@
+//! Built-in `sandbox` fixture: a temporary directory tree declared by the
+//! test.
+//!
+//! A test parameter named `sandbox` of type [`FsSandbox`] receives a fresh,
+//! empty directory, removed when the test ends like a
+//! [`TempDir`](crate::TempDir). The test lays out the files it needs with
+//! [`file`](FsSandbox::file) and [`dir`](FsSandbox::dir), runs the code under
+//! test in it, and checks what it left behind with
+//! [`assert_tree`](FsSandbox::assert_tree), which prints a diff of the trees
+//! when they differ.
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn writes_the_report(sandbox: FsSandbox) {
+//!     sandbox.file("config/app.toml", "output = \"out\"");
+//!     let _cwd = sandbox.chdir();
+//!     generate_report().unwrap();
+//!     sandbox.assert_tree(
+//!         "
+//!         config/
+//!           app.toml
+//!         out/
+//!           report.txt
+//!         ",
+//!     );
+//! }
+//! ```
+//!
+//! The current directory belongs to the whole process, so
+//! [`chdir`](FsSandbox::chdir) waits until no other test is in a sandbox it
+//! changed into, and the directory is changed back when its guard is dropped.
+//! Tests that rely on the current directory without calling `chdir` should
+//! be `#[serial]`.
+//!
+//! # Isolated working directories
+//!
+//! A `#[tust::test(isolated_cwd)]` test runs, hooks included, with a fresh
+//! directory named after it as the current directory, so that the relative
+//! paths it writes to do not collide with those of other tests. With
+//! `--isolated-cwd`, the `isolated-cwd` configuration key, or
+//! [`ISOLATED_CWD_ENV`] set, every test does. The directory is removed when
+//! the test ends, unless the test failed and `TUST_KEEP_TEMP=1` is set.
+//!
+//! Under `--process-per-test` each test changes directory in a process of
+//! its own. Otherwise the change is serialized like
+//! [`chdir`](FsSandbox::chdir): tests with isolated working directories take
+//! turns, while other tests still run alongside them and should not rely on
+//! the current directory.
+
+use std::any::{Any, TypeId};
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+use crate::outcome::TestFailure;
+use crate::registry::TestDef;
+use crate::temp::{self, TempDir};
+
+/// Environment variable giving every test a working directory of its own, as
+/// `--isolated-cwd` does.
+pub const ISOLATED_CWD_ENV: &str = "TUST_ISOLATED_CWD";
+
+inventory::submit! {
+    FixtureDef {
+        name: "sandbox",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<FsSandbox>,
+        type_id: TypeId::of::<FsSandbox>,
+        provider: Provider::PerTest(provide_sandbox),
+    }
+}
+
+fn provide_sandbox(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let sandbox =
+        FsSandbox::new().unwrap_or_else(|err| panic!("cannot create a sandbox directory: {}", err));
+    Ok(Box::new(sandbox))
+}
+
+/// The thread whose test changed into a sandbox, and how many of its
+/// [`CurrentDir`] guards are alive.
+static CHANGED: Mutex<Option<(ThreadId, usize)>> = Mutex::new(None);
+static RESTORED: Condvar = Condvar::new();
+
+/// A temporary directory whose tree a test lays out and checks.
+pub struct FsSandbox {
+    dir: TempDir,
+}
+
+impl FsSandbox {
+    /// Creates an empty sandbox in the system's temporary directory, named
+    /// after the current test.
+    pub fn new() -> io::Result<Self> {
+        Ok(FsSandbox {
+            dir: TempDir::new()?,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Writes `contents` to the file at `path`, relative to the sandbox,
+    /// creating its parent directories.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` leaves the sandbox or the file cannot be written.
+    #[track_caller]
+    pub fn file(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> &Self {
+        let path = self.resolve(path.as_ref());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|err| panic!("cannot create {}: {}", parent.display(), err));
+        }
+        fs::write(&path, contents)
+            .unwrap_or_else(|err| panic!("cannot write {}: {}", path.display(), err));
+        self
+    }
+
+    /// Creates the directory at `path`, relative to the sandbox, with its
+    /// parents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` leaves the sandbox or the directory cannot be created.
+    #[track_caller]
+    pub fn dir(&self, path: impl AsRef<Path>) -> &Self {
+        let path = self.resolve(path.as_ref());
+        fs::create_dir_all(&path)
+            .unwrap_or_else(|err| panic!("cannot create {}: {}", path.display(), err));
+        self
+    }
+
+    /// The contents of the file at `path`, relative to the sandbox.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` leaves the sandbox or the file cannot be read as
+    /// UTF-8.
+    #[track_caller]
+    pub fn read(&self, path: impl AsRef<Path>) -> String {
+        let path = self.resolve(path.as_ref());
+        fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("cannot read {}: {}", path.display(), err))
+    }
+
+    /// Makes the sandbox the current directory until the returned guard is
+    /// dropped, first waiting for tests of other threads to leave theirs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current directory cannot be changed.
+    #[track_caller]
+    pub fn chdir(&self) -> CurrentDir {
+        let guard = CurrentDir::enter();
+        std::env::set_current_dir(self.path())
+            .unwrap_or_else(|err| panic!("cannot change into {}: {}", self.path().display(), err));
+        guard
+    }
+
+    /// The tree of the sandbox, one entry per line indented by two spaces per
+    /// level, directories first marked with `/`, each level sorted by name.
+    pub fn tree(&self) -> String {
+        let mut out = String::new();
+        render(self.path(), 0, &mut out);
+        out
+    }
+
+    /// Asserts that the tree of the sandbox is `expected`, in the layout of
+    /// [`tree`](Self::tree); blank lines and the indentation common to every
+    /// line of `expected` are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a diff of the two trees if they differ.
+    #[track_caller]
+    pub fn assert_tree(&self, expected: &str) {
+        let expected = unindent(expected);
+        let actual = self.tree();
+        if expected != actual {
+            TestFailure::new("the sandbox tree differs from the expected tree")
+                .with_labeled_diff(
+                    "expected",
+                    "actual",
+                    expected.trim_end_matches('\n'),
+                    actual.trim_end_matches('\n'),
+                )
+                .raise();
+        }
+    }
+
+    /// Asserts that the file at `path`, relative to the sandbox, holds
+    /// `expected`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a diff if the contents differ, or if the file cannot be
+    /// read.
+    #[track_caller]
+    pub fn assert_file(&self, path: impl AsRef<Path>, expected: &str) {
+        let path = path.as_ref();
+        let actual = self.read(path);
+        if actual != expected {
+            TestFailure::new(format!(
+                "{} does not hold the expected contents",
+                path.display()
+            ))
+            .with_labeled_diff("expected", "actual", expected, actual)
+            .raise();
+        }
+    }
+
+    /// `path` within the sandbox.
+    #[track_caller]
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let inside = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            panic!(
+                "sandbox paths must be relative and stay inside the sandbox, not {}",
+                path.display()
+            );
+        }
+        self.path().join(path)
+    }
+}
+
+impl Deref for FsSandbox {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl AsRef<Path> for FsSandbox {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl fmt::Debug for FsSandbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FsSandbox").field(&self.path()).finish()
+    }
+}
+
+/// Keeps a sandbox the current directory, changing back to the previous
+/// directory when dropped.
+#[must_use = "the current directory is changed back when the guard is dropped"]
+pub struct CurrentDir {
+    previous: Option<PathBuf>,
+    /// The guard must be dropped on the thread that took it.
+    _thread: PhantomData<*const ()>,
+}
+
+impl CurrentDir {
+    /// Waits until no other thread is in a sandbox, and records the current
+    /// directory.
+    fn enter() -> Self {
+        let me = thread::current().id();
+        let mut changed = CHANGED.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match &mut *changed {
+                Some((thread, count)) if *thread == me => {
+                    *count += 1;
+                    break;
+                }
+                Some(_) => {
+                    changed = RESTORED
+                        .wait(changed)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                None => {
+                    *changed = Some((me, 1));
+                    break;
+                }
+            }
+        }
+        CurrentDir {
+            previous: std::env::current_dir().ok(),
+            _thread: PhantomData,
+        }
+    }
+}
+
+impl Drop for CurrentDir {
+    fn drop(&mut self) {
+        if let Some(previous) = &self.previous {
+            if let Err(err) = std::env::set_current_dir(previous) {
+                eprintln!(
+                    "warning: cannot change back into {}: {}",
+                    previous.display(),
+                    err
+                );
+            }
+        }
+        let mut changed = CHANGED.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, count)) = &mut *changed {
+            *count -= 1;
+            if *count == 0 {
+                *changed = None;
+                RESTORED.notify_all();
+            }
+        }
+    }
+}
+
+/// Whether every test runs in a working directory of its own; see
+/// [Isolated working directories](self#isolated-working-directories).
+pub fn isolated_cwd_enabled() -> bool {
+    std::env::var(ISOLATED_CWD_ENV).is_ok_and(|value| value == "1" || value == "true")
+}
+
+/// The fresh directory a test runs in.
+pub(crate) struct IsolatedCwd {
+    // Declared first so that the directory is left before it is removed.
+    cwd: CurrentDir,
+    dir: TempDir,
+}
+
+impl IsolatedCwd {
+    /// Changes into a fresh directory for `test` if it has its working
+    /// directory isolated, waiting for other tests to leave theirs.
+    pub(crate) fn enter(test: &TestDef) -> io::Result<Option<Self>> {
+        if !test.isolated_cwd && !isolated_cwd_enabled() {
+            return Ok(None);
+        }
+        let dir = TempDir::new()?;
+        let cwd = CurrentDir::enter();
+        std::env::set_current_dir(dir.path())?;
+        Ok(Some(IsolatedCwd { cwd, dir }))
+    }
+
+    /// Changes back, and removes the directory unless `failed` and
+    /// `TUST_KEEP_TEMP` asks for it to be kept.
+    pub(crate) fn leave(self, test: &TestDef, failed: bool) {
+        let IsolatedCwd { cwd, dir } = self;
+        drop(cwd);
+        if failed && temp::keep_requested() {
+            eprintln!(
+                "note: kept working directory of failed test `{}`: {}",
+                test.path(),
+                dir.keep().display()
+            );
+        }
+    }
+}
+
+/// Appends the entries of `dir` to `out`, `depth` levels deep.
+fn render(dir: &Path, depth: usize, out: &mut String) {
+    let mut entries: Vec<(bool, String, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let path = entry.path();
+                let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                (
+                    !is_dir,
+                    entry.file_name().to_string_lossy().into_owned(),
+                    path,
+                )
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+    for (is_file, name, path) in entries {
+        let indent = "  ".repeat(depth);
+        if is_file {
+            match fs::read_link(&path) {
+                Ok(target) => {
+                    let _ = writeln!(out, "{}{} -> {}", indent, name, target.display());
+                }
+                Err(_) => {
+                    let _ = writeln!(out, "{}{}", indent, name);
+                }
+            }
+        } else {
+            let _ = writeln!(out, "{}{}/", indent, name);
+            render(&path, depth + 1, out);
+        }
+    }
+}
+
+/// `text` without blank lines, trailing spaces, or the indentation common
+/// to its lines, each line ending with a newline.
+fn unindent(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let common = lines
+        .iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| format!("{}\n", &line[common..]))
+        .collect()
+}
@@ -0,0 +1,146 @@
+//! Scenarios: tests written as named Given/When/Then steps.
+//!
+//! A [`Scenario`] runs each step as it is added, on a state the steps share,
+//! and prints the steps to the test's output as they start, so that the
+//! output of a failed test reads as the specification it checks. When a
+//! step fails, the test fails with the step named in front of the failure's
+//! message and the scenario's name in its metadata, keeping the failure's
+//! location and diff; the steps after it do not run.
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn withdrawing_cash() {
+//!     Scenario::new("withdrawing cash")
+//!         .given("an account holding 100", |account: &mut Account| account.deposit(100))
+//!         .when("its holder withdraws 20", |account| account.withdraw(20).unwrap())
+//!         .then("it holds 80", |account| assert_eq!(account.balance(), 80))
+//!         .but("withdrawing 100 more is refused", |account| {
+//!             assert!(account.withdraw(100).is_err())
+//!         });
+//! }
+//! ```
+//!
+//! ```text
+//! Scenario: withdrawing cash
+//!   Given an account holding 100
+//!   When its holder withdraws 20
+//!   Then it holds 80
+//!   But withdrawing 100 more is refused
+//! ```
+
+use std::fmt;
+
+use crate::panic;
+
+/// The steps of a scenario, run as they are added, on a state of type `S`.
+pub struct Scenario<S> {
+    name: String,
+    state: S,
+    /// The keyword of the last Given, When, or Then step, which `And` and
+    /// `But` steps continue.
+    section: Keyword,
+}
+
+/// The keyword a step is written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keyword {
+    Given,
+    When,
+    Then,
+    And,
+    But,
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Keyword::Given => "Given",
+            Keyword::When => "When",
+            Keyword::Then => "Then",
+            Keyword::And => "And",
+            Keyword::But => "But",
+        })
+    }
+}
+
+impl<S: Default> Scenario<S> {
+    /// Starts the scenario `name` with the default state.
+    pub fn new(name: impl Into<String>) -> Self {
+        Scenario::with_state(name, S::default())
+    }
+}
+
+impl<S> Scenario<S> {
+    /// Starts the scenario `name` with `state`.
+    pub fn with_state(name: impl Into<String>, state: S) -> Self {
+        let name = name.into();
+        println!("Scenario: {}", name);
+        Scenario {
+            name,
+            state,
+            section: Keyword::Given,
+        }
+    }
+
+    /// Runs the Given step `step`, which sets up the state.
+    #[track_caller]
+    pub fn given(self, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        self.step(Keyword::Given, step, f)
+    }
+
+    /// Runs the When step `step`, which acts on the state.
+    #[track_caller]
+    pub fn when(self, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        self.step(Keyword::When, step, f)
+    }
+
+    /// Runs the Then step `step`, which checks the state.
+    #[track_caller]
+    pub fn then(self, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        self.step(Keyword::Then, step, f)
+    }
+
+    /// Runs the step `step`, another of the kind before it.
+    #[track_caller]
+    pub fn and(self, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        self.step(Keyword::And, step, f)
+    }
+
+    /// Runs the step `step`, another of the kind before it, in contrast
+    /// with that one.
+    #[track_caller]
+    pub fn but(self, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        self.step(Keyword::But, step, f)
+    }
+
+    /// The state as the steps so far left it.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Ends the scenario, returning its state.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    #[track_caller]
+    fn step(mut self, keyword: Keyword, step: &str, f: impl FnOnce(&mut S)) -> Self {
+        if !matches!(keyword, Keyword::And | Keyword::But) {
+            self.section = keyword;
+        }
+        println!("  {} {}", keyword, step);
+        if let Err(mut failure) = panic::catch(|| f(&mut self.state)) {
+            failure.message = format!("{} {}: {}", keyword, step, failure.message);
+            failure
+                .metadata
+                .insert(0, ("scenario".to_string(), self.name));
+            if matches!(keyword, Keyword::And | Keyword::But) {
+                failure
+                    .metadata
+                    .insert(1, ("section".to_string(), self.section.to_string()));
+            }
+            failure.raise();
+        }
+        self
+    }
+}
@@ -0,0 +1,224 @@
+//! Splitting a run across machines with `--shard K/N`.
+//!
+//! Each of `N` runs given the same tests and `--shard K/N` for a different
+//! `K` runs a disjoint part of them, and together they run every test once.
+//! By default a test's shard is picked by a stable hash of its name, and so
+//! stays the same as tests are added or removed. Tests that depend on each
+//! other, directly or not, run on the same shard, picked by the name of the
+//! first of them, so that no test runs on the shards of several tests
+//! depending on it.
+//!
+//! With `--shard-timings FILE`, tests are instead balanced by how long they
+//! took in an earlier run, as recorded by `--report timings=FILE` or in the
+//! runner's [timings cache](crate::timings): the slowest tests are spread
+//! first, each onto the shard with the least work so far, and tests that
+//! depend on each other as one. Every shard must read the same file, or
+//! tests may be run twice or not at all.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::depends;
+use crate::registry::TestDef;
+use crate::timings::Timings;
+
+/// Part `index` of `count` equal parts of the tests, counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Shard `index` of `count`, if `1 <= index <= count`.
+    pub fn new(index: usize, count: usize) -> Option<Self> {
+        (1..=count)
+            .contains(&index)
+            .then_some(Shard { index, count })
+    }
+
+    /// The tests of `tests` in this shard, in their original order.
+    pub fn select(
+        &self,
+        tests: &[&'static TestDef],
+        timings: Option<&Timings>,
+    ) -> Vec<&'static TestDef> {
+        let groups = depends::groups(tests);
+        let shards = match timings {
+            Some(timings) => balance(tests, &groups, self.count, timings),
+            None => tests
+                .iter()
+                .map(|test| (hash(&test.path()) % self.count as u64) as usize)
+                .collect(),
+        };
+        tests
+            .iter()
+            .zip(groups)
+            .filter(|&(_, group)| shards[group] == self.index - 1)
+            .map(|(&test, _)| test)
+            .collect()
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Assigns each group of `tests`, as [`depends::groups`] gives them in
+/// `groups`, to one of `count` shards, slowest first, each to the shard with
+/// the least work so far. The shard of a group is given at the index of its
+/// first test. Tests without a timing are assumed to take as long as the
+/// average test that has one.
+fn balance(
+    tests: &[&'static TestDef],
+    groups: &[usize],
+    count: usize,
+    timings: &Timings,
+) -> Vec<usize> {
+    let average = timings.average(tests);
+    let mut durations = vec![Duration::ZERO; tests.len()];
+    let mut sizes = vec![0usize; tests.len()];
+    for (test, &group) in tests.iter().zip(groups) {
+        durations[group] += timings.get(test).unwrap_or(average);
+        sizes[group] += 1;
+    }
+    let paths: Vec<String> = tests.iter().map(|test| test.path()).collect();
+    let mut order: Vec<usize> = (0..tests.len())
+        .filter(|&index| groups[index] == index)
+        .collect();
+    order.sort_by(|&a, &b| {
+        durations[b]
+            .cmp(&durations[a])
+            .then_with(|| paths[a].cmp(&paths[b]))
+    });
+
+    let mut loads = vec![Duration::ZERO; count];
+    let mut shard_sizes = vec![0usize; count];
+    let mut shards = vec![0; tests.len()];
+    for group in order {
+        let shard = (0..count)
+            .min_by_key(|&shard| (loads[shard], shard_sizes[shard], shard))
+            .unwrap_or(0);
+        loads[shard] += durations[group];
+        shard_sizes[shard] += sizes[group];
+        shards[group] = shard;
+    }
+    shards
+}
+
+/// FNV-1a, which unlike `std`'s hashers is the same on every machine and
+/// Rust release.
+fn hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(name: &'static str, depends_on: &'static [&'static str]) -> &'static TestDef {
+        Box::leak(Box::new(TestDef {
+            depends_on,
+            ..TestDef::ran_elsewhere("krate", name, "t.rs", 1)
+        }))
+    }
+
+    fn tests() -> Vec<&'static TestDef> {
+        let mut tests: Vec<_> = (0..40)
+            .map(|index| test(Box::leak(format!("t{}", index).into_boxed_str()), &[]))
+            .collect();
+        tests.extend([
+            test("setup", &[]),
+            test("reads", &["setup"]),
+            test("writes", &["setup"]),
+            test("cleanup", &["writes"]),
+        ]);
+        tests
+    }
+
+    fn assert_partition(shards: &[Vec<&'static TestDef>], tests: &[&'static TestDef]) {
+        let mut seen: Vec<String> = shards.iter().flatten().map(|test| test.path()).collect();
+        seen.sort();
+        let mut expected: Vec<String> = tests.iter().map(|test| test.path()).collect();
+        expected.sort();
+        assert_eq!(seen, expected, "shards are not disjoint or miss tests");
+
+        let group = ["setup", "reads", "writes", "cleanup"];
+        let holding: Vec<_> = shards
+            .iter()
+            .filter(|shard| shard.iter().any(|test| group.contains(&test.name)))
+            .collect();
+        assert_eq!(holding.len(), 1, "dependent tests were split");
+        assert_eq!(
+            holding[0]
+                .iter()
+                .filter(|test| group.contains(&test.name))
+                .count(),
+            group.len()
+        );
+    }
+
+    #[test]
+    fn shards_by_hash_are_disjoint_and_keep_groups_together() {
+        let tests = tests();
+        for count in 1..=5 {
+            let shards: Vec<_> = (1..=count)
+                .map(|index| Shard::new(index, count).unwrap().select(&tests, None))
+                .collect();
+            assert_partition(&shards, &tests);
+        }
+    }
+
+    #[test]
+    fn shards_by_timings_are_disjoint_balanced_and_keep_groups_together() {
+        let tests = tests();
+        let mut timings = Timings::default();
+        for (index, test) in tests.iter().enumerate() {
+            timings.insert(test, Duration::from_millis(10 + index as u64));
+        }
+        let shards: Vec<_> = (1..=3)
+            .map(|index| Shard::new(index, 3).unwrap().select(&tests, Some(&timings)))
+            .collect();
+        assert_partition(&shards, &tests);
+        let loads: Vec<Duration> = shards
+            .iter()
+            .map(|shard| shard.iter().map(|test| timings.get(test).unwrap()).sum())
+            .collect();
+        let (min, max) = (loads.iter().min().unwrap(), loads.iter().max().unwrap());
+        assert!(*max - *min <= Duration::from_millis(200), "{:?}", loads);
+    }
+
+    #[test]
+    fn selects_in_the_original_order() {
+        let tests = tests();
+        let selected = Shard::new(1, 2).unwrap().select(&tests, None);
+        let positions: Vec<_> = selected
+            .iter()
+            .map(|test| tests.iter().position(|t| std::ptr::eq(*t, *test)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn rejects_shards_out_of_range() {
+        assert_eq!(Shard::new(0, 3), None);
+        assert_eq!(Shard::new(4, 3), None);
+        assert_eq!(
+            Shard::new(3, 3).map(|shard| shard.to_string()),
+            Some("3/3".into())
+        );
+    }
+
+    #[test]
+    fn hash_is_fnv_1a() {
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,290 @@
+//! Large data sets loaded once per run and shared by every test.
+//!
+//! A [`SharedData`] returned by a session-scoped fixture is loaded the first
+//! time a test asks for it and then shared, read-only, by every test and
+//! worker thread of the run; cloning it only counts a reference. The runner
+//! reports how long each load took after the run, apart from the durations
+//! of the tests, so that a slow first test can be told from a slow load:
+//!
+//! ```ignore
+//! #[fixture(scope = "session")]
+//! fn corpus() -> SharedData<Corpus> {
+//!     SharedData::load("corpus", || Corpus::parse("data/corpus.jsonl"))
+//! }
+//!
+//! #[fixture(scope = "session")]
+//! fn weights() -> SharedData<MappedFile> {
+//!     SharedData::map("data/weights.bin").expect("cannot map the weights")
+//! }
+//!
+//! #[tust::test]
+//! fn classifies_the_corpus(corpus: SharedData<Corpus>, weights: SharedData<MappedFile>) {
+//!     let model = Model::from_bytes(&weights);
+//!     assert!(corpus.iter().all(|doc| model.classify(doc).is_some()));
+//! }
+//! ```
+//!
+//! ```text
+//! shared data:
+//!       4.182s  corpus, loaded by classifier::classifies_the_corpus
+//!       0.003s  data/weights.bin (2.1 GiB, memory-mapped)
+//! ```
+//!
+//! A load runs as part of the test that first asks for the data, and counts
+//! toward that test's time limit. With `--process-per-test`, each test's
+//! process loads the data anew, and each load is reported.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::alloc;
+use crate::registry::{self, TestDef};
+
+/// A value loaded once and shared read-only by the tests of a run.
+pub struct SharedData<T: ?Sized> {
+    value: Arc<T>,
+}
+
+impl<T: Send + Sync + 'static> SharedData<T> {
+    /// Loads the data set `name` with `load`, recording how long it took.
+    #[track_caller]
+    pub fn load(name: impl Into<String>, load: impl FnOnce() -> T) -> Self {
+        let start = Instant::now();
+        let value = load();
+        record(DataLoad {
+            name: alloc::untracked(|| name.into()),
+            test: registry::current(),
+            duration: start.elapsed(),
+            bytes: None,
+            mapped: false,
+        });
+        SharedData {
+            value: Arc::new(value),
+        }
+    }
+}
+
+impl SharedData<MappedFile> {
+    /// Maps the file at `path` into memory, recording how long it took.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened or mapped.
+    pub fn map(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let start = Instant::now();
+        let file = MappedFile::open(path)?;
+        record(DataLoad {
+            name: alloc::untracked(|| path.display().to_string()),
+            test: registry::current(),
+            duration: start.elapsed(),
+            bytes: Some(file.len() as u64),
+            mapped: file.is_mapped(),
+        });
+        Ok(SharedData {
+            value: Arc::new(file),
+        })
+    }
+}
+
+impl<T: ?Sized> Clone for SharedData<T> {
+    fn clone(&self) -> Self {
+        SharedData {
+            value: Arc::clone(&self.value),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for SharedData<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for SharedData<T> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SharedData<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedData").field(&&*self.value).finish()
+    }
+}
+
+/// The contents of a file, mapped into memory on Unix and read into it
+/// elsewhere. Dereferences to the file's bytes.
+///
+/// The file must not change while it is mapped.
+pub struct MappedFile {
+    contents: Contents,
+}
+
+enum Contents {
+    #[cfg(unix)]
+    Mapped {
+        ptr: *mut libc::c_void,
+        len: usize,
+    },
+    Read(Vec<u8>),
+}
+
+// The mapping is private and read-only, and unmapped only when dropped.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// Maps the file at `path`, or reads it where files cannot be mapped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened, read, or mapped.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file too large to map"))?;
+        #[cfg(unix)]
+        if len > 0 {
+            use std::os::unix::io::AsRawFd;
+
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(MappedFile {
+                contents: Contents::Mapped { ptr, len },
+            });
+        }
+        let mut bytes = Vec::with_capacity(len);
+        file.read_to_end(&mut bytes)?;
+        Ok(MappedFile {
+            contents: Contents::Read(bytes),
+        })
+    }
+
+    /// Whether the file is mapped rather than read into memory.
+    pub fn is_mapped(&self) -> bool {
+        match self.contents {
+            #[cfg(unix)]
+            Contents::Mapped { .. } => true,
+            Contents::Read(_) => false,
+        }
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.contents {
+            #[cfg(unix)]
+            Contents::Mapped { ptr, len } => unsafe {
+                std::slice::from_raw_parts(*ptr as *const u8, *len)
+            },
+            Contents::Read(bytes) => bytes,
+        }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedFile")
+            .field("len", &self.len())
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Contents::Mapped { ptr, len } = self.contents {
+            unsafe {
+                libc::munmap(ptr, len);
+            }
+        }
+    }
+}
+
+/// One load of a [`SharedData`].
+#[derive(Debug, Clone)]
+pub struct DataLoad {
+    /// The name given to [`SharedData::load`], or the path of a mapped file.
+    pub name: String,
+    /// The test that first asked for the data, if a test did.
+    pub test: Option<&'static TestDef>,
+    pub duration: Duration,
+    /// The size of the data, where known.
+    pub bytes: Option<u64>,
+    /// Whether the data is a file mapped into memory.
+    pub mapped: bool,
+}
+
+static LOADS: Mutex<Vec<DataLoad>> = Mutex::new(Vec::new());
+
+fn record(load: DataLoad) {
+    // The record outlives the test that made it.
+    alloc::untracked(|| LOADS.lock().unwrap_or_else(|e| e.into_inner()).push(load));
+}
+
+/// Removes the loads recorded so far, and returns them.
+pub fn take_loads() -> Vec<DataLoad> {
+    std::mem::take(&mut *LOADS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+pub(crate) fn to_json(loads: &[DataLoad]) -> Value {
+    loads
+        .iter()
+        .map(|load| {
+            json!({
+                "name": load.name,
+                "load_time": load.duration.as_secs_f64(),
+                "bytes": load.bytes,
+                "mapped": load.mapped,
+            })
+        })
+        .collect()
+}
+
+/// Records the loads a child process of `--process-per-test` made while
+/// running `test`, as [`to_json`] wrote them.
+pub(crate) fn record_json(test: &'static TestDef, loads: &Value) {
+    for load in loads.as_array().into_iter().flatten() {
+        let (Some(name), Some(secs)) = (load["name"].as_str(), load["load_time"].as_f64()) else {
+            continue;
+        };
+        record(DataLoad {
+            name: name.to_string(),
+            test: Some(test),
+            duration: Duration::try_from_secs_f64(secs).unwrap_or_default(),
+            bytes: load["bytes"].as_u64(),
+            mapped: load["mapped"].as_bool() == Some(true),
+        });
+    }
+}
@@ -0,0 +1,893 @@
+//! Snapshot storage behind `assert_snapshot!`.
+//!
+//! Each snapshot lives in `tests/snapshots/<name>.snap` under the manifest
+//! directory of the crate being tested, or in the `snapshot-dir` given by
+//! `--snapshot-dir`, [`DIR_ENV`], or the `tust.toml` file (see
+//! [`crate::config`]). A snapshot file starts with a small
+//! header recording where it came from, followed by the snapshot itself:
+//!
+//! ```text
+//! ---
+//! source: tests/render.rs
+//! expression: render(&page)
+//! ---
+//! <h1>Hello</h1>
+//! ```
+//!
+//! When a value differs from its stored snapshot, or no snapshot exists yet,
+//! the new value is written next to it as `<name>.snap.new` and the assertion
+//! fails. Review the `.snap.new` files, then either rename them over the
+//! `.snap` files (or rerun with `TUST_UPDATE_SNAPSHOTS=1`) to accept them, or
+//! delete them to reject them. With `TUST_UPDATE_SNAPSHOTS=1` set, changed
+//! and new snapshots are written straight to their `.snap` files and the
+//! assertion passes.
+//!
+//! Unnamed snapshots are named after the running test, with `-2`, `-3`, ...
+//! appended to the second and later snapshots taken by the same test.
+//!
+//! # Binary and image snapshots
+//!
+//! `assert_binary_snapshot!` stores bytes as they are, in a file without a
+//! header whose extension comes from the snapshot's name, such as
+//! `logo.png`, or is `bin`; a name of just an extension, such as `".png"`,
+//! names the snapshot after the test. A changed or new value is written
+//! next to it with `new` before the extension, such as `logo.new.png`, so
+//! that it still opens in a viewer, and reviewed like a text snapshot.
+//!
+//! `assert_image_snapshot!` decodes its value and the stored snapshot as
+//! PNG images and compares them pixel by pixel, so that a re-encoded image
+//! matches, and so, within [`ImageOptions`], do images whose pixels differ
+//! a little, such as those of text rendered with different antialiasing.
+//! On a mismatch it also writes a diff image, `<name>.diff.png`, showing
+//! the snapshot faded with the differing pixels in red. Under the tust
+//! runner the snapshot, the new image, and the diff image are
+//! [attached](crate::context::TestContext::attach) to the test, so that the
+//! reports link to them; under libtest the diff image is written next to
+//! the new one.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::alloc;
+use crate::config::{self, Config};
+use crate::context::TestContext;
+use crate::png::{self, Image};
+use crate::registry::{self, TestDef};
+
+/// Environment variable that accepts changed and new snapshots when set to
+/// `1` or `true`.
+pub const UPDATE_ENV: &str = "TUST_UPDATE_SNAPSHOTS";
+
+/// Environment variable naming the directory holding snapshots, relative to
+/// the manifest directory.
+pub const DIR_ENV: &str = "TUST_SNAPSHOT_DIR";
+
+/// Directory holding snapshots unless configured otherwise, relative to the
+/// manifest directory.
+const SNAPSHOT_DIR: &str = "tests/snapshots";
+
+/// Snapshots changed during this run, in the order they were recorded.
+static CHANGES: Mutex<Vec<SnapshotChange>> = Mutex::new(Vec::new());
+
+/// Number of unnamed snapshots taken so far, per base name.
+static COUNTERS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+/// A value to compare against its stored snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotAssertion<'a> {
+    /// `CARGO_MANIFEST_DIR` of the crate taking the snapshot.
+    pub manifest_dir: &'a str,
+    /// Source file of the assertion, recorded in the snapshot header.
+    pub source_file: &'a str,
+    /// Source text of the asserted expression, recorded in the header.
+    pub expression: &'a str,
+    /// Explicit snapshot name; derived from the running test if `None`.
+    pub name: Option<&'a str>,
+    /// The rendered value.
+    pub contents: &'a str,
+}
+
+/// Result of comparing a value against its stored snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    /// The value matches the stored snapshot.
+    Matched,
+    /// The snapshot was written because [`UPDATE_ENV`] is set.
+    Accepted { path: PathBuf },
+    /// The value differs from the stored snapshot; it was written to
+    /// `pending` for review.
+    Mismatched {
+        path: PathBuf,
+        pending: PathBuf,
+        old: String,
+    },
+    /// No snapshot exists yet; the value was written to `pending` for review.
+    Missing { path: PathBuf, pending: PathBuf },
+}
+
+/// A snapshot written during the run, summarized once the run ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotChange {
+    /// The written file, relative to the manifest directory where possible.
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// How a snapshot changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Written to a `.snap.new` file, or a binary snapshot's `.new` file,
+    /// awaiting review.
+    Pending,
+    /// Written straight to its snapshot file.
+    Accepted,
+}
+
+/// Failure to read or write a snapshot file.
+#[derive(Debug)]
+pub struct SnapshotError {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot access snapshot {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Bytes to compare against their stored snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct BinarySnapshotAssertion<'a> {
+    /// `CARGO_MANIFEST_DIR` of the crate taking the snapshot.
+    pub manifest_dir: &'a str,
+    /// Source text of the asserted expression.
+    pub expression: &'a str,
+    /// Explicit snapshot name, with the extension of its file; derived from
+    /// the running test if `None` or just an extension.
+    pub name: Option<&'a str>,
+    pub contents: &'a [u8],
+    /// Compares the bytes as PNG images if set, and byte for byte if not.
+    pub image: Option<ImageOptions>,
+}
+
+/// How different two images may be and still match, for
+/// `assert_image_snapshot!`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageOptions {
+    /// How different two pixels may be, from 0, for none, to 1, for black
+    /// and white, by the perceptual color difference of pixelmatch. 0.1
+    /// unless set.
+    pub threshold: f64,
+    /// How many pixels may differ by more than the threshold; none unless
+    /// set.
+    pub max_diff_pixels: u64,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            threshold: 0.1,
+            max_diff_pixels: 0,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max_diff_pixels(mut self, pixels: u64) -> Self {
+        self.max_diff_pixels = pixels;
+        self
+    }
+}
+
+/// Result of comparing bytes against their stored snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinarySnapshotOutcome {
+    /// The value matches the stored snapshot.
+    Matched,
+    /// The snapshot was written because [`UPDATE_ENV`] is set.
+    Accepted { path: PathBuf },
+    /// The value differs from the stored snapshot; it was written to
+    /// `pending` for review.
+    Mismatched {
+        path: PathBuf,
+        pending: PathBuf,
+        /// How the value differs, such as `2 of 100 pixels differ`.
+        difference: String,
+        /// The diff image of an image snapshot, where it was written.
+        diff_image: Option<PathBuf>,
+    },
+    /// No snapshot exists yet; the value was written to `pending` for review.
+    Missing { path: PathBuf, pending: PathBuf },
+    /// The value of an image snapshot is not a PNG image, for `reason`.
+    NotAnImage { reason: String },
+}
+
+/// Compares `assertion` against its stored snapshot, writing the new value
+/// as described in the [module documentation](self#binary-and-image-snapshots).
+///
+/// # Panics
+///
+/// Panics if `assertion` has no name and no test is running on the current
+/// thread.
+pub fn check_binary(
+    assertion: &BinarySnapshotAssertion<'_>,
+) -> Result<BinarySnapshotOutcome, SnapshotError> {
+    let default_extension = if assertion.image.is_some() {
+        "png"
+    } else {
+        "bin"
+    };
+    let (name, extension) = match assertion.name {
+        Some(name) => match name.rsplit_once('.') {
+            Some(("", extension)) => (next_unnamed(&default_name()), extension),
+            Some((name, extension)) => (sanitize(name), extension),
+            None => (sanitize(name), default_extension),
+        },
+        None => (next_unnamed(&default_name()), default_extension),
+    };
+    let extension = sanitize(extension);
+    let root = Path::new(assertion.manifest_dir);
+    let dir = root.join(dir(root));
+    let path = dir.join(format!("{}.{}", name, extension));
+    let pending = dir.join(format!("{}.new.{}", name, extension));
+    let diff_path = dir.join(format!("{}.diff.png", name));
+
+    let new_image = match assertion.image {
+        Some(_) => match png::decode(assertion.contents) {
+            Ok(image) => Some(image),
+            Err(err) => {
+                return Ok(BinarySnapshotOutcome::NotAnImage {
+                    reason: err.to_string(),
+                })
+            }
+        },
+        None => None,
+    };
+    let old = match fs::read(&path) {
+        Ok(old) => Some(old),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(SnapshotError { path, error }),
+    };
+
+    let mut diff_image = None;
+    let difference = match (&old, &new_image, assertion.image) {
+        (None, ..) => None,
+        (Some(old), _, _) if old.as_slice() == assertion.contents => {
+            remove_stale(&pending)?;
+            remove_stale(&diff_path)?;
+            return Ok(BinarySnapshotOutcome::Matched);
+        }
+        (Some(old), Some(new), Some(options)) => match png::decode(old) {
+            Ok(old) => match compare_images(&old, new, options) {
+                ImageComparison::Matched => {
+                    remove_stale(&pending)?;
+                    remove_stale(&diff_path)?;
+                    return Ok(BinarySnapshotOutcome::Matched);
+                }
+                ImageComparison::Sizes(difference) => Some(difference),
+                ImageComparison::Pixels { difference, diff } => {
+                    diff_image = Some(diff);
+                    Some(difference)
+                }
+            },
+            Err(err) => Some(format!("the snapshot is not a PNG image: {}", err)),
+        },
+        (Some(old), ..) => Some(describe_bytes(old, assertion.contents)),
+    };
+
+    if update_requested() {
+        write(&path, assertion.contents)?;
+        remove_stale(&pending)?;
+        remove_stale(&diff_path)?;
+        record(root, &path, ChangeKind::Accepted);
+        return Ok(BinarySnapshotOutcome::Accepted { path });
+    }
+
+    write(&pending, assertion.contents)?;
+    record(root, &pending, ChangeKind::Pending);
+    let Some(difference) = difference else {
+        return Ok(BinarySnapshotOutcome::Missing { path, pending });
+    };
+    let ctx = TestContext::current().filter(|_| assertion.image.is_some());
+    if let Some(ctx) = ctx {
+        ctx.attach_file(&path);
+        ctx.attach_file(&pending);
+    }
+    remove_stale(&diff_path)?;
+    let diff_image = match (diff_image, ctx) {
+        (Some(diff), Some(ctx)) => {
+            Some(ctx.attach(&format!("{}.diff.png", name), png::encode(&diff)))
+        }
+        (Some(diff), None) => {
+            write(&diff_path, png::encode(&diff))?;
+            Some(diff_path)
+        }
+        (None, _) => None,
+    };
+    Ok(BinarySnapshotOutcome::Mismatched {
+        path,
+        pending,
+        difference,
+        diff_image,
+    })
+}
+
+/// How two byte strings differ, for a mismatch of binary snapshots.
+fn describe_bytes(old: &[u8], new: &[u8]) -> String {
+    let first = old.iter().zip(new).position(|(old, new)| old != new);
+    let mut difference = match first {
+        Some(offset) => format!(
+            "the value differs from the snapshot from byte {} on",
+            offset
+        ),
+        None => "the shorter of the value and the snapshot starts the other".to_string(),
+    };
+    if old.len() != new.len() {
+        difference.push_str(&format!(
+            "; it has {} bytes, the snapshot {}",
+            new.len(),
+            old.len()
+        ));
+    }
+    difference
+}
+
+enum ImageComparison {
+    Matched,
+    Sizes(String),
+    Pixels { difference: String, diff: Image },
+}
+
+/// Compares `old` and `new` pixel by pixel, drawing a diff image of them if
+/// more pixels differ than `options` allow.
+fn compare_images(old: &Image, new: &Image, options: ImageOptions) -> ImageComparison {
+    if (old.width, old.height) != (new.width, new.height) {
+        return ImageComparison::Sizes(format!(
+            "the image is {}x{} pixels, the snapshot {}x{}",
+            new.width, new.height, old.width, old.height
+        ));
+    }
+    // As pixelmatch does: 35215 is the largest difference there can be.
+    let limit = 35215.0 * options.threshold * options.threshold;
+    let pixels = old.width as usize * old.height as usize;
+    let mut differing = 0u64;
+    let mut diff = Vec::with_capacity(pixels * 4);
+    for index in 0..pixels {
+        let (before, after) = (old.pixel(index), new.pixel(index));
+        if color_delta(before, after) > limit {
+            differing += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let [y, _, _] = yiq(blend(before));
+            let faded = (255.0 - (255.0 - y) * 0.1) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+    if differing <= options.max_diff_pixels {
+        return ImageComparison::Matched;
+    }
+    let mut difference = format!(
+        "{} of {} pixels ({:.2}%) differ by more than the threshold of {}",
+        differing,
+        pixels,
+        differing as f64 * 100.0 / pixels as f64,
+        options.threshold
+    );
+    if options.max_diff_pixels > 0 {
+        difference.push_str(&format!("; at most {} may", options.max_diff_pixels));
+    }
+    ImageComparison::Pixels {
+        difference,
+        diff: Image {
+            width: old.width,
+            height: old.height,
+            rgba: diff,
+        },
+    }
+}
+
+/// `pixel` blended onto white, as RGB.
+fn blend([r, g, b, a]: [u8; 4]) -> [f64; 3] {
+    let alpha = f64::from(a) / 255.0;
+    [r, g, b].map(|channel| 255.0 + (f64::from(channel) - 255.0) * alpha)
+}
+
+fn yiq([r, g, b]: [f64; 3]) -> [f64; 3] {
+    [
+        r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+        r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+        r * 0.21147017 - g * 0.52261711 + b * 0.31114694,
+    ]
+}
+
+/// The squared perceptual difference of two pixels, in YIQ, as pixelmatch
+/// measures it.
+fn color_delta(a: [u8; 4], b: [u8; 4]) -> f64 {
+    if a == b {
+        return 0.0;
+    }
+    let [y1, i1, q1] = yiq(blend(a));
+    let [y2, i2, q2] = yiq(blend(b));
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+/// Compares `assertion` against its stored snapshot, writing the new value
+/// as described in the [module documentation](self).
+///
+/// # Panics
+///
+/// Panics if `assertion` has no name and no test is running on the current
+/// thread.
+pub fn check(assertion: &SnapshotAssertion<'_>) -> Result<SnapshotOutcome, SnapshotError> {
+    let name = match assertion.name {
+        Some(name) => sanitize(name),
+        None => next_unnamed(&default_name()),
+    };
+    let root = Path::new(assertion.manifest_dir);
+    let dir = root.join(dir(root));
+    let path = dir.join(format!("{}.snap", name));
+    let pending = dir.join(format!("{}.snap.new", name));
+
+    let old = match fs::read_to_string(&path) {
+        Ok(file) => Some(parse_contents(&file)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(SnapshotError { path, error }),
+    };
+    let new = normalize(assertion.contents);
+
+    if old.as_deref() == Some(new.as_str()) {
+        remove_stale(&pending)?;
+        return Ok(SnapshotOutcome::Matched);
+    }
+
+    let file = render_file(assertion, &new);
+    if update_requested() {
+        write(&path, &file)?;
+        remove_stale(&pending)?;
+        record(root, &path, ChangeKind::Accepted);
+        return Ok(SnapshotOutcome::Accepted { path });
+    }
+
+    write(&pending, &file)?;
+    record(root, &pending, ChangeKind::Pending);
+    Ok(match old {
+        Some(old) => SnapshotOutcome::Mismatched { path, pending, old },
+        None => SnapshotOutcome::Missing { path, pending },
+    })
+}
+
+/// The directory holding the snapshots of the crate at `root`: [`DIR_ENV`],
+/// which the runner sets from its options, or else the `snapshot-dir` of
+/// the crate's `tust.toml`, for tests run by libtest.
+fn dir(root: &Path) -> PathBuf {
+    if let Some(dir) = std::env::var_os(DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let profile = std::env::var(config::PROFILE_ENV).ok();
+    config::find(root)
+        .and_then(|path| Config::load(&path, profile.as_deref()).ok())
+        .and_then(|config| config.snapshot_dir)
+        .unwrap_or_else(|| PathBuf::from(SNAPSHOT_DIR))
+}
+
+/// Returns the snapshots written during the run so far, clearing the list.
+pub fn take_changes() -> Vec<SnapshotChange> {
+    std::mem::take(&mut *CHANGES.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Whether [`UPDATE_ENV`] asks for snapshots to be accepted.
+pub fn update_requested() -> bool {
+    std::env::var(UPDATE_ENV).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// Names the running test's snapshots after its module path and name, e.g.
+/// `render__pages__home` for `pages::home` in the `render` crate.
+fn default_name() -> String {
+    if let Some(test) = registry::current() {
+        return sanitize(&format!("{}::{}", test.module_path, test.name));
+    }
+    // libtest names its test threads after the test being run.
+    match std::thread::current().name() {
+        Some(name) if name != "main" => sanitize(name),
+        _ => panic!("snapshots taken outside of a test must be given a name"),
+    }
+}
+
+/// Restarts the numbering of `test`'s unnamed snapshots, so that a retried
+/// test compares against the same files again.
+pub(crate) fn restart_numbering(test: &TestDef) {
+    let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(counters) = counters.as_mut() {
+        counters.remove(&sanitize(&format!("{}::{}", test.module_path, test.name)));
+    }
+}
+
+fn next_unnamed(base: &str) -> String {
+    let count = alloc::untracked(|| {
+        let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counters
+            .get_or_insert_with(HashMap::new)
+            .entry(base.to_string())
+            .or_insert(0);
+        *count += 1;
+        *count
+    });
+    match count {
+        1 => base.to_string(),
+        n => format!("{}-{}", base, n),
+    }
+}
+
+/// Turns a test path into a file name: `a::b` becomes `a__b`, and characters
+/// that are unsafe in file names become `_`.
+fn sanitize(name: &str) -> String {
+    name.replace("::", "__")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Normalizes line endings and trailing newlines so snapshots survive
+/// editors and `core.autocrlf`.
+fn normalize(contents: &str) -> String {
+    contents
+        .replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+fn parse_contents(file: &str) -> String {
+    let file = file.replace("\r\n", "\n");
+    let body = file
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+        .map_or(file.as_str(), |(_header, body)| body);
+    normalize(body)
+}
+
+fn render_file(assertion: &SnapshotAssertion<'_>, contents: &str) -> String {
+    let expression = assertion.expression.replace('\n', " ");
+    format!(
+        "---\nsource: {}\nexpression: {}\n---\n{}\n",
+        assertion.source_file, expression, contents
+    )
+}
+
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), SnapshotError> {
+    let result = match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).and_then(|()| fs::write(path, contents)),
+        None => fs::write(path, contents),
+    };
+    result.map_err(|error| SnapshotError {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn remove_stale(pending: &Path) -> Result<(), SnapshotError> {
+    match fs::remove_file(pending) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(SnapshotError {
+            path: pending.to_path_buf(),
+            error,
+        }),
+    }
+}
+
+fn record(root: &Path, path: &Path, kind: ChangeKind) {
+    alloc::untracked(|| {
+        let path = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+        let mut changes = CHANGES.lock().unwrap_or_else(|e| e.into_inner());
+        // A retried test records the same change again.
+        if !changes.iter().any(|change| change.path == path) {
+            changes.push(SnapshotChange { path, kind });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes the tests, which share the list of changes and set
+    /// [`UPDATE_ENV`].
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// An empty manifest directory for `test`.
+    fn manifest_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tust-snapshot-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn text<'a>(root: &'a Path, name: &'a str, contents: &'a str) -> SnapshotAssertion<'a> {
+        SnapshotAssertion {
+            manifest_dir: root.to_str().unwrap(),
+            source_file: "tests/render.rs",
+            expression: "render(\n    &page,\n)",
+            name: Some(name),
+            contents,
+        }
+    }
+
+    fn binary<'a>(
+        root: &'a Path,
+        name: &'a str,
+        contents: &'a [u8],
+    ) -> BinarySnapshotAssertion<'a> {
+        BinarySnapshotAssertion {
+            manifest_dir: root.to_str().unwrap(),
+            expression: "logo()",
+            name: Some(name),
+            contents,
+            image: None,
+        }
+    }
+
+    fn image_assertion<'a>(
+        root: &'a Path,
+        contents: &'a [u8],
+        options: ImageOptions,
+    ) -> BinarySnapshotAssertion<'a> {
+        BinarySnapshotAssertion {
+            image: Some(options),
+            ..binary(root, "chart.png", contents)
+        }
+    }
+
+    fn image(width: u32, height: u32, rgba: impl Fn(usize) -> [u8; 4]) -> Image {
+        let pixels = width as usize * height as usize;
+        Image {
+            width,
+            height,
+            rgba: (0..pixels).flat_map(rgba).collect(),
+        }
+    }
+
+    /// `image` encoded differently from [`png::encode`], so that the bytes
+    /// differ and only the pixels match.
+    fn reencode(image: &Image) -> Vec<u8> {
+        let mut encoded = png::encode(image);
+        // An unknown ancillary chunk before IEND changes the bytes only.
+        let end = encoded.len() - 12;
+        let chunk = [0, 0, 0, 0, b't', b'E', b'S', b't', 0, 0, 0, 0];
+        encoded.splice(end..end, chunk);
+        encoded
+    }
+
+    #[test]
+    fn text_snapshots_round_trip() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let root = manifest_dir("text");
+        let dir = root.join("tests/snapshots");
+        let (path, pending) = (
+            dir.join("pages__home.snap"),
+            dir.join("pages__home.snap.new"),
+        );
+        take_changes();
+
+        let first = check(&text(&root, "pages::home", "<h1>Hello</h1>\n")).unwrap();
+        assert_eq!(
+            first,
+            SnapshotOutcome::Missing {
+                path: path.clone(),
+                pending: pending.clone()
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(&pending).unwrap(),
+            "---\nsource: tests/render.rs\nexpression: render(     &page, )\n---\n<h1>Hello</h1>\n"
+        );
+        fs::rename(&pending, &path).unwrap();
+        let same = text(&root, "pages::home", "<h1>Hello</h1>\r\n\n");
+        assert_eq!(check(&same).unwrap(), SnapshotOutcome::Matched);
+
+        let changed = check(&text(&root, "pages::home", "<h1>Bye</h1>")).unwrap();
+        assert_eq!(
+            changed,
+            SnapshotOutcome::Mismatched {
+                path: path.clone(),
+                pending: pending.clone(),
+                old: "<h1>Hello</h1>".to_string()
+            }
+        );
+        assert!(fs::read_to_string(&pending)
+            .unwrap()
+            .ends_with("\n---\n<h1>Bye</h1>\n"));
+        // Matching again removes the stale value awaiting review.
+        assert_eq!(check(&same).unwrap(), SnapshotOutcome::Matched);
+        assert!(!pending.exists());
+        assert_eq!(
+            take_changes(),
+            [SnapshotChange {
+                path: PathBuf::from("tests/snapshots/pages__home.snap.new"),
+                kind: ChangeKind::Pending
+            }]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn updating_accepts_changed_snapshots() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let root = manifest_dir("update");
+        let path = root.join("tests/snapshots/greeting.snap");
+        take_changes();
+        check(&text(&root, "greeting", "hello")).unwrap();
+
+        std::env::set_var(UPDATE_ENV, "true");
+        let accepted = check(&text(&root, "greeting", "hello"));
+        let bytes = check_binary(&binary(&root, "greeting.bin", b"hi"));
+        std::env::remove_var(UPDATE_ENV);
+
+        assert_eq!(
+            accepted.unwrap(),
+            SnapshotOutcome::Accepted { path: path.clone() }
+        );
+        assert!(!root.join("tests/snapshots/greeting.snap.new").exists());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("\n---\nhello\n"));
+        let bin = root.join("tests/snapshots/greeting.bin");
+        assert_eq!(
+            bytes.unwrap(),
+            BinarySnapshotOutcome::Accepted { path: bin.clone() }
+        );
+        assert_eq!(fs::read(&bin).unwrap(), b"hi");
+        let kinds: Vec<_> = take_changes()
+            .into_iter()
+            .map(|change| change.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ChangeKind::Pending,
+                ChangeKind::Accepted,
+                ChangeKind::Accepted
+            ]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn binary_snapshots_compare_bytes() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let root = manifest_dir("binary");
+        let dir = root.join("tests/snapshots");
+        let (path, pending) = (dir.join("blob.bin"), dir.join("blob.new.bin"));
+
+        let missing = check_binary(&binary(&root, "blob", b"\x00\x01\x02")).unwrap();
+        assert_eq!(
+            missing,
+            BinarySnapshotOutcome::Missing {
+                path: path.clone(),
+                pending: pending.clone()
+            }
+        );
+        fs::rename(&pending, &path).unwrap();
+        let matched = check_binary(&binary(&root, "blob", b"\x00\x01\x02")).unwrap();
+        assert_eq!(matched, BinarySnapshotOutcome::Matched);
+        let changed = check_binary(&binary(&root, "blob", b"\x00\x09")).unwrap();
+        assert_eq!(
+            changed,
+            BinarySnapshotOutcome::Mismatched {
+                path,
+                pending: pending.clone(),
+                difference: "the value differs from the snapshot from byte 1 on; it has 2 \
+                             bytes, the snapshot 3"
+                    .to_string(),
+                diff_image: None
+            }
+        );
+        assert_eq!(fs::read(&pending).unwrap(), b"\x00\x09");
+        assert_eq!(
+            describe_bytes(b"ab", b"abc"),
+            "the shorter of the value and the snapshot starts the other; it has 3 bytes, the \
+             snapshot 2"
+        );
+        take_changes();
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn image_snapshots_compare_pixels() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let root = manifest_dir("image");
+        let dir = root.join("tests/snapshots");
+        let gray = |_| [128, 128, 128, 255];
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("chart.png"), reencode(&image(4, 1, gray))).unwrap();
+
+        // A re-encoded image with the same pixels matches.
+        let same = png::encode(&image(4, 1, gray));
+        let matched =
+            check_binary(&image_assertion(&root, &same, ImageOptions::default())).unwrap();
+        assert_eq!(matched, BinarySnapshotOutcome::Matched);
+
+        let spotted = |index| {
+            if index == 0 {
+                [0, 0, 0, 255]
+            } else {
+                [130, 128, 128, 255]
+            }
+        };
+        let spotted = png::encode(&image(4, 1, spotted));
+        let lenient = ImageOptions::default().max_diff_pixels(1);
+        assert_eq!(
+            check_binary(&image_assertion(&root, &spotted, lenient)).unwrap(),
+            BinarySnapshotOutcome::Matched
+        );
+        let outcome =
+            check_binary(&image_assertion(&root, &spotted, ImageOptions::default())).unwrap();
+        let diff_path = dir.join("chart.diff.png");
+        assert_eq!(
+            outcome,
+            BinarySnapshotOutcome::Mismatched {
+                path: dir.join("chart.png"),
+                pending: dir.join("chart.new.png"),
+                difference: "1 of 4 pixels (25.00%) differ by more than the threshold of 0.1"
+                    .to_string(),
+                diff_image: Some(diff_path.clone())
+            }
+        );
+        let diff = png::decode(&fs::read(&diff_path).unwrap()).unwrap();
+        assert_eq!(diff.pixel(0), [255, 0, 0, 255]);
+        assert_eq!(diff.pixel(1), [242, 242, 242, 255]);
+
+        let wide = png::encode(&image(2, 2, gray));
+        let BinarySnapshotOutcome::Mismatched { difference, .. } =
+            check_binary(&image_assertion(&root, &wide, ImageOptions::default())).unwrap()
+        else {
+            panic!("the sizes differ");
+        };
+        assert_eq!(difference, "the image is 2x2 pixels, the snapshot 4x1");
+        assert_eq!(
+            check_binary(&image_assertion(&root, b"GIF89a", ImageOptions::default())).unwrap(),
+            BinarySnapshotOutcome::NotAnImage {
+                reason: "it does not start with the PNG signature".to_string()
+            }
+        );
+        take_changes();
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn names_are_safe_file_names() {
+        assert_eq!(sanitize("render::pages::home"), "render__pages__home");
+        assert_eq!(sanitize("a b/c.d-e"), "a_b_c.d-e");
+        assert_eq!(normalize("a\r\nb\n\n"), "a\nb");
+        assert_eq!(parse_contents("---\nsource: x\n---\nbody\n"), "body");
+        assert_eq!(parse_contents("no header\n"), "no header");
+    }
+}
@@ -0,0 +1,926 @@
+//! Built-in `socket_server` fixture: a local TCP server, or one on a Unix
+//! socket, holding the conversations the test scripts with its clients.
+//!
+//! A test parameter named `socket_server` of type [`MockSocketServer`]
+//! receives a server listening on a free port of `127.0.0.1`, started for the
+//! test and stopped once the value is dropped at the end of it;
+//! [`MockSocketServer::start_unix`] starts one on a Unix socket instead. The
+//! test scripts each connection it expects, in the order the connections are
+//! made: the bytes or frames the client must send, and those the server
+//! sends back, and points the client under test at the server's address:
+//!
+//! ```ignore
+//! #[tust::test]
+//! fn logs_in(socket_server: MockSocketServer) {
+//!     socket_server
+//!         .connection()
+//!         .framing(Framing::Delimited(b'\n'))
+//!         .expect("LOGIN ada")
+//!         .send("OK")
+//!         .expect("QUIT");
+//!     let mut client = Client::connect(socket_server.addr().unwrap());
+//!     client.login("ada").unwrap();
+//! }
+//! ```
+//!
+//! Each step of a script waits for the client, up to a timeout of 5 seconds
+//! unless told otherwise, then compares what it sent against the expected
+//! frame. The first frame that differs, a timeout, a connection closed before
+//! its script ended, data sent after it ended, a scripted connection never
+//! made, and a connection no script was left for, each fail the test, which
+//! keeps running as after a soft assertion. The failure shows the transcript
+//! of the connection, with `->` before what the client sent and `<-` before
+//! what the server did.
+//!
+//! Failures are reported once the server is dropped, or earlier by
+//! [`MockSocketServer::verify`].
+
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::Location as CallerLocation;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+use crate::outcome::{Location, TestFailure};
+use crate::registry;
+use crate::soft;
+
+/// How long a step waits for the client unless its script says otherwise.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+inventory::submit! {
+    FixtureDef {
+        name: "socket_server",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<MockSocketServer>,
+        type_id: TypeId::of::<MockSocketServer>,
+        provider: Provider::PerTest(provide_socket_server),
+    }
+}
+
+fn provide_socket_server(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let server = MockSocketServer::start()
+        .unwrap_or_else(|err| panic!("cannot start the mock socket server: {}", err));
+    Ok(Box::new(server))
+}
+
+/// How the frames of a script are cut out of a connection's bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// No framing: an expected frame matches as many bytes as it holds, and
+    /// a sent frame is sent as it is.
+    #[default]
+    Raw,
+    /// Frames end with the byte, such as `b'\n'`, which the frames of the
+    /// script leave out.
+    Delimited(u8),
+    /// Frames start with their length, in as many bytes as given, from 1 to
+    /// 8, in network byte order, which the frames of the script leave out.
+    LengthPrefixed(usize),
+}
+
+/// A local server that holds scripted conversations with its clients.
+pub struct MockSocketServer {
+    endpoint: Endpoint,
+    state: Arc<Mutex<State>>,
+    stopping: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone)]
+enum Endpoint {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+#[derive(Default)]
+struct State {
+    scripts: Vec<Script>,
+    /// How many connections were accepted.
+    connections: usize,
+    /// What was sent over each connection, in the order they were made.
+    transcripts: Vec<Vec<Event>>,
+    /// Clones of the open streams, to shut them down when the server stops.
+    streams: Vec<Stream>,
+    handlers: Vec<JoinHandle<()>>,
+    /// Failures not yet reported, with where to report them; `None` is the
+    /// test itself.
+    failures: Vec<(Option<&'static CallerLocation<'static>>, String)>,
+}
+
+struct Script {
+    framing: Framing,
+    timeout: Duration,
+    steps: Vec<Step>,
+    /// Whether a connection took the script.
+    connected: bool,
+    /// Whether a missing connection was reported.
+    reported: bool,
+    location: &'static CallerLocation<'static>,
+}
+
+#[derive(Clone)]
+enum Step {
+    Expect(Vec<u8>, &'static CallerLocation<'static>),
+    Send(Vec<u8>),
+    Close,
+}
+
+/// Bytes sent over a connection, by the client or by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Received(Vec<u8>),
+    Sent(Vec<u8>),
+}
+
+impl MockSocketServer {
+    /// Starts a server on a free port of `127.0.0.1`.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let endpoint = Endpoint::Tcp(listener.local_addr()?);
+        Self::spawn(Listener::Tcp(listener), endpoint)
+    }
+
+    /// Starts a server on a Unix socket in the system's temporary directory,
+    /// which is removed when the server is dropped.
+    #[cfg(unix)]
+    pub fn start_unix() -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "tust-socket-{}-{}.sock",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        Self::spawn(Listener::Unix(listener), Endpoint::Unix(path))
+    }
+
+    fn spawn(listener: Listener, endpoint: Endpoint) -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let state = Arc::clone(&state);
+            let stopping = Arc::clone(&stopping);
+            thread::Builder::new()
+                .name("tust-socket".to_string())
+                .spawn(move || accept(listener, state, stopping))?
+        };
+        Ok(MockSocketServer {
+            endpoint,
+            state,
+            stopping,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// The address of a TCP server, or `None` for one on a Unix socket.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        match &self.endpoint {
+            Endpoint::Tcp(addr) => Some(*addr),
+            #[cfg(unix)]
+            Endpoint::Unix(_) => None,
+        }
+    }
+
+    /// The path of a server on a Unix socket, or `None` for a TCP server.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.endpoint {
+            Endpoint::Tcp(_) => None,
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Some(path),
+        }
+    }
+
+    /// Scripts the next connection, after those scripted already. The script
+    /// uses [`Framing::Raw`] and has no steps until told otherwise; a
+    /// connection whose script has no steps only needs to be made.
+    #[track_caller]
+    pub fn connection(&self) -> ScriptBuilder<'_> {
+        let mut state = self.state();
+        state.scripts.push(Script {
+            framing: Framing::Raw,
+            timeout: DEFAULT_TIMEOUT,
+            steps: Vec::new(),
+            connected: false,
+            reported: false,
+            location: CallerLocation::caller(),
+        });
+        let index = state.scripts.len() - 1;
+        ScriptBuilder {
+            server: self,
+            index,
+        }
+    }
+
+    /// How many connections the server accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state().connections
+    }
+
+    /// The transcript of every connection so far, as shown in failures.
+    pub fn transcript(&self) -> String {
+        let state = self.state();
+        (0..state.transcripts.len())
+            .map(|connection| transcript(&state, connection))
+            .collect()
+    }
+
+    /// Fails the current test for every failure of a conversation so far and
+    /// every scripted connection not yet made. Conversations still going on
+    /// are checked once they end, or once the server is dropped.
+    #[track_caller]
+    pub fn verify(&self) {
+        let failures = {
+            let mut state = self.state();
+            let mut failures = std::mem::take(&mut state.failures);
+            let made = state.connections;
+            for (index, script) in state.scripts.iter_mut().enumerate() {
+                if script.connected || script.reported {
+                    continue;
+                }
+                script.reported = true;
+                let message = format!(
+                    "expected connection {} to the mock socket server, but it received {}",
+                    index + 1,
+                    connections(made)
+                );
+                failures.push((Some(script.location), message));
+            }
+            failures
+        };
+        // Nothing in the test caused a connection no script was left for,
+        // so point at the test itself.
+        let test = registry::current().map(|test| Location {
+            file: test.file.to_string(),
+            line: test.line,
+            column: 1,
+        });
+        for (location, message) in failures {
+            let location = match location {
+                Some(location) => Some(Location {
+                    file: location.file().to_string(),
+                    line: location.line(),
+                    column: location.column(),
+                }),
+                None => test.clone(),
+            };
+            soft::record(TestFailure {
+                location,
+                ..TestFailure::new(message)
+            });
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for MockSocketServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.endpoint {
+            Endpoint::Tcp(addr) => f.debug_tuple("MockSocketServer").field(addr).finish(),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => f.debug_tuple("MockSocketServer").field(path).finish(),
+        }
+    }
+}
+
+impl Drop for MockSocketServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the acceptor, which is blocked waiting for a connection.
+        match &self.endpoint {
+            Endpoint::Tcp(addr) => drop(TcpStream::connect(addr)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => drop(UnixStream::connect(path)),
+        }
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let handlers = {
+            let mut state = self.state();
+            for stream in &state.streams {
+                let _ = stream.shutdown();
+            }
+            std::mem::take(&mut state.handlers)
+        };
+        for handler in handlers {
+            let _ = handler.join();
+        }
+        #[cfg(unix)]
+        if let Endpoint::Unix(path) = &self.endpoint {
+            let _ = std::fs::remove_file(path);
+        }
+        if !thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+fn connections(count: usize) -> String {
+    match count {
+        0 => "none".to_string(),
+        1 => "1 connection".to_string(),
+        count => format!("{} connections", count),
+    }
+}
+
+/// Configures a script added by [`MockSocketServer::connection`].
+pub struct ScriptBuilder<'a> {
+    server: &'a MockSocketServer,
+    index: usize,
+}
+
+impl ScriptBuilder<'_> {
+    /// Cuts the frames of the connection out of its bytes with `framing`.
+    pub fn framing(self, framing: Framing) -> Self {
+        if let Framing::LengthPrefixed(bytes) = framing {
+            assert!(
+                (1..=8).contains(&bytes),
+                "a length prefix takes 1 to 8 bytes, not {}",
+                bytes
+            );
+        }
+        self.update(|script| script.framing = framing)
+    }
+
+    /// Waits up to `timeout` for each frame the client must send.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.update(|script| script.timeout = timeout)
+    }
+
+    /// Expects the client to send `frame` next.
+    #[track_caller]
+    pub fn expect(self, frame: impl Into<Vec<u8>>) -> Self {
+        let step = Step::Expect(frame.into(), CallerLocation::caller());
+        self.update(|script| script.steps.push(step))
+    }
+
+    /// Sends `frame` to the client.
+    pub fn send(self, frame: impl Into<Vec<u8>>) -> Self {
+        let step = Step::Send(frame.into());
+        self.update(|script| script.steps.push(step))
+    }
+
+    /// Closes the connection, ending the script.
+    pub fn close(self) -> Self {
+        self.update(|script| script.steps.push(Step::Close))
+    }
+
+    fn update(self, f: impl FnOnce(&mut Script)) -> Self {
+        if let Some(script) = self.server.state().scripts.get_mut(self.index) {
+            f(script);
+        }
+        self
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => Ok(Stream::Tcp(listener.accept()?.0)),
+            #[cfg(unix)]
+            Listener::Unix(listener) => Ok(Stream::Unix(listener.accept()?.0)),
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?)),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+fn accept(listener: Listener, state: Arc<Mutex<State>>, stopping: Arc<AtomicBool>) {
+    loop {
+        let stream = listener.accept();
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else { continue };
+        let mut locked = state.lock().unwrap_or_else(PoisonError::into_inner);
+        let connection = locked.connections;
+        locked.connections += 1;
+        locked.transcripts.push(Vec::new());
+        if let Some(script) = locked.scripts.get_mut(connection) {
+            script.connected = true;
+        }
+        if let Ok(clone) = stream.try_clone() {
+            locked.streams.push(clone);
+        }
+        let handler = {
+            let state = Arc::clone(&state);
+            let stopping = Arc::clone(&stopping);
+            thread::Builder::new()
+                .name("tust-socket-connection".to_string())
+                .spawn(move || converse(stream, connection, &state, &stopping))
+        };
+        if let Ok(handler) = handler {
+            locked.handlers.push(handler);
+        }
+    }
+}
+
+/// Holds connection `connection` to its script, recording what the client
+/// and the server send and how the conversation fails, if it does.
+fn converse(stream: Stream, connection: usize, state: &Mutex<State>, stopping: &AtomicBool) {
+    let lock = || state.lock().unwrap_or_else(PoisonError::into_inner);
+    let record = |event: Event| lock().transcripts[connection].push(event);
+    let fail = |location: Option<&'static CallerLocation<'static>>, message: String| {
+        let mut state = lock();
+        let message = format!("{}\n{}", message, transcript(&state, connection).trim_end());
+        state.failures.push((location, message));
+    };
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let number = connection + 1;
+
+    let Some((framing, timeout, script_location)) = lock()
+        .scripts
+        .get(connection)
+        .map(|script| (script.framing, script.timeout, script.location))
+    else {
+        let received = read_to_end(&mut reader);
+        if !received.is_empty() {
+            record(Event::Received(received));
+        }
+        let scripted = lock().scripts.len();
+        fail(
+            None,
+            format!(
+                "the mock socket server received connection {}, but {} scripted",
+                number,
+                match scripted {
+                    0 => "none was".to_string(),
+                    1 => "only 1 was".to_string(),
+                    scripted => format!("only {} were", scripted),
+                }
+            ),
+        );
+        return;
+    };
+
+    let mut step = 0;
+    loop {
+        let next = lock().scripts[connection].steps.get(step).cloned();
+        step += 1;
+        match next {
+            Some(Step::Expect(expected, location)) => {
+                let _ = reader.get_ref().set_read_timeout(Some(timeout));
+                let mut received = Vec::new();
+                let result = read_frame(&mut reader, framing, expected.len(), &mut received);
+                match &result {
+                    Ok(frame) => record(Event::Received(frame.clone())),
+                    Err(_) if !received.is_empty() => record(Event::Received(received)),
+                    Err(_) => {}
+                }
+                let message = match result {
+                    Ok(frame) if frame == expected => continue,
+                    Ok(frame) => format!(
+                        "expected `{}` on connection {} to the mock socket server, but received `{}`",
+                        expected.escape_ascii(),
+                        number,
+                        frame.escape_ascii()
+                    ),
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        format!(
+                            "timed out after {:?} waiting for `{}` on connection {} to the mock socket server",
+                            timeout,
+                            expected.escape_ascii(),
+                            number
+                        )
+                    }
+                    Err(_) if stopping.load(Ordering::SeqCst) => format!(
+                        "the test ended while the mock socket server waited for `{}` on connection {}",
+                        expected.escape_ascii(),
+                        number
+                    ),
+                    Err(_) => format!(
+                        "connection {} to the mock socket server was closed while it waited for `{}`",
+                        number,
+                        expected.escape_ascii()
+                    ),
+                };
+                fail(Some(location), message);
+                return;
+            }
+            Some(Step::Send(frame)) => {
+                let sent = write_frame(&mut writer, framing, &frame);
+                record(Event::Sent(frame));
+                if sent.is_err() {
+                    fail(
+                        Some(script_location),
+                        format!(
+                            "connection {} to the mock socket server was closed before it sent every frame",
+                            number
+                        ),
+                    );
+                    return;
+                }
+            }
+            Some(Step::Close) => {
+                let _ = writer.shutdown();
+                return;
+            }
+            None => break,
+        }
+    }
+
+    // The script ended; the client must not send anything more.
+    let _ = reader.get_ref().set_read_timeout(None);
+    let received = read_to_end(&mut reader);
+    if !received.is_empty() {
+        record(Event::Received(received.clone()));
+        fail(
+            Some(script_location),
+            format!(
+                "connection {} to the mock socket server received `{}` after its script ended",
+                number,
+                received.escape_ascii()
+            ),
+        );
+    }
+}
+
+/// Reads one frame, a frame of `len` bytes if `framing` is raw. The bytes
+/// read are appended to `received`, even if reading fails.
+fn read_frame(
+    reader: &mut impl BufRead,
+    framing: Framing,
+    len: usize,
+    received: &mut Vec<u8>,
+) -> io::Result<Vec<u8>> {
+    match framing {
+        Framing::Raw => {
+            read_bytes(reader, len, received)?;
+            Ok(received.clone())
+        }
+        Framing::Delimited(delimiter) => {
+            reader.read_until(delimiter, received)?;
+            match received.split_last() {
+                Some((&last, frame)) if last == delimiter => Ok(frame.to_vec()),
+                _ => Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+        Framing::LengthPrefixed(bytes) => {
+            read_bytes(reader, bytes, received)?;
+            let len = received
+                .iter()
+                .fold(0u64, |len, &byte| len << 8 | u64::from(byte));
+            let len = usize::try_from(len)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "frame too long"))?;
+            read_bytes(reader, len, received)?;
+            Ok(received[bytes..].to_vec())
+        }
+    }
+}
+
+/// Reads `len` bytes, appending them to `received` as they arrive.
+fn read_bytes(reader: &mut impl Read, len: usize, received: &mut Vec<u8>) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+    let mut left = len;
+    while left > 0 {
+        let read = reader.read(&mut buffer[..left.min(4096)])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        received.extend_from_slice(&buffer[..read]);
+        left -= read;
+    }
+    Ok(())
+}
+
+/// Reads until the client closes the connection, or it is shut down.
+fn read_to_end(reader: &mut impl Read) -> Vec<u8> {
+    let mut received = Vec::new();
+    let mut buffer = [0; 4096];
+    while let Ok(read @ 1..) = reader.read(&mut buffer) {
+        received.extend_from_slice(&buffer[..read]);
+    }
+    received
+}
+
+fn write_frame(writer: &mut impl Write, framing: Framing, frame: &[u8]) -> io::Result<()> {
+    match framing {
+        Framing::Raw => writer.write_all(frame)?,
+        Framing::Delimited(delimiter) => {
+            writer.write_all(frame)?;
+            writer.write_all(&[delimiter])?;
+        }
+        Framing::LengthPrefixed(bytes) => {
+            let len = (frame.len() as u64).to_be_bytes();
+            writer.write_all(&len[8 - bytes..])?;
+            writer.write_all(frame)?;
+        }
+    }
+    writer.flush()
+}
+
+/// The transcript of `connection`, as shown in failures.
+fn transcript(state: &State, connection: usize) -> String {
+    let mut text = format!("transcript of connection {}:\n", connection + 1);
+    let events = &state.transcripts[connection];
+    if events.is_empty() {
+        text.push_str("  (nothing sent)\n");
+    }
+    for event in events {
+        let (arrow, bytes) = match event {
+            Event::Received(bytes) => ("->", bytes),
+            Event::Sent(bytes) => ("<-", bytes),
+        };
+        text.push_str(&format!("  {} `{}`\n", arrow, bytes.escape_ascii()));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    /// Waits for the conversations to have failed `count` times, so that
+    /// dropping the server does not cut one short.
+    fn wait_for_failures(server: &MockSocketServer, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.state().failures.len() < count {
+            assert!(Instant::now() < deadline, "the conversations did not fail");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn connect(server: &MockSocketServer) -> TcpStream {
+        TcpStream::connect(server.addr().unwrap()).unwrap()
+    }
+
+    fn read_all(stream: &mut impl Read) -> Vec<u8> {
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        received
+    }
+
+    fn messages(failures: &[TestFailure]) -> Vec<&str> {
+        failures
+            .iter()
+            .map(|failure| failure.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn scripted_conversations_pass() {
+        let server = MockSocketServer::start().unwrap();
+        assert_eq!(server.path(), None);
+        server
+            .connection()
+            .framing(Framing::Delimited(b'\n'))
+            .expect("LOGIN ada")
+            .send("OK")
+            .expect("QUIT")
+            .close();
+        let mut client = connect(&server);
+        client.write_all(b"LOGIN ada\nQUIT\n").unwrap();
+        assert_eq!(read_all(&mut client), b"OK\n");
+        assert_eq!(server.connections(), 1);
+        assert_eq!(
+            server.transcript(),
+            "transcript of connection 1:\n  -> `LOGIN ada`\n  <- `OK`\n  -> `QUIT`\n"
+        );
+        let ((), failures) = soft::collect(|| drop(server));
+        assert!(failures.is_empty(), "{:?}", messages(&failures));
+    }
+
+    #[test]
+    fn frames_are_cut_as_the_script_says() {
+        let server = MockSocketServer::start().unwrap();
+        server
+            .connection()
+            .framing(Framing::LengthPrefixed(2))
+            .expect("ping")
+            .send("pong")
+            .close();
+        server.connection().expect("HELLO").send([0, 1]).close();
+        let mut client = connect(&server);
+        client.write_all(b"\x00\x04ping").unwrap();
+        assert_eq!(read_all(&mut client), b"\x00\x04pong");
+        // Raw frames may arrive in pieces.
+        let mut client = connect(&server);
+        client.write_all(b"HEL").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        client.write_all(b"LO").unwrap();
+        assert_eq!(read_all(&mut client), [0, 1]);
+        let ((), failures) = soft::collect(|| drop(server));
+        assert!(failures.is_empty(), "{:?}", messages(&failures));
+    }
+
+    #[test]
+    fn unexpected_frames_fail_with_the_transcript() {
+        let server = MockSocketServer::start().unwrap();
+        let expected_at = line!() + 6;
+        server
+            .connection()
+            .framing(Framing::Delimited(b'\n'))
+            .expect("LOGIN ada")
+            .send("OK")
+            .expect("QUIT");
+        let mut client = connect(&server);
+        client.write_all(b"LOGIN ada\nEXIT\n").unwrap();
+        wait_for_failures(&server, 1);
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(
+            messages(&failures),
+            [
+                "expected `QUIT` on connection 1 to the mock socket server, but received `EXIT`\n\
+              transcript of connection 1:\n  -> `LOGIN ada`\n  <- `OK`\n  -> `EXIT`"
+            ]
+        );
+        let location = failures[0].location.as_ref().unwrap();
+        assert_eq!(
+            (location.file.as_str(), location.line),
+            (file!(), expected_at)
+        );
+    }
+
+    #[test]
+    fn silent_clients_time_out() {
+        let server = MockSocketServer::start().unwrap();
+        server
+            .connection()
+            .timeout(Duration::from_millis(20))
+            .expect("HELLO");
+        let _client = connect(&server);
+        wait_for_failures(&server, 1);
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(
+            messages(&failures),
+            [
+                "timed out after 20ms waiting for `HELLO` on connection 1 to the mock socket \
+              server\ntranscript of connection 1:\n  (nothing sent)"
+            ]
+        );
+    }
+
+    #[test]
+    fn clients_must_keep_to_the_script() {
+        let server = MockSocketServer::start().unwrap();
+        server.connection().expect("HELLO");
+        server.connection().send("BYE");
+        server.connection();
+        let mut first = connect(&server);
+        first.write_all(b"HEL").unwrap();
+        drop(first);
+        wait_for_failures(&server, 1);
+        let mut second = connect(&server);
+        second.write_all(b"MORE").unwrap();
+        drop(second);
+        wait_for_failures(&server, 2);
+        let ((), failures) = soft::collect(|| server.verify());
+        assert_eq!(
+            messages(&failures),
+            [
+                "connection 1 to the mock socket server was closed while it waited for `HELLO`\n\
+                 transcript of connection 1:\n  -> `HEL`",
+                "connection 2 to the mock socket server received `MORE` after its script \
+                 ended\ntranscript of connection 2:\n  <- `BYE`\n  -> `MORE`",
+                "expected connection 3 to the mock socket server, but it received 2 connections",
+            ]
+        );
+        let third = connect(&server);
+        drop(third);
+        drop(connect(&server));
+        wait_for_failures(&server, 1);
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(
+            messages(&failures),
+            [
+                "the mock socket server received connection 4, but only 3 were scripted\n\
+              transcript of connection 4:\n  (nothing sent)"
+            ]
+        );
+    }
+
+    #[test]
+    fn dropping_the_server_ends_waiting_conversations() {
+        let server = MockSocketServer::start().unwrap();
+        server.connection().expect("HELLO");
+        let _client = connect(&server);
+        while server.connections() == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let ((), failures) = soft::collect(|| drop(server));
+        assert_eq!(
+            messages(&failures),
+            [
+                "the test ended while the mock socket server waited for `HELLO` on connection 1\n\
+              transcript of connection 1:\n  (nothing sent)"
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a length prefix takes 1 to 8 bytes, not 9")]
+    fn length_prefixes_fit_in_a_u64() {
+        let server = MockSocketServer::start().unwrap();
+        server.connection().framing(Framing::LengthPrefixed(9));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_sockets_hold_conversations_and_are_removed() {
+        let server = MockSocketServer::start_unix().unwrap();
+        server.connection().expect("ping").send("pong").close();
+        let path = server.path().unwrap().to_path_buf();
+        assert_eq!(server.addr(), None);
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"ping").unwrap();
+        assert_eq!(read_all(&mut client), b"pong");
+        let ((), failures) = soft::collect(|| drop(server));
+        assert!(failures.is_empty(), "{:?}", messages(&failures));
+        assert!(!path.exists());
+    }
+}
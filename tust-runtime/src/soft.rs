@@ -0,0 +1,71 @@
+//! Soft failures: assertion failures recorded without stopping the test.
+//!
+//! While the runtime executes a test, failures passed to [`record`] on the
+//! test's thread are collected and reported together once the test finishes.
+//! Outside of a test, or on threads spawned by it, [`record`] panics instead,
+//! so a failure is never silently dropped.
+
+use std::cell::RefCell;
+
+use crate::context;
+use crate::outcome::{Location, TestFailure};
+use crate::registry;
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Vec<TestFailure>>> = const { RefCell::new(None) };
+}
+
+/// Records a failure for the test running on the current thread, letting the
+/// test continue. Without such a test, panics with the failure's message.
+///
+/// If `failure` has no location, the caller's location is used.
+#[track_caller]
+pub fn record(mut failure: TestFailure) {
+    if failure.location.is_none() {
+        let caller = std::panic::Location::caller();
+        failure.location = Some(Location {
+            file: caller.file().to_string(),
+            line: caller.line(),
+            column: caller.column(),
+        });
+    }
+
+    let unrecorded = COLLECTOR.with(|collector| match collector.borrow_mut().as_mut() {
+        Some(failures) => {
+            if let Some(test) = registry::current() {
+                context::note_step(test, &mut failure, true);
+            }
+            failures.push(failure);
+            None
+        }
+        None => Some(failure),
+    });
+    if let Some(failure) = unrecorded {
+        panic!("{}", failure.description());
+    }
+}
+
+/// Whether soft failures are currently being collected on this thread.
+pub fn is_collecting() -> bool {
+    COLLECTOR.with(|collector| collector.borrow().is_some())
+}
+
+/// Whether the test running on the current thread has recorded a soft
+/// failure so far.
+pub(crate) fn has_failures() -> bool {
+    COLLECTOR.with(|collector| {
+        collector
+            .borrow()
+            .as_ref()
+            .is_some_and(|failures| !failures.is_empty())
+    })
+}
+
+/// Runs `f` while collecting the soft failures it records. `f` must not
+/// unwind.
+pub(crate) fn collect<R>(f: impl FnOnce() -> R) -> (R, Vec<TestFailure>) {
+    let outer = COLLECTOR.with(|collector| collector.replace(Some(Vec::new())));
+    let result = f();
+    let failures = COLLECTOR.with(|collector| collector.replace(outer));
+    (result, failures.unwrap_or_default())
+}
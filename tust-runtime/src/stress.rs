@@ -0,0 +1,171 @@
+//! Stress mode: running one test body many times, possibly from several
+//! threads at once, to shake out race conditions.
+//!
+//! `#[tust::test(repeat = 1000, threads = 8)]` runs the body 1000 times,
+//! handing the iterations out to 8 threads that run them concurrently. Each
+//! iteration receives fresh fixtures. The first failing iteration stops the
+//! threads from starting new ones, and the test fails with that iteration's
+//! failures, its index, the thread it ran on, and the run seed.
+//!
+//! Randomness from [`rng`](crate::rng::rng) is derived per iteration rather
+//! than per test, so an iteration sees the same values in every run with the
+//! same seed, whichever thread happens to run it.
+
+use std::cell::Cell;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::fixture::{FixtureError, Fixtures};
+use crate::outcome::TestFailure;
+use crate::registry::{self, TestDef};
+use crate::{panic, rng, soft};
+
+thread_local! {
+    /// The iteration running on this thread, and how many generators it has
+    /// been handed so far.
+    static ITERATION: Cell<Option<(u32, u64)>> = const { Cell::new(None) };
+}
+
+/// How a `#[tust::test(repeat = N, threads = N)]` test runs its body.
+#[derive(Debug, Clone, Copy)]
+pub struct StressConfig {
+    /// Number of iterations in all.
+    pub repeat: u32,
+    /// Number of threads running iterations at the same time.
+    pub threads: u32,
+}
+
+/// Index of the iteration running on the current thread, counting from 0, if
+/// it runs one.
+pub fn iteration() -> Option<u32> {
+    ITERATION.with(|current| current.get().map(|(iteration, _)| iteration))
+}
+
+/// The iteration running on the current thread and the number of its next
+/// generator, counting the generator as handed out.
+pub(crate) fn next_stream() -> Option<(u32, u64)> {
+    ITERATION.with(|current| {
+        let next = current
+            .get()
+            .map(|(iteration, streams)| (iteration, streams + 1));
+        current.set(next);
+        next
+    })
+}
+
+/// An iteration that failed.
+struct Failed {
+    iteration: u32,
+    thread: u32,
+    failures: Vec<TestFailure>,
+}
+
+/// Runs `once` as `config` asks, with fixtures resolved like those of
+/// `fixtures`, and records the failures of the first failing iteration.
+///
+/// # Errors
+///
+/// Returns the error of the first iteration whose fixtures could not be
+/// provided.
+pub fn run(
+    config: &StressConfig,
+    fixtures: &mut Fixtures,
+    once: fn(&mut Fixtures) -> Result<(), FixtureError>,
+) -> Result<(), FixtureError> {
+    let repeat = config.repeat.max(1);
+    let threads = config.threads.clamp(1, repeat);
+    let module = fixtures.module();
+    let test = registry::current();
+    let next = AtomicU32::new(0);
+    let stop = AtomicBool::new(false);
+    let failed = Mutex::new(Vec::<Failed>::new());
+    let error = Mutex::new(None::<FixtureError>);
+
+    let worker = |thread: u32| {
+        while !stop.load(Ordering::Relaxed) {
+            let iteration = next.fetch_add(1, Ordering::Relaxed);
+            if iteration >= repeat {
+                break;
+            }
+            let (result, mut failures) = soft::collect(|| {
+                let outer = ITERATION.with(|current| current.replace(Some((iteration, 0))));
+                let result = panic::catch(|| once(&mut Fixtures::new(module)));
+                ITERATION.with(|current| current.set(outer));
+                result
+            });
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    stop.store(true, Ordering::Relaxed);
+                    error
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get_or_insert(err);
+                    break;
+                }
+                Err(failure) => failures.push(failure),
+            }
+            if !failures.is_empty() {
+                stop.store(true, Ordering::Relaxed);
+                failed
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(Failed {
+                        iteration,
+                        thread,
+                        failures,
+                    });
+            }
+        }
+    };
+    let in_test = |test: Option<&'static TestDef>, thread: u32| match test {
+        Some(test) => registry::with_current(test, || worker(thread)),
+        None => worker(thread),
+    };
+
+    thread::scope(|scope| {
+        for thread in 1..threads {
+            let spawned = thread::Builder::new()
+                .name(format!("stress-{}", thread))
+                .spawn_scoped(scope, move || in_test(test, thread));
+            match spawned {
+                Ok(_) => {}
+                // Without threads, as on WebAssembly, this thread runs every
+                // iteration.
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => break,
+                Err(err) => panic!("cannot spawn a stress thread: {}", err),
+            }
+        }
+        in_test(test, 0);
+    });
+
+    if let Some(err) = error.into_inner().unwrap_or_else(PoisonError::into_inner) {
+        return Err(err);
+    }
+    let mut failed = failed.into_inner().unwrap_or_else(PoisonError::into_inner);
+    failed.sort_by_key(|failed| failed.iteration);
+    let count = failed.len();
+    if let Some(first) = failed.into_iter().next() {
+        let mut header = format!("iteration {} of {} failed", first.iteration, repeat);
+        if threads > 1 {
+            header.push_str(&format!(" on thread {} of {}", first.thread, threads));
+        }
+        header.push_str(&format!(" (run seed {}", rng::run_seed()));
+        if count > 1 {
+            header.push_str(&format!(
+                "; {} iterations failed before the threads stopped",
+                count
+            ));
+        }
+        header.push(')');
+        for (index, mut failure) in first.failures.into_iter().enumerate() {
+            if index == 0 {
+                failure.message = format!("{}\n{}", header, failure.message);
+            }
+            soft::record(failure);
+        }
+    }
+    Ok(())
+}
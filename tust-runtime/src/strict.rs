@@ -0,0 +1,526 @@
+//! Strict mode, enabled with `--strict` or `strict = true` in `tust.toml`:
+//! flagging tests that pass but leave a mess behind.
+//!
+//! Each test that passed is checked for
+//!
+//! - `threads`: threads it started and left running, found by counting the
+//!   threads of the process (on Linux only);
+//! - `tokio-tasks`: tasks an `async fn` test spawned on its Tokio runtime
+//!   and left running when it returned, which dropping the runtime cancels,
+//!   each with the backtrace of where it was spawned when Tokio's unstable
+//!   task hooks are built in, with `RUSTFLAGS="--cfg tokio_unstable"`;
+//! - `temp-files`: files and directories it left in the system's temporary
+//!   directory, [`std::env::temp_dir`];
+//! - `slow`: taking longer than `slow-threshold`, 5 seconds unless set.
+//!
+//! By default a check warns about the tests it flags: the warnings are
+//! listed after the run, and do not fail it. The `[strict-checks]` table of
+//! `tust.toml` sets each check to `"warn"`, to `"fail"`, failing the tests
+//! it flags, or to `"off"`:
+//!
+//! ```toml
+//! strict = true
+//!
+//! [strict-checks]
+//! threads = "fail"
+//! temp-files = "off"
+//! slow-threshold = "2s"
+//! ```
+//!
+//! Threads belong to the whole process and the temporary directory to the
+//! whole machine, so tests running at the same time would be blamed for
+//! each other's. The runner therefore checks for them only while tests run
+//! one at a time, with `--jobs 1`, and for threads also under
+//! `--process-per-test`, where each test has a process of its own.
+//! Anything else on the machine writing to the temporary directory during
+//! a test is still blamed on it.
+//!
+//! The standard library has no hook for spawning a thread, so a thread left
+//! running is reported by its name, without where it was spawned; naming
+//! the threads a test starts, with [`std::thread::Builder::name`], tells
+//! them apart.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use web_time::Instant;
+
+use crate::alloc;
+use crate::bench;
+use crate::outcome::{TestFailure, TestOutcome, TestStatus};
+use crate::registry::TestDef;
+
+/// Levels of the checks made while a test runs, handed to the processes of
+/// `--process-per-test`.
+const CHECKS_ENV: &str = "__TUST_STRICT";
+
+/// How long a test's threads get to exit after it returns.
+const THREAD_GRACE: Duration = Duration::from_millis(100);
+
+/// The checks of the run, set by [`enable`].
+static CHECKS: OnceLock<Checks> = OnceLock::new();
+
+static WARNINGS: Mutex<Vec<StrictWarning>> = Mutex::new(Vec::new());
+
+/// Findings of Tokio runtimes, until their test finishes: the number of
+/// tasks left, and where those the runtime recorded were spawned.
+static TASKS_LEFT: Mutex<Vec<(&'static TestDef, usize, Vec<String>)>> = Mutex::new(Vec::new());
+
+/// What a check does with the tests it flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    Off,
+    /// Lists the test after the run.
+    #[default]
+    Warn,
+    /// Fails the test.
+    Fail,
+}
+
+impl Level {
+    /// Parses `"off"`, `"warn"`, or `"fail"`.
+    pub fn parse(level: &str) -> Option<Level> {
+        match level {
+            "off" => Some(Level::Off),
+            "warn" => Some(Level::Warn),
+            "fail" => Some(Level::Fail),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Warn => "warn",
+            Level::Fail => "fail",
+        }
+    }
+}
+
+/// The checks strict mode makes, as the `[strict-checks]` table of
+/// `tust.toml` sets them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checks {
+    pub threads: Level,
+    pub tokio_tasks: Level,
+    pub temp_files: Level,
+    pub slow: Level,
+    /// How long a test may take before `slow` flags it.
+    pub slow_threshold: Duration,
+}
+
+impl Default for Checks {
+    fn default() -> Self {
+        Checks {
+            threads: Level::Warn,
+            tokio_tasks: Level::Warn,
+            temp_files: Level::Warn,
+            slow: Level::Warn,
+            slow_threshold: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A test that passed, flagged by a check set to warn.
+#[derive(Debug, Clone)]
+pub struct StrictWarning {
+    pub test: &'static TestDef,
+    /// The check, named as in `tust.toml`, such as `threads`.
+    pub check: &'static str,
+    /// What the test did, such as `left 2 threads running`.
+    pub message: String,
+    /// Backtraces of where the tasks it left running were spawned, where
+    /// they were recorded.
+    pub spawned_at: Vec<String>,
+}
+
+/// Turns strict mode on for a run of up to `jobs` tests at once, making
+/// `checks` where tests can be told apart.
+pub(crate) fn enable(checks: &Checks, jobs: usize, process_per_test: bool) {
+    let mut in_test = checks.clone();
+    let mut unchecked = Vec::new();
+    if jobs > 1 && !process_per_test && in_test.threads != Level::Off {
+        in_test.threads = Level::Off;
+        unchecked.push("threads");
+    }
+    if jobs > 1 && in_test.temp_files != Level::Off {
+        in_test.temp_files = Level::Off;
+        unchecked.push("temporary files");
+    }
+    if !unchecked.is_empty() {
+        eprintln!(
+            "note: strict mode checks for leftover {} only with --jobs 1",
+            unchecked.join(" and ")
+        );
+    }
+    // Read by each test, in this process and in those of
+    // `--process-per-test`.
+    std::env::set_var(
+        CHECKS_ENV,
+        format!(
+            "threads={},tokio-tasks={},temp-files={}",
+            in_test.threads.name(),
+            in_test.tokio_tasks.name(),
+            in_test.temp_files.name()
+        ),
+    );
+    let _ = CHECKS.set(in_test);
+}
+
+/// The levels of the checks made while a test runs, if strict mode is on.
+fn in_test_levels() -> Option<(Level, Level, Level)> {
+    let levels = std::env::var(CHECKS_ENV).ok()?;
+    let level = |name: &str| {
+        levels
+            .split(',')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            .and_then(Level::parse)
+            .unwrap_or(Level::Off)
+    };
+    Some((level("threads"), level("tokio-tasks"), level("temp-files")))
+}
+
+/// What a test found when it started, to compare with what it leaves.
+pub(crate) struct Watch {
+    threads: Level,
+    tokio_tasks: Level,
+    temp_files: Level,
+    thread_ids: Option<HashSet<OsString>>,
+    temp_entries: Option<HashSet<OsString>>,
+}
+
+impl Watch {
+    /// Starts watching the test about to run, if strict mode is on.
+    pub(crate) fn start() -> Option<Watch> {
+        let (threads, tokio_tasks, temp_files) = in_test_levels()?;
+        Some(Watch {
+            threads,
+            tokio_tasks,
+            temp_files,
+            thread_ids: (threads != Level::Off).then(thread_ids).flatten(),
+            temp_entries: (temp_files != Level::Off).then(temp_entries).flatten(),
+        })
+    }
+
+    /// Checks `test`, if it `passed`, recording the warnings and returning
+    /// the failures of what it left behind.
+    pub(crate) fn finish(self, test: &'static TestDef, passed: bool) -> Vec<TestFailure> {
+        let tasks_left = take_tasks_left(test);
+        if !passed {
+            return Vec::new();
+        }
+        let mut findings = Vec::new();
+        if let Some(before) = &self.thread_ids {
+            if let Some(names) = threads_left(before) {
+                let noun = if names.len() == 1 {
+                    "thread"
+                } else {
+                    "threads"
+                };
+                findings.push(Finding {
+                    level: self.threads,
+                    check: "threads",
+                    message: format!(
+                        "left {} {} running: {}",
+                        names.len(),
+                        noun,
+                        names.join(", ")
+                    ),
+                    spawned_at: Vec::new(),
+                });
+            }
+        }
+        if let Some((tasks, spawned_at)) = tasks_left {
+            let noun = if tasks == 1 { "task" } else { "tasks" };
+            findings.push(Finding {
+                level: self.tokio_tasks,
+                check: "tokio-tasks",
+                message: format!("left {} Tokio {} running when it returned", tasks, noun),
+                spawned_at,
+            });
+        }
+        if let (Some(before), Some(after)) = (&self.temp_entries, temp_entries()) {
+            let mut left: Vec<String> = after
+                .difference(before)
+                .map(|name| std::env::temp_dir().join(name).display().to_string())
+                .collect();
+            if !left.is_empty() {
+                left.sort();
+                let noun = if left.len() == 1 { "entry" } else { "entries" };
+                findings.push(Finding {
+                    level: self.temp_files,
+                    check: "temp-files",
+                    message: format!(
+                        "left {} {} in the temporary directory: {}",
+                        left.len(),
+                        noun,
+                        left.join(", ")
+                    ),
+                    spawned_at: Vec::new(),
+                });
+            }
+        }
+        judge(test, findings)
+    }
+}
+
+/// Where the tasks of a test's Tokio runtime are spawned, recorded by the
+/// runtime's task hooks while the `tokio-tasks` check is on. Tokio has the
+/// hooks only when built with `--cfg tokio_unstable`.
+#[cfg(feature = "tokio")]
+#[derive(Default)]
+pub(crate) struct TaskSpawns {
+    #[cfg(tokio_unstable)]
+    spawned: Option<std::sync::Arc<Mutex<SpawnedTasks>>>,
+}
+
+#[cfg(all(feature = "tokio", tokio_unstable))]
+type SpawnedTasks = std::collections::HashMap<tokio::task::Id, std::backtrace::Backtrace>;
+
+#[cfg(feature = "tokio")]
+impl TaskSpawns {
+    /// Records where the tasks of the runtime `builder` builds are spawned,
+    /// if the `tokio-tasks` check is on.
+    #[cfg_attr(not(tokio_unstable), allow(unused_variables))]
+    pub(crate) fn hook(builder: &mut tokio::runtime::Builder) -> TaskSpawns {
+        #[cfg(tokio_unstable)]
+        if tokio_tasks_checked() {
+            let spawned = std::sync::Arc::new(Mutex::new(SpawnedTasks::new()));
+            let on_spawn = std::sync::Arc::clone(&spawned);
+            let on_terminate = std::sync::Arc::clone(&spawned);
+            builder
+                .on_task_spawn(move |task| {
+                    let backtrace = std::backtrace::Backtrace::force_capture();
+                    alloc::untracked(|| {
+                        on_spawn
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .insert(task.id(), backtrace)
+                    });
+                })
+                .on_task_terminate(move |task| {
+                    alloc::untracked(|| {
+                        on_terminate
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .remove(&task.id())
+                    });
+                });
+            return TaskSpawns {
+                spawned: Some(spawned),
+            };
+        }
+        TaskSpawns::default()
+    }
+
+    /// Backtraces of where the tasks still running were spawned.
+    fn pending(self) -> Vec<String> {
+        #[cfg(tokio_unstable)]
+        if let Some(spawned) = self.spawned {
+            let spawned = std::mem::take(&mut *spawned.lock().unwrap_or_else(|e| e.into_inner()));
+            return spawned
+                .values()
+                .map(|backtrace| crate::panic::spawn_backtrace(backtrace, "tokio"))
+                .collect();
+        }
+        Vec::new()
+    }
+}
+
+/// Notes that the Tokio runtime of the test running on this thread had
+/// `tasks` tasks left when the test returned, spawned where `spawns` says.
+#[cfg(feature = "tokio")]
+pub(crate) fn tokio_tasks_left(tasks: usize, spawns: TaskSpawns) {
+    let Some(test) = crate::registry::current() else {
+        return;
+    };
+    if tasks == 0 || !tokio_tasks_checked() {
+        return;
+    }
+    alloc::untracked(|| {
+        let spawned_at = spawns.pending();
+        TASKS_LEFT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((test, tasks, spawned_at))
+    });
+}
+
+#[cfg(feature = "tokio")]
+fn tokio_tasks_checked() -> bool {
+    in_test_levels().is_some_and(|(_, tokio_tasks, _)| tokio_tasks != Level::Off)
+}
+
+fn take_tasks_left(test: &TestDef) -> Option<(usize, Vec<String>)> {
+    let mut left = TASKS_LEFT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut tasks = 0;
+    let mut spawned_at = Vec::new();
+    left.retain_mut(|(of, count, spawns)| {
+        let mine = std::ptr::eq(*of, test);
+        if mine {
+            tasks += *count;
+            spawned_at.append(spawns);
+        }
+        !mine
+    });
+    (tasks > 0).then_some((tasks, spawned_at))
+}
+
+/// Flags `outcome` if its test passed but took longer than the run allows.
+pub(crate) fn check_duration(outcome: &mut TestOutcome) {
+    let Some(checks) = CHECKS.get() else {
+        return;
+    };
+    if checks.slow == Level::Off
+        || outcome.status != TestStatus::Passed
+        || outcome.duration <= checks.slow_threshold
+    {
+        return;
+    }
+    let message = format!(
+        "took {}, more than the slow-threshold of {}",
+        bench::format_time(outcome.duration),
+        bench::format_time(checks.slow_threshold)
+    );
+    let failures = judge(
+        outcome.test,
+        vec![Finding {
+            level: checks.slow,
+            check: "slow",
+            message,
+            spawned_at: Vec::new(),
+        }],
+    );
+    if !failures.is_empty() {
+        outcome.status = TestStatus::Failed(failures);
+    }
+}
+
+/// What a check found a test to have done.
+struct Finding {
+    level: Level,
+    check: &'static str,
+    message: String,
+    spawned_at: Vec<String>,
+}
+
+/// Records the findings of `test` set to warn, and returns those set to
+/// fail as failures.
+fn judge(test: &'static TestDef, findings: Vec<Finding>) -> Vec<TestFailure> {
+    let mut failures = Vec::new();
+    for finding in findings {
+        match finding.level {
+            Level::Off => {}
+            Level::Warn => record(StrictWarning {
+                test,
+                check: finding.check,
+                message: finding.message,
+                spawned_at: finding.spawned_at,
+            }),
+            Level::Fail => {
+                let mut message = format!(
+                    "strict mode: the test {} (`{}` check)",
+                    finding.message, finding.check
+                );
+                for backtrace in &finding.spawned_at {
+                    message.push_str(&format!("\n\ntask spawned at:\n{}", backtrace));
+                }
+                failures.push(TestFailure::new(message));
+            }
+        }
+    }
+    failures
+}
+
+fn record(warning: StrictWarning) {
+    alloc::untracked(|| {
+        WARNINGS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(warning)
+    });
+}
+
+/// Removes the warnings recorded so far, and returns them.
+pub fn take_warnings() -> Vec<StrictWarning> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+pub(crate) fn to_json(warnings: &[StrictWarning]) -> Value {
+    warnings
+        .iter()
+        .map(|warning| {
+            json!({
+                "check": warning.check,
+                "message": warning.message,
+                "spawned_at": warning.spawned_at,
+            })
+        })
+        .collect()
+}
+
+/// Records the warnings a child process running `test` handed over.
+pub(crate) fn record_json(test: &'static TestDef, warnings: &Value) {
+    for warning in warnings.as_array().into_iter().flatten() {
+        let check = match warning["check"].as_str() {
+            Some("threads") => "threads",
+            Some("tokio-tasks") => "tokio-tasks",
+            Some("temp-files") => "temp-files",
+            _ => continue,
+        };
+        if let Some(message) = warning["message"].as_str() {
+            let spawned_at = warning["spawned_at"].as_array().into_iter().flatten();
+            record(StrictWarning {
+                test,
+                check,
+                message: message.to_string(),
+                spawned_at: spawned_at
+                    .filter_map(|backtrace| backtrace.as_str().map(str::to_string))
+                    .collect(),
+            });
+        }
+    }
+}
+
+/// The ids of the process's threads, where the platform lists them.
+fn thread_ids() -> Option<HashSet<OsString>> {
+    let tasks = fs::read_dir("/proc/self/task").ok()?;
+    Some(tasks.flatten().map(|task| task.file_name()).collect())
+}
+
+/// The names of the threads started since `before` that are still running
+/// once they have had [`THREAD_GRACE`] to exit, if any are.
+fn threads_left(before: &HashSet<OsString>) -> Option<Vec<String>> {
+    let start = Instant::now();
+    loop {
+        let started: Vec<OsString> = thread_ids()?.difference(before).cloned().collect();
+        if started.is_empty() {
+            return None;
+        }
+        if start.elapsed() >= THREAD_GRACE {
+            let mut names: Vec<String> = started
+                .iter()
+                .map(|id| {
+                    let comm = format!("/proc/self/task/{}/comm", id.to_string_lossy());
+                    match fs::read_to_string(comm) {
+                        Ok(name) => format!("`{}`", name.trim_end()),
+                        Err(_) => format!("thread {}", id.to_string_lossy()),
+                    }
+                })
+                .collect();
+            names.sort();
+            return Some(names);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// The names of the entries of the temporary directory.
+fn temp_entries() -> Option<HashSet<OsString>> {
+    let entries = fs::read_dir(std::env::temp_dir()).ok()?;
+    Some(entries.flatten().map(|entry| entry.file_name()).collect())
+}
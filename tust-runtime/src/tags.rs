@@ -0,0 +1,265 @@
+//! Tag expressions accepted by `--tag` and `--skip-tag`.
+//!
+//! An expression is a tag name, or tag names combined with `!` (not), `&&`
+//! (and), `||` (or), and parentheses. `!` binds tightest and `||` loosest, so
+//! `db && !slow || smoke` reads as `(db && (!slow)) || smoke`.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed tag expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpr {
+    /// Matches tests carrying this tag.
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    /// Whether a test carrying `tags` matches the expression.
+    pub fn matches(&self, tags: &[&str]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.contains(&tag.as_str()),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(left, right) => left.matches(tags) && right.matches(tags),
+            TagExpr::Or(left, right) => left.matches(tags) || right.matches(tags),
+        }
+    }
+
+    /// The tag names the expression mentions, in order.
+    pub fn tags(&self) -> Vec<&str> {
+        match self {
+            TagExpr::Tag(tag) => vec![tag.as_str()],
+            TagExpr::Not(expr) => expr.tags(),
+            TagExpr::And(left, right) | TagExpr::Or(left, right) => {
+                let mut tags = left.tags();
+                tags.extend(right.tags());
+                tags
+            }
+        }
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = TagExprError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let parsed = parser.or()?;
+        match parser.peek() {
+            None => Ok(parsed),
+            Some(token) => Err(TagExprError(format!("unexpected `{}`", token))),
+        }
+    }
+}
+
+/// A tag expression that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagExprError(String);
+
+impl fmt::Display for TagExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TagExprError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Tag(tag) => f.write_str(tag),
+            Token::Not => f.write_str("!"),
+            Token::And => f.write_str("&&"),
+            Token::Or => f.write_str("||"),
+            Token::Open => f.write_str("("),
+            Token::Close => f.write_str(")"),
+        }
+    }
+}
+
+/// Whether `c` may appear in a tag name; `#[tust::test(tags(...))]` accepts
+/// the same characters.
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, TagExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' | '|' => {
+                if chars.next_if(|&(_, next)| next == c).is_none() {
+                    return Err(TagExprError(format!("expected `{}{}`", c, c)));
+                }
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            c if is_tag_char(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars.next_if(|&(_, c)| is_tag_char(c)) {
+                    end = index + c.len_utf8();
+                }
+                Token::Tag(expr[start..end].to_string())
+            }
+            c => return Err(TagExprError(format!("unexpected `{}`", c))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A recursive-descent parser over the tokens of an expression.
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.next += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<TagExpr, TagExprError> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<TagExpr, TagExprError> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = TagExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<TagExpr, TagExprError> {
+        let Some(token) = self.tokens.get(self.next).cloned() else {
+            return Err(TagExprError("expected a tag".to_string()));
+        };
+        self.next += 1;
+        match token {
+            Token::Tag(tag) => Ok(TagExpr::Tag(tag)),
+            Token::Not => Ok(TagExpr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err(TagExprError("expected `)`".to_string()));
+                }
+                Ok(expr)
+            }
+            token => Err(TagExprError(format!("expected a tag, found `{}`", token))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(expr: &str) -> TagExpr {
+        expr.parse().unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        expr.parse::<TagExpr>().unwrap_err().to_string()
+    }
+
+    fn tag(name: &str) -> Box<TagExpr> {
+        Box::new(TagExpr::Tag(name.to_string()))
+    }
+
+    #[test]
+    fn not_binds_tightest_and_or_loosest() {
+        assert_eq!(
+            parse("db && !slow || smoke"),
+            TagExpr::Or(
+                Box::new(TagExpr::And(tag("db"), Box::new(TagExpr::Not(tag("slow"))))),
+                tag("smoke"),
+            )
+        );
+        assert_eq!(
+            parse("db && (slow || smoke)"),
+            TagExpr::And(tag("db"), Box::new(TagExpr::Or(tag("slow"), tag("smoke"))))
+        );
+    }
+
+    #[test]
+    fn matches_tests_by_their_tags() {
+        let expr = parse("db && !slow || smoke");
+        assert!(expr.matches(&["db"]));
+        assert!(!expr.matches(&["db", "slow"]));
+        assert!(expr.matches(&["db", "slow", "smoke"]));
+        assert!(!expr.matches(&[]));
+    }
+
+    #[test]
+    fn lists_the_tags_mentioned_in_order() {
+        assert_eq!(parse("db").tags(), ["db"]);
+        assert_eq!(
+            parse("!(db || net) && (slow || db)").tags(),
+            ["db", "net", "slow", "db"]
+        );
+    }
+
+    #[test]
+    fn not_chains() {
+        assert_eq!(
+            parse("!!db"),
+            TagExpr::Not(Box::new(TagExpr::Not(tag("db"))))
+        );
+        assert!(parse("!!db").matches(&["db"]));
+        assert!(parse("!!!db").matches(&["net"]));
+    }
+
+    #[test]
+    fn tags_may_hold_dashes_dots_and_colons() {
+        assert_eq!(parse(" os::linux-x86.64 "), *tag("os::linux-x86.64"));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert_eq!(error(""), "expected a tag");
+        assert_eq!(error("   "), "expected a tag");
+        assert_eq!(error("a &"), "expected `&&`");
+        assert_eq!(error("a | b"), "expected `||`");
+        assert_eq!(error("a ||"), "expected a tag");
+        assert_eq!(error("(a"), "expected `)`");
+        assert_eq!(error("a)"), "unexpected `)`");
+        assert_eq!(error("a b"), "unexpected `b`");
+        assert_eq!(error("&& a"), "expected a tag, found `&&`");
+        assert_eq!(error("a + b"), "unexpected `+`");
+    }
+}
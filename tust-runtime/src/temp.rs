@@ -0,0 +1,230 @@
+//! Built-in `temp_dir` and `temp_file` fixtures.
+//!
+//! A test parameter named `temp_dir` of type [`TempDir`] receives a fresh,
+//! empty directory, and one named `temp_file` of type [`TempFile`] a fresh,
+//! empty file. Both are named after the test and removed once the value is
+//! dropped at the end of the test.
+//!
+//! With `TUST_KEEP_TEMP=1` set, the directories and files of failing tests
+//! are kept instead, and their paths printed, so that what the test left
+//! behind can be inspected.
+
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::fixture::{FixtureDef, FixtureError, Fixtures, Provider};
+use crate::process;
+use crate::registry;
+use crate::soft;
+
+/// Environment variable that keeps the temporary directories and files of
+/// failing tests when set to `1` or `true`.
+pub const KEEP_ENV: &str = "TUST_KEEP_TEMP";
+
+inventory::submit! {
+    FixtureDef {
+        name: "temp_dir",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<TempDir>,
+        type_id: TypeId::of::<TempDir>,
+        provider: Provider::PerTest(provide_temp_dir),
+    }
+}
+
+inventory::submit! {
+    FixtureDef {
+        name: "temp_file",
+        module_path: module_path!(),
+        file: file!(),
+        line: line!(),
+        dependencies: &[],
+        type_name: std::any::type_name::<TempFile>,
+        type_id: TypeId::of::<TempFile>,
+        provider: Provider::PerTest(provide_temp_file),
+    }
+}
+
+fn provide_temp_dir(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let dir =
+        TempDir::new().unwrap_or_else(|err| panic!("cannot create a temporary directory: {}", err));
+    Ok(Box::new(dir))
+}
+
+fn provide_temp_file(_: &mut Fixtures) -> Result<Box<dyn Any>, FixtureError> {
+    let file =
+        TempFile::new().unwrap_or_else(|err| panic!("cannot create a temporary file: {}", err));
+    Ok(Box::new(file))
+}
+
+/// A directory removed, with its contents, when dropped.
+pub struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    /// Creates an empty directory in the system's temporary directory, named
+    /// after the current test.
+    pub fn new() -> io::Result<Self> {
+        loop {
+            let path = unique_path();
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(TempDir { path, keep: false }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the directory rather than removing it when dropped, returning
+    /// its path.
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep && !kept("directory", &self.path) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// A file removed when dropped.
+pub struct TempFile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempFile {
+    /// Creates an empty file in the system's temporary directory, named after
+    /// the current test.
+    pub fn new() -> io::Result<Self> {
+        loop {
+            let path = unique_path();
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(TempFile { path, keep: false }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the file rather than removing it when dropped, returning its
+    /// path.
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.keep && !kept("file", &self.path) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+macro_rules! path_impls {
+    ($($ty:ty),*) => {$(
+        impl Deref for $ty {
+            type Target = Path;
+
+            fn deref(&self) -> &Path {
+                &self.path
+            }
+        }
+
+        impl AsRef<Path> for $ty {
+            fn as_ref(&self) -> &Path {
+                &self.path
+            }
+        }
+
+        impl fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple(stringify!($ty)).field(&self.path).finish()
+            }
+        }
+    )*};
+}
+
+path_impls!(TempDir, TempFile);
+
+/// Whether [`KEEP_ENV`] asks for the temporary files of failing tests to be
+/// kept.
+pub fn keep_requested() -> bool {
+    std::env::var(KEEP_ENV).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// Decides whether `path`, about to be removed, is kept because the test
+/// dropping it failed, printing it if so.
+fn kept(kind: &str, path: &Path) -> bool {
+    let failed = std::thread::panicking() || soft::has_failures();
+    if !failed || !keep_requested() {
+        return false;
+    }
+    match registry::current() {
+        Some(test) => eprintln!(
+            "note: kept temporary {} of failed test `{}`: {}",
+            kind,
+            test.path(),
+            path.display()
+        ),
+        None => eprintln!("note: kept temporary {}: {}", kind, path.display()),
+    }
+    true
+}
+
+/// A path in the system's temporary directory that is unique to this process
+/// and named after the current test, if any.
+fn unique_path() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let test: String = registry::current()
+        .map(|test| test.path())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let id = format!("{}-{}", process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+    let name = if test.is_empty() {
+        format!("tust-{}", id)
+    } else {
+        format!("tust-{}-{}", test, id)
+    };
+    system_temp_dir().join(name)
+}
+
+/// The system's temporary directory. WASI has no such notion, so there it is
+/// `TMPDIR`, or else `/tmp`, either of which the host must make available.
+fn system_temp_dir() -> PathBuf {
+    if cfg!(target_family = "wasm") {
+        std::env::var_os("TMPDIR").map_or_else(|| PathBuf::from("/tmp"), PathBuf::from)
+    } else {
+        std::env::temp_dir()
+    }
+}
@@ -0,0 +1,213 @@
+//! Test durations recorded across runs.
+//!
+//! After every run, the runner records how long each test took in a cache
+//! file, by default `tust/timings.json` in Cargo's target directory, or the
+//! file named by [`CACHE_ENV`]. Later runs read it back to start the slowest
+//! tests first when running tests in parallel, so that a long test does not
+//! start last and hold up the end of the run. `--report timings=FILE`
+//! records durations in the same format, to a file of one's choosing.
+//!
+//! The file maps each test, by its module path including the crate name, to
+//! the seconds it took. Tests recorded in an existing file that did not run
+//! are kept, so the test binaries of a package can share one file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::outcome::TestOutcome;
+use crate::registry::TestDef;
+
+/// Environment variable naming the cache file; if set but empty, durations
+/// are neither read nor recorded.
+pub const CACHE_ENV: &str = "TUST_TIMINGS";
+
+/// How long each test took in earlier runs.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    durations: HashMap<String, Duration>,
+}
+
+impl Timings {
+    /// Reads timings recorded by the runner or by `--report timings=PATH`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tests = value["tests"].as_object().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "expected a `tests` object")
+        })?;
+        let durations = tests
+            .iter()
+            .filter_map(|(key, secs)| {
+                let secs = secs
+                    .as_f64()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)?;
+                Some((key.clone(), Duration::from_secs_f64(secs)))
+            })
+            .collect();
+        Ok(Timings { durations })
+    }
+
+    /// Writes the timings to `path`, creating its directory if needed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut tests: Vec<_> = self.durations.iter().collect();
+        tests.sort_by_key(|&(key, _)| key);
+        let tests: serde_json::Map<String, Value> = tests
+            .into_iter()
+            .map(|(key, duration)| (key.clone(), json!(duration.as_secs_f64())))
+            .collect();
+        let mut text = serde_json::to_string_pretty(&json!({ "tests": tests }))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        text.push('\n');
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    /// Whether no test has a recorded duration.
+    pub fn is_empty(&self) -> bool {
+        self.durations.is_empty()
+    }
+
+    pub fn get(&self, test: &TestDef) -> Option<Duration> {
+        self.durations.get(&key(test)).copied()
+    }
+
+    pub fn insert(&mut self, test: &TestDef, duration: Duration) {
+        self.durations.insert(key(test), duration);
+    }
+
+    /// Records the duration of every test in `outcomes` that ran.
+    pub(crate) fn record(&mut self, outcomes: &[TestOutcome]) {
+        for outcome in outcomes.iter().filter(|outcome| outcome.attempts > 0) {
+            self.insert(outcome.test, outcome.duration);
+        }
+    }
+
+    /// The average recorded duration of `tests`, or zero if none has one.
+    pub(crate) fn average(&self, tests: &[&'static TestDef]) -> Duration {
+        let known: Vec<Duration> = tests.iter().filter_map(|test| self.get(test)).collect();
+        match known.len() {
+            0 => Duration::ZERO,
+            len => known.iter().sum::<Duration>() / len as u32,
+        }
+    }
+}
+
+pub(crate) fn key(test: &TestDef) -> String {
+    format!("{}::{}", test.module_path, test.name)
+}
+
+/// The cache file: [`CACHE_ENV`] if set, else `tust/timings.json` in the
+/// target directory holding the running binary, if there is one.
+pub fn cache_path() -> Option<PathBuf> {
+    cache::path(CACHE_ENV, "timings.json")
+}
+
+/// The durations recorded in the cache, if any.
+pub(crate) fn load_cache() -> Timings {
+    cache_path()
+        .and_then(|path| Timings::read(&path).ok())
+        .unwrap_or_default()
+}
+
+/// Records the durations of `outcomes` in the cache.
+pub(crate) fn update_cache(outcomes: &[TestOutcome]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    let mut timings = Timings::read(&path).unwrap_or_default();
+    timings.record(outcomes);
+    if let Err(err) = timings.write(&path) {
+        eprintln!(
+            "warning: failed to record test timings in {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::TestStatus;
+
+    fn test(name: &'static str) -> &'static TestDef {
+        Box::leak(Box::new(TestDef::ran_elsewhere(
+            "krate::db",
+            name,
+            "t.rs",
+            1,
+        )))
+    }
+
+    fn outcome(test: &'static TestDef, millis: u64, attempts: u32) -> TestOutcome {
+        TestOutcome {
+            test,
+            status: TestStatus::Passed,
+            duration: Duration::from_millis(millis),
+            output: String::new(),
+            attempts,
+            attachments: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn records_the_tests_that_ran() {
+        let (insert, query, skipped) = (test("insert"), test("query"), test("skipped"));
+        let mut timings = Timings::default();
+        assert!(timings.is_empty());
+        timings.record(&[
+            outcome(insert, 250, 1),
+            outcome(query, 50, 2),
+            outcome(skipped, 0, 0),
+        ]);
+        assert_eq!(timings.get(insert), Some(Duration::from_millis(250)));
+        assert_eq!(timings.get(query), Some(Duration::from_millis(50)));
+        assert_eq!(timings.get(skipped), None);
+        assert_eq!(
+            timings.average(&[insert, query, skipped]),
+            Duration::from_millis(150)
+        );
+        assert_eq!(Timings::default().average(&[insert]), Duration::ZERO);
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let dir = std::env::temp_dir().join(format!("tust-timings-{}", std::process::id()));
+        let path = dir.join("nested").join("timings.json");
+        let insert = test("insert");
+        let mut timings = Timings::default();
+        timings.insert(insert, Duration::from_millis(1500));
+        timings.write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"tests\": {\n    \"krate::db::insert\": 1.5\n  }\n}\n"
+        );
+        assert_eq!(
+            Timings::read(&path).unwrap().get(insert),
+            Some(Duration::from_millis(1500))
+        );
+
+        fs::write(
+            &path,
+            r#"{"tests": {"krate::db::insert": -1, "krate::db::query": "slow"}}"#,
+        )
+        .unwrap();
+        assert!(Timings::read(&path).unwrap().is_empty());
+        fs::write(&path, r#"{"durations": {}}"#).unwrap();
+        assert_eq!(
+            Timings::read(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
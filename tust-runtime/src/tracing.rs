@@ -0,0 +1,279 @@
+//! Capturing `tracing` spans and events per test.
+//!
+//! [`init_tracing`], called from a test or a `before_each` hook, installs a
+//! subscriber on the test's thread for the rest of the test. It records, in
+//! memory, every event at or above a level, together with the spans it
+//! occurred in. Once the test finishes, the events are printed to stderr if
+//! the test failed and discarded otherwise, so passing tests stay quiet.
+//!
+//! The subscriber is installed on the test's thread only: events emitted on
+//! threads the test spawns, including the worker threads of a multi-threaded
+//! Tokio runtime, are not captured.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing_core::dispatcher::{self, DefaultGuard, Dispatch};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Interest, Level, LevelFilter, Metadata, Subscriber};
+use web_time::Instant;
+
+use crate::registry;
+
+/// Environment variable setting the lowest level [`init_tracing`] captures,
+/// e.g. `debug`. Defaults to `trace`.
+pub const LEVEL_ENV: &str = "TUST_TRACE_LEVEL";
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Active>> = const { RefCell::new(None) };
+}
+
+/// The subscriber installed for the test running on this thread.
+struct Active {
+    log: Arc<Log>,
+    _guard: DefaultGuard,
+}
+
+/// Captures the spans and events of the current test, printing them if the
+/// test fails. The lowest level captured is read from [`LEVEL_ENV`].
+///
+/// Does nothing outside of a test, or if the test already called it.
+pub fn init_tracing() {
+    let level = match std::env::var(LEVEL_ENV) {
+        Ok(level) => level.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "warning: ignoring {}={:?}, which is not a level",
+                LEVEL_ENV, level
+            );
+            Level::TRACE
+        }),
+        Err(_) => Level::TRACE,
+    };
+    init_tracing_at(level);
+}
+
+/// Like [`init_tracing`], capturing only events at `level` or above.
+pub fn init_tracing_at(level: Level) {
+    if registry::current().is_none() {
+        return;
+    }
+    ACTIVE.with(|active| {
+        let mut active = active.borrow_mut();
+        if active.is_some() {
+            return;
+        }
+        let log = Arc::new(Log::new(level));
+        let guard = dispatcher::set_default(&Dispatch::from(CaptureSubscriber(Arc::clone(&log))));
+        *active = Some(Active { log, _guard: guard });
+    });
+}
+
+/// Uninstalls the subscriber of the test that just finished on this thread,
+/// printing what it captured if the test `failed`.
+pub(crate) fn finish(failed: bool) {
+    let Some(active) = ACTIVE.with(|active| active.borrow_mut().take()) else {
+        return;
+    };
+    let log = Arc::clone(&active.log);
+    drop(active);
+    let lines = std::mem::take(&mut *log.lines.lock().unwrap_or_else(|e| e.into_inner()));
+    if failed && !lines.is_empty() {
+        let mut dump = format!("---- tracing events (at {} or above) ----\n", log.level);
+        for line in lines {
+            dump.push_str(&line);
+            dump.push('\n');
+        }
+        eprint!("{}", dump);
+    }
+}
+
+/// What a [`CaptureSubscriber`] has recorded.
+struct Log {
+    level: Level,
+    start: Instant,
+    next_id: AtomicU64,
+    state: Mutex<State>,
+    lines: Mutex<Vec<String>>,
+}
+
+#[derive(Default)]
+struct State {
+    spans: HashMap<u64, Span>,
+    /// The spans entered, innermost last.
+    stack: Vec<u64>,
+}
+
+struct Span {
+    name: &'static str,
+    fields: String,
+    parent: Option<u64>,
+}
+
+impl Log {
+    fn new(level: Level) -> Self {
+        Log {
+            level,
+            start: Instant::now(),
+            next_id: AtomicU64::new(1),
+            state: Mutex::new(State::default()),
+            lines: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    /// The span an event or span without an explicit parent belongs to.
+    fn current(&self) -> Option<u64> {
+        self.stack.last().copied()
+    }
+
+    /// `span` and its ancestors, outermost first, as `outer{a=1}:inner`.
+    fn context(&self, span: Option<u64>) -> String {
+        let mut names = Vec::new();
+        let mut next = span;
+        while let Some(span) = next.and_then(|id| self.spans.get(&id)) {
+            if span.fields.is_empty() {
+                names.push(span.name.to_string());
+            } else {
+                names.push(format!("{}{{{}}}", span.name, span.fields));
+            }
+            next = span.parent;
+        }
+        names.reverse();
+        names.join(":")
+    }
+}
+
+struct CaptureSubscriber(Arc<Log>);
+
+impl Subscriber for CaptureSubscriber {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // Callsite interest is shared by every thread's subscriber, so
+        // decide per call in `enabled` instead.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() || *metadata.level() <= self.0.level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::TRACE)
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let mut state = self.0.state();
+        let parent = if attrs.is_contextual() {
+            state.current()
+        } else {
+            attrs.parent().map(Id::into_u64)
+        };
+        state.spans.insert(
+            id,
+            Span {
+                name: attrs.metadata().name(),
+                fields: fields.finish(),
+                parent,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        let fields = fields.finish();
+        if let Some(span) = self.0.state().spans.get_mut(&span.into_u64()) {
+            if !span.fields.is_empty() && !fields.is_empty() {
+                span.fields.push(' ');
+            }
+            span.fields.push_str(&fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let context = {
+            let state = self.0.state();
+            let parent = if event.is_contextual() {
+                state.current()
+            } else {
+                event.parent().map(Id::into_u64)
+            };
+            state.context(parent)
+        };
+
+        let mut line = format!(
+            "{:>9.3}s {:>5} {}",
+            self.0.start.elapsed().as_secs_f64(),
+            metadata.level(),
+            metadata.target()
+        );
+        if !context.is_empty() {
+            let _ = write!(line, " {}", context);
+        }
+        let _ = write!(line, ": {}", fields.finish());
+        self.0
+            .lines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(line);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.0.state().stack.push(span.into_u64());
+    }
+
+    fn exit(&self, span: &Id) {
+        let mut state = self.0.state();
+        let id = span.into_u64();
+        if let Some(index) = state.stack.iter().rposition(|&entered| entered == id) {
+            state.stack.remove(index);
+        }
+    }
+}
+
+/// Formats fields as `message key=value ...`.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Fields {
+    fn finish(self) -> String {
+        match (self.message.is_empty(), self.rest.is_empty()) {
+            (_, true) => self.message,
+            (true, false) => self.rest,
+            (false, false) => format!("{} {}", self.message, self.rest),
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            if !self.rest.is_empty() {
+                self.rest.push(' ');
+            }
+            let _ = write!(self.rest, "{}={:?}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Running tests on WebAssembly.
+//!
+//! WebAssembly has neither threads nor processes, and its panics abort
+//! rather than unwind. The runner therefore runs one test at a time on the
+//! main thread, does not capture output or enforce timeouts, and, since a
+//! panic takes the instance with it, cannot carry on past a test that
+//! panics. It records here the outcomes reported so far, so that the panic
+//! hook can judge the panicking test, report it with the rest of the run,
+//! and end the run, leaving the remaining tests not run. Failures that do
+//! not panic, such as those of [`soft`](crate::soft) assertions, are
+//! reported without ending the run.
+//!
+//! On `wasm32-unknown-unknown`, which has no standard output of its own, the
+//! reporters write to the JavaScript console through `wasm-bindgen`, and a
+//! failed run throws an exception once it has been reported.
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::outcome::{RunSummary, TestFailure, TestOutcome, TestStatus};
+use crate::registry::TestDef;
+use crate::report::{ConsoleReporter, Reporter, Style};
+use crate::runner::{self, EXIT_SUCCESS, EXIT_TESTS_FAILED};
+
+thread_local! {
+    static PROGRESS: RefCell<Option<Progress>> = const { RefCell::new(None) };
+}
+
+/// What the runner has reported so far.
+struct Progress {
+    style: Style,
+    output_lines: usize,
+    outcomes: Vec<TestOutcome>,
+    summary: RunSummary,
+    /// Tests not reported yet, including the one running.
+    remaining: usize,
+    /// When the last test was reported, and so about when the running test
+    /// started.
+    last: Instant,
+}
+
+/// Starts recording a run of `total` tests reported by a console reporter of
+/// `style`, counted into `summary`.
+pub(crate) fn start(style: Style, output_lines: usize, total: usize, summary: &RunSummary) {
+    let progress = Progress {
+        style,
+        output_lines,
+        outcomes: Vec::with_capacity(total),
+        summary: summary.clone(),
+        remaining: total,
+        last: Instant::now(),
+    };
+    PROGRESS.with(|current| *current.borrow_mut() = Some(progress));
+}
+
+/// Records an outcome the runner has reported.
+pub(crate) fn record(outcome: &TestOutcome) {
+    PROGRESS.with(|current| {
+        if let Some(progress) = &mut *current.borrow_mut() {
+            progress.summary.record(outcome);
+            progress.outcomes.push(outcome.clone());
+            progress.remaining = progress.remaining.saturating_sub(1);
+            progress.last = Instant::now();
+        }
+    });
+}
+
+/// Ends the run at `failure`, the panic of `test`: reports `test` and the
+/// end of the run, and exits.
+pub(crate) fn abort(test: &'static TestDef, failure: TestFailure) -> ! {
+    let Some(mut progress) = PROGRESS.with(|current| current.borrow_mut().take()) else {
+        console::exit(EXIT_TESTS_FAILED);
+    };
+    let outcome = runner::check_expected(TestOutcome {
+        test,
+        status: TestStatus::Failed(vec![failure]),
+        duration: progress.last.elapsed(),
+        output: String::new(),
+        attempts: 1,
+        attachments: Vec::new(),
+        steps: Vec::new(),
+    });
+    progress.summary.record(&outcome);
+    progress.summary.not_run = progress.remaining.saturating_sub(1);
+    progress.summary.duration = progress
+        .outcomes
+        .iter()
+        .map(|outcome| outcome.duration)
+        .sum::<Duration>()
+        + outcome.duration;
+
+    let mut reporter = ConsoleReporter::new(progress.style, false, 0, progress.output_lines);
+    reporter.on_test_start(test);
+    reporter.on_test_finish(&outcome);
+    progress.outcomes.push(outcome);
+    reporter.on_run_end(&progress.outcomes, &progress.summary);
+    console::write_line(&format!(
+        "note: panics abort WebAssembly, so the run ended when `{}` panicked",
+        test.path()
+    ));
+    console::exit(
+        if progress.summary.not_run == 0 && progress.summary.is_success() {
+            EXIT_SUCCESS
+        } else {
+            EXIT_TESTS_FAILED
+        },
+    );
+}
+
+/// Ends a run that completed with exit code `code`.
+pub(crate) fn finish(code: u8) {
+    PROGRESS.with(|current| current.borrow_mut().take());
+    if code != EXIT_SUCCESS {
+        console::fail(code);
+    }
+}
+
+#[cfg(target_os = "unknown")]
+pub(crate) use console::Console;
+
+#[cfg(target_os = "unknown")]
+mod console {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = console)]
+        fn log(line: &str);
+    }
+
+    thread_local! {
+        /// The line written so far, logged once it ends.
+        static LINE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Writes lines to the JavaScript console, one `console.log` each.
+    pub(crate) struct Console;
+
+    impl Write for Console {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            LINE.with(|line| {
+                let mut line = line.borrow_mut();
+                for &byte in buf {
+                    if byte == b'\n' {
+                        log(&String::from_utf8_lossy(&line));
+                        line.clear();
+                    } else {
+                        line.push(byte);
+                    }
+                }
+            });
+            Ok(buf.len())
+        }
+
+        // `console.log` always starts a new line, so a partial line waits
+        // for the rest of it.
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub(super) fn write_line(line: &str) {
+        let _ = writeln!(Console, "{}", line);
+    }
+
+    /// Throws, so that the JavaScript host sees the run failed.
+    pub(super) fn fail(code: u8) -> ! {
+        exit(code)
+    }
+
+    /// Returning from a panic would abort, so a run ended by one always
+    /// throws, whatever its exit code.
+    pub(super) fn exit(code: u8) -> ! {
+        LINE.with(|line| {
+            let mut line = line.borrow_mut();
+            if !line.is_empty() {
+                log(&String::from_utf8_lossy(&line));
+                line.clear();
+            }
+        });
+        wasm_bindgen::throw_str(&format!("tust: the test run ended with exit code {}", code))
+    }
+}
+
+#[cfg(not(target_os = "unknown"))]
+mod console {
+    use std::io::{self, Write};
+
+    pub(super) fn write_line(line: &str) {
+        let _ = writeln!(io::stdout(), "{}", line);
+    }
+
+    /// The exit code of `main` is the run's.
+    pub(super) fn fail(_code: u8) {}
+
+    pub(super) fn exit(code: u8) -> ! {
+        let _ = io::stdout().flush();
+        std::process::exit(i32::from(code))
+    }
+}
@@ -0,0 +1,698 @@
+//! Running the tests of every package of a Cargo workspace as one run.
+//!
+//! `cargo tust workspace` builds the test binaries of the workspace's
+//! packages, runs each one using the tust runner with `--format json`, and
+//! merges the events they print into a single run, reported by the
+//! reporters the options ask for as a run of one binary is. Each test is
+//! named after its package and, unless it is a unit test of the package's
+//! library, its test target, so that the tree groups the tests by package,
+//! the JUnit report has a test suite per package, and one failing test can
+//! be told from another of the same name:
+//!
+//! ```text
+//! parser
+//!   lexer
+//!     ✓ empty
+//!   integration
+//!     ✓ parses_a_file
+//! storage
+//!   ✗ flushes
+//! ```
+//!
+//! The console reports then sum up each package:
+//!
+//! ```text
+//! packages:
+//!   parser      14 passed; 0 failed; 1 skipped    0.412s
+//!   storage      3 passed; 1 failed; 0 skipped    0.057s
+//! ```
+//!
+//! [`discover`] builds the binaries and [`run`] runs them and reports the
+//! merged run; a [`Merge`] merges events recorded some other way, such as
+//! by binaries run on different machines. Binaries testing with libtest are
+//! left out, as libtest prints JSON only on nightly toolchains. A binary
+//! that exits before its run ends, having crashed or been given options it
+//! does not know, fails the merged run, along with the test it was running
+//! if any.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::Duration;
+
+use serde_json::Value;
+use web_time::Instant;
+
+use crate::capture::Stdout;
+use crate::cli::{Args, OutputFormat};
+use crate::context;
+use crate::outcome::{
+    self, FailureDiff, FailureKind, Location, RunSummary, TestFailure, TestOutcome, TestStatus,
+};
+use crate::registry::TestDef;
+use crate::report::{Reporter, Reporters};
+use crate::rerun;
+use crate::runner::{self, EXIT_SUCCESS, EXIT_TESTS_FAILED, EXIT_USAGE};
+
+/// A test binary Cargo built for a package of the workspace.
+#[derive(Debug, Clone)]
+pub struct TestBinary {
+    pub package: String,
+    /// The test target, such as an integration test; `None` for the unit
+    /// tests of the package's library.
+    pub target: Option<String>,
+    pub executable: PathBuf,
+    /// Directory of the package's manifest, which Cargo runs tests in.
+    pub package_dir: PathBuf,
+}
+
+impl TestBinary {
+    /// The package and its target, such as `parser (integration)`.
+    pub fn label(&self) -> String {
+        let target = self.target.as_deref().unwrap_or("lib");
+        format!("{} ({})", self.package, target)
+    }
+}
+
+/// Builds the test binaries of the packages `cargo_args` selects, such as
+/// `--workspace` or `-p NAME`, with `cargo test --no-run`, and returns them.
+///
+/// # Errors
+///
+/// Fails if Cargo cannot be run, or the build fails.
+pub fn discover(cargo_args: &[String]) -> Result<Vec<TestBinary>, String> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let packages = packages(&cargo)?;
+    let output = Command::new(&cargo)
+        .args([
+            "test",
+            "--no-run",
+            "--message-format=json-render-diagnostics",
+        ])
+        .args(cargo_args)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("cannot run cargo: {}", err))?;
+    if !output.status.success() {
+        return Err("the tests failed to build".to_string());
+    }
+    Ok(test_binaries(
+        &String::from_utf8_lossy(&output.stdout),
+        &packages,
+    ))
+}
+
+/// The test binaries built according to Cargo's JSON `messages`, each
+/// once, named after their package in `packages` by manifest path.
+fn test_binaries(messages: &str, packages: &HashMap<PathBuf, String>) -> Vec<TestBinary> {
+    let mut binaries: Vec<TestBinary> = Vec::new();
+    for line in messages.lines() {
+        let Ok(message) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-artifact" || message["profile"]["test"] != true {
+            continue;
+        }
+        let (Some(executable), Some(manifest)) = (
+            message["executable"].as_str(),
+            message["manifest_path"].as_str(),
+        ) else {
+            continue;
+        };
+        if binaries
+            .iter()
+            .any(|binary| binary.executable == Path::new(executable))
+        {
+            continue;
+        }
+        let manifest = Path::new(manifest);
+        let is_lib = message["target"]["kind"]
+            .as_array()
+            .is_some_and(|kinds| kinds.iter().any(|kind| kind == "lib"));
+        binaries.push(TestBinary {
+            package: match packages.get(manifest) {
+                Some(name) => name.clone(),
+                None => message["target"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+            target: (!is_lib)
+                .then(|| message["target"]["name"].as_str().map(str::to_string))
+                .flatten(),
+            executable: PathBuf::from(executable),
+            package_dir: manifest.parent().map(Path::to_path_buf).unwrap_or_default(),
+        });
+    }
+    binaries.sort_by_key(TestBinary::label);
+    binaries
+}
+
+/// The names of the workspace's packages, by manifest path.
+fn packages(cargo: &std::ffi::OsStr) -> Result<HashMap<PathBuf, String>, String> {
+    let output = Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("cannot run cargo: {}", err))?;
+    if !output.status.success() {
+        return Err("cannot read the workspace's metadata".to_string());
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|err| format!("cannot read the workspace's metadata: {}", err))?;
+    Ok(package_names(&metadata))
+}
+
+/// The names of the packages of `cargo metadata`, by manifest path.
+fn package_names(metadata: &Value) -> HashMap<PathBuf, String> {
+    metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            Some((
+                PathBuf::from(package["manifest_path"].as_str()?),
+                package["name"].as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Whether `executable` tests with the tust runner, which names itself in
+/// its `--help`.
+pub fn uses_tust(executable: &Path) -> bool {
+    Command::new(executable)
+        .arg("--help")
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains("registered tust test")
+        })
+}
+
+/// Runs each of `binaries` that uses the tust runner, one at a time, with
+/// `test_args`, and reports the merged run as `args` asks, returning its
+/// exit code.
+///
+/// Only the options choosing how a run is reported apply, such as
+/// `--format`, `--report`, `--color`, and `--show-output`; the options
+/// choosing which tests run and how are for `test_args`.
+pub fn run(binaries: &[TestBinary], test_args: &[String], args: &Args) -> ExitCode {
+    let start = Instant::now();
+    let mut merge = Merge::new();
+    for binary in binaries {
+        if !uses_tust(&binary.executable) {
+            eprintln!(
+                "note: the tests of {} do not use the tust runner; run them with cargo test",
+                binary.label()
+            );
+            continue;
+        }
+        eprintln!("running the tests of {}", binary.label());
+        let output = Command::new(&binary.executable)
+            .args(["--format", "json"])
+            .args(args.show_output.then_some("--show-output"))
+            .args(test_args)
+            .current_dir(&binary.package_dir)
+            .env("CARGO_MANIFEST_DIR", &binary.package_dir)
+            .env("CARGO_PKG_NAME", &binary.package)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output();
+        match output {
+            Ok(output) => {
+                let events = String::from_utf8_lossy(&output.stdout);
+                if !merge.add(&binary.package, binary.target.as_deref(), &events) {
+                    eprintln!(
+                        "error: the tests of {} exited with {} before their run ended",
+                        binary.label(),
+                        output.status
+                    );
+                }
+            }
+            Err(err) => {
+                eprintln!("error: cannot run the tests of {}: {}", binary.label(), err);
+                merge.incomplete += 1;
+            }
+        }
+    }
+    if merge.binaries == 0 && merge.incomplete == 0 {
+        eprintln!("error: no test binary uses the tust runner");
+        return ExitCode::from(EXIT_USAGE);
+    }
+    merge.report(args, start.elapsed())
+}
+
+/// The runs of several test binaries, merged into one.
+#[derive(Default)]
+pub struct Merge {
+    tests: Vec<&'static TestDef>,
+    outcomes: Vec<TestOutcome>,
+    summary: RunSummary,
+    /// Each package's summary, in the order the packages were added.
+    packages: Vec<(String, RunSummary)>,
+    /// The number of runs added.
+    binaries: usize,
+    /// The number of runs that did not end.
+    incomplete: usize,
+}
+
+impl Merge {
+    pub fn new() -> Self {
+        Merge::default()
+    }
+
+    /// Adds the run of a test binary of `package`, from the `--format json`
+    /// events it printed; `target` names the test target of the binary,
+    /// unless it tests the package's library. Returns whether the events
+    /// hold the end of the run; if they do not, the merged run fails, and
+    /// so does a test started and not finished.
+    pub fn add(&mut self, package: &str, target: Option<&str>, events: &str) -> bool {
+        self.binaries += 1;
+        // The package stands in for the crate, so that it names the JUnit
+        // test suite, and starts the test's module, which the tree and the
+        // test's full name show.
+        let prefix = match target {
+            Some(target) => format!("{0}::{0}::{1}", package, target),
+            None => format!("{0}::{0}", package),
+        };
+        let mut summary = std::mem::take(self.package(package));
+        let mut running: Option<(String, &'static TestDef)> = None;
+        let mut ended = false;
+        for line in events.lines().map(str::trim) {
+            // Tolerate what else was printed to the same stream.
+            if !line.starts_with('{') {
+                continue;
+            }
+            let Ok(event) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            let name = event["name"].as_str();
+            match (event["type"].as_str(), event["event"].as_str(), name) {
+                (Some("suite"), Some("ok" | "failed"), _) => {
+                    for summary in [&mut summary, &mut self.summary] {
+                        summary.filtered_out += count(&event["filtered_out"]);
+                        summary.not_run += count(&event["not_run"]);
+                    }
+                    summary.duration += seconds(&event["exec_time"]);
+                    ended = true;
+                }
+                (Some("test"), Some("started"), Some(name)) => {
+                    running = Some((name.to_string(), test(&prefix, name)));
+                }
+                (Some("test"), Some(_), Some(name)) => {
+                    let test = match running.take() {
+                        Some((started, test)) if started == name => test,
+                        _ => test(&prefix, name),
+                    };
+                    self.record(&mut summary, &event, outcome(test, &event));
+                }
+                _ => {}
+            }
+        }
+        if let Some((_, test)) = running {
+            let message = "the test binary exited while the test was running".to_string();
+            let outcome = runner::failed(test, message, Duration::ZERO);
+            self.record(&mut summary, &Value::Null, outcome);
+        }
+        if !ended {
+            self.incomplete += 1;
+        }
+        *self.package(package) = summary;
+        ended
+    }
+
+    /// The summary of `package`, added if new.
+    fn package(&mut self, package: &str) -> &mut RunSummary {
+        let index = match self.packages.iter().position(|(name, _)| name == package) {
+            Some(index) => index,
+            None => {
+                self.packages
+                    .push((package.to_string(), RunSummary::default()));
+                self.packages.len() - 1
+            }
+        };
+        &mut self.packages[index].1
+    }
+
+    fn record(&mut self, package: &mut RunSummary, event: &Value, outcome: TestOutcome) {
+        // The quarantine is the binary's, and the event says whether the
+        // test is in it.
+        let quarantined = outcome.status.is_failure() && event["quarantined"] == true;
+        for summary in [&mut *package, &mut self.summary] {
+            if quarantined {
+                summary.quarantined += 1;
+            } else {
+                summary.record(&outcome);
+            }
+        }
+        self.tests.push(outcome.test);
+        self.outcomes.push(outcome);
+    }
+
+    /// Reports the merged run, which took `duration`, as `args` asks, and
+    /// returns its exit code.
+    pub fn report(mut self, args: &Args, duration: Duration) -> ExitCode {
+        let mut reporter = Reporters(runner::reporters(args));
+        reporter.on_run_start(&self.tests);
+        for outcome in &self.outcomes {
+            reporter.on_progress(outcome.test);
+            reporter.on_test_start(outcome.test);
+            reporter.on_test_finish(outcome);
+        }
+        self.summary.duration = duration;
+        reporter.on_run_end(&self.outcomes, &self.summary);
+        if matches!(
+            args.format,
+            OutputFormat::Tree | OutputFormat::Pretty | OutputFormat::Plain
+        ) && !args.quiet
+        {
+            self.print_packages();
+        }
+        if self.incomplete == 0 && self.summary.is_success() {
+            ExitCode::from(EXIT_SUCCESS)
+        } else {
+            ExitCode::from(EXIT_TESTS_FAILED)
+        }
+    }
+
+    fn print_packages(&self) {
+        let width = self
+            .packages
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let mut out = Stdout;
+        let _ = writeln!(out, "packages:");
+        for (name, summary) in &self.packages {
+            let _ = writeln!(
+                out,
+                "  {:width$}  {:>4} passed; {} failed; {} skipped  {:>8.3}s",
+                name,
+                summary.passed,
+                summary.failed,
+                summary.skipped,
+                summary.duration.as_secs_f64(),
+                width = width
+            );
+        }
+        let _ = out.flush();
+    }
+}
+
+/// The test named `name` by a binary whose tests' module paths start with
+/// `prefix`.
+fn test(prefix: &str, name: &str) -> &'static TestDef {
+    let (module, name) = match name.rsplit_once("::") {
+        Some((module, name)) => (format!("{}::{}", prefix, module), name),
+        None => (prefix.to_string(), name),
+    };
+    // Tests live as long as the process, like registered ones do.
+    let leak = |text: &str| -> &'static str { Box::leak(text.to_string().into_boxed_str()) };
+    Box::leak(Box::new(TestDef::ran_elsewhere(
+        leak(&module),
+        leak(name),
+        "",
+        0,
+    )))
+}
+
+/// The outcome of `test` the JSON reporter's `event` describes.
+fn outcome(test: &'static TestDef, event: &Value) -> TestOutcome {
+    let reason = event["message"].as_str().map(str::to_string);
+    let mut output = event["stdout"].as_str().unwrap_or_default().to_string();
+    let status = match event["event"].as_str() {
+        Some("failed") => {
+            let failures: Vec<TestFailure> = event["failures"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(failure)
+                .collect();
+            // The JSON reporter follows the output with the failures.
+            let report: Vec<String> = failures.iter().map(ToString::to_string).collect();
+            if let Some(before) = output.strip_suffix(&format!("{}\n", report.join("\n\n"))) {
+                output = before.to_string();
+            }
+            if let Some(command) = event["rerun"].as_str() {
+                rerun::record(test, command.to_string());
+            }
+            TestStatus::Failed(failures)
+        }
+        Some("ignored") if event["skipped_at_run_time"] == true => {
+            outcome::request_skip(test, reason.unwrap_or_default());
+            outcome::status_of(test, Vec::new())
+        }
+        Some("ignored") => TestStatus::Skipped(reason),
+        _ if event["xfail"] == true => TestStatus::ExpectedFailure(reason),
+        _ => TestStatus::Passed,
+    };
+    context::record_json(test, &event["attachments"]);
+    context::record_steps_json(test, &event["steps"]);
+    let skipped = matches!(status, TestStatus::Skipped(_));
+    TestOutcome {
+        test,
+        status,
+        duration: seconds(&event["exec_time"]),
+        output: output.trim_end().to_string(),
+        attempts: match event["attempts"].as_u64() {
+            Some(attempts) => u32::try_from(attempts).unwrap_or(u32::MAX),
+            None => u32::from(!skipped),
+        },
+        attachments: context::take_attachments(test),
+        steps: context::take_steps(test),
+    }
+}
+
+/// A failure in the `failures` of a failed test's event.
+fn failure(failure: &Value) -> TestFailure {
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    let location = failure["file"].as_str().map(|file| Location {
+        file: file.to_string(),
+        line: count(&failure["line"]) as u32,
+        column: count(&failure["column"]) as u32,
+    });
+    let diff = match &failure["diff"] {
+        Value::Null => None,
+        diff => Some(Box::new(FailureDiff {
+            labels: (text(&diff["labels"][0]), text(&diff["labels"][1])),
+            left: text(&diff["left"]),
+            right: text(&diff["right"]),
+        })),
+    };
+    TestFailure {
+        message: text(&failure["message"]),
+        diff,
+        location,
+        metadata: failure["metadata"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|entry| (text(&entry[0]), text(&entry[1])))
+            .collect(),
+        backtrace: None,
+        panicked: false,
+        kind: failure["kind"]
+            .as_str()
+            .and_then(FailureKind::from_name)
+            .unwrap_or_default(),
+    }
+}
+
+fn count(value: &Value) -> usize {
+    value
+        .as_u64()
+        .and_then(|count| usize::try_from(count).ok())
+        .unwrap_or(0)
+}
+
+fn seconds(value: &Value) -> Duration {
+    value
+        .as_f64()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn artifact(manifest: &str, name: &str, kind: &str, test: bool, executable: &str) -> String {
+        json!({
+            "reason": "compiler-artifact",
+            "manifest_path": manifest,
+            "target": { "name": name, "kind": [kind] },
+            "profile": { "test": test },
+            "executable": executable,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn discovers_the_test_binaries_of_each_member() {
+        let metadata = json!({
+            "packages": [
+                { "name": "parser", "manifest_path": "/ws/parser/Cargo.toml" },
+                { "name": "store-db", "manifest_path": "/ws/storage/Cargo.toml" },
+                { "manifest_path": "/ws/broken/Cargo.toml" },
+            ],
+        });
+        let packages = package_names(&metadata);
+        assert_eq!(packages.len(), 2);
+
+        let messages = [
+            r#"{"reason":"build-script-executed"}"#.to_string(),
+            "   Compiling parser v0.1.0".to_string(),
+            artifact(
+                "/ws/storage/Cargo.toml",
+                "store_db",
+                "lib",
+                true,
+                "/t/store_db-1",
+            ),
+            artifact(
+                "/ws/parser/Cargo.toml",
+                "parser",
+                "lib",
+                true,
+                "/t/parser-1",
+            ),
+            artifact(
+                "/ws/parser/Cargo.toml",
+                "integration",
+                "test",
+                true,
+                "/t/integration-1",
+            ),
+            // Built twice, as for two features.
+            artifact(
+                "/ws/parser/Cargo.toml",
+                "integration",
+                "test",
+                true,
+                "/t/integration-1",
+            ),
+            // Not a test.
+            artifact("/ws/parser/Cargo.toml", "parser", "lib", false, "/t/parser"),
+            // Not a member.
+            artifact(
+                "/elsewhere/Cargo.toml",
+                "helper",
+                "test",
+                true,
+                "/t/helper-1",
+            ),
+        ]
+        .join("\n");
+        let binaries: Vec<(String, PathBuf, PathBuf)> = test_binaries(&messages, &packages)
+            .into_iter()
+            .map(|binary| (binary.label(), binary.executable, binary.package_dir))
+            .collect();
+        let binary = |label: &str, executable: &str, dir: &str| {
+            (
+                label.to_string(),
+                PathBuf::from(executable),
+                PathBuf::from(dir),
+            )
+        };
+        assert_eq!(
+            binaries,
+            [
+                binary("helper (helper)", "/t/helper-1", "/elsewhere"),
+                binary("parser (integration)", "/t/integration-1", "/ws/parser"),
+                binary("parser (lib)", "/t/parser-1", "/ws/parser"),
+                binary("store-db (lib)", "/t/store_db-1", "/ws/storage"),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_the_runs_of_each_package() {
+        let mut merge = Merge::new();
+        let lib = concat!(
+            "running 3 tests\n",
+            r#"{ "type": "suite", "event": "started", "test_count": 3 }"#,
+            "\n",
+            r#"{ "type": "test", "event": "started", "name": "lexer::empty" }"#,
+            "\n",
+            r#"{ "type": "test", "event": "ok", "name": "lexer::empty", "exec_time": 0.5 }"#,
+            "\n",
+            r#"{ "type": "test", "event": "ignored", "name": "slow", "message": "slow" }"#,
+            "\n",
+            r#"{ "type": "test", "event": "failed", "name": "flaky", "quarantined": true,
+                "failures": [{ "message": "boom" }] }"#,
+            "\n",
+            r#"{ "type": "suite", "event": "ok", "filtered_out": 2, "exec_time": 0.75 }"#,
+        );
+        assert!(merge.add("parser", None, &lib.replace(",\n", ", ")));
+        let integration = concat!(
+            r#"{ "type": "suite", "event": "started", "test_count": 2 }"#,
+            "\n",
+            r#"{ "type": "test", "event": "failed", "name": "parses", "exec_time": 0.25,
+                "stdout": "reading\nboom\n  at tests/a.rs:4:9\n",
+                "failures": [{ "message": "boom", "file": "tests/a.rs",
+                "line": 4, "column": 9 }] }"#,
+            "\n",
+            r#"{ "type": "test", "event": "started", "name": "crashes" }"#,
+        );
+        assert!(!merge.add(
+            "parser",
+            Some("integration"),
+            &integration.replace(",\n", ", ")
+        ));
+
+        let names: Vec<String> = merge.tests.iter().map(|test| test.path()).collect();
+        assert_eq!(
+            names,
+            [
+                "parser::lexer::empty",
+                "parser::slow",
+                "parser::flaky",
+                "parser::integration::parses",
+                "parser::integration::crashes",
+            ]
+        );
+        assert_eq!(merge.tests[0].module_path, "parser::parser::lexer");
+        let parses = &merge.outcomes[3];
+        assert_eq!(parses.output, "reading");
+        assert_eq!(parses.duration, Duration::from_millis(250));
+        let TestStatus::Failed(failures) = &parses.status else {
+            panic!("{:?}", parses.status);
+        };
+        assert_eq!(failures[0].to_string(), "boom\n  at tests/a.rs:4:9");
+        let TestStatus::Failed(failures) = &merge.outcomes[4].status else {
+            panic!("{:?}", merge.outcomes[4].status);
+        };
+        assert_eq!(
+            failures[0].message,
+            "the test binary exited while the test was running"
+        );
+        assert_eq!(
+            merge.outcomes[1].status,
+            TestStatus::Skipped(Some("slow".into()))
+        );
+        assert_eq!(merge.outcomes[1].attempts, 0);
+
+        let summary = &merge.summary;
+        assert_eq!(
+            (
+                summary.passed,
+                summary.failed,
+                summary.quarantined,
+                summary.skipped
+            ),
+            (1, 2, 1, 1)
+        );
+        assert_eq!(summary.filtered_out, 2);
+        assert_eq!((merge.binaries, merge.incomplete), (2, 1));
+        let (name, package) = &merge.packages[0];
+        assert_eq!(name, "parser");
+        assert_eq!(merge.packages.len(), 1);
+        assert_eq!(package.duration, Duration::from_millis(750));
+        assert_eq!((package.passed, package.failed), (1, 2));
+    }
+}
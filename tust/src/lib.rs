@@ -1,19 +1,1629 @@
-// tust - A modern, batteries-included test framework for Rust
-//
-// This is the main entry point for the tust test framework.
-// It re-exports all macros, runtime utilities, and assertions.
+//! tust - A modern, batteries-included test framework for Rust.
+//!
+//! This is the main entry point for the tust test framework. It re-exports
+//! all macros, runtime utilities, and assertions.
+//!
+//! # Writing tests
+//!
+//! ```
+//! #[tust::test]
+//! fn addition() {
+//!     assert_eq!(2 + 2, 4);
+//! }
+//! ```
+//!
+//! Every `#[tust::test]` is registered in a link-time registry that the tust
+//! runtime uses to discover tests. The attribute also emits a regular
+//! `#[test]` wrapper, so tests keep working under `cargo test` with libtest.
+//!
+//! # Assertions
+//!
+//! [`expect_that!`], [`check!`], [`check_eq!`], and [`check_ne!`] replace
+//! `assert!` and `assert_eq!`; on failure they print a colored, line-by-line
+//! diff of the pretty-printed values instead of one flat line per value.
+//! Every failure shows the assertion as written and the file and line it is
+//! on, and `expect_that!(a < b)` and `check!(a >= b)`, like `==` and `!=`,
+//! show the value of each operand, so messages rarely need to repeat them:
+//!
+//! ```text
+//! assertion `queue.len() <= limit` failed
+//!   left: 12
+//!  right: 10
+//!   at tests/queue.rs:31:5
+//! ```
+//!
+//! `expect_that!` ends the test when it fails. The `check` family is soft: a
+//! failed check is recorded and the test keeps running, and every failure is
+//! reported together once the test finishes.
+//!
+//! [`assert_ok!`], [`assert_err!`], [`assert_some!`], and [`assert_none!`]
+//! unwrap a `Result` or `Option` of the expected variant, or end the test
+//! with what it held instead. [`assert_matches!`] checks a value against a
+//! pattern, and [`assert_panics!`] that a closure panics, optionally with
+//! `contains = "..."` to check the message. [`assert_approx_eq!`] compares
+//! floats, and slices and arrays of them, within an `epsilon`, a `relative`
+//! tolerance, or a number of `ulps`; other containers can implement
+//! [`approx::ApproxEq`].
+//!
+//! [`assert_same_elements!`] compares collections as multisets, ignoring
+//! order, and [`assert_contains_all!`] checks that one holds every element of
+//! another; both name the missing and extra elements on failure.
+//! [`assert_sorted!`] and [`assert_sorted_by!`] check order, and
+//! [`assert_map_eq!`] compares maps of any kind, listing the missing, extra,
+//! and changed entries. [`assert_all!`] and [`assert_any!`] check a
+//! predicate lazily over any iterator, and name the index and value of the
+//! first element that fails it:
+//!
+//! ```
+//! # use tust::prelude::*;
+//! assert_all!((0..u32::MAX).take_while(|n| *n < 1000), |n| *n < 1000);
+//! assert_any!(std::iter::successors(Some(1u64), |n| Some(n * 3)), |n| *n > 1_000_000);
+//! ```
+//!
+//! [`assert_str_eq!`] points at the line and column where two strings first
+//! differ, with a diff for multiline strings; [`assert_contains!`],
+//! [`assert_starts_with!`], [`assert_ends_with!`], and
+//! [`assert_matches_regex!`] check parts of a string. The options
+//! `normalize_whitespace` and `ignore_case` relax the comparison:
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! let page = "<h1>Welcome</h1>\n  <p>Hello,   world</p>\n";
+//! assert_contains!(page, "hello, world", normalize_whitespace, ignore_case);
+//! assert_str_eq!(page, "<h1>Welcome</h1>\n<p>Hello, world</p>", normalize_whitespace);
+//! ```
+//!
+//! [`expect!`] checks a value against composable [`matchers`], which the
+//! [`prelude`] brings into scope:
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! expect!(vec![3, 1, 2]).to(both(contain(3), have_len(3)));
+//! expect!("tust.toml").to(end_with(".toml"));
+//! ```
+//!
+//! [`assert_ready!`] and [`assert_pending!`] poll a future once and check
+//! whether it is ready, without an executor; in `async` tests,
+//! [`assert_stream_yields!`] checks the items of a stream, and
+//! [`assert_completes_within!`] that a future completes in time:
+//!
+//! ```
+//! use std::time::Duration;
+//! use tust::prelude::*;
+//!
+//! # async fn ping() -> &'static str { "pong" }
+//! #[tust::test]
+//! async fn answers_quickly() {
+//!     let reply = assert_completes_within!(Duration::from_millis(200), ping());
+//!     assert_eq!(reply, "pong");
+//! }
+//! ```
+//!
+//! [`expect_messages!`] receives messages from a channel, waiting up to a
+//! second for them unless given `timeout = ...`, and checks them in order,
+//! or in any order after `unordered:`. It takes `std::sync::mpsc` receivers,
+//! and with the `tokio` feature Tokio's `mpsc` and `broadcast` ones:
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn workers_report_back() {
+//!     let (sender, mut receiver) = std::sync::mpsc::channel();
+//!     for worker in 0..3 {
+//!         let sender = sender.clone();
+//!         std::thread::spawn(move || sender.send(worker).unwrap());
+//!     }
+//!     expect_messages!(receiver, unordered: [0, 1, 2]);
+//! }
+//! ```
+//!
+//! [`assert_json_eq!`] compares anything `Serialize` as JSON, regardless of
+//! key order, and lists each difference by its path; [`assert_json_includes!`]
+//! accepts extra fields. `ignore = ["$.timestamp", "$..id"]` leaves volatile
+//! values out of the comparison:
+//!
+//! ```
+//! use tust::json::json;
+//! use tust::prelude::*;
+//!
+//! let order = json!({"id": "ord_91", "total": 25, "lines": [{"sku": "A1", "qty": 2}]});
+//! assert_json_includes!(order, json!({"total": 25, "lines": [{"sku": "A1"}]}));
+//! assert_json_eq!(order, json!({"id": "", "total": 25.0, "lines": [{"qty": 2, "sku": "A1"}]}),
+//!     ignore = ["$.id"]);
+//! ```
+//!
+//! [`assert_no_alloc!`] checks that a closure does not touch the heap, and
+//! [`assert_allocates_at_most!`] that it allocates at most so many bytes,
+//! to keep hot paths from growing allocations unnoticed. Both count the
+//! allocations of the calling thread through the tracking allocator of
+//! [`alloc`], which the test target installs:
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[global_allocator]
+//! static ALLOC: tust::alloc::TrackingAllocator = tust::alloc::TrackingAllocator::new();
+//!
+//! # fn main() {
+//! let mut buffer = String::with_capacity(64);
+//! assert_no_alloc!(|| buffer.push_str("fits in the buffer"));
+//! let words: Vec<&str> = assert_allocates_at_most!(256, || buffer.split(' ').collect());
+//! # assert_eq!(words.len(), 4);
+//! # }
+//! ```
+//!
+//! With the allocator installed, the runner's `--leak-check` (or
+//! `leak-check = true` in `tust.toml`) also fails every test that leaves
+//! memory allocated once its hooks have run and its fixtures are dropped.
+//! `#[allow_leaks]` below `#[tust::test]` exempts a test that keeps memory
+//! on purpose, say in a cache; see [`alloc`] for what counts as a leak.
+//!
+//! Assertions specific to a domain, say `assert_valid_invoice`, get the same
+//! failure format as the built-in ones: implement [`Assertion`] and run it
+//! with [`assert_that!`] or the soft [`check_that!`], or build the message
+//! with a [`FailureBuilder`] from a macro of your own. See [`assertion`].
+//!
+//! Assertions fail with a [`TestFailure`] rather than a panic message: its
+//! message, the compared values, its location, and metadata such as a seed
+//! stay apart, so reporters and `--format json` receive the values to diff,
+//! and soft failures of one test are all reported. Code of your own can fail
+//! the same way with [`TestFailure::raise`] or [`soft::record`].
+//!
+//! # Snapshots
+//!
+//! [`assert_snapshot!`] compares a value's `Display` output (and
+//! [`assert_debug_snapshot!`] its `{:#?}` output) against a file in
+//! `tests/snapshots/`, named after the test. New and changed values fail the
+//! test and are written to `.snap.new` files for review; the runner lists
+//! them at the end of the run. Set `TUST_UPDATE_SNAPSHOTS=1` to accept them.
+//!
+//! [`assert_binary_snapshot!`] snapshots bytes, and [`assert_image_snapshot!`]
+//! PNG images, which match if their pixels do, within a `threshold` of
+//! color difference and a `max_diff_pixels` count of pixels beyond it. An
+//! image that does not match also gets a diff image, attached to the test,
+//! with the differing pixels in red.
+//!
+//! ```no_run
+//! # use tust::prelude::*;
+//! # fn screenshot() -> Vec<u8> { Vec::new() }
+//! assert_image_snapshot!("home_page.png", screenshot(), max_diff_pixels = 20);
+//! ```
+//!
+//! [`assert_matches_file!`] compares a value against a checked-in golden file
+//! at a path of the test's choosing, relative to the crate's manifest
+//! directory. Set `TUST_UPDATE_GOLDEN=1` to rewrite the golden files of
+//! failing assertions; the runner lists the files it rewrote.
+//!
+//! ```no_run
+//! # use tust::prelude::*;
+//! # fn render() -> String { String::new() }
+//! assert_matches_file!(render(), "tests/golden/output.txt");
+//! ```
+//!
+//! # Parameterized tests
+//!
+//! Each `#[case(...)]` attribute placed below `#[tust::test]` registers a
+//! separate test whose name embeds the case values, such as
+//! `adds::case_1_1_2_3`, with `neg` for the sign of a negative number, as in
+//! `adds::case_2_neg1_1_0`. Case values bind to the leading parameters; use
+//! `#[case::name(...)]` to name a case explicitly.
+//!
+//! ```
+//! #[tust::test]
+//! #[case(1, 2, 3)]
+//! #[case(-1, 1, 0)]
+//! #[case::zero(0, 0, 0)]
+//! fn adds(a: i32, b: i32, expected: i32) {
+//!     assert_eq!(a + b, expected);
+//! }
+//! ```
+//!
+//! `#[values(...)]` lists values for parameters by name instead, and
+//! registers a case for every combination of them, such as
+//! `renders::case_1_width_40_theme_light`. Several `#[values(...)]`
+//! attributes combine into one matrix, as do the lists of one, and each
+//! `#[exclude(...)]` leaves out the combinations with all of the values it
+//! names.
+//!
+//! ```
+//! # fn render(_: u32, _: &str) -> String { String::from("x") }
+//! #[tust::test]
+//! #[values(width = [40, 80], theme = ["light", "dark"])]
+//! #[exclude(width = 40, theme = "dark")]
+//! fn renders(width: u32, theme: &str) {
+//!     assert!(!render(width, theme).is_empty());
+//! }
+//! ```
+//!
+//! `#[tust::test(cases_from = "tests/data/cases.yaml")]` reads the cases from
+//! a JSON, YAML, or CSV file instead, relative to the crate's manifest
+//! directory, so that cases can be added without touching Rust code. Each
+//! row is a case, named after its `name` field or its values, deserialized
+//! into the test's first parameter, which must implement serde's
+//! `Deserialize`; see [`data`].
+//!
+//! ```ignore
+//! #[derive(serde::Deserialize)]
+//! struct Parse {
+//!     input: String,
+//!     expected: u32,
+//! }
+//!
+//! #[tust::test(cases_from = "tests/data/parse.csv")]
+//! fn parses(case: Parse) {
+//!     assert_eq!(case.input.parse::<u32>().unwrap(), case.expected);
+//! }
+//! ```
+//!
+//! # Property-based tests
+//!
+//! `#[tust::property]` runs a test against generated values of its
+//! parameters, which must implement [`Arbitrary`](property::Arbitrary):
+//! integers, floats, `bool`, `char`, `String`, and `Vec`s, `Option`s, `Box`es,
+//! and tuples of such types do. A failing case is shrunk to a minimal
+//! counterexample, reported along with the seed that reproduces the run when
+//! set in `TUST_PROPERTY_SEED`.
+//!
+//! ```
+//! #[tust::property(cases = 500)]
+//! fn reversing_twice_is_identity(values: Vec<u32>) {
+//!     let mut reversed = values.clone();
+//!     reversed.reverse();
+//!     reversed.reverse();
+//!     assert_eq!(reversed, values);
+//! }
+//! ```
+//!
+//! Properties run 100 cases unless `cases = N` or `TUST_PROPERTY_CASES` says
+//! otherwise, and `seed = N` fixes the seed. The helper attributes of
+//! `#[tust::test]`, such as `#[timeout(ms)]` or `#[skip]`, go below
+//! `#[tust::property]`.
+//!
+//! # Fuzz targets
+//!
+//! `#[tust::fuzz]` tests a function taking `&[u8]`, or a value of an
+//! `Arbitrary` type generated from bytes, against many inputs. Every run
+//! replays the inputs saved in `fuzz/corpus/<target>` and
+//! `fuzz/artifacts/<target>`, then tries mutations of them for a budget of
+//! 10,000 inputs or one second, whichever ends first. An input the target
+//! fails on is shrunk and saved to `fuzz/artifacts/<target>`, so every later
+//! run replays it until it is removed.
+//!
+//! ```
+//! #[tust::fuzz(iterations = 1000, time = 500)]
+//! fn parses_without_panicking(data: &[u8]) {
+//!     let _ = std::str::from_utf8(data);
+//! }
+//! ```
+//!
+//! `iterations = N` and `time = MILLISECONDS` set the budget, and
+//! `TUST_FUZZ_ITERATIONS` and `TUST_FUZZ_TIME` set it for targets that do
+//! not; `corpus = "dir"` reads the inputs from another directory. The
+//! directories are those of cargo-fuzz, and `--export-fuzz fuzz` writes each
+//! target as a cargo-fuzz target, for coverage-guided fuzzing that grows the
+//! corpus every run then replays. See [`fuzz`](mod@fuzz) for the details.
+//!
+//! # Benchmarks
+//!
+//! `#[tust::bench]` registers a test whose body is measured. Run normally,
+//! the body runs once, like any test; with `--bench` (which `cargo bench`
+//! passes) or `TUST_BENCH=1`, it is warmed up and then timed over many
+//! iterations, and the runner reports the median, mean, and 95th percentile
+//! time per iteration, with the samples that lie far from the rest counted as
+//! outliers:
+//!
+//! ```
+//! #[tust::bench(warmup = 500, samples = 100, threshold = 5)]
+//! fn sort_10k() {
+//!     let mut values: Vec<u32> = (0..10_000).rev().collect();
+//!     values.sort();
+//! }
+//! ```
+//!
+//! ```text
+//! benchmarks:
+//!     sort_10k  median 8.31µs  mean 8.40µs ± 210ns  p95 8.92µs  3 outliers  +1.2%
+//! ```
+//!
+//! The first measurement is saved as the benchmark's baseline in
+//! `target/tust/bench/` (`TUST_BENCH_DIR` moves it), and later runs fail a
+//! benchmark whose median is slower than its baseline's by more than its
+//! `threshold` percentage: 10% unless set, or `TUST_BENCH_THRESHOLD`
+//! overrides the default. Run with `TUST_UPDATE_BENCHMARKS=1` to accept the
+//! new times as baselines. Benchmarks run serially and are tagged `bench`, so
+//! `--skip-tag bench` leaves them out; parameters are fixtures, set up once
+//! rather than per iteration. `--format json` reports each measurement as a
+//! `"type": "bench"` event.
+//!
+//! # Randomness
+//!
+//! [`rng()`] gives a test a random number generator seeded from the run's
+//! seed and the test's name. When a test asked for randomness, the runner
+//! prints the seed at the end of the run, and `--seed N` (or `TUST_SEED=N`,
+//! for libtest) replays the run with the same values. Property tests draw
+//! their inputs from the same seed.
+//!
+//! ```
+//! #[tust::test]
+//! fn sorts_shuffled_input() {
+//!     let mut values: Vec<u32> = (0..100).collect();
+//!     tust::rng().shuffle(&mut values);
+//!     values.sort();
+//!     assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+//! }
+//! ```
+//!
+//! `#[derive(TestBuilder)]` generates a builder for a struct, its name
+//! followed by `Builder`, with a setter named after each field. `build()`
+//! gives the fields the test did not set [`Arbitrary`](property::Arbitrary)
+//! values drawn from the test's generator, so a test sets only the fields it
+//! is about, and a failure from the values it did not set replays with the
+//! run's seed. A field of a type without an `Arbitrary` implementation takes
+//! its value from `#[test_builder(default = ...)]`, which can also fix the
+//! value of any other field.
+//!
+//! ```
+//! use tust::TestBuilder;
+//!
+//! #[derive(TestBuilder)]
+//! struct Order {
+//!     id: u64,
+//!     customer: String,
+//!     quantity: u32,
+//!     #[test_builder(default = vec!["gift-wrap".to_string()])]
+//!     notes: Vec<String>,
+//! }
+//!
+//! #[tust::test]
+//! fn large_orders_are_flagged() {
+//!     let order = OrderBuilder::new().quantity(1_000).build();
+//!     assert!(order.quantity >= 100);
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! # Fixtures
+//!
+//! Test parameters not bound by a case are injected by fixtures: functions
+//! annotated with [`fixture`] whose name matches the parameter name.
+//! Fixtures can depend on other fixtures the same way.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[fixture]
+//! fn base() -> u32 {
+//!     40
+//! }
+//!
+//! #[fixture]
+//! fn answer(base: u32) -> u32 {
+//!     base + 2
+//! }
+//!
+//! #[tust::test]
+//! fn uses_answer(answer: u32) {
+//!     assert_eq!(answer, 42);
+//! }
+//! ```
+//!
+//! A fixture can be an `async fn`: its future is run to completion on the
+//! executor an async test without executor options uses, before the test
+//! starts, and the test receives its output.
+//!
+//! By default each parameter receives a freshly created value. Fixtures
+//! declared with `#[fixture(scope = "session")]` are created once per run,
+//! cloned into every test that requests them, and dropped when the run ends;
+//! their type must be `Clone + Send`. With `--process-per-test`, each test's
+//! process creates the session fixtures it asks for, once for that test.
+//!
+//! A data set too large to load for every test, such as a model's weights
+//! or a corpus, is returned from a session fixture as a [`SharedData`]: it is
+//! loaded once, by the first test that asks for it, and shared read-only by
+//! every test and worker thread, cloning only a reference; with
+//! `--process-per-test`, each test's process loads it again. `SharedData::map`
+//! maps a file into memory rather than reading it, on Unix, as a
+//! [`MappedFile`] of its bytes. The runner lists each load with its time after
+//! the run, so that it is not mistaken for the first test being slow.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[fixture(scope = "session")]
+//! fn vocabulary() -> SharedData<Vec<String>> {
+//!     SharedData::load("vocabulary", || {
+//!         (0..100_000).map(|index| format!("word{}", index)).collect()
+//!     })
+//! }
+//!
+//! #[tust::test]
+//! fn knows_common_words(vocabulary: SharedData<Vec<String>>) {
+//!     assert!(vocabulary.iter().any(|word| word == "word42"));
+//! }
+//! ```
+//!
+//! Seven fixtures are built in. A `temp_dir: TempDir` parameter receives a
+//! fresh, empty directory and a `temp_file: TempFile` parameter a fresh,
+//! empty file, both removed when the test ends. Set `TUST_KEEP_TEMP=1` to
+//! keep those of failing tests for inspection; their paths are printed.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn writes_a_config(temp_dir: TempDir) {
+//!     let path = temp_dir.join("tust.toml");
+//!     std::fs::write(&path, "jobs = 4").unwrap();
+//!     assert!(path.exists());
+//! }
+//! ```
+//!
+//! A `sandbox: FsSandbox` parameter receives a fresh directory too, which the
+//! test lays out with `file(path, contents)` and `dir(path)`, and checks with
+//! `assert_tree`, printing a diff of the trees when they differ. Its
+//! `chdir()` makes it the current directory until the guard it returns is
+//! dropped, one test at a time; see [`sandbox`].
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn moves_the_config(sandbox: FsSandbox) {
+//!     sandbox.file("config/app.toml", "jobs = 4").dir("out");
+//!     std::fs::rename(sandbox.join("config/app.toml"), sandbox.join("out/app.toml")).unwrap();
+//!     sandbox.assert_tree(
+//!         "
+//!         config/
+//!         out/
+//!           app.toml
+//!         ",
+//!     );
+//! }
+//! ```
+//!
+//! Tests that write to relative paths can instead ask for a working
+//! directory of their own: `#[tust::test(isolated_cwd)]` runs the test, hooks
+//! included, in a fresh directory removed when it ends, and the runner's
+//! `--isolated-cwd` (or `isolated-cwd = true` in `tust.toml`) does so for
+//! every test. Such tests take turns unless `--process-per-test` runs each in
+//! a process of its own; see [`sandbox`].
+//!
+//! ```
+//! #[tust::test(isolated_cwd)]
+//! fn writes_relative_paths() {
+//!     std::fs::write("report.txt", "ok").unwrap();
+//!     assert_eq!(std::fs::read_to_string("report.txt").unwrap(), "ok");
+//! }
+//! ```
+//!
+//! An `http_server: MockHttpServer` parameter receives a local HTTP server
+//! for the test. The test tells it which requests to expect and how to answer
+//! them, and hands its [`url`](MockHttpServer::url) to the code under test;
+//! expected requests that never arrive, and requests nobody expected, fail
+//! the test when it ends. See [`http`] for the details.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn fetches_the_user(http_server: MockHttpServer) {
+//!     http_server
+//!         .expect("GET", "/users/1")
+//!         .respond_with(Response::json(r#"{"name": "Ada"}"#));
+//!     // Point the client under test at `http_server.url()` ...
+//! }
+//! ```
+//!
+//! For clients of other protocols, a `socket_server: MockSocketServer`
+//! parameter receives a local TCP server, and
+//! [`MockSocketServer::start_unix`] starts one on a Unix socket. The test
+//! scripts each connection: the frames the client must send, raw bytes,
+//! delimited, or length-prefixed, and those to send back. A frame that
+//! differs, one that does not arrive within the timeout, and a connection
+//! that is missing or unexpected fail the test, showing what was sent each
+//! way; see [`socket`].
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn greets(socket_server: MockSocketServer) {
+//!     socket_server
+//!         .connection()
+//!         .framing(Framing::Delimited(b'\n'))
+//!         .expect("HELLO")
+//!         .send("WELCOME");
+//!     // Point the client under test at `socket_server.addr()` ...
+//! }
+//! ```
+//!
+//! A `clock: Clock` parameter receives a clock for code that reads the time
+//! through it instead of `Instant::now()`. It runs with real time until the
+//! test calls `freeze()`, and then moves only by `advance(duration)`, so tests
+//! of timeouts and expiry finish without waiting. With the `tokio` feature,
+//! freezing it also pauses Tokio's time; see [`clock`].
+//!
+//! An `env: Env` parameter sets and removes environment variables for the
+//! test, and puts back their original values when it ends. For variables
+//! known up front, `#[with_env("KEY" = value, ...)]` below `#[tust::test]`
+//! sets them before any fixture is created. The environment belongs to the
+//! whole process, so tests doing either run [serially](#running-tests-with-the-tust-runner), as
+//! do tests whose fixtures take an `Env`.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! #[with_env("LOG_LEVEL" = "debug")]
+//! fn reads_the_log_level(mut env: Env) {
+//!     env.remove("NO_COLOR");
+//!     assert_eq!(std::env::var("LOG_LEVEL").unwrap(), "debug");
+//! }
+//! ```
+//!
+//! `#[with_locale("de_DE.UTF-8", tz = "Europe/Berlin")]` pins the locale and
+//! the time zone the same way, so that tests of formatted dates and numbers
+//! do not depend on the machine: it sets `LC_ALL` and `LANG` to the locale,
+//! and `TZ` to the time zone, either of which can be left out. It serves
+//! code that reads them from the environment, as most date, time zone, and
+//! locale libraries do.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! #[with_locale("de_DE.UTF-8", tz = "Europe/Berlin")]
+//! fn formats_dates_for_germany() {
+//!     assert_eq!(std::env::var("TZ").unwrap(), "Europe/Berlin");
+//!     assert_eq!(std::env::var("LC_ALL").unwrap(), "de_DE.UTF-8");
+//! }
+//! ```
+//!
+//! A `ctx: TestContext` parameter receives the context of the test: its
+//! name and tags, an artifact directory kept after the run, a scratch
+//! directory removed when the test ends, and a seeded [`Rng`]. Its
+//! `add_cleanup(f)` runs `f` when the test ends, before the `after_each`
+//! hooks, so that helper libraries can undo what they set up without a
+//! fixture; they reach the context with [`TestContext::current()`]. Its
+//! `attach(name, bytes)` and `attach_file(path)` put files such as
+//! screenshots in the artifact directory and link them from the test's
+//! entry in the HTML, JUnit, and JSON reports. Its `step(name, f)` and
+//! `step_async(name, future)` time named phases of the test, listed below it
+//! in the console and the reports, and a failure during a step names the
+//! step in its metadata. See [`context`].
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test(tags("io"))]
+//! fn writes_a_report(ctx: TestContext) {
+//!     let report = ctx.artifact_dir().join("report.txt");
+//!     ctx.step("write", || std::fs::write(&report, ctx.name()).unwrap());
+//!     let marker = ctx.scratch_dir().join("running");
+//!     std::fs::write(&marker, "").unwrap();
+//!     ctx.add_cleanup(move || assert!(marker.exists()));
+//!     assert_eq!(ctx.tags(), ["io"]);
+//! }
+//! ```
+//!
+//! Code that looks its services up with
+//! [`overrides::resolve_or_else`] gets the replacement a test registered
+//! with [`overrides::register`], keyed by the type it stands in for, and its
+//! own otherwise. Replacements belong to the test that registered them and
+//! are dropped when it ends, so fakes swap in without global state to reset.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use tust::overrides;
+//!
+//! trait Mailer: Send + Sync {
+//!     fn send(&self, to: &str);
+//! }
+//!
+//! fn welcome(user: &str) {
+//!     # struct Smtp;
+//!     # impl Mailer for Smtp { fn send(&self, _: &str) {} }
+//!     let mailer = overrides::resolve_or_else::<dyn Mailer>(|| Arc::new(Smtp));
+//!     mailer.send(user);
+//! }
+//!
+//! #[derive(Default)]
+//! struct FakeMailer(Mutex<Vec<String>>);
+//!
+//! impl Mailer for FakeMailer {
+//!     fn send(&self, to: &str) {
+//!         self.0.lock().unwrap().push(to.to_string());
+//!     }
+//! }
+//!
+//! #[tust::test]
+//! fn welcomes_new_users() {
+//!     let mailer = Arc::new(FakeMailer::default());
+//!     overrides::register::<dyn Mailer>(mailer.clone());
+//!     welcome("ada@example.com");
+//!     assert_eq!(*mailer.0.lock().unwrap(), ["ada@example.com"]);
+//! }
+//! ```
+//!
+//! A [`Container`] runs a Docker image for tests of code that talks to a
+//! real service. [`Container::builder`] takes the image, its environment,
+//! the ports to publish, and what to wait for, such as a line in its output
+//! or a port accepting connections; the test then reaches the service at
+//! [`address(port)`](Container::address). The container is removed when the
+//! value is dropped, so a session-scoped fixture starts it once and shares
+//! it with every test. See [`container`] for the details.
+//!
+//! ```ignore
+//! use tust::container::{Container, Wait};
+//!
+//! #[fixture(scope = "session")]
+//! fn redis() -> Container {
+//!     Container::builder("redis:7")
+//!         .port(6379)
+//!         .wait_for(Wait::log("Ready to accept connections"))
+//!         .start()
+//!         .expect("cannot start redis")
+//! }
+//!
+//! #[tust::test]
+//! fn caches_the_session(redis: Container) {
+//!     let url = format!("redis://{}", redis.address(6379));
+//!     // ...
+//! }
+//! ```
+//!
+//! With the `sqlx` or `diesel` feature, a `TestDb` fixture gives each test a
+//! database connection in a transaction that is rolled back when the test
+//! ends, so tests never see each other's rows. Migrations given to it run
+//! once per run, before the first test connects:
+//!
+//! ```ignore
+//! use tust::db::sqlx::TestDb;
+//!
+//! static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+//!
+//! #[fixture]
+//! fn db() -> TestDb<sqlx::Postgres> {
+//!     TestDb::new(std::env::var("DATABASE_URL").unwrap()).migrate(&MIGRATOR)
+//! }
+//!
+//! #[tust::test]
+//! async fn creates_a_user(mut db: TestDb<sqlx::Postgres>) {
+//!     let conn = db.conn().await.unwrap();
+//!     // ...
+//! }
+//! ```
+//!
+//! `tust::db::diesel::TestDb` does the same for Diesel connections. See the
+//! `db` module for the details.
+//!
+//! A crate publishing fixtures for others, such as a client of a service,
+//! implements [`Fixture`] for the fixture's type, saying how the value is set
+//! up from other fixtures, how it is torn down, and its scope, and registers
+//! it under a name with [`register_fixture!`]. [`AsyncFixture`] is the same
+//! with async setup and teardown. Tests of the crates that depend on it then
+//! take the fixture as a parameter of that name, unless they declare a
+//! fixture of the same name themselves; see [`fixtures`] for the details.
+//!
+//! ```
+//! use tust::prelude::*;
+//! use tust::{FixtureError, Fixtures, Scope};
+//!
+//! #[derive(Clone)]
+//! pub struct Config {
+//!     pub jobs: u32,
+//! }
+//!
+//! impl Fixture for Config {
+//!     const SCOPE: Scope = Scope::Session;
+//!
+//!     fn setup(_: &mut Fixtures) -> Result<Self, FixtureError> {
+//!         Ok(Config { jobs: 4 })
+//!     }
+//! }
+//!
+//! tust::register_fixture!(config: Config);
+//!
+//! #[tust::test]
+//! fn runs_four_jobs(config: Config) {
+//!     assert_eq!(config.jobs, 4);
+//! }
+//! ```
+//!
+//! # Hooks
+//!
+//! `#[before_each]` and `#[after_each]` functions run around every test in
+//! their module and its submodules; `#[before_all]` and `#[after_all]`
+//! functions run once, before the first and after the last of those tests.
+//! Declared at the crate root, they apply to the whole run. Hooks of outer
+//! modules run first on the way in and last on the way out, and after hooks
+//! run even when the test or a before hook failed; a failing hook fails the
+//! test it ran for.
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[before_all]
+//! fn start_database() {
+//!     // ...
+//! }
+//!
+//! #[after_each]
+//! fn clear_tables() {
+//!     // ...
+//! }
+//! ```
+//!
+//! With `--process-per-test`, every hook runs in each test's process, where
+//! the test sees what it set up, so `before_all` and `after_all` hooks run
+//! once for each test rather than once for the module. Under
+//! libtest, which does not say which tests it will run, `after_all` hooks run
+//! only once every test they apply to has run.
+//!
+//! # Suites
+//!
+//! [`suite!`] declares tests in nested `describe` and `it` blocks, as in Jest
+//! or RSpec. Each `describe` block becomes a module and each `it` block a
+//! test, named after their descriptions, so the test below is
+//! `parser::nested_input::reports_the_depth`, and the runner groups its
+//! output the same way. A block may hold `before_each`, `after_each`,
+//! `before_all`, and `after_all` hooks, which apply to the tests of the
+//! block and the blocks inside it, as well as fixtures, helper functions, and
+//! other items. Attributes written before `it`, such as `#[timeout(100)]` or
+//! `#[case(...)]`, apply to its test, and parameters in parentheses after the
+//! description are injected fixtures. `async it` declares an async test.
+//!
+//! ```
+//! tust::suite! {
+//!     describe "parser" {
+//!         before_each {
+//!             // ...
+//!         }
+//!
+//!         it "handles empty input" {
+//!             assert!("".is_empty());
+//!         }
+//!
+//!         describe "nested input" {
+//!             #[timeout(100)]
+//!             it "reports the depth" {
+//!                 assert_eq!("((a))".matches('(').count(), 2);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Items in a block see those of the blocks around it. Descriptions in one
+//! block must name different modules and tests, so they must differ in more
+//! than punctuation and case.
+//!
+//! [`test_suite!`] declares tests generic over one type, such as the
+//! contract every implementation of a trait must meet, to be run against
+//! each implementation. It defines a macro named after the suite; each call
+//! of that macro takes a module name and an expression building the value
+//! under test, and defines the module with one test per test of the suite,
+//! each given a value of its own. The tests take that value as their
+//! parameter of the suite's type; their other parameters are fixtures, and
+//! their attributes apply as they do on `#[tust::test]`.
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! pub trait Storage {
+//!     fn put(&mut self, key: &str, value: &str);
+//!     fn get(&self, key: &str) -> Option<String>;
+//! }
+//! # impl Storage for HashMap<String, String> {
+//! #     fn put(&mut self, key: &str, value: &str) { self.insert(key.into(), value.into()); }
+//! #     fn get(&self, key: &str) -> Option<String> { HashMap::get(self, key).cloned() }
+//! # }
+//!
+//! tust::test_suite! {
+//!     /// The contract of [`Storage`].
+//!     pub storage_contract<S: Storage> {
+//!         fn reads_what_it_stored(mut storage: S) {
+//!             storage.put("key", "value");
+//!             assert_eq!(storage.get("key").as_deref(), Some("value"));
+//!         }
+//!
+//!         fn overwrites_values(mut storage: S) {
+//!             storage.put("key", "old");
+//!             storage.put("key", "new");
+//!             assert_eq!(storage.get("key").as_deref(), Some("new"));
+//!         }
+//!     }
+//! }
+//!
+//! storage_contract!(in_memory, HashMap::<String, String>::new());
+//! # fn main() {}
+//! ```
+//!
+//! The tests above are `in_memory::reads_what_it_stored` and
+//! `in_memory::overwrites_values`. A `pub` suite is exported from its
+//! crate, as `macro_rules!` macros are with `#[macro_export]`, for
+//! implementations in other crates to instantiate; the names in its tests are
+//! resolved where it is instantiated, so they should be in scope there.
+//!
+//! A [`Scenario`] writes a test as Given/When/Then steps, each named and
+//! run on a state the steps share. The test's output lists the steps as
+//! they ran, and a step that fails is named in front of the failure's
+//! message, so the failure reads as the line of the specification that
+//! broke. See [`scenario`].
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn withdrawing_cash() {
+//!     Scenario::new("withdrawing cash")
+//!         .given("an account holding 100", |balance: &mut u32| *balance = 100)
+//!         .when("its holder withdraws 20", |balance| *balance -= 20)
+//!         .then("it holds 80", |balance| assert_eq!(*balance, 80));
+//! }
+//! ```
+//!
+//! # Documentation examples
+//!
+//! [`doctests!`] reads files at compile time and turns each fenced Rust
+//! example in them into a test, so that the examples of a README or of doc
+//! comments run with the rest of the suite, through its filters, reporters,
+//! and parallelism:
+//!
+//! ```ignore
+//! // tests/docs.rs
+//! tust::doctests!("README.md", "src/lib.rs");
+//! ```
+//!
+//! Paths are relative to the crate root. Each file becomes a module named
+//! after its path, and each example a test named after the line of its
+//! opening fence, such as `readme_md::line_12`, tagged `doctest`. In
+//! markdown files, examples are the blocks marked `rust`; in Rust files, the
+//! blocks of `///` and `//!` comments that are not marked another language,
+//! as rustdoc reads them. Lines starting with `# ` are compiled but hidden,
+//! as in rustdoc, and an example that defines `fn main`, or ends with
+//! `Ok::<(), E>(())` and uses `?`, fails if it returns an error.
+//!
+//! Fence attributes follow rustdoc's: `ignore` skips the test without
+//! building it, `should_panic` expects it to fail, and `no_run` only builds
+//! it. `compile_fail` and `test_harness` examples are left to rustdoc. The
+//! examples are built as part of the test target, so the items they use must
+//! be in scope there: through the crate's name, or items of the module that
+//! calls the macro, which every generated module imports. The test target is
+//! rebuilt when one of the files changes.
+//!
+//! # Command-line programs
+//!
+//! [`cmd`] runs a program, such as a binary of the package under test found
+//! with [`Cmd::cargo_bin`], with the arguments, environment and stdin the
+//! test gives it, and asserts on its exit code, stdout and stderr:
+//!
+//! ```ignore
+//! use tust::cmd::Cmd;
+//!
+//! #[tust::test]
+//! fn prints_the_version() {
+//!     Cmd::cargo_bin("mytool")
+//!         .arg("--version")
+//!         .run()
+//!         .success()
+//!         .stdout_matches(r"^mytool \d+\.\d+\.\d+\n$")
+//!         .stderr_is_empty();
+//! }
+//! ```
+//!
+//! A failed assertion shows the command line, how the program exited, and
+//! what it printed, which also goes to the test's captured output. Pass
+//! `stdout()` to `assert_snapshot!` to snapshot it.
+//!
+//! `spawn()` instead of `run()` starts an interactive program: the returned
+//! [`Session`](cmd::Session) sends it lines with `send_line` and waits for
+//! its replies with `expect`, failing with a transcript of the conversation
+//! when a reply does not come in time.
+//!
+//! # Mocking
+//!
+//! `#[automock]` on a trait generates `Mock` followed by the trait's name,
+//! a type implementing the trait whose methods answer calls as the test
+//! expects. `expect_<method>()` adds an expectation, configured with
+//! matchers for the arguments, the value to return, and how often it is
+//! called:
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[automock]
+//! trait Prices {
+//!     fn price(&self, item: &str) -> u32;
+//! }
+//!
+//! fn total(prices: &dyn Prices, items: &[&str]) -> u32 {
+//!     items.iter().map(|item| prices.price(item)).sum()
+//! }
+//!
+//! let mut prices = MockPrices::new();
+//! prices.expect_price().with(eq("tea")).return_const(3u32).times(2);
+//! prices.expect_price().with(eq("cake")).returning(|_| 5).once();
+//! assert_eq!(total(&prices, &["tea", "cake", "tea"]), 11);
+//! ```
+//!
+//! A call that no expectation accepts panics. Expectations are verified when
+//! the mock is dropped: one called fewer times than expected, by default at
+//! least once, fails the test like a failed `check!`, pointing at the
+//! `expect_` call. See [`mock`] for the details.
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature enabled, `init_tracing()` captures the
+//! `tracing` spans and events of the current test in memory and prints them
+//! only if the test fails. Call it from a test, or from a `before_each` hook
+//! to capture them for every test of a module:
+//!
+//! ```ignore
+//! #[before_each]
+//! fn capture_logs() {
+//!     tust::init_tracing();
+//! }
+//! ```
+//!
+//! `TUST_TRACE_LEVEL=debug` captures only events at `debug` or above;
+//! `init_tracing_at(level)` sets the level in code. Only events emitted on the
+//! test's own thread are captured.
+//!
+//! # Skipping tests and expected failures
+//!
+//! These attributes go below `#[tust::test]`:
+//!
+//! - `#[skip]` or `#[skip(reason = "...")]` never runs the test; libtest
+//!   treats it as `#[ignore]`.
+//! - `#[skip_if(cfg(windows))]` or `#[skip_if(env = "CI")]` skips the test
+//!   when the configuration predicate holds, or when the environment variable
+//!   is set and not empty, optionally with a `reason = "..."`.
+//! - `#[should_fail]` passes only if the test fails, and with
+//!   `expected = "..."` only if a failure message contains that text. Unlike
+//!   `#[should_panic]`, it also counts soft failures.
+//! - `#[should_panic]` passes only if the test panics, and with
+//!   `contains = "..."` or `regex = "..."` only if the panic's message
+//!   contains the text or matches the regular expression. It is judged by
+//!   the tust runner, so it also holds for `async fn` tests and under
+//!   `--process-per-test`; libtest's `expected = "..."` means `contains`.
+//! - `#[xfail]` or `#[xfail(reason = "...")]` marks a known-broken test: its
+//!   failure is reported as expected rather than as a pass, and if it passes
+//!   it fails the run, so that the marker is removed once the bug is fixed.
+//!
+//! ```
+//! #[tust::test]
+//! #[xfail(reason = "rounding is off by one")]
+//! fn rounds_half_to_even() {
+//!     assert_eq!((2.5f64).round(), 2.0);
+//! }
+//! ```
+//!
+//! A test that can only tell once it runs that it cannot run, such as one
+//! missing the credentials of a service, returns a [`TestResult`]:
+//! `TestResult::Skipped(reason)` skips it, reported as `skipped at run time`
+//! and counted apart from the tests its attributes skip. Tests may also
+//! return `Result<(), E>`, or `Result<TestResult, E>`, so that `?` fails
+//! them with the error; see [`IntoTestResult`].
+//!
+//! ```
+//! use tust::prelude::*;
+//!
+//! #[tust::test]
+//! fn reads_the_config() -> Result<TestResult, std::io::Error> {
+//!     let Ok(path) = std::env::var("SERVICE_CONFIG") else {
+//!         return Ok(TestResult::Skipped("SERVICE_CONFIG is not set".into()));
+//!     };
+//!     let config = std::fs::read_to_string(path)?;
+//!     assert!(!config.is_empty());
+//!     Ok(TestResult::Passed)
+//! }
+//! ```
+//!
+//! # Test dependencies
+//!
+//! `#[depends_on("...")]` below `#[tust::test]` makes a test wait for other
+//! tests, for suites whose steps build on each other, such as an end-to-end
+//! suite that migrates a schema before exercising it. The runner starts the
+//! test only once those tests have finished, and skips it, reported as
+//! `skipped: dependency failed`, unless they all passed:
+//!
+//! ```
+//! #[tust::test]
+//! fn setup_schema() {
+//!     // ...
+//! }
+//!
+//! #[tust::test]
+//! #[depends_on("setup_schema")]
+//! fn insert_rows() {
+//!     // ...
+//! }
+//! ```
+//!
+//! A name is relative to the test's module unless it contains `::`, in which
+//! case it is a path from the crate root as the runner prints it, like
+//! `db::setup_schema`, which may start with `crate::`; naming a module
+//! depends on every test in it. Running a
+//! test also runs the tests it depends on, even if they are filtered out. A
+//! dependency that names no test, or a cycle of dependencies, is an error
+//! reported before any test runs. libtest runs tests in an order of its own,
+//! so under libtest `#[depends_on]` has no effect.
+//!
+//! # Async tests
+//!
+//! `#[tust::test]` on an `async fn` runs the test on an executor: Tokio when
+//! the `tokio` feature is enabled, otherwise async-std when the `async-std`
+//! feature is enabled, otherwise a minimal built-in executor without timers
+//! or I/O. The executor can be configured per test:
+//!
+//! ```ignore
+//! #[tust::test(flavor = "multi_thread", worker_threads = 4)]
+//! async fn concurrent() {
+//!     tokio::spawn(async {}).await.unwrap();
+//! }
+//! ```
+//!
+//! `runtime = "tokio" | "async-std" | "builtin"` picks an executor
+//! explicitly; `flavor` and `worker_threads` are only honored by Tokio.
+//!
+//! # Running tests with the tust runner
+//!
+//! To let tust own argument parsing, filtering, execution, and exit codes,
+//! disable libtest for a test target and invoke [`main!`] from it:
+//!
+//! ```toml
+//! [[test]]
+//! name = "integration"
+//! harness = false
+//! ```
+//!
+//! ```ignore
+//! // tests/integration.rs
+//! #[tust::test]
+//! fn addition() {
+//!     assert_eq!(2 + 2, 4);
+//! }
+//!
+//! tust::main!();
+//! ```
+//!
+//! The runner prints the tests grouped by module, marking each passed,
+//! failed, or skipped, with a progress bar below them while it runs in a
+//! terminal, and ends with a numbered digest of the failures:
+//!
+//! ```text
+//! running 4 tests
+//! parser
+//!   ✓ empty
+//!   ✗ nested
+//!   ○ unicode (skipped, not ready)
+//!   ✓ large 1.24s
+//!
+//! failures:
+//!
+//!   1) parser::nested
+//!      assertion `depth == 2` failed
+//!        left: 1
+//!       right: 2
+//!        at src/parser.rs:120:9
+//!      rerun: cargo test -p parser --test integration -- --exact parser::nested
+//! ```
+//!
+//! Each failure comes with the command that reruns only that test, a case
+//! of a parameterized test included, with the run's seed if the test asked
+//! for randomness; the JSON, JUnit, and HTML reports record it too. See
+//! [`rerun`].
+//!
+//! `--format pretty` prints one line per test instead, like libtest, and
+//! `--format terse` (or `-q`) one character per test. `--format plain`
+//! prints pretty's lines once the run ends, sorted by test name even when the
+//! tests ran shuffled, without colors, and with every duration shown as
+//! zero, so that runs with the same results and seeds print the same
+//! output: a snapshot of it checks a suite's reports, and tools can parse
+//! it.
+//!
+//! Tests run in parallel, each on its own thread named after the test, on as
+//! many threads as there are CPUs; `--jobs N` (or `RUST_TEST_THREADS`)
+//! changes that. Results are printed in name order regardless of which test
+//! finishes first. `--shuffle` runs, and reports, the tests in a random order
+//! instead, to reveal tests that only pass after others; a failed run prints
+//! the seed that `--shuffle=SEED` replays the order with. A test marked
+//! `#[serial]` (or `#[tust::test(serial)]`) never runs alongside another
+//! test, under libtest as well:
+//!
+//! ```
+//! #[tust::test]
+//! #[serial]
+//! fn changes_the_working_directory() {
+//!     // ...
+//! }
+//! ```
+//!
+//! Tests that share an external resource, but not the whole process, name a
+//! lock instead: tests with the same `lock` never run at the same time, and
+//! other tests run alongside them. `lock("database", "cache")` takes several.
+//! The locks are also held as files in `target/tust/locks`, so that tests of
+//! other test targets, of `--process-per-test` runs, and of runs started at
+//! the same time wait for each other too; see [`locks`].
+//!
+//! ```
+//! #[tust::test(lock = "database")]
+//! fn migrates_the_schema() {
+//!     // ...
+//! }
+//! ```
+//!
+//! The runner captures what tests print, including from threads and child
+//! processes they start, and shows a test's output only if it fails;
+//! `--show-output` shows the output of passed tests too, and `--nocapture`
+//! lets tests print directly. Tests running in parallel share the process's
+//! stdout and stderr, so output printed while several tests run is attributed
+//! to each of them, with a note; `--jobs 1` or `--process-per-test` separates
+//! it exactly. Output is captured on Unix only.
+//!
+//! `--fail-fast` stops starting tests after the first failure, and
+//! `--max-failures N` after N of them. Tests already running finish, hooks
+//! and fixtures are torn down as usual, and the tests that never started are
+//! counted as not run.
+//!
+//! Ctrl-C, or `SIGTERM`, stops a run the same way on Unix, so that no
+//! container or temporary directory is left behind: no further test starts,
+//! the running tests are given 10 seconds to finish, or as long as
+//! `--grace-period` says, and the run is torn down and reported before
+//! exiting with 130. Tests still running after that fail as abandoned; a
+//! second Ctrl-C stops the run at once. A long test can check
+//! [`interrupt::requested`] to end early.
+//!
+//! With `--process-per-test`, each test runs in a fresh process re-executing
+//! the test binary, so a segfault, abort, or stack overflow fails only that
+//! test, reported with its signal or exit code.
+//!
+//! `#[limits(...)]` below `#[tust::test]` caps the memory and processor time
+//! of such a process on Unix, so that a runaway test fails with "memory limit
+//! of 512 MiB exceeded" instead of exhausting the machine. Memory is limited
+//! as address space, in `B`, `KB`, `MB`, or `GB` (binary multiples), and
+//! processor time in whole seconds or minutes. Tests run without
+//! `--process-per-test` are not limited, and the runner notes so.
+//!
+//! ```
+//! #[tust::test]
+//! #[limits(memory = "512MB", cpu_time = "10s")]
+//! fn builds_the_index() {
+//!     // ...
+//! }
+//! ```
+//!
+//! `#[timeout(ms)]` below `#[tust::test]` (or `#[tust::test(timeout = ms)]`)
+//! fails a test that runs longer than the limit; `--timeout 30s` sets a limit
+//! for every other test. A test that times out in-process keeps running in
+//! the background, since threads cannot be stopped; with
+//! `--process-per-test`, its process is killed.
+//!
+//! `#[retry(n)]` (or `#[tust::test(retry = n)]`) re-runs a failing test up to
+//! `n` more times, and `--retries n` does so for every other test. A test
+//! that passes on a later attempt is reported as flaky rather than as a clean
+//! pass, and the runner lists flaky tests at the end of the run. Under
+//! libtest, only `#[retry]` applies.
+//!
+//! ```
+//! #[tust::test]
+//! #[retry(2)]
+//! fn talks_to_a_slow_service() {
+//!     // ...
+//! }
+//! ```
+//!
+//! Tests too flaky to block a build can be quarantined in `tust.toml`. They
+//! still run, but their failures are reported apart, as quarantined, and do
+//! not fail the run; a quarantined test that passes ten runs in a row (or
+//! `stable-after` runs) is listed as ready to leave. See [`quarantine`].
+//!
+//! ```toml
+//! [quarantine]
+//! tests = ["replica::catches_up", "net::**"]
+//! ```
+//!
+//! `--strict` (or `strict = true` in `tust.toml`) flags tests that pass but
+//! leave threads, Tokio tasks, or files in the temporary directory behind,
+//! or that take longer than a threshold. Flagged tests are listed after the
+//! run; the `[strict-checks]` table of `tust.toml` makes each check fail
+//! the tests it flags instead, or turns it off. Built with
+//! `RUSTFLAGS="--cfg tokio_unstable"`, the Tokio tasks left are shown with
+//! the backtraces of where they were spawned. See [`strict`].
+//!
+//! `#[tust::test(repeat = 1000, threads = 8)]` runs a test's body 1000 times,
+//! from 8 threads at once, to shake out race conditions; `threads` alone runs
+//! it once per thread. Each iteration gets fresh fixtures and, in
+//! [`stress::iteration`], its index. The first failing iteration stops the
+//! others, and the test fails with its index, its thread, and the run seed,
+//! which replays the random values of every iteration.
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! static HITS: AtomicUsize = AtomicUsize::new(0);
+//!
+//! #[tust::test(repeat = 1000, threads = 8)]
+//! fn counts_concurrent_hits() {
+//!     HITS.fetch_add(1, Ordering::SeqCst);
+//! }
+//! ```
+//!
+//! `#[tust::test(model_check)]` runs a test's body under loom with the `loom`
+//! feature, which explores every interleaving of the threads it spawns, or
+//! under shuttle's random scheduler with the `shuttle` feature; without
+//! either, the body runs once as usual. Only threads, atomics, and locks
+//! taken from the checker's crate are explored. See [`model`].
+//!
+//! `#[tust::test(tags("slow", "db"))]` tags a test, and `--tag` and
+//! `--skip-tag` select tests by tag expressions combining tags with `!`,
+//! `&&`, `||`, and parentheses: `--tag "db && !slow"` runs the database tests
+//! that are not slow.
+//!
+//! Besides libtest-style substring filters and `--exact`, `--filter` selects
+//! tests by a glob over their full name, in which `*` matches within one path
+//! segment and `**` across segments, so `--filter 'db::*::insert_*'` runs the
+//! insert tests of every module of `db`; `--filter-regex` takes a regular
+//! expression. See [`filter`].
+//!
+//! `--list` prints the tests a command line selects, without running them,
+//! each with its location, tags, and attributes such as `serial` or
+//! `timeout 30s`. `--list --format json` prints the same inventory as one
+//! JSON object per test, for IDE plugins and test selection tools, and
+//! `--list --format terse` only the names, as libtest does.
+//!
+//! `--dry-run` lists the selected tests the same way, each case of a
+//! parameterized test on its own line, and then checks what would fail
+//! before any test body runs: fixtures that are not registered, are
+//! ambiguous, have another type than the parameter, or depend on
+//! themselves. It also warns about filters, `--skip` patterns, `--file`
+//! paths, and tags of `--tag` and `--skip-tag` that match no test, such as a
+//! misspelled tag that would quietly select nothing. The dry run fails if a
+//! fixture would not resolve, so CI can run it as a quick first step.
+//!
+//! The runner accepts libtest-style filters and options; run the binary with
+//! `--help` for the full list. `--report junit=results.xml` additionally
+//! writes a JUnit XML report for CI systems, and `--format json` replaces the
+//! console output with newline-delimited JSON events in the style of
+//! libtest's, for IDEs and other tools. `--format tap` prints TAP version 14.
+//! `--report html=target/tust-report` writes a browsable HTML report to a
+//! directory; reports written to the same directory across runs show which
+//! tests started or stopped failing and how their durations changed.
+//!
+//! On GitHub Actions, the runner also prints each failure as an `::error`
+//! workflow command, so that it shows on the lines of the pull request's
+//! diff, and appends a Markdown table of the results to the job summary in
+//! `GITHUB_STEP_SUMMARY`. `--report github=PATH` asks for the same elsewhere,
+//! with the summary appended to `PATH`; with `--format json`, `tap`, or
+//! `plain`, whose output is meant for programs, it is not added unless asked
+//! for.
+//!
+//! Reporters of your own, such as one posting failures to a chat channel,
+//! implement [`Reporter`] and are registered with [`register_reporter!`]; the
+//! runner then sends them every event of the run alongside its own output.
+//! See [`report`].
+//!
+//! ```
+//! use tust::{Reporter, RunSummary, TestOutcome};
+//!
+//! struct Notifier;
+//!
+//! impl Reporter for Notifier {
+//!     fn on_test_finish(&mut self, _outcome: &TestOutcome) {}
+//!
+//!     fn on_run_end(&mut self, _outcomes: &[TestOutcome], summary: &RunSummary) {
+//!         if !summary.is_success() {
+//!             eprintln!("notify: {} tests failed", summary.failed);
+//!         }
+//!     }
+//! }
+//!
+//! tust::register_reporter!(|| Notifier);
+//! ```
+//!
+//! `--shard K/N` runs only the K-th of N disjoint parts of the selected
+//! tests, so CI can split one test binary across N machines. Tests are
+//! assigned to shards by a stable hash of their name, or, with
+//! `--shard-timings FILE`, balanced by the durations that `--report
+//! timings=FILE` recorded in an earlier run.
+//!
+//! The runner records every test's duration in `target/tust/timings.json`
+//! (or the file named by `TUST_TIMINGS`), and starts the slowest tests first
+//! when running tests in parallel. That file can also be passed to
+//! `--shard-timings`. After a run, the ten slowest tests are listed;
+//! `--slowest N` changes how many.
+//!
+//! `#[priority(high)]` below `#[tust::test]` starts a test before the
+//! others, such as a quick test that catches most regressions, so that its
+//! failure is reported early; `#[priority(low)]` starts one after them.
+//! Within a priority, the slowest tests start first, and tests always start
+//! after the tests they depend on. `--shuffle` ignores priorities.
+//! `--schedule` prints the selected tests in the order they would start in,
+//! with their priorities and recorded durations, instead of running them.
+//!
+//! ```
+//! #[tust::test]
+//! #[priority(high)]
+//! fn checkout_charges_the_card() {
+//!     // ...
+//! }
+//! ```
+//!
+//! Each run is also appended to `target/tust/history.jsonl` (or the file
+//! named by `TUST_HISTORY`), with the result and duration of every test.
+//! `--trends` compares the tests of the run against their last 20 recorded
+//! runs, or `--trends=N`, and lists those that started failing, those that
+//! took at least twice their median duration, and those that keep changing
+//! between passing and failing or were flaky; see [`history`].
+//!
+//! Each failure in the summary shows the panic message and location, the
+//! backtrace when `RUST_BACKTRACE` is set, without the frames of the
+//! standard library and of tust, and the last 20 lines the test printed;
+//! `--output-lines N` changes how many, and `RUST_BACKTRACE=full` keeps
+//! every frame.
+//!
+//! The tests that failed the last time they ran are recorded in
+//! `target/tust/last-failed.json` (or the file named by `TUST_LAST_FAILED`).
+//! As with pytest, `--last-failed` (`--lf`) runs only those tests, or every
+//! test if none failed, and `--failed-first` (`--ff`) runs them before the
+//! others.
+//!
+//! `--cached` skips the tests that passed in an earlier `--cached` run with
+//! the same inputs: the same options changing how tests run, and either the
+//! same contents of the source files a test executed, for tests whose
+//! coverage `--record-coverage` recorded, or else the same test binary.
+//! Passes are recorded in `target/tust/passed.json` (or the file named by
+//! `TUST_PASSED`). With coverage recorded, a change reruns only the tests
+//! that executed the changed files, which keeps iterating on a large suite
+//! quick; files and services a test reads as it runs are not among its
+//! inputs, so CI runs should not use it.
+//!
+//! `--file PATH` runs only the tests defined in a source file. The
+//! `cargo-tust` package builds on it and `--failed-first`: `cargo tust watch`
+//! rebuilds the tests whenever a source file changes and reruns those
+//! defined in the changed files, failed ones first.
+//!
+//! `cargo tust compare BASELINE CURRENT` compares two runs recorded with
+//! `--format json`, such as one of the main branch and one of a pull
+//! request. It lists the tests that started failing, started passing, were
+//! added, or were removed, and the tests whose duration changed by more than
+//! `--threshold PERCENT`, and exits with 1 if a test started failing:
+//!
+//! ```text
+//! cargo test -- --format json > baseline.json
+//! cargo test -- --format json | cargo tust compare baseline.json -
+//! ```
+//!
+//! `cargo tust workspace` builds the test binaries of every package of the
+//! workspace and reports their tests as one run, grouped by package, with a
+//! summary of each package after the tests; `-p`, `--exclude`, and
+//! `--features` select what Cargo builds, and the arguments after `--` go to
+//! every test binary. See [`workspace`].
+//!
+//! With LLVM's source-based coverage, the runner can also select the tests
+//! a change affects. `--record-coverage` runs each test in its own process
+//! and records the source files it executed in `target/tust/coverage.json`;
+//! `--changed-since REF` then runs only the tests that executed a file
+//! changed since the Git revision `REF`, and those never recorded:
+//!
+//! ```text
+//! RUSTFLAGS="-C instrument-coverage" cargo test --test integration -- --record-coverage
+//! cargo test --test integration -- --changed-since origin/main
+//! ```
+//!
+//! Recording needs the `llvm-tools-preview` component; see [`coverage`].
+//!
+//! Mutation testing tools use the same coverage through `--mutation-hook`,
+//! with which the binary answers requests read from stdin, one JSON object
+//! per line, instead of running tests: which files each test covers, and
+//! runs of only the tests covering a file, each test in its own process,
+//! stopping at the first failure. See [`mutation`] for the requests and
+//! their answers.
+//!
+//! ```text
+//! $ echo '{ "command": "run", "files": ["src/lexer.rs"] }' | target/debug/deps/integration-… --mutation-hook
+//! { "type": "run", "ok": false, "passed": 3, "failed": 1, "skipped": 0, "not_run": 12, ... }
+//! ```
+//!
+//! Defaults for these options can be kept in a `tust.toml` file next to the
+//! crate's `Cargo.toml` or at the workspace root, with `[profile.NAME]`
+//! sections selected by `--profile NAME` or `TUST_PROFILE`. Environment
+//! variables such as `TUST_TIMEOUT` override the file, and command-line
+//! options override both. See [`config`] for the keys.
+//!
+//! ```toml
+//! jobs = 8
+//! timeout = "30s"
+//! skip-tags = ["slow"]
+//! snapshot-dir = "tests/__snapshots__"
+//!
+//! [profile.ci]
+//! retries = 2
+//! reports = ["junit=target/tust/junit.xml"]
+//! ```
+//!
+//! # Running tests on WebAssembly
+//!
+//! The same test binaries build for `wasm32-wasip1` and
+//! `wasm32-unknown-unknown`. For WASI, a runner such as wasmtime runs them
+//! with `cargo test`, given access to the files the tests use and to `/tmp`
+//! (or `TMPDIR`) for temporary directories:
+//!
+//! ```toml
+//! # .cargo/config.toml
+//! [target.wasm32-wasip1]
+//! runner = "wasmtime run --dir=. --dir=/tmp"
+//! ```
+//!
+//! For `wasm32-unknown-unknown`, `wasm-bindgen` turns a test binary into a
+//! module that runs its tests as soon as it is loaded, by Node.js or by a
+//! page in a browser. The report goes to the JavaScript console, and a
+//! failed run ends by throwing an exception:
+//!
+//! ```text
+//! cargo build --tests --target wasm32-unknown-unknown
+//! wasm-bindgen --target nodejs --out-dir target/wasm-tests \
+//!     target/wasm32-unknown-unknown/debug/deps/integration-*.wasm
+//! node target/wasm-tests/integration-*.js
+//! ```
+//!
+//! WebAssembly has no threads or processes, so tests run one at a time,
+//! timeouts are not enforced, output is not captured, and
+//! `--process-per-test` is not available. Its panics abort rather than
+//! unwind: the first test that panics, be it from a failed assertion or a
+//! `#[should_fail]` test failing as expected, is reported along with the
+//! tests before it and ends the run. Soft assertions such as [`check!`]
+//! record their failures without panicking, so a run continues past them.
+//! Output that tests print is lost on `wasm32-unknown-unknown`, which has no
+//! standard output.
 
-// Re-export macros from tust-macros
-// pub use tust_macros::*;
+/// Registers a function as a tust test.
+pub use tust_macros::test;
+
+/// Registers a function as a property-based test.
+pub use tust_macros::property;
+
+/// Registers a function as a fuzz target; see
+/// [Fuzz targets](crate#fuzz-targets).
+pub use tust_macros::fuzz;
+
+/// Registers a function as a benchmark; see [Benchmarks](crate#benchmarks).
+pub use tust_macros::bench;
+
+/// Registers a function as a fixture, injected into parameters of the same
+/// name.
+pub use tust_macros::fixture;
+
+/// Declares setup and teardown hooks; see [Hooks](crate#hooks).
+pub use tust_macros::{after_all, after_each, before_all, before_each};
+
+/// Generates a mock implementation of a trait; see [Mocking](crate#mocking).
+pub use tust_macros::automock;
+
+/// Generates a builder giving the fields a test does not set seeded random
+/// values; see [Randomness](crate#randomness).
+pub use tust_macros::TestBuilder;
+
+/// Declares tests in nested `describe` and `it` blocks; see
+/// [Suites](crate#suites).
+pub use tust_macros::suite;
+
+/// Declares tests generic over one type, to instantiate for each
+/// implementation of a trait; see [Suites](crate#suites).
+pub use tust_macros::test_suite;
+
+/// Runs the examples of markdown files and doc comments as tests; see
+/// [Documentation examples](crate#documentation-examples).
+pub use tust_macros::doctests;
 
 // Re-export runtime utilities
-// pub use tust_runtime::*;
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+pub use tust_runtime::db;
+pub use tust_runtime::{
+    alloc, bench, block_on, clock, cmd, config, container, context, coverage, data, env, executor,
+    filter, fixture as fixtures, fuzz, golden, history, hooks, http, interrupt, locks, model,
+    mutation, overrides, property, quarantine, register_reporter, registry, report, rerun, rng,
+    run, run_with_args, sandbox, scenario, shared, snapshot, socket, soft, stress, strict, tags,
+    temp, tests, workspace, Args, AsyncConfig, AsyncFixture, AsyncRuntime, Attachment, Clock, Cmd,
+    Config, Container, Env, FailureDiff, FailureKind, Fixture, FixtureError, Fixtures, Flavor,
+    FsSandbox, IntoTestResult, Location, MappedFile, Reporter, Rng, RunSummary, Scenario, Scope,
+    SharedData, Step, TempDir, TempFile, TestContext, TestDef, TestFailure, TestOutcome,
+    TestResult, TestStatus,
+};
+#[cfg(feature = "tracing")]
+pub use tust_runtime::{init_tracing, init_tracing_at, tracing};
 
 // Re-export assertions
-// pub use tust_assertions::*;
+pub use tust_assertions::*;
+
+/// Generates a `main` function that runs every registered test with the tust
+/// runner.
+///
+/// Use it from test targets declared with `harness = false`.
+#[macro_export]
+macro_rules! main {
+    () => {
+        fn main() -> ::std::process::ExitCode {
+            // Names the target in the commands that rerun failed tests.
+            $crate::__private::set_built($crate::__private::Built {
+                package: ::std::option_env!("CARGO_PKG_NAME"),
+                crate_name: ::std::option_env!("CARGO_CRATE_NAME"),
+                bin_name: ::std::option_env!("CARGO_BIN_NAME"),
+                target_tmpdir: ::std::option_env!("CARGO_TARGET_TMPDIR").is_some(),
+                file: ::std::file!(),
+            });
+            $crate::run()
+        }
+    };
+}
+
+/// Registers a type implementing [`Fixture`], or [`AsyncFixture`] with
+/// `async`, as the fixture of the given name; see [Fixtures](crate#fixtures).
+///
+/// ```ignore
+/// tust::register_fixture!(redis: Redis);
+/// tust::register_fixture!(async kafka: Kafka);
+/// ```
+#[macro_export]
+macro_rules! register_fixture {
+    ($name:ident: $ty:ty) => {
+        $crate::register_fixture!(@def $name, $ty, $crate::__private::fixture_provider::<$ty>());
+    };
+    (async $name:ident: $ty:ty) => {
+        $crate::register_fixture!(
+            @def $name,
+            $ty,
+            $crate::__private::async_fixture_provider::<$ty>()
+        );
+    };
+    (@def $name:ident, $ty:ty, $provider:expr) => {
+        $crate::__private::inventory::submit! {
+            $crate::__private::FixtureDef {
+                name: ::core::stringify!($name),
+                module_path: ::core::module_path!(),
+                file: ::core::file!(),
+                line: ::core::line!(),
+                dependencies: &[],
+                type_name: ::core::any::type_name::<$ty>,
+                type_id: ::core::any::TypeId::of::<$ty>,
+                provider: $provider,
+            }
+        }
+    };
+}
 
 /// Convenience prelude module
 pub mod prelude {
-    // TODO: Re-export commonly used items
-    // pub use crate::*;
+    pub use crate::http::{MockHttpServer, Response};
+    pub use crate::matchers::*;
+    pub use crate::property::{Arbitrary, Gen};
+    pub use crate::socket::{Framing, MockSocketServer};
+    pub use crate::{
+        after_all, after_each, automock, before_all, before_each, doctests, fixture, suite,
+        TestBuilder,
+    };
+    pub use crate::{
+        assert_all, assert_allocates_at_most, assert_any, assert_approx_eq, assert_binary_snapshot,
+        assert_completes_within, assert_contains, assert_contains_all, assert_debug_snapshot,
+        assert_ends_with, assert_err, assert_image_snapshot, assert_json_eq, assert_json_includes,
+        assert_map_eq, assert_matches, assert_matches_file, assert_matches_regex, assert_no_alloc,
+        assert_none, assert_ok, assert_panics, assert_pending, assert_ready, assert_same_elements,
+        assert_snapshot, assert_some, assert_sorted, assert_sorted_by, assert_starts_with,
+        assert_str_eq, assert_stream_yields, assert_that, check, check_eq, check_ne, check_that,
+        expect, expect_messages, expect_that,
+    };
+    pub use crate::{
+        AsyncFixture, Clock, Container, Env, Fixture, FsSandbox, MappedFile, Scenario, SharedData,
+        TempDir, TempFile, TestContext, TestResult,
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use tust_runtime::__private::*;
+
+    pub use tust_assertions::mock;
 }
@@ -0,0 +1,1246 @@
+//! Runs the tests of this binary through the tust runner, and checks what
+//! the runner prints and the status it exits with.
+//!
+//! The binary is both the runner and its driver. Run by `cargo test`, it
+//! runs itself again for each check below, with [`CHILD_ENV`] set and the
+//! arguments of the check; run that way, it is a tust test binary whose
+//! tests, in the modules below, pass and fail on purpose.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+/// Set in the runs of the binary that run its tests.
+const CHILD_ENV: &str = "TUST_RUNNER_TEST_CHILD";
+
+mod math {
+    pub fn add(a: u32, b: u32) -> u32 {
+        a + b
+    }
+
+    #[tust::test]
+    fn adds() {
+        assert_eq!(add(1, 1), 2);
+    }
+
+    #[tust::test]
+    fn adds_zero() {
+        println!("printed by a passing test");
+        assert_eq!(add(1, 0), 1);
+    }
+}
+
+mod broken {
+    #[tust::test]
+    fn fails() {
+        println!("printed before failing");
+        assert_eq!(super::math::add(1, 1), 3);
+    }
+}
+
+mod parallel {
+    /// Starts the test `mine` and waits for the test `theirs` to start, which
+    /// it only does in time if the two run at once.
+    fn meet(mine: &str, theirs: &str) {
+        std::fs::write(super::check_dir().join(mine), "").unwrap();
+        let start = std::time::Instant::now();
+        while !super::check_dir().join(theirs).exists() {
+            assert!(start.elapsed().as_secs() < 3, "{} did not start", theirs);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[tust::test]
+    fn meets_second() {
+        meet("first", "second");
+    }
+
+    #[tust::test]
+    fn meets_first() {
+        meet("second", "first");
+    }
+}
+
+mod serial {
+    /// Holds the marker of a running serial test for a while, failing if
+    /// another test holds it.
+    fn run_alone() {
+        let marker = super::check_dir().join("serial");
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&marker)
+            .expect("ran alongside another serial test");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        std::fs::remove_file(marker).unwrap();
+    }
+
+    #[tust::test]
+    #[serial]
+    fn first() {
+        run_alone();
+    }
+
+    #[tust::test]
+    #[serial]
+    fn second() {
+        run_alone();
+    }
+}
+
+mod crashing {
+    #[tust::test]
+    fn aborts() {
+        println!("printed before aborting");
+        std::process::abort();
+    }
+
+    #[tust::test]
+    fn survives() {}
+}
+
+mod slow {
+    #[tust::test]
+    #[timeout(100)]
+    fn outlives_its_timeout() {
+        std::thread::sleep(std::time::Duration::from_secs(5));
+    }
+
+    #[tust::test]
+    fn takes_a_while() {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+    }
+}
+
+mod flaky {
+    /// Fails the first time the test `name` runs in a check.
+    fn fail_once(name: &str) {
+        let marker = super::check_dir().join(name);
+        if !marker.exists() {
+            std::fs::write(marker, "").unwrap();
+            panic!("failed the first attempt");
+        }
+    }
+
+    #[tust::test]
+    #[retry(1)]
+    fn retries_itself() {
+        fail_once("retries_itself");
+    }
+
+    #[tust::test]
+    fn needs_retries() {
+        fail_once("needs_retries");
+    }
+}
+
+mod fixtures {
+    #[tust::fixture]
+    fn port() -> u16 {
+        8080
+    }
+
+    #[tust::test(tags("net"))]
+    fn takes_a_fixture(port: u16) {
+        assert_eq!(port, 8080);
+    }
+
+    #[tust::test]
+    fn takes_a_missing_fixture(host: String) {
+        assert!(!host.is_empty());
+    }
+}
+
+/// A directory emptied before each check, holding the files its runs
+/// share: the caches of the runner, and markers through which the tests of
+/// a run see each other run.
+fn check_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("runner-check")
+}
+
+mod session {
+    /// Appends a line to the `session-builds` marker each time it is built.
+    #[tust::fixture(scope = "session")]
+    fn counted() -> u32 {
+        use std::io::Write;
+
+        let mut builds = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(super::check_dir().join("session-builds"))
+            .unwrap();
+        writeln!(builds, "built").unwrap();
+        1
+    }
+
+    #[tust::test]
+    fn first(counted: u32) {
+        assert_eq!(counted, 1);
+    }
+
+    #[tust::test]
+    fn second(counted: u32) {
+        assert_eq!(counted, 1);
+    }
+
+    #[tust::test]
+    fn third(counted: u32) {
+        assert_eq!(counted, 1);
+    }
+}
+
+mod hooks {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static SET_UP: AtomicUsize = AtomicUsize::new(0);
+
+    /// Appends `line` to the `hooks` marker.
+    fn mark(line: &str) {
+        use std::io::Write;
+
+        let mut marker = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(super::check_dir().join("hooks"))
+            .unwrap();
+        writeln!(marker, "{}", line).unwrap();
+    }
+
+    #[tust::before_all]
+    fn set_up() {
+        SET_UP.fetch_add(1, Ordering::SeqCst);
+        mark("set up");
+    }
+
+    #[tust::after_all]
+    fn tear_down() {
+        mark("torn down");
+    }
+
+    #[tust::test]
+    fn sees_the_set_up() {
+        assert_eq!(SET_UP.load(Ordering::SeqCst), 1);
+    }
+
+    #[tust::test]
+    fn also_sees_the_set_up() {
+        assert_eq!(SET_UP.load(Ordering::SeqCst), 1);
+    }
+}
+
+mod shared {
+    use tust::SharedData;
+
+    /// Appends a line to the `shared-loads` marker each time it is loaded.
+    #[tust::fixture(scope = "session")]
+    fn words() -> SharedData<Vec<String>> {
+        SharedData::load("words", || {
+            use std::io::Write;
+
+            let mut loads = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(super::check_dir().join("shared-loads"))
+                .unwrap();
+            writeln!(loads, "loaded").unwrap();
+            vec!["alpha".to_string(), "beta".to_string()]
+        })
+    }
+
+    #[tust::test]
+    fn reads_the_words(words: SharedData<Vec<String>>) {
+        assert_eq!(words.len(), 2);
+    }
+
+    #[tust::test]
+    fn reads_the_words_too(words: SharedData<Vec<String>>) {
+        assert_eq!(words[0], "alpha");
+    }
+
+    #[tust::test]
+    fn reads_the_words_again(words: SharedData<Vec<String>>) {
+        assert_eq!(words[1], "beta");
+    }
+}
+
+mod reported {
+    use tust::{Reporter, RunSummary, TestDef, TestOutcome};
+
+    /// Appends `line` to the `reported` marker.
+    fn mark(line: String) {
+        use std::io::Write;
+
+        let mut marker = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(super::check_dir().join("reported"))
+            .unwrap();
+        writeln!(marker, "{}", line).unwrap();
+    }
+
+    /// Marks when the tests of this module start and finish.
+    struct Recorder;
+
+    impl Reporter for Recorder {
+        fn on_test_start(&mut self, test: &'static TestDef) {
+            if test.module_path.ends_with("::reported") {
+                mark(format!("start {}", test.name));
+            }
+        }
+
+        fn on_test_finish(&mut self, outcome: &TestOutcome) {
+            if outcome.test.module_path.ends_with("::reported") {
+                mark(format!("finish {}", outcome.test.name));
+            }
+        }
+
+        fn on_run_end(&mut self, _outcomes: &[TestOutcome], _summary: &RunSummary) {}
+    }
+
+    tust::register_reporter!(|| Recorder);
+
+    #[tust::test]
+    fn one() {
+        mark("body one".to_string());
+    }
+
+    #[tust::test]
+    fn two() {
+        mark("body two".to_string());
+    }
+}
+
+/// How many times the `session::counted` fixture was built in the check.
+fn session_builds() -> usize {
+    std::fs::read_to_string(check_dir().join("session-builds"))
+        .map(|builds| builds.lines().count())
+        .unwrap_or(0)
+}
+
+/// The number of tests a run selecting `selected` of the tests of this
+/// binary filters out.
+fn filtered_out(selected: usize) -> usize {
+    let run = Run::new(&["--list", "--format=terse"]);
+    run.stdout
+        .lines()
+        .filter(|line| line.ends_with(": test"))
+        .count()
+        - selected
+}
+
+/// A finished run of the tests of this binary.
+struct Run {
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Run {
+    /// Runs the tests with `args`, without colors or backtraces, and
+    /// without the options the environment of `cargo test` could set. The
+    /// runner keeps its caches in the [`check_dir`].
+    fn new(args: &[&str]) -> Run {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        for (env, name) in [
+            ("TUST_HISTORY", "history.jsonl"),
+            ("TUST_LAST_FAILED", "last-failed.json"),
+            ("TUST_PASSED", "passed.json"),
+            ("TUST_QUARANTINE", "quarantine.json"),
+            ("TUST_TIMINGS", "timings.json"),
+        ] {
+            command.env(env, check_dir().join(name));
+        }
+        let output = command
+            .env(CHILD_ENV, "1")
+            .env("RUST_BACKTRACE", "0")
+            .env_remove("RUST_TEST_THREADS")
+            .env_remove("GITHUB_ACTIONS")
+            .env_remove("TUST_CONFIG")
+            .env_remove("TUST_PROFILE")
+            .env_remove("TUST_RETRIES")
+            .env_remove("TUST_SEED")
+            .env_remove("TUST_TIMEOUT")
+            .arg("--color=never")
+            .args(args)
+            .output()
+            .unwrap();
+        Run {
+            code: output.status.code(),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+        }
+    }
+
+    /// Runs the tests with `args` and `--format plain`, whose output is the
+    /// same from run to run, without the slowest tests.
+    fn plain(args: &[&str]) -> Run {
+        Run::new(&[&["--format=plain", "--slowest=0"], args].concat())
+    }
+
+    fn assert_code(&self, code: i32) -> &Run {
+        assert_eq!(self.code, Some(code), "{}", self);
+        self
+    }
+
+    fn assert_stdout(&self, expected: &str) -> &Run {
+        assert_eq!(self.stdout, expected, "{}", self);
+        self
+    }
+
+    fn assert_stdout_contains(&self, expected: &str) -> &Run {
+        assert!(
+            self.stdout.contains(expected),
+            "expected {:?} in {}",
+            expected,
+            self
+        );
+        self
+    }
+
+    fn assert_stdout_excludes(&self, unexpected: &str) -> &Run {
+        assert!(
+            !self.stdout.contains(unexpected),
+            "unexpected {:?} in {}",
+            unexpected,
+            self
+        );
+        self
+    }
+
+    fn assert_stderr_contains(&self, expected: &str) -> &Run {
+        assert!(
+            self.stderr.contains(expected),
+            "expected {:?} in {}",
+            expected,
+            self
+        );
+        self
+    }
+}
+
+impl std::fmt::Display for Run {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the run exiting with {:?}\n--- stdout\n{}--- stderr\n{}",
+            self.code, self.stdout, self.stderr
+        )
+    }
+}
+
+fn passing_runs_exit_successfully() {
+    Run::plain(&["math"]).assert_code(0).assert_stdout(&format!(
+        "
+running 2 tests
+test math::adds ... ok
+test math::adds_zero ... ok
+
+test result: ok. 2 passed; 0 failed; 0 skipped; {} filtered out; finished in 0.00s
+
+",
+        filtered_out(2)
+    ));
+}
+
+fn failures_are_reported_with_their_output() {
+    Run::plain(&["math", "broken"])
+        .assert_code(1)
+        .assert_stdout_contains(
+            "
+running 3 tests
+test broken::fails ... FAILED
+test math::adds ... ok
+test math::adds_zero ... ok
+
+failures:
+
+---- broken::fails ----
+printed before failing
+
+assertion `left == right` failed
+  left: 2
+ right: 3
+  at tust/tests/runner.rs:36:9
+",
+        )
+        .assert_stdout_contains("--exact broken::fails\n")
+        .assert_stdout_contains(&format!(
+            "
+failures:
+    broken::fails
+
+test result: FAILED. 2 passed; 1 failed; 0 skipped; {} filtered out; finished in 0.00s
+",
+            filtered_out(3)
+        ));
+}
+
+fn filters_select_and_skip_tests_by_name() {
+    Run::plain(&["zero", "fails", "--skip", "broken"])
+        .assert_code(0)
+        .assert_stdout_contains("running 1 test\ntest math::adds_zero ... ok\n");
+    Run::plain(&["--exact", "math"])
+        .assert_code(0)
+        .assert_stdout_contains("running 0 tests\n");
+    Run::plain(&["--exact", "math::adds_zero"])
+        .assert_code(0)
+        .assert_stdout_contains("running 1 test\ntest math::adds_zero ... ok\n");
+}
+
+fn lists_tests_instead_of_running_them() {
+    Run::new(&["--list", "math", "broken"])
+        .assert_code(0)
+        .assert_stdout(
+            "\
+broken::fails    tust/tests/runner.rs:34
+math::adds       tust/tests/runner.rs:21
+math::adds_zero  tust/tests/runner.rs:26
+
+3 tests
+",
+        );
+}
+
+fn bad_arguments_exit_with_the_usage() {
+    Run::new(&["--bogus"])
+        .assert_code(2)
+        .assert_stdout("")
+        .assert_stderr_contains("error: unrecognized option `--bogus`\n\nUsage: ");
+    Run::new(&["--jobs"])
+        .assert_code(2)
+        .assert_stderr_contains("error: `--jobs` requires a value\n");
+    Run::new(&["--help"])
+        .assert_code(0)
+        .assert_stdout_contains("Usage: <test-binary> [OPTIONS] [FILTERS...]\n");
+}
+
+fn reports_results_as_junit_xml() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("runner-junit.xml");
+    Run::plain(&[
+        "math",
+        "broken",
+        "--report",
+        &format!("junit={}", path.display()),
+    ])
+    .assert_code(1);
+    let xml = std::fs::read_to_string(&path).unwrap();
+    for expected in [
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"tust\" tests=\"3\" \
+         failures=\"1\" errors=\"0\" skipped=\"0\"",
+        "<testsuite name=\"runner\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"0\"",
+        "<testcase name=\"fails\" classname=\"runner::broken\" time=\"",
+        "file=\"tust/tests/runner.rs\" line=\"34\">",
+        "<failure message=\"assertion `left == right` failed\" type=\"assertion\">",
+        "<system-out>printed before failing</system-out>",
+        "file=\"tust/tests/runner.rs\" line=\"21\"/>",
+    ] {
+        assert!(xml.contains(expected), "expected {:?} in {}", expected, xml);
+    }
+}
+
+fn streams_json_events() {
+    let run = Run::new(&["math", "broken", "--format", "json", "--jobs=1"]);
+    run.assert_code(1);
+    let events: Vec<serde_json::Value> = run
+        .stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| {
+            format!(
+                "{} {} {}",
+                event["type"].as_str().unwrap(),
+                event["event"].as_str().unwrap(),
+                event["name"].as_str().unwrap_or("")
+            )
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "suite started ",
+            "test started broken::fails",
+            "test failed broken::fails",
+            "test started math::adds",
+            "test ok math::adds",
+            "test started math::adds_zero",
+            "test ok math::adds_zero",
+            "suite failed ",
+        ],
+        "{}",
+        run
+    );
+    assert_eq!(events[0]["test_count"], 3);
+    let failed = &events[2];
+    assert_eq!(
+        failed["stdout"].as_str().unwrap().lines().next(),
+        Some("printed before failing")
+    );
+    assert_eq!(failed["failures"][0]["kind"], "assertion");
+    assert_eq!(failed["failures"][0]["line"], 36);
+    assert!(failed["exec_time"].is_f64());
+    let suite = &events[7];
+    assert_eq!(
+        (suite["passed"].as_u64(), suite["failed"].as_u64()),
+        (Some(2), Some(1))
+    );
+
+    // Tests running at once are both started before either finishes.
+    clear_check_dir();
+    let run = Run::new(&["parallel::", "--format", "json", "--jobs=2"]);
+    run.assert_code(0);
+    let kinds: Vec<_> = run
+        .stdout
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["type"] == "test")
+        .map(|event| event["event"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(kinds, ["started", "started", "ok", "ok"], "{}", run);
+}
+
+fn prints_the_test_anything_protocol() {
+    let run = Run::new(&["math", "broken", "--format", "tap"]);
+    run.assert_code(1);
+    // Durations differ from run to run.
+    let lines: Vec<_> = run
+        .stdout
+        .lines()
+        .map(|line| match line.split_once("duration_ms: ") {
+            Some((indent, _)) => format!("{}duration_ms: _", indent),
+            None => line.split(", finished in").next().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(
+        lines.join("\n"),
+        format!(
+            "\
+TAP version 14
+1..3
+not ok 1 - broken::fails
+  ---
+  duration_ms: _
+  failures:
+    - message: \"assertion `left == right` failed\\n  left: 2\\n right: 3\"
+      at:
+        file: \"tust/tests/runner.rs\"
+        line: 36
+        column: 9
+  output: \"printed before failing\"
+  ...
+ok 2 - math::adds
+ok 3 - math::adds_zero
+# passed 2, failed 1, quarantined 0, flaky 0, skipped 0, xfailed 0, filtered out {}",
+            filtered_out(3)
+        ),
+        "{}",
+        run
+    );
+}
+
+fn parallel_runs_run_tests_at_once() {
+    let pair = ["parallel::", "--jobs", "2"];
+    Run::plain(&pair).assert_code(0);
+    clear_check_dir();
+    Run::plain(&[&pair[..], &["--nocapture"]].concat()).assert_code(0);
+    clear_check_dir();
+    Run::plain(&["parallel::", "--jobs", "1"])
+        .assert_code(1)
+        .assert_stdout_contains("test parallel::meets_first ... FAILED\n")
+        .assert_stdout_contains("test parallel::meets_second ... ok\n")
+        .assert_stdout_contains("first did not start");
+}
+
+fn serial_tests_run_alone() {
+    Run::plain(&["serial::", "--jobs", "4"])
+        .assert_code(0)
+        .assert_stdout_contains("2 passed; 0 failed;");
+    Run::plain(&["serial::", "--jobs", "4", "--nocapture"]).assert_code(0);
+}
+
+fn crashes_fail_only_their_test_in_processes() {
+    // `abort` raises `SIGABRT` on Unix, and exits with code 3 on Windows.
+    let exit = if cfg!(unix) {
+        "was terminated by signal 6 (SIGABRT)"
+    } else {
+        "exited with code 3 before reporting a result"
+    };
+    for jobs in ["1", "2"] {
+        Run::plain(&["crashing::", "--process-per-test", "--jobs", jobs])
+            .assert_code(1)
+            .assert_stdout_contains(
+                "test crashing::aborts ... FAILED\ntest crashing::survives ... ok\n",
+            )
+            .assert_stdout_contains(&format!(
+                "
+---- crashing::aborts ----
+printed before aborting
+
+the test process {}
+",
+                exit
+            ));
+    }
+}
+
+fn slow_tests_fail_past_their_timeout() {
+    Run::plain(&["slow::", "--jobs", "1"])
+        .assert_code(1)
+        .assert_stdout_contains(
+            "
+running 2 tests
+test slow::outlives_its_timeout ... FAILED
+test slow::takes_a_while ... ok
+
+failures:
+
+---- slow::outlives_its_timeout ----
+test timed out after 100ms; it is still running in the background
+",
+        );
+    Run::plain(&["slow::", "--process-per-test", "--timeout", "200ms"])
+        .assert_code(1)
+        .assert_stdout_contains("test slow::takes_a_while ... FAILED\n")
+        .assert_stdout_contains(
+            "
+---- slow::outlives_its_timeout ----
+test timed out after 100ms; its process was killed
+",
+        )
+        .assert_stdout_contains(
+            "
+---- slow::takes_a_while ----
+test timed out after 200ms; its process was killed
+",
+        );
+}
+
+fn retried_tests_that_pass_are_flaky() {
+    Run::plain(&["flaky::"])
+        .assert_code(1)
+        .assert_stdout_contains(
+            "
+test flaky::needs_retries ... FAILED
+test flaky::retries_itself ... ok (flaky, passed on attempt 2)
+",
+        )
+        .assert_stdout_contains(
+            "
+flaky tests:
+    flaky::retries_itself (passed on attempt 2)
+
+test result: FAILED. 1 passed (1 flaky); 1 failed;",
+        );
+    clear_check_dir();
+    Run::plain(&["flaky::", "--retries", "1"])
+        .assert_code(0)
+        .assert_stdout(&format!(
+            "
+running 2 tests
+test flaky::needs_retries ... ok (flaky, passed on attempt 2)
+test flaky::retries_itself ... ok (flaky, passed on attempt 2)
+
+flaky tests:
+    flaky::needs_retries (passed on attempt 2)
+    flaky::retries_itself (passed on attempt 2)
+
+test result: ok. 2 passed (2 flaky); 0 failed; 0 skipped; {} filtered out; finished in 0.00s
+
+",
+            filtered_out(2)
+        ));
+}
+
+fn shuffled_runs_print_the_seed_replaying_their_order() {
+    // The order the tests started in, one at a time, and the seed of it.
+    let run = |shuffle: &str| {
+        let run = Run::new(&[
+            "math",
+            "broken",
+            "serial::",
+            "--format=json",
+            "--jobs=1",
+            shuffle,
+        ]);
+        run.assert_code(1);
+        let events: Vec<serde_json::Value> = run
+            .stdout
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let started: Vec<_> = events
+            .iter()
+            .filter(|event| event["event"] == "started" && event["type"] == "test")
+            .map(|event| event["name"].as_str().unwrap().to_string())
+            .collect();
+        (started, events.last().unwrap()["shuffle_seed"].as_u64())
+    };
+
+    let (order, seed) = run("--shuffle");
+    let seed = seed.expect("a shuffled run reports its seed");
+    assert_eq!(run(&format!("--shuffle={}", seed)), (order, Some(seed)));
+    let (first, _) = run("--shuffle=1");
+    assert_eq!(run("--shuffle=1").0, first);
+    assert_ne!(run("--shuffle=2").0, first);
+    let mut sorted = first.clone();
+    sorted.sort();
+    assert_eq!(
+        sorted,
+        [
+            "broken::fails",
+            "math::adds",
+            "math::adds_zero",
+            "serial::first",
+            "serial::second"
+        ]
+    );
+
+    Run::plain(&["math", "broken", "--shuffle=1"])
+        .assert_code(1)
+        .assert_stdout_contains(
+            "\ntests ran in shuffled order (rerun with --shuffle=1 to replay it)\n",
+        );
+    Run::plain(&["math", "--shuffle=1"])
+        .assert_code(0)
+        .assert_stdout_contains("test math::adds ... ok\ntest math::adds_zero ... ok\n");
+}
+
+fn shards_split_the_tests_between_runs() {
+    // The names `--list --format terse` prints.
+    let list = |args: &[&str]| {
+        let run = Run::new(&[&["--list", "--format=terse"], args].concat());
+        run.assert_code(0);
+        let names: Vec<_> = run
+            .stdout
+            .lines()
+            .filter_map(|line| line.strip_suffix(": test"))
+            .map(str::to_string)
+            .collect();
+        names
+    };
+
+    let all = list(&[]);
+    let timings = check_dir().join("shard-timings.json");
+    Run::plain(&[
+        "math",
+        "--report",
+        &format!("timings={}", timings.display()),
+    ])
+    .assert_code(0);
+    let timings = timings.to_str().unwrap();
+    for by_timings in [&[][..], &["--shard-timings", timings]] {
+        let mut shards = Vec::new();
+        for index in 1..=3 {
+            let shard = list(&[&["--shard", &format!("{}/3", index)], by_timings].concat());
+            assert!(!shard.is_empty() && shard.len() < all.len(), "{:?}", shard);
+            shards.extend(shard);
+        }
+        shards.sort();
+        assert_eq!(shards, all, "the shards are not disjoint or miss tests");
+    }
+
+    Run::new(&["--shard", "4/3"])
+        .assert_code(2)
+        .assert_stderr_contains(
+            "error: invalid `--shard` value `4/3`; expected K/N with 1 <= K <= N\n",
+        );
+    Run::new(&["--shard-timings", timings])
+        .assert_code(2)
+        .assert_stderr_contains("error: `--shard-timings` requires `--shard`\n");
+    let missing = check_dir().join("missing.json");
+    Run::new(&[
+        "--shard",
+        "1/2",
+        "--shard-timings",
+        missing.to_str().unwrap(),
+    ])
+    .assert_code(2)
+    .assert_stderr_contains("error: cannot read shard timings from ");
+}
+
+fn slowest_tests_are_listed_and_started_first() {
+    let tests = ["math", "slow::takes_a_while"];
+    // The tests in the order `--schedule` starts them in.
+    let schedule = |jobs: &str| {
+        let run = Run::new(&[&tests[..], &["--schedule", "--jobs", jobs]].concat());
+        run.assert_code(0);
+        let names: Vec<_> = run
+            .stdout
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_whitespace().nth(3))
+            .map(str::to_string)
+            .collect();
+        names
+    };
+    assert_eq!(
+        schedule("4"),
+        ["math::adds", "math::adds_zero", "slow::takes_a_while"]
+    );
+
+    let run = Run::new(&[&tests[..], &["--format=pretty", "--slowest=1"]].concat());
+    run.assert_code(0);
+    let slowest: Vec<_> = run
+        .stdout
+        .split("\nslowest tests:\n")
+        .nth(1)
+        .expect("the slowest tests are listed")
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .collect();
+    assert_eq!(slowest.len(), 1, "{}", run);
+    assert!(slowest[0].ends_with("s  slow::takes_a_while"), "{}", run);
+    Run::new(&[&tests[..], &["--format=pretty", "--slowest=0"]].concat())
+        .assert_code(0)
+        .assert_stdout_contains("test result: ok.")
+        .assert_stdout_excludes("slowest tests:");
+
+    let cache = std::fs::read_to_string(check_dir().join("timings.json")).unwrap();
+    assert!(
+        cache.contains("\"runner::slow::takes_a_while\": "),
+        "{}",
+        cache
+    );
+    assert_eq!(
+        schedule("4"),
+        ["slow::takes_a_while", "math::adds", "math::adds_zero"]
+    );
+    assert_eq!(
+        schedule("1"),
+        ["math::adds", "math::adds_zero", "slow::takes_a_while"]
+    );
+}
+
+fn failures_stop_the_run_from_starting_tests() {
+    Run::plain(&["math", "broken", "--fail-fast", "--jobs=1"])
+        .assert_code(1)
+        .assert_stdout_contains("\nrunning 3 tests\ntest broken::fails ... FAILED\n\nfailures:\n")
+        .assert_stdout_contains(
+            "
+stopped after 1 failure; 2 tests were not run
+
+test result: FAILED. 0 passed; 1 failed; 0 skipped; 2 not run;",
+        );
+
+    let tests = ["math", "broken", "flaky::needs_retries", "--jobs=1"];
+    Run::plain(&[&tests[..], &["--max-failures", "2"]].concat())
+        .assert_code(1)
+        .assert_stdout_contains(
+            "
+running 4 tests
+test broken::fails ... FAILED
+test flaky::needs_retries ... FAILED
+
+failures:
+",
+        )
+        .assert_stdout_contains("0 passed; 2 failed; 0 skipped; 2 not run;");
+    clear_check_dir();
+    Run::plain(&[&tests[..], &["--max-failures", "3"]].concat())
+        .assert_code(1)
+        .assert_stdout_contains(&format!(
+            "2 passed; 2 failed; 0 skipped; {} filtered out;",
+            filtered_out(4)
+        ))
+        .assert_stdout_excludes("not run");
+}
+
+fn last_failures_run_alone_or_first() {
+    Run::plain(&["math", "broken"]).assert_code(1);
+    Run::plain(&["math", "broken", "--last-failed"])
+        .assert_code(1)
+        .assert_stdout_contains("\nrunning 1 test\ntest broken::fails ... FAILED\n")
+        .assert_stdout_contains(&format!(
+            "0 passed; 1 failed; 0 skipped; {} filtered out;",
+            filtered_out(1)
+        ));
+    Run::plain(&["math", "--lf"])
+        .assert_code(0)
+        .assert_stdout_contains("\nrunning 2 tests\n")
+        .assert_stderr_contains(
+            "note: none of the selected tests failed in its last run; running them all\n",
+        );
+
+    // `flaky::needs_retries` fails its first run, and passes the others.
+    let tests = ["crashing::survives", "flaky::needs_retries", "--jobs=1"];
+    Run::plain(&tests).assert_code(1);
+    let run = Run::new(&[&tests[..], &["--format=json", "--ff"]].concat());
+    run.assert_code(0);
+    let started: Vec<_> = run
+        .stdout
+        .lines()
+        .filter(|line| line.contains("\"type\":\"test\",\"event\":\"started\""))
+        .collect();
+    assert_eq!(
+        started,
+        [
+            "{\"type\":\"test\",\"event\":\"started\",\"name\":\"flaky::needs_retries\"}",
+            "{\"type\":\"test\",\"event\":\"started\",\"name\":\"crashing::survives\"}",
+        ],
+        "{}",
+        run
+    );
+    Run::plain(&[&tests[..], &["--last-failed"]].concat())
+        .assert_code(0)
+        .assert_stdout_contains("2 passed; 0 failed;")
+        .assert_stderr_contains("none of the selected tests failed in its last run");
+}
+
+fn dry_runs_check_what_would_fail_without_running_tests() {
+    Run::new(&["fixtures::takes_a_fixture", "--dry-run", "--tag", "net"])
+        .assert_code(0)
+        .assert_stdout(
+            "\
+fixtures::takes_a_fixture  tust/tests/runner.rs:143  [net]  fixtures port
+
+1 test
+",
+        )
+        .assert_stderr_contains("dry run: 1 test would run; no problems\n");
+    Run::new(&["fixtures::", "math", "--dry-run"])
+        .assert_code(1)
+        .assert_stdout_contains("\n4 tests\n")
+        .assert_stderr_contains(
+            "\
+error: fixtures::takes_a_missing_fixture: no fixture named `host` is registered (requested \
+             as `alloc::string::String`)
+dry run: 4 tests would run; 1 problem
+",
+        );
+    Run::new(&[
+        "math",
+        "mathh",
+        "--skip",
+        "slowly",
+        "--dry-run",
+        "--tag",
+        "nett || net",
+    ])
+    .assert_code(0)
+    .assert_stderr_contains(
+        "\
+warning: the filter `mathh` matches no test
+warning: --skip `slowly` matches no test
+warning: the tag `nett` of --tag is carried by no test
+dry run: 0 tests would run; 3 problems
+",
+    );
+    // No test ran, so none had its duration recorded.
+    assert!(!check_dir().join("timings.json").exists());
+}
+
+fn parallel_runs_build_session_fixtures_once() {
+    Run::plain(&["session::", "--jobs", "4"])
+        .assert_code(0)
+        .assert_stdout_contains("3 passed; 0 failed;");
+    assert_eq!(session_builds(), 1);
+}
+
+fn session_fixtures_are_built_once_per_process() {
+    for jobs in ["1", "2", "8"] {
+        clear_check_dir();
+        Run::plain(&["session::", "--jobs", jobs]).assert_code(0);
+        assert_eq!(session_builds(), 1, "with --jobs {}", jobs);
+    }
+    clear_check_dir();
+    Run::plain(&["session::", "--jobs", "2", "--process-per-test"]).assert_code(0);
+    assert_eq!(session_builds(), 3);
+}
+
+fn before_all_hooks_run_where_the_tests_run() {
+    let marks = || std::fs::read_to_string(check_dir().join("hooks")).unwrap();
+    Run::plain(&["hooks::", "--jobs", "4"])
+        .assert_code(0)
+        .assert_stdout_contains("2 passed; 0 failed;");
+    assert_eq!(marks(), "set up\ntorn down\n");
+    clear_check_dir();
+    Run::plain(&["hooks::", "--jobs", "4", "--process-per-test"])
+        .assert_code(0)
+        .assert_stdout_contains("2 passed; 0 failed;");
+    // Once in each test's process, which may run at the same time.
+    let mut marks: Vec<_> = marks().lines().map(str::to_string).collect();
+    marks.sort();
+    assert_eq!(marks, ["set up", "set up", "torn down", "torn down"]);
+}
+
+fn shared_data_is_loaded_once_per_process() {
+    let loads = || {
+        std::fs::read_to_string(check_dir().join("shared-loads"))
+            .unwrap()
+            .lines()
+            .count()
+    };
+    Run::new(&["shared::", "--format=pretty", "--jobs", "4"])
+        .assert_code(0)
+        .assert_stdout_contains("\nshared data:\n")
+        .assert_stdout_contains("s  words, loaded by shared::reads_the_words");
+    assert_eq!(loads(), 1);
+    clear_check_dir();
+    Run::plain(&["shared::", "--jobs", "4", "--process-per-test"]).assert_code(0);
+    assert_eq!(loads(), 3);
+}
+
+fn reporters_see_tests_start_before_they_run() {
+    for args in [
+        &["--jobs", "1"][..],
+        &["--jobs", "2"],
+        &["--process-per-test"],
+    ] {
+        clear_check_dir();
+        Run::plain(&[&["reported::"], args].concat()).assert_code(0);
+        let marks = std::fs::read_to_string(check_dir().join("reported")).unwrap();
+        let position = |mark: &str| marks.lines().position(|line| line == mark);
+        for test in ["one", "two"] {
+            let order =
+                ["start", "body", "finish"].map(|what| position(&format!("{} {}", what, test)));
+            assert!(
+                order.iter().all(Option::is_some) && order.windows(2).all(|w| w[0] < w[1]),
+                "{:?}: expected start, body and finish of {} in order in\n{}",
+                args,
+                test,
+                marks
+            );
+        }
+    }
+}
+
+/// The checks, by name.
+const CHECKS: &[(&str, fn())] = &[
+    (
+        "passing_runs_exit_successfully",
+        passing_runs_exit_successfully,
+    ),
+    (
+        "failures_are_reported_with_their_output",
+        failures_are_reported_with_their_output,
+    ),
+    (
+        "filters_select_and_skip_tests_by_name",
+        filters_select_and_skip_tests_by_name,
+    ),
+    (
+        "lists_tests_instead_of_running_them",
+        lists_tests_instead_of_running_them,
+    ),
+    (
+        "bad_arguments_exit_with_the_usage",
+        bad_arguments_exit_with_the_usage,
+    ),
+    ("reports_results_as_junit_xml", reports_results_as_junit_xml),
+    ("streams_json_events", streams_json_events),
+    (
+        "prints_the_test_anything_protocol",
+        prints_the_test_anything_protocol,
+    ),
+    (
+        "parallel_runs_run_tests_at_once",
+        parallel_runs_run_tests_at_once,
+    ),
+    ("serial_tests_run_alone", serial_tests_run_alone),
+    (
+        "crashes_fail_only_their_test_in_processes",
+        crashes_fail_only_their_test_in_processes,
+    ),
+    (
+        "slow_tests_fail_past_their_timeout",
+        slow_tests_fail_past_their_timeout,
+    ),
+    (
+        "retried_tests_that_pass_are_flaky",
+        retried_tests_that_pass_are_flaky,
+    ),
+    (
+        "shuffled_runs_print_the_seed_replaying_their_order",
+        shuffled_runs_print_the_seed_replaying_their_order,
+    ),
+    (
+        "shards_split_the_tests_between_runs",
+        shards_split_the_tests_between_runs,
+    ),
+    (
+        "slowest_tests_are_listed_and_started_first",
+        slowest_tests_are_listed_and_started_first,
+    ),
+    (
+        "failures_stop_the_run_from_starting_tests",
+        failures_stop_the_run_from_starting_tests,
+    ),
+    (
+        "last_failures_run_alone_or_first",
+        last_failures_run_alone_or_first,
+    ),
+    (
+        "dry_runs_check_what_would_fail_without_running_tests",
+        dry_runs_check_what_would_fail_without_running_tests,
+    ),
+    (
+        "parallel_runs_build_session_fixtures_once",
+        parallel_runs_build_session_fixtures_once,
+    ),
+    (
+        "session_fixtures_are_built_once_per_process",
+        session_fixtures_are_built_once_per_process,
+    ),
+    (
+        "before_all_hooks_run_where_the_tests_run",
+        before_all_hooks_run_where_the_tests_run,
+    ),
+    (
+        "shared_data_is_loaded_once_per_process",
+        shared_data_is_loaded_once_per_process,
+    ),
+    (
+        "reporters_see_tests_start_before_they_run",
+        reporters_see_tests_start_before_they_run,
+    ),
+];
+
+/// Empties the [`check_dir`].
+fn clear_check_dir() {
+    let _ = std::fs::remove_dir_all(check_dir());
+    std::fs::create_dir_all(check_dir()).unwrap();
+}
+
+/// Runs the checks whose names contain one of the arguments, or all of them.
+fn main() -> ExitCode {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        return tust::run();
+    }
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    let checks: Vec<_> = CHECKS
+        .iter()
+        .filter(|(name, _)| filters.is_empty() || filters.iter().any(|f| name.contains(f.as_str())))
+        .collect();
+    println!(
+        "\nrunning {} {}",
+        checks.len(),
+        if checks.len() == 1 { "check" } else { "checks" }
+    );
+    let mut failed = Vec::new();
+    for (name, check) in checks {
+        clear_check_dir();
+        let passed = std::panic::catch_unwind(check).is_ok();
+        println!(
+            "check {} ... {}",
+            name,
+            if passed { "ok" } else { "FAILED" }
+        );
+        if !passed {
+            failed.push(name);
+        }
+    }
+    if failed.is_empty() {
+        println!("\ncheck result: ok");
+        ExitCode::SUCCESS
+    } else {
+        println!("\nfailed checks:");
+        for name in failed {
+            println!("    {}", name);
+        }
+        ExitCode::FAILURE
+    }
+}